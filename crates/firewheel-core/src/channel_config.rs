@@ -175,6 +175,89 @@ impl From<(usize, usize)> for ChannelConfig {
     }
 }
 
+/// The position of a speaker in a [`ChannelLayout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Speaker {
+    FrontLeft,
+    FrontRight,
+    FrontCenter,
+    /// The low-frequency effects channel (subwoofer).
+    LowFrequency,
+    BackLeft,
+    BackRight,
+}
+
+/// A description of the speaker layout of a set of channels.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChannelLayout {
+    /// A single channel.
+    Mono,
+    /// Left and right channels.
+    #[default]
+    Stereo,
+    /// Front left, front right, back left, and back right channels.
+    Quad,
+    /// 5.1 surround, in the order front left, front right, front center,
+    /// low-frequency effects, back left, and back right.
+    Surround5_1,
+    /// A number of channels with no defined speaker positions.
+    Discrete(NonZeroChannelCount),
+}
+
+impl ChannelLayout {
+    /// The number of channels in this layout.
+    pub const fn num_channels(&self) -> NonZeroChannelCount {
+        match self {
+            Self::Mono => NonZeroChannelCount::MONO,
+            Self::Stereo => NonZeroChannelCount::STEREO,
+            Self::Quad => NonZeroChannelCount(NonZeroU32::new(4).unwrap()),
+            Self::Surround5_1 => NonZeroChannelCount(NonZeroU32::new(6).unwrap()),
+            Self::Discrete(count) => *count,
+        }
+    }
+
+    /// The speaker position of each channel in this layout, in channel order.
+    ///
+    /// Returns an empty slice for [`ChannelLayout::Discrete`].
+    pub const fn speakers(&self) -> &'static [Speaker] {
+        match self {
+            Self::Mono => &[Speaker::FrontCenter],
+            Self::Stereo => &[Speaker::FrontLeft, Speaker::FrontRight],
+            Self::Quad => &[
+                Speaker::FrontLeft,
+                Speaker::FrontRight,
+                Speaker::BackLeft,
+                Speaker::BackRight,
+            ],
+            Self::Surround5_1 => &[
+                Speaker::FrontLeft,
+                Speaker::FrontRight,
+                Speaker::FrontCenter,
+                Speaker::LowFrequency,
+                Speaker::BackLeft,
+                Speaker::BackRight,
+            ],
+            Self::Discrete(_) => &[],
+        }
+    }
+
+    /// Return the layout matching the given number of channels. Channel counts
+    /// without a standard layout return [`ChannelLayout::Discrete`].
+    pub const fn from_num_channels(num_channels: NonZeroChannelCount) -> Self {
+        match num_channels.0.get() {
+            1 => Self::Mono,
+            2 => Self::Stereo,
+            4 => Self::Quad,
+            6 => Self::Surround5_1,
+            _ => Self::Discrete(num_channels),
+        }
+    }
+}
+
 /// An invalid channel configuration
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ChannelConfigError {
@@ -111,6 +111,19 @@ impl Diff for EventInstant {
             }
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        if !path.is_empty() {
+            return None;
+        }
+
+        Some(match self {
+            EventInstant::Seconds(s) => (*s).into(),
+            EventInstant::Samples(s) => (*s).into(),
+            #[cfg(feature = "musical_transport")]
+            EventInstant::Musical(m) => (*m).into(),
+        })
+    }
 }
 
 #[cfg(feature = "scheduled_events")]
@@ -150,6 +163,13 @@ impl Diff for Option<EventInstant> {
             }
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        match self {
+            Some(instant) => instant.get_param(path),
+            None => path.is_empty().then_some(ParamData::None),
+        }
+    }
 }
 
 #[cfg(feature = "scheduled_events")]
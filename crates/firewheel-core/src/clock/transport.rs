@@ -1,5 +1,6 @@
 mod dynamic_transport;
 mod static_transport;
+mod tap_tempo;
 
 use bevy_platform::prelude::Vec;
 use bevy_platform::sync::Arc;
@@ -8,6 +9,7 @@ use core::{fmt::Debug, num::NonZeroU32, ops::Range};
 
 pub use dynamic_transport::{DynamicTransport, TransportKeyframe};
 pub use static_transport::StaticTransport;
+pub use tap_tempo::{TapTempo, TapTempoConfig};
 
 use crate::{
     clock::{DurationSeconds, EventInstant, InstantMusical, InstantSamples, InstantSeconds},
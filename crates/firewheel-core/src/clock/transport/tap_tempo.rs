@@ -0,0 +1,204 @@
+use bevy_platform::prelude::Vec;
+
+use crate::clock::{InstantSeconds, TransportState};
+
+/// The configuration of a [`TapTempo`] helper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TapTempoConfig {
+    /// The maximum number of recent taps that are averaged together.
+    ///
+    /// By default this is set to `8`.
+    pub max_taps: usize,
+    /// If the time between two taps is longer than this (in seconds), then
+    /// the previous taps are forgotten and a new series of taps begins.
+    ///
+    /// By default this is set to `2.0`.
+    pub timeout_seconds: f64,
+    /// The amount (as a fraction of the median interval between taps) that an
+    /// interval may deviate from the median before it is rejected as an
+    /// outlier.
+    ///
+    /// By default this is set to `0.25`.
+    pub outlier_tolerance: f64,
+    /// The minimum tempo in beats per minute. Slower tempos are clamped to
+    /// this value.
+    ///
+    /// By default this is set to `30.0`.
+    pub min_bpm: f64,
+    /// The maximum tempo in beats per minute. Faster tempos are clamped to
+    /// this value.
+    ///
+    /// By default this is set to `300.0`.
+    pub max_bpm: f64,
+}
+
+impl Default for TapTempoConfig {
+    fn default() -> Self {
+        Self {
+            max_taps: 8,
+            timeout_seconds: 2.0,
+            outlier_tolerance: 0.25,
+            min_bpm: 30.0,
+            max_bpm: 300.0,
+        }
+    }
+}
+
+/// A helper which converts a series of tap timestamps into a tempo in beats
+/// per minute, for setting the tempo of the musical transport live.
+///
+/// The tempo is the mean of the intervals between recent taps, ignoring any
+/// intervals which deviate too far from the median (i.e. a missed or a double
+/// tap).
+#[derive(Debug, Clone, PartialEq)]
+pub struct TapTempo {
+    config: TapTempoConfig,
+    taps: Vec<InstantSeconds>,
+    intervals: Vec<f64>,
+    beats_per_minute: Option<f64>,
+}
+
+impl TapTempo {
+    pub fn new(config: TapTempoConfig) -> Self {
+        let max_taps = config.max_taps.max(2);
+
+        Self {
+            config,
+            taps: Vec::with_capacity(max_taps),
+            intervals: Vec::with_capacity(max_taps - 1),
+            beats_per_minute: None,
+        }
+    }
+
+    pub fn config(&self) -> &TapTempoConfig {
+        &self.config
+    }
+
+    /// Register a tap at the given time.
+    ///
+    /// Returns the new tempo in beats per minute, or `None` if there are not
+    /// enough taps in the current series to measure one yet.
+    pub fn tap(&mut self, instant: InstantSeconds) -> Option<f64> {
+        if let Some(last) = self.taps.last() {
+            let elapsed = instant.0 - last.0;
+            if elapsed <= 0.0 || elapsed > self.config.timeout_seconds {
+                self.taps.clear();
+                self.beats_per_minute = None;
+            }
+        }
+
+        if self.taps.len() >= self.config.max_taps.max(2) {
+            self.taps.remove(0);
+        }
+        self.taps.push(instant);
+
+        if self.taps.len() < 2 {
+            return None;
+        }
+
+        self.intervals.clear();
+        self.intervals
+            .extend(self.taps.windows(2).map(|w| w[1].0 - w[0].0));
+        self.intervals.sort_unstable_by(|a, b| a.total_cmp(b));
+
+        let median = self.intervals[self.intervals.len() / 2];
+        let tolerance = median * self.config.outlier_tolerance;
+
+        let (sum, count) = self
+            .intervals
+            .iter()
+            .filter(|&&interval| (interval - median).abs() <= tolerance)
+            .fold((0.0, 0usize), |(sum, count), interval| {
+                (sum + interval, count + 1)
+            });
+
+        // The median itself is always within the tolerance.
+        let bpm = 60.0 * count as f64 / sum;
+
+        let bpm = bpm.clamp(self.config.min_bpm, self.config.max_bpm);
+        self.beats_per_minute = Some(bpm);
+        Some(bpm)
+    }
+
+    /// Register a tap at the given time, and set the transport to a static
+    /// tempo of the resulting beats per minute.
+    ///
+    /// Note, this replaces the current transport with a
+    /// [`MusicalTransport::Static`](crate::clock::MusicalTransport::Static).
+    /// The transport is left unchanged if there are not enough taps to measure
+    /// a tempo yet.
+    ///
+    /// Returns the new tempo in beats per minute.
+    pub fn tap_transport(
+        &mut self,
+        instant: InstantSeconds,
+        transport: &mut TransportState,
+    ) -> Option<f64> {
+        let bpm = self.tap(instant)?;
+        transport.set_static_transport(Some(bpm));
+        Some(bpm)
+    }
+
+    /// The most recently measured tempo in beats per minute, or `None` if no
+    /// tempo has been measured in the current series of taps.
+    pub fn beats_per_minute(&self) -> Option<f64> {
+        self.beats_per_minute
+    }
+
+    /// The number of taps in the current series.
+    pub fn num_taps(&self) -> usize {
+        self.taps.len()
+    }
+
+    /// Forget all previous taps.
+    pub fn reset(&mut self) {
+        self.taps.clear();
+        self.beats_per_minute = None;
+    }
+}
+
+impl Default for TapTempo {
+    fn default() -> Self {
+        Self::new(TapTempoConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn four_evenly_spaced_taps_produce_their_bpm() {
+        let mut tap_tempo = TapTempo::default();
+
+        // 0.5 seconds apart is 120 beats per minute.
+        assert_eq!(tap_tempo.tap(InstantSeconds(10.0)), None);
+        tap_tempo.tap(InstantSeconds(10.5));
+        tap_tempo.tap(InstantSeconds(11.0));
+        let bpm = tap_tempo.tap(InstantSeconds(11.5)).unwrap();
+        assert!((bpm - 120.0).abs() < 1e-9);
+
+        let mut transport = TransportState::default();
+        tap_tempo.tap_transport(InstantSeconds(12.0), &mut transport);
+        assert!((transport.beats_per_minute().unwrap() - 120.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn outliers_and_timeouts_are_ignored() {
+        let mut tap_tempo = TapTempo::default();
+
+        // A late tap among taps 0.6 seconds apart (100 beats per minute).
+        for t in [0.0, 0.6, 1.2, 2.0, 2.6, 3.2] {
+            tap_tempo.tap(InstantSeconds(t));
+        }
+        let bpm = tap_tempo.beats_per_minute().unwrap();
+        assert!((bpm - 100.0).abs() < 1e-9);
+
+        // A long pause starts a new series.
+        assert_eq!(tap_tempo.tap(InstantSeconds(10.0)), None);
+        assert_eq!(tap_tempo.num_taps(), 1);
+        assert_eq!(tap_tempo.beats_per_minute(), None);
+    }
+}
@@ -14,6 +14,19 @@ macro_rules! sequence_diff {
                     item.diff(&baseline[i], path.with(i as u32), event_queue);
                 }
             }
+
+            fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+                let (first, tail) = path.split_first()?;
+                self.get(*first as usize)?.get_param(tail)
+            }
+
+            fn resolve_path<'a>(
+                names: &mut impl Iterator<Item = &'a str>,
+                path: PathBuilder,
+            ) -> Option<PathBuilder> {
+                let index: u32 = names.next()?.parse().ok()?;
+                $gen::resolve_path(names, path.with(index))
+            }
         }
 
         impl<$gen: Patch> Patch for $ty {
@@ -43,6 +56,22 @@ impl<T: Diff, const LEN: usize> Diff for [T; LEN] {
             item.diff(&baseline[i], path.with(i as u32), event_queue);
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        let (first, tail) = path.split_first()?;
+        self.get(*first as usize)?.get_param(tail)
+    }
+
+    fn resolve_path<'a>(
+        names: &mut impl Iterator<Item = &'a str>,
+        path: PathBuilder,
+    ) -> Option<PathBuilder> {
+        let index: u32 = names.next()?.parse().ok()?;
+        if index as usize >= LEN {
+            return None;
+        }
+        T::resolve_path(names, path.with(index))
+    }
 }
 
 impl<T: Patch, const LEN: usize> Patch for [T; LEN] {
@@ -79,6 +108,29 @@ macro_rules! tuple_diff {
                     $gen.diff($base, path.with($index), event_queue);
                 )*
             }
+
+            fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+                let ($($gen,)*) = self;
+
+                match path {
+                    $(
+                        [$index, tail @ ..] => $gen.get_param(tail),
+                    )*
+                    _ => None,
+                }
+            }
+
+            fn resolve_path<'n>(
+                names: &mut impl Iterator<Item = &'n str>,
+                path: PathBuilder,
+            ) -> Option<PathBuilder> {
+                match names.next()?.parse::<u32>().ok()? {
+                    $(
+                        $index => $gen::resolve_path(names, path.with($index)),
+                    )*
+                    _ => None,
+                }
+            }
         }
 
         #[allow(non_snake_case, unused_variables)]
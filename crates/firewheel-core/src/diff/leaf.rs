@@ -21,6 +21,10 @@ macro_rules! primitive_diff {
                     event_queue.push_param(*self, path);
                 }
             }
+
+            fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+                path.is_empty().then(|| (*self).into())
+            }
         }
 
         impl Patch for $ty {
@@ -44,6 +48,10 @@ macro_rules! primitive_diff {
                     event_queue.push_param(*self, path);
                 }
             }
+
+            fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+                path.is_empty().then(|| (*self).into())
+            }
         }
 
         impl Patch for Option<$ty> {
@@ -70,6 +78,10 @@ macro_rules! primitive_diff {
                     event_queue.push_param(*self as $cast, path);
                 }
             }
+
+            fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+                path.is_empty().then(|| (*self as $cast).into())
+            }
         }
 
         impl Patch for $ty {
@@ -93,6 +105,10 @@ macro_rules! primitive_diff {
                     event_queue.push_param(self.map(|v| v as $cast), path);
                 }
             }
+
+            fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+                path.is_empty().then(|| self.map(|v| v as $cast).into())
+            }
         }
 
         impl Patch for Option<$ty> {
@@ -159,6 +175,10 @@ impl<A: ?Sized + Send + Sync + 'static> Diff for ArcGc<A> {
             });
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        path.is_empty().then(|| ParamData::any(self.clone()))
+    }
 }
 
 impl<A: ?Sized + Send + Sync + 'static> Patch for ArcGc<A> {
@@ -185,6 +205,10 @@ impl<T: Send + Sync + RealtimeClone + PartialEq + 'static> Diff for Option<T> {
             event_queue.push_param(ParamData::opt_any(self.clone()), path);
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        path.is_empty().then(|| ParamData::opt_any(self.clone()))
+    }
 }
 
 impl<T: Send + Sync + RealtimeClone + PartialEq + 'static> Patch for Option<T> {
@@ -202,6 +202,7 @@
 //! implement [`Patch`] manually.
 
 use bevy_platform::sync::Arc;
+use core::any::Any;
 
 #[cfg(not(feature = "std"))]
 use bevy_platform::prelude::Vec;
@@ -369,6 +370,95 @@ pub use firewheel_macros::{Diff, Patch, RealtimeClone};
 pub trait Diff {
     /// Compare `self` to `baseline` and generate events to resolve any differences.
     fn diff<E: EventQueue>(&self, baseline: &Self, path: PathBuilder, event_queue: &mut E);
+
+    /// Read the current value at the given index path, in the same form
+    /// [`Diff::diff`] would send it.
+    ///
+    /// Returns `None` if the path does not lead to a value, or if the type
+    /// does not support reading values.
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        let _ = path;
+        None
+    }
+
+    /// Resolve the remaining field names in `names` into indices, appending
+    /// them to `path`.
+    ///
+    /// Leaf types only accept an empty list of names. Prefer using
+    /// [`resolve_param_path`] over calling this directly.
+    fn resolve_path<'a>(
+        names: &mut impl Iterator<Item = &'a str>,
+        path: PathBuilder,
+    ) -> Option<PathBuilder> {
+        names.next().is_none().then_some(path)
+    }
+}
+
+/// Resolve a dot-separated field path such as `"cutoff_hz"` or
+/// `"filter.q"` into the index path generated by [`Diff`].
+///
+/// Elements of tuples, arrays, and sequences are addressed by their index
+/// (i.e. `"gains.1"`).
+///
+/// ```
+/// # use firewheel_core::diff::{resolve_param_path, Diff};
+/// #[derive(Diff)]
+/// struct FilterNode {
+///     cutoff_hz: f32,
+///     q: f32,
+/// }
+///
+/// assert_eq!(&*resolve_param_path::<FilterNode>("q").unwrap(), &[1]);
+/// assert!(resolve_param_path::<FilterNode>("gain").is_none());
+/// ```
+pub fn resolve_param_path<T: Diff>(path: &str) -> Option<ParamPath> {
+    if path.is_empty() {
+        return None;
+    }
+
+    T::resolve_path(&mut path.split('.'), PathBuilder::default()).map(PathBuilder::build)
+}
+
+/// Type-erased access to a set of parameters by path.
+///
+/// This is automatically implemented for all types which implement both
+/// [`Diff`] and [`Patch`], which allows tools to read and write the
+/// parameters of a node without knowing its concrete type.
+pub trait DynParams {
+    /// Resolve a dot-separated field path into an index path. See
+    /// [`resolve_param_path`].
+    fn param_path(&self, path: &str) -> Option<ParamPath>;
+
+    /// Read the current value at the given index path. See
+    /// [`Diff::get_param`].
+    fn read_param(&self, path: &[u32]) -> Option<ParamData>;
+
+    /// Patch the value at the given index path.
+    fn write_param(&mut self, data: &ParamData, path: &[u32]) -> Result<(), PatchError>;
+
+    /// Get the parameters as [`Any`], so they can be downcast to their
+    /// concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Diff + Patch + 'static> DynParams for T {
+    fn param_path(&self, path: &str) -> Option<ParamPath> {
+        resolve_param_path::<T>(path)
+    }
+
+    fn read_param(&self, path: &[u32]) -> Option<ParamData> {
+        self.get_param(path)
+    }
+
+    fn write_param(&mut self, data: &ParamData, path: &[u32]) -> Result<(), PatchError> {
+        let patch = T::patch(data, path)?;
+        self.apply(patch);
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
 }
 
 /// A path of indices that uniquely describes an arbitrarily nested field.
@@ -734,6 +824,33 @@ mod test {
         b: bool,
     }
 
+    #[derive(Debug, Clone, Diff, Patch, PartialEq)]
+    struct NestedDiff {
+        inner: StructDiff,
+        gains: [f32; 2],
+    }
+
+    #[test]
+    fn test_param_by_name() {
+        let params = NestedDiff {
+            inner: StructDiff { a: 0.25, b: true },
+            gains: [1.0, 0.5],
+        };
+
+        let path = resolve_param_path::<NestedDiff>("inner.a").unwrap();
+        assert_eq!(&*path, &[0, 0]);
+        assert!(matches!(params.get_param(&path), Some(ParamData::F32(v)) if v == 0.25));
+
+        let path = resolve_param_path::<NestedDiff>("gains.1").unwrap();
+        assert_eq!(&*path, &[1, 1]);
+        assert!(matches!(params.get_param(&path), Some(ParamData::F32(v)) if v == 0.5));
+
+        assert!(resolve_param_path::<NestedDiff>("inner").is_none());
+        assert!(resolve_param_path::<NestedDiff>("inner.c").is_none());
+        assert!(resolve_param_path::<NestedDiff>("gains.2").is_none());
+        assert!(resolve_param_path::<NestedDiff>("inner.a.b").is_none());
+    }
+
     #[test]
     fn test_simple_diff() {
         let mut a = StructDiff { a: 1.0, b: false };
@@ -61,6 +61,10 @@ impl Diff for CoeffUpdateFactor {
             event_queue.push_param(ParamData::U32(self.0), path);
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        path.is_empty().then_some(ParamData::U32(self.0))
+    }
 }
 
 impl Patch for CoeffUpdateFactor {
@@ -0,0 +1,188 @@
+use core::ops::Range;
+
+/// Runs computations that don't need per-sample updates (such as meters or
+/// slow envelopes) at a decimated control rate, while the audio itself is
+/// still processed at the full sample rate.
+///
+/// Unlike [`CoeffUpdateMask`](super::coeff_update::CoeffUpdateMask), the
+/// position within the control period carries over between blocks, so the
+/// control rate stays steady regardless of the block size.
+///
+/// ```
+/// # use firewheel_core::dsp::control_rate::ControlRate;
+/// let mut control_rate = ControlRate::new(32);
+/// let input = [0.0f32; 100];
+/// let mut meter: f32 = 0.0;
+///
+/// for block in control_rate.blocks(input.len()) {
+///     if block.update {
+///         // Run the control rate computation.
+///         meter = input[block.frames.start].abs();
+///     }
+///
+///     // Process the audio in `block.frames` at full rate.
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ControlRate {
+    interval_frames: usize,
+    /// The number of frames left until the next update.
+    frames_left: usize,
+}
+
+impl ControlRate {
+    /// Create a new control rate which updates every `interval_frames`
+    /// frames (a value of `0` is treated as `1`). The first frame that is
+    /// processed is always an update.
+    pub const fn new(interval_frames: usize) -> Self {
+        Self {
+            interval_frames: if interval_frames == 0 {
+                1
+            } else {
+                interval_frames
+            },
+            frames_left: 0,
+        }
+    }
+
+    /// The number of frames between each update.
+    pub const fn interval_frames(&self) -> usize {
+        self.interval_frames
+    }
+
+    /// Set the number of frames between each update (a value of `0` is
+    /// treated as `1`).
+    ///
+    /// The time until the next update is shortened if needed, but never
+    /// extended.
+    pub fn set_interval_frames(&mut self, interval_frames: usize) {
+        self.interval_frames = interval_frames.max(1);
+        self.frames_left = self.frames_left.min(self.interval_frames);
+    }
+
+    /// Make the next frame that is processed an update.
+    pub fn reset(&mut self) {
+        self.frames_left = 0;
+    }
+
+    /// Split a block of `frames` frames into sub-blocks, each of which
+    /// starts with an update or ends the block.
+    pub fn blocks(&mut self, frames: usize) -> ControlBlocks<'_> {
+        ControlBlocks {
+            control_rate: self,
+            frame: 0,
+            frames,
+        }
+    }
+}
+
+impl Default for ControlRate {
+    fn default() -> Self {
+        Self::new(crate::dsp::coeff_update::CoeffUpdateFactor::DEFAULT.interval_frames())
+    }
+}
+
+/// A sub-block of frames returned by [`ControlRate::blocks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ControlBlock {
+    /// The range of frames in this sub-block, relative to the start of the
+    /// processed block.
+    pub frames: Range<usize>,
+    /// Whether the control rate computation should run at the first frame of
+    /// this sub-block.
+    ///
+    /// This is only `false` for the first sub-block of a block which starts
+    /// in the middle of a control period.
+    pub update: bool,
+}
+
+/// An iterator over the sub-blocks of a block of frames. See
+/// [`ControlRate::blocks`].
+#[derive(Debug)]
+pub struct ControlBlocks<'a> {
+    control_rate: &'a mut ControlRate,
+    frame: usize,
+    frames: usize,
+}
+
+impl<'a> Iterator for ControlBlocks<'a> {
+    type Item = ControlBlock;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.frame >= self.frames {
+            return None;
+        }
+
+        let update = self.control_rate.frames_left == 0;
+        if update {
+            self.control_rate.frames_left = self.control_rate.interval_frames;
+        }
+
+        let len = self.control_rate.frames_left.min(self.frames - self.frame);
+        self.control_rate.frames_left -= len;
+
+        let frames = self.frame..self.frame + len;
+        self.frame += len;
+
+        Some(ControlBlock { frames, update })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use super::*;
+    use crate::dsp::envelope_follower::{EnvelopeFollower, EnvelopeFollowerConfig};
+
+    #[test]
+    fn meter_updates_every_interval_and_matches_full_rate() {
+        const INTERVAL: usize = 16;
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+
+        let input: Vec<f32> = (0..2_000)
+            .map(|i| (i as f32 * 0.05).sin() * (i as f32 / 2_000.0))
+            .collect();
+
+        // The reference meter is updated every frame.
+        let mut envelope = EnvelopeFollower::new(EnvelopeFollowerConfig::default(), sample_rate);
+        let full_rate: Vec<f32> = input.iter().map(|&s| envelope.process(s)).collect();
+
+        // The control rate meter only reads the envelope once per interval,
+        // using block sizes which don't line up with the interval.
+        let mut envelope = EnvelopeFollower::new(EnvelopeFollowerConfig::default(), sample_rate);
+        let mut control_rate = ControlRate::new(INTERVAL);
+        let mut meter = 0.0;
+        let mut update_frames = Vec::new();
+        let mut meter_values = Vec::new();
+
+        for (block_i, block) in input.chunks(37).enumerate() {
+            let block_start = block_i * 37;
+
+            for control_block in control_rate.blocks(block.len()) {
+                for (i, &s) in block[control_block.frames.clone()].iter().enumerate() {
+                    let value = envelope.process(s);
+
+                    if control_block.update && i == 0 {
+                        meter = value;
+                        update_frames.push(block_start + control_block.frames.start);
+                    }
+
+                    meter_values.push(meter);
+                }
+            }
+        }
+
+        let expected: Vec<usize> = (0..input.len()).step_by(INTERVAL).collect();
+        assert_eq!(update_frames, expected);
+
+        for &frame in &update_frames {
+            assert_eq!(meter_values[frame], full_rate[frame]);
+            // The meter holds its value until the next update.
+            let hold_end = (frame + INTERVAL).min(input.len());
+            assert!(meter_values[frame..hold_end]
+                .iter()
+                .all(|&v| v == full_rate[frame]));
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use super::filter::single_pole_iir::{OnePoleIirHPF, OnePoleIirHPFCoeff};
+
+/// The default cutoff frequency of a [`DcBlocker`] in hertz.
+pub const DEFAULT_DC_BLOCKER_HZ: f32 = 5.0;
+
+/// A filter that removes DC offset from a signal.
+///
+/// This is a single-pole highpass filter with a very low cutoff, so
+/// audible frequencies pass through virtually unaffected.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct DcBlocker {
+    filter: OnePoleIirHPF,
+    coeff: OnePoleIirHPFCoeff,
+}
+
+impl DcBlocker {
+    pub fn new(cutoff_hz: f32, sample_rate_recip: f32) -> Self {
+        Self {
+            filter: OnePoleIirHPF::default(),
+            coeff: OnePoleIirHPFCoeff::new(cutoff_hz, sample_rate_recip),
+        }
+    }
+
+    /// Change the cutoff frequency without resetting the filter state.
+    pub fn set_cutoff_hz(&mut self, cutoff_hz: f32, sample_rate_recip: f32) {
+        self.coeff = OnePoleIirHPFCoeff::new(cutoff_hz, sample_rate_recip);
+    }
+
+    #[inline(always)]
+    pub fn process(&mut self, s: f32) -> f32 {
+        self.filter.process(s, self.coeff)
+    }
+
+    /// Filter a block of samples in place.
+    pub fn process_block(&mut self, buffer: &mut [f32]) {
+        for s in buffer.iter_mut() {
+            *s = self.filter.process(*s, self.coeff);
+        }
+    }
+
+    /// Returns `true` if the filter has no remaining output when fed silence.
+    pub fn is_silent(&self, amp_epsilon: f32) -> bool {
+        self.filter.yz1.abs() <= amp_epsilon && self.filter.xz1.abs() <= amp_epsilon
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// Run one second of warmup, and then return the second after that.
+    fn filter(signal: impl Fn(usize) -> f32) -> Vec<f32> {
+        let mut blocker = DcBlocker::new(DEFAULT_DC_BLOCKER_HZ, SAMPLE_RATE.recip());
+        let mut buffer: Vec<f32> = (0..2 * SAMPLE_RATE as usize).map(signal).collect();
+        blocker.process_block(&mut buffer);
+        buffer.split_off(SAMPLE_RATE as usize)
+    }
+
+    fn sine_100hz(i: usize) -> f32 {
+        (core::f32::consts::TAU * 100.0 * i as f32 / SAMPLE_RATE).sin()
+    }
+
+    #[test]
+    fn removes_dc_but_passes_tones() {
+        let out = filter(|i| 0.5 + sine_100hz(i) * 0.25);
+        let mean = out.iter().sum::<f32>() / out.len() as f32;
+        assert!(mean.abs() < 1e-3);
+
+        let out = filter(sine_100hz);
+        let peak = out.iter().fold(0.0f32, |p, s| p.max(s.abs()));
+        assert!(peak > 0.99 && peak < 1.01);
+    }
+}
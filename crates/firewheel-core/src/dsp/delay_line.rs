@@ -0,0 +1,163 @@
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The number of whole frames needed to hold a delay of `seconds` at the
+/// given sample rate (rounded up).
+pub fn delay_frames_for_seconds(seconds: f32, sample_rate: NonZeroU32) -> usize {
+    (seconds.max(0.0) * sample_rate.get() as f32).ceil() as usize
+}
+
+/// A single channel ring buffer delay line with fractional (linearly
+/// interpolated) reads.
+///
+/// This is the building block for delays, choruses, flangers, and other
+/// effects that read audio back some time after it was written. Samples are
+/// pushed one at a time with [`RingDelayLine::write`] and read back with
+/// [`RingDelayLine::read_at`], where a delay of `0.0` is the most recently
+/// written sample.
+#[derive(Debug, Clone)]
+pub struct RingDelayLine {
+    buffer: Vec<f32>,
+    /// `buffer.len() - 1`. The length is always a power of two, so this is
+    /// used to wrap indices.
+    mask: usize,
+    /// The index of the most recently written sample.
+    head: usize,
+    max_delay_frames: usize,
+}
+
+impl RingDelayLine {
+    /// Construct a new delay line that can read delays of up to
+    /// `max_delay_frames` frames.
+    ///
+    /// The buffer is initialized with silence.
+    pub fn new(max_delay_frames: usize) -> Self {
+        // Leave room for the most recent sample and for the interpolated
+        // sample past the longest delay.
+        let capacity = (max_delay_frames + 2).next_power_of_two();
+
+        Self {
+            buffer: vec![0.0; capacity],
+            mask: capacity - 1,
+            head: 0,
+            max_delay_frames,
+        }
+    }
+
+    /// Construct a new delay line that can read delays of up to
+    /// `max_delay_seconds` seconds at the given sample rate.
+    pub fn from_seconds(max_delay_seconds: f32, sample_rate: NonZeroU32) -> Self {
+        Self::new(delay_frames_for_seconds(max_delay_seconds, sample_rate))
+    }
+
+    /// The longest delay in frames that can be read.
+    pub fn max_delay_frames(&self) -> usize {
+        self.max_delay_frames
+    }
+
+    /// The number of samples the buffer holds. Once this many samples of
+    /// silence have been written, everything in the delay line is silent.
+    pub fn capacity(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Push a new sample into the delay line.
+    #[inline]
+    pub fn write(&mut self, s: f32) {
+        self.head = (self.head + 1) & self.mask;
+        self.buffer[self.head] = s;
+    }
+
+    /// Read the sample that was written `delay_frames` frames ago, linearly
+    /// interpolating between samples for fractional delays.
+    ///
+    /// A delay of `0.0` is the most recently written sample. The delay is
+    /// clamped to the range `[0.0, max_delay_frames]`.
+    #[inline]
+    pub fn read_at(&self, delay_frames: f32) -> f32 {
+        let delay_frames = delay_frames.clamp(0.0, self.max_delay_frames as f32);
+        let delay_int = delay_frames.floor();
+        let frac = delay_frames - delay_int;
+
+        let i0 = self.head.wrapping_sub(delay_int as usize) & self.mask;
+        let i1 = i0.wrapping_sub(1) & self.mask;
+
+        self.buffer[i0] + (self.buffer[i1] - self.buffer[i0]) * frac
+    }
+
+    /// Read the sample that was written exactly `delay_frames` frames ago.
+    ///
+    /// A delay of `0` is the most recently written sample. The delay is
+    /// clamped to `max_delay_frames`.
+    #[inline]
+    pub fn read_at_frames(&self, delay_frames: usize) -> f32 {
+        let delay_frames = delay_frames.min(self.max_delay_frames);
+        self.buffer[self.head.wrapping_sub(delay_frames) & self.mask]
+    }
+
+    /// Fill the delay line with silence.
+    pub fn reset(&mut self) {
+        self.buffer.fill(0.0);
+        self.head = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_are_correct_across_wraparound() {
+        let mut line = RingDelayLine::new(5);
+        assert_eq!(line.capacity(), 8);
+
+        // Write enough samples to wrap around the buffer several times.
+        for i in 0..100 {
+            line.write(i as f32);
+
+            for delay in 0..=i.min(5) {
+                assert_eq!(line.read_at_frames(delay), (i - delay) as f32);
+                assert_eq!(line.read_at(delay as f32), (i - delay) as f32);
+            }
+        }
+
+        // Delays past the maximum are clamped.
+        assert_eq!(line.read_at_frames(50), 94.0);
+        assert_eq!(line.read_at(50.0), 94.0);
+
+        line.reset();
+        assert_eq!(line.read_at(3.0), 0.0);
+    }
+
+    #[test]
+    fn fractional_reads_interpolate_between_samples() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut line = RingDelayLine::from_seconds(0.01, sample_rate);
+        assert_eq!(line.max_delay_frames(), 480);
+
+        // A slow sine is close to linear between samples, so a fractional
+        // read should land very close to the true delayed value.
+        let freq = 100.0 / sample_rate.get() as f32;
+        let signal = |n: f32| (core::f32::consts::TAU * freq * n).sin();
+
+        for n in 0..2_000 {
+            line.write(signal(n as f32));
+        }
+
+        let newest = 1_999.0;
+        for delay in [0.25, 1.5, 37.75, 479.5] {
+            let expected = signal(newest - delay);
+            assert!((line.read_at(delay) - expected).abs() < 1e-4);
+        }
+
+        // Halfway between two samples is their average.
+        let a = line.read_at_frames(10);
+        let b = line.read_at_frames(11);
+        assert!((line.read_at(10.5) - (a + b) * 0.5).abs() < 1e-6);
+    }
+}
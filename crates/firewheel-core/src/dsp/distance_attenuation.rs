@@ -173,6 +173,19 @@ pub struct DistanceAttenuation {
     pub max_distance_muffle_cutoff_hz: f32,
 }
 
+impl DistanceAttenuation {
+    /// The gain (in raw amplitude) these parameters apply to a sound at the
+    /// given distance from the listener, not including muffling.
+    pub fn gain_at_distance(&self, distance: f32) -> f32 {
+        self.distance_model.calculate_gain(
+            distance,
+            self.distance_gain_factor,
+            self.reference_distance.max(0.00001),
+            self.max_distance.max(0.0),
+        )
+    }
+}
+
 impl Default for DistanceAttenuation {
     fn default() -> Self {
         Self {
@@ -0,0 +1,342 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use core::num::NonZeroU32;
+
+use super::filter::smoothing_filter::SmoothingFilterCoeff;
+use crate::diff::{Diff, Patch};
+
+/// How much slower the slow stage of the automatic release is than the
+/// configured release time.
+const AUTO_RELEASE_SLOW_FACTOR: f32 = 8.0;
+
+/// How the level of a signal is measured by an [`EnvelopeFollower`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Diff, Patch)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DetectorMode {
+    /// Follow the absolute value of each sample. This reacts to transients
+    /// as quickly as the attack time allows.
+    #[default]
+    Peak,
+    /// Follow the root-mean-square of the signal over
+    /// [`EnvelopeFollowerConfig::rms_window_secs`]. This tracks perceived
+    /// loudness more closely and largely ignores short transients.
+    Rms,
+}
+
+/// The configuration of an [`EnvelopeFollower`].
+#[derive(Debug, Clone, Copy, PartialEq, Diff, Patch)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeFollowerConfig {
+    /// How the level of the signal is measured.
+    ///
+    /// By default this is set to [`DetectorMode::Peak`].
+    pub mode: DetectorMode,
+
+    /// The time in seconds it takes the envelope to rise towards a louder
+    /// level.
+    ///
+    /// By default this is set to `0.005` (5ms).
+    pub attack_secs: f32,
+
+    /// The time in seconds it takes the envelope to fall towards a quieter
+    /// level.
+    ///
+    /// By default this is set to `0.1` (100ms).
+    pub release_secs: f32,
+
+    /// If `true`, then the release time adapts to the material. Short
+    /// transients release quickly, while sustained loud passages release
+    /// slowly (up to `8` times `release_secs`) to avoid pumping.
+    ///
+    /// By default this is set to `false`.
+    pub auto_release: bool,
+
+    /// The averaging window in seconds used in [`DetectorMode::Rms`].
+    ///
+    /// By default this is set to `0.01` (10ms).
+    pub rms_window_secs: f32,
+}
+
+impl Default for EnvelopeFollowerConfig {
+    fn default() -> Self {
+        Self {
+            mode: DetectorMode::Peak,
+            attack_secs: 0.005,
+            release_secs: 0.1,
+            auto_release: false,
+            rms_window_secs: 0.01,
+        }
+    }
+}
+
+/// A level detector for dynamics processors such as gates and compressors.
+///
+/// The output is a linear amplitude envelope. Use [`Knee`] to turn it into
+/// an amount of gain change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvelopeFollower {
+    config: EnvelopeFollowerConfig,
+
+    attack: SmoothingFilterCoeff,
+    release: SmoothingFilterCoeff,
+    slow_attack: SmoothingFilterCoeff,
+    slow_release: SmoothingFilterCoeff,
+    rms: SmoothingFilterCoeff,
+
+    mean_square: f32,
+    envelope: f32,
+    slow_envelope: f32,
+}
+
+impl EnvelopeFollower {
+    pub fn new(config: EnvelopeFollowerConfig, sample_rate: NonZeroU32) -> Self {
+        let placeholder = SmoothingFilterCoeff::new(sample_rate, 0.0);
+
+        let mut new_self = Self {
+            config,
+            attack: placeholder,
+            release: placeholder,
+            slow_attack: placeholder,
+            slow_release: placeholder,
+            rms: placeholder,
+            mean_square: 0.0,
+            envelope: 0.0,
+            slow_envelope: 0.0,
+        };
+
+        new_self.set_config(config, sample_rate);
+
+        new_self
+    }
+
+    pub fn config(&self) -> &EnvelopeFollowerConfig {
+        &self.config
+    }
+
+    /// Change the configuration without resetting the current envelope.
+    pub fn set_config(&mut self, config: EnvelopeFollowerConfig, sample_rate: NonZeroU32) {
+        self.config = config;
+
+        self.attack = SmoothingFilterCoeff::new(sample_rate, config.attack_secs);
+        self.release = SmoothingFilterCoeff::new(sample_rate, config.release_secs);
+        self.rms = SmoothingFilterCoeff::new(sample_rate, config.rms_window_secs);
+
+        // The slow stage only charges up on sustained material, so brief
+        // transients never hold the release open.
+        self.slow_attack = SmoothingFilterCoeff::new(
+            sample_rate,
+            config.attack_secs.max(config.release_secs) * AUTO_RELEASE_SLOW_FACTOR,
+        );
+        self.slow_release =
+            SmoothingFilterCoeff::new(sample_rate, config.release_secs * AUTO_RELEASE_SLOW_FACTOR);
+    }
+
+    /// Process a single sample, returning the current envelope.
+    #[inline]
+    pub fn process(&mut self, s: f32) -> f32 {
+        let level = match self.config.mode {
+            DetectorMode::Peak => s.abs(),
+            DetectorMode::Rms => {
+                self.mean_square = (s * s * self.rms.a0) + (self.mean_square * self.rms.b1);
+                self.mean_square.sqrt()
+            }
+        };
+
+        self.envelope = follow(self.envelope, level, self.attack, self.release);
+
+        if self.config.auto_release {
+            self.slow_envelope = follow(
+                self.slow_envelope,
+                level,
+                self.slow_attack,
+                self.slow_release,
+            );
+            self.envelope.max(self.slow_envelope)
+        } else {
+            self.envelope
+        }
+    }
+
+    /// Process a block of samples, writing the envelope into `out`.
+    pub fn process_block(&mut self, input: &[f32], out: &mut [f32]) {
+        for (&s, out_s) in input.iter().zip(out.iter_mut()) {
+            *out_s = self.process(s);
+        }
+    }
+
+    /// The current envelope.
+    pub fn value(&self) -> f32 {
+        if self.config.auto_release {
+            self.envelope.max(self.slow_envelope)
+        } else {
+            self.envelope
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.mean_square = 0.0;
+        self.envelope = 0.0;
+        self.slow_envelope = 0.0;
+    }
+}
+
+#[inline(always)]
+fn follow(
+    envelope: f32,
+    level: f32,
+    attack: SmoothingFilterCoeff,
+    release: SmoothingFilterCoeff,
+) -> f32 {
+    let coeff = if level > envelope { attack } else { release };
+    (level * coeff.a0) + (envelope * coeff.b1)
+}
+
+/// Compressor ratios at or above this value are treated as infinite, which
+/// turns the compressor into a hard limiter.
+pub const LIMITER_RATIO: f32 = 100.0;
+
+/// A static gain curve with an optional soft knee, used to turn the level
+/// from an [`EnvelopeFollower`] into an amount of gain change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Knee {
+    /// The level in decibels at which gain change begins.
+    pub threshold_db: f32,
+
+    /// The ratio of gain change (i.e. `4.0` means `4:1`). Values less than
+    /// `1.0` are treated as `1.0`.
+    pub ratio: f32,
+
+    /// The width in decibels of the region around the threshold over which
+    /// the ratio is gradually applied. A value of `0.0` gives a hard knee.
+    pub knee_db: f32,
+}
+
+impl Default for Knee {
+    fn default() -> Self {
+        Self {
+            threshold_db: -20.0,
+            ratio: 4.0,
+            knee_db: 6.0,
+        }
+    }
+}
+
+impl Knee {
+    /// The gain change in decibels (always `<= 0.0`) a compressor should
+    /// apply to a signal at the given level.
+    pub fn compressor_gain_db(&self, level_db: f32) -> f32 {
+        let slope = 1.0 - self.ratio.max(1.0).recip();
+        let over = level_db - self.threshold_db;
+        let half_knee = self.knee_db.max(0.0) * 0.5;
+
+        if over <= -half_knee {
+            0.0
+        } else if over >= half_knee {
+            -slope * over
+        } else {
+            let x = over + half_knee;
+            -slope * x * x / (4.0 * half_knee)
+        }
+    }
+
+    /// The gain change in decibels (always `<= 0.0`) a downward expander or
+    /// gate should apply to a signal at the given level.
+    pub fn expander_gain_db(&self, level_db: f32) -> f32 {
+        let slope = self.ratio.max(1.0) - 1.0;
+        let under = self.threshold_db - level_db;
+        let half_knee = self.knee_db.max(0.0) * 0.5;
+
+        if under <= -half_knee {
+            0.0
+        } else if under >= half_knee {
+            -slope * under
+        } else {
+            let x = under + half_knee;
+            -slope * x * x / (4.0 * half_knee)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: NonZeroU32 = NonZeroU32::new(48_000).unwrap();
+
+    /// The number of frames it takes the envelope to reach half of a
+    /// full-scale step.
+    fn rise_frames(mode: DetectorMode) -> usize {
+        let mut follower = EnvelopeFollower::new(
+            EnvelopeFollowerConfig {
+                mode,
+                attack_secs: 0.001,
+                ..Default::default()
+            },
+            SAMPLE_RATE,
+        );
+
+        (0..48_000)
+            .take_while(|_| follower.process(1.0) < 0.5)
+            .count()
+    }
+
+    #[test]
+    fn rms_is_slower_than_peak() {
+        let peak = rise_frames(DetectorMode::Peak);
+        let rms = rise_frames(DetectorMode::Rms);
+
+        assert!(peak < 48);
+        assert!(rms > peak * 2);
+    }
+
+    #[test]
+    fn auto_release_adapts_to_material() {
+        let config = EnvelopeFollowerConfig {
+            auto_release: true,
+            ..Default::default()
+        };
+        let release_frames = 4800;
+
+        // A short transient should release about as fast as the fixed release.
+        let mut transient = vec![0.0; 48 + release_frames];
+        transient[..48].fill(1.0);
+        let mut follower = EnvelopeFollower::new(config, SAMPLE_RATE);
+        follower.process_block(&transient, &mut vec![0.0; transient.len()]);
+        let after_transient = follower.value();
+
+        // Sustained material should hold the envelope up for longer.
+        let mut sustained = vec![0.0; 48_000 + release_frames];
+        sustained[..48_000].fill(1.0);
+        let mut follower = EnvelopeFollower::new(config, SAMPLE_RATE);
+        follower.process_block(&sustained, &mut vec![0.0; sustained.len()]);
+        let after_sustained = follower.value();
+
+        assert!(after_sustained > after_transient * 2.0);
+    }
+
+    #[test]
+    fn soft_knee() {
+        let hard = Knee {
+            knee_db: 0.0,
+            ..Default::default()
+        };
+        let soft = Knee::default();
+
+        assert_eq!(hard.compressor_gain_db(-30.0), 0.0);
+        assert_eq!(hard.compressor_gain_db(-20.0), 0.0);
+        assert!((hard.compressor_gain_db(-10.0) + 7.5).abs() < 1e-5);
+
+        // The soft knee starts compressing below the threshold and joins the
+        // hard curve above it.
+        assert!(soft.compressor_gain_db(-20.0) < 0.0);
+        assert!((soft.compressor_gain_db(-10.0) + 7.5).abs() < 1e-5);
+
+        assert_eq!(hard.expander_gain_db(-10.0), 0.0);
+        assert!((hard.expander_gain_db(-30.0) + 30.0).abs() < 1e-4);
+    }
+}
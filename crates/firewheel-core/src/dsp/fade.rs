@@ -3,14 +3,19 @@ use num_traits::Float;
 
 use core::f32::consts::FRAC_PI_2;
 
-use crate::diff::{Diff, Patch};
+use crate::{
+    collector::ArcGc,
+    diff::{Diff, Patch},
+};
 
 /// The algorithm used to map a normalized crossfade/panning value in the
 /// range `[-1.0, 1.0]` to the corresponding gain values for two inputs.
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Diff, Patch)]
+///
+/// Note that this type is not [`Copy`] because of the [`FadeCurve::Custom`]
+/// variant. Cloning it is still cheap and does not allocate.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Diff, Patch)]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-#[repr(u32)]
 pub enum FadeCurve {
     /// This curve makes the combined signal appear to play at a constant volume
     /// across the entire fade range for most signals.
@@ -18,7 +23,7 @@ pub enum FadeCurve {
     /// More specifically this a circular curve with each input at -3dB at
     /// center.
     #[default]
-    EqualPower3dB = 0,
+    EqualPower3dB,
     /// Same as [`FadeCurve::EqualPower3dB`], but each input will be at -6dB
     /// at center which may be better for some signals.
     EqualPower6dB,
@@ -30,6 +35,23 @@ pub enum FadeCurve {
     /// correlated such as a wet/dry mix, then this mode may actually provide
     /// better results.)
     Linear,
+    /// A user-defined curve read from a lookup table.
+    ///
+    /// The table describes the gain of the second input as the fade goes from
+    /// `0.0` (the first entry) to `1.0` (the last entry), with the entries
+    /// evenly spaced in between. Values between entries are linearly
+    /// interpolated. The gain of the first input is the same curve mirrored,
+    /// so a table filled with `[0.0, ..., 1.0]` in equal steps is equivalent
+    /// to [`FadeCurve::Linear`].
+    ///
+    /// An empty table behaves like [`FadeCurve::Linear`].
+    ///
+    /// This variant cannot be serialized.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    Custom(
+        #[cfg_attr(feature = "bevy_reflect", reflect(ignore, default = "empty_table"))]
+        ArcGc<[f32]>,
+    ),
 }
 
 impl FadeCurve {
@@ -60,6 +82,7 @@ impl FadeCurve {
                 }
                 Self::SquareRoot => ((1.0 - fade).sqrt(), fade.sqrt()),
                 Self::Linear => ((1.0 - fade), fade),
+                Self::Custom(table) => (sample_table(table, 1.0 - fade), sample_table(table, fade)),
             }
         }
     }
@@ -93,6 +116,7 @@ impl FadeCurve {
                 }
                 Self::SquareRoot => ((1.0 - fade).sqrt(), fade.sqrt()),
                 Self::Linear => ((1.0 - fade), fade),
+                Self::Custom(table) => (sample_table(table, 1.0 - fade), sample_table(table, fade)),
             }
         }
     }
@@ -106,3 +130,53 @@ impl FadeCurve {
         }
     }
 }
+
+#[cfg(feature = "bevy_reflect")]
+fn empty_table() -> ArcGc<[f32]> {
+    ArcGc::new_unsized(|| bevy_platform::sync::Arc::<[f32]>::from([]))
+}
+
+/// Sample a normalized lookup table at the position `x` in the range
+/// `[0.0, 1.0]` using linear interpolation.
+fn sample_table(table: &[f32], x: f32) -> f32 {
+    match table.len() {
+        0 => x,
+        1 => table[0],
+        len => {
+            let pos = x.clamp(0.0, 1.0) * (len - 1) as f32;
+            let i0 = (pos as usize).min(len - 2);
+            let t = pos - i0 as f32;
+
+            table[i0] + (table[i0 + 1] - table[i0]) * t
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_custom_table_matches_linear() {
+        extern crate std;
+        use std::{sync::Arc, vec::Vec};
+
+        // Use a coarse table so that the test also exercises interpolation.
+        let table: Vec<f32> = (0..=16).map(|i| i as f32 / 16.0).collect();
+        let custom = FadeCurve::Custom(ArcGc::new_unsized(|| Arc::<[f32]>::from(table)));
+
+        for i in 0..=200 {
+            let fade = i as f32 / 200.0;
+
+            let (a0, a1) = custom.compute_gains_0_to_1(fade);
+            let (b0, b1) = FadeCurve::Linear.compute_gains_0_to_1(fade);
+            assert!((a0 - b0).abs() < 1e-5 && (a1 - b1).abs() < 1e-5);
+
+            let fade = fade * 2.0 - 1.0;
+
+            let (a0, a1) = custom.compute_gains_neg1_to_1(fade);
+            let (b0, b1) = FadeCurve::Linear.compute_gains_neg1_to_1(fade);
+            assert!((a0 - b0).abs() < 1e-5 && (a1 - b1).abs() < 1e-5);
+        }
+    }
+}
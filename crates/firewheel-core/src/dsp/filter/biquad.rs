@@ -0,0 +1,101 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use core::f32::consts::TAU;
+
+use crate::diff::{Diff, Patch};
+
+/// The coefficients of a biquad (second order IIR) filter, normalized so
+/// that `a0` is `1.0`.
+///
+/// The difference equation of the filter is:
+///
+/// `y[n] = b0*x[n] + b1*x[n-1] + b2*x[n-2] - a1*y[n-1] - a2*y[n-2]`
+#[derive(Debug, Clone, Copy, PartialEq, Diff, Patch)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BiquadCoeff {
+    pub b0: f32,
+    pub b1: f32,
+    pub b2: f32,
+    pub a1: f32,
+    pub a2: f32,
+}
+
+impl BiquadCoeff {
+    /// Coefficients which pass the signal through unchanged.
+    pub const NO_OP: Self = Self {
+        b0: 1.0,
+        b1: 0.0,
+        b2: 0.0,
+        a1: 0.0,
+        a2: 0.0,
+    };
+
+    /// A lowpass filter (-12 dB per octave).
+    ///
+    /// This is based on the "Audio EQ Cookbook" by Robert Bristow-Johnson.
+    pub fn lowpass(cutoff_hz: f32, q: f32, sample_rate_recip: f32) -> Self {
+        let w0 = TAU * cutoff_hz * sample_rate_recip;
+        let cos_w0 = w0.cos();
+        let alpha = w0.sin() / (2.0 * q);
+
+        let a0_recip = (1.0 + alpha).recip();
+        let b1 = (1.0 - cos_w0) * a0_recip;
+
+        Self {
+            b0: b1 * 0.5,
+            b1,
+            b2: b1 * 0.5,
+            a1: -2.0 * cos_w0 * a0_recip,
+            a2: (1.0 - alpha) * a0_recip,
+        }
+    }
+
+    /// Returns `true` if all coefficients are finite and both poles of the
+    /// filter lie inside the unit circle.
+    ///
+    /// Note, the set of stable `(a1, a2)` pairs is convex, so linearly
+    /// interpolating between two stable sets of coefficients always results
+    /// in a stable filter.
+    pub fn is_stable(&self) -> bool {
+        self.b0.is_finite()
+            && self.b1.is_finite()
+            && self.b2.is_finite()
+            && self.a1.is_finite()
+            && self.a2.is_finite()
+            && self.a2.abs() < 1.0
+            && self.a1.abs() < 1.0 + self.a2
+    }
+}
+
+impl Default for BiquadCoeff {
+    fn default() -> Self {
+        Self::NO_OP
+    }
+}
+
+/// The state of a biquad filter, using the transposed direct form II
+/// structure.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct BiquadState {
+    pub s1: f32,
+    pub s2: f32,
+}
+
+impl BiquadState {
+    #[inline(always)]
+    pub fn process(&mut self, input: f32, coeff: &BiquadCoeff) -> f32 {
+        let output = coeff.b0 * input + self.s1;
+        self.s1 = coeff.b1 * input - coeff.a1 * output + self.s2;
+        self.s2 = coeff.b2 * input - coeff.a2 * output;
+
+        output
+    }
+
+    #[inline(always)]
+    pub fn reset(&mut self) {
+        self.s1 = 0.0;
+        self.s2 = 0.0;
+    }
+}
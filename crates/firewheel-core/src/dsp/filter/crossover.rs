@@ -0,0 +1,75 @@
+//! Fourth order Linkwitz-Riley crossovers for splitting a signal into bands.
+
+use core::num::NonZeroU32;
+
+use super::{
+    butterworth::Q_BUTTERWORTH_ORD2,
+    svf::{SvfCoeff, SvfState},
+};
+
+/// The lowest allowed crossover frequency in hertz.
+pub const MIN_CROSSOVER_HZ: f32 = 20.0;
+/// The highest allowed crossover frequency in hertz.
+pub const MAX_CROSSOVER_HZ: f32 = 20_000.0;
+
+/// The highest crossover frequency in hertz that is usable at the given
+/// sample rate.
+pub fn max_crossover_hz(sample_rate: NonZeroU32) -> f32 {
+    MAX_CROSSOVER_HZ.min(sample_rate.get() as f32 * 0.45)
+}
+
+/// The coefficients of a single [`Lr4Crossover`].
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Lr4CrossoverCoeffs {
+    pub lowpass: SvfCoeff,
+    pub highpass: SvfCoeff,
+    /// An allpass filter with the same phase response as the sum of the
+    /// lowpass and highpass outputs.
+    ///
+    /// Pass a band that doesn't go through this crossover through this
+    /// filter to keep it in phase with the bands that do.
+    pub allpass: SvfCoeff,
+}
+
+impl Lr4CrossoverCoeffs {
+    pub fn new(crossover_hz: f32, sample_rate_recip: f32) -> Self {
+        Self {
+            lowpass: SvfCoeff::lowpass_ord2(crossover_hz, Q_BUTTERWORTH_ORD2, sample_rate_recip),
+            highpass: SvfCoeff::highpass_ord2(crossover_hz, Q_BUTTERWORTH_ORD2, sample_rate_recip),
+            // The sum of a fourth order Linkwitz-Riley lowpass and highpass
+            // is a second order allpass with a Butterworth Q.
+            allpass: SvfCoeff::allpass(crossover_hz, Q_BUTTERWORTH_ORD2, sample_rate_recip),
+        }
+    }
+}
+
+/// A fourth order Linkwitz-Riley crossover, where each side is made of two
+/// cascaded second order Butterworth filters.
+///
+/// The low and high outputs sum back to an allpassed version of the input.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Lr4Crossover {
+    lowpass: [SvfState; 2],
+    highpass: [SvfState; 2],
+}
+
+impl Lr4Crossover {
+    /// Split a sample into its low and high bands.
+    #[inline]
+    pub fn split(&mut self, s: f32, coeffs: &Lr4CrossoverCoeffs) -> [f32; 2] {
+        [
+            lr4(&mut self.lowpass, s, &coeffs.lowpass),
+            lr4(&mut self.highpass, s, &coeffs.highpass),
+        ]
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[inline(always)]
+fn lr4(states: &mut [SvfState; 2], s: f32, coeff: &SvfCoeff) -> f32 {
+    let s = states[0].process(s, coeff);
+    states[1].process(s, coeff)
+}
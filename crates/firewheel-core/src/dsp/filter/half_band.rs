@@ -0,0 +1,236 @@
+//! Polyphase IIR half-band filters for `2x` oversampling.
+//!
+//! This is based on the polyphase allpass design used by Laurent de Soras'
+//! HIIR library.
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The number of allpass coefficients in a [`HalfBandCoeffs`].
+///
+/// With the default transition band this gives a flat passband up to
+/// `0.46` times the (non-oversampled) sample rate, and roughly `-100` dB
+/// of rejection above `0.54` times the sample rate.
+pub const HALF_BAND_NUM_COEFFS: usize = 8;
+
+/// The default width of the transition band, normalized to the oversampled
+/// sample rate.
+pub const DEFAULT_HALF_BAND_TRANSITION: f64 = 0.04;
+
+/// The coefficients of a half-band filter made of two parallel chains of
+/// first order allpass filters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HalfBandCoeffs([f32; HALF_BAND_NUM_COEFFS]);
+
+impl Default for HalfBandCoeffs {
+    fn default() -> Self {
+        Self::new(DEFAULT_HALF_BAND_TRANSITION)
+    }
+}
+
+impl HalfBandCoeffs {
+    /// Compute the coefficients for the given width of the transition band,
+    /// normalized to the oversampled sample rate (in the range
+    /// `(0.0, 0.5)`).
+    pub fn new(transition: f64) -> Self {
+        let order = (HALF_BAND_NUM_COEFFS * 2 + 1) as f64;
+
+        let k = ((1.0 - transition * 2.0) * core::f64::consts::FRAC_PI_4)
+            .tan()
+            .powi(2);
+        let kk_sqrt = (1.0 - k * k).powf(0.25);
+        let e = 0.5 * (1.0 - kk_sqrt) / (1.0 + kk_sqrt);
+        let e4 = e.powi(4);
+        let q = e * (1.0 + e4 * (2.0 + e4 * (15.0 + 150.0 * e4)));
+
+        Self(core::array::from_fn(|i| {
+            let c = (i + 1) as f64;
+
+            let num = q.powf(0.25)
+                * elliptic_sum(0, |i| {
+                    q.powi((i * (i + 1)) as i32)
+                        * (((i * 2 + 1) as f64) * c * core::f64::consts::PI / order).sin()
+                });
+            let den = 0.5
+                + elliptic_sum(1, |i| {
+                    -q.powi((i * i) as i32)
+                        * ((i * 2) as f64 * c * core::f64::consts::PI / order).cos()
+                });
+
+            let ww = (num / den).powi(2);
+            let x = ((1.0 - ww * k) * (1.0 - ww / k)).sqrt() / (1.0 + ww);
+
+            ((1.0 - x) / (1.0 + x)) as f32
+        }))
+    }
+}
+
+/// Sum an alternating series until its terms vanish.
+fn elliptic_sum(first: usize, term: impl Fn(usize) -> f64) -> f64 {
+    let mut acc = 0.0;
+    let mut sign = 1.0;
+
+    for i in first.. {
+        let t = term(i) * sign;
+        acc += t;
+        sign = -sign;
+
+        if t.abs() <= 1e-100 || i > 64 {
+            break;
+        }
+    }
+
+    acc
+}
+
+/// The state of a chain of first order allpass filters.
+#[derive(Debug, Clone, Copy)]
+struct AllpassChain<const LANES: usize, const STAGES: usize> {
+    x1: [[f32; LANES]; STAGES],
+    y1: [[f32; LANES]; STAGES],
+}
+
+impl<const LANES: usize, const STAGES: usize> Default for AllpassChain<LANES, STAGES> {
+    fn default() -> Self {
+        Self {
+            x1: [[0.0; LANES]; STAGES],
+            y1: [[0.0; LANES]; STAGES],
+        }
+    }
+}
+
+impl<const LANES: usize, const STAGES: usize> AllpassChain<LANES, STAGES> {
+    /// Process a sample through the chain, using every other coefficient
+    /// starting at `first_coeff`.
+    #[inline(always)]
+    fn process(
+        &mut self,
+        mut s: [f32; LANES],
+        coeffs: &HalfBandCoeffs,
+        first_coeff: usize,
+    ) -> [f32; LANES] {
+        for (stage, c) in coeffs.0.iter().skip(first_coeff).step_by(2).enumerate() {
+            let y: [f32; LANES] =
+                core::array::from_fn(|i| (s[i] - self.y1[stage][i]) * c + self.x1[stage][i]);
+
+            self.x1[stage] = s;
+            self.y1[stage] = y;
+            s = y;
+        }
+
+        s
+    }
+}
+
+const CHAIN_STAGES: usize = HALF_BAND_NUM_COEFFS / 2;
+
+/// Upsamples a signal by a factor of `2`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Upsampler2x<const LANES: usize> {
+    even: AllpassChain<LANES, CHAIN_STAGES>,
+    odd: AllpassChain<LANES, CHAIN_STAGES>,
+}
+
+impl<const LANES: usize> Upsampler2x<LANES> {
+    /// Returns the two oversampled frames for the given frame.
+    #[inline]
+    pub fn process(&mut self, s: [f32; LANES], coeffs: &HalfBandCoeffs) -> [[f32; LANES]; 2] {
+        [
+            self.even.process(s, coeffs, 0),
+            self.odd.process(s, coeffs, 1),
+        ]
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Downsamples a signal by a factor of `2`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Downsampler2x<const LANES: usize> {
+    even: AllpassChain<LANES, CHAIN_STAGES>,
+    odd: AllpassChain<LANES, CHAIN_STAGES>,
+}
+
+impl<const LANES: usize> Downsampler2x<LANES> {
+    /// Returns the decimated frame for the given two oversampled frames.
+    #[inline]
+    pub fn process(&mut self, s: [[f32; LANES]; 2], coeffs: &HalfBandCoeffs) -> [f32; LANES] {
+        let a = self.even.process(s[1], coeffs, 0);
+        let b = self.odd.process(s[0], coeffs, 1);
+
+        core::array::from_fn(|i| (a[i] + b[i]) * 0.5)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f32::consts::TAU;
+
+    use super::*;
+
+    /// The amplitude of the sine wave at `freq` (relative to the sample
+    /// rate) in the second half of `samples`.
+    fn tone_level(samples: &[f32], freq: f32) -> f32 {
+        let tail = &samples[samples.len() / 2..];
+        let (re, im) = tail
+            .iter()
+            .enumerate()
+            .fold((0.0, 0.0), |(re, im), (n, &s)| {
+                let w = TAU * freq * n as f32;
+                (re + s * w.cos(), im - s * w.sin())
+            });
+        (re * re + im * im).sqrt() * 2.0 / tail.len() as f32
+    }
+
+    fn sine(freq: f32, frames: usize) -> impl Iterator<Item = f32> {
+        (0..frames).map(move |i| (TAU * freq * i as f32).sin())
+    }
+
+    #[test]
+    fn passband_is_flat() {
+        let coeffs = HalfBandCoeffs::default();
+
+        for freq in [0.01, 0.1, 0.25, 0.4, 0.45] {
+            let mut up = Upsampler2x::<1>::default();
+            let mut down = Downsampler2x::<1>::default();
+
+            let out: Vec<f32> = sine(freq, 4_000)
+                .map(|s| down.process(up.process([s], &coeffs), &coeffs)[0])
+                .collect();
+
+            let level = tone_level(&out, freq);
+            assert!((level - 1.0).abs() < 0.01, "{level} at {freq}");
+        }
+    }
+
+    #[test]
+    fn images_and_aliases_are_rejected() {
+        let coeffs = HalfBandCoeffs::default();
+
+        // Upsampling a tone must not create an image above the original
+        // Nyquist frequency.
+        let mut up = Upsampler2x::<1>::default();
+        let upsampled: Vec<f32> = sine(0.1, 4_000)
+            .flat_map(|s| up.process([s], &coeffs))
+            .map(|s| s[0])
+            .collect();
+        assert!((tone_level(&upsampled, 0.05) - 1.0).abs() < 0.01);
+        assert!(tone_level(&upsampled, 0.45) < 0.001);
+
+        // Downsampling a tone above the new Nyquist frequency must not alias
+        // it back down.
+        let mut down = Downsampler2x::<1>::default();
+        let oversampled: Vec<f32> = sine(0.3, 8_000).collect();
+        let decimated: Vec<f32> = oversampled
+            .chunks_exact(2)
+            .map(|s| down.process([[s[0]], [s[1]]], &coeffs)[0])
+            .collect();
+        assert!(tone_level(&decimated, 0.4) < 0.001);
+    }
+}
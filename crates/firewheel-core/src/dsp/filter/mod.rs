@@ -1,7 +1,10 @@
 #[cfg(not(feature = "std"))]
 use num_traits::Float;
 
+pub mod biquad;
 pub mod butterworth;
+pub mod crossover;
+pub mod half_band;
 pub mod single_pole_iir;
 pub mod smoothing_filter;
 pub mod svf;
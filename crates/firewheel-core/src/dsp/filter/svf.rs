@@ -119,6 +119,18 @@ impl SvfCoeff {
         })
     }
 
+    /// A bandpass filter with a gain of `1.0` at the center frequency.
+    ///
+    /// Subtracting the output of this filter from its input gives the
+    /// matching notch, so the two can be used to split a signal into a band
+    /// and everything outside it.
+    pub fn bandpass(cutoff_hz: f32, q: f32, sample_rate_recip: f32) -> Self {
+        let g = g(cutoff_hz, sample_rate_recip);
+        let k = 1.0 / q;
+
+        Self::from_g_and_k(g, k, 0.0, k, 0.0)
+    }
+
     pub fn notch(cutoff_hz: f32, q: f32, sample_rate_recip: f32) -> Self {
         let g = g(cutoff_hz, sample_rate_recip);
         let k = 1.0 / q;
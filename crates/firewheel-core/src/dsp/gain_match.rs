@@ -0,0 +1,102 @@
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use super::volume::DEFAULT_AMP_EPSILON;
+
+/// The default length in seconds of the window a [`GainMatch`] averages
+/// loudness over.
+pub const DEFAULT_GAIN_MATCH_WINDOW_SECS: f32 = 0.5;
+
+/// The largest gain a [`GainMatch`] will apply (about `+24` dB).
+const MAX_GAIN: f32 = 16.0;
+
+/// Measures the difference in loudness between the dry input and the wet
+/// output of an effect.
+///
+/// When the effect is bypassed, the dry signal can be multiplied by
+/// [`GainMatch::gain`] so that it plays at the same loudness as the wet
+/// signal did. This makes A/B comparisons fair, since a louder signal tends
+/// to sound "better" regardless of the effect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GainMatch {
+    dry_power: f32,
+    wet_power: f32,
+    window_frames: f32,
+}
+
+impl GainMatch {
+    /// Construct a new gain matcher.
+    ///
+    /// * `window_secs` - The length of the window in seconds to average
+    ///   loudness over.
+    pub fn new(window_secs: f32, sample_rate: NonZeroU32) -> Self {
+        Self {
+            dry_power: 0.0,
+            wet_power: 0.0,
+            window_frames: (window_secs * sample_rate.get() as f32).max(1.0),
+        }
+    }
+
+    /// Measure a block of dry input and the matching block of wet output.
+    ///
+    /// Blocks where the dry input is silent are ignored, so the last
+    /// measured gain is kept while nothing is playing.
+    pub fn measure<D: AsRef<[f32]>, W: AsRef<[f32]>>(
+        &mut self,
+        dry: &[D],
+        wet: &[W],
+        frames: usize,
+    ) {
+        let mut dry_sum = 0.0;
+        let mut wet_sum = 0.0;
+
+        for (dry_ch, wet_ch) in dry.iter().zip(wet.iter()) {
+            let dry_ch = &dry_ch.as_ref()[..frames];
+            let wet_ch = &wet_ch.as_ref()[..frames];
+
+            dry_sum += dry_ch.iter().map(|&s| s * s).sum::<f32>();
+            wet_sum += wet_ch.iter().map(|&s| s * s).sum::<f32>();
+        }
+
+        let samples = (frames * dry.len().min(wet.len())) as f32;
+        if samples == 0.0 || dry_sum / samples <= DEFAULT_AMP_EPSILON * DEFAULT_AMP_EPSILON {
+            return;
+        }
+
+        // Average the power with a one-pole filter, adjusted so that the
+        // result does not depend on the block size.
+        let alpha = 1.0 - (-(frames as f32) / self.window_frames).exp();
+        if self.dry_power == 0.0 {
+            self.dry_power = dry_sum / samples;
+            self.wet_power = wet_sum / samples;
+        } else {
+            self.dry_power += alpha * (dry_sum / samples - self.dry_power);
+            self.wet_power += alpha * (wet_sum / samples - self.wet_power);
+        }
+    }
+
+    /// The gain (in raw amplitude) that brings the dry signal to the same
+    /// loudness as the wet signal.
+    ///
+    /// This is `1.0` if nothing has been measured yet.
+    pub fn gain(&self) -> f32 {
+        if self.dry_power == 0.0 {
+            return 1.0;
+        }
+
+        (self.wet_power / self.dry_power).sqrt().min(MAX_GAIN)
+    }
+
+    /// Change the length of the window without resetting the measurement.
+    pub fn set_window(&mut self, window_secs: f32, sample_rate: NonZeroU32) {
+        self.window_frames = (window_secs * sample_rate.get() as f32).max(1.0);
+    }
+
+    /// Forget the measured gain.
+    pub fn reset(&mut self) {
+        self.dry_power = 0.0;
+        self.wet_power = 0.0;
+    }
+}
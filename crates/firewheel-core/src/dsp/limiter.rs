@@ -0,0 +1,387 @@
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use super::{
+    filter::smoothing_filter::SmoothingFilterCoeff,
+    volume::{amp_to_db, db_to_amp},
+};
+
+/// The configuration of a [`Limiter`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LimiterConfig {
+    /// The maximum level in decibels of the output. Values above `0.0`
+    /// are treated as `0.0`.
+    ///
+    /// By default this is set to `-1.0`.
+    pub ceiling_db: f32,
+
+    /// The time in seconds it takes the gain to recover after a peak.
+    ///
+    /// By default this is set to `0.1` (100ms).
+    pub release_secs: f32,
+
+    /// The time in seconds the limiter looks ahead of the signal.
+    ///
+    /// With a lookahead, the gain is ramped down smoothly before a peak
+    /// arrives instead of jumping down instantly, which is much more
+    /// transparent. The output is still guaranteed to never exceed the
+    /// ceiling, but it is delayed by the lookahead time (see
+    /// [`Limiter::latency_frames`]).
+    ///
+    /// By default this is set to `0.0` (no lookahead).
+    pub lookahead_secs: f32,
+}
+
+impl LimiterConfig {
+    /// The number of frames of lookahead (and thus latency) at the given
+    /// sample rate.
+    pub fn lookahead_frames(&self, sample_rate: NonZeroU32) -> u32 {
+        (self.lookahead_secs.max(0.0) * sample_rate.get() as f32).round() as u32
+    }
+}
+
+impl Default for LimiterConfig {
+    fn default() -> Self {
+        Self {
+            ceiling_db: -1.0,
+            release_secs: 0.1,
+            lookahead_secs: 0.0,
+        }
+    }
+}
+
+/// A simple peak limiter with an instantaneous attack, or a smooth attack
+/// if [`LimiterConfig::lookahead_secs`] is set.
+///
+/// The gain is linked across all channels so the stereo image is kept, and
+/// the output is guaranteed to never exceed the configured ceiling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Limiter {
+    config: LimiterConfig,
+    ceiling: f32,
+    release: SmoothingFilterCoeff,
+    gain: f32,
+    lookahead: Option<Lookahead>,
+}
+
+impl Limiter {
+    /// Construct a new limiter for audio with the given number of channels.
+    ///
+    /// The number of channels is only used to allocate the lookahead delay
+    /// line.
+    pub fn new(config: LimiterConfig, num_channels: usize, sample_rate: NonZeroU32) -> Self {
+        let lookahead_frames = config.lookahead_frames(sample_rate) as usize;
+
+        Self {
+            config,
+            ceiling: db_to_amp(config.ceiling_db.min(0.0)),
+            release: SmoothingFilterCoeff::new(sample_rate, config.release_secs),
+            gain: 1.0,
+            lookahead: (lookahead_frames > 0 && num_channels > 0)
+                .then(|| Lookahead::new(lookahead_frames, num_channels)),
+        }
+    }
+
+    pub fn config(&self) -> &LimiterConfig {
+        &self.config
+    }
+
+    /// Change the configuration without resetting the current gain.
+    ///
+    /// If the lookahead time changes, then the lookahead delay line is
+    /// cleared.
+    pub fn set_config(
+        &mut self,
+        config: LimiterConfig,
+        num_channels: usize,
+        sample_rate: NonZeroU32,
+    ) {
+        let gain = self.gain;
+        let lookahead = self.lookahead.take();
+
+        *self = Self::new(config, num_channels, sample_rate);
+        self.gain = gain;
+
+        if let Some(lookahead) = lookahead {
+            if self.lookahead.as_ref().is_some_and(|new| {
+                new.frames == lookahead.frames && new.num_channels == lookahead.num_channels
+            }) {
+                self.lookahead = Some(lookahead);
+            }
+        }
+    }
+
+    /// The latency in frames this limiter adds to the signal.
+    pub fn latency_frames(&self) -> u32 {
+        self.lookahead
+            .as_ref()
+            .map(|lookahead| lookahead.frames as u32)
+            .unwrap_or(0)
+    }
+
+    /// Limit a block of interleaved audio data in place.
+    ///
+    /// Returns the largest amount of gain reduction applied in this block,
+    /// in decibels (a value `>= 0.0`).
+    pub fn process_interleaved(&mut self, data: &mut [f32], num_channels: usize) -> f32 {
+        if num_channels == 0 {
+            return 0.0;
+        }
+
+        if let Some(lookahead) = &mut self.lookahead {
+            debug_assert_eq!(num_channels, lookahead.num_channels);
+
+            if num_channels == lookahead.num_channels {
+                let min_gain = lookahead.process_interleaved(
+                    data,
+                    self.ceiling,
+                    &self.release,
+                    &mut self.gain,
+                );
+
+                return -amp_to_db(min_gain).min(0.0);
+            }
+        }
+
+        let mut min_gain: f32 = self.gain;
+
+        for frame in data.chunks_exact_mut(num_channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+            let target = if peak > self.ceiling {
+                self.ceiling / peak
+            } else {
+                1.0
+            };
+
+            self.gain = if target < self.gain {
+                target
+            } else {
+                (target * self.release.a0) + (self.gain * self.release.b1)
+            };
+
+            for s in frame.iter_mut() {
+                *s *= self.gain;
+            }
+
+            min_gain = min_gain.min(self.gain);
+        }
+
+        -amp_to_db(min_gain).min(0.0)
+    }
+
+    /// The current amount of gain reduction in decibels (a value `>= 0.0`).
+    pub fn gain_reduction_db(&self) -> f32 {
+        let gain = match &self.lookahead {
+            Some(lookahead) => lookahead.applied_gain,
+            None => self.gain,
+        };
+
+        -amp_to_db(gain).min(0.0)
+    }
+
+    pub fn reset(&mut self) {
+        self.gain = 1.0;
+
+        if let Some(lookahead) = &mut self.lookahead {
+            lookahead.reset();
+        }
+    }
+}
+
+/// The state of a limiter with lookahead.
+///
+/// The target gain of each incoming frame is run through a sliding minimum
+/// over the lookahead window, then through the release filter, and finally
+/// through a moving average over the same window. Every value in the moving
+/// average is at most the target gain of the frame leaving the delay line,
+/// so the attack becomes a smooth ramp without ever overshooting the ceiling.
+#[derive(Debug, Clone, PartialEq)]
+struct Lookahead {
+    /// The length of the delay line in frames.
+    frames: usize,
+    num_channels: usize,
+
+    /// The interleaved delay line.
+    delay: Vec<f32>,
+    /// The target gains of the last `frames + 1` frames.
+    targets: Vec<f32>,
+    /// The smoothed gains of the last `frames + 1` frames.
+    gains: Vec<f32>,
+    gain_sum: f64,
+
+    /// A monotonic queue of the positions of the frames in `targets` which
+    /// are candidates for the sliding minimum, with the smallest at the front.
+    min_queue: Vec<u64>,
+    min_queue_head: usize,
+    min_queue_len: usize,
+
+    /// The total number of frames processed.
+    pos: u64,
+    applied_gain: f32,
+}
+
+impl Lookahead {
+    fn new(frames: usize, num_channels: usize) -> Self {
+        Self {
+            frames,
+            num_channels,
+            delay: vec![0.0; frames * num_channels],
+            targets: vec![1.0; frames + 1],
+            gains: vec![1.0; frames + 1],
+            gain_sum: (frames + 1) as f64,
+            min_queue: vec![0; frames + 1],
+            min_queue_head: 0,
+            min_queue_len: 0,
+            pos: 0,
+            applied_gain: 1.0,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = Self::new(self.frames, self.num_channels);
+    }
+
+    fn process_interleaved(
+        &mut self,
+        data: &mut [f32],
+        ceiling: f32,
+        release: &SmoothingFilterCoeff,
+        gain: &mut f32,
+    ) -> f32 {
+        let window = self.frames + 1;
+        let mut min_gain: f32 = self.applied_gain;
+
+        for frame in data.chunks_exact_mut(self.num_channels) {
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+
+            let target = if peak > ceiling { ceiling / peak } else { 1.0 };
+
+            // Remove the frame that left the window from the sliding minimum.
+            if self.min_queue_len > 0
+                && self.min_queue[self.min_queue_head] + window as u64 <= self.pos
+            {
+                self.min_queue_head = (self.min_queue_head + 1) % window;
+                self.min_queue_len -= 1;
+            }
+
+            let slot = (self.pos % window as u64) as usize;
+            self.targets[slot] = target;
+
+            while self.min_queue_len > 0 {
+                let back = (self.min_queue_head + self.min_queue_len - 1) % window;
+                let back_slot = (self.min_queue[back] % window as u64) as usize;
+
+                if self.targets[back_slot] < target {
+                    break;
+                }
+
+                self.min_queue_len -= 1;
+            }
+            self.min_queue[(self.min_queue_head + self.min_queue_len) % window] = self.pos;
+            self.min_queue_len += 1;
+
+            let front_slot = (self.min_queue[self.min_queue_head] % window as u64) as usize;
+            let window_min = self.targets[front_slot];
+
+            *gain = if window_min < *gain {
+                window_min
+            } else {
+                (window_min * release.a0) + (*gain * release.b1)
+            };
+
+            self.gain_sum += f64::from(*gain) - f64::from(self.gains[slot]);
+            self.gains[slot] = *gain;
+
+            // The oldest frame in the window is the one leaving the delay line.
+            // Clamping to its target guards against rounding errors in the sum.
+            let exit_target = self.targets[(slot + 1) % window];
+            let applied_gain = ((self.gain_sum / window as f64) as f32)
+                .min(exit_target)
+                .min(1.0);
+
+            let delay_start = (self.pos % self.frames as u64) as usize * self.num_channels;
+            let delayed = &mut self.delay[delay_start..delay_start + self.num_channels];
+            for (s, d) in frame.iter_mut().zip(delayed.iter_mut()) {
+                let input = *s;
+                *s = *d * applied_gain;
+                *d = input;
+            }
+
+            self.applied_gain = applied_gain;
+            min_gain = min_gain.min(applied_gain);
+            self.pos += 1;
+        }
+
+        min_gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_never_exceeds_ceiling() {
+        let config = LimiterConfig::default();
+        let mut limiter = Limiter::new(config, 2, NonZeroU32::new(48_000).unwrap());
+        let ceiling = db_to_amp(config.ceiling_db);
+
+        let mut data: Vec<f32> = (0..4_800).map(|i| 4.0 * (i as f32 * 0.05).sin()).collect();
+        let reduction_db = limiter.process_interleaved(&mut data, 2);
+
+        assert!(data.iter().all(|s| s.abs() <= ceiling + 1e-6));
+        assert!(reduction_db > 12.0);
+
+        // Quiet material is eventually left untouched.
+        let mut quiet = vec![0.1; 96_000];
+        limiter.process_interleaved(&mut quiet, 2);
+        assert!(limiter.gain_reduction_db() < 0.01);
+    }
+
+    #[test]
+    fn lookahead_ramps_gain_without_exceeding_ceiling() {
+        let config = LimiterConfig {
+            lookahead_secs: 0.001,
+            ..Default::default()
+        };
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut limiter = Limiter::new(config, 1, sample_rate);
+        let ceiling = db_to_amp(config.ceiling_db);
+        let latency = limiter.latency_frames() as usize;
+        assert_eq!(latency, 48);
+
+        // A quiet signal with a single loud burst.
+        let input: Vec<f32> = (0..2_000)
+            .map(|i| {
+                if (1_000..1_010).contains(&i) {
+                    4.0
+                } else {
+                    0.5
+                }
+            })
+            .collect();
+        let mut data = input.clone();
+        limiter.process_interleaved(&mut data, 1);
+
+        assert!(data.iter().all(|s| s.abs() <= ceiling + 1e-6));
+        assert!(data[..latency].iter().all(|s| *s == 0.0));
+
+        // The gain is ramped down smoothly ahead of the burst instead of
+        // jumping down all at once.
+        let gains: Vec<f32> = (latency..=1_000 + latency)
+            .map(|i| data[i] / input[i - latency])
+            .collect();
+        assert_eq!(gains[0], 1.0);
+        assert!((ceiling / 4.0 - gains[1_000]).abs() < 1e-4);
+        for w in gains.windows(2) {
+            assert!(w[0] - w[1] < 0.02);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Loudness measurement as described in ITU-R BS.1770.
+
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use super::filter::biquad::{BiquadCoeff, BiquadState};
+
+/// The length of the momentary loudness window in seconds.
+pub const MOMENTARY_WINDOW_SECS: f32 = 0.4;
+/// The length of the short-term loudness window in seconds.
+pub const SHORT_TERM_WINDOW_SECS: f32 = 3.0;
+
+/// The number of sub-blocks per second that a [`LoudnessWindow`] is updated
+/// in (every `100ms`).
+const SUB_BLOCKS_PER_SEC: u32 = 10;
+const MOMENTARY_SUB_BLOCKS: usize = 4;
+const SHORT_TERM_SUB_BLOCKS: usize = 30;
+
+/// The coefficients of the two stage K-weighting filter from ITU-R BS.1770,
+/// which approximates how loud each frequency sounds.
+///
+/// The first stage is a high shelf which models the acoustic effect of the
+/// head, and the second stage is a highpass filter (the "RLB" weighting
+/// curve).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KWeightingCoeffs {
+    pub shelf: BiquadCoeff,
+    pub highpass: BiquadCoeff,
+}
+
+impl KWeightingCoeffs {
+    /// Compute the coefficients for the given sample rate.
+    ///
+    /// The standard only lists the coefficients at 48 kHz, so the analog
+    /// prototypes are recomputed here to match them at any sample rate.
+    pub fn new(sample_rate: NonZeroU32) -> Self {
+        let fs = f64::from(sample_rate.get());
+
+        let shelf = {
+            let f0 = 1_681.974_450_955_533;
+            let gain_db = 3.999_843_853_973_347;
+            let q = 0.707_175_236_955_419_6;
+
+            let k = (core::f64::consts::PI * f0 / fs).tan();
+            let vh = 10.0f64.powf(gain_db / 20.0);
+            let vb = vh.powf(0.499_666_774_154_541_6);
+            let a0 = 1.0 + k / q + k * k;
+
+            BiquadCoeff {
+                b0: ((vh + vb * k / q + k * k) / a0) as f32,
+                b1: (2.0 * (k * k - vh) / a0) as f32,
+                b2: ((vh - vb * k / q + k * k) / a0) as f32,
+                a1: (2.0 * (k * k - 1.0) / a0) as f32,
+                a2: ((1.0 - k / q + k * k) / a0) as f32,
+            }
+        };
+
+        let highpass = {
+            let f0 = 38.135_470_876_024_44;
+            let q = 0.500_327_037_323_877_3;
+
+            let k = (core::f64::consts::PI * f0 / fs).tan();
+            let a0 = 1.0 + k / q + k * k;
+
+            BiquadCoeff {
+                b0: 1.0,
+                b1: -2.0,
+                b2: 1.0,
+                a1: (2.0 * (k * k - 1.0) / a0) as f32,
+                a2: ((1.0 - k / q + k * k) / a0) as f32,
+            }
+        };
+
+        Self { shelf, highpass }
+    }
+}
+
+/// The state of the K-weighting filter for one channel.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct KWeightingFilter {
+    shelf: BiquadState,
+    highpass: BiquadState,
+}
+
+impl KWeightingFilter {
+    #[inline]
+    pub fn process(&mut self, s: f32, coeffs: &KWeightingCoeffs) -> f32 {
+        let s = self.shelf.process(s, &coeffs.shelf);
+        self.highpass.process(s, &coeffs.highpass)
+    }
+
+    pub fn reset(&mut self) {
+        self.shelf.reset();
+        self.highpass.reset();
+    }
+}
+
+/// The mean square of one channel over the momentary (`400ms`) and
+/// short-term (`3s`) windows.
+///
+/// Samples are accumulated into sub-blocks of `100ms`, so the windows slide
+/// in steps of `100ms` no matter how many frames are processed at a time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoudnessWindow {
+    sub_block_frames: u32,
+    frames_in_sub_block: u32,
+    sum: f64,
+    /// The sums of squares of the most recent complete sub-blocks.
+    history: [f64; SHORT_TERM_SUB_BLOCKS],
+    /// The index of the most recent complete sub-block in `history`.
+    newest: usize,
+}
+
+impl LoudnessWindow {
+    pub fn new(sample_rate: NonZeroU32) -> Self {
+        Self {
+            sub_block_frames: (sample_rate.get() / SUB_BLOCKS_PER_SEC).max(1),
+            frames_in_sub_block: 0,
+            sum: 0.0,
+            history: [0.0; SHORT_TERM_SUB_BLOCKS],
+            newest: 0,
+        }
+    }
+
+    /// Add a sample (not squared).
+    #[inline]
+    pub fn push(&mut self, s: f32) {
+        self.sum += f64::from(s * s);
+        self.frames_in_sub_block += 1;
+
+        if self.frames_in_sub_block == self.sub_block_frames {
+            self.finish_sub_block();
+        }
+    }
+
+    /// Add the given number of silent samples.
+    pub fn push_silence(&mut self, mut frames: usize) {
+        while frames > 0 {
+            let n = frames.min((self.sub_block_frames - self.frames_in_sub_block) as usize);
+            self.frames_in_sub_block += n as u32;
+            frames -= n;
+
+            if self.frames_in_sub_block == self.sub_block_frames {
+                self.finish_sub_block();
+            }
+        }
+    }
+
+    fn finish_sub_block(&mut self) {
+        self.newest = (self.newest + 1) % SHORT_TERM_SUB_BLOCKS;
+        self.history[self.newest] = self.sum;
+        self.sum = 0.0;
+        self.frames_in_sub_block = 0;
+    }
+
+    fn mean_square(&self, sub_blocks: usize) -> f32 {
+        let sum: f64 = (0..sub_blocks)
+            .map(|i| {
+                self.history[(self.newest + SHORT_TERM_SUB_BLOCKS - i) % SHORT_TERM_SUB_BLOCKS]
+            })
+            .sum();
+
+        (sum / (sub_blocks as f64 * f64::from(self.sub_block_frames))) as f32
+    }
+
+    /// The mean square over the last `400ms`.
+    pub fn momentary_mean_square(&self) -> f32 {
+        self.mean_square(MOMENTARY_SUB_BLOCKS)
+    }
+
+    /// The mean square over the last `3s`.
+    pub fn short_term_mean_square(&self) -> f32 {
+        self.mean_square(SHORT_TERM_SUB_BLOCKS)
+    }
+
+    pub fn reset(&mut self) {
+        self.frames_in_sub_block = 0;
+        self.sum = 0.0;
+        self.history = [0.0; SHORT_TERM_SUB_BLOCKS];
+    }
+}
+
+/// Convert the sum of the mean squares of the K-weighted channels to
+/// loudness in LUFS.
+///
+/// Returns `f32::NEG_INFINITY` for silence.
+#[inline]
+pub fn mean_square_to_lufs(weighted_mean_square: f32) -> f32 {
+    if weighted_mean_square <= 0.0 {
+        f32::NEG_INFINITY
+    } else {
+        -0.691 + 10.0 * weighted_mean_square.log10()
+    }
+}
@@ -60,8 +60,14 @@ impl Mix {
         self.0 * 100.0
     }
 
+    /// Scale the amount of the second (wet) signal by `amount`, where `0.0`
+    /// results in [`Mix::FULLY_FIRST`] and `1.0` leaves the mix unchanged.
+    pub fn scaled(self, amount: f32) -> Self {
+        Self::new(self.0 * amount)
+    }
+
     /// Compute the raw gain values for both inputs.
-    pub fn compute_gains(&self, fade_curve: FadeCurve) -> (f32, f32) {
+    pub fn compute_gains(&self, fade_curve: &FadeCurve) -> (f32, f32) {
         fade_curve.compute_gains_0_to_1(self.0)
     }
 }
@@ -96,6 +102,10 @@ impl Diff for Mix {
             event_queue.push_param(ParamData::F32(self.0), path);
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        path.is_empty().then_some(ParamData::F32(self.0))
+    }
 }
 
 impl Patch for Mix {
@@ -114,10 +124,14 @@ impl Patch for Mix {
 }
 
 /// A DSP helper struct that efficiently mixes two signals together.
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct MixDSP {
     gain_0: SmoothedParam,
     gain_1: SmoothedParam,
+    mix: Mix,
+    fade_curve: FadeCurve,
+    /// `None` until the first call to [`MixDSP::set_global_wet`].
+    global_wet: Option<f32>,
 }
 
 impl MixDSP {
@@ -127,16 +141,51 @@ impl MixDSP {
         config: SmootherConfig,
         sample_rate: NonZeroU32,
     ) -> Self {
-        let (gain_0, gain_1) = mix.compute_gains(fade_curve);
+        let (gain_0, gain_1) = mix.compute_gains(&fade_curve);
 
         Self {
             gain_0: SmoothedParam::new(gain_0, config, sample_rate),
             gain_1: SmoothedParam::new(gain_1, config, sample_rate),
+            mix,
+            fade_curve,
+            global_wet: None,
         }
     }
 
     pub fn set_mix(&mut self, mix: Mix, fade_curve: FadeCurve) {
-        let (gain_0, gain_1) = mix.compute_gains(fade_curve);
+        self.mix = mix;
+        self.fade_curve = fade_curve;
+
+        self.update_gains();
+    }
+
+    /// Scale the mix by a global wet amount in the range `[0.0, 1.0]` (see
+    /// [`ProcInfo::global_wet`]).
+    ///
+    /// This is cheap to call once every process cycle. Changes are smoothed,
+    /// except for the very first call, which takes effect immediately so
+    /// that a newly constructed node doesn't fade in from the wrong mix.
+    ///
+    /// [`ProcInfo::global_wet`]: crate::node::ProcInfo::global_wet
+    pub fn set_global_wet(&mut self, global_wet: f32) {
+        if self.global_wet == Some(global_wet) {
+            return;
+        }
+        let first = self.global_wet.is_none();
+        self.global_wet = Some(global_wet);
+
+        self.update_gains();
+
+        if first {
+            self.reset_to_target();
+        }
+    }
+
+    fn update_gains(&mut self) {
+        let (gain_0, gain_1) = self
+            .mix
+            .scaled(self.global_wet.unwrap_or(1.0))
+            .compute_gains(&self.fade_curve);
 
         self.gain_0.set_value(gain_0);
         self.gain_1.set_value(gain_1);
@@ -148,6 +197,20 @@ impl MixDSP {
         self.gain_1.reset_to_target();
     }
 
+    /// Set the time in seconds of the internal smoothing filter without
+    /// resetting the current gains.
+    pub fn set_smooth_seconds(&mut self, seconds: f32, sample_rate: NonZeroU32) {
+        self.gain_0.set_smooth_seconds(seconds, sample_rate);
+        self.gain_1.set_smooth_seconds(seconds, sample_rate);
+    }
+
+    /// Set the minimum time in seconds of the internal smoothing filter (see
+    /// [`SmootherConfig::min_smooth_seconds`]).
+    pub fn set_min_smooth_seconds(&mut self, seconds: f32, sample_rate: NonZeroU32) {
+        self.gain_0.set_min_smooth_seconds(seconds, sample_rate);
+        self.gain_1.set_min_smooth_seconds(seconds, sample_rate);
+    }
+
     pub fn update_sample_rate(&mut self, sample_rate: NonZeroU32) {
         self.gain_0.update_sample_rate(sample_rate);
         self.gain_1.update_sample_rate(sample_rate);
@@ -272,6 +335,7 @@ impl MixDSP {
     ) {
         if second.len() == 1 {
             self.mix_first_into_second_mono(first[0].as_ref(), second[0].as_mut(), frames);
+            return;
         } else if second.len() == 2 {
             let (second_l, second_r) = second.split_first_mut().unwrap();
             self.mix_first_into_second_stereo(
@@ -281,6 +345,7 @@ impl MixDSP {
                 second_r[0].as_mut(),
                 frames,
             );
+            return;
         }
 
         if self.is_smoothing() {
@@ -1,10 +1,21 @@
 pub mod algo;
 pub mod buffer;
 pub mod coeff_update;
+pub mod control_rate;
+pub mod dc_blocker;
 pub mod declick;
+pub mod delay_line;
 pub mod distance_attenuation;
+pub mod envelope_follower;
 pub mod fade;
 pub mod filter;
+pub mod gain_match;
 pub mod interleave;
+pub mod limiter;
+pub mod loudness;
 pub mod mix;
+pub mod normalize;
+pub mod phase_accumulator;
+pub mod tail_gate;
+pub mod true_peak;
 pub mod volume;
@@ -0,0 +1,178 @@
+use core::{mem::discriminant, num::NonZeroU32};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use super::volume::DEFAULT_AMP_EPSILON;
+use crate::diff::{Diff, Patch};
+
+/// The default length in seconds of the window a [`Normalizer`] measures
+/// the level of a signal over.
+pub const DEFAULT_NORMALIZE_WINDOW_SECS: f32 = 0.3;
+
+/// The largest gain a [`Normalizer`] will apply (about `+24` dB).
+const MAX_GAIN: f32 = 16.0;
+
+/// The level a [`Normalizer`] brings a signal to.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Diff, Patch)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Normalize {
+    /// The signal is left as is.
+    #[default]
+    Off,
+    /// Scale the signal so that its peak is at the given raw amplitude.
+    Peak(f32),
+    /// Scale the signal so that its RMS level is at the given raw amplitude.
+    Rms(f32),
+}
+
+/// Scales a signal to a target peak or RMS level (see [`Normalize`]).
+///
+/// This is useful for making the output of generators with different raw
+/// amplitudes (i.e. a sine wave versus a pulse wave, or white versus pink
+/// noise) play at a predictable level when they are mixed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Normalizer {
+    mode: Normalize,
+    power: f32,
+    peak: f32,
+    gain: f32,
+    measured: bool,
+    window_frames: f32,
+}
+
+impl Normalizer {
+    /// Construct a new normalizer.
+    ///
+    /// * `window_secs` - The length of the window in seconds to measure the
+    ///   level of the signal over.
+    pub fn new(window_secs: f32, sample_rate: NonZeroU32) -> Self {
+        Self {
+            mode: Normalize::Off,
+            power: 0.0,
+            peak: 0.0,
+            gain: 1.0,
+            measured: false,
+            window_frames: (window_secs * sample_rate.get() as f32).max(1.0),
+        }
+    }
+
+    /// Scale a block of samples in place to the level given by `mode`.
+    ///
+    /// The gain is ramped across the block, so it changes without clicks.
+    /// Silent blocks are ignored, so the last gain is kept while nothing is
+    /// playing.
+    pub fn process(&mut self, mode: Normalize, block: &mut [f32]) {
+        if discriminant(&mode) != discriminant(&self.mode) {
+            self.reset();
+            self.mode = mode;
+        }
+
+        let target = match mode {
+            Normalize::Off => return,
+            Normalize::Peak(target) | Normalize::Rms(target) => target.max(0.0),
+        };
+
+        let frames = block.len();
+        if frames == 0 {
+            return;
+        }
+
+        let level = match mode {
+            Normalize::Peak(_) => {
+                let block_peak = block.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+                if block_peak <= DEFAULT_AMP_EPSILON {
+                    None
+                } else {
+                    // Let the peak fall back down over the window, adjusted
+                    // so that the result does not depend on the block size.
+                    let decay = (-(frames as f32) / self.window_frames).exp();
+                    self.peak = block_peak.max(self.peak * decay);
+                    Some(self.peak)
+                }
+            }
+            _ => {
+                let mean_square = block.iter().map(|&s| s * s).sum::<f32>() / frames as f32;
+                if mean_square <= DEFAULT_AMP_EPSILON * DEFAULT_AMP_EPSILON {
+                    None
+                } else {
+                    // Average the power with a one-pole filter, adjusted so
+                    // that the result does not depend on the block size.
+                    let alpha = 1.0 - (-(frames as f32) / self.window_frames).exp();
+                    if self.measured {
+                        self.power += alpha * (mean_square - self.power);
+                    } else {
+                        self.power = mean_square;
+                    }
+                    Some(self.power.sqrt())
+                }
+            }
+        };
+
+        let Some(level) = level else {
+            for s in block.iter_mut() {
+                *s *= self.gain;
+            }
+            return;
+        };
+
+        let new_gain = (target / level).min(MAX_GAIN);
+
+        // Don't ramp up from unity gain on the first measurement.
+        let start_gain = if self.measured { self.gain } else { new_gain };
+        self.measured = true;
+
+        let step = (new_gain - start_gain) / frames as f32;
+        for (i, s) in block.iter_mut().enumerate() {
+            *s *= start_gain + step * (i + 1) as f32;
+        }
+
+        self.gain = new_gain;
+    }
+
+    /// The gain (in raw amplitude) applied to the end of the last block.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Change the length of the window without resetting the measurement.
+    pub fn set_window(&mut self, window_secs: f32, sample_rate: NonZeroU32) {
+        self.window_frames = (window_secs * sample_rate.get() as f32).max(1.0);
+    }
+
+    /// Forget the measured level.
+    pub fn reset(&mut self) {
+        self.power = 0.0;
+        self.peak = 0.0;
+        self.gain = 1.0;
+        self.measured = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peak_mode_scales_sine_to_target_peak() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+
+        for amplitude in [0.05, 0.3, 2.0] {
+            let mut normalizer = Normalizer::new(DEFAULT_NORMALIZE_WINDOW_SECS, sample_rate);
+
+            let mut out: Vec<f32> = (0..48_000)
+                .map(|i| amplitude * (i as f32 * core::f32::consts::TAU * 220.0 / 48_000.0).sin())
+                .collect();
+            for block in out.chunks_mut(256) {
+                normalizer.process(Normalize::Peak(0.5), block);
+            }
+
+            let peak = out[24_000..].iter().fold(0.0f32, |p, s| p.max(s.abs()));
+            assert!(
+                (peak - 0.5).abs() < 0.01,
+                "amplitude {amplitude}: peak {peak}"
+            );
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use super::volume::db_to_amp;
+use crate::diff::{Diff, Patch};
+
+/// The configuration of a [`TailGate`].
+#[derive(Debug, Clone, Copy, PartialEq, Diff, Patch)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TailGateConfig {
+    /// Whether or not the gate is enabled.
+    ///
+    /// By default this is set to `false`.
+    pub enabled: bool,
+
+    /// The level in decibels the input must reach to open the gate.
+    ///
+    /// By default this is set to `-40.0`.
+    pub threshold_db: f32,
+
+    /// The time in seconds the gate stays open after the input last
+    /// reached the threshold.
+    ///
+    /// By default this is set to `0.25` (250ms).
+    pub hold_secs: f32,
+
+    /// The time in seconds it takes the gate to close once the hold time
+    /// has elapsed. Keep this short for an abrupt cut, but not zero, or the
+    /// cut will click.
+    ///
+    /// By default this is set to `0.005` (5ms).
+    pub release_secs: f32,
+}
+
+impl Default for TailGateConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold_db: -40.0,
+            hold_secs: 0.25,
+            release_secs: 0.005,
+        }
+    }
+}
+
+/// A gate for the tail of a reverb or other time-based effect.
+///
+/// The gate is keyed on the dry input rather than on the effect output. It
+/// opens whenever the input reaches the threshold and cuts the wet signal
+/// once the hold time has passed since the input last did, giving the
+/// classic "gated reverb" drum sound.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TailGate {
+    config: TailGateConfig,
+
+    threshold_amp: f32,
+    hold_frames: u32,
+    release_step: f32,
+
+    hold_left: u32,
+    gain: f32,
+}
+
+impl TailGate {
+    /// Construct a new gate. The gate starts out closed.
+    pub fn new(config: TailGateConfig, sample_rate: NonZeroU32) -> Self {
+        let mut new_self = Self {
+            config,
+            threshold_amp: 0.0,
+            hold_frames: 0,
+            release_step: 1.0,
+            hold_left: 0,
+            gain: 0.0,
+        };
+
+        new_self.set_config(config, sample_rate);
+
+        new_self
+    }
+
+    pub fn config(&self) -> &TailGateConfig {
+        &self.config
+    }
+
+    /// Change the configuration without resetting the state of the gate.
+    pub fn set_config(&mut self, config: TailGateConfig, sample_rate: NonZeroU32) {
+        let sample_rate = sample_rate.get() as f32;
+
+        self.config = config;
+        self.threshold_amp = db_to_amp(config.threshold_db);
+        self.hold_frames = (config.hold_secs.max(0.0) * sample_rate).round() as u32;
+        self.release_step = 1.0 / (config.release_secs * sample_rate).max(1.0);
+    }
+
+    /// The current gain of the gate in the range `[0.0, 1.0]`.
+    pub fn gain(&self) -> f32 {
+        self.gain
+    }
+
+    /// Returns `true` if the gate is fully closed.
+    pub fn is_closed(&self) -> bool {
+        self.gain == 0.0
+    }
+
+    /// Advance the gate by one frame, returning the gain to apply to the
+    /// wet signal.
+    ///
+    /// * `key` - The level (absolute value) of the dry input for this frame.
+    #[inline]
+    pub fn process(&mut self, key: f32) -> f32 {
+        if key >= self.threshold_amp {
+            self.hold_left = self.hold_frames;
+            self.gain = 1.0;
+        } else if self.hold_left > 0 {
+            self.hold_left -= 1;
+        } else {
+            self.gain = (self.gain - self.release_step).max(0.0);
+        }
+
+        self.gain
+    }
+
+    /// Gate a block of the wet signal, keyed on the loudest channel of the
+    /// dry input.
+    ///
+    /// If the gate is not enabled, then this does nothing.
+    pub fn process_block<VI: AsRef<[f32]>, VO: AsMut<[f32]>>(
+        &mut self,
+        inputs: &[VI],
+        outputs: &mut [VO],
+        frames: usize,
+    ) {
+        if !self.config.enabled {
+            return;
+        }
+
+        for frame in 0..frames {
+            let key = inputs
+                .iter()
+                .fold(0.0f32, |max, ch| max.max(ch.as_ref()[frame].abs()));
+            let gain = self.process(key);
+
+            if gain != 1.0 {
+                for ch in outputs.iter_mut() {
+                    ch.as_mut()[frame] *= gain;
+                }
+            }
+        }
+    }
+
+    /// Close the gate.
+    pub fn reset(&mut self) {
+        self.hold_left = 0;
+        self.gain = 0.0;
+    }
+}
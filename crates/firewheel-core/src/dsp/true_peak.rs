@@ -0,0 +1,107 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The oversampling factor used to measure true peaks.
+pub const TRUE_PEAK_OVERSAMPLING: usize = 4;
+/// The number of taps in each phase of the true peak interpolation filter.
+pub const TRUE_PEAK_TAPS: usize = 12;
+
+/// Measures the true peak of one channel by interpolating `4x` oversampled
+/// values in between the samples.
+///
+/// The true peak is the peak of the reconstructed analog signal, which
+/// can be higher than the peak of the samples themselves.
+#[derive(Debug, Clone, Copy)]
+pub struct TruePeakDetector {
+    /// The most recent samples, newest first.
+    history: [f32; TRUE_PEAK_TAPS],
+}
+
+impl Default for TruePeakDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TruePeakDetector {
+    pub const fn new() -> Self {
+        Self {
+            history: [0.0; TRUE_PEAK_TAPS],
+        }
+    }
+
+    /// Returns the largest magnitude of the oversampled signal in `samples`.
+    pub fn process(&mut self, samples: &[f32], coeffs: &TruePeakCoeffs) -> f32 {
+        samples
+            .iter()
+            .fold(0.0, |peak, &s| peak.max(self.process_sample(s, coeffs)))
+    }
+
+    /// Returns the largest magnitude of the oversampled signal in between
+    /// the previous sample and `s`.
+    #[inline]
+    pub fn process_sample(&mut self, s: f32, coeffs: &TruePeakCoeffs) -> f32 {
+        self.history.copy_within(0..TRUE_PEAK_TAPS - 1, 1);
+        self.history[0] = s;
+
+        let mut peak: f32 = 0.0;
+        for phase in coeffs.0.iter() {
+            let y: f32 = phase
+                .iter()
+                .zip(self.history.iter())
+                .map(|(&c, &x)| c * x)
+                .sum();
+            peak = peak.max(y.abs());
+        }
+
+        peak
+    }
+
+    pub fn reset(&mut self) {
+        self.history = [0.0; TRUE_PEAK_TAPS];
+    }
+}
+
+/// The polyphase coefficients of a windowed-sinc interpolation filter, one
+/// set of taps per oversampled phase.
+#[derive(Debug, Clone, Copy)]
+pub struct TruePeakCoeffs([[f32; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING]);
+
+impl Default for TruePeakCoeffs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TruePeakCoeffs {
+    pub fn new() -> Self {
+        let len = TRUE_PEAK_TAPS * TRUE_PEAK_OVERSAMPLING;
+        // The center of the filter, which lands on an input sample so that
+        // phase `0` reproduces the input exactly.
+        let center = (len / 2) as f32;
+
+        let mut coeffs = [[0.0; TRUE_PEAK_TAPS]; TRUE_PEAK_OVERSAMPLING];
+        for (phase_i, phase) in coeffs.iter_mut().enumerate() {
+            for (tap_i, c) in phase.iter_mut().enumerate() {
+                let n = (tap_i * TRUE_PEAK_OVERSAMPLING + phase_i) as f32;
+                let x = (n - center) / TRUE_PEAK_OVERSAMPLING as f32;
+                let sinc = if x == 0.0 {
+                    1.0
+                } else {
+                    (core::f32::consts::PI * x).sin() / (core::f32::consts::PI * x)
+                };
+                let window = 0.5 - 0.5 * (core::f32::consts::TAU * n / len as f32).cos();
+
+                *c = sinc * window;
+            }
+
+            // Normalize each phase to unity gain at DC.
+            let sum: f32 = phase.iter().sum();
+            for c in phase.iter_mut() {
+                *c /= sum;
+            }
+        }
+
+        Self(coeffs)
+    }
+}
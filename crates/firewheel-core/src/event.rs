@@ -525,6 +525,43 @@ impl<'a> ProcEvents<'a> {
             .into_iter()
             .filter_map(|(e, timestamp)| T::patch_event(&e).map(|patch| (patch, timestamp)))
     }
+
+    /// Drain only the events for which `filter` returns `true`, passing each
+    /// one to `f` in order and leaving the rest in the list.
+    ///
+    /// This lets a node that wraps the processor of another node take its own
+    /// events out before passing the list on.
+    pub fn drain_where(
+        &mut self,
+        mut filter: impl FnMut(&NodeEventType) -> bool,
+        mut f: impl FnMut(NodeEventType),
+    ) {
+        let immediate_event_buffer = &mut *self.immediate_event_buffer;
+        #[cfg(feature = "scheduled_events")]
+        let scheduled_event_arena = &mut *self.scheduled_event_arena;
+
+        self.indices.retain(|index| match *index {
+            ProcEventsIndex::Immediate(i) => {
+                let slot = &mut immediate_event_buffer[i as usize];
+                if filter(&slot.as_ref().unwrap().event) {
+                    f(slot.take().unwrap().event);
+                    false
+                } else {
+                    true
+                }
+            }
+            #[cfg(feature = "scheduled_events")]
+            ProcEventsIndex::Scheduled(i) => {
+                let slot = &mut scheduled_event_arena[i as usize];
+                if filter(&slot.as_ref().unwrap().event.event) {
+                    f(slot.take().unwrap().event.event);
+                    false
+                } else {
+                    true
+                }
+            }
+        });
+    }
 }
 
 /// Used internally by the Firewheel processor.
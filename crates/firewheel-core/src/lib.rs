@@ -37,6 +37,12 @@ pub struct StreamInfo {
     /// The latency of the input to output stream in seconds.
     pub input_to_output_latency_seconds: f64,
     pub declick_frames: NonZeroU32,
+    /// The minimum time in seconds over which a parameter changed by an
+    /// event is ramped, unless the node opted out with
+    /// [`AudioNodeInfo::stepped_params`].
+    ///
+    /// [`AudioNodeInfo::stepped_params`]: crate::node::AudioNodeInfo::stepped_params
+    pub min_param_ramp_seconds: f32,
     /// The identifier of the output audio device (converted to a string).
     pub output_device_id: String,
     /// The identifier of the input audio device (converted to a string).
@@ -54,6 +60,7 @@ impl Default for StreamInfo {
             num_stream_out_channels: 2,
             input_to_output_latency_seconds: 0.0,
             declick_frames: NonZeroU32::MIN,
+            min_param_ramp_seconds: dsp::filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
             output_device_id: String::new(),
             input_device_id: None,
         }
@@ -21,6 +21,7 @@ use crate::{
     clock::{DurationSamples, InstantSamples, InstantSeconds},
     dsp::declick::DeclickValues,
     event::{NodeEvent, NodeEventType, ProcEvents},
+    param::smoother::SmootherConfig,
     StreamInfo,
 };
 
@@ -55,8 +56,10 @@ pub struct AudioNodeInfo {
     debug_name: &'static str,
     channel_config: ChannelConfig,
     call_update_method: bool,
-    custom_state: Option<Box<dyn Any>>,
+    custom_state: Option<Box<dyn Any + Send>>,
     latency_frames: u32,
+    analysis_only: bool,
+    stepped_params: bool,
 }
 
 impl AudioNodeInfo {
@@ -71,6 +74,8 @@ impl AudioNodeInfo {
             call_update_method: false,
             custom_state: None,
             latency_frames: 0,
+            analysis_only: false,
+            stepped_params: false,
         }
     }
 
@@ -113,12 +118,15 @@ impl AudioNodeInfo {
         self
     }
 
-    /// Custom `!Send` state that can be stored in the Firewheel context and accessed
+    /// Custom state that can be stored in the Firewheel context and accessed
     /// by the user.
     ///
     /// The user accesses this state via `FirewheelCtx::node_state` and
     /// `FirewheelCtx::node_state_mut`.
-    pub fn custom_state<T: 'static>(mut self, custom_state: T) -> Self {
+    ///
+    /// The state must be `Send` so that a node can be constructed on another
+    /// thread (see `PrewarmedNode`).
+    pub fn custom_state<T: Send + 'static>(mut self, custom_state: T) -> Self {
         self.custom_state = Some(Box::new(custom_state));
         self
     }
@@ -130,6 +138,30 @@ impl AudioNodeInfo {
         self.latency_frames = latency_frames;
         self
     }
+
+    /// Set to `true` if this node is purely an analysis tap (i.e. a meter,
+    /// scope, or detector) that never modifies the audio passing through it.
+    ///
+    /// The Firewheel context will pass the inputs of an analysis-only node
+    /// straight through to its outputs (and leave any extra outputs silent),
+    /// regardless of what the node writes to its output buffers or what
+    /// [`ProcessStatus`] it returns.
+    ///
+    /// By default this is set to `false`.
+    pub const fn analysis_only(mut self, analysis_only: bool) -> Self {
+        self.analysis_only = analysis_only;
+        self
+    }
+
+    /// Set to `true` if the parameters of this node are meant to change in
+    /// steps (i.e. the bit depth of a bitcrusher), opting this node out of
+    /// the minimum parameter ramp time ([`StreamInfo::min_param_ramp_seconds`]).
+    ///
+    /// By default this is set to `false`.
+    pub const fn stepped_params(mut self, stepped_params: bool) -> Self {
+        self.stepped_params = stepped_params;
+        self
+    }
 }
 
 impl Default for AudioNodeInfo {
@@ -146,6 +178,8 @@ impl From<AudioNodeInfo> for AudioNodeInfoInner {
             call_update_method: value.call_update_method,
             custom_state: value.custom_state,
             latency_frames: value.latency_frames,
+            analysis_only: value.analysis_only,
+            stepped_params: value.stepped_params,
         }
     }
 }
@@ -156,8 +190,10 @@ pub struct AudioNodeInfoInner {
     pub debug_name: &'static str,
     pub channel_config: ChannelConfig,
     pub call_update_method: bool,
-    pub custom_state: Option<Box<dyn Any>>,
+    pub custom_state: Option<Box<dyn Any + Send>>,
     pub latency_frames: u32,
+    pub analysis_only: bool,
+    pub stepped_params: bool,
 }
 
 /// A trait representing a node in a Firewheel audio graph.
@@ -249,22 +285,58 @@ pub struct ConstructProcessorContext<'a> {
     pub node_id: NodeID,
     /// Information about the running audio stream.
     pub stream_info: &'a StreamInfo,
-    custom_state: &'a mut Option<Box<dyn Any>>,
+    /// If the context is running in deterministic mode, then this is a
+    /// seed unique to this node. Nodes that generate random values should
+    /// use this seed in place of any other source of randomness so that
+    /// renders are reproducible.
+    ///
+    /// This is `None` if the context is not running in deterministic mode.
+    pub deterministic_seed: Option<u64>,
+    /// The minimum time in seconds over which a parameter changed by an
+    /// event should be ramped.
+    ///
+    /// This is [`StreamInfo::min_param_ramp_seconds`], or `0.0` if this node
+    /// opted out with [`AudioNodeInfo::stepped_params`].
+    pub min_param_ramp_seconds: f32,
+    custom_state: &'a mut Option<Box<dyn Any + Send>>,
 }
 
 impl<'a> ConstructProcessorContext<'a> {
     pub fn new(
         node_id: NodeID,
         stream_info: &'a StreamInfo,
-        custom_state: &'a mut Option<Box<dyn Any>>,
+        deterministic_seed: Option<u64>,
+        stepped_params: bool,
+        custom_state: &'a mut Option<Box<dyn Any + Send>>,
     ) -> Self {
         Self {
             node_id,
             stream_info,
+            deterministic_seed,
+            min_param_ramp_seconds: if stepped_params {
+                0.0
+            } else {
+                stream_info.min_param_ramp_seconds
+            },
             custom_state,
         }
     }
 
+    /// Apply the minimum parameter ramp time
+    /// ([`ConstructProcessorContext::min_param_ramp_seconds`]) to the given
+    /// smoother configuration.
+    ///
+    /// Nodes should pass the configuration of every [`SmoothedParam`] through
+    /// this method, so that parameter changes never jump instantly.
+    ///
+    /// [`SmoothedParam`]: crate::param::smoother::SmoothedParam
+    pub fn smoother_config(&self, config: SmootherConfig) -> SmootherConfig {
+        SmootherConfig {
+            min_smooth_seconds: config.min_smooth_seconds.max(self.min_param_ramp_seconds),
+            ..config
+        }
+    }
+
     /// Get an immutable reference to the custom state that was created in
     /// [`AudioNodeInfo::custom_state`].
     pub fn custom_state<T: 'static>(&self) -> Option<&T> {
@@ -289,7 +361,8 @@ pub struct UpdateContext<'a> {
     /// Information about the running audio stream. If no audio stream is running,
     /// then this will be `None`.
     pub stream_info: Option<&'a StreamInfo>,
-    custom_state: &'a mut Option<Box<dyn Any>>,
+    custom_state: &'a mut Option<Box<dyn Any + Send>>,
+    latency_frames: &'a mut u32,
     event_queue: &'a mut Vec<NodeEvent>,
 }
 
@@ -297,17 +370,36 @@ impl<'a> UpdateContext<'a> {
     pub fn new(
         node_id: NodeID,
         stream_info: Option<&'a StreamInfo>,
-        custom_state: &'a mut Option<Box<dyn Any>>,
+        custom_state: &'a mut Option<Box<dyn Any + Send>>,
+        latency_frames: &'a mut u32,
         event_queue: &'a mut Vec<NodeEvent>,
     ) -> Self {
         Self {
             node_id,
             stream_info,
             custom_state,
+            latency_frames,
             event_queue,
         }
     }
 
+    /// The latency of this node in frames (samples in a single channel of
+    /// audio), as last reported with [`AudioNodeInfo::latency_frames`] or
+    /// [`UpdateContext::set_latency_frames`].
+    pub fn latency_frames(&self) -> u32 {
+        *self.latency_frames
+    }
+
+    /// Report a new latency for this node in frames (samples in a single
+    /// channel of audio).
+    ///
+    /// Use this when the latency of this node depends on its parameters (i.e.
+    /// the lookahead of a limiter). If the latency changed, then the Firewheel
+    /// context will recompile the audio graph with the new latency.
+    pub fn set_latency_frames(&mut self, latency_frames: u32) {
+        *self.latency_frames = latency_frames;
+    }
+
     /// Queue an event to send to this node's processor counterpart.
     pub fn queue_event(&mut self, event: NodeEventType) {
         self.event_queue.push(NodeEvent {
@@ -383,6 +475,17 @@ pub trait DynAudioNode {
     fn update(&mut self, cx: UpdateContext) {
         let _ = cx;
     }
+
+    /// Get this node as [`Any`], so that it can be downcast to its concrete
+    /// type (i.e. to read the configuration of a [`Constructor`]).
+    ///
+    /// Returns `None` by default.
+    fn as_any(&self) -> Option<&dyn Any>
+    where
+        Self: 'static,
+    {
+        None
+    }
 }
 
 /// Pairs constructors with their configurations.
@@ -402,6 +505,18 @@ impl<T: AudioNode> Constructor<T, T::Configuration> {
     }
 }
 
+impl<T, C> Constructor<T, C> {
+    /// The node this was constructed with.
+    pub fn constructor(&self) -> &T {
+        &self.constructor
+    }
+
+    /// The configuration of the node.
+    pub fn configuration(&self) -> &C {
+        &self.configuration
+    }
+}
+
 impl<T: AudioNode> DynAudioNode for Constructor<T, T::Configuration> {
     fn info(&self) -> AudioNodeInfo {
         self.constructor.info(&self.configuration)
@@ -417,6 +532,13 @@ impl<T: AudioNode> DynAudioNode for Constructor<T, T::Configuration> {
     fn update(&mut self, cx: UpdateContext) {
         self.constructor.update(&self.configuration, cx);
     }
+
+    fn as_any(&self) -> Option<&dyn Any>
+    where
+        Self: 'static,
+    {
+        Some(self)
+    }
 }
 
 /// The trait describing the realtime processor counterpart to an
@@ -455,6 +577,17 @@ pub trait AudioNodeProcessor: 'static + Send {
         let _ = stream_info;
         let _ = context;
     }
+
+    /// Called once the output of this node has been silent for a number of
+    /// consecutive processing blocks (configured in the Firewheel context).
+    ///
+    /// Nodes with recursive state (feedback delay lines, reverb tails, etc.)
+    /// can override this to reset that state to exact zero. This prevents
+    /// denormal numbers from lingering in the node's internal buffers while
+    /// the graph is idle, which can waste CPU and power.
+    ///
+    /// This is called on the audio thread, so it must be realtime-safe.
+    fn flush_idle_state(&mut self) {}
 }
 
 impl AudioNodeProcessor for Box<dyn AudioNodeProcessor> {
@@ -473,6 +606,9 @@ impl AudioNodeProcessor for Box<dyn AudioNodeProcessor> {
     fn stream_stopped(&mut self, context: &mut ProcStreamCtx) {
         self.as_mut().stream_stopped(context)
     }
+    fn flush_idle_state(&mut self) {
+        self.as_mut().flush_idle_state()
+    }
 }
 
 pub struct ProcStreamCtx<'a> {
@@ -648,6 +784,16 @@ pub struct ProcInfo {
     /// or if the current transport is currently paused.
     #[cfg(feature = "musical_transport")]
     pub transport_info: Option<TransportInfo>,
+
+    /// A global amount in the range `[0.0, 1.0]` that effect nodes with a
+    /// dry/wet `mix` parameter scale their local mix by, so that all effects
+    /// can be pulled back at once (i.e. `0.0` means every such node passes
+    /// the dry signal through).
+    ///
+    /// This is `0.0` for a node whose wet path has been bypassed.
+    ///
+    /// By default this is `1.0`.
+    pub global_wet: f32,
 }
 
 impl ProcInfo {
@@ -821,6 +967,15 @@ pub enum ProcessStatus {
     /// their corresponding output buffers for you as efficiently
     /// as possible.
     Bypass,
+    /// No output buffers were modified, and the outputs are identical
+    /// to the inputs. This is like [`ProcessStatus::Bypass`], except
+    /// that when the number of input and output channels match, the
+    /// engine may hand each input buffer over to its corresponding
+    /// output instead of copying it (zero-copy).
+    ///
+    /// The engine falls back to copying whenever the input buffer is
+    /// still needed by another node.
+    PassThrough,
     /// All output buffers were filled with data.
     ///
     /// WARNING: The node must fill all audio audio output buffers
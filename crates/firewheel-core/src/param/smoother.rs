@@ -21,6 +21,30 @@ pub struct SmootherConfig {
     ///
     /// By default this is set to `0.00001`.
     pub settle_epsilon: f32,
+    /// The minimum amount of smoothing in seconds. Both
+    /// [`SmootherConfig::smooth_seconds`] and any later value given to
+    /// [`SmoothedParam::set_smooth_seconds`] are raised to at least this
+    /// value.
+    ///
+    /// Audio nodes usually set this with
+    /// [`ConstructProcessorContext::smoother_config`].
+    ///
+    /// By default this is set to `0.0`.
+    ///
+    /// [`ConstructProcessorContext::smoother_config`]: crate::node::ConstructProcessorContext::smoother_config
+    pub min_smooth_seconds: f32,
+    /// If `true`, then a tiny amount of noise (on the order of the smallest
+    /// step an `f32` can take at the current value) is added to each step
+    /// of the ramp.
+    ///
+    /// With long smoothing times, each step of the ramp can become smaller
+    /// than the precision of an `f32`, causing the ramp to get stuck just
+    /// short of its target. This is most audible in long fades to very low
+    /// levels. Dithering the ramp lets it continue smoothly down to the
+    /// target.
+    ///
+    /// By default this is set to `false`.
+    pub dither: bool,
 }
 
 impl Default for SmootherConfig {
@@ -28,6 +52,8 @@ impl Default for SmootherConfig {
         Self {
             smooth_seconds: smoothing_filter::DEFAULT_SMOOTH_SECONDS,
             settle_epsilon: smoothing_filter::DEFAULT_SETTLE_EPSILON,
+            min_smooth_seconds: 0.0,
+            dither: false,
         }
     }
 }
@@ -40,13 +66,17 @@ pub struct SmoothedParam {
     filter: SmoothingFilter,
     coeff: SmoothingFilterCoeff,
     smooth_secs: f32,
+    min_smooth_secs: f32,
     settle_epsilon: f32,
+    dither: bool,
+    fpd: u32,
 }
 
 impl SmoothedParam {
     /// Construct a new smoothed f32 parameter with the given configuration.
     pub fn new(value: f32, config: SmootherConfig, sample_rate: NonZeroU32) -> Self {
-        let smooth_secs = config.smooth_seconds.max(0.00001);
+        let min_smooth_secs = config.min_smooth_seconds.max(0.0);
+        let smooth_secs = config.smooth_seconds.max(min_smooth_secs).max(0.00001);
         let settle_epsilon = config.settle_epsilon.max(f32::EPSILON);
 
         let coeff = SmoothingFilterCoeff::new(sample_rate, smooth_secs);
@@ -57,7 +87,10 @@ impl SmoothedParam {
             filter: SmoothingFilter::new(value),
             coeff,
             smooth_secs,
+            min_smooth_secs,
             settle_epsilon,
+            dither: config.dither,
+            fpd: 17,
         }
     }
 
@@ -111,6 +144,10 @@ impl SmoothedParam {
     /// Return the next smoothed value.
     #[inline(always)]
     pub fn next_smoothed(&mut self) -> f32 {
+        if self.dither {
+            return self.next_dithered();
+        }
+
         self.filter
             .process_sample_a(self.target_times_a, self.coeff.b1)
     }
@@ -118,8 +155,14 @@ impl SmoothedParam {
     /// Fill the given buffer with the smoothed values.
     pub fn process_into_buffer(&mut self, buffer: &mut [f32]) {
         if self.is_smoothing() {
-            self.filter
-                .process_into_buffer(buffer, self.target_value, self.coeff);
+            if self.dither {
+                for s in buffer.iter_mut() {
+                    *s = self.next_dithered();
+                }
+            } else {
+                self.filter
+                    .process_into_buffer(buffer, self.target_value, self.coeff);
+            }
 
             self.filter.settle(self.target_value, self.settle_epsilon);
         } else {
@@ -127,14 +170,59 @@ impl SmoothedParam {
         }
     }
 
+    fn next_dithered(&mut self) -> f32 {
+        let z = self.filter.z1;
+        if z == self.target_value {
+            return z;
+        }
+
+        // Triangular noise in the range `(-1.0, 1.0)`, scaled to the distance
+        // to the next representable value. Rounding the step with this noise
+        // added keeps the ramp moving on average, even when the step itself
+        // is smaller than that distance.
+        let r1 = self.next_random();
+        let r2 = self.next_random();
+        let ulp = f32::from_bits(z.abs().to_bits() + 1) - z.abs();
+        let noise = (r1 - r2) * ulp;
+
+        self.filter.z1 = z + ((self.target_value - z) * self.coeff.a0 + noise);
+        self.filter.z1
+    }
+
+    /// Get a random value in the range `[0.0, 1.0)`.
+    #[inline(always)]
+    fn next_random(&mut self) -> f32 {
+        self.fpd ^= self.fpd << 13;
+        self.fpd ^= self.fpd >> 17;
+        self.fpd ^= self.fpd << 5;
+
+        (self.fpd >> 8) as f32 * (1.0 / 16_777_216.0)
+    }
+
+    /// Set the amount of smoothing in seconds.
+    ///
+    /// This is raised to at least [`SmootherConfig::min_smooth_seconds`].
     pub fn set_smooth_seconds(&mut self, seconds: f32, sample_rate: NonZeroU32) {
+        let seconds = seconds.max(self.min_smooth_secs);
         self.coeff = SmoothingFilterCoeff::new(sample_rate, seconds);
+        self.target_times_a = self.target_value * self.coeff.a0;
         self.smooth_secs = seconds;
     }
 
     /// Update the sample rate.
     pub fn update_sample_rate(&mut self, sample_rate: NonZeroU32) {
         self.coeff = SmoothingFilterCoeff::new(sample_rate, self.smooth_secs);
+        self.target_times_a = self.target_value * self.coeff.a0;
+    }
+
+    /// Set the minimum amount of smoothing in seconds (see
+    /// [`SmootherConfig::min_smooth_seconds`]), raising the current amount of
+    /// smoothing if it is lower.
+    pub fn set_min_smooth_seconds(&mut self, seconds: f32, sample_rate: NonZeroU32) {
+        self.min_smooth_secs = seconds.max(0.0);
+        if self.smooth_secs < self.min_smooth_secs {
+            self.set_smooth_seconds(self.min_smooth_secs, sample_rate);
+        }
     }
 }
 
@@ -235,3 +323,63 @@ impl SmoothedParamBuffer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Render a fade from `1.0` down to `floor`, and return the largest
+    /// relative error (per 10 ms block) between the remaining distance to
+    /// the floor and that of an ideal ramp.
+    fn long_fade_error(dither: bool, floor: f32) -> f64 {
+        const SAMPLE_RATE: u32 = 48_000;
+        const SMOOTH_SECONDS: f32 = 2.0;
+        const BLOCK_FRAMES: usize = SAMPLE_RATE as usize / 100;
+
+        let mut gain = SmoothedParam::new(
+            1.0,
+            SmootherConfig {
+                smooth_seconds: SMOOTH_SECONDS,
+                settle_epsilon: f32::EPSILON,
+                dither,
+                ..Default::default()
+            },
+            NonZeroU32::new(SAMPLE_RATE).unwrap(),
+        );
+        gain.set_value(floor);
+
+        let b1 = gain.coeff.b1 as f64;
+        let mut ideal = 1.0f64;
+        let mut max_error = 0.0f64;
+        let mut buffer = [0.0f32; BLOCK_FRAMES];
+
+        // 30 seconds of fading.
+        for _ in 0..3_000 {
+            gain.process_into_buffer(&mut buffer);
+
+            let mut sum = 0.0f64;
+            let mut ideal_sum = 0.0f64;
+            for &s in buffer.iter() {
+                ideal = floor as f64 + (ideal - floor as f64) * b1;
+                sum += s as f64 - floor as f64;
+                ideal_sum += ideal - floor as f64;
+            }
+
+            max_error = max_error.max((sum - ideal_sum).abs() / ideal_sum);
+        }
+
+        max_error
+    }
+
+    #[test]
+    fn dithered_long_fade_has_no_stepping() {
+        // Fade down to -60 dB.
+        let floor = 0.001;
+
+        // Without dither, the ramp gets stuck once each step becomes
+        // smaller than the precision of the gain.
+        assert!(long_fade_error(false, floor) > 1.0);
+
+        assert!(long_fade_error(true, floor) < 0.1);
+    }
+}
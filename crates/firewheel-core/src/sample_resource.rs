@@ -1,7 +1,18 @@
 use core::{num::NonZeroUsize, ops::Range};
 
+use arrayvec::ArrayVec;
 #[cfg(not(feature = "std"))]
-use bevy_platform::prelude::Vec;
+use bevy_platform::prelude::{vec, Vec};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::{channel_config::MAX_CHANNELS, dsp::fade::FadeCurve};
+
+#[cfg(feature = "std")]
+use bevy_platform::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+#[cfg(feature = "std")]
+use core::sync::atomic::fence;
 
 /// Trait returning information about a resource of audio samples
 pub trait SampleResourceInfo: Send + Sync + 'static {
@@ -33,6 +44,72 @@ pub trait SampleResource: SampleResourceInfo {
         buffer_range: Range<usize>,
         start_frame: u64,
     );
+
+    /// Read a single fractional frame from the resource, interpolating
+    /// between the neighboring frames and converting them to `f32` in one
+    /// pass. This is useful for nodes that resample the resource on the fly.
+    ///
+    /// * `out` - The value of each channel at the given frame. If the length
+    ///   of `out` is greater than the number of channels in this resource, then
+    ///   ignore the extra values.
+    /// * `frame` - The position in the resource to read, in samples of a
+    ///   single channel of audio. Positions outside of the resource are read
+    ///   as silence.
+    /// * `interpolation` - How to interpolate between frames.
+    ///
+    /// The default implementation reads the neighboring frames with
+    /// [`SampleResource::fill_buffers`].
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        let num_channels = out.len().min(self.num_channels().get()).min(MAX_CHANNELS);
+        let len_frames = self.len_frames() as i64;
+
+        // The frames from one before the position to two after it.
+        let first = frame.floor() as i64 - 1;
+        let start = first.clamp(0, len_frames);
+        let end = (first + 4).clamp(0, len_frames);
+
+        let mut neighbors = [[0.0; 4]; MAX_CHANNELS];
+        if start < end {
+            let offset = (start - first) as usize;
+            let range = offset..offset + (end - start) as usize;
+            let mut buffers: ArrayVec<&mut [f32], MAX_CHANNELS> = neighbors[..num_channels]
+                .iter_mut()
+                .map(|ch| ch.as_mut_slice())
+                .collect();
+            self.fill_buffers(&mut buffers, range, start as u64);
+        }
+
+        read_frame_with(
+            &mut out[..num_channels],
+            frame,
+            interpolation,
+            len_frames as usize,
+            |ch, i| neighbors[ch][(i as i64 - first) as usize],
+        );
+    }
+}
+
+/// How to interpolate between the frames of a [`SampleResource`] when
+/// reading a fractional frame with [`SampleResource::read_frame_interpolated`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SampleInterpolation {
+    /// Use the nearest frame. This is the cheapest, but adds the most
+    /// aliasing.
+    Nearest,
+    /// Linearly interpolate between the two neighboring frames.
+    #[default]
+    Linear,
+    /// Interpolate with a cubic (Catmull-Rom) spline through the four
+    /// neighboring frames. This is smoother than linear interpolation, at
+    /// the cost of reading twice as many frames.
+    Cubic,
 }
 
 /// A resource of audio samples stored as de-interleaved f32 values.
@@ -45,6 +122,8 @@ pub trait SampleResourceF32: SampleResourceInfo {
 pub struct InterleavedResourceI16 {
     pub data: Vec<i16>,
     pub channels: NonZeroUsize,
+    /// How the samples are scaled to `f32` values.
+    pub scaling: PcmScaling,
 }
 
 impl SampleResourceInfo for InterleavedResourceI16 {
@@ -70,9 +149,20 @@ impl SampleResource for InterleavedResourceI16 {
             start_frame as usize,
             self.channels,
             &self.data,
-            pcm_i16_to_f32,
+            |s| self.scaling.i16_to_f32(s),
         );
     }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_interleaved(out, frame, interpolation, self.channels, &self.data, |s| {
+            self.scaling.i16_to_f32(s)
+        });
+    }
 }
 
 impl core::fmt::Debug for InterleavedResourceI16 {
@@ -118,6 +208,22 @@ impl SampleResource for InterleavedResourceU16 {
             pcm_u16_to_f32,
         );
     }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_interleaved(
+            out,
+            frame,
+            interpolation,
+            self.channels,
+            &self.data,
+            pcm_u16_to_f32,
+        );
+    }
 }
 
 impl core::fmt::Debug for InterleavedResourceU16 {
@@ -163,6 +269,15 @@ impl SampleResource for InterleavedResourceF32 {
             |s| s,
         );
     }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_interleaved(out, frame, interpolation, self.channels, &self.data, |s| s);
+    }
 }
 
 impl core::fmt::Debug for InterleavedResourceF32 {
@@ -176,6 +291,188 @@ impl core::fmt::Debug for InterleavedResourceF32 {
     }
 }
 
+/// A resource of interleaved 24 bit samples.
+///
+/// Each sample is stored unpacked in the low 24 bits of an `i32` (the
+/// upper 8 bits are ignored, so they may either hold the sign extension or
+/// be zero). Use [`pcm_i24_from_le_bytes`] to unpack packed 3 byte data.
+#[derive(Clone)]
+pub struct InterleavedResourceI24 {
+    pub data: Vec<i32>,
+    pub channels: NonZeroUsize,
+    /// How the samples are scaled to `f32` values.
+    pub scaling: PcmScaling,
+}
+
+impl SampleResourceInfo for InterleavedResourceI24 {
+    fn num_channels(&self) -> NonZeroUsize {
+        self.channels
+    }
+
+    fn len_frames(&self) -> u64 {
+        (self.data.len() / self.channels.get()) as u64
+    }
+}
+
+impl SampleResource for InterleavedResourceI24 {
+    fn fill_buffers(
+        &self,
+        buffers: &mut [&mut [f32]],
+        buffer_range: Range<usize>,
+        start_frame: u64,
+    ) {
+        fill_buffers_interleaved(
+            buffers,
+            buffer_range,
+            start_frame as usize,
+            self.channels,
+            &self.data,
+            |s| self.scaling.i24_to_f32(s),
+        );
+    }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_interleaved(out, frame, interpolation, self.channels, &self.data, |s| {
+            self.scaling.i24_to_f32(s)
+        });
+    }
+}
+
+impl core::fmt::Debug for InterleavedResourceI24 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "InterleavedResourceI24 {{ channels: {}, frames: {} }}",
+            self.channels.get(),
+            self.data.len() / self.channels.get(),
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct InterleavedResourceI32 {
+    pub data: Vec<i32>,
+    pub channels: NonZeroUsize,
+    /// How the samples are scaled to `f32` values.
+    pub scaling: PcmScaling,
+}
+
+impl SampleResourceInfo for InterleavedResourceI32 {
+    fn num_channels(&self) -> NonZeroUsize {
+        self.channels
+    }
+
+    fn len_frames(&self) -> u64 {
+        (self.data.len() / self.channels.get()) as u64
+    }
+}
+
+impl SampleResource for InterleavedResourceI32 {
+    fn fill_buffers(
+        &self,
+        buffers: &mut [&mut [f32]],
+        buffer_range: Range<usize>,
+        start_frame: u64,
+    ) {
+        fill_buffers_interleaved(
+            buffers,
+            buffer_range,
+            start_frame as usize,
+            self.channels,
+            &self.data,
+            |s| self.scaling.i32_to_f32(s),
+        );
+    }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_interleaved(out, frame, interpolation, self.channels, &self.data, |s| {
+            self.scaling.i32_to_f32(s)
+        });
+    }
+}
+
+impl core::fmt::Debug for InterleavedResourceI32 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "InterleavedResourceI32 {{ channels: {}, frames: {} }}",
+            self.channels.get(),
+            self.data.len() / self.channels.get(),
+        )
+    }
+}
+
+#[derive(Clone)]
+pub struct InterleavedResourceF64 {
+    pub data: Vec<f64>,
+    pub channels: NonZeroUsize,
+}
+
+impl SampleResourceInfo for InterleavedResourceF64 {
+    fn num_channels(&self) -> NonZeroUsize {
+        self.channels
+    }
+
+    fn len_frames(&self) -> u64 {
+        (self.data.len() / self.channels.get()) as u64
+    }
+}
+
+impl SampleResource for InterleavedResourceF64 {
+    fn fill_buffers(
+        &self,
+        buffers: &mut [&mut [f32]],
+        buffer_range: Range<usize>,
+        start_frame: u64,
+    ) {
+        fill_buffers_interleaved(
+            buffers,
+            buffer_range,
+            start_frame as usize,
+            self.channels,
+            &self.data,
+            pcm_f64_to_f32,
+        );
+    }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_interleaved(
+            out,
+            frame,
+            interpolation,
+            self.channels,
+            &self.data,
+            pcm_f64_to_f32,
+        );
+    }
+}
+
+impl core::fmt::Debug for InterleavedResourceF64 {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(
+            f,
+            "InterleavedResourceF64 {{ channels: {}, frames: {} }}",
+            self.channels.get(),
+            self.data.len() / self.channels.get(),
+        )
+    }
+}
+
 impl SampleResourceInfo for Vec<Vec<i16>> {
     fn num_channels(&self) -> NonZeroUsize {
         NonZeroUsize::new(self.len()).unwrap()
@@ -201,6 +498,15 @@ impl SampleResource for Vec<Vec<i16>> {
             pcm_i16_to_f32,
         );
     }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_deinterleaved(out, frame, interpolation, self.as_slice(), pcm_i16_to_f32);
+    }
 }
 
 impl SampleResourceInfo for Vec<Vec<u16>> {
@@ -228,6 +534,15 @@ impl SampleResource for Vec<Vec<u16>> {
             pcm_u16_to_f32,
         );
     }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_deinterleaved(out, frame, interpolation, self.as_slice(), pcm_u16_to_f32);
+    }
 }
 
 impl SampleResourceInfo for Vec<Vec<f32>> {
@@ -249,6 +564,15 @@ impl SampleResource for Vec<Vec<f32>> {
     ) {
         fill_buffers_deinterleaved_f32(buffers, buffer_range, start_frame as usize, self);
     }
+
+    fn read_frame_interpolated(
+        &self,
+        out: &mut [f32],
+        frame: f64,
+        interpolation: SampleInterpolation,
+    ) {
+        read_frame_deinterleaved(out, frame, interpolation, self.as_slice(), |s| s);
+    }
 }
 
 impl SampleResourceF32 for Vec<Vec<f32>> {
@@ -257,16 +581,295 @@ impl SampleResourceF32 for Vec<Vec<f32>> {
     }
 }
 
+/// Copy a resource into a new de-interleaved `f32` resource with an
+/// equal-power crossfade baked into the end of the loop region.
+///
+/// The last `crossfade_frames` frames of the loop are crossfaded into the
+/// frames leading up to the loop start, so jumping from the end of the loop
+/// back to the start is seamless. A sampler can then loop the returned
+/// resource with a plain linear read, trading memory for CPU.
+///
+/// The length of the crossfade is clamped to the length of the loop and to
+/// the number of frames before the loop start. Frames outside of the loop
+/// are left unchanged.
+///
+/// * `resource` - The resource to copy.
+/// * `loop_range` - The range of frames in the resource to loop.
+/// * `crossfade_frames` - The length of the crossfade in frames.
+pub fn bake_loop_crossfade<R: SampleResource + ?Sized>(
+    resource: &R,
+    loop_range: Range<u64>,
+    crossfade_frames: usize,
+) -> Vec<Vec<f32>> {
+    let len_frames = resource.len_frames() as usize;
+
+    let mut data: Vec<Vec<f32>> = (0..resource.num_channels().get())
+        .map(|_| vec![0.0; len_frames])
+        .collect();
+    {
+        let mut buffers: Vec<&mut [f32]> = data.iter_mut().map(|ch| ch.as_mut_slice()).collect();
+        resource.fill_buffers(&mut buffers, 0..len_frames, 0);
+    }
+
+    let loop_end = (loop_range.end as usize).min(len_frames);
+    let loop_start = (loop_range.start as usize).min(loop_end);
+    let crossfade_frames = crossfade_frames.min(loop_end - loop_start).min(loop_start);
+
+    if crossfade_frames == 0 {
+        return data;
+    }
+
+    let fade_start = loop_end - crossfade_frames;
+    let pre_loop_start = loop_start - crossfade_frames;
+    let step = (crossfade_frames as f32).recip();
+
+    for ch in data.iter_mut() {
+        // When the loop is exactly as long as the crossfade, the region
+        // being written overlaps the region being read, so read from a copy.
+        let pre_loop: Vec<f32> = ch[pre_loop_start..loop_start].to_vec();
+
+        for (i, (s, &pre_s)) in ch[fade_start..loop_end]
+            .iter_mut()
+            .zip(pre_loop.iter())
+            .enumerate()
+        {
+            // Reach the pre-loop audio fully on the last frame so that it
+            // leads directly into the loop start.
+            let (gain_out, gain_in) =
+                FadeCurve::EqualPower3dB.compute_gains_0_to_1((i + 1) as f32 * step);
+
+            *s = (*s * gain_out) + (pre_s * gain_in);
+        }
+    }
+
+    data
+}
+
+/// How signed integer PCM samples are scaled to `f32` values.
+///
+/// The range of a signed integer is not symmetric (i.e. `i16` goes from
+/// `-32768` to `32767`), so no single scale factor maps both ends exactly to
+/// `-1.0` and `1.0`.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PcmScaling {
+    /// Divide by the largest positive value, so that positive full scale
+    /// maps exactly to `1.0`.
+    ///
+    /// The most negative value maps slightly beyond `-1.0` (`i16::MIN` maps
+    /// to `-1.0000305`). See [`pcm_i16_to_f32`].
+    #[default]
+    MaxPositive,
+    /// Divide by the magnitude of the most negative value, so that every
+    /// output is in the range `[-1.0, 1.0)`.
+    ///
+    /// Positive full scale maps slightly below `1.0` (`i16::MAX` maps to
+    /// `0.9999695`). See [`pcm_i16_to_f32_symmetric`].
+    Symmetric,
+    /// The same as [`PcmScaling::MaxPositive`], except that the most
+    /// negative value is clamped to exactly `-1.0`.
+    ///
+    /// See [`pcm_i16_to_f32_clamped`].
+    Clamped,
+}
+
+impl PcmScaling {
+    /// Convert an `i16` sample to an `f32` sample using this scaling.
+    #[inline]
+    pub fn i16_to_f32(&self, s: i16) -> f32 {
+        match self {
+            Self::MaxPositive => pcm_i16_to_f32(s),
+            Self::Symmetric => pcm_i16_to_f32_symmetric(s),
+            Self::Clamped => pcm_i16_to_f32_clamped(s),
+        }
+    }
+
+    /// Convert a 24 bit sample stored in the low 24 bits of an `i32` to an
+    /// `f32` sample using this scaling.
+    #[inline]
+    pub fn i24_to_f32(&self, s: i32) -> f32 {
+        match self {
+            Self::MaxPositive => pcm_i24_to_f32(s),
+            Self::Symmetric => sign_extend_i24(s) as f32 * (1.0 / 8_388_608.0),
+            Self::Clamped => pcm_i24_to_f32(s).max(-1.0),
+        }
+    }
+
+    /// Convert an `i32` sample to an `f32` sample using this scaling.
+    #[inline]
+    pub fn i32_to_f32(&self, s: i32) -> f32 {
+        match self {
+            Self::MaxPositive => pcm_i32_to_f32(s),
+            Self::Symmetric => (f64::from(s) * (1.0 / 2_147_483_648.0)) as f32,
+            Self::Clamped => pcm_i32_to_f32(s).max(-1.0),
+        }
+    }
+}
+
+/// Convert an `i16` sample to an `f32` sample by dividing by `i16::MAX`.
+///
+/// Note that `i16::MIN` maps slightly beyond `-1.0` (`-1.0000305`). Use
+/// [`pcm_i16_to_f32_symmetric`] or [`pcm_i16_to_f32_clamped`] if every
+/// output must be in the range `[-1.0, 1.0]`.
 #[inline]
 pub fn pcm_i16_to_f32(s: i16) -> f32 {
     f32::from(s) * (1.0 / core::i16::MAX as f32)
 }
 
+/// Convert an `i16` sample to an `f32` sample by dividing by the magnitude
+/// of `i16::MIN`.
+///
+/// Every output is in the range `[-1.0, 1.0)`, and `i16::MAX` maps to
+/// `0.9999695`.
+#[inline]
+pub fn pcm_i16_to_f32_symmetric(s: i16) -> f32 {
+    f32::from(s) * (1.0 / 32_768.0)
+}
+
+/// Convert an `i16` sample to an `f32` sample by dividing by `i16::MAX`,
+/// clamping `i16::MIN` to `-1.0`.
+#[inline]
+pub fn pcm_i16_to_f32_clamped(s: i16) -> f32 {
+    pcm_i16_to_f32(s).max(-1.0)
+}
+
 #[inline]
 pub fn pcm_u16_to_f32(s: u16) -> f32 {
     ((f32::from(s)) * (2.0 / core::u16::MAX as f32)) - 1.0
 }
 
+/// The largest value of a 24 bit sample.
+pub const I24_MAX: i32 = (1 << 23) - 1;
+/// The smallest value of a 24 bit sample.
+pub const I24_MIN: i32 = -(1 << 23);
+
+/// Sign extend the low 24 bits of an `i32`.
+#[inline]
+fn sign_extend_i24(s: i32) -> i32 {
+    (s << 8) >> 8
+}
+
+/// Unpack a little endian, packed 3 byte sample into an `i32`.
+#[inline]
+pub fn pcm_i24_from_le_bytes(bytes: [u8; 3]) -> i32 {
+    sign_extend_i24(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+/// Convert a 24 bit sample stored in the low 24 bits of an `i32` to an `f32`
+/// sample by dividing by [`I24_MAX`].
+///
+/// The upper 8 bits are ignored. Like [`pcm_i16_to_f32`], [`I24_MIN`] maps
+/// slightly beyond `-1.0` (`-1.0000001`).
+#[inline]
+pub fn pcm_i24_to_f32(s: i32) -> f32 {
+    sign_extend_i24(s) as f32 * (1.0 / I24_MAX as f32)
+}
+
+/// Convert an `i32` sample to an `f32` sample by dividing by `i32::MAX`.
+///
+/// The result is rounded to the nearest `f32`, so both `i32::MAX` and
+/// `i32::MIN` map exactly to `1.0` and `-1.0`.
+#[inline]
+pub fn pcm_i32_to_f32(s: i32) -> f32 {
+    (f64::from(s) * (1.0 / i32::MAX as f64)) as f32
+}
+
+#[inline]
+pub fn pcm_f64_to_f32(s: f64) -> f32 {
+    s as f32
+}
+
+/// A helper method to read a single interpolated frame, where
+/// `sample(ch, i)` returns the `f32` value of frame `i` in channel `ch`.
+///
+/// `sample` is only called for frames in the range `[0, len_frames)`, and
+/// frames outside of that range are read as silence.
+fn read_frame_with(
+    out: &mut [f32],
+    frame: f64,
+    interpolation: SampleInterpolation,
+    len_frames: usize,
+    sample: impl Fn(usize, usize) -> f32,
+) {
+    let pos = frame.floor();
+    let frac = (frame - pos) as f32;
+    let i0 = pos as i64;
+
+    let get = |ch: usize, i: i64| {
+        if i >= 0 && (i as u64) < len_frames as u64 {
+            sample(ch, i as usize)
+        } else {
+            0.0
+        }
+    };
+
+    for (ch, out_s) in out.iter_mut().enumerate() {
+        *out_s = match interpolation {
+            SampleInterpolation::Nearest => get(ch, if frac < 0.5 { i0 } else { i0 + 1 }),
+            SampleInterpolation::Linear => {
+                let s0 = get(ch, i0);
+                s0 + (get(ch, i0 + 1) - s0) * frac
+            }
+            SampleInterpolation::Cubic => {
+                let s_1 = get(ch, i0 - 1);
+                let s0 = get(ch, i0);
+                let s1 = get(ch, i0 + 1);
+                let s2 = get(ch, i0 + 2);
+
+                let c1 = 0.5 * (s1 - s_1);
+                let c2 = s_1 - 2.5 * s0 + 2.0 * s1 - 0.5 * s2;
+                let c3 = 0.5 * (s2 - s_1) + 1.5 * (s0 - s1);
+
+                ((c3 * frac + c2) * frac + c1) * frac + s0
+            }
+        };
+    }
+}
+
+/// A helper method to read a single interpolated frame from a resource of
+/// interleaved samples.
+pub fn read_frame_interleaved<T: Clone + Copy>(
+    out: &mut [f32],
+    frame: f64,
+    interpolation: SampleInterpolation,
+    channels: NonZeroUsize,
+    data: &[T],
+    convert: impl Fn(T) -> f32,
+) {
+    let channels = channels.get();
+    let num_channels = out.len().min(channels);
+
+    read_frame_with(
+        &mut out[..num_channels],
+        frame,
+        interpolation,
+        data.len() / channels,
+        |ch, i| convert(data[i * channels + ch]),
+    );
+}
+
+/// A helper method to read a single interpolated frame from a resource of
+/// deinterleaved samples.
+pub fn read_frame_deinterleaved<T: Clone + Copy, V: AsRef<[T]>>(
+    out: &mut [f32],
+    frame: f64,
+    interpolation: SampleInterpolation,
+    data: &[V],
+    convert: impl Fn(T) -> f32,
+) {
+    let num_channels = out.len().min(data.len());
+    let len_frames = data.first().map(|ch| ch.as_ref().len()).unwrap_or(0);
+
+    read_frame_with(
+        &mut out[..num_channels],
+        frame,
+        interpolation,
+        len_frames,
+        |ch, i| convert(data[ch].as_ref()[i]),
+    );
+}
+
 /// A helper method to fill buffers from a resource of interleaved samples.
 pub fn fill_buffers_interleaved<T: Clone + Copy>(
     buffers: &mut [&mut [f32]],
@@ -374,3 +977,621 @@ pub fn fill_buffers_deinterleaved_f32<V: AsRef<[f32]>>(
         );
     }
 }
+
+/// A source of audio which is decoded on demand by a
+/// [`StreamingSampleResource`], such as a reader for a compressed audio file.
+///
+/// The decoder is moved to a worker thread, so its methods never run on the
+/// audio thread.
+#[cfg(feature = "std")]
+pub trait StreamDecoder: Send + 'static {
+    /// The number of channels in the decoded audio.
+    fn num_channels(&self) -> NonZeroUsize;
+
+    /// The length of the decoded audio in frames, as reported by the
+    /// container.
+    fn len_frames(&self) -> u64;
+
+    /// Seek so that the next call to [`StreamDecoder::decode`] returns audio
+    /// starting exactly at `frame`.
+    ///
+    /// Returns `false` if seeking failed, in which case the stream is treated
+    /// as having ended until the next seek.
+    fn seek(&mut self, frame: u64) -> bool;
+
+    /// Decode the next chunk of audio and append it to `out` as interleaved
+    /// samples.
+    ///
+    /// Returns the number of frames that were appended, or `0` if the end of
+    /// the stream was reached (or an error occurred).
+    fn decode(&mut self, out: &mut Vec<f32>) -> usize;
+}
+
+/// A [`SampleResource`] which decodes audio from a [`StreamDecoder`] on a
+/// worker thread as it is played, instead of holding the whole sample in
+/// memory. This is useful for long music beds or ambiences.
+///
+/// The worker thread decodes ahead of the most recently read frame into a
+/// fixed-size buffer, which the audio thread reads from without locking.
+/// Reading a frame which is not in the buffer (i.e. after a seek) makes the
+/// worker seek to it, and any frames which are not ready yet are filled
+/// with silence.
+///
+/// This is designed for a single playhead at a time. If several nodes play
+/// the same resource at different positions, they will repeatedly cause the
+/// worker to seek.
+#[cfg(feature = "std")]
+pub struct StreamingSampleResource {
+    shared: bevy_platform::sync::Arc<StreamShared>,
+    num_channels: NonZeroUsize,
+    len_frames: u64,
+}
+
+#[cfg(feature = "std")]
+impl StreamingSampleResource {
+    /// The default size of the decode-ahead buffer in frames (about 1.5
+    /// seconds at 44.1kHz).
+    pub const DEFAULT_BUFFER_FRAMES: usize = 1 << 16;
+
+    /// Start decoding the given stream on a new worker thread.
+    ///
+    /// * `buffer_frames` - The size of the decode-ahead buffer in frames.
+    ///   Larger buffers can better survive a busy worker thread, at the cost
+    ///   of memory.
+    pub fn new(decoder: impl StreamDecoder, buffer_frames: usize) -> Self {
+        let num_channels = decoder.num_channels();
+        let len_frames = decoder.len_frames();
+
+        let shared = bevy_platform::sync::Arc::new(StreamShared::new(
+            num_channels.get(),
+            buffer_frames.max(STREAM_MIN_BUFFER_FRAMES),
+        ));
+
+        let worker_shared = bevy_platform::sync::Arc::clone(&shared);
+        std::thread::spawn(move || stream_worker(decoder, len_frames, &worker_shared));
+
+        Self {
+            shared,
+            num_channels,
+            len_frames,
+        }
+    }
+
+    /// Start decoding from the given frame ahead of time, i.e. before
+    /// starting playback in the middle of the resource.
+    pub fn prefetch(&self, frame: u64) {
+        self.shared.read_frame.store(frame, Ordering::Relaxed);
+    }
+
+    /// The range of frames which are currently decoded and ready to be read.
+    pub fn buffered_frames(&self) -> Range<u64> {
+        let end = self.shared.end.load(Ordering::Acquire);
+        let start = self.shared.start.load(Ordering::Relaxed);
+        start.min(end)..end
+    }
+
+    /// The number of times audio was read before it was decoded, causing
+    /// a gap of silence.
+    pub fn underruns(&self) -> u64 {
+        self.shared.underruns.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for StreamingSampleResource {
+    fn drop(&mut self) {
+        self.shared.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(feature = "std")]
+impl core::fmt::Debug for StreamingSampleResource {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("StreamingSampleResource")
+            .field("num_channels", &self.num_channels)
+            .field("len_frames", &self.len_frames)
+            .field("buffered_frames", &self.buffered_frames())
+            .finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl SampleResourceInfo for StreamingSampleResource {
+    fn num_channels(&self) -> NonZeroUsize {
+        self.num_channels
+    }
+
+    fn len_frames(&self) -> u64 {
+        self.len_frames
+    }
+}
+
+#[cfg(feature = "std")]
+impl SampleResource for StreamingSampleResource {
+    fn fill_buffers(
+        &self,
+        buffers: &mut [&mut [f32]],
+        buffer_range: Range<usize>,
+        start_frame: u64,
+    ) {
+        let frames = buffer_range.len() as u64;
+        self.shared.read_frame.store(start_frame, Ordering::Relaxed);
+
+        let ready = self.shared.read(buffers, buffer_range.clone(), start_frame);
+
+        // Fill whatever was not ready with silence.
+        let num_channels = buffers.len().min(self.num_channels.get());
+        for b in buffers[..num_channels].iter_mut() {
+            let b = &mut b[buffer_range.clone()];
+            b[..(ready.start - start_frame) as usize].fill(0.0);
+            b[(ready.end - start_frame) as usize..].fill(0.0);
+        }
+
+        let expected_end = (start_frame + frames).min(self.len_frames);
+        if start_frame < expected_end && (ready.start > start_frame || ready.end < expected_end) {
+            self.shared.underruns.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The smallest decode-ahead buffer of a [`StreamingSampleResource`].
+#[cfg(feature = "std")]
+const STREAM_MIN_BUFFER_FRAMES: usize = 1024;
+
+/// How long the worker thread of a [`StreamingSampleResource`] sleeps when
+/// its buffer is full.
+#[cfg(feature = "std")]
+const STREAM_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(2);
+
+/// The decode-ahead buffer shared between the worker thread and the audio
+/// thread.
+///
+/// The buffer holds the frames in `start..end`, with frame `f` stored in slot
+/// `f % capacity_frames`. The worker is the only writer. It moves `start`
+/// forward before overwriting old frames, and bumps `seek_seq` around moving
+/// the buffer to a new position, so the audio thread can tell when the
+/// frames it read were overwritten while it was reading them.
+#[cfg(feature = "std")]
+struct StreamShared {
+    /// The decoded samples, interleaved.
+    ring: Box<[crate::atomic_float::AtomicF32]>,
+    num_channels: usize,
+    capacity_frames: usize,
+    /// Odd while the worker is moving the buffer to a new position.
+    seek_seq: AtomicU64,
+    start: AtomicU64,
+    end: AtomicU64,
+    /// The frame most recently read by the audio thread.
+    read_frame: AtomicU64,
+    underruns: AtomicU64,
+    stop: AtomicBool,
+}
+
+#[cfg(feature = "std")]
+impl StreamShared {
+    fn new(num_channels: usize, capacity_frames: usize) -> Self {
+        Self {
+            ring: (0..num_channels * capacity_frames)
+                .map(|_| crate::atomic_float::AtomicF32::new(0.0))
+                .collect(),
+            num_channels,
+            capacity_frames,
+            seek_seq: AtomicU64::new(0),
+            start: AtomicU64::new(0),
+            end: AtomicU64::new(0),
+            read_frame: AtomicU64::new(0),
+            underruns: AtomicU64::new(0),
+            stop: AtomicBool::new(false),
+        }
+    }
+
+    /// Copy the buffered frames in the given range into `buffers`, returning
+    /// the range of frames which were copied intact.
+    fn read(
+        &self,
+        buffers: &mut [&mut [f32]],
+        buffer_range: Range<usize>,
+        start_frame: u64,
+    ) -> Range<u64> {
+        let end_frame = start_frame + buffer_range.len() as u64;
+        let num_channels = buffers.len().min(self.num_channels);
+
+        let seq = self.seek_seq.load(Ordering::Acquire);
+        if seq % 2 == 1 {
+            return start_frame..start_frame;
+        }
+        let end = self.end.load(Ordering::Acquire);
+        let start = self.start.load(Ordering::Relaxed);
+
+        let copy_start = start_frame.max(start).min(end_frame);
+        let copy_end = end_frame.min(end).max(copy_start);
+
+        for frame in copy_start..copy_end {
+            let slot = (frame % self.capacity_frames as u64) as usize * self.num_channels;
+            let i = buffer_range.start + (frame - start_frame) as usize;
+
+            for (ch, b) in buffers[..num_channels].iter_mut().enumerate() {
+                b[i] = self.ring[slot + ch].load(Ordering::Relaxed);
+            }
+        }
+
+        // If any of the samples were overwritten while they were being read,
+        // then the worker moved `start` (or `seek_seq`) before doing so.
+        fence(Ordering::Acquire);
+        if self.seek_seq.load(Ordering::Relaxed) != seq {
+            return start_frame..start_frame;
+        }
+        let start = self.start.load(Ordering::Relaxed);
+
+        copy_start.max(start).min(copy_end)..copy_end
+    }
+
+    /// Move the buffer to begin at `frame`, discarding all buffered frames.
+    fn reset(&self, frame: u64) {
+        self.seek_seq.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::Release);
+        self.start.store(frame, Ordering::Relaxed);
+        self.end.store(frame, Ordering::Relaxed);
+        self.seek_seq.fetch_add(1, Ordering::Release);
+    }
+
+    /// Append interleaved frames to the end of the buffer, discarding the
+    /// oldest frames if it is full.
+    fn write(&self, samples: &[f32]) {
+        let frames = (samples.len() / self.num_channels) as u64;
+        let end = self.end.load(Ordering::Relaxed);
+        let new_end = end + frames;
+
+        let capacity = self.capacity_frames as u64;
+        if new_end > self.start.load(Ordering::Relaxed) + capacity {
+            self.start.store(new_end - capacity, Ordering::Relaxed);
+        }
+        fence(Ordering::Release);
+
+        for (i, frame) in samples.chunks_exact(self.num_channels).enumerate() {
+            let slot = ((end + i as u64) % capacity) as usize * self.num_channels;
+            for (s, &value) in self.ring[slot..slot + self.num_channels].iter().zip(frame) {
+                s.store(value, Ordering::Relaxed);
+            }
+        }
+
+        self.end.store(new_end, Ordering::Release);
+    }
+}
+
+/// Keep the buffer of a [`StreamingSampleResource`] filled ahead of the
+/// frame most recently read by the audio thread.
+#[cfg(feature = "std")]
+fn stream_worker(mut decoder: impl StreamDecoder, len_frames: u64, shared: &StreamShared) {
+    let num_channels = shared.num_channels;
+    let capacity = shared.capacity_frames as u64;
+
+    // Decoded samples which do not fit in the buffer yet.
+    let mut pending: Vec<f32> = Vec::new();
+    let mut pending_offset = 0;
+    let mut ended = false;
+
+    while !shared.stop.load(Ordering::Relaxed) {
+        let read_frame = shared.read_frame.load(Ordering::Relaxed);
+        let start = shared.start.load(Ordering::Relaxed);
+        let end = shared.end.load(Ordering::Relaxed);
+
+        // Seek if the audio thread jumped backward out of the buffer, or so
+        // far forward that decoding up to it would take too long.
+        if read_frame < start || read_frame > end + capacity / 2 {
+            pending.clear();
+            pending_offset = 0;
+            ended = !decoder.seek(read_frame);
+            shared.reset(read_frame);
+            continue;
+        }
+
+        // Keep a quarter of the buffer behind the read frame for small
+        // backward jumps.
+        let write_limit = read_frame + capacity - capacity / 4;
+        let free_frames = write_limit.saturating_sub(end) as usize;
+
+        if pending_offset < pending.len() && free_frames > 0 {
+            let n = ((pending.len() - pending_offset) / num_channels).min(free_frames);
+            shared.write(&pending[pending_offset..pending_offset + n * num_channels]);
+            pending_offset += n * num_channels;
+        } else if pending_offset >= pending.len() && !ended && end < len_frames && free_frames > 0 {
+            pending.clear();
+            pending_offset = 0;
+            ended = decoder.decode(&mut pending) == 0;
+        } else {
+            std::thread::sleep(STREAM_POLL_INTERVAL);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_safe_i16_scaling_stays_in_range() {
+        assert!(pcm_i16_to_f32(i16::MIN) < -1.0);
+        assert_eq!(pcm_i16_to_f32(i16::MAX), 1.0);
+
+        for scaling in [PcmScaling::Symmetric, PcmScaling::Clamped] {
+            assert!((i16::MIN..=i16::MAX)
+                .map(|s| scaling.i16_to_f32(s))
+                .all(|s| (-1.0..=1.0).contains(&s)));
+            assert_eq!(scaling.i16_to_f32(i16::MIN), -1.0);
+            assert_eq!(scaling.i16_to_f32(0), 0.0);
+        }
+
+        assert_eq!(PcmScaling::Clamped.i16_to_f32(i16::MAX), 1.0);
+        assert!(PcmScaling::Symmetric.i16_to_f32(i16::MAX) < 1.0);
+
+        // The resource applies its scaling when filling buffers.
+        let resource = InterleavedResourceI16 {
+            data: vec![i16::MIN, i16::MAX],
+            channels: NonZeroUsize::new(1).unwrap(),
+            scaling: PcmScaling::Clamped,
+        };
+        let mut buf = [0.0; 2];
+        resource.fill_buffers(&mut [&mut buf], 0..2, 0);
+        assert_eq!(buf, [-1.0, 1.0]);
+    }
+
+    #[test]
+    fn wide_pcm_full_scale_round_trips() {
+        // Quantizing full scale and converting back gives full scale again.
+        for x in [1.0f64, -1.0] {
+            assert_eq!(
+                pcm_i24_to_f32((x * I24_MAX as f64).round() as i32),
+                x as f32
+            );
+            assert_eq!(
+                pcm_i32_to_f32((x * i32::MAX as f64).round() as i32),
+                x as f32
+            );
+            assert_eq!(pcm_f64_to_f32(x), x as f32);
+        }
+
+        // The most negative 32 bit value does not clip beyond `-1.0`.
+        assert_eq!(pcm_i32_to_f32(i32::MIN), -1.0);
+        assert_eq!(pcm_i32_to_f32(i32::MAX), 1.0);
+        assert!(pcm_i24_to_f32(I24_MIN) < -1.0);
+        assert_eq!(pcm_i24_to_f32(I24_MAX), 1.0);
+
+        for scaling in [PcmScaling::Symmetric, PcmScaling::Clamped] {
+            assert_eq!(scaling.i24_to_f32(I24_MIN), -1.0);
+            assert_eq!(scaling.i32_to_f32(i32::MIN), -1.0);
+            assert!(scaling.i24_to_f32(I24_MAX) <= 1.0);
+            assert!(scaling.i32_to_f32(i32::MAX) <= 1.0);
+        }
+
+        // 24 bit samples are sign extended whether or not the upper 8 bits
+        // hold the sign.
+        assert_eq!(pcm_i24_to_f32(0x80_0000), pcm_i24_to_f32(I24_MIN));
+        assert_eq!(pcm_i24_to_f32(0xFF_FFFF), pcm_i24_to_f32(-1));
+        assert_eq!(pcm_i24_from_le_bytes([0x00, 0x00, 0x80]), I24_MIN);
+        assert_eq!(pcm_i24_from_le_bytes([0xFF, 0xFF, 0x7F]), I24_MAX);
+        assert_eq!(pcm_i24_from_le_bytes([0xFF, 0xFF, 0xFF]), -1);
+
+        // The resources convert their samples when filling buffers.
+        let stereo = NonZeroUsize::new(2).unwrap();
+        let mut left = [0.0; 2];
+        let mut right = [0.0; 2];
+
+        InterleavedResourceI24 {
+            data: vec![I24_MAX, I24_MIN, 0x80_0000, 0],
+            channels: stereo,
+            scaling: PcmScaling::Clamped,
+        }
+        .fill_buffers(&mut [&mut left, &mut right], 0..2, 0);
+        assert_eq!((left, right), ([1.0, -1.0], [-1.0, 0.0]));
+
+        InterleavedResourceI32 {
+            data: vec![i32::MAX, i32::MIN, 0, i32::MIN],
+            channels: stereo,
+            scaling: PcmScaling::MaxPositive,
+        }
+        .fill_buffers(&mut [&mut left, &mut right], 0..2, 0);
+        assert_eq!((left, right), ([1.0, 0.0], [-1.0, -1.0]));
+
+        InterleavedResourceF64 {
+            data: vec![1.0, -1.0, 0.5, -0.25],
+            channels: stereo,
+        }
+        .fill_buffers(&mut [&mut left, &mut right], 0..2, 0);
+        assert_eq!((left, right), ([1.0, 0.5], [-1.0, -0.25]));
+    }
+
+    /// A resource that only implements `fill_buffers`, to exercise the
+    /// default implementation of `read_frame_interpolated`.
+    struct FillOnly(Vec<Vec<i16>>);
+
+    impl SampleResourceInfo for FillOnly {
+        fn num_channels(&self) -> NonZeroUsize {
+            self.0.num_channels()
+        }
+
+        fn len_frames(&self) -> u64 {
+            self.0.len_frames()
+        }
+    }
+
+    impl SampleResource for FillOnly {
+        fn fill_buffers(
+            &self,
+            buffers: &mut [&mut [f32]],
+            buffer_range: Range<usize>,
+            start_frame: u64,
+        ) {
+            self.0.fill_buffers(buffers, buffer_range, start_frame);
+        }
+    }
+
+    #[test]
+    fn fractional_read_interpolates_midpoint() {
+        let left = [0, 8_192, 16_384, 24_576];
+        let right = [-8_192, 0, 8_192, 16_384];
+        let interleaved = InterleavedResourceI16 {
+            data: left.iter().zip(right).flat_map(|(l, r)| [*l, r]).collect(),
+            channels: NonZeroUsize::new(2).unwrap(),
+            scaling: PcmScaling::MaxPositive,
+        };
+        let deinterleaved = vec![left.to_vec(), right.to_vec()];
+        let fill_only = FillOnly(deinterleaved.clone());
+
+        let resources: [&dyn SampleResource; 3] = [&interleaved, &deinterleaved, &fill_only];
+        for resource in resources {
+            let read = |frame: f64, interpolation| {
+                let mut out = [0.0; 2];
+                resource.read_frame_interpolated(&mut out, frame, interpolation);
+                out
+            };
+
+            // The midpoint between frames 0 and 1.
+            let midpoint = [
+                (pcm_i16_to_f32(left[0]) + pcm_i16_to_f32(left[1])) * 0.5,
+                (pcm_i16_to_f32(right[0]) + pcm_i16_to_f32(right[1])) * 0.5,
+            ];
+            assert_eq!(read(0.5, SampleInterpolation::Linear), midpoint);
+
+            // Whole frames are read exactly.
+            let frame_2 = [pcm_i16_to_f32(left[2]), pcm_i16_to_f32(right[2])];
+            assert_eq!(read(2.0, SampleInterpolation::Linear), frame_2);
+            assert_eq!(read(2.0, SampleInterpolation::Cubic), frame_2);
+            assert_eq!(read(1.6, SampleInterpolation::Nearest), frame_2);
+
+            // The frames form a straight line, so the cubic spline follows it
+            // between the inner frames.
+            let cubic = read(1.5, SampleInterpolation::Cubic);
+            let linear = read(1.5, SampleInterpolation::Linear);
+            assert!((cubic[0] - linear[0]).abs() < 1e-6);
+            assert!((cubic[1] - linear[1]).abs() < 1e-6);
+
+            // Frames past the end are read as silence.
+            assert_eq!(read(4.0, SampleInterpolation::Linear), [0.0; 2]);
+            assert_eq!(
+                read(3.5, SampleInterpolation::Linear),
+                [
+                    pcm_i16_to_f32(left[3]) * 0.5,
+                    pcm_i16_to_f32(right[3]) * 0.5
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn baked_loop_is_continuous() {
+        let sample_rate = 48_000.0;
+        let freq = 220.0;
+        let step = core::f32::consts::TAU * freq / sample_rate;
+        let sine: Vec<f32> = (0..48_000).map(|i| (i as f32 * step).sin()).collect();
+
+        // Loop points that don't line up with the period of the sine wave.
+        let loop_range = 10_000..30_123;
+        let end = loop_range.end as usize;
+        let start = loop_range.start as usize;
+
+        // The largest jump between two neighboring samples of the sine wave.
+        let max_jump = step * 1.01;
+        assert!((sine[end - 1] - sine[start]).abs() > max_jump * 4.0);
+
+        let baked = bake_loop_crossfade(&vec![sine.clone()], loop_range, 1_024);
+        let baked = &baked[0];
+
+        assert_eq!(baked.len(), sine.len());
+        assert!((baked[end - 1] - baked[start]).abs() <= max_jump);
+
+        // The audio is continuous throughout the crossfade.
+        for w in baked[start..end].windows(2) {
+            assert!((w[1] - w[0]).abs() <= max_jump * 2.0);
+        }
+
+        // Audio outside of the crossfade is untouched.
+        assert_eq!(&baked[..end - 1_024], &sine[..end - 1_024]);
+        assert_eq!(&baked[end..], &sine[end..]);
+    }
+
+    /// A stereo stream where each sample holds its own frame index (negated
+    /// in the right channel).
+    #[cfg(feature = "std")]
+    struct CountingDecoder {
+        next_frame: u64,
+        len_frames: u64,
+    }
+
+    #[cfg(feature = "std")]
+    impl StreamDecoder for CountingDecoder {
+        fn num_channels(&self) -> NonZeroUsize {
+            NonZeroUsize::new(2).unwrap()
+        }
+
+        fn len_frames(&self) -> u64 {
+            self.len_frames
+        }
+
+        fn seek(&mut self, frame: u64) -> bool {
+            self.next_frame = frame;
+            true
+        }
+
+        fn decode(&mut self, out: &mut Vec<f32>) -> usize {
+            let end = (self.next_frame + 700).min(self.len_frames);
+            for frame in self.next_frame..end {
+                out.extend_from_slice(&[frame as f32, -(frame as f32)]);
+            }
+
+            let frames = (end - self.next_frame) as usize;
+            self.next_frame = end;
+            frames
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn streaming_resource_reads_correct_frames_after_seeks() {
+        let resource = StreamingSampleResource::new(
+            CountingDecoder {
+                next_frame: 0,
+                len_frames: 1_000_000,
+            },
+            4_096,
+        );
+        assert_eq!(resource.len_frames(), 1_000_000);
+
+        let wait_for = |frames: Range<u64>| {
+            for _ in 0..5_000 {
+                let buffered = resource.buffered_frames();
+                if buffered.start <= frames.start && buffered.end >= frames.end {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            panic!("frames {frames:?} were never buffered");
+        };
+
+        let mut left = [f32::NAN; 512];
+        let mut right = [f32::NAN; 512];
+
+        // Read sequentially, then jump forward and backward.
+        for start_frame in [0, 512, 1_024, 900_000, 900_512, 10_000, 9_800, 999_800] {
+            resource.prefetch(start_frame);
+            wait_for(start_frame..(start_frame + 512).min(1_000_000));
+
+            resource.fill_buffers(&mut [&mut left, &mut right], 0..512, start_frame);
+
+            for i in 0..512 {
+                let frame = start_frame + i as u64;
+                let expected = if frame < 1_000_000 { frame as f32 } else { 0.0 };
+                assert_eq!(left[i], expected, "frame {frame}");
+                assert_eq!(right[i], -expected, "frame {frame}");
+            }
+        }
+
+        // Reading frames which are not decoded yet gives silence instead of
+        // blocking.
+        let underruns = resource.underruns();
+        resource.fill_buffers(&mut [&mut left, &mut right], 0..512, 500_000);
+        if left.contains(&0.0) {
+            assert_eq!(resource.underruns(), underruns + 1);
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Recording live parameter changes as automation.
+
+use core::ops::Range;
+
+use firewheel_core::{
+    clock::InstantSamples,
+    diff::ParamPath,
+    event::{NodeEvent, NodeEventType, ParamData},
+    node::NodeID,
+};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
+
+/// A single recorded change to a parameter.
+#[derive(Debug, Clone)]
+pub struct AutomationPoint {
+    /// The time of the audio clock when the change was made.
+    pub time: InstantSamples,
+    /// The new value of the parameter.
+    pub data: ParamData,
+}
+
+/// The recorded changes to a single parameter of a node, in order of time.
+#[derive(Debug, Clone)]
+pub struct AutomationCurve {
+    node_id: NodeID,
+    path: ParamPath,
+    points: Vec<AutomationPoint>,
+}
+
+impl AutomationCurve {
+    /// The ID of the node this parameter belongs to.
+    pub fn node_id(&self) -> NodeID {
+        self.node_id
+    }
+
+    /// The path of the parameter.
+    pub fn path(&self) -> &ParamPath {
+        &self.path
+    }
+
+    /// All recorded changes, in order of time.
+    pub fn points(&self) -> &[AutomationPoint] {
+        &self.points
+    }
+
+    /// The value the parameter was set to at the given time, or `None` if
+    /// it was not changed before that time.
+    pub fn value_at(&self, time: InstantSamples) -> Option<&ParamData> {
+        let i = self.points.partition_point(|p| p.time <= time);
+        i.checked_sub(1).map(|i| &self.points[i].data)
+    }
+
+    /// The recorded changes that happened within the given range of time.
+    pub fn points_in(&self, range: Range<InstantSamples>) -> &[AutomationPoint] {
+        let start = self.points.partition_point(|p| p.time < range.start);
+        let end = self.points.partition_point(|p| p.time < range.end);
+        &self.points[start..end]
+    }
+}
+
+/// The parameter changes captured by
+/// [`FirewheelCtx::start_param_recording`][crate::FirewheelCtx::start_param_recording],
+/// with one [`AutomationCurve`] for each parameter that was changed.
+#[derive(Default, Debug, Clone)]
+pub struct ParamRecording {
+    curves: Vec<AutomationCurve>,
+}
+
+impl ParamRecording {
+    pub(crate) fn record(
+        &mut self,
+        node_id: NodeID,
+        path: &ParamPath,
+        data: &ParamData,
+        time: InstantSamples,
+    ) {
+        let i = match self
+            .curves
+            .iter()
+            .position(|c| c.node_id == node_id && &c.path == path)
+        {
+            Some(i) => i,
+            None => {
+                self.curves.push(AutomationCurve {
+                    node_id,
+                    path: path.clone(),
+                    points: Vec::new(),
+                });
+                self.curves.len() - 1
+            }
+        };
+
+        let points = &mut self.curves[i].points;
+        // Keep the points sorted in case the clock was read out of order.
+        let insert_at = points.partition_point(|p| p.time <= time);
+        points.insert(
+            insert_at,
+            AutomationPoint {
+                time,
+                data: data.clone(),
+            },
+        );
+    }
+
+    /// Returns `true` if no parameter changes were recorded.
+    pub fn is_empty(&self) -> bool {
+        self.curves.is_empty()
+    }
+
+    /// The curves of every parameter that was changed.
+    pub fn curves(&self) -> &[AutomationCurve] {
+        &self.curves
+    }
+
+    /// The curve of the given parameter, if it was changed.
+    pub fn curve(&self, node_id: NodeID, path: &ParamPath) -> Option<&AutomationCurve> {
+        self.curves
+            .iter()
+            .find(|c| c.node_id == node_id && &c.path == path)
+    }
+
+    /// Replay the changes that happened within the given range of time as
+    /// events, i.e. to queue them for a block being rendered offline.
+    ///
+    /// Changes to the same parameter are returned in order of time.
+    pub fn events_in(&self, range: Range<InstantSamples>) -> impl Iterator<Item = NodeEvent> + '_ {
+        self.curves.iter().flat_map(move |curve| {
+            curve.points_in(range.clone()).iter().map(|point| {
+                NodeEvent::new(
+                    curve.node_id,
+                    NodeEventType::Param {
+                        data: point.data.clone(),
+                        path: curve.path.clone(),
+                    },
+                )
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_nodes::svf::SvfStereoNode;
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    use super::*;
+
+    #[test]
+    fn replayed_param_recording_reproduces_trajectory() {
+        const BLOCK_FRAMES: usize = 256;
+
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let node_id = cx.add_node_with_params(SvfStereoNode::default(), None);
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+
+        // Move a "slider" over a few blocks, noting the value each block was
+        // rendered with.
+        cx.start_param_recording();
+        let mut trajectory = Vec::new();
+        for (i, value) in [200.0f32, 400.0, 800.0, 800.0, 1_600.0]
+            .into_iter()
+            .enumerate()
+        {
+            if i != 3 {
+                cx.set_param(node_id, "cutoff_hz", value).unwrap();
+            }
+            cx.update().unwrap();
+            stream.process_block(BLOCK_FRAMES);
+            trajectory.push(value);
+        }
+        let recording = cx.stop_param_recording().unwrap();
+        cx.set_param(node_id, "cutoff_hz", 20.0f32).unwrap();
+
+        assert_eq!(recording.curves().len(), 1);
+        assert_eq!(recording.curves()[0].points().len(), 4);
+        assert!(recording.curve(node_id, &ParamPath::Single(1)).is_some());
+
+        // Replay the recording block by block into a fresh context.
+        let mut replay_cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let replay_id = replay_cx.add_node_with_params(SvfStereoNode::default(), None);
+        assert_eq!(replay_id, node_id);
+
+        for (i, &value) in trajectory.iter().enumerate() {
+            let start = InstantSamples((i * BLOCK_FRAMES) as i64);
+            let end = InstantSamples(((i + 1) * BLOCK_FRAMES) as i64);
+            for event in recording.events_in(start..end) {
+                replay_cx.queue_event(event);
+            }
+
+            assert!(matches!(
+                replay_cx.get_param(replay_id, "cutoff_hz"),
+                Some(ParamData::F32(v)) if v == value
+            ));
+            assert!(matches!(
+                recording.curves()[0].value_at(start),
+                Some(&ParamData::F32(v)) if v == value
+            ));
+        }
+    }
+}
@@ -8,6 +8,8 @@ use firewheel_core::{node::StreamStatus, StreamInfo};
 
 use crate::processor::FirewheelProcessor;
 
+pub mod dummy_backend;
+
 /// A trait describing an audio backend.
 ///
 /// When an instance is dropped, then it must automatically stop its
@@ -0,0 +1,225 @@
+//! A backend without an audio device, for stepping a context's processor
+//! by hand.
+//!
+//! This is useful for integration tests and offline rendering, where the
+//! whole graph needs to be driven deterministically block-by-block. Start
+//! a context with a [`DummyStream`] that has the desired [`StreamInfo`],
+//! and then call [`DummyStream::process_block`] to render each block.
+
+use core::{cell::RefCell, convert::Infallible, time::Duration};
+
+use alloc::{rc::Rc, vec, vec::Vec};
+use firewheel_core::{node::StreamStatus, StreamInfo};
+
+use crate::{
+    backend::{AudioBackend, BackendProcessInfo},
+    processor::FirewheelProcessor,
+};
+
+/// A "dummy" [`AudioBackend`] with no real audio device, used for driving
+/// a context's processor by hand (see [`DummyStream`]).
+pub struct DummyBackend {
+    stream: DummyStream,
+}
+
+/// The configuration of a [`DummyBackend`] stream, and a handle to its
+/// processor once the stream has been started.
+///
+/// Pass a clone of this to [`FirewheelCtx::start_stream`], and then call
+/// [`DummyStream::process_block`] or [`DummyStream::process`] to step the
+/// graph. Nothing is processed in between calls.
+///
+/// [`FirewheelCtx::start_stream`]: crate::FirewheelCtx::start_stream
+#[derive(Clone, Default)]
+pub struct DummyStream {
+    processor: Rc<RefCell<Option<FirewheelProcessor<DummyBackend>>>>,
+    /// The stream info reported to the context when the stream is started.
+    ///
+    /// Changing this after the stream has started has no effect.
+    pub stream_info: StreamInfo,
+}
+
+impl DummyStream {
+    /// Construct a new stream with the given stream info.
+    pub fn new(stream_info: StreamInfo) -> Self {
+        Self {
+            processor: Rc::default(),
+            stream_info,
+        }
+    }
+
+    /// Returns `true` if a context has started this stream and its
+    /// processor is ready to be stepped.
+    pub fn is_running(&self) -> bool {
+        self.processor.borrow().is_some()
+    }
+
+    /// The processor of this stream, for inspecting its state in tests.
+    #[cfg(test)]
+    pub(crate) fn processor(
+        &self,
+    ) -> core::cell::Ref<'_, Option<FirewheelProcessor<DummyBackend>>> {
+        self.processor.borrow()
+    }
+
+    /// Drop the processor, as if the audio device had stopped the stream
+    /// on its own.
+    #[cfg(test)]
+    pub(crate) fn stop_unexpectedly(&self) {
+        self.processor.borrow_mut().take();
+    }
+
+    /// Process one call worth of interleaved audio data.
+    ///
+    /// The number of frames is `output.len()` divided by the number of
+    /// output channels.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream has not been started by a context.
+    pub fn process(&self, input: &[f32], output: &mut [f32]) {
+        self.process_with_stream_time(input, output, Duration::ZERO);
+    }
+
+    /// Process the given number of frames with silent input, reporting the
+    /// given wall-clock time since the stream started to the processor.
+    #[cfg(test)]
+    pub(crate) fn process_block_at(
+        &self,
+        frames: usize,
+        duration_since_stream_start: Duration,
+    ) -> Vec<f32> {
+        let input = vec![0.0; frames * self.stream_info.num_stream_in_channels as usize];
+        let mut output = vec![0.0; frames * self.stream_info.num_stream_out_channels as usize];
+        self.process_with_stream_time(&input, &mut output, duration_since_stream_start);
+        output
+    }
+
+    fn process_with_stream_time(
+        &self,
+        input: &[f32],
+        output: &mut [f32],
+        duration_since_stream_start: Duration,
+    ) {
+        let num_in_channels = self.stream_info.num_stream_in_channels as usize;
+        let num_out_channels = self.stream_info.num_stream_out_channels as usize;
+
+        self.processor
+            .borrow_mut()
+            .as_mut()
+            .expect("stream was not started")
+            .process_interleaved(
+                input,
+                output,
+                BackendProcessInfo {
+                    num_in_channels,
+                    num_out_channels,
+                    frames: output.len() / num_out_channels,
+                    process_timestamp: (),
+                    duration_since_stream_start,
+                    input_stream_status: StreamStatus::empty(),
+                    output_stream_status: StreamStatus::empty(),
+                    dropped_frames: 0,
+                },
+            );
+    }
+
+    /// Process the given number of frames with silent input, returning the
+    /// interleaved output.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the stream has not been started by a context.
+    pub fn process_block(&self, frames: usize) -> Vec<f32> {
+        let input = vec![0.0; frames * self.stream_info.num_stream_in_channels as usize];
+        let mut output = vec![0.0; frames * self.stream_info.num_stream_out_channels as usize];
+        self.process(&input, &mut output);
+        output
+    }
+}
+
+impl AudioBackend for DummyBackend {
+    type DeviceID = ();
+    type AudioAPI = ();
+    type ExtraInputDeviceInfo = ();
+    type ExtraOutputDeviceInfo = ();
+    type Config = DummyStream;
+    type StartStreamError = Infallible;
+    type StreamError = Infallible;
+    type Instant = ();
+
+    fn start_stream(config: Self::Config) -> Result<(Self, StreamInfo), Self::StartStreamError> {
+        let stream_info = config.stream_info.clone();
+        Ok((Self { stream: config }, stream_info))
+    }
+
+    fn set_processor(&mut self, processor: FirewheelProcessor<Self>) {
+        *self.stream.processor.borrow_mut() = Some(processor);
+    }
+
+    fn poll_status(&mut self) -> Result<(), Self::StreamError> {
+        Ok(())
+    }
+
+    fn delay_from_last_process(&self, _process_timestamp: Self::Instant) -> Option<Duration> {
+        None
+    }
+}
+
+impl Drop for DummyBackend {
+    fn drop(&mut self) {
+        self.stream.processor.borrow_mut().take();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use firewheel_core::dsp::volume::Volume;
+    use firewheel_nodes::{beep_test::BeepTestNode, volume::VolumeNode};
+
+    use crate::{FirewheelConfig, FirewheelCtx};
+
+    use super::*;
+
+    #[test]
+    fn stepping_beep_through_gain_yields_expected_blocks() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let beep = cx.add_node(BeepTestNode::default(), None);
+        let gain = cx.add_node(VolumeNode::from_linear(0.5), None);
+        let graph_out = cx.graph_out_node_id();
+        cx.connect(beep, gain, &[(0, 0)], false).unwrap();
+        cx.connect(gain, graph_out, &[(0, 0)], false).unwrap();
+
+        let stream = DummyStream::new(StreamInfo {
+            sample_rate: NonZeroU32::new(48_000).unwrap(),
+            max_block_frames: NonZeroU32::new(128).unwrap(),
+            ..Default::default()
+        });
+        assert!(!stream.is_running());
+        cx.start_stream(stream.clone()).unwrap();
+        assert!(stream.is_running());
+        assert_eq!(cx.stream_info().unwrap().sample_rate.get(), 48_000);
+
+        // The same sine wave the beep node renders, through both gains.
+        let gain = BeepTestNode::default().volume.amp() * Volume::Linear(0.5).amp();
+        let phasor_inc = 440.0 * (48_000.0f64).recip() as f32;
+        let mut phasor = 0.0f32;
+
+        // Each block spans more than one internal processing block.
+        for _ in 0..4 {
+            let block = stream.process_block(300);
+            assert_eq!(block.len(), 600);
+
+            for frame in block.chunks(2) {
+                let expected = (phasor * core::f32::consts::TAU).sin() * gain;
+                phasor = (phasor + phasor_inc).fract();
+
+                assert!((frame[0] - expected).abs() < 1e-6);
+                assert_eq!(frame[1], 0.0);
+            }
+        }
+    }
+}
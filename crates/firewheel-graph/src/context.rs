@@ -1,16 +1,26 @@
+use bevy_platform::sync::atomic::Ordering;
 use bevy_platform::time::Instant;
 use core::cell::RefCell;
 use core::num::NonZeroU32;
 use core::time::Duration;
 use core::{any::Any, f64};
+use firewheel_core::atomic_float::AtomicF32;
 use firewheel_core::clock::DurationSeconds;
+use firewheel_core::collector::ArcGc;
 use firewheel_core::log::{RealtimeLogger, RealtimeLoggerConfig, RealtimeLoggerMainThread};
 use firewheel_core::node::ProcStore;
 use firewheel_core::{
-    channel_config::{ChannelConfig, ChannelCount},
+    channel_config::{ChannelConfig, ChannelCount, ChannelLayout, MAX_CHANNELS},
     clock::AudioClock,
-    dsp::declick::DeclickValues,
-    event::{NodeEvent, NodeEventType},
+    diff::{Diff, ParamPath, Patch, PatchError, PathBuilder},
+    dsp::{
+        declick::DeclickValues,
+        filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        limiter::Limiter,
+        limiter::LimiterConfig,
+        volume::{db_to_amp_clamped, DEFAULT_DB_EPSILON},
+    },
+    event::{NodeEvent, NodeEventType, ParamData},
     node::{AudioNode, DynAudioNode, NodeID},
     StreamInfo,
 };
@@ -25,16 +35,25 @@ use bevy_platform::prelude::Box;
 #[cfg(not(feature = "std"))]
 use bevy_platform::prelude::Vec;
 
+use crate::automation::ParamRecording;
+use crate::backend::dummy_backend::{DummyBackend, DummyStream};
 use crate::backend::DeviceInfo;
-use crate::error::RemoveNodeError;
-use crate::processor::BufferOutOfSpaceMode;
+use crate::error::{
+    AddProbeError, ApplyPresetError, BounceNodeError, RebuildNodeError, RemoveNodeError,
+    SetParamError,
+};
+use crate::input_activity::{InputActivityConfig, InputActivityEvent};
+use crate::meter::{MasterMeter, MasterMeterProcessor};
+use crate::preset::PresetRegistry;
+use crate::probe::{Probe, ProbePoint};
+use crate::processor::{BufferOutOfSpaceMode, NonFiniteSampleMode};
 use crate::{
     backend::AudioBackend,
     error::{AddEdgeError, StartStreamError, UpdateError},
-    graph::{AudioGraph, Edge, EdgeID, NodeEntry, PortIdx},
+    graph::{AudioGraph, Edge, EdgeID, NodeEntry, NodeOrderHint, PortIdx, PrewarmedNode},
     processor::{
-        ContextToProcessorMsg, FirewheelProcessor, FirewheelProcessorInner, ProcessorToContextMsg,
-        SharedClock,
+        ContextToProcessorMsg, FirewheelProcessor, FirewheelProcessorInner, MasterLimiter,
+        ProcessorToContextMsg, SharedClock, VoiceBudget,
     },
 };
 
@@ -44,7 +63,13 @@ use crate::processor::ClearScheduledEventsEvent;
 use firewheel_core::clock::EventInstant;
 
 #[cfg(feature = "musical_transport")]
-use firewheel_core::clock::TransportState;
+use firewheel_core::clock::{TapTempo, TransportState};
+
+#[cfg(feature = "serde")]
+use crate::{
+    descriptor::{GraphDescriptor, NodeRegistry},
+    error::{DeserializeGraphError, SerializeGraphError},
+};
 
 /// The configuration of a Firewheel context.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -54,7 +79,15 @@ pub struct FirewheelConfig {
     /// The number of input channels in the audio graph.
     pub num_graph_inputs: ChannelCount,
     /// The number of output channels in the audio graph.
+    ///
+    /// This is ignored if [`FirewheelConfig::output_layout`] is set.
     pub num_graph_outputs: ChannelCount,
+    /// The speaker layout of the output of the audio graph (i.e. mono,
+    /// stereo, quad, or 5.1 surround). If set, the graph output node will
+    /// have one input port per channel in this layout.
+    ///
+    /// By default this is set to `None`.
+    pub output_layout: Option<ChannelLayout>,
     /// If `true`, then all outputs will be hard clipped at 0db to help
     /// protect the system's speakers.
     ///
@@ -64,6 +97,64 @@ pub struct FirewheelConfig {
     ///
     /// By default this is set to `false`.
     pub hard_clip_outputs: bool,
+    /// If set, then a limiter is applied to the final output of the audio
+    /// graph after all nodes have been processed, preventing the output from
+    /// exceeding [`LimiterConfig::ceiling_db`].
+    ///
+    /// The current gain reduction can be read with
+    /// [`FirewheelCtx::master_limiter_gain_reduction_db`].
+    ///
+    /// If [`LimiterConfig::lookahead_secs`] is set, then the whole output is
+    /// delayed by the lookahead time, including the output of a previewed
+    /// node (see [`FirewheelCtx::preview_node`]), so every path to the output
+    /// stays aligned. The added latency can be read with
+    /// [`FirewheelCtx::output_latency_frames`].
+    ///
+    /// By default this is set to `None`.
+    pub master_limiter: Option<LimiterConfig>,
+    /// If `true`, then the peak, RMS, and true peak levels of each channel
+    /// of the final output are measured after every processed block, after
+    /// all other output processing.
+    ///
+    /// The levels can be read without blocking through
+    /// [`FirewheelCtx::master_meter`].
+    ///
+    /// By default this is set to `false`.
+    pub master_meter: bool,
+    /// The amount of time in seconds over which the output is faded up from
+    /// silence whenever an audio stream is started.
+    ///
+    /// This avoids a pop when the stream first opens, i.e. if the graph
+    /// starts with a loud signal already playing. Set to `0.0` to disable.
+    ///
+    /// By default this is set to `0.0`.
+    pub soft_start_seconds: f32,
+    /// The longest delay in seconds that can be set on an output channel
+    /// with [`FirewheelCtx::set_output_channel_delay`].
+    ///
+    /// The delay lines for every output channel are allocated up front when
+    /// the stream starts, so this bounds their memory use. The default fits
+    /// speakers up to roughly 34 meters further away than the furthest one.
+    ///
+    /// By default this is set to `0.1` (100ms).
+    pub max_output_channel_delay_seconds: f32,
+    /// The maximum number of voices that may be audible at once. When more
+    /// voices than this are audible, the quietest voices are culled (with the
+    /// oldest voice culled first when several are equally quiet).
+    ///
+    /// Nodes are marked as voices with [`FirewheelCtx::set_node_voice`].
+    ///
+    /// By default this is set to `None`.
+    pub max_voices: Option<u32>,
+    /// The maximum fraction of the available time per process cycle that
+    /// processing the audio graph may take (i.e. `0.5` means half of the
+    /// time). When processing takes longer than this, the quietest voices
+    /// are culled until the time they took covers the excess.
+    ///
+    /// Measuring the processing time requires the `std` feature.
+    ///
+    /// By default this is set to `None`.
+    pub cpu_budget: Option<f32>,
     /// An initial capacity to allocate for the nodes in the audio graph.
     ///
     /// By default this is set to `64`.
@@ -77,6 +168,17 @@ pub struct FirewheelConfig {
     ///
     /// By default this is set to `10.0 / 1_000.0`.
     pub declick_seconds: f32,
+    /// The minimum time in seconds over which a parameter changed by an
+    /// event is ramped, so that a node never jumps to a new value instantly
+    /// and clicks. Nodes whose parameters are meant to change in steps opt
+    /// out with [`AudioNodeInfo::stepped_params`].
+    ///
+    /// Set to `0.0` to let nodes use their own smoothing times as-is.
+    ///
+    /// By default this is set to `15.0 / 1_000.0`.
+    ///
+    /// [`AudioNodeInfo::stepped_params`]: firewheel_core::node::AudioNodeInfo::stepped_params
+    pub min_param_ramp_seconds: f32,
     /// The initial capacity for a group of events.
     ///
     /// By default this is set to `128`.
@@ -98,6 +200,17 @@ pub struct FirewheelConfig {
     ///
     /// By default this is set to `512`.
     pub immediate_event_capacity: usize,
+    /// The maximum number of events that can be queued in the context
+    /// between calls to [`FirewheelCtx::update`]. If this is `None`, then
+    /// the queue grows as needed and no events are ever dropped.
+    ///
+    /// By default this is set to `None`.
+    pub pending_event_capacity: Option<usize>,
+    /// What happens when an event is queued while there are already
+    /// [`FirewheelConfig::pending_event_capacity`] events pending.
+    ///
+    /// By default this is set to [`EventQueueOverflowPolicy::Error`].
+    pub event_queue_overflow_policy: EventQueueOverflowPolicy,
     /// The maximum number of scheduled events (events that have a scheduled
     /// time component) that can be stored at once in the audio thread.
     ///
@@ -125,11 +238,95 @@ pub struct FirewheelConfig {
     ///
     /// By default this is set to `false`.
     pub debug_force_clear_buffers: bool,
+    /// How to handle a node outputting a non-finite sample (`NaN` or
+    /// infinity).
+    ///
+    /// Use [`NonFiniteSampleMode::Panic`] while debugging to find the node
+    /// at fault, and [`NonFiniteSampleMode::Sanitize`] in production to keep
+    /// one misbehaving node from silencing the whole output.
+    ///
+    /// By default this is set to [`NonFiniteSampleMode::Ignore`].
+    pub non_finite_sample_mode: NonFiniteSampleMode,
 
     /// The initial number of slots to allocate for the [`ProcStore`].
     ///
     /// By default this is set to `8`.
     pub proc_store_capacity: usize,
+
+    /// The number of consecutive processing blocks a node's output must be
+    /// silent before [`AudioNodeProcessor::flush_idle_state`] is called on
+    /// it, flushing any recursive state to exact zero.
+    ///
+    /// Set to `None` to disable idle flushing.
+    ///
+    /// By default this is set to `None`.
+    ///
+    /// [`AudioNodeProcessor::flush_idle_state`]: firewheel_core::node::AudioNodeProcessor::flush_idle_state
+    pub idle_flush_blocks: Option<NonZeroU32>,
+
+    /// If `true`, then nodes whose output is not consumed are put to sleep
+    /// to save CPU. A node's output is consumed if it has a path to the
+    /// graph output, to a node with no outputs (i.e. a meter), to a node
+    /// with a probe attached, or to the node being previewed.
+    ///
+    /// A sleeping node is not processed and outputs silence, except in
+    /// blocks where it has events to receive, so it never misses a parameter
+    /// change. It wakes up as soon as it is reconnected. Whether or not a
+    /// node is asleep can be read with [`NodeEntry::asleep`].
+    ///
+    /// Only enable this if no node relies on being processed while it is
+    /// disconnected (i.e. a sampler whose playhead should keep advancing).
+    ///
+    /// By default this is set to `false`.
+    pub sleep_unconsumed_nodes: bool,
+
+    /// If `true`, then [`FirewheelCtx::connect_all`] connects a source node
+    /// with a single output channel to every input channel of the
+    /// destination node, so a mono source feeding a stereo node is heard on
+    /// both channels without an explicit splitter.
+    ///
+    /// If `false`, then the mono source is only connected to the first input
+    /// channel, and the rest are left silent.
+    ///
+    /// By default this is set to `true`.
+    pub fan_out_mono: bool,
+
+    /// If `true`, then a source node (a node with output channels but no
+    /// input channels) added to the graph is automatically connected to the
+    /// graph output node with [`FirewheelCtx::connect_all`], so it is heard
+    /// right away without manual wiring.
+    ///
+    /// By default this is set to `false`.
+    pub auto_connect_sources: bool,
+
+    /// If set, then the context runs in deterministic mode, which is useful
+    /// for regression testing.
+    ///
+    /// In this mode, every node is given a seed derived from this value and
+    /// its node ID (see [`ConstructProcessorContext::deterministic_seed`]),
+    /// and the wall-clock timing information given to nodes is derived from
+    /// the audio clock instead. Rendering an identical graph with identical
+    /// input will then produce identical output across runs.
+    ///
+    /// By default this is set to `None`.
+    ///
+    /// [`ConstructProcessorContext::deterministic_seed`]: firewheel_core::node::ConstructProcessorContext::deterministic_seed
+    pub deterministic_seed: Option<u64>,
+
+    /// If set, then the audio graph is processed in blocks of at most this
+    /// many frames, instead of the block size given by the audio backend.
+    ///
+    /// Each callback from the audio device is divided into blocks of this
+    /// size, and nodes see this value as [`StreamInfo::max_block_frames`].
+    /// A smaller block size lowers the latency of events and modulation at
+    /// the cost of more CPU overhead. A larger block size lowers the CPU
+    /// overhead, but it only has an effect if the audio device delivers
+    /// that many frames in a single callback.
+    ///
+    /// This is clamped to a maximum of `65535`.
+    ///
+    /// By default this is set to `None`.
+    pub max_block_frames: Option<NonZeroU32>,
 }
 
 impl Default for FirewheelConfig {
@@ -137,24 +334,51 @@ impl Default for FirewheelConfig {
         Self {
             num_graph_inputs: ChannelCount::ZERO,
             num_graph_outputs: ChannelCount::STEREO,
+            output_layout: None,
             hard_clip_outputs: false,
+            master_limiter: None,
+            master_meter: false,
+            soft_start_seconds: 0.0,
+            max_output_channel_delay_seconds: 0.1,
+            max_voices: None,
+            cpu_budget: None,
             initial_node_capacity: 128,
             initial_edge_capacity: 256,
             declick_seconds: DeclickValues::DEFAULT_FADE_SECONDS,
+            min_param_ramp_seconds: DEFAULT_SMOOTH_SECONDS,
             initial_event_group_capacity: 128,
             channel_capacity: 64,
             event_queue_capacity: 128,
             immediate_event_capacity: 512,
+            pending_event_capacity: None,
+            event_queue_overflow_policy: EventQueueOverflowPolicy::default(),
             #[cfg(feature = "scheduled_events")]
             scheduled_event_capacity: 512,
             buffer_out_of_space_mode: BufferOutOfSpaceMode::AllocateOnAudioThread,
             logger_config: RealtimeLoggerConfig::default(),
             debug_force_clear_buffers: false,
+            non_finite_sample_mode: NonFiniteSampleMode::Ignore,
             proc_store_capacity: 8,
+            idle_flush_blocks: None,
+            sleep_unconsumed_nodes: false,
+            fan_out_mono: true,
+            auto_connect_sources: false,
+            deterministic_seed: None,
+            max_block_frames: None,
         }
     }
 }
 
+impl FirewheelConfig {
+    /// The number of output channels in the audio graph, taking
+    /// [`FirewheelConfig::output_layout`] into account.
+    pub fn graph_output_channels(&self) -> ChannelCount {
+        self.output_layout
+            .map(|layout| layout.num_channels().get())
+            .unwrap_or(self.num_graph_outputs)
+    }
+}
+
 struct ActiveState<B: AudioBackend> {
     backend_handle: B,
     stream_info: StreamInfo,
@@ -183,6 +407,23 @@ pub struct FirewheelCtx<B: AudioBackend> {
     sample_rate: NonZeroU32,
     sample_rate_recip: f64,
 
+    master_limiter_gain_reduction: ArcGc<AtomicF32>,
+    master_meter: MasterMeter,
+    cpu_load: ArcGc<AtomicF32>,
+    culled_voices: Vec<NodeID>,
+    nodes_fading_out: Vec<NodeID>,
+    active_inputs: Vec<NodeID>,
+    input_activity_events: Vec<InputActivityEvent>,
+    previewed_node: Option<NodeID>,
+    active_scene: Option<NodeID>,
+    global_wet: f32,
+    mono_audition: bool,
+    output_channel_delays_ms: [f32; MAX_CHANNELS],
+    presets: PresetRegistry,
+    #[cfg(feature = "serde")]
+    node_registry: NodeRegistry,
+    param_recording: Option<ParamRecording>,
+
     #[cfg(feature = "musical_transport")]
     transport_state: Box<TransportState>,
     #[cfg(feature = "musical_transport")]
@@ -192,6 +433,10 @@ pub struct FirewheelCtx<B: AudioBackend> {
     event_group_pool: Vec<Vec<NodeEvent>>,
     event_group: Vec<NodeEvent>,
     initial_event_group_capacity: usize,
+    // Whether `event_group` has already been coalesced since it overflowed.
+    event_group_coalesced: bool,
+    num_rejected_events: usize,
+    num_dropped_events: u64,
 
     #[cfg(feature = "scheduled_events")]
     queued_clear_scheduled_events: Vec<ClearScheduledEventsEvent>,
@@ -238,6 +483,22 @@ impl<B: AudioBackend> FirewheelCtx<B> {
             shared_clock_output: RefCell::new(shared_clock_output),
             sample_rate: NonZeroU32::new(44100).unwrap(),
             sample_rate_recip: 44100.0f64.recip(),
+            master_limiter_gain_reduction: ArcGc::new(AtomicF32::new(0.0)),
+            master_meter: MasterMeter::new(),
+            cpu_load: ArcGc::new(AtomicF32::new(0.0)),
+            culled_voices: Vec::new(),
+            nodes_fading_out: Vec::new(),
+            active_inputs: Vec::new(),
+            input_activity_events: Vec::new(),
+            previewed_node: None,
+            active_scene: None,
+            global_wet: 1.0,
+            mono_audition: false,
+            output_channel_delays_ms: [0.0; MAX_CHANNELS],
+            presets: PresetRegistry::default(),
+            #[cfg(feature = "serde")]
+            node_registry: NodeRegistry::default(),
+            param_recording: None,
             #[cfg(feature = "musical_transport")]
             transport_state: Box::new(TransportState::default()),
             #[cfg(feature = "musical_transport")]
@@ -245,6 +506,9 @@ impl<B: AudioBackend> FirewheelCtx<B> {
             event_group_pool,
             event_group: Vec::with_capacity(initial_event_group_capacity),
             initial_event_group_capacity,
+            event_group_coalesced: false,
+            num_rejected_events: 0,
+            num_dropped_events: 0,
             #[cfg(feature = "scheduled_events")]
             queued_clear_scheduled_events: Vec::new(),
             config,
@@ -382,10 +646,15 @@ impl<B: AudioBackend> FirewheelCtx<B> {
             B::start_stream(config).map_err(|e| StartStreamError::BackendError(e))?;
 
         stream_info.sample_rate_recip = (stream_info.sample_rate.get() as f64).recip();
+        if let Some(max_block_frames) = self.config.max_block_frames {
+            stream_info.max_block_frames =
+                NonZeroU32::new(max_block_frames.get().min(u16::MAX as u32)).unwrap();
+        }
         stream_info.declick_frames = NonZeroU32::new(
             (self.config.declick_seconds * stream_info.sample_rate.get() as f32).round() as u32,
         )
         .unwrap_or(NonZeroU32::MIN);
+        stream_info.min_param_ramp_seconds = self.config.min_param_ramp_seconds.max(0.0);
 
         let maybe_processor = self.processor_channel.take();
 
@@ -416,9 +685,30 @@ impl<B: AudioBackend> FirewheelCtx<B> {
                     self.config.event_queue_capacity,
                     &stream_info,
                     self.config.hard_clip_outputs,
+                    self.config.master_limiter.map(|config| MasterLimiter {
+                        limiter: Limiter::new(
+                            config,
+                            stream_info.num_stream_out_channels as usize,
+                            stream_info.sample_rate,
+                        ),
+                        gain_reduction_db: ArcGc::clone(&self.master_limiter_gain_reduction),
+                    }),
+                    self.config
+                        .master_meter
+                        .then(|| MasterMeterProcessor::new(self.master_meter.clone())),
+                    self.config.soft_start_seconds,
+                    self.config.max_output_channel_delay_seconds,
+                    VoiceBudget::new(
+                        self.config.max_voices,
+                        self.config.cpu_budget,
+                        ArcGc::clone(&self.cpu_load),
+                    ),
                     self.config.buffer_out_of_space_mode,
                     logger,
                     self.config.debug_force_clear_buffers,
+                    self.config.non_finite_sample_mode,
+                    self.config.idle_flush_blocks,
+                    self.config.deterministic_seed.is_some(),
                     proc_store,
                 )
             } else {
@@ -453,8 +743,19 @@ impl<B: AudioBackend> FirewheelCtx<B> {
     pub fn stop_stream(&mut self) {
         // When the backend handle is dropped, the backend will automatically
         // stop its stream.
+        self.deactivate_stream();
+    }
+
+    fn deactivate_stream(&mut self) {
         self.active_state = None;
         self.graph.deactivate();
+
+        // Without a running stream the fades can't finish, so remove the
+        // nodes now.
+        for node_id in core::mem::take(&mut self.nodes_fading_out) {
+            self.culled_voices.retain(|id| *id != node_id);
+            let _ = self.graph.remove_node(node_id);
+        }
     }
 
     /// Returns `true` if there is currently a running audio stream.
@@ -631,6 +932,32 @@ impl<B: AudioBackend> FirewheelCtx<B> {
         Ok(())
     }
 
+    /// Register a tap at the current time of the audio clock with the given
+    /// [`TapTempo`] helper, and set the musical transport to the resulting
+    /// tempo.
+    ///
+    /// Returns the new tempo in beats per minute, or `None` if there are not
+    /// enough taps yet to measure one (in which case the transport is left
+    /// unchanged).
+    ///
+    /// If the message channel is full, then this will return an error.
+    #[cfg(feature = "musical_transport")]
+    pub fn tap_tempo(
+        &mut self,
+        tap_tempo: &mut TapTempo,
+    ) -> Result<Option<f64>, UpdateError<B::StreamError>> {
+        let now = self.audio_clock_corrected().seconds;
+
+        let mut transport = (*self.transport_state).clone();
+        let Some(bpm) = tap_tempo.tap_transport(now, &mut transport) else {
+            return Ok(None);
+        };
+
+        self.sync_transport(&transport)?;
+
+        Ok(Some(bpm))
+    }
+
     /// Get the current transport state.
     #[cfg(feature = "musical_transport")]
     pub fn transport_state(&self) -> &TransportState {
@@ -669,6 +996,293 @@ impl<B: AudioBackend> FirewheelCtx<B> {
             .map_err(|(_, e)| e)
     }
 
+    /// Fade the final output of the graph to `target_db` over `duration_ms`
+    /// milliseconds, starting from the current master gain.
+    ///
+    /// This is useful for app-level fade-ins and fade-outs and for scene
+    /// transitions. Use `f32::NEG_INFINITY` to fade to silence and `0.0` to
+    /// fade back to unity gain. The gain is ramped smoothly, so this does not
+    /// click.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn fade_master(
+        &mut self,
+        target_db: f32,
+        duration_ms: f32,
+    ) -> Result<(), UpdateError<B::StreamError>> {
+        self.send_message_to_processor(ContextToProcessorMsg::FadeMaster {
+            target_gain: db_to_amp_clamped(target_db, DEFAULT_DB_EPSILON),
+            duration_secs: duration_ms / 1_000.0,
+        })
+        .map_err(|(_, e)| e)
+    }
+
+    /// The amount of gain reduction in decibels (a value `>= 0.0`) the master
+    /// limiter applied during the last processed block.
+    ///
+    /// Returns `None` if [`FirewheelConfig::master_limiter`] is not set.
+    pub fn master_limiter_gain_reduction_db(&self) -> Option<f32> {
+        self.config
+            .master_limiter
+            .map(|_| self.master_limiter_gain_reduction.load(Ordering::Relaxed))
+    }
+
+    /// The levels of the final output, measured after every processed
+    /// block.
+    ///
+    /// The returned handle can be cloned and read from any thread.
+    ///
+    /// Returns `None` if [`FirewheelConfig::master_meter`] is `false`.
+    pub fn master_meter(&self) -> Option<&MasterMeter> {
+        self.config.master_meter.then_some(&self.master_meter)
+    }
+
+    /// The latency in frames that is added to the output of the audio graph
+    /// after all nodes have been processed (i.e. the lookahead of the master
+    /// limiter).
+    ///
+    /// Returns `None` if no audio stream is running.
+    pub fn output_latency_frames(&self) -> Option<u32> {
+        self.stream_info().map(|stream_info| {
+            self.config
+                .master_limiter
+                .map(|config| config.lookahead_frames(stream_info.sample_rate))
+                .unwrap_or(0)
+        })
+    }
+
+    /// Delay a single physical output channel by `delay_ms` milliseconds.
+    ///
+    /// This is used to time-align speakers placed at different distances
+    /// from the listener (i.e. in an installation), by delaying the closer
+    /// speakers so that sound from every speaker arrives at the same time.
+    /// Each channel is delayed independently at the very end of the output
+    /// stage, after the master limiter.
+    ///
+    /// The delay is clamped to the range
+    /// `[0.0, FirewheelConfig::max_output_channel_delay_seconds]`. Channels
+    /// past the number of output channels of the stream are ignored. The
+    /// delay jumps to the new value without declicking, so this is meant to
+    /// be set while calibrating rather than during playback.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn set_output_channel_delay(
+        &mut self,
+        channel: usize,
+        delay_ms: f32,
+    ) -> Result<(), UpdateError<B::StreamError>> {
+        let max_delay_ms = self.config.max_output_channel_delay_seconds.max(0.0) * 1_000.0;
+        let delay_ms = delay_ms.clamp(0.0, max_delay_ms);
+
+        if channel >= MAX_CHANNELS || self.output_channel_delays_ms[channel] == delay_ms {
+            return Ok(());
+        }
+
+        self.send_message_to_processor(ContextToProcessorMsg::SetOutputChannelDelay {
+            channel,
+            delay_secs: delay_ms / 1_000.0,
+        })
+        .map_err(|(_, e)| e)?;
+        self.output_channel_delays_ms[channel] = delay_ms;
+
+        Ok(())
+    }
+
+    /// The delay in milliseconds of a physical output channel set with
+    /// [`FirewheelCtx::set_output_channel_delay`].
+    pub fn output_channel_delay_ms(&self, channel: usize) -> f32 {
+        self.output_channel_delays_ms
+            .get(channel)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Set the maximum number of voices that may be audible at once. See
+    /// [`FirewheelConfig::max_voices`].
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn set_max_voices(
+        &mut self,
+        max_voices: Option<u32>,
+    ) -> Result<(), UpdateError<B::StreamError>> {
+        self.config.max_voices = max_voices;
+        self.send_voice_budget()
+    }
+
+    /// Set the maximum fraction of the available time per process cycle that
+    /// processing the audio graph may take (i.e. `0.5` means half of the
+    /// time), or `None` to disable the limit. See [`FirewheelConfig::cpu_budget`].
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn set_cpu_budget(
+        &mut self,
+        fraction: Option<f32>,
+    ) -> Result<(), UpdateError<B::StreamError>> {
+        self.config.cpu_budget = fraction.map(|f| f.max(0.0));
+        self.send_voice_budget()
+    }
+
+    fn send_voice_budget(&mut self) -> Result<(), UpdateError<B::StreamError>> {
+        self.send_message_to_processor(ContextToProcessorMsg::SetVoiceBudget {
+            max_voices: self.config.max_voices,
+            cpu_budget: self.config.cpu_budget,
+        })
+        .map_err(|(_, e)| e)
+    }
+
+    /// Audition the output of a single node by sending it directly to the
+    /// graph output, without changing the routing of the graph.
+    ///
+    /// The normal mix is crossfaded with the node's output over `fade_ms`
+    /// milliseconds, so the overall level stays about the same during the
+    /// transition. A mono node is sent to every output channel. Call
+    /// [`FirewheelCtx::stop_preview`] to fade back to the normal mix.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn preview_node(
+        &mut self,
+        node_id: NodeID,
+        fade_ms: f32,
+    ) -> Result<(), UpdateError<B::StreamError>> {
+        self.send_message_to_processor(ContextToProcessorMsg::SetPreview {
+            node_id: Some(node_id),
+            fade_secs: fade_ms / 1_000.0,
+        })
+        .map_err(|(_, e)| e)?;
+        self.previewed_node = Some(node_id);
+        self.graph.set_previewed_node(Some(node_id));
+
+        Ok(())
+    }
+
+    /// Stop auditioning the node set with [`FirewheelCtx::preview_node`],
+    /// fading back to the normal mix over `fade_ms` milliseconds.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn stop_preview(&mut self, fade_ms: f32) -> Result<(), UpdateError<B::StreamError>> {
+        if self.previewed_node.is_none() {
+            return Ok(());
+        }
+
+        self.send_message_to_processor(ContextToProcessorMsg::SetPreview {
+            node_id: None,
+            fade_secs: fade_ms / 1_000.0,
+        })
+        .map_err(|(_, e)| e)?;
+        self.previewed_node = None;
+        self.graph.set_previewed_node(None);
+
+        Ok(())
+    }
+
+    /// The node currently being auditioned with [`FirewheelCtx::preview_node`].
+    pub fn previewed_node(&self) -> Option<NodeID> {
+        self.previewed_node
+    }
+
+    /// Crossfade from the active scene to a new scene over `fade_ms`
+    /// milliseconds (i.e. for a transition between two levels of a game).
+    ///
+    /// A scene is a sub-graph whose audio all passes through a single output
+    /// node (such as a volume node) which is connected to the graph
+    /// output. The outputs of both scenes are faded with an equal power
+    /// curve in lockstep, down to the frame. The nodes in the old scene keep
+    /// being processed while it fades out so that reverb tails and other
+    /// sounds ring out naturally, and afterwards its output node is
+    /// silenced. The old scene can then be removed from the graph.
+    ///
+    /// Call this before the [`FirewheelCtx::update`] that adds the new scene
+    /// to the graph, so that it does not play at full volume before the
+    /// crossfade starts.
+    ///
+    /// Pass `None` to fade the active scene out to silence. If a crossfade
+    /// is already in progress, then the scene that was fading out is cut off.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn crossfade_to_scene(
+        &mut self,
+        scene_out_node: Option<NodeID>,
+        fade_ms: f32,
+    ) -> Result<(), UpdateError<B::StreamError>> {
+        if scene_out_node == self.active_scene {
+            return Ok(());
+        }
+
+        self.send_message_to_processor(ContextToProcessorMsg::CrossfadeScene {
+            node_id: scene_out_node,
+            fade_secs: fade_ms / 1_000.0,
+        })
+        .map_err(|(_, e)| e)?;
+        self.active_scene = scene_out_node;
+
+        Ok(())
+    }
+
+    /// The output node of the scene most recently set with
+    /// [`FirewheelCtx::crossfade_to_scene`].
+    pub fn active_scene(&self) -> Option<NodeID> {
+        self.active_scene
+    }
+
+    /// Set a global wet amount in the range `[0.0, 1.0]` that every effect
+    /// node with a dry/wet `mix` parameter scales its local mix by.
+    ///
+    /// This is useful for quickly comparing the processed and unprocessed
+    /// sound, since all effects can be pulled back at once: `0.0` makes
+    /// every such node pass the dry signal through regardless of its local
+    /// mix, and `1.0` (the default) leaves each local mix unchanged. Changes
+    /// are smoothed by each node, so this does not click.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn set_global_wet(&mut self, global_wet: f32) -> Result<(), UpdateError<B::StreamError>> {
+        let global_wet = global_wet.clamp(0.0, 1.0);
+        if self.global_wet == global_wet {
+            return Ok(());
+        }
+        self.global_wet = global_wet;
+
+        self.send_message_to_processor(ContextToProcessorMsg::SetGlobalWet(global_wet))
+            .map_err(|(_, e)| e)
+    }
+
+    /// The global wet amount set with [`FirewheelCtx::set_global_wet`].
+    pub fn global_wet(&self) -> f32 {
+        self.global_wet
+    }
+
+    /// Temporarily audition the mono sum of the output.
+    ///
+    /// While enabled, the first two output channels (the front left and
+    /// right) are both replaced with their sum at -6 dB. This is useful for
+    /// checking that a mix with widened or Haas-delayed parts still holds up
+    /// when played back in mono: content that is out of phase between the
+    /// channels cancels out. The fold happens before the master limiter.
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn set_mono_audition(&mut self, enabled: bool) -> Result<(), UpdateError<B::StreamError>> {
+        if self.mono_audition == enabled {
+            return Ok(());
+        }
+        self.mono_audition = enabled;
+
+        self.send_message_to_processor(ContextToProcessorMsg::SetMonoAudition(enabled))
+            .map_err(|(_, e)| e)
+    }
+
+    /// Whether or not the mono sum of the output is being auditioned. See
+    /// [`FirewheelCtx::set_mono_audition`].
+    pub fn mono_audition(&self) -> bool {
+        self.mono_audition
+    }
+
+    /// The fraction of the available time the last process cycle took to
+    /// process (i.e. `0.5` means half of the time).
+    ///
+    /// This is always `0.0` if the `std` feature is disabled.
+    pub fn cpu_load(&self) -> f32 {
+        self.cpu_load.load(Ordering::Relaxed)
+    }
+
     /// Update the firewheel context.
     ///
     /// This must be called reguarly (i.e. once every frame).
@@ -706,6 +1320,34 @@ impl<B: AudioBackend> FirewheelCtx<B> {
                 ProcessorToContextMsg::ReturnSchedule(schedule_data) => {
                     let _ = schedule_data;
                 }
+                ProcessorToContextMsg::NodeFadedOut(node_id) => {
+                    if let Some(i) = self.nodes_fading_out.iter().position(|id| *id == node_id) {
+                        self.nodes_fading_out.remove(i);
+                        self.culled_voices.retain(|id| *id != node_id);
+                        let _ = self.graph.remove_node(node_id);
+                    }
+                }
+                ProcessorToContextMsg::InputActivity { node_id, active } => {
+                    // Ignore messages from a detector that has since been disabled.
+                    if self
+                        .graph
+                        .node_info(node_id)
+                        .is_some_and(|n| n.input_activity.is_some())
+                    {
+                        self.active_inputs.retain(|id| *id != node_id);
+                        if active {
+                            self.active_inputs.push(node_id);
+                        }
+
+                        self.input_activity_events
+                            .push(InputActivityEvent { node_id, active });
+                    }
+                }
+                ProcessorToContextMsg::VoiceCulled(node_id) => {
+                    if !self.culled_voices.contains(&node_id) {
+                        self.culled_voices.push(node_id);
+                    }
+                }
                 #[cfg(feature = "musical_transport")]
                 ProcessorToContextMsg::ReturnTransportState(transport_state) => {
                     if self.transport_state_alloc_reuse.is_none() {
@@ -726,8 +1368,7 @@ impl<B: AudioBackend> FirewheelCtx<B> {
 
         if let Some(active_state) = &mut self.active_state {
             if let Err(e) = active_state.backend_handle.poll_status() {
-                self.active_state = None;
-                self.graph.deactivate();
+                self.deactivate_stream();
 
                 return Err(UpdateError::StreamStoppedUnexpectedly(Some(e)));
             }
@@ -739,8 +1380,7 @@ impl<B: AudioBackend> FirewheelCtx<B> {
                 .try_peek()
                 .is_some()
             {
-                self.active_state = None;
-                self.graph.deactivate();
+                self.deactivate_stream();
 
                 return Err(UpdateError::StreamStoppedUnexpectedly(None));
             }
@@ -789,6 +1429,7 @@ impl<B: AudioBackend> FirewheelCtx<B> {
                     .pop()
                     .unwrap_or_else(|| Vec::with_capacity(self.initial_event_group_capacity));
                 core::mem::swap(&mut next_event_group, &mut self.event_group);
+                self.event_group_coalesced = false;
 
                 if let Err((msg, e)) = self
                     .send_message_to_processor(ContextToProcessorMsg::EventGroup(next_event_group))
@@ -805,6 +1446,11 @@ impl<B: AudioBackend> FirewheelCtx<B> {
             }
         }
 
+        if self.num_rejected_events > 0 {
+            let num_rejected = core::mem::take(&mut self.num_rejected_events);
+            return Err(UpdateError::EventQueueOverflow(num_rejected));
+        }
+
         Ok(())
     }
 
@@ -824,12 +1470,73 @@ impl<B: AudioBackend> FirewheelCtx<B> {
         node: T,
         config: Option<T::Configuration>,
     ) -> NodeID {
-        self.graph.add_node(node, config)
+        let node_id = self.graph.add_node(node, config);
+        self.auto_connect_source(node_id);
+        node_id
+    }
+
+    /// Add a node to the audio graph, keeping a copy of its parameters so they
+    /// can be read and written by path with [`FirewheelCtx::get_param`] and
+    /// [`FirewheelCtx::set_param`].
+    ///
+    /// The copy is kept in sync with every parameter event queued for this
+    /// node, including ones generated by [`Diff`].
+    pub fn add_node_with_params<T: AudioNode + Diff + Patch + Clone + 'static>(
+        &mut self,
+        node: T,
+        config: Option<T::Configuration>,
+    ) -> NodeID {
+        let node_id = self.graph.add_node_with_params(node, config);
+        self.auto_connect_source(node_id);
+        node_id
+    }
+
+    /// Add a node whose processor was constructed ahead of time (i.e. on a
+    /// worker thread) to the audio graph.
+    ///
+    /// See [`PrewarmedNode`] for more information.
+    pub fn add_prewarmed_node(&mut self, node: PrewarmedNode) -> NodeID {
+        let node_id = self.graph.add_prewarmed_node(node);
+        self.auto_connect_source(node_id);
+        node_id
     }
 
     /// Add a node to the audio graph which implements the type-erased [`DynAudioNode`] trait.
     pub fn add_dyn_node<T: DynAudioNode + 'static>(&mut self, node: T) -> NodeID {
-        self.graph.add_dyn_node(node)
+        let node_id = self.graph.add_dyn_node(node);
+        self.auto_connect_source(node_id);
+        node_id
+    }
+
+    /// Whether newly added source nodes are automatically connected to the
+    /// graph output node (see [`FirewheelConfig::auto_connect_sources`]).
+    pub fn auto_connect_sources(&self) -> bool {
+        self.config.auto_connect_sources
+    }
+
+    /// Set whether newly added source nodes are automatically connected to
+    /// the graph output node (see [`FirewheelConfig::auto_connect_sources`]).
+    pub fn set_auto_connect_sources(&mut self, auto_connect: bool) {
+        self.config.auto_connect_sources = auto_connect;
+    }
+
+    /// Connect a newly added node to the graph output node if it is a source
+    /// node and [`FirewheelConfig::auto_connect_sources`] is enabled.
+    fn auto_connect_source(&mut self, node_id: NodeID) {
+        if !self.config.auto_connect_sources {
+            return;
+        }
+
+        let Some(entry) = self.graph.node_info(node_id) else {
+            return;
+        };
+        let channel_config = entry.info.channel_config;
+        if channel_config.num_inputs.get() > 0 || channel_config.num_outputs.get() == 0 {
+            return;
+        }
+
+        // A source node has no inputs, so this can never create a cycle.
+        let _ = self.connect_all(node_id, self.graph_out_node_id(), false);
     }
 
     /// Remove the given node from the audio graph.
@@ -846,53 +1553,730 @@ impl<B: AudioBackend> FirewheelCtx<B> {
         &mut self,
         node_id: NodeID,
     ) -> Result<SmallVec<[EdgeID; 4]>, RemoveNodeError> {
+        self.culled_voices.retain(|id| *id != node_id);
+        self.nodes_fading_out.retain(|id| *id != node_id);
+        self.active_inputs.retain(|id| *id != node_id);
+
+        if self.previewed_node == Some(node_id) {
+            let _ = self.stop_preview(0.0);
+        }
+        if self.active_scene == Some(node_id) {
+            let _ = self.crossfade_to_scene(None, 0.0);
+        }
+
         self.graph.remove_node(node_id)
     }
 
-    /// Get information about a node in the graph.
-    pub fn node_info(&self, id: NodeID) -> Option<&NodeEntry> {
-        self.graph.node_info(id)
-    }
+    /// Fade the outputs of the given node to silence over `fade_ms`
+    /// milliseconds, and then remove it from the audio graph.
+    ///
+    /// Removing a node with [`FirewheelCtx::remove_node`] cuts off its audio
+    /// immediately, which can cause an audible click. The node stays
+    /// connected while it fades out, and it is removed (along with its edges)
+    /// in the call to [`FirewheelCtx::update`] after the fade has finished.
+    ///
+    /// If no audio stream is running or the node has not been processed yet,
+    /// then the node is removed immediately.
+    pub fn remove_node_faded(
+        &mut self,
+        node_id: NodeID,
+        fade_ms: f32,
+    ) -> Result<(), RemoveNodeError> {
+        if node_id == self.graph.graph_in_node() {
+            return Err(RemoveNodeError::CannotRemoveGraphInNode);
+        }
+        if node_id == self.graph.graph_out_node() {
+            return Err(RemoveNodeError::CannotRemoveGraphOutNode);
+        }
 
-    /// Get an immutable reference to the custom state of a node.
-    pub fn node_state<T: 'static>(&self, id: NodeID) -> Option<&T> {
-        self.graph.node_state(id)
-    }
+        let Some(node_entry) = self.graph.node_info(node_id) else {
+            return Ok(());
+        };
+        if self.nodes_fading_out.contains(&node_id) {
+            return Ok(());
+        }
 
-    /// Get a type-erased, immutable reference to the custom state of a node.
-    pub fn node_state_dyn(&self, id: NodeID) -> Option<&dyn Any> {
-        self.graph.node_state_dyn(id)
-    }
+        let fade_frames =
+            (fade_ms.max(0.0) / 1_000.0 * self.sample_rate.get() as f32).round() as u32;
 
-    /// Get a mutable reference to the custom state of a node.
-    pub fn node_state_mut<T: 'static>(&mut self, id: NodeID) -> Option<&mut T> {
-        self.graph.node_state_mut(id)
-    }
+        if self.active_state.is_none() || !node_entry.processor_constructed || fade_frames == 0 {
+            return self.remove_node(node_id).map(|_| ());
+        }
 
-    pub fn node_state_dyn_mut(&mut self, id: NodeID) -> Option<&mut dyn Any> {
-        self.graph.node_state_dyn_mut(id)
-    }
+        if self
+            .send_message_to_processor(ContextToProcessorMsg::FadeOutNode {
+                node_id,
+                fade_frames,
+            })
+            .is_err()
+        {
+            return self.remove_node(node_id).map(|_| ());
+        }
 
-    /// Get a list of all the existing nodes in the graph.
-    pub fn nodes<'a>(&'a self) -> impl Iterator<Item = &'a NodeEntry> {
-        self.graph.nodes()
-    }
+        self.nodes_fading_out.push(node_id);
 
-    /// Get a list of all the existing edges in the graph.
-    pub fn edges<'a>(&'a self) -> impl Iterator<Item = &'a Edge> {
-        self.graph.edges()
+        Ok(())
     }
 
-    /// Set the number of input and output channels to and from the audio graph.
+    /// Replace the given node with a rebuilt version of it, i.e. to change
+    /// the number of channels of a filter from stereo to quad.
     ///
-    /// Returns the list of edges that were removed.
+    /// The new node is added to the graph and every edge of the old node
+    /// whose port still exists on the new node is reconnected to it. Edges
+    /// to ports that no longer exist are dropped, and any added ports are
+    /// left unconnected. The mute, solo, voice, and order hint settings of
+    /// the old node are carried over.
+    ///
+    /// To avoid a click, the old node's outputs are faded out over `fade_ms`
+    /// milliseconds (see [`FirewheelCtx::remove_node_faded`]) while the new
+    /// node takes over, so the two briefly overlap.
+    ///
+    /// On success, this returns the ID of the new node.
+    pub fn rebuild_node<T: AudioNode + 'static>(
+        &mut self,
+        node_id: NodeID,
+        node: T,
+        config: Option<T::Configuration>,
+        fade_ms: f32,
+    ) -> Result<NodeID, RebuildNodeError> {
+        if node_id == self.graph.graph_in_node() {
+            return Err(RebuildNodeError::CannotRebuildGraphInNode);
+        }
+        if node_id == self.graph.graph_out_node() {
+            return Err(RebuildNodeError::CannotRebuildGraphOutNode);
+        }
+
+        let Some(old_entry) = self.graph.node_info(node_id) else {
+            return Err(RebuildNodeError::NodeNotFound(node_id));
+        };
+        let muted = old_entry.muted;
+        let soloed = old_entry.soloed;
+        let solo_safe = old_entry.solo_safe;
+        let voice = old_entry.voice;
+        let wet_bypassed = old_entry.wet_bypassed;
+        let order_hint = old_entry.order_hint;
+
+        let incoming: SmallVec<[Edge; 4]> = self.incoming_edges(node_id).copied().collect();
+        let outgoing: SmallVec<[Edge; 4]> = self.outgoing_edges(node_id).copied().collect();
+
+        let new_id = self.graph.add_node(node, config);
+        let channel_config = self.graph.node_info(new_id).unwrap().channel_config();
+
+        // The ports were valid on the old node and are checked against the
+        // new node, so connecting cannot fail.
+        for edge in incoming
+            .iter()
+            .filter(|e| e.dst_port < channel_config.num_inputs.get())
+        {
+            let _ = self.graph.connect_with_gain(
+                edge.src_node,
+                new_id,
+                &[(edge.src_port, edge.dst_port)],
+                edge.gain,
+                false,
+            );
+        }
+        for edge in outgoing
+            .iter()
+            .filter(|e| e.src_port < channel_config.num_outputs.get())
+        {
+            let _ = self.graph.connect_with_gain(
+                new_id,
+                edge.dst_node,
+                &[(edge.src_port, edge.dst_port)],
+                edge.gain,
+                false,
+            );
+        }
+
+        self.graph.set_node_muted(new_id, muted);
+        self.graph.set_node_soloed(new_id, soloed);
+        self.graph.set_node_solo_safe(new_id, solo_safe);
+        self.graph.set_node_voice(new_id, voice);
+        self.graph.set_node_wet_bypassed(new_id, wet_bypassed);
+        self.graph.set_node_order_hint(new_id, order_hint);
+
+        // The graph in and out nodes were rejected above.
+        let _ = self.remove_node_faded(node_id, fade_ms);
+
+        Ok(new_id)
+    }
+
+    /// Render the output of a node, along with every node feeding into it,
+    /// offline into a sample of `frames` frames.
+    ///
+    /// This is useful for "freezing" an expensive sub-graph: play the
+    /// returned sample with a sampler node instead, and then remove the
+    /// sub-graph to save CPU.
+    ///
+    /// The sub-graph is rendered from fresh copies of its nodes, so the live
+    /// nodes are left untouched. The copies are constructed from each node
+    /// as it was added, so parameter changes made since then are not
+    /// reflected. The graph input is treated as silence, and none of the
+    /// processing on the final output of the graph (i.e. the master limiter)
+    /// is applied. The stream info of the running stream is used if there is
+    /// one.
+    pub fn bounce_node(
+        &self,
+        node_id: NodeID,
+        frames: usize,
+    ) -> Result<Vec<Vec<f32>>, BounceNodeError> {
+        let num_outputs = self
+            .graph
+            .node_info(node_id)
+            .ok_or(BounceNodeError::NodeNotFound(node_id))?
+            .info
+            .channel_config
+            .num_outputs;
+        if num_outputs.get() == 0 {
+            return Err(BounceNodeError::NoOutputs(node_id));
+        }
+
+        // Gather the node and everything upstream of it.
+        let graph_in = self.graph_in_node_id();
+        let mut sub_graph = Vec::new();
+        sub_graph.push(node_id);
+        let mut i = 0;
+        while i < sub_graph.len() {
+            for edge in self.incoming_edges(sub_graph[i]) {
+                if edge.src_node != graph_in && !sub_graph.contains(&edge.src_node) {
+                    sub_graph.push(edge.src_node);
+                }
+            }
+            i += 1;
+        }
+
+        let mut bounce_cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            num_graph_outputs: num_outputs,
+            declick_seconds: self.config.declick_seconds,
+            min_param_ramp_seconds: self.config.min_param_ramp_seconds,
+            max_block_frames: self.config.max_block_frames,
+            ..Default::default()
+        });
+
+        let mut stream = DummyStream::new(StreamInfo {
+            sample_rate: self.sample_rate,
+            sample_rate_recip: self.sample_rate_recip,
+            prev_sample_rate: self.sample_rate,
+            max_block_frames: self
+                .stream_info()
+                .map(|stream_info| stream_info.max_block_frames)
+                .unwrap_or(StreamInfo::default().max_block_frames),
+            num_stream_in_channels: 0,
+            num_stream_out_channels: num_outputs.get(),
+            ..Default::default()
+        });
+        bounce_cx
+            .start_stream(stream.clone())
+            .map_err(|e| match e {
+                StartStreamError::GraphCompileError(e) => BounceNodeError::GraphCompileError(e),
+                _ => unreachable!("a new context always starts its stream"),
+            })?;
+        stream.stream_info = bounce_cx.stream_info().unwrap().clone();
+
+        let mut bounce_ids = Vec::with_capacity(sub_graph.len());
+        for &id in sub_graph.iter() {
+            let node = self.graph.prewarm_copy(id, &stream.stream_info).unwrap();
+            bounce_ids.push(bounce_cx.add_prewarmed_node(node));
+        }
+
+        let bounce_id = |id: NodeID| bounce_ids[sub_graph.iter().position(|n| *n == id).unwrap()];
+        for &id in sub_graph.iter() {
+            for edge in self.incoming_edges(id).filter(|e| e.src_node != graph_in) {
+                // The live graph has no cycles, so neither does the copy.
+                let _ = bounce_cx.connect_with_gain(
+                    bounce_id(edge.src_node),
+                    bounce_id(id),
+                    &[(edge.src_port, edge.dst_port)],
+                    edge.gain,
+                    false,
+                );
+            }
+        }
+
+        let graph_out = bounce_cx.graph_out_node_id();
+        let ports: Vec<(PortIdx, PortIdx)> = (0..num_outputs.get()).map(|i| (i, i)).collect();
+        let _ = bounce_cx.connect(bounce_id(node_id), graph_out, &ports, false);
+
+        if let Err(UpdateError::GraphCompileError(e)) = bounce_cx.update() {
+            return Err(BounceNodeError::GraphCompileError(e));
+        }
+
+        let output = stream.process_block(frames);
+
+        let num_channels = num_outputs.get() as usize;
+        Ok((0..num_channels)
+            .map(|ch| {
+                output
+                    .iter()
+                    .skip(ch)
+                    .step_by(num_channels)
+                    .copied()
+                    .collect()
+            })
+            .collect())
+    }
+
+    /// Set whether or not the output of a node is muted.
+    ///
+    /// A muted node is still processed, but its output is replaced with
+    /// silence. This takes effect the next time the graph is compiled.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_muted(&mut self, node_id: NodeID, muted: bool) -> bool {
+        self.graph.set_node_muted(node_id, muted)
+    }
+
+    /// Set whether or not a node is soloed.
+    ///
+    /// While any node is soloed, the outputs of all other nodes are silenced,
+    /// except for the nodes that feed a soloed node, the nodes that a soloed
+    /// node feeds, and nodes marked as solo-safe with
+    /// [`FirewheelCtx::set_node_solo_safe`]. This takes effect the next time
+    /// the graph is compiled.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_soloed(&mut self, node_id: NodeID, soloed: bool) -> bool {
+        self.graph.set_node_soloed(node_id, soloed)
+    }
+
+    /// Set whether or not a node stays audible while another node is soloed
+    /// (i.e. a master reverb return or a talkback channel). The nodes it feeds
+    /// stay audible as well.
+    ///
+    /// Muting a solo-safe node still silences it.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_solo_safe(&mut self, node_id: NodeID, solo_safe: bool) -> bool {
+        self.graph.set_node_solo_safe(node_id, solo_safe)
+    }
+
+    /// Set whether or not the wet path of an effect node is bypassed.
+    ///
+    /// Unlike muting, this only silences the processed part of the node's
+    /// output: the node behaves as if [`FirewheelCtx::set_global_wet`] were
+    /// `0.0` for it alone, so its dry signal still passes through. This is
+    /// useful for an effect that sits in a parallel send. Only nodes with a
+    /// dry/wet `mix` parameter are affected. This takes effect the next time
+    /// the graph is compiled.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_wet_bypassed(&mut self, node_id: NodeID, wet_bypassed: bool) -> bool {
+        self.graph.set_node_wet_bypassed(node_id, wet_bypassed)
+    }
+
+    /// Set where a node prefers to be placed in the processing schedule (i.e.
+    /// [`NodeOrderHint::Last`] for a meter tap that should run after the
+    /// nodes it measures). This takes effect the next time the graph is
+    /// compiled.
+    ///
+    /// The hint only decides between nodes whose order is not already fixed
+    /// by the connections in the graph.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_order_hint(&mut self, node_id: NodeID, hint: NodeOrderHint) -> bool {
+        self.graph.set_node_order_hint(node_id, hint)
+    }
+
+    /// The current latency of a node in frames (samples in a single channel
+    /// of audio).
+    ///
+    /// Returns `None` if the node does not exist.
+    pub fn node_latency_frames(&self, node_id: NodeID) -> Option<u32> {
+        self.graph.node_info(node_id).map(|n| n.latency_frames())
+    }
+
+    /// Set the latency of a node in frames (samples in a single channel of
+    /// audio), i.e. after changing a parameter that affects its lookahead.
+    /// If the latency changed, then the audio graph is recompiled on the next
+    /// call to [`FirewheelCtx::update`].
+    ///
+    /// Nodes that call [`AudioNode::update`] can also report their own
+    /// latency with [`UpdateContext::set_latency_frames`].
+    ///
+    /// Returns `false` if the node does not exist.
+    ///
+    /// [`UpdateContext::set_latency_frames`]: firewheel_core::node::UpdateContext::set_latency_frames
+    pub fn set_node_latency_frames(&mut self, node_id: NodeID, latency_frames: u32) -> bool {
+        self.graph.set_node_latency_frames(node_id, latency_frames)
+    }
+
+    /// Set whether or not a node is a voice (i.e. a sampler in a voice pool)
+    /// that may be culled to stay within [`FirewheelConfig::max_voices`] and
+    /// [`FirewheelConfig::cpu_budget`]. This takes effect the next time the
+    /// graph is compiled.
+    ///
+    /// A culled voice outputs silence and is no longer processed until it is
+    /// revived with [`FirewheelCtx::revive_voice`].
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_voice(&mut self, node_id: NodeID, voice: bool) -> bool {
+        if !voice {
+            self.culled_voices.retain(|id| *id != node_id);
+        }
+
+        self.graph.set_node_voice(node_id, voice)
+    }
+
+    /// Returns `true` if the given voice has been culled to stay within the
+    /// voice budget.
+    ///
+    /// This is updated when [`FirewheelCtx::update`] is called.
+    pub fn is_voice_culled(&self, node_id: NodeID) -> bool {
+        self.culled_voices.contains(&node_id)
+    }
+
+    /// Resume processing a voice that was culled to stay within the voice
+    /// budget (i.e. when the voice is reused to play a new sound).
+    ///
+    /// If the message channel is full, then this will return an error.
+    pub fn revive_voice(&mut self, node_id: NodeID) -> Result<(), UpdateError<B::StreamError>> {
+        if !self.is_voice_culled(node_id) {
+            return Ok(());
+        }
+
+        self.send_message_to_processor(ContextToProcessorMsg::ReviveVoice(node_id))
+            .map_err(|(_, e)| e)?;
+        self.culled_voices.retain(|id| *id != node_id);
+
+        Ok(())
+    }
+
+    /// Attach a level probe to the input or output ports of a node, without
+    /// adding a meter node or changing the connections in the graph.
+    ///
+    /// The returned [`Probe`] holds the peak level of each channel measured
+    /// in the last processed block. If a probe is already attached at that
+    /// point, then a handle to the existing probe is returned.
+    ///
+    /// Probes take effect the next time the graph is compiled (i.e. on the
+    /// next call to [`FirewheelCtx::update`]).
+    pub fn add_probe(
+        &mut self,
+        node_id: NodeID,
+        point: ProbePoint,
+    ) -> Result<Probe, AddProbeError> {
+        self.graph.add_probe(node_id, point)
+    }
+
+    /// Detach the level probe from the input or output ports of a node.
+    ///
+    /// Returns `true` if a probe was removed.
+    pub fn remove_probe(&mut self, node_id: NodeID, point: ProbePoint) -> bool {
+        self.graph.remove_probe(node_id, point)
+    }
+
+    /// Enable (`Some`) or disable (`None`) reporting when the input of a
+    /// node transitions between silent and active, i.e. for gating logic or
+    /// for showing which nodes currently carry signal in a UI.
+    ///
+    /// The transitions are collected when [`FirewheelCtx::update`] is called,
+    /// and can then be read with [`FirewheelCtx::drain_input_activity_events`].
+    /// This takes effect the next time the graph is compiled.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_input_activity(
+        &mut self,
+        node_id: NodeID,
+        config: Option<InputActivityConfig>,
+    ) -> bool {
+        if config.is_none() {
+            self.active_inputs.retain(|id| *id != node_id);
+        }
+
+        self.graph.set_node_input_activity(node_id, config)
+    }
+
+    /// Returns `true` if the input of the given node is currently active.
+    ///
+    /// This is only tracked for nodes with input activity detection enabled
+    /// (see [`FirewheelCtx::set_node_input_activity`]), and is updated when
+    /// [`FirewheelCtx::update`] is called.
+    pub fn is_input_active(&self, node_id: NodeID) -> bool {
+        self.active_inputs.contains(&node_id)
+    }
+
+    /// Take the input activity transitions collected since the last call,
+    /// in the order they happened.
+    ///
+    /// The events are kept until they are drained, so this should be
+    /// called regularly if any node has input activity detection enabled.
+    pub fn drain_input_activity_events(&mut self) -> impl Iterator<Item = InputActivityEvent> + '_ {
+        self.input_activity_events.drain(..)
+    }
+
+    /// Get information about a node in the graph.
+    pub fn node_info(&self, id: NodeID) -> Option<&NodeEntry> {
+        self.graph.node_info(id)
+    }
+
+    /// Get an immutable reference to the custom state of a node.
+    pub fn node_state<T: 'static>(&self, id: NodeID) -> Option<&T> {
+        self.graph.node_state(id)
+    }
+
+    /// Read the latest queued value of a parameter by its dot-separated
+    /// field path (i.e. `"cutoff_hz"` or `"filter.q"`).
+    ///
+    /// Returns `None` if the node or path does not exist, or if the node was
+    /// not added with [`FirewheelCtx::add_node_with_params`].
+    pub fn get_param(&self, node_id: NodeID, path: &str) -> Option<ParamData> {
+        let params = self.graph.node_params(node_id)?;
+        let path = params.param_path(path)?;
+        params.read_param(&path)
+    }
+
+    /// Set a parameter by its dot-separated field path (i.e. `"cutoff_hz"`
+    /// or `"filter.q"`), queueing a parameter event for the node's processor.
+    ///
+    /// This only works for nodes added with
+    /// [`FirewheelCtx::add_node_with_params`].
+    pub fn set_param(
+        &mut self,
+        node_id: NodeID,
+        path: &str,
+        value: impl Into<ParamData>,
+    ) -> Result<(), SetParamError> {
+        if self.graph.node_info(node_id).is_none() {
+            return Err(SetParamError::NodeNotFound(node_id));
+        }
+        let params = self
+            .graph
+            .node_params_mut(node_id)
+            .ok_or(SetParamError::ParamsNotAccessible(node_id))?;
+
+        let path = params.param_path(path).ok_or(SetParamError::InvalidPath)?;
+        let data = value.into();
+
+        params.write_param(&data, &path).map_err(|e| match e {
+            PatchError::InvalidPath => SetParamError::InvalidPath,
+            PatchError::InvalidData => SetParamError::InvalidData,
+        })?;
+
+        self.record_param(node_id, &path, &data);
+
+        // The local copy is already up to date, so bypass `queue_event`.
+        self.push_pending_event(NodeEvent {
+            node_id,
+            #[cfg(feature = "scheduled_events")]
+            time: None,
+            event: NodeEventType::Param { data, path },
+        });
+
+        Ok(())
+    }
+
+    /// The registry of named parameter presets for each node type.
+    pub fn presets(&self) -> &PresetRegistry {
+        &self.presets
+    }
+
+    /// The registry of named parameter presets for each node type.
+    pub fn presets_mut(&mut self) -> &mut PresetRegistry {
+        &mut self.presets
+    }
+
+    /// Apply a preset registered in [`FirewheelCtx::presets`] to a node,
+    /// queueing parameter events for every parameter which differs from the
+    /// node's current value.
+    ///
+    /// This only works for nodes added with
+    /// [`FirewheelCtx::add_node_with_params`].
+    pub fn apply_preset(&mut self, node_id: NodeID, name: &str) -> Result<(), ApplyPresetError> {
+        let type_name = self
+            .graph
+            .node_info(node_id)
+            .ok_or(ApplyPresetError::NodeNotFound(node_id))?
+            .type_name;
+        let params = self
+            .graph
+            .node_params(node_id)
+            .ok_or(ApplyPresetError::ParamsNotAccessible(node_id))?;
+
+        let mut events = Vec::new();
+        if !self.presets.diff(type_name, name, params, &mut events) {
+            return Err(ApplyPresetError::PresetNotFound);
+        }
+
+        for event in events {
+            self.queue_event_for(node_id, event);
+        }
+
+        Ok(())
+    }
+
+    /// The registry of node types used to serialize and deserialize the
+    /// audio graph.
+    #[cfg(feature = "serde")]
+    pub fn node_registry(&self) -> &NodeRegistry {
+        &self.node_registry
+    }
+
+    /// The registry of node types used to serialize and deserialize the
+    /// audio graph.
+    #[cfg(feature = "serde")]
+    pub fn node_registry_mut(&mut self) -> &mut NodeRegistry {
+        &mut self.node_registry
+    }
+
+    /// Capture the nodes (with their current parameters and configurations)
+    /// and edges of the audio graph into a serializable [`GraphDescriptor`].
+    ///
+    /// The type of every node must be registered in
+    /// [`FirewheelCtx::node_registry_mut`]. The current parameters are only
+    /// known for nodes added with [`FirewheelCtx::add_node_with_params`],
+    /// otherwise the parameters the node was added with are used.
+    #[cfg(feature = "serde")]
+    pub fn serialize_graph(&self) -> Result<GraphDescriptor, SerializeGraphError> {
+        crate::descriptor::serialize_graph(&self.graph, &self.node_registry)
+    }
+
+    /// Add the nodes and edges described by a [`GraphDescriptor`] to the
+    /// audio graph, alongside any existing nodes.
+    ///
+    /// The nodes are added as if by [`FirewheelCtx::add_node_with_params`]
+    /// (without automatically connecting sources). On success, this returns
+    /// the IDs of the new nodes, in the same order as
+    /// [`GraphDescriptor::nodes`]. On failure, no nodes are added.
+    #[cfg(feature = "serde")]
+    pub fn deserialize_graph(
+        &mut self,
+        descriptor: &GraphDescriptor,
+    ) -> Result<Vec<NodeID>, DeserializeGraphError> {
+        crate::descriptor::deserialize_graph(&mut self.graph, &self.node_registry, descriptor)
+    }
+
+    /// Change the parameters of every node of type `T` at once (i.e. to turn
+    /// down all reverbs in the scene).
+    ///
+    /// The closure is called with a copy of each node's current parameters,
+    /// and the changes it makes are diffed into parameter events queued for
+    /// that node. Returns the number of nodes that were visited.
+    ///
+    /// This only visits nodes added with
+    /// [`FirewheelCtx::add_node_with_params`], since the current parameters
+    /// of other nodes are not known to the context.
+    pub fn for_each_node_of_type<T: AudioNode + Diff + Patch + Clone + 'static>(
+        &mut self,
+        mut f: impl FnMut(&mut T),
+    ) -> usize {
+        let node_ids: Vec<NodeID> = self
+            .graph
+            .nodes()
+            .filter(|entry| {
+                entry
+                    .params
+                    .as_ref()
+                    .is_some_and(|params| params.as_any().is::<T>())
+            })
+            .map(|entry| entry.id)
+            .collect();
+
+        for &node_id in node_ids.iter() {
+            let baseline = self
+                .graph
+                .node_params(node_id)
+                .and_then(|params| params.as_any().downcast_ref::<T>())
+                .unwrap()
+                .clone();
+
+            let mut params = baseline.clone();
+            (f)(&mut params);
+
+            params.diff(
+                &baseline,
+                PathBuilder::default(),
+                &mut self.event_queue(node_id),
+            );
+        }
+
+        node_ids.len()
+    }
+
+    /// Get a type-erased, immutable reference to the custom state of a node.
+    pub fn node_state_dyn(&self, id: NodeID) -> Option<&dyn Any> {
+        self.graph.node_state_dyn(id)
+    }
+
+    /// Get a mutable reference to the custom state of a node.
+    pub fn node_state_mut<T: 'static>(&mut self, id: NodeID) -> Option<&mut T> {
+        self.graph.node_state_mut(id)
+    }
+
+    pub fn node_state_dyn_mut(&mut self, id: NodeID) -> Option<&mut dyn Any> {
+        self.graph.node_state_dyn_mut(id)
+    }
+
+    /// Get a list of all the existing nodes in the graph.
+    ///
+    /// Each entry includes the node's type name, channel configuration, and
+    /// reported latency. Together with [`FirewheelCtx::edges`], this can be
+    /// used to reconstruct the connection map without tracking it separately.
+    pub fn nodes<'a>(&'a self) -> impl Iterator<Item = &'a NodeEntry> {
+        self.graph.nodes()
+    }
+
+    /// Get a list of all the existing edges in the graph.
+    pub fn edges<'a>(&'a self) -> impl Iterator<Item = &'a Edge> {
+        self.graph.edges()
+    }
+
+    /// Get a list of the edges connected to the input ports of the given node.
+    pub fn incoming_edges<'a>(&'a self, node_id: NodeID) -> impl Iterator<Item = &'a Edge> {
+        self.graph.edges().filter(move |e| e.dst_node == node_id)
+    }
+
+    /// Get a list of the edges connected to the output ports of the given node.
+    pub fn outgoing_edges<'a>(&'a self, node_id: NodeID) -> impl Iterator<Item = &'a Edge> {
+        self.graph.edges().filter(move |e| e.src_node == node_id)
+    }
+
+    /// Set the number of input and output channels to and from the audio graph.
+    ///
+    /// If the number of output channels no longer matches the current
+    /// [`FirewheelCtx::output_layout`], then the output layout is cleared.
+    ///
+    /// Returns the list of edges that were removed.
     pub fn set_graph_channel_config(
         &mut self,
         channel_config: ChannelConfig,
     ) -> SmallVec<[EdgeID; 4]> {
+        if self
+            .config
+            .output_layout
+            .is_some_and(|layout| layout.num_channels().get() != channel_config.num_outputs)
+        {
+            self.config.output_layout = None;
+        }
+        self.config.num_graph_outputs = channel_config.num_outputs;
+        self.config.num_graph_inputs = channel_config.num_inputs;
+
         self.graph.set_graph_channel_config(channel_config)
     }
 
+    /// The speaker layout of the output of the audio graph.
+    ///
+    /// Returns `None` if no layout was configured, in which case the graph
+    /// output has [`FirewheelConfig::num_graph_outputs`] channels.
+    pub fn output_layout(&self) -> Option<ChannelLayout> {
+        self.config.output_layout
+    }
+
+    /// Set the speaker layout of the output of the audio graph. The graph
+    /// output node will be resized to have one input port per channel in
+    /// the layout.
+    ///
+    /// Returns the list of edges that were removed.
+    pub fn set_output_layout(&mut self, layout: ChannelLayout) -> SmallVec<[EdgeID; 4]> {
+        let removed_edges = self.set_graph_channel_config(ChannelConfig {
+            num_inputs: self.config.num_graph_inputs,
+            num_outputs: layout.num_channels().get(),
+        });
+        self.config.output_layout = Some(layout);
+
+        removed_edges
+    }
+
     /// Add connections (edges) between two nodes to the graph.
     ///
     /// * `src_node` - The ID of the source node.
@@ -921,6 +2305,30 @@ impl<B: AudioBackend> FirewheelCtx<B> {
             .connect(src_node, dst_node, ports_src_dst, check_for_cycles)
     }
 
+    /// Connect each output channel of `src_node` to the input channel of
+    /// `dst_node` with the same index, up to the smaller of the two channel
+    /// counts.
+    ///
+    /// If [`FirewheelConfig::fan_out_mono`] is enabled and `src_node` has a
+    /// single output channel, then that channel is connected to every input
+    /// channel of `dst_node` instead.
+    ///
+    /// See [`FirewheelCtx::connect`] for the meaning of `check_for_cycles`
+    /// and the returned value.
+    pub fn connect_all(
+        &mut self,
+        src_node: NodeID,
+        dst_node: NodeID,
+        check_for_cycles: bool,
+    ) -> Result<SmallVec<[EdgeID; 4]>, AddEdgeError> {
+        self.graph.connect_all(
+            src_node,
+            dst_node,
+            self.config.fan_out_mono,
+            check_for_cycles,
+        )
+    }
+
     /// Remove connections (edges) between two nodes from the graph.
     ///
     /// * `src_node` - The ID of the source node.
@@ -964,6 +2372,38 @@ impl<B: AudioBackend> FirewheelCtx<B> {
         self.graph.edge(edge_id)
     }
 
+    /// Add connections (edges) between two nodes to the graph, with the
+    /// given gain (in raw amplitude) applied to each one (i.e. a send
+    /// level).
+    ///
+    /// This is the same as [`FirewheelCtx::connect`], except that the
+    /// signal on each edge is scaled by `gain` when it is summed into the
+    /// destination port, so a volume node isn't needed on the connection.
+    /// If an edge already exists, then its gain is updated.
+    pub fn connect_with_gain(
+        &mut self,
+        src_node: NodeID,
+        dst_node: NodeID,
+        ports_src_dst: &[(PortIdx, PortIdx)],
+        gain: f32,
+        check_for_cycles: bool,
+    ) -> Result<SmallVec<[EdgeID; 4]>, AddEdgeError> {
+        self.graph
+            .connect_with_gain(src_node, dst_node, ports_src_dst, gain, check_for_cycles)
+    }
+
+    /// Set the gain (in raw amplitude) applied to the signal on the given
+    /// edge when it is summed into the destination port.
+    ///
+    /// Note, changing the gain recompiles the audio graph, so use a
+    /// volume node instead for gain that changes often (i.e. automation).
+    ///
+    /// If the edge did not exist in this graph, then `false` will be
+    /// returned.
+    pub fn set_edge_gain(&mut self, edge_id: EdgeID, gain: f32) -> bool {
+        self.graph.set_edge_gain(edge_id, gain)
+    }
+
     /// Runs a check to see if a cycle exists in the audio graph.
     ///
     /// Note, this method is expensive.
@@ -976,7 +2416,111 @@ impl<B: AudioBackend> FirewheelCtx<B> {
     /// Note, this event will not be sent until the event queue is flushed
     /// in [`FirewheelCtx::update`].
     pub fn queue_event(&mut self, event: NodeEvent) {
-        self.event_group.push(event);
+        if let NodeEventType::Param { data, path } = &event.event {
+            if let Some(params) = self.graph.node_params_mut(event.node_id) {
+                let _ = params.write_param(data, path);
+            }
+            self.record_param(event.node_id, path, data);
+        }
+
+        self.push_pending_event(event);
+    }
+
+    /// Start recording every parameter change sent to a node (through
+    /// [`FirewheelCtx::queue_event`], [`FirewheelCtx::set_param`], or
+    /// diffing), timestamped with the audio clock at the time it is queued.
+    ///
+    /// The result is a [`ParamRecording`] with an automation curve for each
+    /// parameter, which can be replayed offline. If a recording is already
+    /// in progress, then it is discarded.
+    pub fn start_param_recording(&mut self) {
+        self.param_recording = Some(ParamRecording::default());
+    }
+
+    /// Stop recording parameter changes, returning the recording if one was
+    /// in progress.
+    pub fn stop_param_recording(&mut self) -> Option<ParamRecording> {
+        self.param_recording.take()
+    }
+
+    /// The parameter recording in progress, if any. See
+    /// [`FirewheelCtx::start_param_recording`].
+    pub fn param_recording(&self) -> Option<&ParamRecording> {
+        self.param_recording.as_ref()
+    }
+
+    fn record_param(&mut self, node_id: NodeID, path: &ParamPath, data: &ParamData) {
+        if self.param_recording.is_none() {
+            return;
+        }
+
+        let time = self.audio_clock().samples;
+        if let Some(recording) = &mut self.param_recording {
+            recording.record(node_id, path, data, time);
+        }
+    }
+
+    /// The number of events that are queued to be sent to the audio thread
+    /// on the next call to [`FirewheelCtx::update`].
+    pub fn pending_event_count(&self) -> usize {
+        self.event_group.len()
+    }
+
+    /// The maximum number of events that can be queued between calls to
+    /// [`FirewheelCtx::update`] (see [`FirewheelConfig::pending_event_capacity`]).
+    pub fn pending_event_capacity(&self) -> Option<usize> {
+        self.config.pending_event_capacity
+    }
+
+    /// What happens when an event is queued while the pending event queue
+    /// is full.
+    pub fn event_queue_overflow_policy(&self) -> EventQueueOverflowPolicy {
+        self.config.event_queue_overflow_policy
+    }
+
+    /// Set what happens when an event is queued while the pending event
+    /// queue is full.
+    pub fn set_event_queue_overflow_policy(&mut self, policy: EventQueueOverflowPolicy) {
+        self.config.event_queue_overflow_policy = policy;
+    }
+
+    /// The total number of events that were dropped or rejected because the
+    /// pending event queue was full.
+    pub fn num_dropped_events(&self) -> u64 {
+        self.num_dropped_events
+    }
+
+    fn push_pending_event(&mut self, event: NodeEvent) {
+        let Some(capacity) = self.config.pending_event_capacity else {
+            self.event_group.push(event);
+            return;
+        };
+
+        if self.event_group.len() < capacity {
+            self.event_group.push(event);
+            return;
+        }
+
+        match self.config.event_queue_overflow_policy {
+            EventQueueOverflowPolicy::DropNewest => {}
+            EventQueueOverflowPolicy::Coalesce => {
+                // Once the queue has been coalesced, every parameter appears
+                // at most once, so only the new event needs to be checked
+                // until the next flush.
+                coalesce_param_events(&mut self.event_group, &event, !self.event_group_coalesced);
+                self.event_group_coalesced = true;
+
+                if self.event_group.len() < capacity {
+                    self.event_group.push(event);
+                    return;
+                }
+            }
+            EventQueueOverflowPolicy::Error => {
+                self.num_rejected_events += 1;
+            }
+        }
+
+        self.num_dropped_events += 1;
     }
 
     /// Queue an event to be sent to an audio node's processor.
@@ -1152,6 +2696,62 @@ impl<B: AudioBackend> firewheel_core::diff::EventQueue for ContextQueue<'_, B> {
     }
 }
 
+/// What happens when an event is queued while the pending event queue of a
+/// [`FirewheelCtx`] is full (see [`FirewheelConfig::pending_event_capacity`]).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventQueueOverflowPolicy {
+    /// Silently drop the new event.
+    DropNewest,
+    /// Make room by discarding parameter events that were superseded by a
+    /// newer value for the same parameter, so that only the most recent
+    /// value of each parameter is sent. If no room can be made, then the new
+    /// event is dropped.
+    Coalesce,
+    /// Drop the new event and return [`UpdateError::EventQueueOverflow`] on
+    /// the next call to [`FirewheelCtx::update`].
+    #[default]
+    Error,
+}
+
+/// Returns `true` if both events set the same parameter on the same node
+/// at the same time.
+fn is_same_param(a: &NodeEvent, b: &NodeEvent) -> bool {
+    #[cfg(feature = "scheduled_events")]
+    if a.time != b.time {
+        return false;
+    }
+
+    match (&a.event, &b.event) {
+        (NodeEventType::Param { path: path_a, .. }, NodeEventType::Param { path: path_b, .. }) => {
+            a.node_id == b.node_id && path_a == path_b
+        }
+        _ => false,
+    }
+}
+
+/// Remove every parameter event which is superseded by `newer` or (if
+/// `coalesce_queued` is `true`) by a later event in `events`, compacting the
+/// remaining events in place.
+fn coalesce_param_events(events: &mut Vec<NodeEvent>, newer: &NodeEvent, coalesce_queued: bool) {
+    let mut kept = 0;
+    for i in 0..events.len() {
+        let superseded = is_same_param(&events[i], newer)
+            || (coalesce_queued
+                && events[i + 1..]
+                    .iter()
+                    .any(|later| is_same_param(&events[i], later)));
+
+        if !superseded {
+            events.swap(kept, i);
+            kept += 1;
+        }
+    }
+
+    events.truncate(kept);
+}
+
 /// The type of scheduled events to clear
 #[cfg(feature = "scheduled_events")]
 #[derive(Default, Debug, Clone, Copy, PartialEq)]
@@ -1185,3 +2785,720 @@ fn audio_clock_update_instant_and_delay<B: AudioBackend>(
             })
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use firewheel_core::{
+        channel_config::{ChannelConfig, ChannelCount, ChannelLayout, NonZeroChannelCount},
+        diff::Memo,
+        dsp::volume::Volume,
+        event::{NodeEventType, ParamData, ProcEvents},
+        node::{
+            AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+            NodeID, ProcBuffers, ProcExtra, ProcInfo, ProcessStatus, UpdateContext,
+        },
+    };
+    use firewheel_nodes::{
+        beep_test::BeepTestNode,
+        convolution::ConvolutionNode,
+        noise_generator::{pink::PinkNoiseGenNode, white::WhiteNoiseGenNode},
+        sampler::SamplerNode,
+        svf::{SvfNode, SvfStereoNode, SvfType},
+        volume::{VolumeNode, VolumeNodeConfig},
+    };
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        error::{ApplyPresetError, BounceNodeError, RebuildNodeError, SetParamError},
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        EventQueueOverflowPolicy, FirewheelConfig, FirewheelCtx,
+    };
+
+    fn graph_out_channels(cx: &FirewheelCtx<DummyBackend>) -> ChannelCount {
+        cx.node_info(cx.graph_out_node_id())
+            .unwrap()
+            .info
+            .channel_config
+            .num_inputs
+    }
+
+    #[test]
+    fn surround_output_layout() {
+        let cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            output_layout: Some(ChannelLayout::Surround5_1),
+            ..Default::default()
+        });
+
+        assert_eq!(graph_out_channels(&cx), ChannelCount::new(6).unwrap());
+        assert_eq!(cx.output_layout(), Some(ChannelLayout::Surround5_1));
+    }
+
+    #[test]
+    fn auto_connect_sources_to_graph_out() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            auto_connect_sources: true,
+            ..Default::default()
+        });
+        let graph_out = cx.graph_out_node_id();
+
+        let outgoing = |cx: &FirewheelCtx<DummyBackend>, node_id| {
+            let mut edges: Vec<_> = cx
+                .outgoing_edges(node_id)
+                .map(|e| (e.src_port, e.dst_node, e.dst_port))
+                .collect();
+            edges.sort();
+            edges
+        };
+
+        // A stereo source is connected channel for channel.
+        let stereo = cx.add_node(SamplerNode::default(), None);
+        assert_eq!(
+            outgoing(&cx, stereo),
+            [(0, graph_out, 0), (1, graph_out, 1)]
+        );
+
+        // A mono source is fanned out to both output channels.
+        let mono = cx.add_node(PinkNoiseGenNode::default(), None);
+        assert_eq!(outgoing(&cx, mono), [(0, graph_out, 0), (0, graph_out, 1)]);
+
+        // Effects are left for the user to wire up.
+        let effect = cx.add_node(VolumeNode::default(), None);
+        assert!(outgoing(&cx, effect).is_empty());
+
+        cx.set_auto_connect_sources(false);
+        let unconnected = cx.add_node(PinkNoiseGenNode::default(), None);
+        assert!(outgoing(&cx, unconnected).is_empty());
+    }
+
+    #[test]
+    fn change_output_layout() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        assert_eq!(graph_out_channels(&cx), ChannelCount::STEREO);
+
+        cx.set_output_layout(ChannelLayout::Quad);
+        assert_eq!(graph_out_channels(&cx), ChannelCount::new(4).unwrap());
+        assert_eq!(
+            cx.node_info(cx.graph_in_node_id())
+                .unwrap()
+                .info
+                .channel_config
+                .num_outputs,
+            ChannelCount::ZERO
+        );
+
+        cx.set_graph_channel_config(ChannelConfig::new(0, 1));
+        assert_eq!(graph_out_channels(&cx), ChannelCount::MONO);
+        assert_eq!(cx.output_layout(), None);
+    }
+
+    fn cutoff_hz(cx: &FirewheelCtx<DummyBackend>, node_id: firewheel_core::node::NodeID) -> f32 {
+        match cx.get_param(node_id, "cutoff_hz") {
+            Some(ParamData::F32(cutoff_hz)) => cutoff_hz,
+            other => panic!("unexpected parameter value {other:?}"),
+        }
+    }
+
+    #[test]
+    fn set_param_by_path() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let filter = SvfStereoNode::default();
+        let node_id = cx.add_node_with_params(filter, None);
+
+        assert_eq!(cutoff_hz(&cx, node_id), filter.cutoff_hz);
+
+        cx.set_param(node_id, "cutoff_hz", 2_500.0f32).unwrap();
+        assert_eq!(cutoff_hz(&cx, node_id), 2_500.0);
+        assert_eq!(cx.event_group.len(), 1);
+
+        // Changes made through diffing are tracked as well.
+        let mut memo = Memo::new(SvfStereoNode {
+            cutoff_hz: 2_500.0,
+            ..filter
+        });
+        memo.cutoff_hz = 400.0;
+        memo.update_memo(&mut cx.event_queue(node_id));
+        assert_eq!(cutoff_hz(&cx, node_id), 400.0);
+
+        assert_eq!(
+            cx.set_param(node_id, "cutoff", 100.0f32),
+            Err(SetParamError::InvalidPath)
+        );
+        assert_eq!(
+            cx.set_param(node_id, "cutoff_hz", true),
+            Err(SetParamError::InvalidData)
+        );
+        assert_eq!(cutoff_hz(&cx, node_id), 400.0);
+
+        let plain_id = cx.add_node(WhiteNoiseGenNode::default(), None);
+        assert_eq!(
+            cx.set_param(plain_id, "volume", 0.5f32),
+            Err(SetParamError::ParamsNotAccessible(plain_id))
+        );
+        assert!(cx.get_param(plain_id, "enabled").is_none());
+    }
+
+    #[test]
+    fn coalesce_overflowing_event_queue() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            pending_event_capacity: Some(4),
+            event_queue_overflow_policy: EventQueueOverflowPolicy::Coalesce,
+            ..Default::default()
+        });
+        let node_id = cx.add_node_with_params(SvfStereoNode::default(), None);
+        assert_eq!(cx.pending_event_capacity(), Some(4));
+
+        cx.set_param(node_id, "cutoff_hz", 100.0f32).unwrap();
+        cx.set_param(node_id, "cutoff_hz", 200.0f32).unwrap();
+        cx.set_param(node_id, "q_factor", 0.5f32).unwrap();
+        cx.set_param(node_id, "cutoff_hz", 300.0f32).unwrap();
+        assert_eq!(cx.pending_event_count(), 4);
+
+        // The queue is full, so older values for the same parameters are
+        // discarded to make room.
+        cx.set_param(node_id, "cutoff_hz", 400.0f32).unwrap();
+
+        let values: Vec<f32> = cx
+            .event_group
+            .iter()
+            .map(|event| match &event.event {
+                NodeEventType::Param {
+                    data: ParamData::F32(value),
+                    ..
+                } => *value,
+                _ => panic!("unexpected event"),
+            })
+            .collect();
+        assert_eq!(values, [0.5, 400.0]);
+        assert_eq!(cx.num_dropped_events(), 0);
+
+        // A full queue of distinct parameters has nothing left to coalesce.
+        cx.set_event_queue_overflow_policy(EventQueueOverflowPolicy::Error);
+        cx.set_param(node_id, "smooth_seconds", 0.01f32).unwrap();
+        cx.set_param(node_id, "enabled", false).unwrap();
+        cx.set_param(node_id, "cutoff_hz", 500.0f32).unwrap();
+        assert_eq!(cx.pending_event_count(), 4);
+        assert_eq!(cx.num_dropped_events(), 1);
+        assert!(matches!(
+            cx.update(),
+            Err(crate::error::UpdateError::EventQueueOverflow(1))
+        ));
+        assert!(cx.update().is_ok());
+    }
+
+    #[test]
+    fn event_queue_is_unbounded_by_default() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let node_id = cx.add_node_with_params(SvfStereoNode::default(), None);
+        assert_eq!(cx.pending_event_capacity(), None);
+
+        for i in 0..2048 {
+            cx.set_param(node_id, "cutoff_hz", i as f32).unwrap();
+        }
+        assert_eq!(cx.pending_event_count(), 2048);
+        assert_eq!(cx.num_dropped_events(), 0);
+    }
+
+    #[test]
+    fn broadcast_wet_gain_to_all_convolution_nodes() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let reverbs: Vec<NodeID> = (0..3)
+            .map(|_| cx.add_node_with_params(ConvolutionNode::<2>::default(), None))
+            .collect();
+        // Nodes of other types are left alone.
+        let filter = cx.add_node_with_params(SvfStereoNode::default(), None);
+
+        let wet_gain = Volume::Decibels(-12.0);
+        let visited = cx.for_each_node_of_type::<ConvolutionNode<2>>(|params| {
+            params.wet_gain = wet_gain;
+        });
+        assert_eq!(visited, 3);
+
+        for node_id in reverbs {
+            let params = cx
+                .graph
+                .node_params(node_id)
+                .unwrap()
+                .as_any()
+                .downcast_ref::<ConvolutionNode<2>>()
+                .unwrap();
+            assert_eq!(params.wet_gain, wet_gain);
+        }
+        let params = cx
+            .graph
+            .node_params(filter)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<SvfStereoNode>()
+            .unwrap();
+        assert_eq!(*params, SvfStereoNode::default());
+
+        // One wet gain event per reverb.
+        assert_eq!(cx.event_group.len(), 3);
+        assert!(cx
+            .event_group
+            .iter()
+            .all(|event| event.node_id != filter
+                && matches!(event.event, NodeEventType::Param { .. })));
+    }
+
+    #[test]
+    fn apply_registered_preset() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let telephone = SvfStereoNode {
+            filter_type: SvfType::Bandpass,
+            cutoff_hz: 1_500.0,
+            q_factor: 0.7,
+            ..Default::default()
+        };
+        let radio = SvfStereoNode {
+            filter_type: SvfType::Bandpass,
+            cutoff_hz: 2_000.0,
+            q_factor: 0.4,
+            ..Default::default()
+        };
+        cx.presets_mut().register("telephone", telephone);
+        cx.presets_mut().register("radio", radio);
+
+        let node_id = cx.add_node_with_params(SvfStereoNode::default(), None);
+        let type_name = cx.node_info(node_id).unwrap().type_name;
+        assert_eq!(
+            cx.presets().names_for_type(type_name).collect::<Vec<_>>(),
+            ["telephone", "radio"]
+        );
+
+        cx.apply_preset(node_id, "telephone").unwrap();
+
+        let params = cx
+            .graph
+            .node_params(node_id)
+            .unwrap()
+            .as_any()
+            .downcast_ref::<SvfStereoNode>()
+            .unwrap();
+        assert_eq!(*params, telephone);
+        // Only the filter type, cutoff, and q differ from the defaults.
+        assert_eq!(cx.event_group.len(), 3);
+
+        assert_eq!(
+            cx.apply_preset(node_id, "megaphone"),
+            Err(ApplyPresetError::PresetNotFound)
+        );
+
+        let plain_id = cx.add_node(SvfStereoNode::default(), None);
+        assert_eq!(
+            cx.apply_preset(plain_id, "radio"),
+            Err(ApplyPresetError::ParamsNotAccessible(plain_id))
+        );
+    }
+
+    #[test]
+    fn widening_stereo_filter_to_quad_preserves_connections() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        let filter = cx.add_node(SvfStereoNode::default(), None);
+        connect_stereo(&mut cx, graph_in, filter);
+        connect_stereo(&mut cx, filter, graph_out);
+        cx.set_node_muted(filter, true);
+
+        let quad = cx
+            .rebuild_node(filter, SvfNode::<4>::default(), None, 10.0)
+            .unwrap();
+
+        // No stream is running, so the old node is removed immediately.
+        assert!(cx.node_info(filter).is_none());
+        assert_eq!(
+            cx.node_info(quad).unwrap().channel_config(),
+            ChannelConfig::new(4, 4)
+        );
+        assert!(cx.node_info(quad).unwrap().muted);
+
+        let mut incoming: Vec<_> = cx
+            .incoming_edges(quad)
+            .map(|e| (e.src_node, e.src_port, e.dst_port))
+            .collect();
+        incoming.sort_by_key(|e| e.2);
+        assert_eq!(incoming, [(graph_in, 0, 0), (graph_in, 1, 1)]);
+
+        let mut outgoing: Vec<_> = cx
+            .outgoing_edges(quad)
+            .map(|e| (e.src_port, e.dst_node, e.dst_port))
+            .collect();
+        outgoing.sort_by_key(|e| e.0);
+        assert_eq!(outgoing, [(0, graph_out, 0), (1, graph_out, 1)]);
+
+        assert_eq!(
+            cx.rebuild_node(graph_out, SvfStereoNode::default(), None, 0.0),
+            Err(RebuildNodeError::CannotRebuildGraphOutNode)
+        );
+    }
+
+    #[test]
+    fn bounced_node_matches_live_output() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let beep = cx.add_node(BeepTestNode::default(), None);
+        let volume = cx.add_node(
+            VolumeNode::from_decibels(-6.0),
+            Some(VolumeNodeConfig {
+                channels: NonZeroChannelCount::MONO,
+                ..Default::default()
+            }),
+        );
+        let graph_out = cx.graph_out_node_id();
+        cx.connect(beep, volume, &[(0, 0)], false).unwrap();
+        cx.connect(volume, graph_out, &[(0, 0)], false).unwrap();
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+        cx.update().unwrap();
+
+        let frames = 4_000;
+        let live: Vec<f32> = stream
+            .process_block(frames)
+            .into_iter()
+            .step_by(2)
+            .collect();
+        assert!(live.iter().any(|&s| s.abs() > 0.1));
+
+        // Bouncing renders fresh copies, so the live nodes keep playing
+        // undisturbed.
+        let bounced = cx.bounce_node(volume, frames).unwrap();
+        assert_eq!(bounced.len(), 1);
+        assert_eq!(bounced[0], live);
+
+        assert_eq!(
+            cx.bounce_node(graph_out, frames),
+            Err(BounceNodeError::NoOutputs(graph_out))
+        );
+    }
+
+    #[test]
+    fn topology_lists_connected_edges() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let a = cx.add_node(WhiteNoiseGenNode::default(), None);
+        let b = cx.add_node(VolumeNode::default(), None);
+        let edges = cx.connect(a, b, &[(0, 1)], false).unwrap();
+
+        let edge = cx.edges().find(|e| e.id == edges[0]).unwrap();
+        assert_eq!(edge.src_node, a);
+        assert_eq!(edge.src_port, 0);
+        assert_eq!(edge.dst_node, b);
+        assert_eq!(edge.dst_port, 1);
+
+        let node_a = cx.nodes().find(|n| n.id == a).unwrap();
+        assert!(node_a.type_name.ends_with("WhiteNoiseGenNode"));
+        assert_eq!(node_a.channel_config().num_outputs, ChannelCount::MONO);
+        assert_eq!(node_a.latency_frames(), 0);
+
+        let node_b = cx.node_info(b).unwrap();
+        assert!(node_b.type_name.ends_with("VolumeNode"));
+
+        assert_eq!(cx.outgoing_edges(a).collect::<Vec<_>>(), [edge]);
+        assert_eq!(cx.incoming_edges(a).count(), 0);
+        assert_eq!(cx.incoming_edges(b).collect::<Vec<_>>(), [edge]);
+    }
+
+    #[test]
+    fn remove_node_faded_fades_out_before_removal() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        let volume = cx.add_node(VolumeNode::default(), None);
+        connect_stereo(&mut cx, graph_in, volume);
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let input = vec![1.0; 1024 * 2];
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+        assert!(output.iter().all(|&s| s == 1.0));
+
+        // A 10ms fade at 44.1kHz.
+        cx.remove_node_faded(volume, 10.0).unwrap();
+        let fade_frames = 441;
+        cx.update().unwrap();
+
+        stream.process(&input, &mut output);
+
+        // The output falls smoothly to silence instead of being cut off.
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        for w in left[..fade_frames].windows(2) {
+            assert!(w[1] < w[0] && w[0] - w[1] < 0.01);
+        }
+        assert!(left[fade_frames - 1..].iter().all(|&s| s == 0.0));
+
+        // The node is still connected until the fade has finished.
+        assert!(cx.node_info(volume).is_some());
+        cx.update().unwrap();
+        assert!(cx.node_info(volume).is_none());
+        assert_eq!(cx.incoming_edges(graph_out).count(), 0);
+    }
+
+    #[test]
+    fn stream_stopping_mid_fade_removes_fading_nodes() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        let volume = cx.add_node(VolumeNode::default(), None);
+        connect_stereo(&mut cx, graph_in, volume);
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+        stream.process_block(64);
+
+        cx.remove_node_faded(volume, 10.0).unwrap();
+        cx.update().unwrap();
+        stream.process_block(64);
+        assert!(cx.node_info(volume).is_some());
+
+        // The fade can never finish once the stream is gone.
+        stream.stop_unexpectedly();
+        assert!(matches!(
+            cx.update(),
+            Err(crate::error::UpdateError::StreamStoppedUnexpectedly(None))
+        ));
+        assert!(!cx.is_audio_stream_running());
+        assert!(cx.node_info(volume).is_none());
+        assert_eq!(cx.incoming_edges(graph_out).count(), 0);
+    }
+
+    /// A generator that records the block sizes it sees.
+    struct BlockSizeProbe {
+        max_block_frames: Arc<AtomicUsize>,
+        largest_block: Arc<AtomicUsize>,
+    }
+
+    impl AudioNode for BlockSizeProbe {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("block_size_probe")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::ZERO,
+                    num_outputs: ChannelCount::MONO,
+                })
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            self.max_block_frames.store(
+                cx.stream_info.max_block_frames.get() as usize,
+                Ordering::Relaxed,
+            );
+
+            BlockSizeProbeProcessor {
+                largest_block: Arc::clone(&self.largest_block),
+            }
+        }
+    }
+
+    struct BlockSizeProbeProcessor {
+        largest_block: Arc<AtomicUsize>,
+    }
+
+    impl AudioNodeProcessor for BlockSizeProbeProcessor {
+        fn process(
+            &mut self,
+            info: &ProcInfo,
+            buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            self.largest_block.fetch_max(info.frames, Ordering::Relaxed);
+            buffers.outputs[0][..info.frames].fill(0.5);
+
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    #[test]
+    fn nodes_observe_max_block_frames_override() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            max_block_frames: NonZeroU32::new(64),
+            ..Default::default()
+        });
+        let graph_out = cx.graph_out_node_id();
+
+        let max_block_frames = Arc::new(AtomicUsize::new(0));
+        let largest_block = Arc::new(AtomicUsize::new(0));
+        let probe = cx.add_node(
+            BlockSizeProbe {
+                max_block_frames: Arc::clone(&max_block_frames),
+                largest_block: Arc::clone(&largest_block),
+            },
+            None,
+        );
+        cx.connect(probe, graph_out, &[(0, 0), (0, 1)], false)
+            .unwrap();
+
+        let stream = DummyStream::default();
+        assert_eq!(stream.stream_info.max_block_frames.get(), 1024);
+        cx.start_stream(stream.clone()).unwrap();
+        cx.update().unwrap();
+
+        assert_eq!(cx.stream_info().unwrap().max_block_frames.get(), 64);
+        assert_eq!(max_block_frames.load(Ordering::Relaxed), 64);
+
+        // A full device callback is divided into blocks of the overridden size.
+        let output = stream.process_block(1024);
+        assert_eq!(largest_block.load(Ordering::Relaxed), 64);
+        assert!(output.iter().all(|&s| s == 0.5));
+    }
+
+    /// The lookahead of a [`LookaheadNode`], which can be changed at runtime.
+    struct LookaheadState {
+        lookahead_frames: u32,
+    }
+
+    /// A node whose latency depends on its lookahead (like a limiter).
+    struct LookaheadNode {
+        lookahead_frames: u32,
+    }
+
+    impl AudioNode for LookaheadNode {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("lookahead")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::MONO,
+                    num_outputs: ChannelCount::MONO,
+                })
+                .latency_frames(self.lookahead_frames)
+                .call_update_method(true)
+                .custom_state(LookaheadState {
+                    lookahead_frames: self.lookahead_frames,
+                })
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            _cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            LookaheadProcessor
+        }
+
+        fn update(&mut self, _config: &Self::Configuration, mut cx: UpdateContext) {
+            let lookahead_frames = cx
+                .custom_state::<LookaheadState>()
+                .unwrap()
+                .lookahead_frames;
+            cx.set_latency_frames(lookahead_frames);
+        }
+    }
+
+    struct LookaheadProcessor;
+
+    impl AudioNodeProcessor for LookaheadProcessor {
+        fn process(
+            &mut self,
+            _info: &ProcInfo,
+            _buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            ProcessStatus::Bypass
+        }
+    }
+
+    #[test]
+    fn runtime_latency_change_recompiles_graph() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let graph_out = cx.graph_out_node_id();
+
+        let limiter = cx.add_node(
+            LookaheadNode {
+                lookahead_frames: 64,
+            },
+            None,
+        );
+        cx.connect(limiter, graph_out, &[(0, 0)], false).unwrap();
+
+        cx.start_stream(DummyStream::default()).unwrap();
+        cx.update().unwrap();
+        assert_eq!(cx.node_latency_frames(limiter), Some(64));
+        assert!(!cx.graph.needs_compile());
+
+        // Increasing the lookahead is reported on the next update.
+        cx.node_state_mut::<LookaheadState>(limiter)
+            .unwrap()
+            .lookahead_frames = 256;
+        cx.graph.update(None, &mut cx.event_group);
+        assert_eq!(cx.node_latency_frames(limiter), Some(256));
+        assert!(cx.graph.needs_compile());
+
+        cx.update().unwrap();
+        assert!(!cx.graph.needs_compile());
+
+        // Reporting the same latency again does not recompile.
+        cx.graph.update(None, &mut cx.event_group);
+        assert!(!cx.graph.needs_compile());
+
+        // The latency can also be set from outside the node.
+        assert!(cx.set_node_latency_frames(limiter, 512));
+        assert!(cx.graph.needs_compile());
+        assert!(!cx.set_node_latency_frames(NodeID::DANGLING, 512));
+    }
+
+    #[test]
+    fn edge_gain_scales_contribution_at_destination() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        // The left output is the sum of the left input and the right input
+        // at half gain, and the right output is only the right input at half
+        // gain.
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        cx.connect(graph_in, graph_out, &[(0, 0)], false).unwrap();
+        let edge_ids = cx
+            .connect_with_gain(graph_in, graph_out, &[(1, 0), (1, 1)], 0.5, false)
+            .unwrap();
+        assert!(edge_ids.iter().all(|id| cx.edge(*id).unwrap().gain == 0.5));
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let input: Vec<f32> = (0..1024)
+            .flat_map(|i| {
+                [
+                    0.25 * (i as f32 * 0.05).sin(),
+                    0.5 * (i as f32 * 0.03).cos(),
+                ]
+            })
+            .collect();
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        for (out, input) in output.chunks(2).zip(input.chunks(2)) {
+            assert_eq!(out, [input[0] + input[1] * 0.5, input[1] * 0.5]);
+        }
+
+        // Setting the gain back to unity recompiles the graph.
+        assert!(cx.set_edge_gain(edge_ids[1], 1.0));
+        assert!(cx.graph.needs_compile());
+        cx.update().unwrap();
+        stream.process(&input, &mut output);
+        for (out, input) in output.chunks(2).zip(input.chunks(2)) {
+            assert_eq!(out[1], input[1]);
+        }
+    }
+}
@@ -0,0 +1,392 @@
+//! Serializing the audio graph of a [`FirewheelCtx`][crate::FirewheelCtx]
+//! (i.e. for saving and loading patches in an editor).
+
+use core::any::type_name;
+
+use bevy_platform::collections::HashMap;
+use firewheel_core::{
+    diff::{Diff, Patch},
+    node::{AudioNode, Constructor, NodeID},
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{String, ToString, Vec};
+
+use crate::{
+    error::{DeserializeGraphError, SerializeGraphError},
+    graph::{AudioGraph, NodeEntry, PortIdx},
+};
+
+/// A node that an edge in a [`GraphDescriptor`] is connected to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum NodeRef {
+    /// The graph input node.
+    GraphIn,
+    /// The graph output node.
+    GraphOut,
+    /// The node at the given index in [`GraphDescriptor::nodes`].
+    Node(usize),
+}
+
+/// A serializable description of a node in the audio graph.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodeDescriptor {
+    /// The tag the node's type was registered with in the [`NodeRegistry`].
+    pub type_tag: String,
+    /// The current parameters of the node.
+    pub params: Value,
+    /// The configuration the node was constructed with.
+    pub config: Value,
+}
+
+/// A serializable description of an edge in the audio graph.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EdgeDescriptor {
+    pub src_node: NodeRef,
+    pub src_port: PortIdx,
+    pub dst_node: NodeRef,
+    pub dst_port: PortIdx,
+    /// The gain (in raw amplitude) of the edge.
+    #[serde(default = "unity_gain")]
+    pub gain: f32,
+}
+
+fn unity_gain() -> f32 {
+    1.0
+}
+
+/// A serializable description of the nodes and edges of an audio graph.
+///
+/// This is created with
+/// [`FirewheelCtx::serialize_graph`][crate::FirewheelCtx::serialize_graph]
+/// and loaded back with
+/// [`FirewheelCtx::deserialize_graph`][crate::FirewheelCtx::deserialize_graph].
+///
+/// Note, the parameters and configurations of the nodes are stored as
+/// self-describing values, so this can only be deserialized from
+/// self-describing formats (i.e. JSON or RON).
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GraphDescriptor {
+    pub nodes: Vec<NodeDescriptor>,
+    pub edges: Vec<EdgeDescriptor>,
+}
+
+/// The serialized parameters and configuration of a node.
+type SerializedNode = Result<(Value, Value), serde_json::Error>;
+
+struct RegisteredNode {
+    tag: &'static str,
+    /// Serialize the parameters and configuration of a node.
+    ///
+    /// Returns `None` if the node is of a different type.
+    serialize: fn(&NodeEntry) -> Option<SerializedNode>,
+    add: fn(&mut AudioGraph, &NodeDescriptor) -> Result<NodeID, serde_json::Error>,
+}
+
+fn serialize_node<T>(entry: &NodeEntry) -> Option<SerializedNode>
+where
+    T: AudioNode + Serialize + 'static,
+    T::Configuration: Serialize + 'static,
+{
+    let constructor = entry
+        .dyn_node
+        .as_any()?
+        .downcast_ref::<Constructor<T, T::Configuration>>()?;
+
+    // Prefer the live copy of the parameters over the ones the node was
+    // constructed with.
+    let params = entry
+        .params
+        .as_ref()
+        .and_then(|params| params.as_any().downcast_ref::<T>())
+        .unwrap_or(constructor.constructor());
+
+    Some(serde_json::to_value(params).and_then(|params| {
+        let config = serde_json::to_value(constructor.configuration())?;
+        Ok((params, config))
+    }))
+}
+
+fn add_node<T>(graph: &mut AudioGraph, node: &NodeDescriptor) -> Result<NodeID, serde_json::Error>
+where
+    T: AudioNode + Diff + Patch + Clone + DeserializeOwned + 'static,
+    T::Configuration: DeserializeOwned,
+{
+    let params = T::deserialize(&node.params)?;
+    let config = T::Configuration::deserialize(&node.config)?;
+
+    Ok(graph.add_node_with_params(params, Some(config)))
+}
+
+/// A registry which maps node types to stable tags, so that the audio graph
+/// can be serialized and the nodes reconstructed from their tags.
+///
+/// Every type of node in the graph must be registered before serializing or
+/// deserializing it (see
+/// [`FirewheelCtx::node_registry_mut`][crate::FirewheelCtx::node_registry_mut]).
+#[derive(Default)]
+pub struct NodeRegistry {
+    /// The registered nodes, keyed by the name of the Rust type (see
+    /// [`NodeEntry::type_name`]).
+    by_type: HashMap<&'static str, RegisteredNode>,
+    /// The names of the Rust types, keyed by tag.
+    by_tag: HashMap<&'static str, &'static str>,
+}
+
+impl NodeRegistry {
+    /// Register the node type `T` with the given tag.
+    ///
+    /// The tag is stored in place of the name of the Rust type, so it should
+    /// stay the same across versions of the application (i.e. `"volume"`).
+    ///
+    /// If `T` or the tag was already registered, then the old registration
+    /// is replaced.
+    pub fn register<T>(&mut self, tag: &'static str)
+    where
+        T: AudioNode + Diff + Patch + Clone + Serialize + DeserializeOwned + 'static,
+        T::Configuration: Serialize + DeserializeOwned + 'static,
+    {
+        if let Some(old_type_name) = self.by_tag.remove(tag) {
+            self.by_type.remove(old_type_name);
+        }
+
+        let old = self.by_type.insert(
+            type_name::<T>(),
+            RegisteredNode {
+                tag,
+                serialize: serialize_node::<T>,
+                add: add_node::<T>,
+            },
+        );
+        if let Some(old) = old {
+            self.by_tag.remove(old.tag);
+        }
+
+        self.by_tag.insert(tag, type_name::<T>());
+    }
+
+    /// The tag the node type `T` was registered with.
+    pub fn tag<T: AudioNode + 'static>(&self) -> Option<&'static str> {
+        self.tag_for_type(type_name::<T>())
+    }
+
+    /// The tag the node type with the given type name (see
+    /// [`NodeEntry::type_name`]) was registered with.
+    pub fn tag_for_type(&self, type_name: &str) -> Option<&'static str> {
+        self.by_type.get(type_name).map(|node| node.tag)
+    }
+
+    fn serialize_node(&self, entry: &NodeEntry) -> Result<NodeDescriptor, SerializeGraphError> {
+        let registered =
+            self.by_type
+                .get(entry.type_name)
+                .ok_or(SerializeGraphError::UnregisteredType {
+                    node: entry.id,
+                    type_name: entry.type_name,
+                })?;
+
+        let (params, config) = (registered.serialize)(entry)
+            .ok_or(SerializeGraphError::NotSerializable(entry.id))?
+            .map_err(|error| SerializeGraphError::Serde {
+                node: entry.id,
+                error,
+            })?;
+
+        Ok(NodeDescriptor {
+            type_tag: registered.tag.to_string(),
+            params,
+            config,
+        })
+    }
+
+    fn add_node(
+        &self,
+        graph: &mut AudioGraph,
+        index: usize,
+        node: &NodeDescriptor,
+    ) -> Result<NodeID, DeserializeGraphError> {
+        let registered = self
+            .by_tag
+            .get(node.type_tag.as_str())
+            .and_then(|type_name| self.by_type.get(type_name))
+            .ok_or_else(|| DeserializeGraphError::UnknownTypeTag(node.type_tag.clone()))?;
+
+        (registered.add)(graph, node).map_err(|error| DeserializeGraphError::Serde { index, error })
+    }
+}
+
+pub(crate) fn serialize_graph(
+    graph: &AudioGraph,
+    registry: &NodeRegistry,
+) -> Result<GraphDescriptor, SerializeGraphError> {
+    let graph_in = graph.graph_in_node();
+    let graph_out = graph.graph_out_node();
+
+    let mut node_refs = HashMap::<NodeID, NodeRef>::default();
+    node_refs.insert(graph_in, NodeRef::GraphIn);
+    node_refs.insert(graph_out, NodeRef::GraphOut);
+
+    let mut nodes = Vec::new();
+    for entry in graph
+        .nodes()
+        .filter(|entry| entry.id != graph_in && entry.id != graph_out)
+    {
+        node_refs.insert(entry.id, NodeRef::Node(nodes.len()));
+        nodes.push(registry.serialize_node(entry)?);
+    }
+
+    let edges = graph
+        .edges()
+        .map(|edge| EdgeDescriptor {
+            src_node: node_refs[&edge.src_node],
+            src_port: edge.src_port,
+            dst_node: node_refs[&edge.dst_node],
+            dst_port: edge.dst_port,
+            gain: edge.gain,
+        })
+        .collect();
+
+    Ok(GraphDescriptor { nodes, edges })
+}
+
+pub(crate) fn deserialize_graph(
+    graph: &mut AudioGraph,
+    registry: &NodeRegistry,
+    descriptor: &GraphDescriptor,
+) -> Result<Vec<NodeID>, DeserializeGraphError> {
+    let mut node_ids = Vec::with_capacity(descriptor.nodes.len());
+
+    let res = add_nodes_and_edges(graph, registry, descriptor, &mut node_ids);
+    if res.is_err() {
+        // Don't leave a partially loaded graph behind.
+        for &node_id in node_ids.iter() {
+            let _ = graph.remove_node(node_id);
+        }
+    }
+
+    res.map(|()| node_ids)
+}
+
+fn add_nodes_and_edges(
+    graph: &mut AudioGraph,
+    registry: &NodeRegistry,
+    descriptor: &GraphDescriptor,
+    node_ids: &mut Vec<NodeID>,
+) -> Result<(), DeserializeGraphError> {
+    for (index, node) in descriptor.nodes.iter().enumerate() {
+        node_ids.push(registry.add_node(graph, index, node)?);
+    }
+
+    let node_id = |node_ref: NodeRef| match node_ref {
+        NodeRef::GraphIn => Ok(graph.graph_in_node()),
+        NodeRef::GraphOut => Ok(graph.graph_out_node()),
+        NodeRef::Node(index) => node_ids
+            .get(index)
+            .copied()
+            .ok_or(DeserializeGraphError::NodeIndexOutOfRange(index)),
+    };
+
+    let edges = descriptor
+        .edges
+        .iter()
+        .map(|edge| Ok((node_id(edge.src_node)?, node_id(edge.dst_node)?, edge)))
+        .collect::<Result<Vec<_>, DeserializeGraphError>>()?;
+
+    for (src_node, dst_node, edge) in edges {
+        graph.connect_with_gain(
+            src_node,
+            dst_node,
+            &[(edge.src_port, edge.dst_port)],
+            edge.gain,
+            true,
+        )?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_core::{
+        channel_config::{ChannelCount, NonZeroChannelCount},
+        event::ParamData,
+    };
+    use firewheel_nodes::{
+        svf::SvfStereoNode,
+        volume::{VolumeNode, VolumeNodeConfig},
+    };
+
+    use crate::{
+        backend::dummy_backend::DummyBackend, error::DeserializeGraphError, FirewheelConfig,
+        FirewheelCtx,
+    };
+
+    use super::*;
+
+    #[test]
+    fn graph_round_trips_through_json() {
+        fn registered_ctx() -> FirewheelCtx<DummyBackend> {
+            let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+            cx.node_registry_mut()
+                .register::<SvfStereoNode>("svf_stereo");
+            cx.node_registry_mut().register::<VolumeNode>("volume");
+            cx
+        }
+
+        let mut cx = registered_ctx();
+        let filter = cx.add_node_with_params(SvfStereoNode::default(), None);
+        let volume = cx.add_node_with_params(
+            VolumeNode::from_linear(0.5),
+            Some(VolumeNodeConfig {
+                channels: NonZeroChannelCount::MONO,
+            }),
+        );
+        let graph_out = cx.graph_out_node_id();
+        cx.connect(filter, volume, &[(1, 0)], false).unwrap();
+        cx.connect(volume, graph_out, &[(0, 0), (0, 1)], false)
+            .unwrap();
+
+        // Parameters changed after the node was added are captured too.
+        cx.set_param(filter, "cutoff_hz", 2_000.0f32).unwrap();
+
+        let original = cx.serialize_graph().unwrap();
+        let json = serde_json::to_string(&original).unwrap();
+        let descriptor: GraphDescriptor = serde_json::from_str(&json).unwrap();
+
+        let mut loaded = registered_ctx();
+        let ids = loaded.deserialize_graph(&descriptor).unwrap();
+        assert_eq!(ids.len(), 2);
+
+        // The loaded graph has the same topology and parameters.
+        assert_eq!(loaded.serialize_graph().unwrap(), original);
+        assert_eq!(loaded.edges().count(), 3);
+        assert!(loaded
+            .incoming_edges(ids[1])
+            .all(|edge| edge.src_node == ids[0] && edge.src_port == 1));
+        assert!(matches!(
+            loaded.get_param(ids[0], "cutoff_hz"),
+            Some(ParamData::F32(cutoff)) if cutoff == 2_000.0
+        ));
+        assert_eq!(
+            loaded
+                .node_info(ids[1])
+                .unwrap()
+                .info
+                .channel_config
+                .num_inputs,
+            ChannelCount::MONO
+        );
+
+        // Unknown tags are rejected without adding any nodes.
+        let mut unknown = descriptor.clone();
+        unknown.nodes[1].type_tag = "reverb".into();
+        let mut empty = registered_ctx();
+        assert!(matches!(
+            empty.deserialize_graph(&unknown),
+            Err(DeserializeGraphError::UnknownTypeTag(tag)) if tag == "reverb"
+        ));
+        assert_eq!(empty.nodes().count(), 2);
+    }
+}
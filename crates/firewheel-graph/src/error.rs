@@ -1,7 +1,10 @@
 use core::error::Error;
 use firewheel_core::{channel_config::ChannelCount, node::NodeID};
 
-use crate::graph::{Edge, EdgeID, PortIdx};
+use crate::{
+    graph::{Edge, EdgeID, PortIdx},
+    probe::ProbePoint,
+};
 
 /// An error occurred while attempting to add an edge to the graph.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
@@ -90,6 +93,60 @@ pub enum UpdateError<E: Error> {
     /// dummy audio stream), should be started as soon as possible.
     #[error("The audio stream stopped unexpectedly: {0}")]
     StreamStoppedUnexpectedly(Option<E>),
+    /// Events were rejected because the context's pending event queue was
+    /// full. This is only returned when using
+    /// [`EventQueueOverflowPolicy::Error`][crate::EventQueueOverflowPolicy::Error].
+    ///
+    /// The value is the number of events that were rejected since the last
+    /// call to [`FirewheelCtx::update`][crate::context::FirewheelCtx::update].
+    #[error("The Firewheel event queue overflowed and {0} events were rejected")]
+    EventQueueOverflow(usize),
+}
+
+/// An error while setting a parameter by path in
+/// [`FirewheelCtx`][crate::context::FirewheelCtx].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum SetParamError {
+    /// The given node was not found in the graph.
+    #[error("Could not set parameter: could not find node with ID {0:?}")]
+    NodeNotFound(NodeID),
+    /// The given node was not added with
+    /// [`FirewheelCtx::add_node_with_params`][crate::context::FirewheelCtx::add_node_with_params].
+    #[error("Could not set parameter: node with ID {0:?} does not have accessible parameters")]
+    ParamsNotAccessible(NodeID),
+    /// The path does not match any parameter on the node.
+    #[error("Could not set parameter: the path does not match any parameter")]
+    InvalidPath,
+    /// The value does not match the type of the parameter.
+    #[error("Could not set parameter: the value does not match the type of the parameter")]
+    InvalidData,
+}
+
+/// An error while applying a parameter preset in
+/// [`FirewheelCtx`][crate::context::FirewheelCtx].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ApplyPresetError {
+    /// The given node was not found in the graph.
+    #[error("Could not apply preset: could not find node with ID {0:?}")]
+    NodeNotFound(NodeID),
+    /// The given node was not added with
+    /// [`FirewheelCtx::add_node_with_params`][crate::context::FirewheelCtx::add_node_with_params].
+    #[error("Could not apply preset: node with ID {0:?} does not have accessible parameters")]
+    ParamsNotAccessible(NodeID),
+    /// No preset with the given name was registered for the type of the node.
+    #[error("Could not apply preset: no preset with that name is registered for the node's type")]
+    PresetNotFound,
+}
+
+/// An error while adding a probe in [`FirewheelCtx`][crate::context::FirewheelCtx].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum AddProbeError {
+    /// The given node was not found in the graph.
+    #[error("Could not add probe: could not find node with ID {0:?}")]
+    NodeNotFound(NodeID),
+    /// The node has no ports at the given probe point.
+    #[error("Could not add probe: node with ID {node:?} has no {point:?} ports")]
+    NoPorts { node: NodeID, point: ProbePoint },
 }
 
 /// An error while removing a node in [`FirewheelCtx`][crate::context::FirewheelCtx].
@@ -102,3 +159,85 @@ pub enum RemoveNodeError {
     #[error("Removing the graph out node is not allowed")]
     CannotRemoveGraphOutNode,
 }
+
+/// An error while rebuilding a node in [`FirewheelCtx`][crate::context::FirewheelCtx].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum RebuildNodeError {
+    /// The given node was not found in the graph.
+    #[error("Could not rebuild node: could not find node with ID {0:?}")]
+    NodeNotFound(NodeID),
+    /// Rebuilding the graph in node is not allowed.
+    #[error("Rebuilding the graph in node is not allowed")]
+    CannotRebuildGraphInNode,
+    /// Rebuilding the graph out node is not allowed.
+    #[error("Rebuilding the graph out node is not allowed")]
+    CannotRebuildGraphOutNode,
+}
+
+/// An error while bouncing a node in [`FirewheelCtx`][crate::context::FirewheelCtx].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum BounceNodeError {
+    /// The given node was not found in the graph.
+    #[error("Could not bounce node: could not find node with ID {0:?}")]
+    NodeNotFound(NodeID),
+    /// The given node has no output ports.
+    #[error("Could not bounce node: node with ID {0:?} has no output ports")]
+    NoOutputs(NodeID),
+    /// The copy of the sub-graph failed to compile.
+    #[error("Could not bounce node: the sub-graph failed to compile: {0}")]
+    GraphCompileError(#[from] CompileGraphError),
+}
+
+/// An error while serializing the audio graph in
+/// [`FirewheelCtx::serialize_graph`][crate::context::FirewheelCtx::serialize_graph].
+#[cfg(feature = "serde")]
+#[derive(Debug, thiserror::Error)]
+pub enum SerializeGraphError {
+    /// The type of a node was not registered in the
+    /// [`NodeRegistry`][crate::descriptor::NodeRegistry].
+    #[error(
+        "Could not serialize graph: node with ID {node:?} of type {type_name} is not registered"
+    )]
+    UnregisteredType {
+        node: NodeID,
+        type_name: &'static str,
+    },
+    /// The node was not added with a constructor whose configuration can be
+    /// read (i.e. it was added with
+    /// [`FirewheelCtx::add_dyn_node`][crate::context::FirewheelCtx::add_dyn_node]).
+    #[error(
+        "Could not serialize graph: the configuration of node with ID {0:?} is not accessible"
+    )]
+    NotSerializable(NodeID),
+    /// The parameters or configuration of a node failed to serialize.
+    #[error("Could not serialize graph: failed to serialize node with ID {node:?}: {error}")]
+    Serde {
+        node: NodeID,
+        error: serde_json::Error,
+    },
+}
+
+/// An error while deserializing an audio graph in
+/// [`FirewheelCtx::deserialize_graph`][crate::context::FirewheelCtx::deserialize_graph].
+#[cfg(feature = "serde")]
+#[derive(Debug, thiserror::Error)]
+pub enum DeserializeGraphError {
+    /// No node type was registered with the given tag in the
+    /// [`NodeRegistry`][crate::descriptor::NodeRegistry].
+    #[error("Could not deserialize graph: no node type is registered with the tag {0:?}")]
+    UnknownTypeTag(bevy_platform::prelude::String),
+    /// The parameters or configuration of a node failed to deserialize.
+    #[error("Could not deserialize graph: failed to deserialize node at index {index}: {error}")]
+    Serde {
+        index: usize,
+        error: serde_json::Error,
+    },
+    /// An edge referred to a node index which is out of range.
+    #[error(
+        "Could not deserialize graph: an edge refers to node index {0}, which is out of range"
+    )]
+    NodeIndexOutOfRange(usize),
+    /// An edge could not be added to the graph.
+    #[error("Could not deserialize graph: {0}")]
+    AddEdge(#[from] AddEdgeError),
+}
@@ -1,4 +1,4 @@
-use core::any::Any;
+use core::any::{type_name, Any};
 use core::fmt::Debug;
 use core::hash::Hash;
 
@@ -7,22 +7,26 @@ use bevy_platform::prelude::{Box, Vec};
 
 use bevy_platform::collections::HashMap;
 use firewheel_core::channel_config::{ChannelConfig, ChannelCount};
+use firewheel_core::diff::{Diff, DynParams, Patch};
 use firewheel_core::event::NodeEvent;
 use firewheel_core::node::{ConstructProcessorContext, UpdateContext};
 use firewheel_core::StreamInfo;
 use smallvec::SmallVec;
 use thunderdome::Arena;
 
-use crate::error::{AddEdgeError, CompileGraphError, RemoveNodeError};
+use crate::error::{AddEdgeError, AddProbeError, CompileGraphError, RemoveNodeError};
 use crate::graph::dummy_node::{DummyNode, DummyNodeConfig};
+use crate::input_activity::InputActivityConfig;
+use crate::probe::{Probe, ProbePoint};
 use crate::FirewheelConfig;
 use firewheel_core::node::{
-    AudioNode, AudioNodeInfo, AudioNodeInfoInner, Constructor, DynAudioNode, NodeID,
+    AudioNode, AudioNodeInfo, AudioNodeInfoInner, AudioNodeProcessor, Constructor, DynAudioNode,
+    NodeID,
 };
 
 pub(crate) use self::compiler::{CompiledSchedule, NodeHeapData, ScheduleHeapData};
 
-pub use self::compiler::{Edge, EdgeID, NodeEntry, PortIdx};
+pub use self::compiler::{Edge, EdgeID, NodeEntry, NodeOrderHint, PortIdx};
 
 mod compiler;
 mod dummy_node;
@@ -35,6 +39,72 @@ struct EdgeHash {
     pub dst_port: PortIdx,
 }
 
+/// An audio node whose processor was constructed ahead of time.
+///
+/// Constructing the processor of a heavy node (i.e. a convolution node with
+/// large buffers) can take a while and allocate a lot of memory. A
+/// `PrewarmedNode` can be built on a worker thread and then added with
+/// [`FirewheelCtx::add_prewarmed_node`], so the processor doesn't need to be
+/// constructed when the graph is next compiled.
+///
+/// The pre-built processor is only used if it was built for the same sample
+/// rate and maximum block size as the running stream, and if the context is
+/// not in deterministic mode. Otherwise it is discarded and a new processor
+/// is constructed as usual.
+///
+/// Note, because the node has not been added to a graph yet, the processor
+/// is constructed with [`NodeID::DANGLING`] as its node ID.
+///
+/// [`FirewheelCtx::add_prewarmed_node`]: crate::FirewheelCtx::add_prewarmed_node
+pub struct PrewarmedNode {
+    info: AudioNodeInfoInner,
+    dyn_node: Box<dyn DynAudioNode + Send>,
+    type_name: &'static str,
+    processor: Box<dyn AudioNodeProcessor>,
+    stream_info: StreamInfo,
+}
+
+impl PrewarmedNode {
+    /// Construct the node and its processor for the given stream.
+    ///
+    /// Use [`FirewheelCtx::stream_info`] to get the information of the
+    /// running stream.
+    ///
+    /// [`FirewheelCtx::stream_info`]: crate::FirewheelCtx::stream_info
+    pub fn new<T: AudioNode + Send + 'static>(
+        node: T,
+        config: Option<T::Configuration>,
+        stream_info: &StreamInfo,
+    ) -> Self
+    where
+        T::Configuration: Send,
+    {
+        let constructor = Constructor::new(node, config);
+        let mut info: AudioNodeInfoInner = constructor.info().into();
+
+        let processor = constructor.construct_processor(ConstructProcessorContext::new(
+            NodeID::DANGLING,
+            stream_info,
+            None,
+            info.stepped_params,
+            &mut info.custom_state,
+        ));
+
+        Self {
+            info,
+            dyn_node: Box::new(constructor),
+            type_name: type_name::<T>(),
+            processor,
+            stream_info: stream_info.clone(),
+        }
+    }
+
+    /// The stream this node's processor was constructed for.
+    pub fn stream_info(&self) -> &StreamInfo {
+        &self.stream_info
+    }
+}
+
 /// The audio graph interface.
 pub(crate) struct AudioGraph {
     nodes: Arena<NodeEntry>,
@@ -50,6 +120,9 @@ pub(crate) struct AudioGraph {
     nodes_to_call_update_method: Vec<NodeID>,
 
     prev_node_arena_capacity: usize,
+    deterministic_seed: Option<u64>,
+    sleep_unconsumed_nodes: bool,
+    previewed_node: Option<NodeID>,
 }
 
 impl AudioGraph {
@@ -64,7 +137,7 @@ impl AudioGraph {
         };
         let graph_out_config = DummyNodeConfig {
             channel_config: ChannelConfig {
-                num_inputs: config.num_graph_outputs,
+                num_inputs: config.graph_output_channels(),
                 num_outputs: ChannelCount::ZERO,
             },
         };
@@ -79,6 +152,7 @@ impl AudioGraph {
             )),
         );
         nodes[graph_in_id.0].id = graph_in_id;
+        nodes[graph_in_id.0].type_name = type_name::<DummyNode>();
 
         let graph_out_id = NodeID(
             nodes.insert(NodeEntry::new(
@@ -90,6 +164,7 @@ impl AudioGraph {
             )),
         );
         nodes[graph_out_id.0].id = graph_out_id;
+        nodes[graph_out_id.0].type_name = type_name::<DummyNode>();
 
         Self {
             nodes,
@@ -104,6 +179,9 @@ impl AudioGraph {
             active_nodes_to_remove: HashMap::with_capacity(config.initial_node_capacity as usize),
             nodes_to_call_update_method: Vec::new(),
             prev_node_arena_capacity: 0,
+            deterministic_seed: config.deterministic_seed,
+            sleep_unconsumed_nodes: config.sleep_unconsumed_nodes,
+            previewed_node: None,
         }
     }
 
@@ -132,6 +210,41 @@ impl AudioGraph {
                 .insert(NodeEntry::new(info, Box::new(constructor))),
         );
         self.nodes[new_id.0].id = new_id;
+        self.nodes[new_id.0].type_name = type_name::<T>();
+
+        if call_update_method {
+            self.nodes_to_call_update_method.push(new_id);
+        }
+
+        self.needs_compile = true;
+
+        new_id
+    }
+
+    /// Add a node to the audio graph, keeping a copy of its parameters so
+    /// they can be accessed by path with [`AudioGraph::node_params`].
+    pub fn add_node_with_params<T: AudioNode + Diff + Patch + Clone + 'static>(
+        &mut self,
+        node: T,
+        config: Option<T::Configuration>,
+    ) -> NodeID {
+        let params = node.clone();
+        let new_id = self.add_node(node, config);
+        self.nodes[new_id.0].params = Some(Box::new(params));
+
+        new_id
+    }
+
+    /// Add a node whose processor was constructed ahead of time to the audio graph.
+    pub fn add_prewarmed_node(&mut self, node: PrewarmedNode) -> NodeID {
+        let call_update_method = node.info.call_update_method;
+
+        let mut entry = NodeEntry::new(node.info, node.dyn_node);
+        entry.type_name = node.type_name;
+        entry.prewarmed_processor = Some((node.processor, node.stream_info));
+
+        let new_id = NodeID(self.nodes.insert(entry));
+        self.nodes[new_id.0].id = new_id;
 
         if call_update_method {
             self.nodes_to_call_update_method.push(new_id);
@@ -149,6 +262,7 @@ impl AudioGraph {
 
         let new_id = NodeID(self.nodes.insert(NodeEntry::new(info, Box::new(node))));
         self.nodes[new_id.0].id = new_id;
+        self.nodes[new_id.0].type_name = type_name::<T>();
 
         if call_update_method {
             self.nodes_to_call_update_method.push(new_id);
@@ -201,11 +315,241 @@ impl AudioGraph {
         Ok(removed_edges)
     }
 
+    /// Set whether or not the output of a node is muted.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_muted(&mut self, node_id: NodeID, muted: bool) -> bool {
+        self.set_node_flag(node_id, muted, |n| &mut n.muted)
+    }
+
+    /// Set whether or not a node is soloed.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_soloed(&mut self, node_id: NodeID, soloed: bool) -> bool {
+        self.set_node_flag(node_id, soloed, |n| &mut n.soloed)
+    }
+
+    /// Set whether or not a node stays audible while another node is soloed.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_solo_safe(&mut self, node_id: NodeID, solo_safe: bool) -> bool {
+        self.set_node_flag(node_id, solo_safe, |n| &mut n.solo_safe)
+    }
+
+    /// Set whether or not a node is a voice that may be culled to stay within
+    /// the polyphony and CPU budget.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_voice(&mut self, node_id: NodeID, voice: bool) -> bool {
+        self.set_node_flag(node_id, voice, |n| &mut n.voice)
+    }
+
+    /// Set whether or not the wet path of a node is bypassed.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_wet_bypassed(&mut self, node_id: NodeID, wet_bypassed: bool) -> bool {
+        self.set_node_flag(node_id, wet_bypassed, |n| &mut n.wet_bypassed)
+    }
+
+    /// Enable or disable reporting when the input of a node transitions
+    /// between silent and active.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_input_activity(
+        &mut self,
+        node_id: NodeID,
+        config: Option<InputActivityConfig>,
+    ) -> bool {
+        let Some(node_entry) = self.nodes.get_mut(node_id.0) else {
+            return false;
+        };
+
+        if node_entry.input_activity != config {
+            node_entry.input_activity = config;
+            self.needs_compile = true;
+        }
+
+        true
+    }
+
+    /// Set where a node prefers to be placed in the processing schedule.
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_order_hint(&mut self, node_id: NodeID, hint: NodeOrderHint) -> bool {
+        let Some(node_entry) = self.nodes.get_mut(node_id.0) else {
+            return false;
+        };
+
+        if node_entry.order_hint != hint {
+            node_entry.order_hint = hint;
+            self.needs_compile = true;
+        }
+
+        true
+    }
+
+    /// Set the latency of a node in frames (samples in a single channel of
+    /// audio), overriding the latency it reported in its [`AudioNodeInfo`].
+    ///
+    /// Returns `false` if the node does not exist.
+    pub fn set_node_latency_frames(&mut self, node_id: NodeID, latency_frames: u32) -> bool {
+        let Some(node_entry) = self.nodes.get_mut(node_id.0) else {
+            return false;
+        };
+
+        if node_entry.info.latency_frames != latency_frames {
+            node_entry.info.latency_frames = latency_frames;
+            self.needs_compile = true;
+        }
+
+        true
+    }
+
+    /// Set the node being previewed by the context, which keeps it (and the
+    /// nodes feeding it) awake.
+    pub(crate) fn set_previewed_node(&mut self, node_id: Option<NodeID>) {
+        if self.previewed_node != node_id {
+            self.previewed_node = node_id;
+            self.needs_compile |= self.sleep_unconsumed_nodes;
+        }
+    }
+
+    fn set_node_flag(
+        &mut self,
+        node_id: NodeID,
+        value: bool,
+        flag: impl FnOnce(&mut NodeEntry) -> &mut bool,
+    ) -> bool {
+        let Some(node_entry) = self.nodes.get_mut(node_id.0) else {
+            return false;
+        };
+
+        let flag = (flag)(node_entry);
+        if *flag != value {
+            *flag = value;
+            self.needs_compile = true;
+        }
+
+        true
+    }
+
+    /// Attach a level probe to the input or output ports of a node.
+    ///
+    /// If a probe is already attached at that point, then a handle to the
+    /// existing probe is returned.
+    pub fn add_probe(
+        &mut self,
+        node_id: NodeID,
+        point: ProbePoint,
+    ) -> Result<Probe, AddProbeError> {
+        let node_entry = self
+            .nodes
+            .get_mut(node_id.0)
+            .ok_or(AddProbeError::NodeNotFound(node_id))?;
+
+        if let Some(probe) = node_entry.probes.get(point) {
+            return Ok(probe.clone());
+        }
+
+        let num_channels = match point {
+            ProbePoint::Input => node_entry.info.channel_config.num_inputs,
+            ProbePoint::Output => node_entry.info.channel_config.num_outputs,
+        };
+        if num_channels.get() == 0 {
+            return Err(AddProbeError::NoPorts {
+                node: node_id,
+                point,
+            });
+        }
+
+        let probe = Probe::new(num_channels);
+        *node_entry.probes.get_mut(point) = Some(probe.clone());
+
+        self.needs_compile = true;
+
+        Ok(probe)
+    }
+
+    /// Detach the level probe from the input or output ports of a node.
+    ///
+    /// Returns `true` if a probe was removed.
+    pub fn remove_probe(&mut self, node_id: NodeID, point: ProbePoint) -> bool {
+        let Some(node_entry) = self.nodes.get_mut(node_id.0) else {
+            return false;
+        };
+
+        if node_entry.probes.get_mut(point).take().is_some() {
+            self.needs_compile = true;
+            true
+        } else {
+            false
+        }
+    }
+
     /// Get information about a node in the graph.
     pub fn node_info(&self, id: NodeID) -> Option<&NodeEntry> {
         self.nodes.get(id.0)
     }
 
+    /// Construct a fresh processor for a node in the graph, for rendering a
+    /// copy of it in another graph (see [`FirewheelCtx::bounce_node`]).
+    ///
+    /// The processor is constructed from the node as it was added, with the
+    /// same deterministic seed the node has in this graph.
+    ///
+    /// [`FirewheelCtx::bounce_node`]: crate::FirewheelCtx::bounce_node
+    pub(crate) fn prewarm_copy(
+        &self,
+        id: NodeID,
+        stream_info: &StreamInfo,
+    ) -> Option<PrewarmedNode> {
+        let entry = self.nodes.get(id.0)?;
+
+        let mut info: AudioNodeInfoInner = entry.dyn_node.info().into();
+        let processor = entry
+            .dyn_node
+            .construct_processor(ConstructProcessorContext::new(
+                NodeID::DANGLING,
+                stream_info,
+                self.deterministic_seed.map(|seed| node_seed(seed, id)),
+                info.stepped_params,
+                &mut info.custom_state,
+            ));
+
+        // The copy always uses the processor constructed above, so it only
+        // needs a placeholder to construct from.
+        let dyn_node = Constructor::new(
+            DummyNode,
+            Some(DummyNodeConfig {
+                channel_config: info.channel_config,
+            }),
+        );
+
+        Some(PrewarmedNode {
+            info,
+            dyn_node: Box::new(dyn_node),
+            type_name: entry.type_name,
+            processor,
+            stream_info: stream_info.clone(),
+        })
+    }
+
+    /// Get the copy of a node's parameters, if it was added with
+    /// [`AudioGraph::add_node_with_params`].
+    pub fn node_params(&self, id: NodeID) -> Option<&(dyn DynParams + 'static)> {
+        self.nodes
+            .get(id.0)
+            .and_then(|node_entry| node_entry.params.as_deref())
+    }
+
+    /// Get a mutable reference to the copy of a node's parameters, if it was
+    /// added with [`AudioGraph::add_node_with_params`].
+    pub fn node_params_mut(&mut self, id: NodeID) -> Option<&mut (dyn DynParams + 'static)> {
+        self.nodes
+            .get_mut(id.0)
+            .and_then(|node_entry| node_entry.params.as_deref_mut())
+    }
+
     /// Get an immutable reference to the custom state of a node.
     pub fn node_state<T: 'static>(&self, id: NodeID) -> Option<&T> {
         self.node_state_dyn(id).and_then(|s| s.downcast_ref())
@@ -213,9 +557,13 @@ impl AudioGraph {
 
     /// Get a type-erased, immutable reference to the custom state of a node.
     pub fn node_state_dyn(&self, id: NodeID) -> Option<&dyn Any> {
-        self.nodes
-            .get(id.0)
-            .and_then(|node_entry| node_entry.info.custom_state.as_ref().map(|s| s.as_ref()))
+        self.nodes.get(id.0).and_then(|node_entry| {
+            node_entry
+                .info
+                .custom_state
+                .as_ref()
+                .map(|s| s.as_ref() as &dyn Any)
+        })
     }
 
     /// Get a mutable reference to the custom state of a node.
@@ -225,9 +573,13 @@ impl AudioGraph {
 
     /// Get a type-erased, mutable reference to the custom state of a node.
     pub fn node_state_dyn_mut(&mut self, id: NodeID) -> Option<&mut dyn Any> {
-        self.nodes
-            .get_mut(id.0)
-            .and_then(|node_entry| node_entry.info.custom_state.as_mut().map(|s| s.as_mut()))
+        self.nodes.get_mut(id.0).and_then(|node_entry| {
+            node_entry
+                .info
+                .custom_state
+                .as_mut()
+                .map(|s| s.as_mut() as &mut dyn Any)
+        })
     }
 
     /// Get a list of all the existing nodes in the graph.
@@ -265,7 +617,7 @@ impl AudioGraph {
             self.needs_compile = true;
         }
 
-        let graph_out_node = self.nodes.get_mut(self.graph_in_id.0).unwrap();
+        let graph_out_node = self.nodes.get_mut(self.graph_out_id.0).unwrap();
 
         if channel_config.num_outputs != graph_out_node.info.channel_config.num_inputs {
             let old_num_outputs = graph_out_node.info.channel_config.num_inputs;
@@ -359,6 +711,7 @@ impl AudioGraph {
                 src_port,
                 dst_node,
                 dst_port,
+                gain: 1.0,
             }));
             self.edges[new_edge_id.0].id = new_edge_id;
             self.existing_edges.insert(
@@ -387,6 +740,72 @@ impl AudioGraph {
         Ok(edge_ids)
     }
 
+    /// Connect each output port of `src_node` to the input port of
+    /// `dst_node` with the same index, up to the smaller of the two port
+    /// counts.
+    ///
+    /// If `fan_out_mono` is `true` and `src_node` has a single output port,
+    /// then that port is connected to every input port of `dst_node`
+    /// instead.
+    pub fn connect_all(
+        &mut self,
+        src_node: NodeID,
+        dst_node: NodeID,
+        fan_out_mono: bool,
+        check_for_cycles: bool,
+    ) -> Result<SmallVec<[EdgeID; 4]>, AddEdgeError> {
+        let num_outputs = self
+            .nodes
+            .get(src_node.0)
+            .ok_or(AddEdgeError::SrcNodeNotFound(src_node))?
+            .info
+            .channel_config
+            .num_outputs
+            .get();
+        let num_inputs = self
+            .nodes
+            .get(dst_node.0)
+            .ok_or(AddEdgeError::DstNodeNotFound(dst_node))?
+            .info
+            .channel_config
+            .num_inputs
+            .get();
+
+        let ports: SmallVec<[(PortIdx, PortIdx); 8]> = if fan_out_mono && num_outputs == 1 {
+            (0..num_inputs).map(|dst_port| (0, dst_port)).collect()
+        } else {
+            (0..num_outputs.min(num_inputs))
+                .map(|port| (port, port))
+                .collect()
+        };
+
+        self.connect(src_node, dst_node, &ports, check_for_cycles)
+    }
+
+    /// Add connections (edges) between two nodes to the graph, with the
+    /// given gain (in raw amplitude) applied to each one.
+    ///
+    /// This is the same as [`AudioGraph::connect`], except that the signal
+    /// on each edge is scaled by `gain` when it is summed into the
+    /// destination port. If an edge already exists, then its gain is
+    /// updated.
+    pub fn connect_with_gain(
+        &mut self,
+        src_node: NodeID,
+        dst_node: NodeID,
+        ports_src_dst: &[(PortIdx, PortIdx)],
+        gain: f32,
+        check_for_cycles: bool,
+    ) -> Result<SmallVec<[EdgeID; 4]>, AddEdgeError> {
+        let edge_ids = self.connect(src_node, dst_node, ports_src_dst, check_for_cycles)?;
+
+        for edge_id in edge_ids.iter() {
+            self.set_edge_gain(*edge_id, gain);
+        }
+
+        Ok(edge_ids)
+    }
+
     /// Remove connections (edges) between two nodes from the graph.
     ///
     /// * `src_node` - The ID of the source node.
@@ -473,6 +892,27 @@ impl AudioGraph {
         self.edges.get(edge_id.0)
     }
 
+    /// Set the gain (in raw amplitude) applied to the signal on the given
+    /// edge when it is summed into the destination port.
+    ///
+    /// Note, changing the gain recompiles the audio graph, so use a volume
+    /// node instead for gain that changes often (i.e. automation).
+    ///
+    /// If the edge did not exist in this graph, then `false` will be
+    /// returned.
+    pub fn set_edge_gain(&mut self, edge_id: EdgeID, gain: f32) -> bool {
+        let Some(edge) = self.edges.get_mut(edge_id.0) else {
+            return false;
+        };
+
+        if edge.gain != gain {
+            edge.gain = gain;
+            self.needs_compile = true;
+        }
+
+        true
+    }
+
     fn remove_edges_with_input_port(
         &mut self,
         node_id: NodeID,
@@ -553,16 +993,43 @@ impl AudioGraph {
             if !entry.processor_constructed {
                 entry.processor_constructed = true;
 
-                let cx = ConstructProcessorContext::new(
-                    entry.id,
-                    stream_info,
-                    &mut entry.info.custom_state,
-                );
+                // Use the processor that was constructed ahead of time if it
+                // is compatible with the current stream.
+                let prewarmed_processor = entry
+                    .prewarmed_processor
+                    .take()
+                    .filter(|(_, prewarmed_stream_info)| {
+                        self.deterministic_seed.is_none()
+                            && prewarmed_stream_info.sample_rate == stream_info.sample_rate
+                            && prewarmed_stream_info.max_block_frames
+                                == stream_info.max_block_frames
+                            && prewarmed_stream_info.min_param_ramp_seconds
+                                == stream_info.min_param_ramp_seconds
+                    })
+                    .map(|(processor, _)| processor);
+
+                let processor = match prewarmed_processor {
+                    Some(processor) => processor,
+                    None => {
+                        let cx = ConstructProcessorContext::new(
+                            entry.id,
+                            stream_info,
+                            self.deterministic_seed
+                                .map(|seed| node_seed(seed, entry.id)),
+                            entry.info.stepped_params,
+                            &mut entry.info.custom_state,
+                        );
+
+                        entry.dyn_node.construct_processor(cx)
+                    }
+                };
 
                 new_node_processors.push(NodeHeapData {
                     id: entry.id,
-                    processor: entry.dyn_node.construct_processor(cx),
+                    debug_name: entry.info.debug_name,
+                    processor,
                     is_pre_process: entry.info.channel_config.is_empty(),
+                    analysis_only: entry.info.analysis_only,
                 });
             }
         }
@@ -610,6 +1077,8 @@ impl AudioGraph {
             self.graph_in_id,
             self.graph_out_id,
             max_block_frames,
+            self.sleep_unconsumed_nodes,
+            self.previewed_node,
         )
     }
 
@@ -621,12 +1090,19 @@ impl AudioGraph {
         let mut cull_list = false;
         for node_id in self.nodes_to_call_update_method.iter() {
             if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                let latency_frames = node_entry.info.latency_frames;
+
                 node_entry.dyn_node.update(UpdateContext::new(
                     *node_id,
                     stream_info,
                     &mut node_entry.info.custom_state,
+                    &mut node_entry.info.latency_frames,
                     event_queue,
                 ));
+
+                if node_entry.info.latency_frames != latency_frames {
+                    self.needs_compile = true;
+                }
             } else {
                 cull_list = true;
             }
@@ -638,3 +1114,92 @@ impl AudioGraph {
         }
     }
 }
+
+/// Derive a seed unique to the given node from the context's seed (using
+/// the SplitMix64 finalizer).
+fn node_seed(context_seed: u64, node_id: NodeID) -> u64 {
+    let mut z = context_seed ^ node_id.0.to_bits().wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use firewheel_core::node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext,
+    };
+    use firewheel_nodes::convolution::{ConvolutionNode, ConvolutionNodeState};
+
+    use crate::{
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig,
+    };
+
+    use super::*;
+
+    /// Counts the number of times the processor of the wrapped node is
+    /// constructed.
+    struct CountConstructions<T> {
+        node: T,
+        count: Arc<AtomicUsize>,
+    }
+
+    impl<T: AudioNode> AudioNode for CountConstructions<T> {
+        type Configuration = T::Configuration;
+
+        fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+            self.node.info(config)
+        }
+
+        fn construct_processor(
+            &self,
+            config: &Self::Configuration,
+            cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            self.node.construct_processor(config, cx)
+        }
+    }
+
+    #[test]
+    fn prewarmed_node_is_not_constructed_again() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // Construct the node on a worker thread.
+        let count = Arc::new(AtomicUsize::new(0));
+        let node = CountConstructions {
+            node: ConvolutionNode::<2>::default(),
+            count: Arc::clone(&count),
+        };
+        let stream_info = cx.stream_info().unwrap().clone();
+        let prewarmed = std::thread::spawn(move || PrewarmedNode::new(node, None, &stream_info))
+            .join()
+            .unwrap();
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        let node_id = cx.add_prewarmed_node(prewarmed);
+        connect_stereo(&mut cx, graph_in, node_id);
+        connect_stereo(&mut cx, node_id, graph_out);
+        cx.update().unwrap();
+
+        // The processor was not constructed again when the graph was compiled.
+        assert_eq!(count.load(Ordering::Relaxed), 1);
+        assert!(cx.node_state::<ConvolutionNodeState>(node_id).is_some());
+
+        // With no impulse response loaded, the node passes its input through.
+        let input = vec![1.0; 256 * 2];
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+        assert_eq!(output, input);
+    }
+}
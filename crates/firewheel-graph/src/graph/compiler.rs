@@ -1,23 +1,97 @@
 use alloc::{collections::VecDeque, rc::Rc};
-use firewheel_core::node::{AudioNodeInfoInner, DynAudioNode, NodeID};
+use core::hash::{Hash, Hasher};
+use firewheel_core::{
+    channel_config::ChannelConfig,
+    diff::DynParams,
+    node::{AudioNodeInfoInner, AudioNodeProcessor, DynAudioNode, NodeID},
+    StreamInfo,
+};
 use smallvec::SmallVec;
 use thunderdome::Arena;
 
 #[cfg(not(feature = "std"))]
 use bevy_platform::prelude::{vec, Box, Vec};
 
-use crate::error::CompileGraphError;
+use crate::{error::CompileGraphError, input_activity::InputActivityConfig, probe::NodeProbes};
 
 mod schedule;
 
 pub use schedule::{CompiledSchedule, NodeHeapData, ScheduleHeapData};
 use schedule::{InBufferAssignment, OutBufferAssignment, PreProcNode, ScheduledNode};
 
+/// A hint for where a node should be placed in the processing schedule.
+///
+/// A hint never overrides the order required by the connections in the
+/// graph. It only decides between nodes which are otherwise ready to run at
+/// the same time.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeOrderHint {
+    /// No preference.
+    #[default]
+    Default,
+    /// Prefer to run this node as early as possible.
+    First,
+    /// Prefer to run this node as late as possible (i.e. a meter tap that
+    /// should run after the nodes it measures).
+    Last,
+}
+
+impl NodeOrderHint {
+    /// The index of the ready queue used for this hint during scheduling.
+    fn queue_index(self) -> usize {
+        match self {
+            Self::First => 0,
+            Self::Default => 1,
+            Self::Last => 2,
+        }
+    }
+}
+
 pub struct NodeEntry {
     pub id: NodeID,
+    /// The name of the Rust type of this node (i.e.
+    /// `firewheel_nodes::volume::VolumeNode`).
+    pub type_name: &'static str,
     pub info: AudioNodeInfoInner,
     pub dyn_node: Box<dyn DynAudioNode>,
+    /// A copy of the node's parameters, if it was added with
+    /// [`FirewheelCtx::add_node_with_params`].
+    ///
+    /// [`FirewheelCtx::add_node_with_params`]: crate::FirewheelCtx::add_node_with_params
+    pub params: Option<Box<dyn DynParams>>,
     pub processor_constructed: bool,
+    /// Whether or not the output of this node is muted.
+    pub muted: bool,
+    /// Whether or not this node is soloed. While any node is soloed, only
+    /// soloed nodes, the nodes feeding them, the nodes they feed, and
+    /// solo-safe nodes are audible.
+    pub soloed: bool,
+    /// Whether or not this node stays audible while another node is soloed
+    /// (i.e. a reverb return or a talkback channel).
+    pub solo_safe: bool,
+    /// Whether or not this node is a voice that may be culled to stay
+    /// within the context's polyphony and CPU budget.
+    pub voice: bool,
+    /// Whether or not the wet path of this node is bypassed, leaving only
+    /// its dry signal.
+    pub wet_bypassed: bool,
+    /// If set, then the audio thread reports when the input of this node
+    /// transitions between silent and active.
+    pub input_activity: Option<InputActivityConfig>,
+    /// Where this node prefers to be placed in the processing schedule.
+    pub order_hint: NodeOrderHint,
+    /// Whether or not this node is asleep because its output is not
+    /// consumed. See [`FirewheelConfig::sleep_unconsumed_nodes`].
+    ///
+    /// This is updated each time the graph is compiled.
+    ///
+    /// [`FirewheelConfig::sleep_unconsumed_nodes`]: crate::FirewheelConfig::sleep_unconsumed_nodes
+    pub asleep: bool,
+    /// The level probes attached to this node.
+    pub(crate) probes: NodeProbes,
+    /// A processor that was constructed ahead of time, along with the
+    /// stream it was constructed for.
+    pub(crate) prewarmed_processor: Option<(Box<dyn AudioNodeProcessor>, StreamInfo)>,
     /// The edges connected to this node's input ports.
     incoming: SmallVec<[Edge; 4]>,
     /// The edges connected to this node's output ports.
@@ -28,13 +102,35 @@ impl NodeEntry {
     pub fn new(info: AudioNodeInfoInner, dyn_node: Box<dyn DynAudioNode>) -> Self {
         Self {
             id: NodeID::DANGLING,
+            type_name: "",
             info,
             dyn_node,
+            params: None,
             processor_constructed: false,
+            muted: false,
+            soloed: false,
+            solo_safe: false,
+            voice: false,
+            wet_bypassed: false,
+            input_activity: None,
+            order_hint: NodeOrderHint::Default,
+            asleep: false,
+            probes: NodeProbes::default(),
+            prewarmed_processor: None,
             incoming: SmallVec::new(),
             outgoing: SmallVec::new(),
         }
     }
+
+    /// The channel configuration of this node.
+    pub fn channel_config(&self) -> ChannelConfig {
+        self.info.channel_config
+    }
+
+    /// The latency in frames this node reports.
+    pub fn latency_frames(&self) -> u32 {
+        self.info.latency_frames
+    }
 }
 
 /// The index of an input/output port on a particular node.
@@ -46,7 +142,7 @@ pub struct EdgeID(pub(super) thunderdome::Index);
 
 /// An [Edge] is a connection from source node and port to a
 /// destination node and port.
-#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug)]
 pub struct Edge {
     pub id: EdgeID,
     /// The ID of the source node used by this edge.
@@ -57,6 +153,38 @@ pub struct Edge {
     pub dst_node: NodeID,
     /// The ID of the destination port used by this edge.
     pub dst_port: PortIdx,
+    /// The gain (in raw amplitude) applied to the signal on this edge when
+    /// it is summed into the destination port.
+    pub gain: f32,
+}
+
+impl Edge {
+    /// The fields of this edge, with the gain compared by its bits so that
+    /// edges can implement [`Eq`] and [`Hash`].
+    fn key(&self) -> (EdgeID, NodeID, PortIdx, NodeID, PortIdx, u32) {
+        (
+            self.id,
+            self.src_node,
+            self.src_port,
+            self.dst_node,
+            self.dst_port,
+            self.gain.to_bits(),
+        )
+    }
+}
+
+impl PartialEq for Edge {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Edge {}
+
+impl Hash for Edge {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
 }
 
 /// A reference to an abstract buffer during buffer allocation.
@@ -124,10 +252,14 @@ pub fn compile(
     graph_in_id: NodeID,
     graph_out_id: NodeID,
     max_block_frames: usize,
+    sleep_unconsumed_nodes: bool,
+    previewed_node: Option<NodeID>,
 ) -> Result<CompiledSchedule, CompileGraphError> {
     Ok(
         GraphIR::preprocess(nodes, edges, graph_in_id, graph_out_id, max_block_frames)
             .sort_topologically(true)?
+            .solve_silenced_nodes()
+            .solve_sleeping_nodes(sleep_unconsumed_nodes, previewed_node)
             .solve_buffer_requirements()?
             .merge(),
     )
@@ -211,9 +343,14 @@ impl<'a> GraphIR<'a> {
 
     /// Sort the nodes topologically using Kahn's algorithm.
     /// <https://www.geeksforgeeks.org/topological-sorting-indegree-based-solution/>
+    ///
+    /// Nodes that are ready to run are kept in one queue per
+    /// [`NodeOrderHint`], and the queues are drained in the order `First`,
+    /// `Default`, `Last`.
     fn sort_topologically(mut self, build_schedule: bool) -> Result<Self, CompileGraphError> {
         let mut in_degree = vec![0i32; self.nodes.capacity()];
-        let mut queue = VecDeque::with_capacity(self.nodes.len());
+        let mut queues: [VecDeque<u32>; 3] =
+            core::array::from_fn(|_| VecDeque::with_capacity(self.nodes.len()));
 
         if build_schedule {
             self.schedule.reserve(self.nodes.len());
@@ -231,7 +368,7 @@ impl<'a> GraphIR<'a> {
         // Make sure that the graph in node is the first entry in the
         // schedule. Otherwise a different root node could overwrite
         // the buffers assigned to the graph in node.
-        queue.push_back(self.graph_in_id.0.slot());
+        queues[NodeOrderHint::First.queue_index()].push_back(self.graph_in_id.0.slot());
 
         // Enqueue all other nodes with 0 in-degree
         for (_, node_entry) in self.nodes.iter() {
@@ -247,13 +384,13 @@ impl<'a> GraphIR<'a> {
 
                     num_visited += 1;
                 } else {
-                    queue.push_back(node_entry.id.0.slot());
+                    queues[node_entry.order_hint.queue_index()].push_back(node_entry.id.0.slot());
                 }
             }
         }
 
         // BFS traversal
-        while let Some(node_slot) = queue.pop_front() {
+        while let Some(node_slot) = queues.iter_mut().find_map(|queue| queue.pop_front()) {
             num_visited += 1;
 
             let (_, node_entry) = self.nodes.get_by_slot(node_slot).unwrap();
@@ -264,16 +401,23 @@ impl<'a> GraphIR<'a> {
 
                 // If in-degree becomes 0, enqueue it
                 if in_degree[edge.dst_node.0.slot() as usize] == 0 {
-                    queue.push_back(edge.dst_node.0.slot());
+                    let hint = self.nodes[edge.dst_node.0].order_hint;
+                    queues[hint.queue_index()].push_back(edge.dst_node.0.slot());
                 }
             }
 
             if build_schedule {
                 if node_slot != self.graph_out_id.0.slot() {
-                    self.schedule.push(ScheduledNode::new(
+                    let mut scheduled_node = ScheduledNode::new(
                         node_entry.id,
                         node_entry.info.debug_name,
-                    ));
+                        node_entry.probes.clone(),
+                    );
+                    scheduled_node.voice = node_entry.voice;
+                    scheduled_node.wet_bypassed = node_entry.wet_bypassed;
+                    scheduled_node.input_activity = node_entry.input_activity;
+
+                    self.schedule.push(scheduled_node);
                 }
             }
         }
@@ -283,8 +427,12 @@ impl<'a> GraphIR<'a> {
             // schedule by waiting to push it after all other nodes have
             // been pushed. Otherwise a different leaf node could overwrite
             // the buffers assigned to the graph out node.
-            self.schedule
-                .push(ScheduledNode::new(self.graph_out_id, "graph_out"));
+            let graph_out_probes = self.nodes[self.graph_out_id.0].probes.clone();
+            self.schedule.push(ScheduledNode::new(
+                self.graph_out_id,
+                "graph_out",
+                graph_out_probes,
+            ));
         }
 
         // If not all vertices are visited, cycle
@@ -295,6 +443,83 @@ impl<'a> GraphIR<'a> {
         Ok(self)
     }
 
+    /// Find the nodes whose outputs are silenced by mute and solo.
+    fn solve_silenced_nodes(mut self) -> Self {
+        let any_soloed = self.nodes.iter().any(|(_, n)| n.soloed);
+        if !any_soloed && !self.nodes.iter().any(|(_, n)| n.muted) {
+            return self;
+        }
+
+        let mut audible = vec![!any_soloed; self.nodes.capacity()];
+
+        if any_soloed {
+            // Nodes that feed a soloed node must stay audible.
+            let roots = self
+                .nodes
+                .iter()
+                .filter(|(_, n)| n.soloed)
+                .map(|(_, n)| n.id);
+            mark_reachable(self.nodes, roots, &mut audible, true);
+
+            // Nodes that are fed by a soloed or solo-safe node carry its
+            // signal, so they must stay audible too.
+            let roots = self
+                .nodes
+                .iter()
+                .filter(|(_, n)| n.soloed || n.solo_safe)
+                .map(|(_, n)| n.id);
+            mark_reachable(self.nodes, roots, &mut audible, false);
+        }
+
+        for entry in self.schedule.iter_mut() {
+            let node_entry = &self.nodes[entry.id.0];
+
+            entry.silenced = entry.id != self.graph_out_id
+                && (node_entry.muted || !audible[entry.id.0.slot() as usize]);
+        }
+
+        self
+    }
+
+    /// Find the nodes whose outputs are not consumed by anything.
+    fn solve_sleeping_nodes(mut self, enabled: bool, previewed_node: Option<NodeID>) -> Self {
+        for (_, node_entry) in self.nodes.iter_mut() {
+            node_entry.asleep = false;
+        }
+
+        if !enabled {
+            return self;
+        }
+
+        let mut consumed = vec![false; self.nodes.capacity()];
+
+        // Nodes with no outputs (i.e. meters) and nodes with probes do
+        // something with their inputs, so everything feeding them is consumed.
+        let roots = self
+            .nodes
+            .iter()
+            .filter(|(_, n)| {
+                n.id == self.graph_out_id
+                    || n.info.channel_config.num_outputs.get() == 0
+                    || n.probes.input.is_some()
+                    || n.probes.output.is_some()
+                    || Some(n.id) == previewed_node
+            })
+            .map(|(_, n)| n.id);
+        mark_reachable(self.nodes, roots, &mut consumed, true);
+
+        for entry in self.schedule.iter_mut() {
+            if entry.id == self.graph_in_id {
+                continue;
+            }
+
+            entry.asleep = !consumed[entry.id.0.slot() as usize];
+            self.nodes[entry.id.0].asleep = entry.asleep;
+        }
+
+        self
+    }
+
     fn solve_buffer_requirements(mut self) -> Result<Self, CompileGraphError> {
         let mut allocator = BufferAllocator::new(64);
         let mut assignment_table: Arena<Rc<BufferRef>> =
@@ -338,12 +563,14 @@ impl<'a> GraphIR<'a> {
                         buffer_index: buffer.idx,
                         //generation: buffer.generation,
                         should_clear: true,
+                        last_reader: true,
                     });
                     buffers_to_release.push(buffer);
-                } else if edges.len() == 1 {
-                    // Case 2: The port is an input, and has exactly one incoming edge. Lookup the
-                    //         corresponding buffer and assign it. Buffer should not be cleared.
-                    //         Release the buffer once the node assignments are done.
+                } else if edges.len() == 1 && edges[0].gain == 1.0 {
+                    // Case 2: The port is an input, and has exactly one incoming edge with unity
+                    //         gain. Lookup the corresponding buffer and assign it. Buffer should
+                    //         not be cleared. Release the buffer once the node assignments are
+                    //         done.
                     let buffer = assignment_table
                         .remove(edges[0].id.0)
                         .expect("No buffer assigned to edge!");
@@ -351,12 +578,14 @@ impl<'a> GraphIR<'a> {
                         buffer_index: buffer.idx,
                         //generation: buffer.generation,
                         should_clear: false,
+                        // No other edge or port still holds on to this buffer.
+                        last_reader: Rc::strong_count(&buffer) == 1,
                     });
                     buffers_to_release.push(buffer);
                 } else {
-                    // Case 3: The port is an input with multiple incoming edges. Compute the
-                    //         summing point, and assign the input buffer assignment to the output
-                    //         of the summing point.
+                    // Case 3: The port is an input with multiple incoming edges (or a single
+                    //         edge with a gain). Compute the summing point, and assign the input
+                    //         buffer assignment to the output of the summing point.
 
                     let sum_buffer = allocator.acquire();
                     let sum_output = OutBufferAssignment {
@@ -375,6 +604,7 @@ impl<'a> GraphIR<'a> {
                                 buffer_index: buf.idx,
                                 //generation: buf.generation,
                                 should_clear: false,
+                                last_reader: false,
                             };
                             allocator.release(buf);
                             assignment
@@ -383,6 +613,7 @@ impl<'a> GraphIR<'a> {
 
                     entry.sum_inputs.push(InsertedSum {
                         input_buffers: sum_inputs,
+                        input_gains: edges.iter().map(|edge| edge.gain).collect(),
                         output_buffer: sum_output,
                     });
 
@@ -392,6 +623,7 @@ impl<'a> GraphIR<'a> {
                         buffer_index: sum_output.buffer_index,
                         //generation: sum_output.generation,
                         should_clear: false,
+                        last_reader: true,
                     });
 
                     buffers_to_release.push(sum_buffer);
@@ -458,8 +690,215 @@ impl<'a> GraphIR<'a> {
     }
 }
 
+/// Mark every node reachable from `roots` (including the roots themselves),
+/// walking either upstream or downstream through the edges.
+fn mark_reachable(
+    nodes: &Arena<NodeEntry>,
+    roots: impl Iterator<Item = NodeID>,
+    visited: &mut [bool],
+    upstream: bool,
+) {
+    let mut stack: Vec<NodeID> = roots.collect();
+
+    while let Some(node_id) = stack.pop() {
+        let node_entry = &nodes[node_id.0];
+
+        let edges = if upstream {
+            node_entry.incoming.iter()
+        } else {
+            node_entry.outgoing.iter()
+        };
+
+        for edge in edges {
+            let next = if upstream {
+                edge.src_node
+            } else {
+                edge.dst_node
+            };
+
+            if !visited[next.0.slot() as usize] {
+                visited[next.0.slot() as usize] = true;
+                stack.push(next);
+            }
+        }
+
+        visited[node_id.0.slot() as usize] = true;
+    }
+}
+
 #[derive(Debug, Clone)]
 struct InsertedSum {
     input_buffers: SmallVec<[InBufferAssignment; 4]>,
+    /// The gain of the edge of each input buffer.
+    input_gains: SmallVec<[f32; 4]>,
     output_buffer: OutBufferAssignment,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use firewheel_core::{
+        channel_config::{ChannelConfig, ChannelCount},
+        event::ProcEvents,
+        node::{
+            AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+            ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+        },
+    };
+    use firewheel_nodes::{
+        noise_generator::{pink::PinkNoiseGenNode, white::WhiteNoiseGenNode},
+        volume::VolumeNode,
+    };
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        probe::ProbePoint,
+        test_util::connect_stereo,
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    #[test]
+    fn solo_safe_node_stays_audible() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let graph_out = cx.graph_out_node_id();
+        let source = cx.add_node(WhiteNoiseGenNode::default(), None);
+        let soloed = cx.add_node(WhiteNoiseGenNode::default(), None);
+        let talkback = cx.add_node(PinkNoiseGenNode::default(), None);
+        cx.connect(source, graph_out, &[(0, 0)], false).unwrap();
+        cx.connect(soloed, graph_out, &[(0, 0)], false).unwrap();
+        cx.connect(talkback, graph_out, &[(0, 1)], false).unwrap();
+
+        let source_level = cx.add_probe(source, ProbePoint::Output).unwrap();
+        let soloed_level = cx.add_probe(soloed, ProbePoint::Output).unwrap();
+        let talkback_level = cx.add_probe(talkback, ProbePoint::Output).unwrap();
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+
+        assert!(cx.set_node_soloed(soloed, true));
+        assert!(cx.set_node_solo_safe(talkback, true));
+        cx.update().unwrap();
+
+        let output = stream.process_block(1024);
+        assert_eq!(source_level.peak_gain(), 0.0);
+        assert!(soloed_level.peak_gain() > 0.0);
+        assert!(talkback_level.peak_gain() > 0.0);
+        assert!(output.iter().skip(1).step_by(2).any(|&s| s != 0.0));
+
+        // Without the solo-safe flag, the talkback is silenced by the solo.
+        cx.set_node_solo_safe(talkback, false);
+        cx.update().unwrap();
+
+        let output = stream.process_block(1024);
+        assert_eq!(talkback_level.peak_gain(), 0.0);
+        assert!(output.iter().skip(1).step_by(2).all(|&s| s == 0.0));
+
+        // Muting wins over solo.
+        cx.set_node_muted(soloed, true);
+        cx.update().unwrap();
+
+        let output = stream.process_block(1024);
+        assert!(output.iter().all(|&s| s == 0.0));
+    }
+
+    /// A generator that counts the number of times it is processed.
+    struct CountProcessCalls {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl AudioNode for CountProcessCalls {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("count_process_calls")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::ZERO,
+                    num_outputs: ChannelCount::MONO,
+                })
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            _cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            CountProcessCallsProcessor {
+                count: Arc::clone(&self.count),
+            }
+        }
+    }
+
+    struct CountProcessCallsProcessor {
+        count: Arc<AtomicUsize>,
+    }
+
+    impl AudioNodeProcessor for CountProcessCallsProcessor {
+        fn process(
+            &mut self,
+            info: &ProcInfo,
+            buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            self.count.fetch_add(1, Ordering::Relaxed);
+            buffers.outputs[0][..info.frames].fill(0.5);
+
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    #[test]
+    fn unconsumed_generator_sleeps_until_reconnected() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            sleep_unconsumed_nodes: true,
+            ..Default::default()
+        });
+        let graph_out = cx.graph_out_node_id();
+
+        let count = Arc::new(AtomicUsize::new(0));
+        let generator = cx.add_node(
+            CountProcessCalls {
+                count: Arc::clone(&count),
+            },
+            None,
+        );
+        let volume = cx.add_node(VolumeNode::default(), None);
+        cx.connect(generator, volume, &[(0, 0)], false).unwrap();
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+
+        stream.process_block(1024);
+        assert!(count.load(Ordering::Relaxed) > 0);
+        assert!(!cx.node_info(generator).unwrap().asleep);
+
+        // Disconnecting the only consumer puts the generator to sleep.
+        assert!(cx.disconnect(generator, volume, &[(0, 0)]));
+        cx.update().unwrap();
+        assert!(cx.node_info(generator).unwrap().asleep);
+        assert!(!cx.node_info(volume).unwrap().asleep);
+
+        stream.process_block(1024);
+        let asleep_count = count.load(Ordering::Relaxed);
+        for _ in 0..4 {
+            stream.process_block(1024);
+        }
+        assert_eq!(count.load(Ordering::Relaxed), asleep_count);
+
+        // Reconnecting it wakes it back up.
+        cx.connect(generator, volume, &[(0, 0)], false).unwrap();
+        cx.update().unwrap();
+        assert!(!cx.node_info(generator).unwrap().asleep);
+
+        let output = stream.process_block(1024);
+        assert!(count.load(Ordering::Relaxed) > asleep_count);
+        assert_eq!(output[0], 0.5);
+    }
+}
@@ -10,6 +10,8 @@ use firewheel_core::{
 };
 
 use super::{InsertedSum, NodeID};
+use crate::input_activity::InputActivityConfig;
+use crate::probe::{NodeProbes, Probe};
 
 #[cfg(not(feature = "std"))]
 use bevy_platform::prelude::{vec, Box, Vec};
@@ -52,10 +54,22 @@ pub(super) struct ScheduledNode {
     pub out_connected_mask: ConnectedMask,
 
     pub sum_inputs: Vec<InsertedSum>,
+
+    pub probes: NodeProbes,
+    /// Whether the outputs of this node are silenced by mute or solo.
+    pub silenced: bool,
+    /// Whether this node is a voice that may be culled by the voice budget.
+    pub voice: bool,
+    /// Whether the wet path of this node is bypassed.
+    pub wet_bypassed: bool,
+    /// The input activity detection of this node, if enabled.
+    pub input_activity: Option<InputActivityConfig>,
+    /// Whether this node is asleep because its output is not consumed.
+    pub asleep: bool,
 }
 
 impl ScheduledNode {
-    pub fn new(id: NodeID, debug_name: &'static str) -> Self {
+    pub fn new(id: NodeID, debug_name: &'static str, probes: NodeProbes) -> Self {
         Self {
             id,
             debug_name,
@@ -64,6 +78,12 @@ impl ScheduledNode {
             in_connected_mask: ConnectedMask::default(),
             out_connected_mask: ConnectedMask::default(),
             sum_inputs: Vec::new(),
+            probes,
+            silenced: false,
+            voice: false,
+            wet_bypassed: false,
+            input_activity: None,
+            asleep: false,
         }
     }
 }
@@ -151,6 +171,10 @@ pub(super) struct InBufferAssignment {
     /// Whether the engine should clear the buffer before
     /// passing it to a process
     pub should_clear: bool,
+    /// Whether this node is the last one to read the buffer, meaning
+    /// the buffer may be handed over to an output of this node on
+    /// [`ProcessStatus::PassThrough`].
+    pub last_reader: bool,
 }
 
 /// Represents a single buffer assigned to an output port
@@ -162,8 +186,10 @@ pub(super) struct OutBufferAssignment {
 
 pub struct NodeHeapData {
     pub id: NodeID,
+    pub debug_name: &'static str,
     pub processor: Box<dyn AudioNodeProcessor>,
     pub is_pre_process: bool,
+    pub analysis_only: bool,
     //pub event_buffer_indices: Vec<u32>,
 }
 
@@ -228,6 +254,12 @@ pub struct CompiledSchedule {
     schedule: Vec<ScheduledNode>,
 
     buffers: Vec<f32>,
+    /// The slot in `buffers` that each buffer index currently points to.
+    ///
+    /// This starts out as the identity mapping. When a node returns
+    /// [`ProcessStatus::PassThrough`], its input and output buffers swap
+    /// slots instead of copying the data.
+    buffer_slots: Vec<usize>,
     buffer_flags: Vec<BufferFlags>,
     num_buffers: usize,
     max_block_frames: usize,
@@ -281,6 +313,7 @@ impl CompiledSchedule {
             pre_proc_nodes,
             schedule,
             buffers,
+            buffer_slots: (0..num_buffers).collect(),
             buffer_flags: vec![
                 BufferFlags {
                     silent: false,
@@ -299,6 +332,39 @@ impl CompiledSchedule {
         self.max_block_frames
     }
 
+    /// The number of buffers which currently live in another buffer's slot
+    /// because a node passed its inputs through to its outputs.
+    #[cfg(test)]
+    pub(crate) fn num_handed_over_buffers(&self) -> usize {
+        self.buffer_slots
+            .iter()
+            .enumerate()
+            .filter(|(i, slot)| i != *slot)
+            .count()
+    }
+
+    /// Whether or not each node in the schedule is a voice.
+    pub fn voice_flags(&self) -> impl Iterator<Item = (NodeID, bool)> + '_ {
+        self.schedule.iter().map(|n| (n.id, n.voice))
+    }
+
+    /// Whether or not the wet path of each node in the schedule is bypassed.
+    pub fn wet_bypass_flags(&self) -> impl Iterator<Item = (NodeID, bool)> + '_ {
+        self.schedule.iter().map(|n| (n.id, n.wet_bypassed))
+    }
+
+    /// The input activity detection of each node in the schedule.
+    pub fn input_activity_configs(
+        &self,
+    ) -> impl Iterator<Item = (NodeID, Option<InputActivityConfig>)> + '_ {
+        self.schedule.iter().map(|n| (n.id, n.input_activity))
+    }
+
+    /// Whether or not each node in the schedule is asleep.
+    pub fn sleep_flags(&self) -> impl Iterator<Item = (NodeID, bool)> + '_ {
+        self.schedule.iter().map(|n| (n.id, n.asleep))
+    }
+
     pub fn prepare_graph_inputs(
         &mut self,
         frames: usize,
@@ -317,6 +383,7 @@ impl CompiledSchedule {
         for i in 0..fill_input_len {
             inputs.push(buffer_slice_mut(
                 &self.buffers,
+                &self.buffer_slots,
                 graph_in_node.output_buffers[i].buffer_index,
                 self.max_block_frames,
                 frames,
@@ -333,8 +400,13 @@ impl CompiledSchedule {
 
         if fill_input_len < graph_in_node.output_buffers.len() {
             for b in graph_in_node.output_buffers.iter().skip(fill_input_len) {
-                let buf_slice =
-                    buffer_slice_mut(&self.buffers, b.buffer_index, self.max_block_frames, frames);
+                let buf_slice = buffer_slice_mut(
+                    &self.buffers,
+                    &self.buffer_slots,
+                    b.buffer_index,
+                    self.max_block_frames,
+                    frames,
+                );
                 buf_slice.fill(0.0);
 
                 flag_mut(&mut self.buffer_flags, b.buffer_index).set_silent(true, frames_u16);
@@ -347,7 +419,13 @@ impl CompiledSchedule {
             let flag = flag_mut(&mut self.buffer_flags, i);
 
             if (flag.silent || flag.constant) && flag.frames < frames_u16 {
-                let buf_slice = buffer_slice_mut(&self.buffers, i, self.max_block_frames, frames);
+                let buf_slice = buffer_slice_mut(
+                    &self.buffers,
+                    &self.buffer_slots,
+                    i,
+                    self.max_block_frames,
+                    frames,
+                );
 
                 if flag.silent {
                     buf_slice[flag.frames as usize..frames].fill(0.0);
@@ -386,6 +464,7 @@ impl CompiledSchedule {
 
             outputs.push(buffer_slice_mut(
                 &self.buffers,
+                &self.buffer_slots,
                 buffer_index,
                 self.max_block_frames,
                 frames,
@@ -443,6 +522,37 @@ impl CompiledSchedule {
 
         for scheduled_node in self.schedule.iter() {
             if scheduled_node.id == self.graph_in_node_id {
+                // The graph inputs have already been written by `prepare_graph_inputs`.
+                if scheduled_node.silenced {
+                    for b in scheduled_node.output_buffers.iter() {
+                        let flag = flag_mut(&mut self.buffer_flags, b.buffer_index);
+
+                        if !flag.silent {
+                            buffer_slice_mut(
+                                &self.buffers,
+                                &self.buffer_slots,
+                                b.buffer_index,
+                                self.max_block_frames,
+                                frames,
+                            )
+                            .fill(0.0);
+                            flag.set_silent(true, frames_u16);
+                        }
+                    }
+                }
+
+                if let Some(probe) = &scheduled_node.probes.output {
+                    measure_probe(
+                        probe,
+                        scheduled_node.output_buffers.iter().map(|b| b.buffer_index),
+                        &self.buffers,
+                        &self.buffer_slots,
+                        &self.buffer_flags,
+                        self.max_block_frames,
+                        frames,
+                    );
+                }
+
                 continue;
             }
 
@@ -450,6 +560,7 @@ impl CompiledSchedule {
                 sum_inputs(
                     inserted_sum,
                     &self.buffers,
+                    &self.buffer_slots,
                     &mut self.buffer_flags,
                     self.max_block_frames,
                     frames,
@@ -465,8 +576,13 @@ impl CompiledSchedule {
             outputs.clear();
 
             for (i, b) in scheduled_node.input_buffers.iter().enumerate() {
-                let buf =
-                    buffer_slice_mut(&self.buffers, b.buffer_index, self.max_block_frames, frames);
+                let buf = buffer_slice_mut(
+                    &self.buffers,
+                    &self.buffer_slots,
+                    b.buffer_index,
+                    self.max_block_frames,
+                    frames,
+                );
                 let flag = flag_mut(&mut self.buffer_flags, b.buffer_index);
 
                 if b.should_clear && (!flag.silent || debug_force_clear_buffers) {
@@ -481,8 +597,13 @@ impl CompiledSchedule {
             }
 
             for (i, b) in scheduled_node.output_buffers.iter().enumerate() {
-                let buf =
-                    buffer_slice_mut(&self.buffers, b.buffer_index, self.max_block_frames, frames);
+                let buf = buffer_slice_mut(
+                    &self.buffers,
+                    &self.buffer_slots,
+                    b.buffer_index,
+                    self.max_block_frames,
+                    frames,
+                );
                 let flag = flag_mut(&mut self.buffer_flags, b.buffer_index);
 
                 if debug_force_clear_buffers {
@@ -496,6 +617,18 @@ impl CompiledSchedule {
                 outputs.push(buf);
             }
 
+            if let Some(probe) = &scheduled_node.probes.input {
+                measure_probe(
+                    probe,
+                    scheduled_node.input_buffers.iter().map(|b| b.buffer_index),
+                    &self.buffers,
+                    &self.buffer_slots,
+                    &self.buffer_flags,
+                    self.max_block_frames,
+                    frames,
+                );
+            }
+
             let status = (process)(
                 scheduled_node.id,
                 in_silence_mask,
@@ -510,10 +643,35 @@ impl CompiledSchedule {
                 },
             );
 
+            let pass_through = status == ProcessStatus::PassThrough
+                && scheduled_node.input_buffers.len() == scheduled_node.output_buffers.len();
+
+            if pass_through {
+                // Hand each input buffer that no other node reads anymore over
+                // to its corresponding output instead of copying it.
+                for (in_buf, out_buf) in scheduled_node
+                    .input_buffers
+                    .iter()
+                    .zip(scheduled_node.output_buffers.iter())
+                    .filter(|(in_buf, _)| in_buf.last_reader)
+                {
+                    self.buffer_slots
+                        .swap(in_buf.buffer_index, out_buf.buffer_index);
+                    self.buffer_flags
+                        .swap(in_buf.buffer_index, out_buf.buffer_index);
+                }
+            }
+
             let clear_buffer = |buffer_index: usize, flag: &mut BufferFlags| {
                 if !flag.silent || debug_force_clear_buffers {
-                    buffer_slice_mut(&self.buffers, buffer_index, self.max_block_frames, frames)
-                        .fill(0.0);
+                    buffer_slice_mut(
+                        &self.buffers,
+                        &self.buffer_slots,
+                        buffer_index,
+                        self.max_block_frames,
+                        frames,
+                    )
+                    .fill(0.0);
                     flag.set_silent(true, frames_u16);
                 }
             };
@@ -527,11 +685,12 @@ impl CompiledSchedule {
                         clear_buffer(b.buffer_index, flag);
                     }
                 }
-                ProcessStatus::Bypass => {
+                ProcessStatus::Bypass | ProcessStatus::PassThrough => {
                     for (in_buf, out_buf) in scheduled_node
                         .input_buffers
                         .iter()
                         .zip(scheduled_node.output_buffers.iter())
+                        .filter(|(in_buf, _)| !(pass_through && in_buf.last_reader))
                     {
                         let in_flag = *flag_mut(&mut self.buffer_flags, in_buf.buffer_index);
                         let out_flag = flag_mut(&mut self.buffer_flags, out_buf.buffer_index);
@@ -541,12 +700,14 @@ impl CompiledSchedule {
                         } else {
                             let in_buf_slice = buffer_slice_mut(
                                 &self.buffers,
+                                &self.buffer_slots,
                                 in_buf.buffer_index,
                                 self.max_block_frames,
                                 frames,
                             );
                             let out_buf_slice = buffer_slice_mut(
                                 &self.buffers,
+                                &self.buffer_slots,
                                 out_buf.buffer_index,
                                 self.max_block_frames,
                                 frames,
@@ -588,6 +749,7 @@ impl CompiledSchedule {
                                 flag.constant = true;
                                 flag.silent = buffer_slice_mut(
                                     &self.buffers,
+                                    &self.buffer_slots,
                                     b.buffer_index,
                                     self.max_block_frames,
                                     1,
@@ -600,13 +762,71 @@ impl CompiledSchedule {
                     }
                 },
             }
+
+            if scheduled_node.silenced {
+                // The node is still processed so that its state stays
+                // current, but its output is discarded.
+                for b in scheduled_node.output_buffers.iter() {
+                    let flag = flag_mut(&mut self.buffer_flags, b.buffer_index);
+
+                    clear_buffer(b.buffer_index, flag);
+                }
+            }
+
+            if let Some(probe) = &scheduled_node.probes.output {
+                measure_probe(
+                    probe,
+                    scheduled_node.output_buffers.iter().map(|b| b.buffer_index),
+                    &self.buffers,
+                    &self.buffer_slots,
+                    &self.buffer_flags,
+                    self.max_block_frames,
+                    frames,
+                );
+            }
         }
     }
 }
 
+/// Store the peak of each of the given buffers into the probe.
+fn measure_probe(
+    probe: &Probe,
+    buffer_indices: impl Iterator<Item = usize>,
+    buffers: &[f32],
+    buffer_slots: &[usize],
+    buffer_flags: &[BufferFlags],
+    max_block_frames: usize,
+    frames: usize,
+) {
+    for (ch_i, buffer_index) in buffer_indices.enumerate() {
+        let flag = buffer_flags[buffer_index];
+
+        let peak = if flag.silent {
+            0.0
+        } else {
+            let buf = buffer_slice_mut(
+                buffers,
+                buffer_slots,
+                buffer_index,
+                max_block_frames,
+                frames,
+            );
+
+            if flag.constant {
+                buf[0].abs()
+            } else {
+                firewheel_core::dsp::algo::max_peak(buf)
+            }
+        };
+
+        probe.store(ch_i, peak);
+    }
+}
+
 fn sum_inputs(
     inserted_sum: &InsertedSum,
     buffers: &Vec<f32>,
+    buffer_slots: &[usize],
     buffer_flags: &mut [BufferFlags],
     max_block_frames: usize,
     frames: usize,
@@ -615,15 +835,21 @@ fn sum_inputs(
 
     let out_slice = buffer_slice_mut(
         buffers,
+        buffer_slots,
         inserted_sum.output_buffer.buffer_index,
         max_block_frames,
         frames,
     );
 
-    if flag_mut(buffer_flags, inserted_sum.input_buffers[0].buffer_index).silent {
+    let first_gain = inserted_sum.input_gains[0];
+
+    if flag_mut(buffer_flags, inserted_sum.input_buffers[0].buffer_index).silent
+        || first_gain == 0.0
+    {
         if !flag_mut(buffer_flags, inserted_sum.output_buffer.buffer_index).silent {
             buffer_slice_mut(
                 buffers,
+                buffer_slots,
                 inserted_sum.output_buffer.buffer_index,
                 max_block_frames,
                 frames,
@@ -633,26 +859,50 @@ fn sum_inputs(
     } else {
         let in_slice = buffer_slice_mut(
             buffers,
+            buffer_slots,
             inserted_sum.input_buffers[0].buffer_index,
             max_block_frames,
             frames,
         );
-        out_slice.copy_from_slice(in_slice);
+        if first_gain == 1.0 {
+            out_slice.copy_from_slice(in_slice);
+        } else {
+            for (os, &is) in out_slice.iter_mut().zip(in_slice.iter()) {
+                *os = is * first_gain;
+            }
+        }
 
         all_buffers_silent = false;
     }
 
-    for buf_id in inserted_sum.input_buffers.iter().skip(1) {
-        if flag_mut(buffer_flags, buf_id.buffer_index).silent {
+    for (buf_id, &gain) in inserted_sum
+        .input_buffers
+        .iter()
+        .zip(inserted_sum.input_gains.iter())
+        .skip(1)
+    {
+        if flag_mut(buffer_flags, buf_id.buffer_index).silent || gain == 0.0 {
             // Input channel is silent, no need to add it.
             continue;
         }
 
         all_buffers_silent = false;
 
-        let in_slice = buffer_slice_mut(buffers, buf_id.buffer_index, max_block_frames, frames);
-        for (os, &is) in out_slice.iter_mut().zip(in_slice.iter()) {
-            *os += is;
+        let in_slice = buffer_slice_mut(
+            buffers,
+            buffer_slots,
+            buf_id.buffer_index,
+            max_block_frames,
+            frames,
+        );
+        if gain == 1.0 {
+            for (os, &is) in out_slice.iter_mut().zip(in_slice.iter()) {
+                *os += is;
+            }
+        } else {
+            for (os, &is) in out_slice.iter_mut().zip(in_slice.iter()) {
+                *os += is * gain;
+            }
         }
     }
 
@@ -664,6 +914,7 @@ fn sum_inputs(
 #[allow(clippy::mut_from_ref)]
 fn buffer_slice_mut<'a>(
     buffers: &'a [f32],
+    buffer_slots: &[usize],
     buffer_index: usize,
     max_block_frames: usize,
     frames: usize,
@@ -688,9 +939,14 @@ fn buffer_slice_mut<'a>(
     // Also, `self` is borrowed mutably here, ensuring that the caller cannot
     // call any other method on [`CompiledSchedule`] while those buffers are
     // still borrowed.
+    //
+    // `buffer_slots` is always a permutation of `0..num_buffers` (slots are
+    // only ever swapped), so the same reasoning applies to the slot.
     unsafe {
+        let slot = *buffer_slots.get_unchecked(buffer_index);
+
         core::slice::from_raw_parts_mut(
-            (buffers.as_ptr() as *mut f32).add(buffer_index * max_block_frames),
+            (buffers.as_ptr() as *mut f32).add(slot * max_block_frames),
             frames,
         )
     }
@@ -715,7 +971,7 @@ mod tests {
     use crate::{
         graph::{
             dummy_node::{DummyNode, DummyNodeConfig},
-            AudioGraph, EdgeID,
+            AudioGraph, EdgeID, NodeOrderHint,
         },
         FirewheelConfig,
     };
@@ -939,6 +1195,62 @@ mod tests {
         verify_node(node6, &[false], 0, &schedule, &graph);
     }
 
+    // A meter tap hinted to run last:
+    //
+    //   ┌───┐  ┌───┐  ┌───┐
+    //   │   ┼──► 2 ┼──► 3 ┼─┐
+    //   │ 0 │  └───┘  └───┘ │ ┌───┐
+    //   │   │  ┌───┐        └─►   │
+    //   │   ┼──► 1 ┼──────────► 5 │
+    //   └───┘  └─┬─┘          └───┘
+    //            │ ┌───┐
+    //            └─► 4 │ (meter)
+    //              └───┘
+    #[test]
+    fn last_hint_schedules_meter_tap_after_other_nodes() {
+        let mut graph = AudioGraph::new(&FirewheelConfig {
+            num_graph_inputs: ChannelCount::MONO,
+            num_graph_outputs: ChannelCount::STEREO,
+            ..Default::default()
+        });
+
+        let node0 = graph.graph_in_node();
+        let node1 = add_dummy_node(&mut graph, (1, 1));
+        let node2 = add_dummy_node(&mut graph, (1, 1));
+        let node3 = add_dummy_node(&mut graph, (1, 1));
+        let node4 = add_dummy_node(&mut graph, (1, 0));
+        let node5 = graph.graph_out_node();
+
+        graph.connect(node0, node1, &[(0, 0)], false).unwrap();
+        graph.connect(node1, node4, &[(0, 0)], false).unwrap();
+        graph.connect(node0, node2, &[(0, 0)], false).unwrap();
+        graph.connect(node2, node3, &[(0, 0)], false).unwrap();
+        graph.connect(node1, node5, &[(0, 0)], false).unwrap();
+        graph.connect(node3, node5, &[(0, 1)], false).unwrap();
+
+        let position = |schedule: &CompiledSchedule, id: NodeID| {
+            schedule.schedule.iter().position(|n| n.id == id).unwrap()
+        };
+
+        // Without a hint, the meter runs as soon as its source has.
+        let schedule = graph.compile_internal(128).unwrap();
+        assert!(position(&schedule, node4) < position(&schedule, node3));
+
+        assert!(graph.set_node_order_hint(node4, NodeOrderHint::Last));
+        let schedule = graph.compile_internal(128).unwrap();
+
+        #[cfg(feature = "std")]
+        dbg!(&schedule);
+
+        // The meter still runs after its source, but now also after every
+        // other node except the graph out node.
+        assert_eq!(schedule.schedule.len(), 6);
+        assert_eq!(schedule.schedule[0].id, node0);
+        assert!(position(&schedule, node4) > position(&schedule, node1));
+        assert_eq!(schedule.schedule[4].id, node4);
+        assert_eq!(schedule.schedule[5].id, node5);
+    }
+
     fn add_dummy_node(graph: &mut AudioGraph, channel_config: impl Into<ChannelConfig>) -> NodeID {
         graph.add_node(
             DummyNode,
@@ -0,0 +1,193 @@
+//! Events for when the input of a node starts or stops carrying signal.
+
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use firewheel_core::{dsp::volume::db_to_amp, node::NodeID};
+
+/// The configuration of the input activity detection of a node (see
+/// [`FirewheelCtx::set_node_input_activity`]).
+///
+/// [`FirewheelCtx::set_node_input_activity`]: crate::FirewheelCtx::set_node_input_activity
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InputActivityConfig {
+    /// The peak level (in decibels) at or above which the input becomes
+    /// active.
+    ///
+    /// By default this is set to `-60.0`.
+    pub threshold_db: f32,
+    /// How far (in decibels) the peak level must fall below
+    /// [`InputActivityConfig::threshold_db`] before the input can become
+    /// inactive. This keeps a signal hovering around the threshold from
+    /// rapidly toggling between active and inactive.
+    ///
+    /// By default this is set to `6.0`.
+    pub hysteresis_db: f32,
+    /// How long (in seconds) the peak level must stay below the lower
+    /// threshold before the input becomes inactive.
+    ///
+    /// By default this is set to `0.2`.
+    pub hold_secs: f32,
+}
+
+impl Default for InputActivityConfig {
+    fn default() -> Self {
+        Self {
+            threshold_db: -60.0,
+            hysteresis_db: 6.0,
+            hold_secs: 0.2,
+        }
+    }
+}
+
+/// An event sent when the input of a node transitions between silent and
+/// active.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InputActivityEvent {
+    /// The node whose input changed.
+    pub node_id: NodeID,
+    /// `true` if the input became active, `false` if it became silent.
+    pub active: bool,
+}
+
+/// The audio thread side of the input activity detection of a node.
+pub(crate) struct InputActivityDetector {
+    pub config: InputActivityConfig,
+    on_gain: f32,
+    off_gain: f32,
+    hold_frames: u64,
+    frames_below: u64,
+    active: bool,
+    /// Whether or not the context has been told the current state.
+    reported_active: bool,
+}
+
+impl InputActivityDetector {
+    pub fn new(config: InputActivityConfig, sample_rate: NonZeroU32) -> Self {
+        Self {
+            config,
+            on_gain: db_to_amp(config.threshold_db),
+            off_gain: db_to_amp(config.threshold_db - config.hysteresis_db.max(0.0)),
+            hold_frames: (config.hold_secs.max(0.0) as f64 * sample_rate.get() as f64).round()
+                as u64,
+            frames_below: 0,
+            active: false,
+            reported_active: false,
+        }
+    }
+
+    /// Update the detector with the peak input level of a block.
+    pub fn process(&mut self, peak_gain: f32, frames: usize) {
+        if peak_gain >= self.on_gain {
+            self.active = true;
+            self.frames_below = 0;
+        } else if self.active {
+            if peak_gain < self.off_gain {
+                self.frames_below += frames as u64;
+
+                if self.frames_below >= self.hold_frames {
+                    self.active = false;
+                    self.frames_below = 0;
+                }
+            } else {
+                self.frames_below = 0;
+            }
+        }
+    }
+
+    /// Returns the new state if it has not been reported to the context
+    /// yet.
+    pub fn unreported_state(&self) -> Option<bool> {
+        (self.active != self.reported_active).then_some(self.active)
+    }
+
+    pub fn mark_reported(&mut self, active: bool) {
+        self.reported_active = active;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_nodes::volume::VolumeNode;
+
+    use crate::{
+        backend::dummy_backend::DummyBackend,
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    use super::*;
+
+    #[test]
+    fn input_activity_reports_one_start_and_one_stop() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let volume = cx.add_node(VolumeNode::from_decibels(0.0), None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, volume);
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let config = InputActivityConfig {
+            threshold_db: -40.0,
+            hysteresis_db: 6.0,
+            hold_secs: 0.05,
+        };
+        assert!(cx.set_node_input_activity(volume, Some(config)));
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let block = |amp: f32| -> Vec<f32> {
+            (0..512)
+                .flat_map(|i| {
+                    let s = amp * (i as f32 * 0.05).sin();
+                    [s, s]
+                })
+                .collect()
+        };
+        let mut output = vec![0.0; 1024];
+        let mut events = Vec::new();
+        let mut run = |cx: &mut FirewheelCtx<DummyBackend>, amp: f32, blocks: usize| {
+            for _ in 0..blocks {
+                stream.process(&block(amp), &mut output);
+                cx.update().unwrap();
+                events.extend(cx.drain_input_activity_events());
+            }
+        };
+
+        // Silence does not start the input.
+        run(&mut cx, 0.0, 4);
+        assert!(!cx.is_input_active(volume));
+
+        // Feed a signal above the threshold.
+        run(&mut cx, 0.5, 8);
+        assert!(cx.is_input_active(volume));
+
+        // A signal which falls below the threshold but stays within the
+        // hysteresis keeps the input active.
+        run(&mut cx, firewheel_core::dsp::volume::db_to_amp(-43.0), 20);
+        assert!(cx.is_input_active(volume));
+
+        // Stop the signal for longer than the hold time.
+        run(&mut cx, 0.0, 20);
+        assert!(!cx.is_input_active(volume));
+
+        assert_eq!(
+            events,
+            [
+                InputActivityEvent {
+                    node_id: volume,
+                    active: true
+                },
+                InputActivityEvent {
+                    node_id: volume,
+                    active: false
+                },
+            ]
+        );
+    }
+}
@@ -1,16 +1,26 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+pub mod automation;
 pub mod backend;
 mod context;
+#[cfg(feature = "serde")]
+pub mod descriptor;
 pub mod error;
 pub mod graph;
+pub mod input_activity;
+pub mod meter;
+pub mod preset;
+pub mod probe;
 pub mod processor;
 
+#[cfg(test)]
+mod test_util;
+
 #[cfg(feature = "unsafe_flush_denormals_to_zero")]
 mod ftz;
 
 #[cfg(feature = "scheduled_events")]
 pub use context::ClearScheduledEventsType;
-pub use context::{ContextQueue, FirewheelConfig, FirewheelCtx};
+pub use context::{ContextQueue, EventQueueOverflowPolicy, FirewheelConfig, FirewheelCtx};
 
 extern crate alloc;
@@ -0,0 +1,214 @@
+//! A built-in meter for the final output of the graph.
+
+use bevy_platform::sync::atomic::{AtomicUsize, Ordering};
+use firewheel_core::{
+    atomic_float::AtomicF32,
+    channel_config::MAX_CHANNELS,
+    collector::ArcGc,
+    dsp::true_peak::{TruePeakCoeffs, TruePeakDetector},
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The levels of a single channel of the master output in the last
+/// processed block, as raw gain (not decibels).
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct ChannelLevels {
+    /// The peak amplitude of the samples.
+    pub peak_gain: f32,
+    /// The RMS amplitude of the samples.
+    pub rms_gain: f32,
+    /// The true peak amplitude, which is the peak of the reconstructed
+    /// analog signal. This can be higher than [`ChannelLevels::peak_gain`].
+    pub true_peak_gain: f32,
+}
+
+impl ChannelLevels {
+    /// The loudest levels across both channels.
+    pub fn max(self, other: Self) -> Self {
+        Self {
+            peak_gain: self.peak_gain.max(other.peak_gain),
+            rms_gain: self.rms_gain.max(other.rms_gain),
+            true_peak_gain: self.true_peak_gain.max(other.true_peak_gain),
+        }
+    }
+}
+
+struct MasterMeterAtomics {
+    num_channels: AtomicUsize,
+    peak_gains: [AtomicF32; MAX_CHANNELS],
+    rms_gains: [AtomicF32; MAX_CHANNELS],
+    true_peak_gains: [AtomicF32; MAX_CHANNELS],
+}
+
+/// A handle to the levels of the master output, measured by the audio
+/// thread after every processed block (see [`FirewheelConfig::master_meter`]).
+///
+/// Reading the levels never blocks, so this can be cloned and polled from a
+/// UI thread.
+///
+/// [`FirewheelConfig::master_meter`]: crate::FirewheelConfig::master_meter
+#[derive(Clone)]
+pub struct MasterMeter {
+    shared: ArcGc<MasterMeterAtomics>,
+}
+
+impl MasterMeter {
+    pub(crate) fn new() -> Self {
+        Self {
+            shared: ArcGc::new(MasterMeterAtomics {
+                num_channels: AtomicUsize::new(0),
+                peak_gains: core::array::from_fn(|_| AtomicF32::new(0.0)),
+                rms_gains: core::array::from_fn(|_| AtomicF32::new(0.0)),
+                true_peak_gains: core::array::from_fn(|_| AtomicF32::new(0.0)),
+            }),
+        }
+    }
+
+    /// The number of output channels being measured.
+    pub fn num_channels(&self) -> usize {
+        self.shared.num_channels.load(Ordering::Relaxed)
+    }
+
+    /// The levels of the given output channel in the last processed block.
+    ///
+    /// Returns all zeros if the channel is out of range.
+    pub fn channel_levels(&self, channel: usize) -> ChannelLevels {
+        if channel >= self.num_channels() {
+            return ChannelLevels::default();
+        }
+
+        ChannelLevels {
+            peak_gain: self.shared.peak_gains[channel].load(Ordering::Relaxed),
+            rms_gain: self.shared.rms_gains[channel].load(Ordering::Relaxed),
+            true_peak_gain: self.shared.true_peak_gains[channel].load(Ordering::Relaxed),
+        }
+    }
+
+    /// The loudest levels across all output channels in the last processed
+    /// block.
+    pub fn levels(&self) -> ChannelLevels {
+        (0..self.num_channels()).fold(ChannelLevels::default(), |levels, ch| {
+            levels.max(self.channel_levels(ch))
+        })
+    }
+}
+
+/// The audio thread side of a [`MasterMeter`].
+pub(crate) struct MasterMeterProcessor {
+    meter: MasterMeter,
+    true_peak_detectors: [TruePeakDetector; MAX_CHANNELS],
+    true_peak_coeffs: TruePeakCoeffs,
+}
+
+impl MasterMeterProcessor {
+    pub fn new(meter: MasterMeter) -> Self {
+        Self {
+            meter,
+            true_peak_detectors: [TruePeakDetector::new(); MAX_CHANNELS],
+            true_peak_coeffs: TruePeakCoeffs::new(),
+        }
+    }
+
+    /// Note, this method gets called on the main thread, not the audio thread.
+    pub fn new_stream(&mut self) {
+        for detector in self.true_peak_detectors.iter_mut() {
+            detector.reset();
+        }
+    }
+
+    /// Measure a block of interleaved audio data.
+    pub fn process_interleaved(&mut self, data: &[f32], num_channels: usize) {
+        let num_channels = num_channels.min(MAX_CHANNELS);
+        self.meter
+            .shared
+            .num_channels
+            .store(num_channels, Ordering::Relaxed);
+
+        if num_channels == 0 {
+            return;
+        }
+
+        let frames = data.len() / num_channels;
+
+        for (ch, detector) in self.true_peak_detectors[..num_channels]
+            .iter_mut()
+            .enumerate()
+        {
+            let mut peak: f32 = 0.0;
+            let mut true_peak: f32 = 0.0;
+            let mut sum_squares: f32 = 0.0;
+
+            for &s in data.iter().skip(ch).step_by(num_channels) {
+                peak = peak.max(s.abs());
+                true_peak = true_peak.max(detector.process_sample(s, &self.true_peak_coeffs));
+                sum_squares += s * s;
+            }
+
+            let rms = if frames == 0 {
+                0.0
+            } else {
+                (sum_squares / frames as f32).sqrt()
+            };
+
+            let shared = &self.meter.shared;
+            shared.peak_gains[ch].store(peak, Ordering::Relaxed);
+            shared.rms_gains[ch].store(rms, Ordering::Relaxed);
+            shared.true_peak_gains[ch].store(true_peak, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        backend::dummy_backend::DummyBackend,
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    #[test]
+    fn master_meter_reflects_output_level() {
+        let mut cx = stereo_ctx(FirewheelConfig {
+            master_meter: true,
+            ..Default::default()
+        });
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // A 1kHz sine wave at half amplitude on the left channel, and a
+        // quarter amplitude on the right channel.
+        let sample_rate = stream.stream_info.sample_rate.get() as f32;
+        let mut input = vec![0.0; 4_410 * 2];
+        for (i, frame) in input.chunks_exact_mut(2).enumerate() {
+            let s = (core::f32::consts::TAU * 1_000.0 * i as f32 / sample_rate).sin();
+            frame[0] = s * 0.5;
+            frame[1] = s * 0.25;
+        }
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        let meter = cx.master_meter().unwrap();
+        assert_eq!(meter.num_channels(), 2);
+
+        let left = meter.channel_levels(0);
+        assert!((left.peak_gain - 0.5).abs() < 0.01, "{left:?}");
+        assert!((left.rms_gain - 0.5 * core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!(left.true_peak_gain >= left.peak_gain && left.true_peak_gain < 0.52);
+
+        let right = meter.channel_levels(1);
+        assert!((right.peak_gain - 0.25).abs() < 0.01, "{right:?}");
+        assert!((right.rms_gain - 0.25 * core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+
+        assert_eq!(meter.levels(), left);
+
+        // The meter is off unless enabled.
+        let cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        assert!(cx.master_meter().is_none());
+    }
+}
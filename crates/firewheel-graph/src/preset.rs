@@ -0,0 +1,133 @@
+//! Named parameter presets for node types.
+
+use core::any::{type_name, Any};
+
+use bevy_platform::collections::HashMap;
+use firewheel_core::{
+    diff::{Diff, DynParams, PathBuilder},
+    event::NodeEventType,
+    node::AudioNode,
+};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{Box, Vec};
+
+/// A named set of parameters for a node type.
+struct NodePreset {
+    name: &'static str,
+    params: Box<dyn Any>,
+    /// Diff the preset parameters against the live parameters of a node.
+    ///
+    /// Returns `false` if the live parameters are of a different type.
+    diff: fn(&dyn Any, &dyn DynParams, &mut Vec<NodeEventType>) -> bool,
+}
+
+fn diff_preset<T: Diff + 'static>(
+    preset: &dyn Any,
+    live: &dyn DynParams,
+    events: &mut Vec<NodeEventType>,
+) -> bool {
+    let (Some(preset), Some(live)) = (
+        preset.downcast_ref::<T>(),
+        live.as_any().downcast_ref::<T>(),
+    ) else {
+        return false;
+    };
+
+    preset.diff(live, PathBuilder::default(), events);
+    true
+}
+
+/// A registry of named factory presets (i.e. "telephone" or "radio" for a
+/// filter), grouped by node type.
+///
+/// Presets are applied to a live node with
+/// [`FirewheelCtx::apply_preset`][crate::FirewheelCtx::apply_preset], which
+/// only sends the parameters that differ from the node's current ones.
+#[derive(Default)]
+pub struct PresetRegistry {
+    /// The presets of each node type, keyed by the name of the Rust type
+    /// (see [`NodeEntry::type_name`][crate::graph::NodeEntry::type_name]).
+    presets: HashMap<&'static str, Vec<NodePreset>>,
+}
+
+impl PresetRegistry {
+    /// Register a preset for the node type `T`.
+    ///
+    /// If a preset with the same name was already registered for `T`, then
+    /// it is replaced.
+    pub fn register<T: AudioNode + Diff + 'static>(&mut self, name: &'static str, params: T) {
+        let preset = NodePreset {
+            name,
+            params: Box::new(params),
+            diff: diff_preset::<T>,
+        };
+
+        let presets = self.presets.entry(type_name::<T>()).or_default();
+        if let Some(existing) = presets.iter_mut().find(|p| p.name == name) {
+            *existing = preset;
+        } else {
+            presets.push(preset);
+        }
+    }
+
+    /// Remove a preset for the node type `T`.
+    ///
+    /// Returns `true` if the preset existed.
+    pub fn unregister<T: AudioNode + 'static>(&mut self, name: &str) -> bool {
+        let Some(presets) = self.presets.get_mut(type_name::<T>()) else {
+            return false;
+        };
+
+        let len = presets.len();
+        presets.retain(|p| p.name != name);
+        presets.len() != len
+    }
+
+    /// The names of the presets registered for the node type `T`, in the
+    /// order they were registered.
+    pub fn names<T: AudioNode + 'static>(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.names_for_type(type_name::<T>())
+    }
+
+    /// The names of the presets registered for the node type with the given
+    /// type name (see
+    /// [`NodeEntry::type_name`][crate::graph::NodeEntry::type_name]), in the
+    /// order they were registered.
+    pub fn names_for_type<'a>(
+        &'a self,
+        type_name: &str,
+    ) -> impl Iterator<Item = &'static str> + 'a {
+        self.presets
+            .get(type_name)
+            .into_iter()
+            .flat_map(|presets| presets.iter().map(|p| p.name))
+    }
+
+    /// Get the parameters of a preset for the node type `T`.
+    pub fn get<T: AudioNode + 'static>(&self, name: &str) -> Option<&T> {
+        self.find(type_name::<T>(), name)?
+            .params
+            .downcast_ref::<T>()
+    }
+
+    fn find(&self, type_name: &str, name: &str) -> Option<&NodePreset> {
+        self.presets.get(type_name)?.iter().find(|p| p.name == name)
+    }
+
+    /// Push the parameter events needed to bring `live` in sync with the
+    /// given preset.
+    ///
+    /// Returns `false` if the preset does not exist or if `live` is of a
+    /// different type.
+    pub(crate) fn diff(
+        &self,
+        type_name: &str,
+        name: &str,
+        live: &dyn DynParams,
+        events: &mut Vec<NodeEventType>,
+    ) -> bool {
+        self.find(type_name, name)
+            .is_some_and(|preset| (preset.diff)(preset.params.as_ref(), live, events))
+    }
+}
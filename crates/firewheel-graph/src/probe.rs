@@ -0,0 +1,176 @@
+//! Lightweight level probes that can be attached to any node in the graph.
+
+use bevy_platform::sync::atomic::Ordering;
+use firewheel_core::{
+    atomic_float::AtomicF32, channel_config::ChannelCount, collector::ArcGc, dsp::volume::amp_to_db,
+};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Box;
+
+/// Where on a node a [`Probe`] measures the signal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ProbePoint {
+    /// Measure the signal going into the node's input ports (pre-gain).
+    Input,
+    /// Measure the signal coming out of the node's output ports (post-gain).
+    Output,
+}
+
+/// A level readout attached to the input or output of a node with
+/// [`FirewheelCtx::add_probe`][crate::FirewheelCtx::add_probe].
+///
+/// Probes do not add nodes or edges to the graph, so they are a cheap way
+/// to check signal levels throughout a complex graph while debugging.
+#[derive(Clone)]
+pub struct Probe {
+    peak_gains: ArcGc<Box<[AtomicF32]>>,
+}
+
+impl Probe {
+    pub(crate) fn new(num_channels: ChannelCount) -> Self {
+        Self {
+            peak_gains: ArcGc::new(
+                (0..num_channels.get())
+                    .map(|_| AtomicF32::new(0.0))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// The number of channels being measured.
+    pub fn num_channels(&self) -> usize {
+        self.peak_gains.len()
+    }
+
+    /// The peak amplitude (linear) of the given channel in the last
+    /// processed block.
+    ///
+    /// Returns `0.0` if the channel is out of range.
+    pub fn channel_peak_gain(&self, channel: usize) -> f32 {
+        self.peak_gains
+            .get(channel)
+            .map(|g| g.load(Ordering::Relaxed))
+            .unwrap_or(0.0)
+    }
+
+    /// The peak amplitude (linear) across all channels in the last
+    /// processed block.
+    pub fn peak_gain(&self) -> f32 {
+        self.peak_gains
+            .iter()
+            .fold(0.0, |peak, g| peak.max(g.load(Ordering::Relaxed)))
+    }
+
+    /// The peak level across all channels in the last processed block, in
+    /// decibels.
+    ///
+    /// * `db_epsilon` - If the peak value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn peak_gain_db(&self, db_epsilon: f32) -> f32 {
+        let db = amp_to_db(self.peak_gain());
+        if db <= db_epsilon {
+            f32::NEG_INFINITY
+        } else {
+            db
+        }
+    }
+
+    pub(crate) fn store(&self, channel: usize, peak_gain: f32) {
+        if let Some(g) = self.peak_gains.get(channel) {
+            g.store(peak_gain, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The probes attached to a single node.
+#[derive(Default, Clone)]
+pub(crate) struct NodeProbes {
+    pub input: Option<Probe>,
+    pub output: Option<Probe>,
+}
+
+impl NodeProbes {
+    pub fn get(&self, point: ProbePoint) -> Option<&Probe> {
+        match point {
+            ProbePoint::Input => self.input.as_ref(),
+            ProbePoint::Output => self.output.as_ref(),
+        }
+    }
+
+    pub fn get_mut(&mut self, point: ProbePoint) -> &mut Option<Probe> {
+        match point {
+            ProbePoint::Input => &mut self.input,
+            ProbePoint::Output => &mut self.output,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_nodes::volume::VolumeNode;
+
+    use crate::{
+        error::AddProbeError,
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig,
+    };
+
+    use super::*;
+
+    #[test]
+    fn probe_reflects_node_output() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let volume = cx.add_node(VolumeNode::from_decibels(-6.0), None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, volume);
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let pre = cx.add_probe(volume, ProbePoint::Input).unwrap();
+        let post = cx.add_probe(volume, ProbePoint::Output).unwrap();
+        assert_eq!(post.num_channels(), 2);
+        assert_eq!(
+            cx.add_probe(graph_out, ProbePoint::Output).err(),
+            Some(AddProbeError::NoPorts {
+                node: graph_out,
+                point: ProbePoint::Output
+            })
+        );
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // The left channel peaks at 0.8 and the right channel at 0.4.
+        let input: Vec<f32> = (0..1024)
+            .flat_map(|i| {
+                let s = (i as f32 * 0.05).sin();
+                [0.8 * s, 0.4 * s]
+            })
+            .collect();
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        let peak = |data: &[f32], ch: usize| {
+            data.iter()
+                .skip(ch)
+                .step_by(2)
+                .fold(0.0f32, |p, s| p.max(s.abs()))
+        };
+
+        assert_eq!(pre.channel_peak_gain(0), peak(&input, 0));
+        assert_eq!(pre.channel_peak_gain(1), peak(&input, 1));
+        assert_eq!(post.channel_peak_gain(0), peak(&output, 0));
+        assert_eq!(post.channel_peak_gain(1), peak(&output, 1));
+        let gain = firewheel_core::dsp::volume::db_to_amp(-6.0);
+        assert!((post.peak_gain() - pre.peak_gain() * gain).abs() < 1e-6);
+
+        // Silence is reported as silence.
+        stream.process_block(1024);
+        assert_eq!(post.peak_gain(), 0.0);
+        assert_eq!(post.peak_gain_db(-100.0), f32::NEG_INFINITY);
+    }
+}
@@ -1,4 +1,4 @@
-use core::{num::NonZeroU32, usize};
+use core::{num::NonZeroU32, ops::Range, usize};
 
 use ringbuf::traits::Producer;
 use thunderdome::Arena;
@@ -7,23 +7,32 @@ use thunderdome::Arena;
 use bevy_platform::prelude::{Box, Vec};
 
 use firewheel_core::{
+    atomic_float::AtomicF32,
     clock::InstantSamples,
-    dsp::{buffer::ChannelBuffer, declick::DeclickValues},
+    collector::ArcGc,
+    dsp::{buffer::ChannelBuffer, declick::DeclickValues, limiter::Limiter},
     event::{NodeEvent, ProcEventsIndex},
     log::RealtimeLogger,
-    node::{AudioNodeProcessor, ProcExtra, ProcStore},
+    node::{AudioNodeProcessor, ProcExtra, ProcStore, ProcessStatus},
     StreamInfo,
 };
 
 use crate::{
     backend::{AudioBackend, BackendProcessInfo},
     graph::ScheduleHeapData,
-    processor::event_scheduler::{EventScheduler, NodeEventSchedulerData},
+    input_activity::InputActivityDetector,
+    meter::MasterMeterProcessor,
+    processor::{
+        event_scheduler::{EventScheduler, NodeEventSchedulerData},
+        master_fade::MasterFade,
+        output_delay::OutputDelay,
+        preview::Preview,
+        scene_crossfade::SceneCrossfade,
+    },
 };
 
 #[cfg(feature = "scheduled_events")]
 use crate::context::ClearScheduledEventsType;
-#[cfg(feature = "scheduled_events")]
 use firewheel_core::node::NodeID;
 #[cfg(feature = "scheduled_events")]
 use smallvec::SmallVec;
@@ -33,7 +42,15 @@ use firewheel_core::clock::{InstantMusical, TransportState};
 
 mod event_scheduler;
 mod handle_messages;
+mod master_fade;
+mod output_delay;
+mod preview;
 mod process;
+mod scene_crossfade;
+mod voice_budget;
+
+pub(crate) use voice_budget::VoiceBudget;
+use voice_budget::VoiceState;
 
 #[cfg(feature = "musical_transport")]
 mod transport;
@@ -107,6 +124,17 @@ pub(crate) struct FirewheelProcessorInner<B: AudioBackend> {
     proc_transport_state: ProcTransportState,
 
     hard_clip_outputs: bool,
+    master_limiter: Option<MasterLimiter>,
+    master_meter: Option<MasterMeterProcessor>,
+    master_fade: MasterFade,
+    soft_start: MasterFade,
+    soft_start_seconds: f32,
+    output_delay: OutputDelay,
+    voice_budget: VoiceBudget,
+    preview: Preview,
+    scene_crossfade: SceneCrossfade,
+    global_wet: f32,
+    mono_audition: bool,
 
     pub(crate) extra: ProcExtra,
 
@@ -115,6 +143,9 @@ pub(crate) struct FirewheelProcessorInner<B: AudioBackend> {
     /// with the shared `Arc<AtomicRefCell<FirewheelProcessorInner>>` object.
     pub(crate) poisoned: bool,
     debug_force_clear_buffers: bool,
+    non_finite_sample_mode: NonFiniteSampleMode,
+    idle_flush_blocks: Option<NonZeroU32>,
+    deterministic: bool,
 }
 
 impl<B: AudioBackend> FirewheelProcessorInner<B> {
@@ -128,11 +159,22 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
         node_event_buffer_capacity: usize,
         stream_info: &StreamInfo,
         hard_clip_outputs: bool,
+        master_limiter: Option<MasterLimiter>,
+        master_meter: Option<MasterMeterProcessor>,
+        soft_start_seconds: f32,
+        max_output_channel_delay_seconds: f32,
+        voice_budget: VoiceBudget,
         buffer_out_of_space_mode: BufferOutOfSpaceMode,
         logger: RealtimeLogger,
         debug_force_clear_buffers: bool,
+        non_finite_sample_mode: NonFiniteSampleMode,
+        idle_flush_blocks: Option<NonZeroU32>,
+        deterministic: bool,
         store: ProcStore,
     ) -> Self {
+        let mut soft_start = MasterFade::new();
+        soft_start.fade_in(soft_start_seconds, stream_info.sample_rate);
+
         Self {
             nodes: Arena::new(),
             schedule_data: None,
@@ -153,6 +195,21 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
             #[cfg(feature = "musical_transport")]
             proc_transport_state: ProcTransportState::new(),
             hard_clip_outputs,
+            master_limiter,
+            master_meter,
+            master_fade: MasterFade::new(),
+            soft_start,
+            soft_start_seconds,
+            output_delay: OutputDelay::new(
+                max_output_channel_delay_seconds,
+                stream_info.num_stream_out_channels as usize,
+                stream_info.sample_rate,
+            ),
+            voice_budget,
+            preview: Preview::new(stream_info.max_block_frames.get() as usize),
+            scene_crossfade: SceneCrossfade::new(),
+            global_wet: 1.0,
+            mono_audition: false,
             extra: ProcExtra {
                 scratch_buffers: ChannelBuffer::new(stream_info.max_block_frames.get() as usize),
                 declick_values: DeclickValues::new(stream_info.declick_frames),
@@ -161,21 +218,156 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
             },
             poisoned: false,
             debug_force_clear_buffers,
+            non_finite_sample_mode,
+            idle_flush_blocks,
+            deterministic,
         }
     }
 }
 
+/// The limiter applied to the final output of the graph.
+pub(crate) struct MasterLimiter {
+    pub limiter: Limiter,
+    /// The largest gain reduction in the last processed block, in decibels.
+    pub gain_reduction_db: ArcGc<AtomicF32>,
+}
+
 pub(crate) struct NodeEntry {
     pub processor: Box<dyn AudioNodeProcessor>,
+    pub debug_name: &'static str,
+    /// Whether or not an error has been logged for this node outputting a
+    /// non-finite sample.
+    pub non_finite_reported: bool,
     pub prev_output_was_silent: bool,
+    /// The number of consecutive blocks this node's output has been silent,
+    /// saturating at the configured idle flush threshold.
+    pub idle_blocks: u32,
+    pub voice: VoiceState,
+    /// Whether or not this node is asleep because its output is not consumed.
+    pub asleep: bool,
+    /// Whether or not this node is an analysis tap whose inputs are passed
+    /// straight through to its outputs.
+    pub analysis_only: bool,
+    /// Whether or not the wet path of this node is bypassed, leaving only
+    /// its dry signal.
+    pub wet_bypassed: bool,
+    /// If set, then the outputs of this node are being faded out before the
+    /// node is removed.
+    pub fade_out: Option<NodeFadeOut>,
+    /// If set, then the context is told when the input of this node
+    /// transitions between silent and active.
+    pub input_activity: Option<InputActivityDetector>,
 
     event_data: NodeEventSchedulerData,
 }
 
+/// A linear fade applied to the outputs of a node before it is removed.
+pub(crate) struct NodeFadeOut {
+    gain: f32,
+    step: f32,
+    frames_left: u32,
+    /// Whether or not the context has been told that the fade has finished.
+    pub reported: bool,
+}
+
+impl NodeFadeOut {
+    pub fn new(fade_frames: u32) -> Self {
+        Self {
+            gain: 1.0,
+            step: 1.0 / (fade_frames.max(1) as f32),
+            frames_left: fade_frames,
+            reported: false,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.frames_left == 0
+    }
+
+    /// Apply the fade to the outputs of the node in the given range.
+    ///
+    /// Returns the new process status of the node.
+    pub fn process(
+        &mut self,
+        process_status: ProcessStatus,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        range: Range<usize>,
+    ) -> ProcessStatus {
+        let frames = range.end - range.start;
+
+        match process_status {
+            ProcessStatus::ClearAllOutputs => {
+                self.advance(frames);
+                return ProcessStatus::ClearAllOutputs;
+            }
+            ProcessStatus::Bypass => {
+                for (out_ch, in_ch) in outputs.iter_mut().zip(inputs.iter()) {
+                    out_ch[range.clone()].copy_from_slice(&in_ch[range.clone()]);
+                }
+                for out_ch in outputs.iter_mut().skip(inputs.len()) {
+                    out_ch[range.clone()].fill(0.0);
+                }
+            }
+            _ => {}
+        }
+
+        if self.finished() {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        for i in range {
+            if self.frames_left > 0 {
+                self.gain = (self.gain - self.step).max(0.0);
+                self.frames_left -= 1;
+            }
+
+            for out_ch in outputs.iter_mut() {
+                out_ch[i] *= self.gain;
+            }
+        }
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn advance(&mut self, frames: usize) {
+        let frames = (frames as u32).min(self.frames_left);
+        self.frames_left -= frames;
+        self.gain = (self.gain - self.step * frames as f32).max(0.0);
+    }
+}
+
 pub(crate) enum ContextToProcessorMsg {
     EventGroup(Vec<NodeEvent>),
     NewSchedule(Box<ScheduleHeapData>),
     HardClipOutputs(bool),
+    FadeMaster {
+        target_gain: f32,
+        duration_secs: f32,
+    },
+    SetOutputChannelDelay {
+        channel: usize,
+        delay_secs: f32,
+    },
+    SetVoiceBudget {
+        max_voices: Option<u32>,
+        cpu_budget: Option<f32>,
+    },
+    ReviveVoice(NodeID),
+    SetPreview {
+        node_id: Option<NodeID>,
+        fade_secs: f32,
+    },
+    FadeOutNode {
+        node_id: NodeID,
+        fade_frames: u32,
+    },
+    CrossfadeScene {
+        node_id: Option<NodeID>,
+        fade_secs: f32,
+    },
+    SetGlobalWet(f32),
+    SetMonoAudition(bool),
     #[cfg(feature = "musical_transport")]
     SetTransportState(Box<TransportState>),
     #[cfg(feature = "scheduled_events")]
@@ -185,6 +377,12 @@ pub(crate) enum ContextToProcessorMsg {
 pub(crate) enum ProcessorToContextMsg {
     ReturnEventGroup(Vec<NodeEvent>),
     ReturnSchedule(Box<ScheduleHeapData>),
+    VoiceCulled(NodeID),
+    NodeFadedOut(NodeID),
+    InputActivity {
+        node_id: NodeID,
+        active: bool,
+    },
     #[cfg(feature = "musical_transport")]
     ReturnTransportState(Box<TransportState>),
     #[cfg(feature = "scheduled_events")]
@@ -225,6 +423,27 @@ impl<I: Clone> Default for SharedClock<I> {
     }
 }
 
+/// How to handle a node outputting a non-finite sample (`NaN` or infinity).
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NonFiniteSampleMode {
+    /// Don't check the outputs of nodes.
+    #[default]
+    Ignore,
+    /// Replace any non-finite samples in the outputs of a node with `0.0`,
+    /// so that they don't spread to the rest of the graph. An error is
+    /// logged the first time a node outputs a non-finite sample.
+    Sanitize,
+    /// Panic on the first non-finite sample, with a message naming the node
+    /// which output it.
+    ///
+    /// This is useful for tracking down bugs while debugging. This only
+    /// panics in debug builds, and behaves the same as
+    /// [`NonFiniteSampleMode::Sanitize`] in release builds.
+    Panic,
+}
+
 /// How to handle event buffers on the audio thread running out of space.
 #[derive(Default, Debug, Clone, Copy, PartialEq, PartialOrd)]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
@@ -246,3 +465,441 @@ pub enum BufferOutOfSpaceMode {
     /// (Not generally recommended, but the option is here if you want it.)
     DropEvents,
 }
+
+#[cfg(test)]
+mod tests {
+    use core::{num::NonZeroU32, time::Duration};
+
+    use firewheel_core::{
+        channel_config::{ChannelConfig, ChannelCount},
+        diff::{Diff, PathBuilder},
+        dsp::{filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS, volume::Volume},
+        event::ProcEvents,
+        node::{
+            AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+            ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+        },
+    };
+    use firewheel_nodes::{
+        freeverb::FreeverbNode,
+        noise_generator::{pink::PinkNoiseGenNode, white::WhiteNoiseGenNode},
+        sampler::{RepeatMode, SamplerConfig, SamplerNode},
+        volume::VolumeNode,
+    };
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx, stereo_sine},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    // Large enough that the reverb produces output within the first block.
+    const BLOCK_FRAMES: usize = 2048;
+
+    /// Render an impulse through a reverb, optionally letting the graph sit
+    /// idle for a while after a first impulse.
+    fn render_impulse(idle_flush_blocks: Option<NonZeroU32>, idle_first: bool) -> Vec<f32> {
+        let mut cx = stereo_ctx(FirewheelConfig {
+            idle_flush_blocks,
+            ..Default::default()
+        });
+
+        let reverb = cx.add_node(FreeverbNode::default(), None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, reverb);
+        connect_stereo(&mut cx, reverb, graph_out);
+
+        let mut stream = DummyStream::default();
+        stream.stream_info.num_stream_in_channels = 2;
+        stream.stream_info.max_block_frames = NonZeroU32::new(BLOCK_FRAMES as u32).unwrap();
+        cx.start_stream(stream.clone()).unwrap();
+
+        let mut impulse = vec![0.0; BLOCK_FRAMES * 2];
+        impulse[0] = 1.0;
+        impulse[1] = 1.0;
+        let mut output = vec![0.0; BLOCK_FRAMES * 2];
+
+        if idle_first {
+            stream.process(&impulse, &mut output);
+
+            // Let the tail ring out well past the reverb's silence threshold.
+            for _ in 0..200 {
+                stream.process_block(BLOCK_FRAMES);
+            }
+        }
+
+        let mut rendered = Vec::new();
+        stream.process(&impulse, &mut output);
+        rendered.extend_from_slice(&output);
+        for _ in 0..8 {
+            rendered.extend(stream.process_block(BLOCK_FRAMES));
+        }
+
+        rendered
+    }
+
+    #[test]
+    fn idle_flush_zeroes_reverb_state() {
+        let fresh = render_impulse(None, false);
+
+        // With idle flushing, the reverb's internal state is reset to exact
+        // zero, so it behaves exactly like a freshly constructed reverb.
+        let flushed = render_impulse(NonZeroU32::new(4), true);
+        assert_eq!(fresh, flushed);
+
+        // Without it, residual state from the first impulse remains.
+        let unflushed = render_impulse(None, true);
+        assert_ne!(fresh, unflushed);
+    }
+
+    /// A generator that outputs the wall-clock time since the stream started,
+    /// as reported to the node.
+    struct StreamTimeSource;
+
+    impl AudioNode for StreamTimeSource {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("stream_time_source")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::ZERO,
+                    num_outputs: ChannelCount::MONO,
+                })
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            _cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            StreamTimeSourceProcessor
+        }
+    }
+
+    struct StreamTimeSourceProcessor;
+
+    impl AudioNodeProcessor for StreamTimeSourceProcessor {
+        fn process(
+            &mut self,
+            info: &ProcInfo,
+            buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            buffers.outputs[0][..info.frames].fill(info.duration_since_stream_start.as_secs_f32());
+
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    /// Render noise mixed with the wall-clock time reported to the nodes.
+    ///
+    /// Each block is reported to have started `wall_clock_jitter` later than
+    /// the audio clock says it did, like a real audio device would.
+    fn render_noise(deterministic_seed: Option<u64>, wall_clock_jitter: Duration) -> Vec<f32> {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            deterministic_seed,
+            ..Default::default()
+        });
+
+        let white = cx.add_node(WhiteNoiseGenNode::default(), None);
+        let pink = cx.add_node(PinkNoiseGenNode::default(), None);
+        let stream_time = cx.add_node(StreamTimeSource, None);
+        let graph_out = cx.graph_out_node_id();
+        cx.connect(white, graph_out, &[(0, 0)], false).unwrap();
+        cx.connect(pink, graph_out, &[(0, 1)], false).unwrap();
+        cx.connect(stream_time, graph_out, &[(0, 0), (0, 1)], false)
+            .unwrap();
+
+        let stream = DummyStream::default();
+        let block_duration = Duration::from_secs_f64(
+            BLOCK_FRAMES as f64 / stream.stream_info.sample_rate.get() as f64,
+        );
+        cx.start_stream(stream.clone()).unwrap();
+
+        let mut rendered = Vec::new();
+        for block in 0..4 {
+            rendered.extend(
+                stream.process_block_at(BLOCK_FRAMES, block_duration * block + wall_clock_jitter),
+            );
+        }
+
+        rendered
+    }
+
+    #[test]
+    fn deterministic_renders_are_identical() {
+        let jitter_a = Duration::from_micros(130);
+        let jitter_b = Duration::from_micros(870);
+
+        let a = render_noise(Some(1234), jitter_a);
+        let b = render_noise(Some(1234), jitter_b);
+        assert!(a.iter().any(|&s| s != 0.0));
+        assert_eq!(a, b);
+
+        // A different seed gives different noise.
+        assert_ne!(a, render_noise(Some(5678), jitter_a));
+
+        // Outside of deterministic mode, the wall-clock timing of the device
+        // leaks into the output even though the noise seeds are fixed.
+        assert_ne!(render_noise(None, jitter_a), render_noise(None, jitter_b));
+    }
+
+    #[test]
+    fn unity_volume_passes_through_without_copying() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let volume = cx.add_node(VolumeNode::default(), None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, volume);
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // Exactly one block, so the buffers are handed over exactly once.
+        let frames = stream.stream_info.max_block_frames.get() as usize;
+        let input = stereo_sine(frames, 0.5, 0.05);
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        assert_eq!(output, input);
+
+        let processor = stream.processor();
+        let schedule = &processor
+            .as_ref()
+            .unwrap()
+            .inner
+            .as_ref()
+            .unwrap()
+            .schedule_data
+            .as_ref()
+            .unwrap()
+            .schedule;
+        assert_eq!(schedule.num_handed_over_buffers(), 4);
+    }
+
+    /// Step a volume node with no smoothing of its own from unity gain to
+    /// silence, returning the left channel of the block after the step.
+    fn render_volume_step(min_param_ramp_seconds: f32) -> Vec<f32> {
+        let mut cx = stereo_ctx(FirewheelConfig {
+            min_param_ramp_seconds,
+            ..Default::default()
+        });
+
+        let mut params = VolumeNode {
+            smooth_seconds: 0.0,
+            ..Default::default()
+        };
+        let volume = cx.add_node(params, None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, volume);
+        connect_stereo(&mut cx, volume, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let frames = stream.stream_info.max_block_frames.get() as usize;
+        let input = vec![1.0; frames * 2];
+        let mut output = vec![0.0; frames * 2];
+        stream.process(&input, &mut output);
+
+        let baseline = params;
+        params.volume = Volume::SILENT;
+        params.diff(
+            &baseline,
+            PathBuilder::default(),
+            &mut cx.event_queue(volume),
+        );
+        cx.update().unwrap();
+
+        stream.process(&input, &mut output);
+        output.iter().step_by(2).copied().collect()
+    }
+
+    #[test]
+    fn stepped_param_is_ramped_over_min_window() {
+        let ramped = render_volume_step(DEFAULT_SMOOTH_SECONDS);
+
+        // The gain starts falling from unity instead of jumping to silence,
+        // and is about one time constant along after the ramp window.
+        let window_frames = (DEFAULT_SMOOTH_SECONDS * 44_100.0) as usize;
+        assert!(ramped[0] > 0.9);
+        assert!((ramped[window_frames] - (-1.0f32).exp()).abs() < 0.05);
+        assert!(ramped.last().unwrap() < &0.3);
+
+        // Without a minimum ramp time, the node reaches silence within a
+        // few frames.
+        let stepped = render_volume_step(0.0);
+        assert!(stepped[3] < 0.01);
+    }
+
+    /// Feed a mono graph input through a stereo volume node, returning the
+    /// interleaved stereo output.
+    fn render_mono_into_stereo(fan_out_mono: bool) -> (Vec<f32>, Vec<f32>) {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            num_graph_inputs: ChannelCount::MONO,
+            fan_out_mono,
+            ..Default::default()
+        });
+
+        let volume = cx.add_node(VolumeNode::default(), None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        cx.connect_all(graph_in, volume, false).unwrap();
+        cx.connect_all(volume, graph_out, false).unwrap();
+
+        let mut stream = DummyStream::default();
+        stream.stream_info.num_stream_in_channels = 1;
+        cx.start_stream(stream.clone()).unwrap();
+
+        let frames = stream.stream_info.max_block_frames.get() as usize;
+        let input: Vec<f32> = (0..frames).map(|i| (i as f32 * 0.05).sin()).collect();
+        let mut output = vec![0.0; frames * 2];
+        stream.process(&input, &mut output);
+
+        (input, output)
+    }
+
+    #[test]
+    fn mono_source_fans_out_to_stereo_node() {
+        let (input, output) = render_mono_into_stereo(true);
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = output.iter().skip(1).step_by(2).copied().collect();
+        assert_eq!(left, input);
+        assert_eq!(right, input);
+
+        // Without fanning out, only the first channel is connected.
+        let (input, output) = render_mono_into_stereo(false);
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        assert_eq!(left, input);
+        assert!(output.iter().skip(1).step_by(2).all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn looped_sample_at_non_unit_speed_is_seamless() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        // A sine wave whose period divides the loop length, so a correct
+        // loop has no discontinuity.
+        let len_frames = 1_000;
+        let source: Vec<f32> = (0..len_frames)
+            .map(|i| (i as f32 * core::f32::consts::TAU / 100.0).sin())
+            .collect();
+
+        let mut sampler = SamplerNode {
+            repeat_mode: RepeatMode::RepeatEndlessly,
+            speed: 1.5,
+            ..Default::default()
+        };
+        sampler.set_sample(firewheel_core::collector::ArcGc::new_unsized(|| {
+            alloc::sync::Arc::new(vec![source.clone()]) as _
+        }));
+        sampler.start_or_restart();
+        // Compare against plain linear interpolation, without the
+        // antialiasing filter.
+        let sampler = cx.add_node(
+            sampler,
+            Some(SamplerConfig {
+                anti_alias: false,
+                ..Default::default()
+            }),
+        );
+        cx.connect(sampler, cx.graph_out_node_id(), &[(0, 0), (1, 1)], false)
+            .unwrap();
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+
+        // Render several loops in uneven block sizes.
+        let mut output = Vec::new();
+        for frames in [300, 517, 1024, 64, 999, 700] {
+            output.extend(stream.process_block(frames).into_iter().step_by(2));
+        }
+
+        for (i, &s) in output.iter().enumerate() {
+            let pos = i as f64 * 1.5;
+            let frame = pos.trunc() as usize;
+            let fract = pos.fract() as f32;
+            let s0 = source[frame % len_frames];
+            let s1 = source[(frame + 1) % len_frames];
+            let expected = s0 + ((s1 - s0) * fract);
+
+            assert!(
+                (s - expected).abs() < 1e-3,
+                "frame {i}: expected {expected}, got {s}"
+            );
+        }
+    }
+
+    #[test]
+    fn sampler_reports_playhead_within_interval() {
+        use firewheel_core::diff::{Diff, PathBuilder};
+        use firewheel_nodes::sampler::{PlayFrom, SamplerState};
+
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let len_frames = 10_000;
+        let mut params = SamplerNode {
+            repeat_mode: RepeatMode::RepeatEndlessly,
+            ..Default::default()
+        };
+        params.set_sample(firewheel_core::collector::ArcGc::new_unsized(|| {
+            alloc::sync::Arc::new(vec![vec![0.5f32; len_frames as usize]]) as _
+        }));
+        params.start_or_restart();
+
+        let interval_blocks = 4;
+        let sampler = cx.add_node(
+            params.clone(),
+            Some(SamplerConfig {
+                playhead_report_interval_blocks: interval_blocks,
+                ..Default::default()
+            }),
+        );
+        cx.connect(sampler, cx.graph_out_node_id(), &[(0, 0)], false)
+            .unwrap();
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+        cx.update().unwrap();
+
+        let block_frames = 256;
+        let reported = |cx: &FirewheelCtx<DummyBackend>| {
+            cx.node_state::<SamplerState>(sampler)
+                .unwrap()
+                .playhead_frames()
+                .0 as u64
+        };
+
+        // Play through the end of the sample a few times. The reported
+        // playhead wraps around with the loop, and lags behind by less than
+        // one reporting interval.
+        let mut expected = 0;
+        for _ in 0..150 {
+            stream.process_block(block_frames);
+            expected = (expected + block_frames as u64) % len_frames;
+
+            let behind = (expected + len_frames - reported(&cx)) % len_frames;
+            assert!(
+                behind < u64::from(interval_blocks) * block_frames as u64,
+                "expected {expected}, got {}",
+                reported(&cx)
+            );
+        }
+
+        // A seek is reported right away.
+        let baseline = params.clone();
+        params.start_from(PlayFrom::Frames(5_000));
+        params.diff(
+            &baseline,
+            PathBuilder::default(),
+            &mut cx.event_queue(sampler),
+        );
+        cx.update().unwrap();
+        stream.process_block(block_frames);
+        assert_eq!(reported(&cx), 5_000);
+    }
+}
@@ -866,3 +866,73 @@ pub(super) struct SubChunkInfo {
     pub sub_chunk_range: Range<usize>,
     pub sub_clock_samples: InstantSamples,
 }
+
+#[cfg(all(test, feature = "scheduled_events"))]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use firewheel_core::{
+        clock::{EventInstant, InstantSamples},
+        diff::{Diff, PathBuilder},
+        dsp::fade::FadeCurve,
+        StreamInfo,
+    };
+    use firewheel_nodes::sampler::{SamplerConfig, SamplerNode, SamplerVoiceFade};
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    #[test]
+    fn scheduled_note_off_releases_at_its_frame_offset() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+
+        let mut params = SamplerNode::default();
+        params.set_sample(firewheel_core::collector::ArcGc::new_unsized(|| {
+            alloc::sync::Arc::new(vec![vec![0.5f32; 48_000]]) as _
+        }));
+        params.start_or_restart();
+        let sampler = cx.add_node(
+            params.clone(),
+            Some(SamplerConfig {
+                voice_fade: Some(SamplerVoiceFade {
+                    attack_secs: 0.0,
+                    release_secs: 0.005,
+                    curve: FadeCurve::Linear,
+                }),
+                ..Default::default()
+            }),
+        );
+        cx.connect(sampler, cx.graph_out_node_id(), &[(0, 0)], false)
+            .unwrap();
+
+        let stream = DummyStream::new(StreamInfo {
+            sample_rate: NonZeroU32::new(48_000).unwrap(),
+            ..Default::default()
+        });
+        cx.start_stream(stream.clone()).unwrap();
+        cx.update().unwrap();
+        stream.process_block(256);
+
+        // Schedule the note-off 100 frames into the next block.
+        let baseline = params.clone();
+        params.stop();
+        let note_off = InstantSamples(cx.audio_clock().samples.0 + 100);
+        params.diff(
+            &baseline,
+            PathBuilder::default(),
+            &mut cx.event_queue_scheduled(sampler, Some(EventInstant::Samples(note_off))),
+        );
+        cx.update().unwrap();
+
+        let output: Vec<f32> = stream.process_block(512).into_iter().step_by(2).collect();
+
+        // The voice sustains right up to the note-off...
+        assert!(output[..100].iter().all(|&s| s == 0.5));
+        // ...and the release begins at exactly that frame.
+        assert!(output[100] < 0.5);
+        assert!(output[100..340].windows(2).all(|w| w[1] <= w[0]));
+        assert!(output[340..].iter().all(|&s| s == 0.0));
+    }
+}
@@ -1,5 +1,5 @@
 use firewheel_core::{
-    dsp::{buffer::ChannelBuffer, declick::DeclickValues},
+    dsp::{buffer::ChannelBuffer, declick::DeclickValues, limiter::Limiter},
     node::ProcStreamCtx,
     StreamInfo,
 };
@@ -14,9 +14,11 @@ use firewheel_core::clock::TransportState;
 use crate::{
     backend::AudioBackend,
     graph::{NodeHeapData, ScheduleHeapData},
+    input_activity::InputActivityDetector,
+    processor::preview::Preview,
     processor::{
         ContextToProcessorMsg, FirewheelProcessorInner, NodeEntry, NodeEventSchedulerData,
-        ProcessorToContextMsg,
+        NodeFadeOut, ProcessorToContextMsg, VoiceState,
     },
 };
 
@@ -45,6 +47,53 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                 ContextToProcessorMsg::HardClipOutputs(hard_clip_outputs) => {
                     self.hard_clip_outputs = hard_clip_outputs;
                 }
+                ContextToProcessorMsg::FadeMaster {
+                    target_gain,
+                    duration_secs,
+                } => {
+                    self.master_fade
+                        .fade_to(target_gain, duration_secs, self.sample_rate);
+                }
+                ContextToProcessorMsg::SetOutputChannelDelay {
+                    channel,
+                    delay_secs,
+                } => {
+                    self.output_delay
+                        .set_delay(channel, delay_secs, self.sample_rate);
+                }
+                ContextToProcessorMsg::SetVoiceBudget {
+                    max_voices,
+                    cpu_budget,
+                } => {
+                    self.voice_budget.max_voices = max_voices;
+                    self.voice_budget.cpu_budget = cpu_budget;
+                }
+                ContextToProcessorMsg::FadeOutNode {
+                    node_id,
+                    fade_frames,
+                } => {
+                    if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                        node_entry.fade_out = Some(NodeFadeOut::new(fade_frames));
+                    }
+                }
+                ContextToProcessorMsg::SetPreview { node_id, fade_secs } => {
+                    self.preview.set_node(node_id, fade_secs, self.sample_rate);
+                }
+                ContextToProcessorMsg::CrossfadeScene { node_id, fade_secs } => {
+                    self.scene_crossfade
+                        .crossfade_to(node_id, fade_secs, self.sample_rate);
+                }
+                ContextToProcessorMsg::SetGlobalWet(global_wet) => {
+                    self.global_wet = global_wet;
+                }
+                ContextToProcessorMsg::SetMonoAudition(mono_audition) => {
+                    self.mono_audition = mono_audition;
+                }
+                ContextToProcessorMsg::ReviveVoice(node_id) => {
+                    if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                        node_entry.voice.culled = false;
+                    }
+                }
                 #[cfg(feature = "musical_transport")]
                 ContextToProcessorMsg::SetTransportState(new_transport_state) => {
                     self.set_transport_state(new_transport_state);
@@ -96,8 +145,10 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
 
                     old_schedule_data.removed_nodes.push(NodeHeapData {
                         id: *node_id,
+                        debug_name: node_entry.debug_name,
                         processor: node_entry.processor,
                         is_pre_process: false,
+                        analysis_only: node_entry.analysis_only,
                     });
                 }
             }
@@ -116,7 +167,16 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                     n.id.0,
                     NodeEntry {
                         processor: n.processor,
+                        debug_name: n.debug_name,
+                        non_finite_reported: false,
                         prev_output_was_silent: true,
+                        idle_blocks: 0,
+                        voice: VoiceState::default(),
+                        wet_bypassed: false,
+                        asleep: false,
+                        analysis_only: n.analysis_only,
+                        fade_out: None,
+                        input_activity: None,
                         event_data: NodeEventSchedulerData::new(n.is_pre_process),
                     }
                 )
@@ -129,6 +189,35 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                 .remove_events_from_removed_nodes(&self.nodes);
         }
 
+        for (node_id, is_voice) in new_schedule_data.schedule.voice_flags() {
+            if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                node_entry.voice.is_voice = is_voice;
+                node_entry.voice.culled &= is_voice;
+            }
+        }
+
+        for (node_id, wet_bypassed) in new_schedule_data.schedule.wet_bypass_flags() {
+            if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                node_entry.wet_bypassed = wet_bypassed;
+            }
+        }
+
+        for (node_id, config) in new_schedule_data.schedule.input_activity_configs() {
+            if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                // Keep the state of the detector if its configuration is unchanged.
+                if node_entry.input_activity.as_ref().map(|d| d.config) != config {
+                    node_entry.input_activity =
+                        config.map(|config| InputActivityDetector::new(config, self.sample_rate));
+                }
+            }
+        }
+
+        for (node_id, asleep) in new_schedule_data.schedule.sleep_flags() {
+            if let Some(node_entry) = self.nodes.get_mut(node_id.0) {
+                node_entry.asleep = asleep;
+            }
+        }
+
         self.schedule_data = Some(new_schedule_data);
     }
 
@@ -178,7 +267,33 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
             );
         }
 
+        self.soft_start
+            .fade_in(self.soft_start_seconds, stream_info.sample_rate);
+
+        if let Some(master_limiter) = &mut self.master_limiter {
+            let config = *master_limiter.limiter.config();
+            master_limiter.limiter = Limiter::new(
+                config,
+                stream_info.num_stream_out_channels as usize,
+                stream_info.sample_rate,
+            );
+        }
+
+        if let Some(master_meter) = &mut self.master_meter {
+            master_meter.new_stream();
+        }
+
+        self.output_delay.new_stream(
+            stream_info.num_stream_out_channels as usize,
+            stream_info.sample_rate,
+        );
+
         if self.sample_rate != stream_info.sample_rate {
+            self.master_fade
+                .update_sample_rate(self.sample_rate, stream_info.sample_rate);
+            self.scene_crossfade
+                .update_sample_rate(self.sample_rate, stream_info.sample_rate);
+
             self.clock_samples = self
                 .clock_samples
                 .to_seconds(self.sample_rate, self.sample_rate_recip)
@@ -209,6 +324,7 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
 
             self.extra.scratch_buffers =
                 ChannelBuffer::new(stream_info.max_block_frames.get() as usize);
+            self.preview = Preview::new(self.max_block_frames);
         }
     }
 }
@@ -0,0 +1,135 @@
+use core::num::NonZeroU32;
+
+/// A declicked gain applied to the final output of the graph, used for
+/// app-level fade-ins, fade-outs, and scene transitions.
+pub(crate) struct MasterFade {
+    gain: f32,
+    target_gain: f32,
+    step: f32,
+    frames_left: usize,
+}
+
+impl MasterFade {
+    pub fn new() -> Self {
+        Self {
+            gain: 1.0,
+            target_gain: 1.0,
+            step: 0.0,
+            frames_left: 0,
+        }
+    }
+
+    /// Linearly ramp the gain (in raw amplitude) to `target_gain` over the
+    /// given duration, starting from the current gain.
+    pub fn fade_to(&mut self, target_gain: f32, duration_secs: f32, sample_rate: NonZeroU32) {
+        let target_gain = target_gain.max(0.0);
+        let frames = (duration_secs.max(0.0) * sample_rate.get() as f32) as usize;
+
+        self.target_gain = target_gain;
+
+        if frames == 0 {
+            self.gain = target_gain;
+            self.step = 0.0;
+            self.frames_left = 0;
+        } else {
+            self.step = (target_gain - self.gain) / frames as f32;
+            self.frames_left = frames;
+        }
+    }
+
+    /// Start from silence and linearly ramp the gain up to unity over the
+    /// given duration.
+    pub fn fade_in(&mut self, duration_secs: f32, sample_rate: NonZeroU32) {
+        self.gain = 0.0;
+        self.fade_to(1.0, duration_secs, sample_rate);
+    }
+
+    /// Apply the gain to a block of interleaved audio data in place.
+    pub fn process_interleaved(&mut self, data: &mut [f32], num_channels: usize) {
+        if num_channels == 0 {
+            return;
+        }
+
+        if self.frames_left == 0 {
+            if self.gain == 0.0 {
+                data.fill(0.0);
+            } else if self.gain != 1.0 {
+                for s in data.iter_mut() {
+                    *s *= self.gain;
+                }
+            }
+
+            return;
+        }
+
+        for frame in data.chunks_exact_mut(num_channels) {
+            if self.frames_left > 0 {
+                self.frames_left -= 1;
+                self.gain = if self.frames_left == 0 {
+                    self.target_gain
+                } else {
+                    self.gain + self.step
+                };
+            }
+
+            for s in frame.iter_mut() {
+                *s *= self.gain;
+            }
+        }
+    }
+
+    /// Change the sample rate, keeping the remaining time of an active fade.
+    pub fn update_sample_rate(&mut self, old_sample_rate: NonZeroU32, sample_rate: NonZeroU32) {
+        if self.frames_left > 0 {
+            let secs_left = self.frames_left as f32 / old_sample_rate.get() as f32;
+            self.fade_to(self.target_gain, secs_left, sample_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig,
+    };
+
+    #[test]
+    fn master_fade_reaches_target() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // A 100ms fade at 44.1kHz.
+        cx.fade_master(-12.0, 100.0).unwrap();
+        let fade_frames = 4_410;
+
+        let input = vec![1.0; (fade_frames + 1_000) * 2];
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        let target = firewheel_core::dsp::volume::db_to_amp(-12.0);
+
+        // The gain falls smoothly, and then holds the target.
+        for frame in output[..fade_frames * 2]
+            .chunks(2)
+            .collect::<Vec<_>>()
+            .windows(2)
+        {
+            assert!(frame[1][0] < frame[0][0] && frame[0][0] - frame[1][0] < 0.001);
+        }
+        assert!(output[..(fade_frames - 1) * 2].iter().all(|&s| s > target));
+        assert!(output[(fade_frames - 1) * 2..]
+            .iter()
+            .all(|&s| (s - target).abs() < 1e-6));
+
+        // Fading to silence mutes the output.
+        cx.fade_master(f32::NEG_INFINITY, 10.0).unwrap();
+        stream.process(&input, &mut output);
+        assert!(output[1_000..].iter().all(|&s| s == 0.0));
+    }
+}
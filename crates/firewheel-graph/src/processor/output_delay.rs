@@ -0,0 +1,143 @@
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use firewheel_core::{
+    channel_config::MAX_CHANNELS,
+    dsp::delay_line::{delay_frames_for_seconds, RingDelayLine},
+};
+
+/// An independent delay for each channel of the final output of the graph,
+/// used to time-align speakers placed at different distances.
+pub(crate) struct OutputDelay {
+    max_delay_seconds: f32,
+    delay_seconds: [f32; MAX_CHANNELS],
+    delay_frames: Vec<usize>,
+    lines: Vec<RingDelayLine>,
+}
+
+impl OutputDelay {
+    /// Note, this method gets called on the main thread, not the audio thread.
+    pub fn new(max_delay_seconds: f32, num_channels: usize, sample_rate: NonZeroU32) -> Self {
+        let mut output_delay = Self {
+            max_delay_seconds: max_delay_seconds.max(0.0),
+            delay_seconds: [0.0; MAX_CHANNELS],
+            delay_frames: Vec::new(),
+            lines: Vec::new(),
+        };
+        output_delay.new_stream(num_channels, sample_rate);
+        output_delay
+    }
+
+    /// Reallocate the delay lines for a new stream, keeping the configured
+    /// delay of each channel.
+    ///
+    /// Note, this method gets called on the main thread, not the audio thread.
+    pub fn new_stream(&mut self, num_channels: usize, sample_rate: NonZeroU32) {
+        let num_channels = num_channels.min(MAX_CHANNELS);
+        let max_delay_frames = delay_frames_for_seconds(self.max_delay_seconds, sample_rate);
+
+        self.lines = (0..num_channels)
+            .map(|_| RingDelayLine::new(max_delay_frames))
+            .collect();
+        self.delay_frames = self.delay_seconds[..num_channels]
+            .iter()
+            .map(|&secs| Self::frames(secs, max_delay_frames, sample_rate))
+            .collect();
+    }
+
+    /// Set the delay of a single output channel in seconds.
+    pub fn set_delay(&mut self, channel: usize, delay_seconds: f32, sample_rate: NonZeroU32) {
+        if channel >= MAX_CHANNELS {
+            return;
+        }
+
+        let delay_seconds = delay_seconds.clamp(0.0, self.max_delay_seconds);
+        self.delay_seconds[channel] = delay_seconds;
+
+        if let Some(line) = self.lines.get_mut(channel) {
+            let frames = Self::frames(delay_seconds, line.max_delay_frames(), sample_rate);
+
+            if self.delay_frames[channel] != frames {
+                // The delay line isn't written to while a channel has no
+                // delay, so clear out any stale samples.
+                line.reset();
+                self.delay_frames[channel] = frames;
+            }
+        }
+    }
+
+    fn frames(delay_seconds: f32, max_delay_frames: usize, sample_rate: NonZeroU32) -> usize {
+        ((delay_seconds * sample_rate.get() as f32).round() as usize).min(max_delay_frames)
+    }
+
+    /// Apply the delays to a block of interleaved audio data in place.
+    pub fn process_interleaved(&mut self, data: &mut [f32], num_channels: usize) {
+        if num_channels == 0 {
+            return;
+        }
+
+        for (ch, (line, &delay_frames)) in self
+            .lines
+            .iter_mut()
+            .zip(self.delay_frames.iter())
+            .enumerate()
+            .take(num_channels)
+        {
+            if delay_frames == 0 {
+                continue;
+            }
+
+            for frame in data.chunks_exact_mut(num_channels) {
+                line.write(frame[ch]);
+                frame[ch] = line.read_at_frames(delay_frames);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig,
+    };
+
+    #[test]
+    fn output_channel_delay_delays_only_that_channel() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // 10ms at 44.1kHz.
+        cx.set_output_channel_delay(1, 10.0).unwrap();
+        assert_eq!(cx.output_channel_delay_ms(1), 10.0);
+        let delay_frames = 441;
+
+        // An impulse on both channels.
+        let mut input = vec![0.0; 1_024 * 2];
+        input[0] = 1.0;
+        input[1] = 1.0;
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let right: Vec<f32> = output.iter().skip(1).step_by(2).copied().collect();
+
+        assert_eq!(left[0], 1.0);
+        assert!(left[1..].iter().all(|&s| s == 0.0));
+
+        assert_eq!(right[delay_frames], 1.0);
+        assert!(right
+            .iter()
+            .enumerate()
+            .all(|(i, &s)| i == delay_frames || s == 0.0));
+    }
+}
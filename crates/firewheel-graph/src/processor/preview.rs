@@ -0,0 +1,218 @@
+use core::{num::NonZeroU32, ops::Range};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+
+use firewheel_core::{
+    channel_config::MAX_CHANNELS,
+    node::{NodeID, ProcessStatus},
+};
+
+/// Routes the output of a single node directly to the graph output,
+/// crossfading it with the normal mix.
+pub(crate) struct Preview {
+    node_id: Option<NodeID>,
+
+    /// The output of the previewed node this block, one channel after another.
+    buffer: Vec<f32>,
+    max_block_frames: usize,
+    /// The number of channels captured this block, or `0` if the previewed
+    /// node was not processed.
+    num_channels: usize,
+
+    /// The amount of the preview in the output, where `0.0` is only the
+    /// normal mix and `1.0` is only the previewed node.
+    amount: f32,
+    target_amount: f32,
+    step: f32,
+    frames_left: usize,
+}
+
+impl Preview {
+    pub fn new(max_block_frames: usize) -> Self {
+        Self {
+            node_id: None,
+            buffer: vec![0.0; max_block_frames * MAX_CHANNELS],
+            max_block_frames,
+            num_channels: 0,
+            amount: 0.0,
+            target_amount: 0.0,
+            step: 0.0,
+            frames_left: 0,
+        }
+    }
+
+    /// Start previewing the given node, or stop previewing if `None`.
+    pub fn set_node(&mut self, node_id: Option<NodeID>, fade_secs: f32, sample_rate: NonZeroU32) {
+        if let Some(node_id) = node_id {
+            self.node_id = Some(node_id);
+        }
+
+        let target_amount = if node_id.is_some() { 1.0 } else { 0.0 };
+        let frames = (fade_secs.max(0.0) * sample_rate.get() as f32) as usize;
+
+        self.target_amount = target_amount;
+
+        if frames == 0 {
+            self.amount = target_amount;
+            self.step = 0.0;
+            self.frames_left = 0;
+
+            if target_amount == 0.0 {
+                self.node_id = None;
+            }
+        } else {
+            self.step = (target_amount - self.amount) / frames as f32;
+            self.frames_left = frames;
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.node_id.is_some()
+    }
+
+    pub fn begin_block(&mut self) {
+        self.num_channels = 0;
+    }
+
+    /// Store the output of a node for the given range of frames if it is the
+    /// node being previewed.
+    pub fn capture(
+        &mut self,
+        node_id: NodeID,
+        process_status: ProcessStatus,
+        inputs: &[&[f32]],
+        outputs: &[&mut [f32]],
+        range: Range<usize>,
+    ) {
+        if self.node_id != Some(node_id) {
+            return;
+        }
+
+        self.num_channels = outputs.len().min(MAX_CHANNELS);
+
+        for (ch_i, out_ch) in outputs.iter().take(self.num_channels).enumerate() {
+            let start = ch_i * self.max_block_frames;
+            let buf = &mut self.buffer[start + range.start..start + range.end];
+
+            match process_status {
+                ProcessStatus::ClearAllOutputs => buf.fill(0.0),
+                ProcessStatus::Bypass => match inputs.get(ch_i) {
+                    Some(in_ch) => buf.copy_from_slice(&in_ch[range.clone()]),
+                    None => buf.fill(0.0),
+                },
+                _ => buf.copy_from_slice(&out_ch[range.clone()]),
+            }
+        }
+    }
+
+    /// Crossfade a block of interleaved graph output with the output of the
+    /// previewed node.
+    ///
+    /// A mono node is sent to every output channel.
+    pub fn mix_interleaved(&mut self, output: &mut [f32], num_out_channels: usize) {
+        if self.node_id.is_none() || num_out_channels == 0 {
+            return;
+        }
+
+        for (frame_i, frame) in output.chunks_exact_mut(num_out_channels).enumerate() {
+            if self.frames_left > 0 {
+                self.frames_left -= 1;
+                self.amount = if self.frames_left == 0 {
+                    self.target_amount
+                } else {
+                    self.amount + self.step
+                };
+            }
+
+            for (ch_i, s) in frame.iter_mut().enumerate() {
+                let preview = if self.num_channels == 0 {
+                    0.0
+                } else {
+                    self.buffer[(ch_i % self.num_channels) * self.max_block_frames + frame_i]
+                };
+
+                *s = *s * (1.0 - self.amount) + preview * self.amount;
+            }
+        }
+
+        if self.frames_left == 0 && self.target_amount == 0.0 {
+            // The preview has faded out.
+            self.node_id = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_core::dsp::volume::Volume;
+    use firewheel_nodes::volume::VolumeNode;
+
+    use crate::{
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig,
+    };
+
+    #[test]
+    fn preview_routes_node_to_output_and_restores_mix() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+
+        // The normal mix passes the input straight through, while the
+        // auditioned node is not connected to the output at all.
+        let mix = cx.add_node(VolumeNode::default(), None);
+        let auditioned = cx.add_node(
+            VolumeNode {
+                volume: Volume::Linear(0.5),
+                ..Default::default()
+            },
+            None,
+        );
+        connect_stereo(&mut cx, graph_in, mix);
+        connect_stereo(&mut cx, mix, graph_out);
+        connect_stereo(&mut cx, graph_in, auditioned);
+        let num_edges = cx.edges().count();
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let input = vec![1.0; 1024 * 2];
+        let mut output = vec![0.0; input.len()];
+
+        // A 10ms fade at 44.1kHz.
+        let fade_frames = 441;
+        cx.preview_node(auditioned, 10.0).unwrap();
+        assert_eq!(cx.previewed_node(), Some(auditioned));
+        stream.process(&input, &mut output);
+
+        // The output crossfades smoothly to the auditioned node.
+        for w in output[..fade_frames * 2]
+            .chunks(2)
+            .collect::<Vec<_>>()
+            .windows(2)
+        {
+            assert!(w[1][0] < w[0][0] && w[0][0] - w[1][0] < 0.01);
+        }
+        assert!(output[fade_frames * 2..]
+            .iter()
+            .all(|&s| (s - 0.25).abs() < 1e-6));
+
+        // Stopping the preview restores the normal mix.
+        cx.stop_preview(10.0).unwrap();
+        assert_eq!(cx.previewed_node(), None);
+        stream.process(&input, &mut output);
+        for w in output[..fade_frames * 2]
+            .chunks(2)
+            .collect::<Vec<_>>()
+            .windows(2)
+        {
+            assert!(w[1][0] > w[0][0] && w[1][0] - w[0][0] < 0.01);
+        }
+        assert!(output[fade_frames * 2..].iter().all(|&s| s == 1.0));
+
+        // The routing of the graph was never changed.
+        assert_eq!(cx.edges().count(), num_edges);
+        assert_eq!(cx.incoming_edges(graph_out).count(), 2);
+    }
+}
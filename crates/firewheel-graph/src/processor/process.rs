@@ -1,9 +1,10 @@
-use core::{num::NonZeroU32, time::Duration};
+use core::{fmt::Write, num::NonZeroU32, ops::Range, time::Duration};
 
 #[cfg(not(feature = "std"))]
 use num_traits::Float;
 
 use arrayvec::ArrayVec;
+use bevy_platform::sync::atomic::Ordering;
 use firewheel_core::{
     channel_config::MAX_CHANNELS,
     clock::{DurationSamples, InstantSamples},
@@ -11,10 +12,14 @@ use firewheel_core::{
     mask::{ConnectedMask, ConstantMask, MaskType, SilenceMask},
     node::{NodeID, ProcBuffers, ProcExtra, ProcInfo, ProcessStatus, StreamStatus},
 };
+use ringbuf::traits::Producer;
 
 use crate::{
     backend::{AudioBackend, BackendProcessInfo},
-    processor::{event_scheduler::SubChunkInfo, FirewheelProcessorInner, NodeEntry, SharedClock},
+    processor::{
+        event_scheduler::SubChunkInfo, FirewheelProcessorInner, NodeEntry, NonFiniteSampleMode,
+        ProcessorToContextMsg, SharedClock,
+    },
 };
 
 #[cfg(feature = "musical_transport")]
@@ -41,6 +46,17 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
             mut dropped_frames,
         } = info;
 
+        // TODO: Remove this feature gate if `bevy_platform` implements this.
+        #[cfg(feature = "std")]
+        let cycle_start = bevy_platform::time::Instant::now();
+
+        let duration_since_stream_start = if self.deterministic {
+            // Don't let wall-clock time leak into the nodes.
+            Duration::from_secs_f64(self.clock_samples.0 as f64 * self.sample_rate_recip)
+        } else {
+            duration_since_stream_start
+        };
+
         if input_stream_status.contains(StreamStatus::INPUT_OVERFLOW) {
             let _ = self.extra.logger.try_error("Firewheel input to output stream channel overflowed! Try increasing the capacity of the channel.");
         }
@@ -116,6 +132,7 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                 );
 
             // Process the block.
+            self.preview.begin_block();
             self.process_block(
                 block_frames,
                 self.sample_rate,
@@ -148,6 +165,13 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                     },
                 );
 
+            // Crossfade the graph output with the output of the previewed node.
+            self.preview.mix_interleaved(
+                &mut output[frames_processed * num_out_channels
+                    ..(frames_processed + block_frames) * num_out_channels],
+                num_out_channels,
+            );
+
             // Advance to the next processing block.
             frames_processed += block_frames;
             clock_samples += DurationSamples(block_frames as i64);
@@ -155,6 +179,42 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
             dropped_frames = 0;
         }
 
+        // --- Mono audition -------------------------------------------------------------------
+
+        if self.mono_audition && num_out_channels >= 2 {
+            for frame in output.chunks_exact_mut(num_out_channels) {
+                let mono = (frame[0] + frame[1]) * 0.5;
+                frame[0] = mono;
+                frame[1] = mono;
+            }
+        }
+
+        // --- Master fade --------------------------------------------------------------------
+
+        self.master_fade
+            .process_interleaved(output, num_out_channels);
+
+        // --- Master limiter -----------------------------------------------------------------
+
+        if let Some(master_limiter) = &mut self.master_limiter {
+            let gain_reduction_db = master_limiter
+                .limiter
+                .process_interleaved(output, num_out_channels);
+            master_limiter
+                .gain_reduction_db
+                .store(gain_reduction_db, Ordering::Relaxed);
+        }
+
+        // --- Soft start ---------------------------------------------------------------------
+
+        self.soft_start
+            .process_interleaved(output, num_out_channels);
+
+        // --- Output channel delay -----------------------------------------------------------
+
+        self.output_delay
+            .process_interleaved(output, num_out_channels);
+
         // --- Hard clip outputs --------------------------------------------------------------
 
         if self.hard_clip_outputs {
@@ -162,6 +222,27 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                 *s = s.fract();
             }
         }
+
+        // --- Master meter -------------------------------------------------------------------
+
+        if let Some(master_meter) = &mut self.master_meter {
+            master_meter.process_interleaved(output, num_out_channels);
+        }
+
+        // --- Voice budget -------------------------------------------------------------------
+
+        let cycle_secs = frames as f64 * self.sample_rate_recip;
+
+        #[cfg(feature = "std")]
+        let load = {
+            let load = (cycle_start.elapsed().as_secs_f64() / cycle_secs) as f32;
+            self.voice_budget.cpu_load.store(load, Ordering::Relaxed);
+            Some(load)
+        };
+        #[cfg(not(feature = "std"))]
+        let load = None;
+
+        self.enforce_voice_budget(load, cycle_secs);
     }
 
     #[cfg(feature = "scheduled_events")]
@@ -225,6 +306,7 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
             dropped_frames,
             #[cfg(feature = "musical_transport")]
             transport_info,
+            global_wet: self.global_wet,
         };
 
         // -- Find scheduled events that have elapsed this block ------------------------------
@@ -233,6 +315,14 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
         self.event_scheduler
             .prepare_process_block(&info, &mut self.nodes);
 
+        let measure_voices = self.voice_budget.measure_voices();
+        let preview_active = self.preview.is_active();
+        let preview = &mut self.preview;
+        let scene_crossfade_active = self.scene_crossfade.is_active();
+        let scene_crossfade = &self.scene_crossfade;
+        let non_finite_sample_mode = self.non_finite_sample_mode;
+        let global_wet = self.global_wet;
+
         // -- Audio graph node processing closure ---------------------------------------------
 
         schedule_data.schedule.process(
@@ -257,9 +347,43 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                 info.in_connected_mask = in_connected_mask;
                 info.out_connected_mask = out_connected_mask;
 
+                // A node with its wet path bypassed only passes its dry signal.
+                info.global_wet = if node_entry.wet_bypassed {
+                    0.0
+                } else {
+                    global_wet
+                };
+
                 // Used to keep track of what status this closure should return.
                 let mut prev_process_status = None;
                 let mut final_mask = None;
+                let mut all_pass_through = true;
+
+                if let Some(detector) = &mut node_entry.input_activity {
+                    let peak = proc_buffers
+                        .inputs
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| !in_silence_mask.is_channel_silent(*i))
+                        .fold(0.0f32, |peak, (_, ch)| {
+                            ch[..block_frames]
+                                .iter()
+                                .fold(peak, |peak, s| peak.max(s.abs()))
+                        });
+
+                    detector.process(peak, block_frames);
+                }
+
+                let is_voice = node_entry.voice.is_voice;
+                let was_silent = node_entry.prev_output_was_silent;
+                node_entry.voice.peak = 0.0;
+
+                // TODO: Remove this feature gate if `bevy_platform` implements this.
+                #[cfg(feature = "std")]
+                let voice_start =
+                    (is_voice && measure_voices).then(bevy_platform::time::Instant::now);
+                #[cfg(not(feature = "std"))]
+                let _ = measure_voices;
 
                 // Process in sub-chunks for each new scheduled event (or process a single
                 // chunk if there are no scheduled events).
@@ -291,7 +415,14 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
 
                         // Call the node's process method.
                         let process_status = {
-                            if sub_chunk_frames == block_frames {
+                            if node_entry.voice.culled {
+                                // This voice was culled to stay within the voice budget.
+                                ProcessStatus::ClearAllOutputs
+                            } else if node_entry.asleep && events.num_events() == 0 {
+                                // Nothing consumes the output of this node, and it has
+                                // no events to receive.
+                                ProcessStatus::ClearAllOutputs
+                            } else if sub_chunk_frames == block_frames {
                                 // If this is the only sub-chunk (because there are no scheduled
                                 // events), there is no need to edit the buffer slices.
                                 let sub_proc_buffers = ProcBuffers {
@@ -331,9 +462,79 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                             }
                         };
 
+                        if non_finite_sample_mode != NonFiniteSampleMode::Ignore
+                            && matches!(
+                                process_status,
+                                ProcessStatus::OutputsModified
+                                    | ProcessStatus::OutputsModifiedWithMask(_)
+                            )
+                        {
+                            check_non_finite_outputs(
+                                node_id,
+                                node_entry,
+                                non_finite_sample_mode,
+                                proc_buffers.outputs,
+                                sub_chunk_range.clone(),
+                                extra,
+                            );
+                        }
+
+                        // Within the block, a pass-through is handled exactly like a bypass.
+                        // The schedule may only hand the input buffers over to the outputs
+                        // if every sub-chunk passed through.
+                        let process_status = if process_status == ProcessStatus::PassThrough {
+                            ProcessStatus::Bypass
+                        } else {
+                            all_pass_through = false;
+                            process_status
+                        };
+
+                        // Analysis-only nodes never alter the audio passing through
+                        // them, whatever they wrote to their output buffers.
+                        let process_status = if node_entry.analysis_only {
+                            ProcessStatus::Bypass
+                        } else {
+                            process_status
+                        };
+
+                        // Fade out the node's outputs if it is about to be removed.
+                        let process_status = if let Some(fade_out) = &mut node_entry.fade_out {
+                            fade_out.process(
+                                process_status,
+                                proc_buffers.inputs,
+                                proc_buffers.outputs,
+                                sub_chunk_range.clone(),
+                            )
+                        } else {
+                            process_status
+                        };
+
+                        // Crossfade the outputs of the node if it is the output of a scene.
+                        let process_status = if scene_crossfade_active {
+                            scene_crossfade.process(
+                                node_id,
+                                process_status,
+                                proc_buffers.inputs,
+                                proc_buffers.outputs,
+                                sub_chunk_range.clone(),
+                            )
+                        } else {
+                            process_status
+                        };
+
+                        if preview_active {
+                            preview.capture(
+                                node_id,
+                                process_status,
+                                proc_buffers.inputs,
+                                proc_buffers.outputs,
+                                sub_chunk_range.clone(),
+                            );
+                        }
+
                         node_entry.prev_output_was_silent = match process_status {
                             ProcessStatus::ClearAllOutputs => true,
-                            ProcessStatus::Bypass => info
+                            ProcessStatus::Bypass | ProcessStatus::PassThrough => info
                                 .in_silence_mask
                                 .all_channels_silent(proc_buffers.inputs.len()),
                             ProcessStatus::OutputsModified => false,
@@ -345,6 +546,27 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                             },
                         };
 
+                        if is_voice && !node_entry.prev_output_was_silent {
+                            let channel_peak = |peak: f32, ch: &[f32]| {
+                                ch[sub_chunk_range.clone()]
+                                    .iter()
+                                    .fold(peak, |peak, s| peak.max(s.abs()))
+                            };
+
+                            let peak = node_entry.voice.peak;
+                            node_entry.voice.peak = if process_status == ProcessStatus::Bypass {
+                                proc_buffers
+                                    .inputs
+                                    .iter()
+                                    .fold(peak, |p, ch| channel_peak(p, ch))
+                            } else {
+                                proc_buffers
+                                    .outputs
+                                    .iter()
+                                    .fold(peak, |p, ch| channel_peak(p, ch))
+                            };
+                        }
+
                         // If there are multiple sub-chunks, and the node returned a different process
                         // status this sub-chunk than the previous sub-chunk, then we must manually
                         // handle the process statuses.
@@ -365,7 +587,7 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                                                 ),
                                             ));
                                         }
-                                        ProcessStatus::Bypass => {
+                                        ProcessStatus::Bypass | ProcessStatus::PassThrough => {
                                             for (out_ch, in_ch) in proc_buffers
                                                 .outputs
                                                 .iter_mut()
@@ -407,7 +629,7 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                                         out_ch[sub_chunk_range.clone()].fill(0.0);
                                     }
                                 }
-                                ProcessStatus::Bypass => {
+                                ProcessStatus::Bypass | ProcessStatus::PassThrough => {
                                     for (out_ch, in_ch) in proc_buffers
                                         .outputs
                                         .iter_mut()
@@ -468,6 +690,66 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                     },
                 );
 
+                // -- Let the context know when a fade before removal has finished. ----------
+
+                if let Some(fade_out) = &mut self.nodes.get_mut(node_id.0).unwrap().fade_out {
+                    if fade_out.finished() && !fade_out.reported {
+                        // If the message channel is full, try again next block.
+                        fade_out.reported = self
+                            .to_graph_tx
+                            .try_push(ProcessorToContextMsg::NodeFadedOut(node_id))
+                            .is_ok();
+                    }
+                }
+
+                // -- Let the context know when the node's input starts or stops. -------------
+
+                if let Some(detector) = &mut self.nodes.get_mut(node_id.0).unwrap().input_activity {
+                    if let Some(active) = detector.unreported_state() {
+                        // If the message channel is full, try again next block.
+                        if self
+                            .to_graph_tx
+                            .try_push(ProcessorToContextMsg::InputActivity { node_id, active })
+                            .is_ok()
+                        {
+                            detector.mark_reported(active);
+                        }
+                    }
+                }
+
+                // -- Keep track of the state used by the voice budget. -----------------------
+
+                if is_voice {
+                    let node_entry = self.nodes.get_mut(node_id.0).unwrap();
+
+                    if was_silent && !node_entry.prev_output_was_silent {
+                        node_entry.voice.started_at = clock_samples;
+                    }
+
+                    #[cfg(feature = "std")]
+                    if let Some(voice_start) = voice_start {
+                        node_entry.voice.cpu_secs = voice_start.elapsed().as_secs_f64();
+                    }
+                }
+
+                // -- Flush the node's recursive state if it has been idle long enough. -------
+
+                if let Some(idle_flush_blocks) = self.idle_flush_blocks {
+                    let node_entry = self.nodes.get_mut(node_id.0).unwrap();
+
+                    if node_entry.prev_output_was_silent {
+                        if node_entry.idle_blocks < idle_flush_blocks.get() {
+                            node_entry.idle_blocks += 1;
+
+                            if node_entry.idle_blocks == idle_flush_blocks.get() {
+                                node_entry.processor.flush_idle_state();
+                            }
+                        }
+                    } else {
+                        node_entry.idle_blocks = 0;
+                    }
+                }
+
                 // -- Done processing in sub-chunks. Return the final process status. ---------
 
                 if let Some(final_mask) = final_mask {
@@ -476,11 +758,16 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
                     ProcessStatus::OutputsModifiedWithMask(final_mask)
                 } else {
                     // Else return the process status returned by the node's proces method.
-                    prev_process_status.unwrap()
+                    match prev_process_status.unwrap() {
+                        ProcessStatus::Bypass if all_pass_through => ProcessStatus::PassThrough,
+                        process_status => process_status,
+                    }
                 }
             },
         );
 
+        self.scene_crossfade.end_block(block_frames);
+
         // -- Clean up event buffers ----------------------------------------------------------
 
         self.event_scheduler.cleanup_process_block();
@@ -506,3 +793,505 @@ impl<B: AudioBackend> FirewheelProcessorInner<B> {
         });
     }
 }
+
+/// Check the outputs of a node for non-finite samples, and handle them
+/// according to the given mode.
+///
+/// [`NonFiniteSampleMode::Panic`] only panics in debug builds. In release
+/// builds it sanitizes the outputs instead, so that a `NaN` can never abort
+/// the audio thread.
+fn check_non_finite_outputs(
+    node_id: NodeID,
+    node_entry: &mut NodeEntry,
+    mode: NonFiniteSampleMode,
+    outputs: &mut [&mut [f32]],
+    range: Range<usize>,
+    extra: &mut ProcExtra,
+) {
+    match mode {
+        NonFiniteSampleMode::Ignore => {}
+        #[cfg(debug_assertions)]
+        NonFiniteSampleMode::Panic => {
+            panic_on_non_finite_outputs(node_id, node_entry, outputs, range)
+        }
+        _ => sanitize_non_finite_outputs(node_id, node_entry, outputs, range, extra),
+    }
+}
+
+/// Panic on the first non-finite sample in the outputs of a node.
+#[cfg(debug_assertions)]
+fn panic_on_non_finite_outputs(
+    node_id: NodeID,
+    node_entry: &NodeEntry,
+    outputs: &[&mut [f32]],
+    range: Range<usize>,
+) {
+    for (ch_i, ch) in outputs.iter().enumerate() {
+        if let Some((i, s)) = ch[range.clone()]
+            .iter()
+            .enumerate()
+            .find(|(_, s)| !s.is_finite())
+        {
+            panic!(
+                "Node \"{}\" ({:?}) output a non-finite sample ({}) on channel {} at frame {}",
+                node_entry.debug_name,
+                node_id,
+                s,
+                ch_i,
+                range.start + i,
+            );
+        }
+    }
+}
+
+/// Replace any non-finite samples in the outputs of a node with silence,
+/// logging an error the first time the node outputs one.
+fn sanitize_non_finite_outputs(
+    node_id: NodeID,
+    node_entry: &mut NodeEntry,
+    outputs: &mut [&mut [f32]],
+    range: Range<usize>,
+    extra: &mut ProcExtra,
+) {
+    for ch in outputs.iter_mut() {
+        for s in ch[range.clone()].iter_mut().filter(|s| !s.is_finite()) {
+            if !node_entry.non_finite_reported {
+                node_entry.non_finite_reported = true;
+
+                let _ = extra.logger.try_error_with(|msg| {
+                    let _ = write!(
+                        msg,
+                        "Node \"{}\" ({:?}) output a non-finite sample. Replacing it with silence.",
+                        node_entry.debug_name, node_id,
+                    );
+                });
+            }
+
+            *s = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_core::{
+        channel_config::{ChannelConfig, ChannelCount},
+        dsp::{limiter::LimiterConfig, mix::Mix},
+        event::{NodeEventType, ProcEvents},
+        node::{
+            AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+            ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+        },
+    };
+    use firewheel_nodes::{
+        convolution::{ConvolutionNode, ImpulseResponse},
+        phaser::PhaserStereoNode,
+        stereo_delay::StereoDelayNode,
+    };
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        processor::NonFiniteSampleMode,
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx, stereo_sine},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    #[test]
+    fn global_wet_of_zero_passes_dry_through_all_effects() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let phaser = cx.add_node(
+            PhaserStereoNode {
+                mix: Mix::FULLY_WET,
+                ..Default::default()
+            },
+            None,
+        );
+        let delay = cx.add_node(
+            StereoDelayNode {
+                mix: Mix::new(0.7),
+                ..Default::default()
+            },
+            None,
+        );
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, phaser);
+        connect_stereo(&mut cx, phaser, delay);
+        connect_stereo(&mut cx, delay, graph_out);
+
+        cx.set_global_wet(0.0).unwrap();
+        assert_eq!(cx.global_wet(), 0.0);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let input = stereo_sine(4096, 0.5, 0.05);
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        assert!(output
+            .iter()
+            .zip(input.iter())
+            .all(|(o, i)| (o - i).abs() < 1e-6));
+
+        // Bringing the effects back in changes the output.
+        cx.set_global_wet(1.0).unwrap();
+        cx.update().unwrap();
+        stream.process(&input, &mut output);
+        stream.process(&input, &mut output);
+
+        assert!(output
+            .iter()
+            .zip(input.iter())
+            .any(|(o, i)| (o - i).abs() > 0.01));
+    }
+
+    #[test]
+    fn mono_audition_outputs_half_the_stereo_sum() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // A Haas-widened signal, where the right channel is a delayed copy
+        // of the left.
+        let input: Vec<f32> = (0..1024)
+            .flat_map(|i| {
+                let s = |i: usize| 0.5 * (i as f32 * 0.05).sin();
+                [s(i + 20), s(i)]
+            })
+            .collect();
+        let mut stereo = vec![0.0; input.len()];
+        stream.process(&input, &mut stereo);
+
+        cx.set_mono_audition(true).unwrap();
+        assert!(cx.mono_audition());
+        cx.update().unwrap();
+
+        let mut mono = vec![0.0; input.len()];
+        stream.process(&input, &mut mono);
+
+        for (m, s) in mono.chunks(2).zip(stereo.chunks(2)) {
+            let expected = (s[0] + s[1]) * 0.5;
+            assert_eq!(m, [expected, expected]);
+        }
+
+        // Turning the audition off restores the stereo output.
+        cx.set_mono_audition(false).unwrap();
+        cx.update().unwrap();
+        stream.process(&input, &mut mono);
+        assert_eq!(mono, stereo);
+    }
+
+    #[test]
+    fn wet_bypass_mutes_reverb_but_passes_dry() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let reverb = cx.add_node(ConvolutionNode::<2>::default(), None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, reverb);
+        connect_stereo(&mut cx, reverb, graph_out);
+
+        assert!(cx.set_node_wet_bypassed(reverb, true));
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // A short echo, so the wet signal is easy to tell apart from the dry.
+        let mut ir = vec![0.0; 64];
+        ir[63] = 1.0;
+        let ir = ImpulseResponse::new_with_partition_size(vec![ir.clone(), ir], 64).unwrap();
+        cx.queue_event_for(reverb, NodeEventType::custom(Some(ir)));
+        cx.update().unwrap();
+
+        let input = stereo_sine(512, 0.5, 0.05);
+        let mut output = vec![0.0; input.len()];
+        for _ in 0..8 {
+            stream.process(&input, &mut output);
+        }
+
+        // Only the dry signal passes through.
+        assert!(output
+            .iter()
+            .zip(input.iter())
+            .all(|(o, i)| (o - i).abs() < 1e-6));
+
+        // Bringing the wet path back in adds the reverb.
+        assert!(cx.set_node_wet_bypassed(reverb, false));
+        cx.update().unwrap();
+        for _ in 0..8 {
+            stream.process(&input, &mut output);
+        }
+
+        assert!(output
+            .iter()
+            .zip(input.iter())
+            .any(|(o, i)| (o - i).abs() > 0.01));
+    }
+
+    /// An analysis tap that (incorrectly) scribbles over its output buffers.
+    struct ScribblingTapNode;
+
+    impl AudioNode for ScribblingTapNode {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("scribbling_tap")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::STEREO,
+                    num_outputs: ChannelCount::STEREO,
+                })
+                .analysis_only(true)
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            _cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            ScribblingTapProcessor
+        }
+    }
+
+    struct ScribblingTapProcessor;
+
+    impl AudioNodeProcessor for ScribblingTapProcessor {
+        fn process(
+            &mut self,
+            info: &ProcInfo,
+            buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            for ch in buffers.outputs.iter_mut() {
+                ch[..info.frames].fill(0.25);
+            }
+
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    #[test]
+    fn analysis_only_node_passes_input_through_exactly() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let tap = cx.add_node(ScribblingTapNode, None);
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, tap);
+        connect_stereo(&mut cx, tap, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        let input = stereo_sine(2048, 0.5, 0.07);
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        assert_eq!(output, input);
+    }
+
+    fn render_hot_input(master_limiter: Option<LimiterConfig>) -> (Vec<f32>, Option<f32>) {
+        let mut cx = stereo_ctx(FirewheelConfig {
+            master_limiter,
+            ..Default::default()
+        });
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // A sine wave peaking at +12dB.
+        let input = stereo_sine(2048, 4.0, 0.03);
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        (output, cx.master_limiter_gain_reduction_db())
+    }
+
+    #[test]
+    fn master_limiter_holds_ceiling() {
+        let (unlimited, gain_reduction) = render_hot_input(None);
+        assert!(unlimited.iter().any(|s| s.abs() > 3.9));
+        assert_eq!(gain_reduction, None);
+
+        let config = LimiterConfig {
+            ceiling_db: -3.0,
+            ..Default::default()
+        };
+        let ceiling = firewheel_core::dsp::volume::db_to_amp(config.ceiling_db);
+        let (limited, gain_reduction) = render_hot_input(Some(config));
+
+        assert!(limited.iter().all(|s| s.abs() <= ceiling + 1e-6));
+        assert!(limited.iter().any(|s| s.abs() > ceiling * 0.9));
+        assert!(gain_reduction.unwrap() > 12.0);
+    }
+
+    #[test]
+    fn master_limiter_lookahead_reports_output_latency() {
+        let mut cx = stereo_ctx(FirewheelConfig {
+            master_limiter: Some(LimiterConfig {
+                lookahead_secs: 0.005,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        assert_eq!(cx.output_latency_frames(), None);
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        // 5ms at 44.1kHz.
+        let latency = cx.output_latency_frames().unwrap();
+        assert_eq!(latency, 221);
+
+        // A quiet impulse comes out exactly that many frames later.
+        let mut input = vec![0.0; 1024];
+        input[0] = 0.5;
+        input[1] = 0.5;
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+
+        let first = output.iter().position(|s| *s != 0.0).unwrap();
+        assert_eq!(first, latency as usize * 2);
+        assert_eq!(output[first], 0.5);
+
+        // Without lookahead, nothing is added.
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            master_limiter: Some(LimiterConfig::default()),
+            ..Default::default()
+        });
+        cx.start_stream(DummyStream::default()).unwrap();
+        assert_eq!(cx.output_latency_frames(), Some(0));
+    }
+
+    #[test]
+    fn soft_start_ramps_output_from_zero() {
+        let mut cx = stereo_ctx(FirewheelConfig {
+            soft_start_seconds: 0.005,
+            ..Default::default()
+        });
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+        connect_stereo(&mut cx, graph_in, graph_out);
+
+        // 5ms at 44.1kHz.
+        let ramp_frames = 220;
+
+        for _ in 0..2 {
+            let stream = start_stereo_stream(&mut cx);
+
+            // A stream that starts hot.
+            let input = vec![1.0; (ramp_frames + 100) * 2];
+            let mut output = vec![0.0; input.len()];
+            stream.process(&input, &mut output);
+
+            // The first frames ramp up from zero, and then the signal passes
+            // through untouched.
+            assert!(output[0] < 0.01);
+            for frame in output[..ramp_frames * 2]
+                .chunks(2)
+                .collect::<Vec<_>>()
+                .windows(2)
+            {
+                assert_eq!(frame[0][0], frame[0][1]);
+                assert!(frame[1][0] > frame[0][0]);
+            }
+            assert!(output[(ramp_frames - 1) * 2..].iter().all(|&s| s == 1.0));
+
+            // Restarting the stream fades in again.
+            cx.stop_stream();
+        }
+    }
+
+    /// A generator that outputs a NaN on its first frame.
+    struct NonFiniteSource;
+
+    impl AudioNode for NonFiniteSource {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("non_finite_source")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::ZERO,
+                    num_outputs: ChannelCount::MONO,
+                })
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            _cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            NonFiniteSourceProcessor
+        }
+    }
+
+    struct NonFiniteSourceProcessor;
+
+    impl AudioNodeProcessor for NonFiniteSourceProcessor {
+        fn process(
+            &mut self,
+            info: &ProcInfo,
+            buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            buffers.outputs[0][..info.frames].fill(0.5);
+            buffers.outputs[0][0] = f32::NAN;
+
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    fn non_finite_graph(mode: NonFiniteSampleMode) -> (FirewheelCtx<DummyBackend>, DummyStream) {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig {
+            non_finite_sample_mode: mode,
+            ..Default::default()
+        });
+
+        let graph_out = cx.graph_out_node_id();
+        let source = cx.add_node(NonFiniteSource, None);
+        cx.connect(source, graph_out, &[(0, 0), (0, 1)], false)
+            .unwrap();
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+
+        (cx, stream)
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "Node \"non_finite_source\"")]
+    fn non_finite_sample_panics_with_node_name() {
+        let (_cx, stream) = non_finite_graph(NonFiniteSampleMode::Panic);
+
+        let input = vec![0.0; 0];
+        let mut output = vec![0.0; 256 * 2];
+        stream.process(&input, &mut output);
+    }
+
+    #[test]
+    fn non_finite_sample_is_sanitized() {
+        let (_cx, stream) = non_finite_graph(NonFiniteSampleMode::Sanitize);
+
+        let input = vec![0.0; 0];
+        let mut output = vec![0.0; 256 * 2];
+        stream.process(&input, &mut output);
+
+        assert!(output.iter().all(|s| s.is_finite()));
+        assert_eq!(output[0], 0.0);
+        assert_eq!(output[2], 0.5);
+    }
+}
@@ -0,0 +1,196 @@
+use core::{num::NonZeroU32, ops::Range};
+
+use firewheel_core::{
+    dsp::fade::FadeCurve,
+    node::{NodeID, ProcessStatus},
+};
+
+/// Crossfades the outputs of two scenes, where each scene is a sub-graph
+/// whose audio passes through a single output node.
+///
+/// The gain of each frame is computed from its position in the crossfade,
+/// so both scenes are faded in lockstep no matter where their output nodes
+/// are in the schedule.
+pub(crate) struct SceneCrossfade {
+    /// The output node of the scene fading out. Once the crossfade has
+    /// finished, this node's outputs are silenced.
+    outgoing: Option<NodeID>,
+    /// The output node of the scene fading in.
+    incoming: Option<NodeID>,
+
+    fade_frames: usize,
+    /// The number of frames of the crossfade that elapsed before the current
+    /// block.
+    elapsed_frames: usize,
+}
+
+impl SceneCrossfade {
+    pub fn new() -> Self {
+        Self {
+            outgoing: None,
+            incoming: None,
+            fade_frames: 0,
+            elapsed_frames: 0,
+        }
+    }
+
+    /// Start crossfading from the current scene to the given scene, or to
+    /// silence if `None`.
+    ///
+    /// If a crossfade is already in progress, then the scene that was fading
+    /// out is cut off.
+    pub fn crossfade_to(
+        &mut self,
+        incoming: Option<NodeID>,
+        fade_secs: f32,
+        sample_rate: NonZeroU32,
+    ) {
+        if incoming == self.incoming {
+            return;
+        }
+
+        self.outgoing = self.incoming;
+        self.incoming = incoming;
+        self.fade_frames = (fade_secs.max(0.0) * sample_rate.get() as f32) as usize;
+        self.elapsed_frames = 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.outgoing.is_some() || self.incoming.is_some()
+    }
+
+    fn is_fading(&self) -> bool {
+        self.elapsed_frames < self.fade_frames
+    }
+
+    /// Apply the crossfade to the outputs of a node for the given range of
+    /// frames in the current block if it is the output node of a scene.
+    ///
+    /// Returns the new process status of the node.
+    pub fn process(
+        &self,
+        node_id: NodeID,
+        process_status: ProcessStatus,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        range: Range<usize>,
+    ) -> ProcessStatus {
+        let is_outgoing = if self.outgoing == Some(node_id) {
+            true
+        } else if self.incoming == Some(node_id) {
+            false
+        } else {
+            return process_status;
+        };
+
+        if !self.is_fading() {
+            return if is_outgoing {
+                ProcessStatus::ClearAllOutputs
+            } else {
+                process_status
+            };
+        }
+
+        match process_status {
+            ProcessStatus::ClearAllOutputs => return ProcessStatus::ClearAllOutputs,
+            ProcessStatus::Bypass => {
+                for (out_ch, in_ch) in outputs.iter_mut().zip(inputs.iter()) {
+                    out_ch[range.clone()].copy_from_slice(&in_ch[range.clone()]);
+                }
+                for out_ch in outputs.iter_mut().skip(inputs.len()) {
+                    out_ch[range.clone()].fill(0.0);
+                }
+            }
+            _ => {}
+        }
+
+        let fade_frames_recip = (self.fade_frames as f32).recip();
+
+        for i in range {
+            let fade = (self.elapsed_frames + i) as f32 * fade_frames_recip;
+            let (gain_out, gain_in) = FadeCurve::EqualPower3dB.compute_gains_0_to_1(fade);
+            let gain = if is_outgoing { gain_out } else { gain_in };
+
+            for out_ch in outputs.iter_mut() {
+                out_ch[i] *= gain;
+            }
+        }
+
+        ProcessStatus::OutputsModified
+    }
+
+    /// Advance the crossfade to the next block.
+    pub fn end_block(&mut self, block_frames: usize) {
+        self.elapsed_frames = (self.elapsed_frames + block_frames).min(self.fade_frames);
+    }
+
+    /// Change the sample rate, keeping the remaining time of an active
+    /// crossfade.
+    pub fn update_sample_rate(&mut self, old_sample_rate: NonZeroU32, sample_rate: NonZeroU32) {
+        let ratio = sample_rate.get() as f64 / old_sample_rate.get() as f64;
+        self.fade_frames = (self.fade_frames as f64 * ratio) as usize;
+        self.elapsed_frames = ((self.elapsed_frames as f64 * ratio) as usize).min(self.fade_frames);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_nodes::volume::VolumeNode;
+
+    use crate::{
+        test_util::{connect_stereo, start_stereo_stream, stereo_ctx},
+        FirewheelConfig,
+    };
+
+    #[test]
+    fn scene_crossfade_blends_then_switches_scenes() {
+        let mut cx = stereo_ctx(FirewheelConfig::default());
+
+        let graph_in = cx.graph_in_node_id();
+        let graph_out = cx.graph_out_node_id();
+
+        // The old scene only plays in the left channel.
+        let old_scene = cx.add_node(VolumeNode::default(), None);
+        cx.connect(graph_in, old_scene, &[(0, 0)], false).unwrap();
+        connect_stereo(&mut cx, old_scene, graph_out);
+
+        let stream = start_stereo_stream(&mut cx);
+
+        cx.crossfade_to_scene(Some(old_scene), 0.0).unwrap();
+
+        let input = vec![1.0; 1024 * 2];
+        let mut output = vec![0.0; input.len()];
+        stream.process(&input, &mut output);
+        assert!(output.chunks(2).all(|f| f == [1.0, 0.0]));
+
+        // The new scene only plays in the right channel.
+        let new_scene = cx.add_node(VolumeNode::default(), None);
+        cx.connect(graph_in, new_scene, &[(1, 1)], false).unwrap();
+        connect_stereo(&mut cx, new_scene, graph_out);
+
+        // A 10ms crossfade at 44.1kHz.
+        cx.crossfade_to_scene(Some(new_scene), 10.0).unwrap();
+        assert_eq!(cx.active_scene(), Some(new_scene));
+        cx.update().unwrap();
+        let fade_frames = 441;
+
+        stream.process(&input, &mut output);
+        let frames: Vec<&[f32]> = output.chunks(2).collect();
+
+        // Both scenes contribute during the crossfade, keeping the overall
+        // power constant.
+        for f in frames[1..fade_frames].iter() {
+            assert!(f[0] > 0.0 && f[0] < 1.0);
+            assert!(f[1] > 0.0 && f[1] < 1.0);
+            assert!((f[0] * f[0] + f[1] * f[1] - 1.0).abs() < 1e-4);
+        }
+        for w in frames[..fade_frames].windows(2) {
+            assert!(w[1][0] < w[0][0] && w[1][1] > w[0][1]);
+        }
+
+        // Afterwards only the new scene is heard.
+        assert!(frames[fade_frames..].iter().all(|f| *f == [0.0, 1.0]));
+        stream.process(&input, &mut output);
+        assert!(output.chunks(2).all(|f| f == [0.0, 1.0]));
+    }
+}
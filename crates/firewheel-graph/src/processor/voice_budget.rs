@@ -0,0 +1,278 @@
+use firewheel_core::{
+    atomic_float::AtomicF32, clock::InstantSamples, collector::ArcGc, node::NodeID,
+};
+use ringbuf::traits::Producer;
+
+use crate::{
+    backend::AudioBackend,
+    processor::{FirewheelProcessorInner, ProcessorToContextMsg},
+};
+
+/// The limits on the number of voices and the amount of CPU time the audio
+/// graph may use.
+pub(crate) struct VoiceBudget {
+    /// The maximum number of audible voices.
+    pub max_voices: Option<u32>,
+    /// The maximum fraction of the available time per process cycle that
+    /// processing may take.
+    pub cpu_budget: Option<f32>,
+    /// The fraction of the available time the last process cycle took.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub cpu_load: ArcGc<AtomicF32>,
+}
+
+impl VoiceBudget {
+    pub fn new(
+        max_voices: Option<u32>,
+        cpu_budget: Option<f32>,
+        cpu_load: ArcGc<AtomicF32>,
+    ) -> Self {
+        Self {
+            max_voices,
+            cpu_budget,
+            cpu_load,
+        }
+    }
+
+    /// Whether or not the time each voice takes to process needs to be measured.
+    pub fn measure_voices(&self) -> bool {
+        cfg!(feature = "std") && self.cpu_budget.is_some()
+    }
+}
+
+/// The state of a node that is used by the voice budget.
+#[derive(Default)]
+pub(crate) struct VoiceState {
+    pub is_voice: bool,
+    /// If `true`, then this voice has been culled and is no longer processed.
+    pub culled: bool,
+    /// The peak absolute output of this voice in the last processed block.
+    pub peak: f32,
+    /// The time in seconds it took to process this voice in the last block.
+    pub cpu_secs: f64,
+    /// The time at which this voice last became audible.
+    pub started_at: InstantSamples,
+}
+
+impl<B: AudioBackend> FirewheelProcessorInner<B> {
+    /// Cull voices until the number of audible voices and the processing load
+    /// are within the budget.
+    ///
+    /// * `load` - The fraction of the available time the last process cycle
+    ///   took, if it was measured.
+    /// * `cycle_secs` - The available time of the last process cycle in seconds.
+    pub(super) fn enforce_voice_budget(&mut self, load: Option<f32>, cycle_secs: f64) {
+        if let Some(max_voices) = self.voice_budget.max_voices {
+            let mut num_audible = self
+                .nodes
+                .iter()
+                .filter(|(_, n)| n.voice.is_voice && !n.voice.culled && !n.prev_output_was_silent)
+                .count();
+
+            while num_audible > max_voices as usize {
+                if self.cull_quietest_voice(true).is_none() {
+                    break;
+                }
+                num_audible -= 1;
+            }
+        }
+
+        if let (Some(load), Some(cpu_budget)) = (load, self.voice_budget.cpu_budget) {
+            if load > cpu_budget {
+                // Cull voices until the time they took covers the time over budget.
+                let mut excess_secs = f64::from(load - cpu_budget) * cycle_secs;
+
+                while excess_secs > 0.0 {
+                    let Some(cpu_secs) = self.cull_quietest_voice(false) else {
+                        break;
+                    };
+                    excess_secs -= cpu_secs;
+                }
+            }
+        }
+    }
+
+    /// Cull the quietest voice, preferring the oldest voice when several are
+    /// equally quiet.
+    ///
+    /// Returns the time in seconds the culled voice took to process in the
+    /// last block, or `None` if there are no voices left to cull.
+    fn cull_quietest_voice(&mut self, audible_only: bool) -> Option<f64> {
+        let mut quietest: Option<(NodeID, f32, InstantSamples)> = None;
+
+        for (index, node) in self.nodes.iter() {
+            if !node.voice.is_voice
+                || node.voice.culled
+                || (audible_only && node.prev_output_was_silent)
+            {
+                continue;
+            }
+
+            let peak = if node.prev_output_was_silent {
+                0.0
+            } else {
+                node.voice.peak
+            };
+
+            let is_quieter = match quietest {
+                None => true,
+                Some((_, q_peak, q_started_at)) => {
+                    peak < q_peak || (peak == q_peak && node.voice.started_at < q_started_at)
+                }
+            };
+
+            if is_quieter {
+                quietest = Some((NodeID(index), peak, node.voice.started_at));
+            }
+        }
+
+        let (node_id, _, _) = quietest?;
+        let node = self.nodes.get_mut(node_id.0).unwrap();
+
+        node.voice.culled = true;
+
+        let _ = self
+            .to_graph_tx
+            .try_push(ProcessorToContextMsg::VoiceCulled(node_id));
+
+        Some(node.voice.cpu_secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+    use std::time::Instant;
+
+    use firewheel_core::{
+        channel_config::{ChannelConfig, ChannelCount},
+        event::ProcEvents,
+        node::{
+            AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+            ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+        },
+    };
+
+    use crate::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        FirewheelConfig, FirewheelCtx,
+    };
+
+    /// A voice that outputs a constant level and takes a fixed amount of
+    /// time to process each block.
+    struct BusyVoiceNode {
+        level: f32,
+        spin: Duration,
+    }
+
+    impl AudioNode for BusyVoiceNode {
+        type Configuration = EmptyConfig;
+
+        fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+            AudioNodeInfo::new()
+                .debug_name("busy_voice")
+                .channel_config(ChannelConfig {
+                    num_inputs: ChannelCount::ZERO,
+                    num_outputs: ChannelCount::MONO,
+                })
+        }
+
+        fn construct_processor(
+            &self,
+            _config: &Self::Configuration,
+            _cx: ConstructProcessorContext,
+        ) -> impl AudioNodeProcessor {
+            BusyVoiceProcessor {
+                level: self.level,
+                spin: self.spin,
+            }
+        }
+    }
+
+    struct BusyVoiceProcessor {
+        level: f32,
+        spin: Duration,
+    }
+
+    impl AudioNodeProcessor for BusyVoiceProcessor {
+        fn process(
+            &mut self,
+            info: &ProcInfo,
+            buffers: ProcBuffers,
+            _events: &mut ProcEvents,
+            _extra: &mut ProcExtra,
+        ) -> ProcessStatus {
+            let start = Instant::now();
+            while start.elapsed() < self.spin {}
+
+            buffers.outputs[0][..info.frames].fill(self.level);
+
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    #[test]
+    fn cpu_budget_culls_quietest_voices() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let graph_out = cx.graph_out_node_id();
+
+        // Six voices that each take 3ms of a ~23ms block (about 78% load).
+        let voices: Vec<_> = (1..=6)
+            .map(|i| {
+                let voice = cx.add_node(
+                    BusyVoiceNode {
+                        level: i as f32 * 0.01,
+                        spin: Duration::from_millis(3),
+                    },
+                    None,
+                );
+                cx.connect(voice, graph_out, &[(0, 0)], false).unwrap();
+                assert!(cx.set_node_voice(voice, true));
+                voice
+            })
+            .collect();
+
+        let stream = DummyStream::default();
+        cx.start_stream(stream.clone()).unwrap();
+
+        cx.set_cpu_budget(Some(0.5)).unwrap();
+        cx.update().unwrap();
+
+        stream.process_block(1024);
+        assert!(cx.cpu_load() > 0.5);
+
+        for _ in 0..4 {
+            stream.process_block(1024);
+        }
+        cx.update().unwrap();
+
+        // The quietest voices were culled, and the loudest voices remain.
+        let culled: Vec<bool> = voices.iter().map(|&v| cx.is_voice_culled(v)).collect();
+        let num_culled = culled.iter().filter(|&&c| c).count();
+        assert!(num_culled > 0 && num_culled < voices.len());
+        assert!(culled[..num_culled].iter().all(|&c| c));
+        assert!(cx.cpu_load() < 0.5, "load {}", cx.cpu_load());
+
+        // Culled voices are silent.
+        let output = stream.process_block(1024);
+        let expected: f32 = (num_culled + 1..=6).map(|i| i as f32 * 0.01).sum();
+        assert!((output[0] - expected).abs() < 1e-6);
+
+        // Limiting the polyphony culls the quietest of the remaining voices.
+        cx.set_max_voices(Some(1)).unwrap();
+        cx.update().unwrap();
+        stream.process_block(1024);
+        cx.update().unwrap();
+        assert!(voices[..5].iter().all(|&v| cx.is_voice_culled(v)));
+        assert!(!cx.is_voice_culled(voices[5]));
+
+        // A revived voice is processed again.
+        cx.set_cpu_budget(None).unwrap();
+        cx.set_max_voices(None).unwrap();
+        cx.revive_voice(voices[0]).unwrap();
+        cx.update().unwrap();
+        assert!(!cx.is_voice_culled(voices[0]));
+        let output = stream.process_block(1024);
+        assert!((output[0] - 0.07).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,40 @@
+//! Fixtures shared by the tests in this crate.
+
+use bevy_platform::prelude::Vec;
+use firewheel_core::{channel_config::ChannelCount, node::NodeID};
+
+use crate::{
+    backend::dummy_backend::{DummyBackend, DummyStream},
+    FirewheelConfig, FirewheelCtx,
+};
+
+/// Construct a context with stereo graph inputs, so that a test can feed its
+/// own signal through the graph.
+pub(crate) fn stereo_ctx(config: FirewheelConfig) -> FirewheelCtx<DummyBackend> {
+    FirewheelCtx::new(FirewheelConfig {
+        num_graph_inputs: ChannelCount::STEREO,
+        ..config
+    })
+}
+
+/// Start a stream with a stereo input on the given context.
+pub(crate) fn start_stereo_stream(cx: &mut FirewheelCtx<DummyBackend>) -> DummyStream {
+    let mut stream = DummyStream::default();
+    stream.stream_info.num_stream_in_channels = 2;
+    cx.start_stream(stream.clone()).unwrap();
+    stream
+}
+
+/// Connect the left and right channels of `src` to those of `dst`.
+pub(crate) fn connect_stereo(cx: &mut FirewheelCtx<DummyBackend>, src: NodeID, dst: NodeID) {
+    cx.connect(src, dst, &[(0, 0), (1, 1)], false).unwrap();
+}
+
+/// An interleaved stereo sine wave, with the same signal on both channels.
+///
+/// `step` is the phase advanced per frame, in radians.
+pub(crate) fn stereo_sine(frames: usize, amplitude: f32, step: f32) -> Vec<f32> {
+    (0..frames * 2)
+        .map(|i| amplitude * ((i / 2) as f32 * step).sin())
+        .collect()
+}
@@ -1,7 +1,7 @@
 use proc_macro::TokenStream;
 use proc_macro2::TokenStream as TokenStream2;
 use quote::{quote, quote_spanned};
-use syn::spanned::Spanned;
+use syn::{ext::IdentExt, spanned::Spanned};
 
 use crate::{get_paths, struct_fields, TypeSet};
 
@@ -31,17 +31,18 @@ pub fn derive_diff(input: TokenStream) -> syn::Result<TokenStream2> {
         }
     }
 
-    let (body, where_generics) = match &input.data {
+    let (DiffOutput { body, extra, .. }, where_generics) = match &input.data {
         syn::Data::Struct(data) => {
-            let DiffOutput { body, bounds } = DiffOutput::from_struct(data, &diff_path)?;
+            let output = DiffOutput::from_struct(data, &firewheel_path, &diff_path)?;
+            let where_generics = generate_where(where_generics, &output.bounds);
 
-            (body, generate_where(where_generics, &bounds))
+            (output, where_generics)
         }
         syn::Data::Enum(data) => {
-            let DiffOutput { body, bounds } =
-                DiffOutput::from_enum(identifier, data, &firewheel_path, &diff_path)?;
+            let output = DiffOutput::from_enum(identifier, data, &firewheel_path, &diff_path)?;
+            let where_generics = generate_where(where_generics, &output.bounds);
 
-            (body, generate_where(where_generics, &bounds))
+            (output, where_generics)
         }
         syn::Data::Union(_) => {
             return Err(syn::Error::new(
@@ -57,18 +58,23 @@ pub fn derive_diff(input: TokenStream) -> syn::Result<TokenStream2> {
             fn diff<__E: #diff_path::EventQueue>(&self, baseline: &Self, path: #diff_path::PathBuilder, event_queue: &mut __E) {
                 #body
             }
+
+            #extra
         }
     })
 }
 
 struct DiffOutput {
     body: TokenStream2,
+    /// Additional trait methods, such as `get_param` and `resolve_path`.
+    extra: TokenStream2,
     bounds: Vec<TokenStream2>,
 }
 
 impl DiffOutput {
     pub fn from_struct(
         data: &syn::DataStruct,
+        firewheel_path: &syn::Path,
         diff_path: &TokenStream2,
     ) -> syn::Result<DiffOutput> {
         let fields: Vec<_> = struct_fields(&data.fields).collect();
@@ -80,6 +86,44 @@ impl DiffOutput {
             }
         });
 
+        let get_arms = fields.iter().enumerate().map(|(i, (identifier, _))| {
+            let index = i as u32;
+            quote! {
+                #index => self.#identifier.get_param(tail),
+            }
+        });
+
+        let resolve_arms = fields.iter().enumerate().map(|(i, (identifier, ty))| {
+            let index = i as u32;
+            let name = match identifier {
+                syn::Member::Named(ident) => ident.unraw().to_string(),
+                syn::Member::Unnamed(index) => index.index.to_string(),
+            };
+            quote! {
+                #name => <#ty as #diff_path::Diff>::resolve_path(names, path.with(#index)),
+            }
+        });
+
+        let extra = quote! {
+            fn get_param(&self, path: &[u32]) -> ::core::option::Option<#firewheel_path::event::ParamData> {
+                let (first, tail) = path.split_first()?;
+                match *first {
+                    #(#get_arms)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+
+            fn resolve_path<'__n>(
+                names: &mut impl ::core::iter::Iterator<Item = &'__n str>,
+                path: #diff_path::PathBuilder,
+            ) -> ::core::option::Option<#diff_path::PathBuilder> {
+                match names.next()? {
+                    #(#resolve_arms)*
+                    _ => ::core::option::Option::None,
+                }
+            }
+        };
+
         let mut types = TypeSet::default();
         for field in &fields {
             types.insert(field.1);
@@ -87,6 +131,7 @@ impl DiffOutput {
 
         Ok(DiffOutput {
             body: quote! { #(#arms)* },
+            extra,
             bounds: types
                 .into_iter()
                 .map(move |ty| {
@@ -123,14 +168,36 @@ impl DiffOutput {
                 }
             });
 
+            let get_arms = data.variants.iter().enumerate().map(|(i, variant)| {
+                let index = i as u32;
+                let variant_ident = &variant.ident;
+
+                quote! {
+                    #identifier::#variant_ident => #index,
+                }
+            });
+
             let body = quote! {
                 match (self, baseline) {
                     #(#diff_arms)*
                 }
             };
 
+            let extra = quote! {
+                fn get_param(&self, path: &[u32]) -> ::core::option::Option<#firewheel_path::event::ParamData> {
+                    if !path.is_empty() {
+                        return ::core::option::Option::None;
+                    }
+
+                    ::core::option::Option::Some(#firewheel_path::event::ParamData::U32(match self {
+                        #(#get_arms)*
+                    }))
+                }
+            };
+
             return Ok(DiffOutput {
                 body,
+                extra,
                 bounds: vec![],
             });
         }
@@ -144,9 +211,18 @@ impl DiffOutput {
             }
         };
 
+        let extra = quote! {
+            fn get_param(&self, path: &[u32]) -> ::core::option::Option<#firewheel_path::event::ParamData> {
+                path.is_empty().then(|| {
+                    #firewheel_path::event::ParamData::any(<#identifier as ::core::clone::Clone>::clone(self))
+                })
+            }
+        };
+
         let span = identifier.span();
         Ok(DiffOutput {
             body,
+            extra,
             bounds: vec![quote_spanned! {span=>
                 #identifier: ::core::cmp::PartialEq
                         + ::core::clone::Clone
@@ -0,0 +1,399 @@
+//! A node that delays a signal by a fractional number of samples without
+//! altering its magnitude response, for phase alignment.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        delay_line::{delay_frames_for_seconds, RingDelayLine},
+        filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+pub type AllpassDelayMonoNode = AllpassDelayNode<1>;
+pub type AllpassDelayStereoNode = AllpassDelayNode<2>;
+
+/// The configuration for an [`AllpassDelayNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllpassDelayNodeConfig {
+    /// The maximum delay time in seconds. This determines the size of the
+    /// allocated delay buffers.
+    ///
+    /// By default this is set to `0.02` (20ms).
+    pub max_delay_seconds: f32,
+}
+
+impl Default for AllpassDelayNodeConfig {
+    fn default() -> Self {
+        Self {
+            max_delay_seconds: 0.02,
+        }
+    }
+}
+
+/// A delay time, in either samples or milliseconds.
+#[derive(Debug, Clone, Copy, PartialEq, Diff, Patch)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DelayTime {
+    /// The delay in samples (frames), which may be fractional.
+    Samples(f32),
+    /// The delay in milliseconds.
+    Milliseconds(f32),
+}
+
+impl DelayTime {
+    /// The delay in (possibly fractional) frames at the given sample rate.
+    pub fn frames(&self, sample_rate: NonZeroU32) -> f32 {
+        match *self {
+            Self::Samples(samples) => samples,
+            Self::Milliseconds(ms) => ms * sample_rate.get() as f32 / 1000.0,
+        }
+    }
+}
+
+impl Default for DelayTime {
+    fn default() -> Self {
+        Self::Samples(0.0)
+    }
+}
+
+/// A node that delays a signal by a fractional number of samples, i.e. for
+/// time aligning a subwoofer with the main speakers.
+///
+/// The whole part of the delay is done with a delay line, and the fractional
+/// part with a first order allpass filter, so unlike linear interpolation
+/// the magnitude response is left untouched. This complements
+/// [`DelayCompensationNode`](crate::delay_compensation::DelayCompensationNode)
+/// with sub-sample precision.
+///
+/// The fractional delay is accurate at low and mid frequencies, and drifts
+/// towards the nearest whole sample near the Nyquist frequency.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AllpassDelayNode<const CHANNELS: usize> {
+    /// The delay time, clamped to
+    /// [`AllpassDelayNodeConfig::max_delay_seconds`].
+    ///
+    /// By default this is set to `0` samples.
+    pub delay: DelayTime,
+    /// The time in seconds of the internal smoothing filter.
+    ///
+    /// This node is meant for mostly static alignment. Changes to the delay
+    /// are smoothed, but they may still be audible while the delay crosses
+    /// whole samples.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl<const CHANNELS: usize> Default for AllpassDelayNode<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            delay: DelayTime::default(),
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for AllpassDelayNode<CHANNELS> {
+    type Configuration = AllpassDelayNodeConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("allpass_delay")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let smoother_config = cx.smoother_config(SmootherConfig {
+            smooth_seconds: self.smooth_seconds,
+            ..Default::default()
+        });
+
+        Processor::new(
+            *self,
+            config.max_delay_seconds,
+            smoother_config,
+            cx.stream_info.sample_rate,
+        )
+    }
+}
+
+/// Split a delay in frames into a whole number of frames for the delay line
+/// and the coefficient of the first order (Thiran) allpass filter that
+/// provides the rest.
+///
+/// The allpass filter is most accurate with a delay in the range
+/// `[0.5, 1.5)`, so that is used whenever the delay allows it.
+fn split_delay(delay_frames: f32) -> (usize, f32) {
+    let (whole, frac) = if delay_frames < 0.5 {
+        (0, delay_frames.max(0.0))
+    } else {
+        let whole = (delay_frames - 0.5).floor();
+        (whole as usize, delay_frames - whole)
+    };
+
+    (whole, (1.0 - frac) / (1.0 + frac))
+}
+
+/// The state of a first order allpass filter.
+#[derive(Default, Debug, Clone, Copy)]
+struct AllpassState {
+    x1: f32,
+    y1: f32,
+}
+
+impl AllpassState {
+    #[inline(always)]
+    fn process(&mut self, x: f32, coeff: f32) -> f32 {
+        let y = coeff * (x - self.y1) + self.x1;
+        self.x1 = x;
+        self.y1 = y;
+        y
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    delay: DelayTime,
+    max_delay_seconds: f32,
+    sample_rate: NonZeroU32,
+    delay_frames: SmoothedParam,
+
+    delay_lines: [RingDelayLine; CHANNELS],
+    allpass: [AllpassState; CHANNELS],
+    /// The number of frames since the input went silent.
+    silent_input_frames: usize,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(
+        params: AllpassDelayNode<CHANNELS>,
+        max_delay_seconds: f32,
+        smoother_config: SmootherConfig,
+        sample_rate: NonZeroU32,
+    ) -> Self {
+        let mut processor = Self {
+            delay: params.delay,
+            max_delay_seconds,
+            sample_rate,
+            delay_frames: SmoothedParam::new(0.0, smoother_config, sample_rate),
+            delay_lines: core::array::from_fn(|_| RingDelayLine::new(0)),
+            allpass: [AllpassState::default(); CHANNELS],
+            silent_input_frames: 0,
+        };
+        processor.update_sample_rate(sample_rate);
+        processor.delay_frames.reset_to_target();
+        processor
+    }
+
+    fn update_sample_rate(&mut self, sample_rate: NonZeroU32) {
+        self.sample_rate = sample_rate;
+
+        // Leave room for the extra frame used by the allpass filter.
+        let max_frames = delay_frames_for_seconds(self.max_delay_seconds, sample_rate) + 1;
+        self.delay_lines = core::array::from_fn(|_| RingDelayLine::new(max_frames));
+        self.allpass = [AllpassState::default(); CHANNELS];
+        self.silent_input_frames = 0;
+
+        self.delay_frames.update_sample_rate(sample_rate);
+        self.update_delay();
+    }
+
+    fn update_delay(&mut self) {
+        let max_frames = self.max_delay_seconds.max(0.0) * self.sample_rate.get() as f32;
+        self.delay_frames
+            .set_value(self.delay.frames(self.sample_rate).clamp(0.0, max_frames));
+    }
+
+    fn delay_block(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        if self.delay_frames.is_smoothing() {
+            for i in 0..frames {
+                let (whole, coeff) = split_delay(self.delay_frames.next_smoothed());
+
+                for (((line, allpass), in_ch), out_ch) in self
+                    .delay_lines
+                    .iter_mut()
+                    .zip(self.allpass.iter_mut())
+                    .zip(inputs.iter())
+                    .zip(outputs.iter_mut())
+                {
+                    line.write(in_ch[i]);
+                    out_ch[i] = allpass.process(line.read_at_frames(whole), coeff);
+                }
+            }
+
+            self.delay_frames.settle();
+        } else {
+            let (whole, coeff) = split_delay(self.delay_frames.target_value());
+
+            for (((line, allpass), in_ch), out_ch) in self
+                .delay_lines
+                .iter_mut()
+                .zip(self.allpass.iter_mut())
+                .zip(inputs.iter())
+                .zip(outputs.iter_mut())
+            {
+                for (&s_in, s_out) in in_ch[..frames].iter().zip(out_ch[..frames].iter_mut()) {
+                    line.write(s_in);
+                    *s_out = allpass.process(line.read_at_frames(whole), coeff);
+                }
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for line in self.delay_lines.iter_mut() {
+            line.reset();
+        }
+        self.allpass = [AllpassState::default(); CHANNELS];
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<AllpassDelayNode<CHANNELS>>() {
+            match patch {
+                AllpassDelayNodePatch::Delay(delay) => {
+                    self.delay = delay;
+                    self.update_delay();
+
+                    if info.prev_output_was_silent {
+                        // Previous block was silent, so no need to smooth.
+                        self.delay_frames.reset_to_target();
+                    }
+                }
+                AllpassDelayNodePatch::SmoothSeconds(seconds) => {
+                    self.delay_frames
+                        .set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+        }
+
+        if self.delay_frames.has_settled_at(0.0) {
+            // A delay of zero passes the signal through unchanged.
+            self.reset();
+            return ProcessStatus::Bypass;
+        }
+
+        if info.in_silence_mask.all_channels_silent(CHANNELS) {
+            // Only skip processing once the delayed signal has left the
+            // delay line and the allpass filter has rung out.
+            if self.silent_input_frames > self.delay_lines[0].capacity() + info.frames {
+                self.delay_frames.reset_to_target();
+                return ProcessStatus::ClearAllOutputs;
+            }
+            self.silent_input_frames += info.frames;
+        } else {
+            self.silent_input_frames = 0;
+        }
+
+        self.delay_block(buffers.inputs, buffers.outputs, info.frames);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.update_sample_rate(stream_info.sample_rate);
+        self.delay_frames.reset_to_target();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::TAU;
+
+    /// The lag (in fractional frames) of the peak of the cross-correlation
+    /// between `a` and `b`, refined with parabolic interpolation.
+    fn cross_correlation_peak(a: &[f32], b: &[f32], max_lag: usize) -> f32 {
+        let correlation = |lag: usize| -> f32 {
+            a[..a.len() - max_lag]
+                .iter()
+                .zip(b[lag..].iter())
+                .map(|(a, b)| a * b)
+                .sum()
+        };
+
+        let c: Vec<f32> = (0..=max_lag).map(correlation).collect();
+        let peak = (1..max_lag).max_by(|&i, &j| c[i].total_cmp(&c[j])).unwrap();
+
+        let (prev, mid, next) = (c[peak - 1], c[peak], c[peak + 1]);
+        peak as f32 + 0.5 * (prev - next) / (prev - 2.0 * mid + next)
+    }
+
+    #[test]
+    fn half_sample_delay_shifts_cross_correlation_peak() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+
+        let mut processor = Processor::new(
+            AllpassDelayMonoNode {
+                delay: DelayTime::Samples(0.5),
+                ..Default::default()
+            },
+            0.01,
+            SmootherConfig::default(),
+            sample_rate,
+        );
+
+        let input: Vec<f32> = (0..9_600)
+            .map(|i| {
+                let t = i as f32 / sample_rate.get() as f32;
+                [180.0, 730.0, 1_900.0, 3_100.0]
+                    .iter()
+                    .map(|f| 0.2 * (TAU * f * t).sin())
+                    .sum()
+            })
+            .collect();
+        let mut output = vec![0.0; input.len()];
+        processor.delay_block(&[&input], &mut [&mut output], input.len());
+
+        // Skip the time it takes the allpass filter to settle, and offset
+        // the output by two whole frames so that the peak can be refined
+        // with the lags on both sides of it.
+        let peak = cross_correlation_peak(&input[482..], &output[480..], 6);
+        let shift = peak - 2.0;
+        assert!((shift - 0.5).abs() < 0.05, "{shift}");
+
+        // The magnitude is left untouched.
+        let rms = |s: &[f32]| (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt();
+        let ratio = rms(&output[480..]) / rms(&input[480..]);
+        assert!((ratio - 1.0).abs() < 0.01, "{ratio}");
+
+        // The same delay in milliseconds.
+        assert_eq!(
+            DelayTime::Milliseconds(0.5 / 48.0).frames(sample_rate),
+            DelayTime::Samples(0.5).frames(sample_rate)
+        );
+    }
+}
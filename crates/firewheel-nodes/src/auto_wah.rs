@@ -0,0 +1,329 @@
+//! An envelope-controlled "auto-wah" filter.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        coeff_update::{CoeffUpdateFactor, CoeffUpdateMask},
+        declick::{DeclickFadeCurve, Declicker},
+        envelope_follower::{DetectorMode, EnvelopeFollower, EnvelopeFollowerConfig},
+        filter::svf::{SvfCoeff, SvfState},
+        volume::DEFAULT_AMP_EPSILON,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The lowest center frequency of an [`AutoWahNode`] in hertz.
+pub const MIN_FREQ_HZ: f32 = 20.0;
+/// The highest center frequency of an [`AutoWahNode`] in hertz.
+pub const MAX_FREQ_HZ: f32 = 20_000.0;
+
+pub type AutoWahMonoNode = AutoWahNode<1>;
+pub type AutoWahStereoNode = AutoWahNode<2>;
+
+/// A bandpass filter whose center frequency follows the level of its input
+/// (an "auto-wah" or "envelope filter").
+///
+/// Playing louder sweeps the filter up from [`AutoWahNode::base_hz`] by up
+/// to [`AutoWahNode::range_octaves`], and the filter falls back down as the
+/// sound decays. The level is measured from all channels together, so every
+/// channel is swept the same way.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AutoWahNode<const CHANNELS: usize> {
+    /// How strongly the level of the input moves the filter. The envelope
+    /// of the input (in raw amplitude) is multiplied by this value, and a
+    /// result of `1.0` or more sweeps the filter to the top of its range.
+    ///
+    /// By default this is set to `4.0`.
+    pub sensitivity: f32,
+    /// The center frequency of the filter in hertz while the input is
+    /// silent.
+    ///
+    /// By default this is set to `350.0`.
+    pub base_hz: f32,
+    /// How far in octaves above [`AutoWahNode::base_hz`] a loud input sweeps
+    /// the filter.
+    ///
+    /// By default this is set to `3.0`.
+    pub range_octaves: f32,
+    /// The quality (q) factor of the filter. Higher values give a narrower,
+    /// more vocal "wah".
+    ///
+    /// By default this is set to `4.0`.
+    pub q_factor: f32,
+    /// The time in seconds it takes the filter to sweep up towards a louder
+    /// level.
+    ///
+    /// By default this is set to `0.01` (10ms).
+    pub attack_secs: f32,
+    /// The time in seconds it takes the filter to fall back down towards a
+    /// quieter level.
+    ///
+    /// By default this is set to `0.15` (150ms).
+    pub release_secs: f32,
+    /// An exponent representing the rate at which the filter coefficients
+    /// are updated as the filter sweeps.
+    ///
+    /// Smaller values will produce less "stair-stepping" artifacts,
+    /// but will also consume more CPU.
+    ///
+    /// The resulting number of frames (samples in a single channel of audio)
+    /// that will elapse between each update is calculated as
+    /// `2^coeff_update_factor`.
+    ///
+    /// By default this is set to `5`.
+    pub coeff_update_factor: CoeffUpdateFactor,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl<const CHANNELS: usize> Default for AutoWahNode<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            sensitivity: 4.0,
+            base_hz: 350.0,
+            range_octaves: 3.0,
+            q_factor: 4.0,
+            attack_secs: 0.01,
+            release_secs: 0.15,
+            coeff_update_factor: CoeffUpdateFactor::DEFAULT,
+            enabled: true,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AutoWahNode<CHANNELS> {
+    fn detector(&self) -> EnvelopeFollowerConfig {
+        EnvelopeFollowerConfig {
+            mode: DetectorMode::Peak,
+            attack_secs: self.attack_secs,
+            release_secs: self.release_secs,
+            ..Default::default()
+        }
+    }
+
+    /// The center frequency of the filter in hertz for the given envelope
+    /// of the input (in raw amplitude).
+    pub fn center_hz(&self, envelope: f32) -> f32 {
+        let sweep = (envelope * self.sensitivity.max(0.0)).clamp(0.0, 1.0);
+
+        (self.base_hz * 2.0f32.powf(self.range_octaves * sweep)).clamp(MIN_FREQ_HZ, MAX_FREQ_HZ)
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for AutoWahNode<CHANNELS> {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("auto_wah")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    params: AutoWahNode<CHANNELS>,
+    follower: EnvelopeFollower,
+    filters: [SvfState; CHANNELS],
+    coeff: SvfCoeff,
+    coeff_update_mask: CoeffUpdateMask,
+    sample_rate: NonZeroU32,
+    enable_declicker: Declicker,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(params: AutoWahNode<CHANNELS>, sample_rate: NonZeroU32) -> Self {
+        let mut processor = Self {
+            params,
+            follower: EnvelopeFollower::new(params.detector(), sample_rate),
+            filters: [SvfState::default(); CHANNELS],
+            coeff: SvfCoeff::default(),
+            coeff_update_mask: params.coeff_update_factor.mask(),
+            sample_rate,
+            enable_declicker: Declicker::from_enabled(params.enabled),
+        };
+        processor.update_coeff();
+        processor
+    }
+
+    /// The current center frequency of the filter in hertz.
+    fn center_hz(&self) -> f32 {
+        let nyquist_hz = self.sample_rate.get() as f32 * 0.45;
+
+        self.params.center_hz(self.follower.value()).min(nyquist_hz)
+    }
+
+    fn update_coeff(&mut self) {
+        self.coeff = SvfCoeff::bandpass(
+            self.center_hz(),
+            self.params.q_factor.max(0.1),
+            (self.sample_rate.get() as f32).recip(),
+        );
+    }
+
+    /// Filter a block of audio, sweeping the filter with its envelope.
+    fn wah<V: AsMut<[f32]>>(&mut self, inputs: &[&[f32]], outputs: &mut [V], frames: usize) {
+        for i in 0..frames {
+            let peak = inputs
+                .iter()
+                .fold(0.0f32, |peak, in_ch| peak.max(in_ch[i].abs()));
+            self.follower.process(peak);
+
+            if self.coeff_update_mask.do_update(i) {
+                self.update_coeff();
+            }
+
+            for ((filter, in_ch), out_ch) in self
+                .filters
+                .iter_mut()
+                .zip(inputs.iter())
+                .zip(outputs.iter_mut())
+            {
+                out_ch.as_mut()[i] = filter.process(in_ch[i], &self.coeff);
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.follower.reset();
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+        self.update_coeff();
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<AutoWahNode<CHANNELS>>() {
+            if let AutoWahNodePatch::Enabled(enabled) = patch {
+                // Tell the declicker to crossfade.
+                self.enable_declicker
+                    .fade_to_enabled(enabled, &extra.declick_values);
+            }
+
+            self.params.apply(patch);
+            self.follower
+                .set_config(self.params.detector(), self.sample_rate);
+            self.coeff_update_mask = self.params.coeff_update_factor.mask();
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+
+            return ProcessStatus::Bypass;
+        }
+
+        // The filter rings for a short while after the input goes silent, so
+        // only skip processing once it has decayed.
+        if info.in_silence_mask.all_channels_silent(CHANNELS)
+            && self.enable_declicker.has_settled()
+            && self.filters.iter().all(|f| {
+                f.ic1eq.abs() <= DEFAULT_AMP_EPSILON && f.ic2eq.abs() <= DEFAULT_AMP_EPSILON
+            })
+        {
+            self.reset();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.wah(buffers.inputs, buffers.outputs, info.frames);
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.follower
+            .set_config(self.params.detector(), self.sample_rate);
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run a block of a stereo tone at the given amplitude through the
+    /// processor, returning the center frequency of the filter at the end.
+    fn center_after_tone(processor: &mut Processor<2>, amplitude: f32) -> f32 {
+        let input: Vec<f32> = (0..4_800)
+            .map(|i| amplitude * (core::f32::consts::TAU * 220.0 * i as f32 / 48_000.0).sin())
+            .collect();
+        let mut out_l = vec![0.0; input.len()];
+        let mut out_r = vec![0.0; input.len()];
+
+        processor.wah(
+            &[&input, &input],
+            &mut [&mut out_l, &mut out_r],
+            input.len(),
+        );
+        assert_eq!(out_l, out_r);
+
+        processor.center_hz()
+    }
+
+    #[test]
+    fn loud_input_sweeps_filter_higher() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let params = AutoWahNode::<2>::default();
+
+        let mut quiet = Processor::new(params, sample_rate);
+        let quiet_hz = center_after_tone(&mut quiet, 0.02);
+
+        let mut loud = Processor::new(params, sample_rate);
+        let loud_hz = center_after_tone(&mut loud, 0.5);
+
+        // A quiet input barely moves the filter, while a loud one sweeps it
+        // all the way to the top of the range.
+        assert!(quiet_hz > params.base_hz && quiet_hz < params.base_hz * 1.5);
+        assert!((loud_hz - params.base_hz * 8.0).abs() < 1.0);
+
+        // Once the input stops, the filter falls back down.
+        let mut released_hz = loud_hz;
+        for _ in 0..5 {
+            released_hz = center_after_tone(&mut loud, 0.0);
+        }
+        assert!(released_hz < loud_hz * 0.5);
+    }
+}
@@ -0,0 +1,514 @@
+//! A beat-repeat (stutter) effect node synced to the musical transport.
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Notify, Patch},
+    dsp::declick::{DeclickFadeCurve, Declicker},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::delay_buffer::DelayBuffer;
+
+/// The smallest allowed value for [`BeatRepeatNode::division_beats`].
+pub const MIN_DIVISION_BEATS: f64 = 1.0 / 64.0;
+
+/// The length of the fade at the edges of each repeated slice, and of the
+/// crossfade between the live input and the repeats.
+const FADE_SECONDS: f32 = 0.002;
+
+/// The configuration of a [`BeatRepeatNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BeatRepeatNodeConfig {
+    /// The number of input and output channels.
+    pub channels: NonZeroChannelCount,
+    /// The longest slice that can be captured in seconds. This determines
+    /// how much memory is allocated. If a slice is longer than this, then
+    /// the rest of it is repeated as silence.
+    ///
+    /// By default this is set to `2.0`.
+    pub max_slice_seconds: f32,
+    /// The starting seed of the random number generator used by
+    /// [`BeatRepeatNode::probability`]. This cannot be zero.
+    ///
+    /// If the context is running in deterministic mode, this is combined
+    /// with the seed the context assigns to this node.
+    ///
+    /// By default this is set to `17`.
+    pub seed: u32,
+}
+
+impl Default for BeatRepeatNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            max_slice_seconds: 2.0,
+            seed: 17,
+        }
+    }
+}
+
+/// A node that captures a slice of its input and loops it, synced to the
+/// musical transport.
+///
+/// A repeat always starts on a boundary of [`BeatRepeatNode::division_beats`].
+/// The input passes through unchanged for one division while it is being
+/// captured, after which the captured slice is played
+/// [`BeatRepeatNode::repeats`] times in place of the input.
+///
+/// A repeat is started either by [`BeatRepeatNode::trigger`], or randomly
+/// every [`BeatRepeatNode::interval_beats`] according to
+/// [`BeatRepeatNode::probability`].
+///
+/// If the transport is not playing, then the input is passed through.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BeatRepeatNode {
+    /// The length of the captured slice in beats. For example, `0.25` is a
+    /// sixteenth note and `0.5` is an eighth note.
+    ///
+    /// By default this is set to `0.25`.
+    pub division_beats: f64,
+    /// The number of times the captured slice is played after it has been
+    /// captured.
+    ///
+    /// By default this is set to `3`.
+    pub repeats: u32,
+    /// How often in beats a random repeat may start. For example, `4.0`
+    /// gives a chance of a repeat at the start of every bar of `4/4`.
+    ///
+    /// By default this is set to `4.0`.
+    pub interval_beats: f64,
+    /// The chance in the range `[0.0, 1.0]` that a repeat starts every
+    /// [`BeatRepeatNode::interval_beats`].
+    ///
+    /// By default this is set to `0.0`, meaning repeats only start from
+    /// [`BeatRepeatNode::trigger`].
+    pub probability: f32,
+    /// The fraction of each repeated slice that is audible in the range
+    /// `[0.0, 1.0]`. Lower values give a choppier stutter.
+    ///
+    /// By default this is set to `1.0`.
+    pub gate: f32,
+    /// Start a repeat at the next boundary of
+    /// [`BeatRepeatNode::division_beats`], regardless of
+    /// [`BeatRepeatNode::probability`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub trigger: Notify<()>,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for BeatRepeatNode {
+    fn default() -> Self {
+        Self {
+            division_beats: 0.25,
+            repeats: 3,
+            interval_beats: 4.0,
+            probability: 0.0,
+            gate: 1.0,
+            trigger: Notify::default(),
+            enabled: true,
+        }
+    }
+}
+
+impl AudioNode for BeatRepeatNode {
+    type Configuration = BeatRepeatNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("beat_repeat")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, config, cx.stream_info, cx.deterministic_seed)
+    }
+}
+
+/// A repeat in progress.
+struct Repeat {
+    /// The position of the transport in beats where capturing started.
+    start_beats: f64,
+    /// The length of the slice in beats.
+    division_beats: f64,
+    /// The number of frames written to the buffers so far.
+    captured_frames: usize,
+}
+
+struct Processor {
+    params: BeatRepeatNode,
+    buffers: Vec<DelayBuffer>,
+    max_slice_seconds: f32,
+
+    repeat: Option<Repeat>,
+    /// Set by [`BeatRepeatNode::trigger`] until the next division boundary.
+    armed: bool,
+    /// The position of the transport on the last processed frame, or `None`
+    /// if the transport was not playing.
+    prev_beats: Option<f64>,
+
+    /// The amount of the repeated signal in the output, where `0.0` is only
+    /// the live input.
+    wet: f32,
+    fade_frames: f32,
+
+    fpd: u32,
+    enable_declicker: Declicker,
+}
+
+impl Processor {
+    fn new(
+        params: BeatRepeatNode,
+        config: &BeatRepeatNodeConfig,
+        info: &StreamInfo,
+        deterministic_seed: Option<u64>,
+    ) -> Self {
+        let seed = match deterministic_seed {
+            Some(node_seed) => config.seed ^ (node_seed ^ (node_seed >> 32)) as u32,
+            None => config.seed,
+        };
+        // Seed cannot be zero.
+        let seed = if seed == 0 { 17 } else { seed };
+
+        let max_slice_seconds = config.max_slice_seconds.max(0.0);
+
+        Self {
+            params,
+            buffers: (0..config.channels.get().get())
+                .map(|_| DelayBuffer::new(max_slice_frames(max_slice_seconds, info)))
+                .collect(),
+            max_slice_seconds,
+            repeat: None,
+            armed: false,
+            prev_beats: None,
+            wet: 0.0,
+            fade_frames: fade_frames(info),
+            fpd: seed,
+            enable_declicker: Declicker::from_enabled(params.enabled),
+        }
+    }
+
+    /// Returns a random value in the range `[0.0, 1.0]`.
+    fn next_unipolar(&mut self) -> f32 {
+        self.fpd ^= self.fpd << 13;
+        self.fpd ^= self.fpd >> 17;
+        self.fpd ^= self.fpd << 5;
+
+        (self.fpd >> 8) as f32 / (u32::MAX >> 8) as f32
+    }
+
+    /// End a finished repeat and start a new one when the transport moves
+    /// from `prev_beats` to `beats`.
+    fn update_repeat(&mut self, prev_beats: f64, beats: f64) {
+        if let Some(repeat) = &self.repeat {
+            let segment = ((beats - repeat.start_beats) / repeat.division_beats).floor();
+
+            // Also end the repeat if the transport jumped backwards past it.
+            if segment >= 0.0 && segment <= f64::from(self.params.repeats) {
+                return;
+            }
+
+            self.repeat = None;
+        }
+
+        let division_beats = self.params.division_beats.max(MIN_DIVISION_BEATS);
+        if (beats / division_beats).floor() == (prev_beats / division_beats).floor() {
+            return;
+        }
+
+        let interval_beats = self.params.interval_beats.max(division_beats);
+        let chance = (beats / interval_beats).floor() != (prev_beats / interval_beats).floor() && {
+            let probability = self.params.probability;
+            probability >= 1.0 || self.next_unipolar() < probability
+        };
+
+        if self.armed || chance {
+            self.armed = false;
+            self.repeat = Some(Repeat {
+                start_beats: (beats / division_beats).floor() * division_beats,
+                division_beats,
+                captured_frames: 0,
+            });
+        }
+    }
+
+    /// Render a block starting at `start_beats` on the transport, advancing by
+    /// `beats_per_frame` each frame.
+    ///
+    /// Returns `false` if the output is identical to the input.
+    fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        start_beats: f64,
+        beats_per_frame: f64,
+    ) -> bool {
+        let fade_step = self.fade_frames.recip();
+        let gate = self.params.gate.clamp(0.0, 1.0);
+        let mut modified = false;
+
+        for i in 0..frames {
+            let beats = start_beats + beats_per_frame * i as f64;
+            if let Some(prev_beats) = self.prev_beats {
+                self.update_repeat(prev_beats, beats);
+            }
+            self.prev_beats = Some(beats);
+
+            let mut wet_target = 0.0;
+            let mut window = 0.0;
+            let mut read_pos = 0.0;
+            let mut captured_frames = 0;
+
+            if let Some(repeat) = &mut self.repeat {
+                let elapsed = beats - repeat.start_beats;
+                let segment = (elapsed / repeat.division_beats).floor();
+
+                if segment < 1.0 {
+                    // Capture the slice while the input passes through.
+                    if repeat.captured_frames + 2 < self.buffers[0].len() {
+                        for (buffer, in_ch) in self.buffers.iter_mut().zip(inputs.iter()) {
+                            buffer.write(repeat.captured_frames, in_ch[i]);
+                        }
+                        repeat.captured_frames += 1;
+                    }
+                } else {
+                    let slice_frames = (repeat.division_beats / beats_per_frame) as f32;
+                    let gate_frames = slice_frames * gate;
+
+                    read_pos =
+                        ((elapsed - segment * repeat.division_beats) / beats_per_frame) as f32;
+                    window = ((read_pos * fade_step).min((gate_frames - read_pos) * fade_step))
+                        .clamp(0.0, 1.0);
+                    wet_target = 1.0;
+                }
+
+                captured_frames = repeat.captured_frames;
+                modified = true;
+            }
+
+            if self.wet < wet_target {
+                self.wet = (self.wet + fade_step).min(wet_target);
+            } else if self.wet > wet_target {
+                self.wet = (self.wet - fade_step).max(wet_target);
+            }
+
+            if self.wet > 0.0 {
+                modified = true;
+            }
+
+            for ((buffer, in_ch), out_ch) in self
+                .buffers
+                .iter()
+                .zip(inputs.iter())
+                .zip(outputs.iter_mut())
+            {
+                let live = in_ch[i];
+                let repeated = if window > 0.0 && read_pos < captured_frames as f32 {
+                    // The buffer is written from the start on each capture, so
+                    // the end of the capture acts as the write position.
+                    let delay_frames = (captured_frames as f32 - read_pos).max(1.0);
+                    buffer.read(captured_frames, delay_frames) * window
+                } else {
+                    0.0
+                };
+
+                out_ch[i] = live + (repeated - live) * self.wet;
+            }
+        }
+
+        modified
+    }
+
+    fn reset(&mut self) {
+        self.repeat = None;
+        self.prev_beats = None;
+        self.wet = 0.0;
+    }
+}
+
+fn max_slice_frames(max_slice_seconds: f32, info: &StreamInfo) -> usize {
+    (max_slice_seconds * info.sample_rate.get() as f32).ceil() as usize
+}
+
+fn fade_frames(info: &StreamInfo) -> f32 {
+    (FADE_SECONDS * info.sample_rate.get() as f32).max(1.0)
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<BeatRepeatNode>() {
+            match patch {
+                BeatRepeatNodePatch::Trigger(_) => {
+                    self.armed = true;
+                }
+                BeatRepeatNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                    self.params.enabled = enabled;
+                }
+                patch => self.params.apply(patch),
+            }
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+            self.armed = false;
+
+            return ProcessStatus::Bypass;
+        }
+
+        let Some(playhead) = info.playhead_range() else {
+            self.reset();
+
+            return ProcessStatus::Bypass;
+        };
+
+        let beats_per_frame = (playhead.end.0 - playhead.start.0) / info.frames as f64;
+        let modified = self.render(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            playhead.start.0,
+            beats_per_frame,
+        );
+
+        if !modified && self.enable_declicker.has_settled() {
+            return ProcessStatus::Bypass;
+        }
+
+        // Crossfade between the processed and input signals to declick
+        // enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        let max_slice_frames = max_slice_frames(self.max_slice_seconds, stream_info);
+        for buffer in self.buffers.iter_mut() {
+            *buffer = DelayBuffer::new(max_slice_frames);
+        }
+        self.fade_frames = fade_frames(stream_info);
+        self.reset();
+    }
+
+    fn flush_idle_state(&mut self) {
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use super::*;
+
+    const SLICE_FRAMES: usize = 8_192;
+    const START_FRAMES: usize = 4_096;
+
+    #[test]
+    fn triggered_repeat_loops_slice() {
+        let info = StreamInfo {
+            sample_rate: NonZeroU32::new(48_000).unwrap(),
+            ..Default::default()
+        };
+        let repeats = 3;
+        let mut processor = Processor::new(
+            BeatRepeatNode {
+                division_beats: 0.25,
+                repeats,
+                ..Default::default()
+            },
+            &BeatRepeatNodeConfig {
+                channels: NonZeroChannelCount::MONO,
+                ..Default::default()
+            },
+            &info,
+            None,
+        );
+        processor.armed = true;
+
+        // A power of two beats per frame keeps the beat positions exact. The
+        // transport starts halfway through a division, so the slice starts
+        // `START_FRAMES` in.
+        let beats_per_frame = 1.0 / 32_768.0;
+        let start_beats = 0.125;
+
+        let frames = START_FRAMES + SLICE_FRAMES * (repeats as usize + 2);
+        let input: Vec<f32> = (0..frames).map(|i| i as f32 / frames as f32).collect();
+        let mut output = vec![0.0; frames];
+
+        for (block_i, (in_block, out_block)) in
+            input.chunks(256).zip(output.chunks_mut(256)).enumerate()
+        {
+            processor.render(
+                &[in_block],
+                &mut [out_block],
+                in_block.len(),
+                start_beats + (block_i * 256) as f64 * beats_per_frame,
+                beats_per_frame,
+            );
+        }
+
+        let fade = processor.fade_frames as usize + 1;
+        let slice = &input[START_FRAMES..START_FRAMES + SLICE_FRAMES];
+
+        // The input passes through while the slice is captured.
+        assert_eq!(
+            &output[..START_FRAMES + SLICE_FRAMES],
+            &input[..START_FRAMES + SLICE_FRAMES]
+        );
+
+        // The slice is then played the configured number of times.
+        for repeat in 1..=repeats as usize {
+            let start = START_FRAMES + SLICE_FRAMES * repeat;
+            for i in fade..SLICE_FRAMES - fade {
+                assert!((output[start + i] - slice[i]).abs() < 1e-6);
+            }
+        }
+
+        // After that the input passes through again.
+        let end = START_FRAMES + SLICE_FRAMES * (repeats as usize + 1);
+        assert_eq!(&output[end + fade..], &input[end + fade..]);
+        assert!(processor.repeat.is_none());
+    }
+}
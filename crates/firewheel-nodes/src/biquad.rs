@@ -0,0 +1,410 @@
+//! A node that applies a biquad filter with user-supplied coefficients.
+
+use core::{fmt::Write, num::NonZeroU32};
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        declick::{DeclickFadeCurve, Declicker},
+        filter::{
+            biquad::{BiquadCoeff, BiquadState},
+            smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        },
+        volume::DEFAULT_AMP_EPSILON,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        NodeID, ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+pub type BiquadMonoNode = BiquadNode<1>;
+pub type BiquadStereoNode = BiquadNode<2>;
+
+/// A node that applies a biquad (second order IIR) filter with raw,
+/// user-supplied coefficients.
+///
+/// This is useful for filter designs not covered by the other filter nodes,
+/// i.e. coefficients computed by an external tool.
+///
+/// Changes to the coefficients are smoothed. Coefficients which would result
+/// in an unstable filter (see [`BiquadCoeff::is_stable`]) are rejected, and
+/// the node keeps using the last stable set of coefficients.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BiquadNode<const CHANNELS: usize> {
+    /// The coefficients of the filter.
+    ///
+    /// By default this is set to [`BiquadCoeff::NO_OP`].
+    pub coeff: BiquadCoeff,
+    /// The time in seconds of the internal smoothing filter.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl<const CHANNELS: usize> Default for BiquadNode<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            coeff: BiquadCoeff::NO_OP,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            enabled: true,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for BiquadNode<CHANNELS> {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("biquad")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let smoother_config = cx.smoother_config(SmootherConfig {
+            smooth_seconds: self.smooth_seconds,
+            ..Default::default()
+        });
+
+        Processor::<CHANNELS>::new(
+            *self,
+            smoother_config,
+            cx.stream_info.sample_rate,
+            cx.node_id,
+        )
+    }
+}
+
+/// The smoothed coefficients of the filter.
+///
+/// All coefficients share the same exponential smoothing filter, so while
+/// smoothing they always lie on the line between the previous and the new
+/// set of coefficients. Because the stable region is convex, the filter stays
+/// stable while it is transitioning between two stable sets.
+struct SmoothedCoeff {
+    b0: SmoothedParam,
+    b1: SmoothedParam,
+    b2: SmoothedParam,
+    a1: SmoothedParam,
+    a2: SmoothedParam,
+}
+
+impl SmoothedCoeff {
+    fn new(coeff: BiquadCoeff, config: SmootherConfig, sample_rate: NonZeroU32) -> Self {
+        Self {
+            b0: SmoothedParam::new(coeff.b0, config, sample_rate),
+            b1: SmoothedParam::new(coeff.b1, config, sample_rate),
+            b2: SmoothedParam::new(coeff.b2, config, sample_rate),
+            a1: SmoothedParam::new(coeff.a1, config, sample_rate),
+            a2: SmoothedParam::new(coeff.a2, config, sample_rate),
+        }
+    }
+
+    fn params_mut(&mut self) -> [&mut SmoothedParam; 5] {
+        [
+            &mut self.b0,
+            &mut self.b1,
+            &mut self.b2,
+            &mut self.a1,
+            &mut self.a2,
+        ]
+    }
+
+    fn set_value(&mut self, coeff: BiquadCoeff) {
+        self.b0.set_value(coeff.b0);
+        self.b1.set_value(coeff.b1);
+        self.b2.set_value(coeff.b2);
+        self.a1.set_value(coeff.a1);
+        self.a2.set_value(coeff.a2);
+    }
+
+    fn target_value(&self) -> BiquadCoeff {
+        BiquadCoeff {
+            b0: self.b0.target_value(),
+            b1: self.b1.target_value(),
+            b2: self.b2.target_value(),
+            a1: self.a1.target_value(),
+            a2: self.a2.target_value(),
+        }
+    }
+
+    #[inline(always)]
+    fn next_smoothed(&mut self) -> BiquadCoeff {
+        BiquadCoeff {
+            b0: self.b0.next_smoothed(),
+            b1: self.b1.next_smoothed(),
+            b2: self.b2.next_smoothed(),
+            a1: self.a1.next_smoothed(),
+            a2: self.a2.next_smoothed(),
+        }
+    }
+
+    fn is_smoothing(&self) -> bool {
+        self.b0.is_smoothing()
+            || self.b1.is_smoothing()
+            || self.b2.is_smoothing()
+            || self.a1.is_smoothing()
+            || self.a2.is_smoothing()
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    params: BiquadNode<CHANNELS>,
+    coeff: SmoothedCoeff,
+    states: [BiquadState; CHANNELS],
+    enable_declicker: Declicker,
+    node_id: NodeID,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(
+        mut params: BiquadNode<CHANNELS>,
+        smoother_config: SmootherConfig,
+        sample_rate: NonZeroU32,
+        node_id: NodeID,
+    ) -> Self {
+        if !params.coeff.is_stable() {
+            params.coeff = BiquadCoeff::NO_OP;
+        }
+
+        Self {
+            params,
+            coeff: SmoothedCoeff::new(params.coeff, smoother_config, sample_rate),
+            states: [BiquadState::default(); CHANNELS],
+            enable_declicker: Declicker::from_enabled(params.enabled),
+            node_id,
+        }
+    }
+
+    fn reset(&mut self) {
+        for state in self.states.iter_mut() {
+            state.reset();
+        }
+    }
+
+    fn filter_block(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        if self.coeff.is_smoothing() {
+            for i in 0..frames {
+                let coeff = self.coeff.next_smoothed();
+
+                for ((state, in_ch), out_ch) in self
+                    .states
+                    .iter_mut()
+                    .zip(inputs.iter())
+                    .zip(outputs.iter_mut())
+                {
+                    out_ch[i] = state.process(in_ch[i], &coeff);
+                }
+            }
+
+            for param in self.coeff.params_mut() {
+                param.settle();
+            }
+        } else {
+            let coeff = self.coeff.target_value();
+
+            for ((state, in_ch), out_ch) in self
+                .states
+                .iter_mut()
+                .zip(inputs.iter())
+                .zip(outputs.iter_mut())
+            {
+                for (&s_in, s_out) in in_ch[..frames].iter().zip(out_ch[..frames].iter_mut()) {
+                    *s_out = state.process(s_in, &coeff);
+                }
+            }
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        let mut coeff_changed = false;
+        for patch in events.drain_patches::<BiquadNode<CHANNELS>>() {
+            match patch {
+                BiquadNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                }
+                BiquadNodePatch::SmoothSeconds(seconds) => {
+                    for param in self.coeff.params_mut() {
+                        param.set_smooth_seconds(seconds, info.sample_rate);
+                    }
+                }
+                BiquadNodePatch::Coeff(_) => coeff_changed = true,
+            }
+
+            self.params.apply(patch);
+        }
+
+        // Only check the coefficients once all patches have been applied, since
+        // a new set of coefficients may arrive as several patches.
+        if coeff_changed {
+            if self.params.coeff.is_stable() {
+                self.coeff.set_value(self.params.coeff);
+
+                if info.prev_output_was_silent {
+                    // Previous block was silent, so no need to smooth.
+                    for param in self.coeff.params_mut() {
+                        param.reset_to_target();
+                    }
+                }
+            } else {
+                let rejected = self.params.coeff;
+                self.params.coeff = self.coeff.target_value();
+
+                let node_id = self.node_id;
+                let _ = extra.logger.try_error_with(|s| {
+                    let _ = write!(
+                        s,
+                        "BiquadNode {:?} rejected unstable coefficients: {:?}",
+                        node_id, rejected
+                    );
+                });
+            }
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+            return ProcessStatus::Bypass;
+        }
+
+        if info.in_silence_mask.all_channels_silent(CHANNELS)
+            && info.prev_output_was_silent
+            && self.enable_declicker.has_settled()
+        {
+            for param in self.coeff.params_mut() {
+                param.reset_to_target();
+            }
+            self.reset();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.filter_block(buffers.inputs, buffers.outputs, info.frames);
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        // Let the tail of the filter ring out before declaring the output
+        // silent.
+        buffers.check_for_silence_on_outputs(DEFAULT_AMP_EPSILON)
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        for param in self.coeff.params_mut() {
+            param.update_sample_rate(stream_info.sample_rate);
+        }
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use firewheel_core::dsp::filter::svf::{SvfCoeff, SvfState};
+
+    #[test]
+    fn lowpass_coefficients_match_svf_lowpass() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let sample_rate_recip = 1.0 / sample_rate.get() as f32;
+        let (cutoff_hz, q) = (1_000.0, 0.707);
+
+        let node = BiquadMonoNode {
+            coeff: BiquadCoeff::lowpass(cutoff_hz, q, sample_rate_recip),
+            ..Default::default()
+        };
+        let mut processor = Processor::new(
+            node,
+            SmootherConfig::default(),
+            sample_rate,
+            NodeID::DANGLING,
+        );
+
+        // An impulse followed by a mix of tones below and above the cutoff.
+        let input: Vec<f32> = (0..4_800)
+            .map(|i| {
+                let t = i as f32 * sample_rate_recip;
+                let impulse = if i == 0 { 1.0 } else { 0.0 };
+                impulse
+                    + 0.5 * (core::f32::consts::TAU * 200.0 * t).sin()
+                    + 0.5 * (core::f32::consts::TAU * 8_000.0 * t).sin()
+            })
+            .collect();
+
+        let mut output = vec![0.0; input.len()];
+        processor.filter_block(&[&input], &mut [&mut output], input.len());
+
+        let svf_coeff = SvfCoeff::lowpass_ord2(cutoff_hz, q, sample_rate_recip);
+        let mut svf_state = SvfState::default();
+        for (i, (&s_in, &s_out)) in input.iter().zip(output.iter()).enumerate() {
+            let expected = svf_state.process(s_in, &svf_coeff);
+            assert!(
+                (s_out - expected).abs() < 0.0001,
+                "frame {i}: {s_out} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn unstable_coefficients_are_rejected() {
+        let lowpass = BiquadCoeff::lowpass(1_000.0, 0.707, 1.0 / 48_000.0);
+        assert!(lowpass.is_stable());
+        assert!(BiquadCoeff::NO_OP.is_stable());
+
+        // A pole outside of the unit circle.
+        let unstable = BiquadCoeff {
+            a1: -2.1,
+            a2: 1.1,
+            ..lowpass
+        };
+        assert!(!unstable.is_stable());
+        assert!(!BiquadCoeff {
+            b0: f32::NAN,
+            ..lowpass
+        }
+        .is_stable());
+
+        let processor = Processor::new(
+            BiquadMonoNode {
+                coeff: unstable,
+                ..Default::default()
+            },
+            SmootherConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+            NodeID::DANGLING,
+        );
+        assert_eq!(processor.coeff.target_value(), BiquadCoeff::NO_OP);
+    }
+}
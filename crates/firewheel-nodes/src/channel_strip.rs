@@ -0,0 +1,591 @@
+//! A wrapper that turns any effect node into a mixer channel strip.
+
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+use firewheel_core::{
+    channel_config::MAX_CHANNELS,
+    diff::{Diff, EventQueue, Patch, PatchError, PathBuilder},
+    dsp::{
+        filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        volume::{Volume, DEFAULT_AMP_EPSILON},
+    },
+    event::{NodeEventType, ParamData, ProcEvents},
+    mask::MaskType,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeInfoInner, AudioNodeProcessor,
+        ConstructProcessorContext, ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+        UpdateContext,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The level (in raw amplitude) above which [`soft_clip`] starts to bend the
+/// signal (about `-6` dBFS).
+pub const SOFT_CLIP_KNEE: f32 = 0.5;
+
+/// The path index of [`ChannelStripNode::strip`]. The effect keeps its own
+/// paths, so that its processor receives the events it expects.
+const STRIP_PATH_INDEX: u32 = u32::MAX;
+
+/// Saturate a sample smoothly towards `±1.0`. Samples below
+/// [`SOFT_CLIP_KNEE`] pass through unchanged.
+#[inline]
+pub fn soft_clip(s: f32) -> f32 {
+    let level = s.abs();
+    if level <= SOFT_CLIP_KNEE {
+        return s;
+    }
+
+    let range = 1.0 - SOFT_CLIP_KNEE;
+    let clipped = SOFT_CLIP_KNEE + range * ((level - SOFT_CLIP_KNEE) / range).tanh();
+    clipped * s.signum()
+}
+
+/// The controls of a [`ChannelStripNode`] around its effect.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelStrip {
+    /// The gain applied to the input before it reaches the effect.
+    ///
+    /// By default this is set to [`Volume::UNITY_GAIN`].
+    pub input_trim: Volume,
+    /// The gain applied to the output of the effect.
+    ///
+    /// By default this is set to [`Volume::UNITY_GAIN`].
+    pub output_gain: Volume,
+    /// If `true`, then the output is saturated with [`soft_clip`] after the
+    /// output gain, so that it never exceeds `±1.0`.
+    ///
+    /// By default this is set to `false`.
+    pub soft_clip: bool,
+    /// The time in seconds of the internal smoothing filter for the gains.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for ChannelStrip {
+    fn default() -> Self {
+        Self {
+            input_trim: Volume::UNITY_GAIN,
+            output_gain: Volume::UNITY_GAIN,
+            soft_clip: false,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+/// A wrapper around any effect node which adds an input trim before it, and
+/// an output gain with an optional soft clipper after it, so that mixer
+/// channels can be built uniformly out of any effect.
+///
+/// The wrapped node keeps its own configuration, channel layout, and
+/// parameters. Its parameters are diffed at the same paths as if it wasn't
+/// wrapped, while the controls in [`ChannelStripNode::strip`] are handled by
+/// the wrapper.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChannelStripNode<N> {
+    /// The wrapped effect node.
+    pub effect: N,
+    /// The controls around the effect.
+    pub strip: ChannelStrip,
+}
+
+impl<N> ChannelStripNode<N> {
+    /// Wrap the given effect node with the default (unity gain) controls.
+    pub fn new(effect: N) -> Self {
+        Self {
+            effect,
+            strip: ChannelStrip::default(),
+        }
+    }
+}
+
+impl<N: Diff> Diff for ChannelStripNode<N> {
+    fn diff<E: EventQueue>(&self, baseline: &Self, path: PathBuilder, event_queue: &mut E) {
+        self.strip
+            .diff(&baseline.strip, path.with(STRIP_PATH_INDEX), event_queue);
+        self.effect.diff(&baseline.effect, path, event_queue);
+    }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        match path.split_first() {
+            Some((&STRIP_PATH_INDEX, rest)) => self.strip.get_param(rest),
+            _ => self.effect.get_param(path),
+        }
+    }
+
+    fn resolve_path<'a>(
+        names: &mut impl Iterator<Item = &'a str>,
+        path: PathBuilder,
+    ) -> Option<PathBuilder> {
+        match names.next() {
+            None => Some(path),
+            Some("strip") => ChannelStrip::resolve_path(names, path.with(STRIP_PATH_INDEX)),
+            Some(name) => N::resolve_path(&mut core::iter::once(name).chain(names), path),
+        }
+    }
+}
+
+/// A patch for a [`ChannelStripNode`].
+pub enum ChannelStripNodePatch<N: Patch> {
+    Effect(N::Patch),
+    Strip(ChannelStripPatch),
+}
+
+impl<N: Patch> Patch for ChannelStripNode<N> {
+    type Patch = ChannelStripNodePatch<N>;
+
+    fn patch(data: &ParamData, path: &[u32]) -> Result<Self::Patch, PatchError> {
+        match path.split_first() {
+            Some((&STRIP_PATH_INDEX, rest)) => {
+                ChannelStrip::patch(data, rest).map(ChannelStripNodePatch::Strip)
+            }
+            _ => N::patch(data, path).map(ChannelStripNodePatch::Effect),
+        }
+    }
+
+    fn apply(&mut self, patch: Self::Patch) {
+        match patch {
+            ChannelStripNodePatch::Effect(patch) => self.effect.apply(patch),
+            ChannelStripNodePatch::Strip(patch) => self.strip.apply(patch),
+        }
+    }
+}
+
+impl<N: AudioNode> AudioNode for ChannelStripNode<N> {
+    type Configuration = N::Configuration;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        self.effect.info(config)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        // The input trim needs a buffer for each input channel of the effect.
+        let num_inputs = AudioNodeInfoInner::from(self.effect.info(config))
+            .channel_config
+            .num_inputs
+            .get() as usize;
+        let stream_info = cx.stream_info.clone();
+        let min_param_ramp_seconds = cx.min_param_ramp_seconds;
+
+        let effect = self.effect.construct_processor(config, cx);
+
+        let mut processor = Processor::new(effect, self.strip, num_inputs, &stream_info);
+        processor
+            .input_trim
+            .set_min_smooth_seconds(min_param_ramp_seconds, stream_info.sample_rate);
+        processor
+            .output
+            .gain
+            .set_min_smooth_seconds(min_param_ramp_seconds, stream_info.sample_rate);
+        processor
+    }
+
+    fn update(&mut self, config: &Self::Configuration, cx: UpdateContext) {
+        self.effect.update(config, cx);
+    }
+}
+
+/// The output gain and soft clipper which follow the effect.
+struct OutputStage {
+    gain: SmoothedParam,
+    soft_clip: bool,
+}
+
+impl OutputStage {
+    /// Returns `true` if this stage leaves the signal unchanged.
+    fn is_transparent(&self) -> bool {
+        !self.soft_clip && self.gain.has_settled_at(1.0)
+    }
+
+    /// Apply the output stage to the result of the effect.
+    ///
+    /// * `status` - The status the effect returned.
+    /// * `inputs` - The inputs the effect was given, for when it bypassed
+    ///   itself.
+    fn process(
+        &mut self,
+        status: ProcessStatus,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        inputs_trimmed: bool,
+    ) -> ProcessStatus {
+        let silence_mask = match status {
+            ProcessStatus::ClearAllOutputs => {
+                self.gain.reset_to_target();
+                return status;
+            }
+            ProcessStatus::Bypass | ProcessStatus::PassThrough => {
+                if !inputs_trimmed && self.is_transparent() {
+                    return status;
+                }
+
+                for (i, out_ch) in outputs.iter_mut().enumerate() {
+                    match inputs.get(i) {
+                        Some(in_ch) => out_ch[..frames].copy_from_slice(&in_ch[..frames]),
+                        None => out_ch[..frames].fill(0.0),
+                    }
+                }
+
+                None
+            }
+            ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(mask)) => Some(mask),
+            ProcessStatus::OutputsModified | ProcessStatus::OutputsModifiedWithMask(_) => None,
+        };
+
+        if self.is_transparent() {
+            // The effect already filled the outputs.
+            return match status {
+                ProcessStatus::Bypass | ProcessStatus::PassThrough => {
+                    ProcessStatus::OutputsModified
+                }
+                _ => status,
+            };
+        }
+
+        if self.gain.is_smoothing() {
+            for i in 0..frames {
+                let gain = self.gain.next_smoothed();
+                for out_ch in outputs.iter_mut() {
+                    out_ch[i] *= gain;
+                }
+            }
+            self.gain.settle();
+        } else {
+            let gain = self.gain.target_value();
+            if gain != 1.0 {
+                for out_ch in outputs.iter_mut() {
+                    for s in out_ch[..frames].iter_mut() {
+                        *s *= gain;
+                    }
+                }
+            }
+        }
+
+        if self.soft_clip {
+            for out_ch in outputs.iter_mut() {
+                for s in out_ch[..frames].iter_mut() {
+                    *s = soft_clip(*s);
+                }
+            }
+        }
+
+        // Silent channels stay silent through the gain and the clipper.
+        match silence_mask {
+            Some(mask) => ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(mask)),
+            None => ProcessStatus::OutputsModified,
+        }
+    }
+}
+
+struct Processor<P> {
+    effect: P,
+    params: ChannelStrip,
+    input_trim: SmoothedParam,
+    output: OutputStage,
+    /// The trimmed input of each channel, one after the other.
+    trimmed: Vec<f32>,
+    num_inputs: usize,
+    max_block_frames: usize,
+}
+
+impl<P: AudioNodeProcessor> Processor<P> {
+    fn new(effect: P, params: ChannelStrip, num_inputs: usize, stream_info: &StreamInfo) -> Self {
+        let max_block_frames = stream_info.max_block_frames.get() as usize;
+        let smoothed = |volume: Volume, sample_rate: NonZeroU32| {
+            SmoothedParam::new(
+                volume.amp_clamped(DEFAULT_AMP_EPSILON),
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            )
+        };
+
+        Self {
+            effect,
+            params,
+            input_trim: smoothed(params.input_trim, stream_info.sample_rate),
+            output: OutputStage {
+                gain: smoothed(params.output_gain, stream_info.sample_rate),
+                soft_clip: params.soft_clip,
+            },
+            trimmed: vec![0.0; num_inputs * max_block_frames],
+            num_inputs,
+            max_block_frames,
+        }
+    }
+
+    fn apply_strip_event(&mut self, event: NodeEventType, sample_rate: NonZeroU32) {
+        let NodeEventType::Param { data, path } = &event else {
+            return;
+        };
+        let Ok(patch) = ChannelStrip::patch(data, &path[1..]) else {
+            return;
+        };
+
+        match patch {
+            ChannelStripPatch::InputTrim(volume) => {
+                self.input_trim
+                    .set_value(volume.amp_clamped(DEFAULT_AMP_EPSILON));
+            }
+            ChannelStripPatch::OutputGain(volume) => {
+                self.output
+                    .gain
+                    .set_value(volume.amp_clamped(DEFAULT_AMP_EPSILON));
+            }
+            ChannelStripPatch::SoftClip(soft_clip) => {
+                self.output.soft_clip = soft_clip;
+            }
+            ChannelStripPatch::SmoothSeconds(seconds) => {
+                self.input_trim.set_smooth_seconds(seconds, sample_rate);
+                self.output.gain.set_smooth_seconds(seconds, sample_rate);
+            }
+        }
+
+        self.params.apply(patch);
+    }
+
+    /// Write the trimmed inputs into `self.trimmed`.
+    fn trim(&mut self, inputs: &[&[f32]], frames: usize) {
+        let channels = self
+            .trimmed
+            .chunks_exact_mut(self.max_block_frames)
+            .zip(inputs.iter());
+        for (buf, in_ch) in channels {
+            buf[..frames].copy_from_slice(&in_ch[..frames]);
+        }
+
+        if self.input_trim.is_smoothing() {
+            for i in 0..frames {
+                let gain = self.input_trim.next_smoothed();
+                for buf in self.trimmed.chunks_exact_mut(self.max_block_frames) {
+                    buf[i] *= gain;
+                }
+            }
+            self.input_trim.settle();
+        } else {
+            let gain = self.input_trim.target_value();
+            for buf in self.trimmed.chunks_exact_mut(self.max_block_frames) {
+                for s in buf[..frames].iter_mut() {
+                    *s *= gain;
+                }
+            }
+        }
+    }
+}
+
+fn is_strip_event(event: &NodeEventType) -> bool {
+    matches!(
+        event,
+        NodeEventType::Param { path, .. } if path.first() == Some(&STRIP_PATH_INDEX)
+    )
+}
+
+impl<P: AudioNodeProcessor> AudioNodeProcessor for Processor<P> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        events.drain_where(is_strip_event, |event| {
+            self.apply_strip_event(event, info.sample_rate)
+        });
+
+        let frames = info.frames;
+        let trim_inputs = !self.input_trim.has_settled_at(1.0);
+        if trim_inputs {
+            self.trim(buffers.inputs, frames);
+        }
+
+        // The buffers passed to the effect must share a lifetime, so reborrow
+        // the outputs alongside the trimmed inputs.
+        let mut inputs: [&[f32]; MAX_CHANNELS] = [&[]; MAX_CHANNELS];
+        if trim_inputs {
+            let channels = inputs
+                .iter_mut()
+                .zip(self.trimmed.chunks_exact(self.max_block_frames));
+            for (in_ch, buf) in channels {
+                *in_ch = &buf[..frames];
+            }
+        } else {
+            for (in_ch, buf) in inputs.iter_mut().zip(buffers.inputs.iter()) {
+                *in_ch = buf;
+            }
+        }
+        let inputs = &inputs[..buffers.inputs.len().min(self.num_inputs)];
+
+        let num_outputs = buffers.outputs.len();
+        let mut outputs_iter = buffers.outputs.iter_mut();
+        let mut outputs: [&mut [f32]; MAX_CHANNELS] =
+            core::array::from_fn(|_| outputs_iter.next().map(|ch| &mut **ch).unwrap_or_default());
+        let outputs = &mut outputs[..num_outputs];
+
+        let status = self.effect.process(
+            info,
+            ProcBuffers {
+                inputs,
+                outputs: &mut *outputs,
+            },
+            events,
+            extra,
+        );
+
+        self.output
+            .process(status, inputs, outputs, frames, trim_inputs)
+    }
+
+    fn stream_stopped(&mut self, context: &mut ProcStreamCtx) {
+        self.effect.stream_stopped(context);
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, context: &mut ProcStreamCtx) {
+        self.input_trim.update_sample_rate(stream_info.sample_rate);
+        self.output.gain.update_sample_rate(stream_info.sample_rate);
+
+        self.max_block_frames = stream_info.max_block_frames.get() as usize;
+        self.trimmed
+            .resize(self.num_inputs * self.max_block_frames, 0.0);
+
+        self.effect.new_stream(stream_info, context);
+    }
+
+    fn flush_idle_state(&mut self) {
+        self.effect.flush_idle_state();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use firewheel_core::{
+        diff::ParamPath,
+        dsp::volume::{amp_to_db, db_to_amp},
+    };
+
+    fn output_stage(gain_db: f32, soft_clip: bool) -> OutputStage {
+        OutputStage {
+            gain: SmoothedParam::new(
+                db_to_amp(gain_db),
+                SmootherConfig::default(),
+                NonZeroU32::new(48_000).unwrap(),
+            ),
+            soft_clip,
+        }
+    }
+
+    #[test]
+    fn output_gain_and_soft_clip_follow_the_effect() {
+        // The effect turned a quiet input up to `0.4`.
+        let input = [0.1; 64];
+        let effect_output = [0.4; 64];
+
+        // +6 dB of output gain doubles the output of the effect.
+        let mut stage = output_stage(6.0206, false);
+        let mut out = effect_output;
+        let status = stage.process(
+            ProcessStatus::OutputsModified,
+            &[&input],
+            &mut [&mut out],
+            64,
+            false,
+        );
+        assert_eq!(status, ProcessStatus::OutputsModified);
+        assert!(out.iter().all(|&s| (s - 0.8).abs() < 1e-4));
+
+        // With soft clipping, the boosted output is bent below full scale,
+        // even though the input of the effect was quiet.
+        let mut stage = output_stage(12.0412, true);
+        let mut out = effect_output;
+        stage.process(
+            ProcessStatus::OutputsModified,
+            &[&input],
+            &mut [&mut out],
+            64,
+            false,
+        );
+        let expected = soft_clip(1.6);
+        assert!(out.iter().all(|&s| (s - expected).abs() < 1e-4));
+        assert!(expected < 1.0 && expected > 0.9);
+
+        // Levels below the knee pass through the clipper untouched.
+        let mut stage = output_stage(-6.0206, true);
+        let mut out = effect_output;
+        stage.process(
+            ProcessStatus::OutputsModified,
+            &[&input],
+            &mut [&mut out],
+            64,
+            false,
+        );
+        assert!(out.iter().all(|&s| (s - 0.2).abs() < 1e-4));
+
+        // A bypassed effect passes its input on to the output stage.
+        let mut stage = output_stage(-6.0206, false);
+        let mut out = [0.0; 64];
+        let status = stage.process(ProcessStatus::Bypass, &[&input], &mut [&mut out], 64, false);
+        assert_eq!(status, ProcessStatus::OutputsModified);
+        assert!(out
+            .iter()
+            .all(|&s| (amp_to_db(s) - amp_to_db(0.05)).abs() < 1e-3));
+
+        // A transparent output stage leaves the status of the effect alone.
+        let mut stage = output_stage(0.0, false);
+        let status = stage.process(ProcessStatus::Bypass, &[&input], &mut [&mut out], 64, false);
+        assert_eq!(status, ProcessStatus::Bypass);
+    }
+
+    #[test]
+    fn strip_params_use_their_own_path() {
+        let baseline = ChannelStripNode::new(crate::volume::VolumeNode::default());
+        let mut node = baseline;
+        node.effect.volume = Volume::Decibels(-6.0);
+        node.strip.output_gain = Volume::Decibels(3.0);
+
+        let mut events = Vec::new();
+        node.diff(&baseline, PathBuilder::default(), &mut events);
+        assert_eq!(events.len(), 2);
+
+        // The effect sees its own patch at its usual path, and the strip
+        // patch is taken out by the wrapper.
+        let mut patched = baseline;
+        for event in &events {
+            let patch = ChannelStripNode::<crate::volume::VolumeNode>::patch_event(event);
+            patched.apply(patch.unwrap());
+
+            let is_effect = crate::volume::VolumeNode::patch_event(event).is_some();
+            assert_eq!(is_effect, !is_strip_event(event));
+        }
+        assert_eq!(patched, node);
+
+        let path = ChannelStripNode::<crate::volume::VolumeNode>::resolve_path(
+            &mut "strip.output_gain".split('.'),
+            PathBuilder::default(),
+        )
+        .unwrap()
+        .build();
+        assert!(matches!(path, ParamPath::Multi(_)));
+        assert_eq!(&*path, &[STRIP_PATH_INDEX, 1]);
+        assert!(matches!(
+            node.get_param(&path),
+            Some(ParamData::Volume(Volume::Decibels(db))) if db == 3.0
+        ));
+    }
+}
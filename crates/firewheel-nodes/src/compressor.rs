@@ -0,0 +1,1065 @@
+//! A dynamic range compressor and limiter node.
+
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        declick::{DeclickFadeCurve, Declicker},
+        envelope_follower::{DetectorMode, EnvelopeFollower, EnvelopeFollowerConfig, Knee},
+        filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        gain_match::{GainMatch, DEFAULT_GAIN_MATCH_WINDOW_SECS},
+        volume::{amp_to_db, db_to_amp, Volume},
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus, UpdateContext,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+pub type CompressorMonoNode = CompressorNode<1>;
+pub type CompressorStereoNode = CompressorNode<2>;
+
+pub use firewheel_core::dsp::envelope_follower::LIMITER_RATIO;
+
+/// A dynamic range compressor.
+///
+/// Signals louder than the threshold are turned down by the ratio, with
+/// the speed of the gain change set by the attack and release times of the
+/// detector. Use [`CompressorNode::glue_preset`] for a gentle setting that
+/// works well on a mix bus.
+///
+/// With a ratio of at least [`LIMITER_RATIO`], this acts as a hard limiter:
+/// once the detector has caught up, the level never rises above the
+/// threshold.
+///
+/// For rhythmic "pumping" without a real sidechain signal, enable the
+/// `musical_transport` feature and then [`CompressorNode::transport_pump`]
+/// to drive the gain from an envelope synced to the musical transport
+/// instead.
+///
+/// To catch the onset of transients, set [`CompressorConfig::lookahead_secs`].
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressorNode<const CHANNELS: usize> {
+    /// The gain applied to the input before it reaches the compressor. This
+    /// changes how hard the signal is driven into the threshold without
+    /// having to adjust the threshold itself.
+    ///
+    /// Changes to this value are smoothed.
+    ///
+    /// By default this is set to [`Volume::UNITY_GAIN`].
+    pub input_trim: Volume,
+    /// If `true`, then the inverse of [`CompressorNode::input_trim`] is
+    /// applied to the output, so the trim only changes the amount of
+    /// compression and not the overall level.
+    ///
+    /// By default this is set to `false`.
+    pub compensate_input_trim: bool,
+    /// The configuration of the level detector.
+    pub detector: EnvelopeFollowerConfig,
+    /// The level in decibels at which compression begins.
+    ///
+    /// By default this is set to `-20.0`.
+    pub threshold_db: f32,
+    /// The ratio of compression (i.e. `4.0` means `4:1`). Values less than
+    /// `1.0` are treated as `1.0`, and values of at least [`LIMITER_RATIO`]
+    /// are treated as infinite.
+    ///
+    /// By default this is set to `4.0`.
+    pub ratio: f32,
+    /// The width in decibels of the soft knee around the threshold. A value
+    /// of `0.0` gives a hard knee.
+    ///
+    /// By default this is set to `6.0`.
+    pub knee_db: f32,
+    /// The gain in decibels applied after compression.
+    ///
+    /// Changes to this value are smoothed.
+    ///
+    /// By default this is set to `0.0`.
+    pub makeup_gain_db: f32,
+    /// If `true`, then all channels share a single detector which follows
+    /// the loudest channel, so the same gain change is applied to each and
+    /// the stereo image doesn't shift.
+    ///
+    /// By default this is set to `true`.
+    pub stereo_link: bool,
+    /// If `true`, then while this node is disabled the dry signal is played
+    /// at the same loudness as the compressed signal, so that toggling
+    /// [`CompressorNode::enabled`] compares the two fairly instead of the
+    /// louder one sounding "better".
+    ///
+    /// The difference in loudness is measured while the node is enabled.
+    ///
+    /// By default this is set to `false`.
+    pub gain_matched_bypass: bool,
+    /// A gain envelope synced to the musical transport which, when enabled,
+    /// replaces the level detector.
+    #[cfg(feature = "musical_transport")]
+    pub transport_pump: TransportPump,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+/// The smallest allowed value for [`TransportPump::division_beats`].
+#[cfg(feature = "musical_transport")]
+pub const MIN_PUMP_DIVISION_BEATS: f64 = 1.0 / 64.0;
+
+/// A gain envelope synced to the musical transport, for side-chain style
+/// "pumping" without a sidechain signal.
+///
+/// At the start of every cycle the gain dips by [`TransportPump::depth_db`],
+/// and then ramps back up to unity by the end of the cycle. While the
+/// transport is not playing, no gain reduction is applied.
+#[cfg(feature = "musical_transport")]
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TransportPump {
+    /// If `true`, then the gain is driven by this envelope instead of the
+    /// level of the input signal.
+    ///
+    /// By default this is set to `false`.
+    pub enabled: bool,
+    /// The length of one cycle in beats. For example, `1.0` pumps once per
+    /// beat, and `0.5` pumps once per eighth note.
+    ///
+    /// By default this is set to `1.0`.
+    pub division_beats: f64,
+    /// The amount of gain reduction at the start of each cycle in decibels
+    /// (a value `>= 0.0`).
+    ///
+    /// By default this is set to `12.0`.
+    pub depth_db: f32,
+    /// The shape of the ramp back up to unity. `1.0` ramps linearly (in
+    /// decibels), while larger values recover faster and then hold near
+    /// unity for the rest of the cycle.
+    ///
+    /// By default this is set to `2.0`.
+    pub curve: f32,
+}
+
+#[cfg(feature = "musical_transport")]
+impl Default for TransportPump {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            division_beats: 1.0,
+            depth_db: 12.0,
+            curve: 2.0,
+        }
+    }
+}
+
+#[cfg(feature = "musical_transport")]
+impl TransportPump {
+    /// The gain (in raw amplitude) of the envelope at the given position of
+    /// the transport in beats.
+    pub fn gain(&self, beats: f64) -> f32 {
+        let cycles = beats / self.division_beats.max(MIN_PUMP_DIVISION_BEATS);
+        let phase = (cycles - cycles.floor()) as f32;
+
+        db_to_amp(-self.depth_db.max(0.0) * (1.0 - phase).powf(self.curve.max(0.0)))
+    }
+}
+
+/// The configuration of a [`CompressorNode`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressorConfig {
+    /// The time in seconds the audio is delayed relative to the level
+    /// detector.
+    ///
+    /// Without a lookahead, the detector only reacts once a transient has
+    /// already arrived, so the first few milliseconds of it slip through at
+    /// full level. With a lookahead, the gain starts to come down before
+    /// the transient reaches the output. The node adds this much latency,
+    /// which is reported to the graph once a stream is running.
+    ///
+    /// By default this is set to `0.0` (no lookahead).
+    pub lookahead_secs: f32,
+}
+
+impl CompressorConfig {
+    /// The number of frames of lookahead (and thus latency) at the given
+    /// sample rate.
+    pub fn lookahead_frames(&self, sample_rate: NonZeroU32) -> u32 {
+        (self.lookahead_secs.max(0.0) * sample_rate.get() as f32).round() as u32
+    }
+}
+
+impl Default for CompressorConfig {
+    fn default() -> Self {
+        Self {
+            lookahead_secs: 0.0,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> Default for CompressorNode<CHANNELS> {
+    fn default() -> Self {
+        let knee = Knee::default();
+
+        Self {
+            input_trim: Volume::UNITY_GAIN,
+            compensate_input_trim: false,
+            detector: EnvelopeFollowerConfig::default(),
+            threshold_db: knee.threshold_db,
+            ratio: knee.ratio,
+            knee_db: knee.knee_db,
+            makeup_gain_db: 0.0,
+            stereo_link: true,
+            gain_matched_bypass: false,
+            #[cfg(feature = "musical_transport")]
+            transport_pump: TransportPump::default(),
+            enabled: true,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> CompressorNode<CHANNELS> {
+    /// A gentle "glue" setting for the master bus or a group bus.
+    ///
+    /// This uses a stereo-linked RMS detector with a slow attack and an
+    /// automatic release, along with a soft knee and a low `2:1` ratio. It
+    /// evens out a mix by a few decibels without audible pumping.
+    pub fn glue_preset() -> Self {
+        Self {
+            input_trim: Volume::UNITY_GAIN,
+            compensate_input_trim: false,
+            detector: EnvelopeFollowerConfig {
+                mode: DetectorMode::Rms,
+                attack_secs: 0.03,
+                release_secs: 0.1,
+                auto_release: true,
+                rms_window_secs: 0.05,
+            },
+            threshold_db: -18.0,
+            ratio: 2.0,
+            knee_db: 10.0,
+            makeup_gain_db: 0.0,
+            stereo_link: true,
+            gain_matched_bypass: false,
+            #[cfg(feature = "musical_transport")]
+            transport_pump: TransportPump::default(),
+            enabled: true,
+        }
+    }
+
+    fn knee(&self) -> Knee {
+        Knee {
+            threshold_db: self.threshold_db,
+            ratio: if self.ratio >= LIMITER_RATIO {
+                f32::INFINITY
+            } else {
+                self.ratio
+            },
+            knee_db: self.knee_db,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for CompressorNode<CHANNELS> {
+    type Configuration = CompressorConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("compressor")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+            // The latency of the lookahead depends on the sample rate.
+            .call_update_method(config.lookahead_secs > 0.0)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(*self, config, cx.stream_info.sample_rate);
+        processor
+            .input_trim
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+            .makeup_gain
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+
+    fn update(&mut self, config: &Self::Configuration, mut cx: UpdateContext) {
+        if let Some(stream_info) = cx.stream_info {
+            let latency_frames = config.lookahead_frames(stream_info.sample_rate);
+            cx.set_latency_frames(latency_frames);
+        }
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    params: CompressorNode<CHANNELS>,
+    knee: Knee,
+    makeup_gain: SmoothedParam,
+    input_trim: SmoothedParam,
+    followers: [EnvelopeFollower; CHANNELS],
+    sample_rate: NonZeroU32,
+    enable_declicker: Declicker,
+    gain_match: GainMatch,
+    /// The position of the transport in beats at the next frame, and the
+    /// number of beats per frame. This is `None` if the transport is not
+    /// playing.
+    #[cfg(feature = "musical_transport")]
+    pump_beats: Option<(f64, f64)>,
+    lookahead_secs: f32,
+    lookahead: Option<LookaheadDelay<CHANNELS>>,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(
+        params: CompressorNode<CHANNELS>,
+        config: &CompressorConfig,
+        sample_rate: NonZeroU32,
+    ) -> Self {
+        let follower = EnvelopeFollower::new(params.detector, sample_rate);
+
+        Self {
+            params,
+            knee: params.knee(),
+            makeup_gain: SmoothedParam::new(
+                db_to_amp(params.makeup_gain_db),
+                SmootherConfig {
+                    smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+            input_trim: SmoothedParam::new(
+                params.input_trim.amp(),
+                SmootherConfig {
+                    smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+            followers: [follower; CHANNELS],
+            sample_rate,
+            enable_declicker: Declicker::from_enabled(params.enabled),
+            gain_match: GainMatch::new(DEFAULT_GAIN_MATCH_WINDOW_SECS, sample_rate),
+            #[cfg(feature = "musical_transport")]
+            pump_beats: None,
+            lookahead_secs: config.lookahead_secs,
+            lookahead: LookaheadDelay::new(config.lookahead_frames(sample_rate) as usize),
+        }
+    }
+
+    fn update_params(&mut self) {
+        self.knee = self.params.knee();
+        self.makeup_gain
+            .set_value(db_to_amp(self.params.makeup_gain_db));
+        for follower in self.followers.iter_mut() {
+            follower.set_config(self.params.detector, self.sample_rate);
+        }
+    }
+
+    /// The gain (not including the makeup gain) for the given envelope.
+    fn gain(&self, envelope: f32) -> f32 {
+        db_to_amp(self.knee.compressor_gain_db(amp_to_db(envelope)))
+    }
+
+    /// The gain of the transport pump at the next frame.
+    #[cfg(feature = "musical_transport")]
+    fn pump_gain(&mut self) -> f32 {
+        let Some((beats, beats_per_frame)) = &mut self.pump_beats else {
+            return 1.0;
+        };
+
+        let gain = self.params.transport_pump.gain(*beats);
+        *beats += *beats_per_frame;
+
+        gain
+    }
+
+    /// Compress a block of audio in place.
+    ///
+    /// Returns the largest amount of gain reduction applied in this block,
+    /// in decibels (a value `>= 0.0`).
+    fn compress(&mut self, mut channels: [&mut [f32]; CHANNELS]) -> f32 {
+        let frames = channels.iter().map(|ch| ch.len()).min().unwrap_or(0);
+        let mut min_gain: f32 = 1.0;
+
+        for i in 0..frames {
+            let trim = self.input_trim.next_smoothed();
+            let makeup_gain = self.makeup_gain.next_smoothed();
+
+            let input: [f32; CHANNELS] = core::array::from_fn(|ch_i| channels[ch_i][i]);
+
+            // With a lookahead, the detector hears each frame before it
+            // reaches the output.
+            let delayed = match &mut self.lookahead {
+                Some(lookahead) => lookahead.next(input),
+                None => input,
+            };
+
+            let trimmed = input.map(|s| s * trim);
+
+            let output_trim = if self.params.compensate_input_trim && trim > 0.0 {
+                trim.recip()
+            } else {
+                1.0
+            };
+
+            #[cfg(feature = "musical_transport")]
+            let pump_gain = self.params.transport_pump.enabled.then(|| self.pump_gain());
+            #[cfg(not(feature = "musical_transport"))]
+            let pump_gain: Option<f32> = None;
+
+            let gains: [f32; CHANNELS] = if let Some(pump_gain) = pump_gain {
+                [pump_gain; CHANNELS]
+            } else if self.params.stereo_link {
+                // Feed the detector the level of the loudest channel.
+                let linked = trimmed.iter().fold(0.0f32, |a, s| a.max(s.abs()));
+
+                let envelope = self.followers[0].process(linked);
+                [self.gain(envelope); CHANNELS]
+            } else {
+                core::array::from_fn(|ch_i| {
+                    let envelope = self.followers[ch_i].process(trimmed[ch_i]);
+                    self.gain(envelope)
+                })
+            };
+
+            for ((ch, s), gain) in channels.iter_mut().zip(delayed).zip(gains) {
+                ch[i] = s * trim * gain * makeup_gain * output_trim;
+                min_gain = min_gain.min(gain);
+            }
+        }
+
+        self.input_trim.settle();
+        self.makeup_gain.settle();
+
+        -amp_to_db(min_gain).min(0.0)
+    }
+
+    /// Compress a block of audio into `outputs`, measuring the difference
+    /// in loudness for the gain-matched bypass.
+    fn process_wet(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        for (out_ch, in_ch) in outputs.iter_mut().zip(inputs.iter()) {
+            out_ch[..frames].copy_from_slice(&in_ch[..frames]);
+        }
+
+        let mut outputs_iter = outputs.iter_mut();
+        self.compress(core::array::from_fn(|_| {
+            &mut outputs_iter.next().unwrap()[..frames]
+        }));
+
+        // Only measure the fully wet signal.
+        if self.enable_declicker == Declicker::SettledAt1 {
+            self.gain_match
+                .measure(&inputs[..CHANNELS], &outputs[..CHANNELS], frames);
+        }
+    }
+
+    /// Copy the dry signal into `outputs`, delayed by the lookahead and (if
+    /// [`CompressorNode::gain_matched_bypass`] is set) matched to the
+    /// loudness of the compressed signal.
+    ///
+    /// If `advance` is `false`, then the lookahead delay line is left as is,
+    /// because the same block is about to be compressed.
+    fn dry<V: AsMut<[f32]>>(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [V],
+        frames: usize,
+        advance: bool,
+    ) {
+        let gain = if self.params.gain_matched_bypass {
+            self.gain_match.gain()
+        } else {
+            1.0
+        };
+
+        match &mut self.lookahead {
+            Some(lookahead) if advance => {
+                let inputs: [&[f32]; CHANNELS] = core::array::from_fn(|ch_i| inputs[ch_i]);
+                for i in 0..frames {
+                    let delayed = lookahead.next(inputs.map(|in_ch| in_ch[i]));
+                    for (out_ch, s) in outputs.iter_mut().zip(delayed) {
+                        out_ch.as_mut()[i] = s;
+                    }
+                }
+            }
+            Some(lookahead) => lookahead.peek(inputs, outputs, frames),
+            None => {
+                for (out_ch, in_ch) in outputs.iter_mut().zip(inputs.iter()) {
+                    out_ch.as_mut()[..frames].copy_from_slice(&in_ch[..frames]);
+                }
+            }
+        }
+
+        if gain != 1.0 {
+            for out_ch in outputs.iter_mut() {
+                for s in out_ch.as_mut()[..frames].iter_mut() {
+                    *s *= gain;
+                }
+            }
+        }
+    }
+
+    /// Whether the lookahead delay line holds only silence.
+    fn lookahead_flushed(&self) -> bool {
+        self.lookahead
+            .as_ref()
+            .is_none_or(|lookahead| lookahead.is_flushed())
+    }
+
+    fn reset(&mut self) {
+        self.input_trim.reset_to_target();
+        self.makeup_gain.reset_to_target();
+        for follower in self.followers.iter_mut() {
+            follower.reset();
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<CompressorNode<CHANNELS>>() {
+            match patch {
+                CompressorNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                }
+                CompressorNodePatch::InputTrim(input_trim) => {
+                    self.input_trim.set_value(input_trim.amp());
+                }
+                _ => {}
+            }
+
+            self.params.apply(patch);
+            self.update_params();
+        }
+
+        let input_silent = info.in_silence_mask.all_channels_silent(CHANNELS);
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+
+            // The dry signal is still delayed by the lookahead so that the
+            // latency of this node doesn't change.
+            if !self.params.gain_matched_bypass && self.lookahead.is_none() {
+                return ProcessStatus::Bypass;
+            }
+
+            if input_silent && self.lookahead_flushed() {
+                return ProcessStatus::ClearAllOutputs;
+            }
+
+            self.dry(buffers.inputs, buffers.outputs, info.frames, true);
+            if let Some(lookahead) = &mut self.lookahead {
+                lookahead.count_silence(input_silent, info.frames);
+            }
+
+            return ProcessStatus::OutputsModified;
+        }
+
+        if input_silent && self.enable_declicker.has_settled() && self.lookahead_flushed() {
+            self.reset();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        #[cfg(feature = "musical_transport")]
+        {
+            self.pump_beats = info.playhead_range().map(|playhead| {
+                (
+                    playhead.start.0,
+                    (playhead.end.0 - playhead.start.0) / info.frames as f64,
+                )
+            });
+        }
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        if (self.params.gain_matched_bypass || self.lookahead.is_some())
+            && !self.enable_declicker.has_settled()
+        {
+            let mut dry = extra.scratch_buffers.channels_mut::<CHANNELS>();
+            self.dry(buffers.inputs, &mut dry, info.frames, false);
+
+            self.process_wet(buffers.inputs, buffers.outputs, info.frames);
+
+            self.enable_declicker.process_crossfade(
+                &dry,
+                buffers.outputs,
+                info.frames,
+                &extra.declick_values,
+                DeclickFadeCurve::Linear,
+            );
+        } else {
+            self.process_wet(buffers.inputs, buffers.outputs, info.frames);
+
+            self.enable_declicker.process_crossfade(
+                buffers.inputs,
+                buffers.outputs,
+                info.frames,
+                &extra.declick_values,
+                DeclickFadeCurve::Linear,
+            );
+        }
+
+        if let Some(lookahead) = &mut self.lookahead {
+            lookahead.count_silence(input_silent, info.frames);
+        }
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.input_trim.update_sample_rate(stream_info.sample_rate);
+        self.makeup_gain.update_sample_rate(stream_info.sample_rate);
+        self.gain_match
+            .set_window(DEFAULT_GAIN_MATCH_WINDOW_SECS, stream_info.sample_rate);
+        self.update_params();
+        self.reset();
+
+        self.lookahead = LookaheadDelay::new(
+            CompressorConfig {
+                lookahead_secs: self.lookahead_secs,
+            }
+            .lookahead_frames(stream_info.sample_rate) as usize,
+        );
+    }
+}
+
+/// The delay line of the audio path of a compressor with a lookahead.
+struct LookaheadDelay<const CHANNELS: usize> {
+    buffers: [Vec<f32>; CHANNELS],
+    pos: usize,
+    /// The number of silent frames pushed into the delay line in a row.
+    silent_frames: usize,
+}
+
+impl<const CHANNELS: usize> LookaheadDelay<CHANNELS> {
+    /// Returns `None` if `frames` is `0`.
+    fn new(frames: usize) -> Option<Self> {
+        (frames > 0).then(|| Self {
+            buffers: core::array::from_fn(|_| vec![0.0; frames]),
+            pos: 0,
+            silent_frames: frames,
+        })
+    }
+
+    fn frames(&self) -> usize {
+        self.buffers[0].len()
+    }
+
+    /// Push a frame into the delay line, returning the frame that was pushed
+    /// [`LookaheadDelay::frames`] frames ago.
+    fn next(&mut self, frame: [f32; CHANNELS]) -> [f32; CHANNELS] {
+        let out = core::array::from_fn(|ch_i| self.buffers[ch_i][self.pos]);
+
+        for (buffer, s) in self.buffers.iter_mut().zip(frame) {
+            buffer[self.pos] = s;
+        }
+
+        self.pos += 1;
+        if self.pos == self.frames() {
+            self.pos = 0;
+        }
+
+        out
+    }
+
+    /// Write what [`LookaheadDelay::next`] would return for a block of
+    /// inputs into `outputs`, without changing the delay line.
+    fn peek<V: AsMut<[f32]>>(&self, inputs: &[&[f32]], outputs: &mut [V], frames: usize) {
+        let len = self.frames();
+
+        for ((out_ch, in_ch), buffer) in outputs.iter_mut().zip(inputs).zip(&self.buffers) {
+            for (i, os) in out_ch.as_mut()[..frames].iter_mut().enumerate() {
+                *os = if i < len {
+                    buffer[(self.pos + i) % len]
+                } else {
+                    in_ch[i - len]
+                };
+            }
+        }
+    }
+
+    fn count_silence(&mut self, silent: bool, frames: usize) {
+        if silent {
+            self.silent_frames = self.silent_frames.saturating_add(frames);
+        } else {
+            self.silent_frames = 0;
+        }
+    }
+
+    fn is_flushed(&self) -> bool {
+        self.silent_frames >= self.frames()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glue_preset_is_gentle_and_does_not_pump() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(
+            CompressorStereoNode::glue_preset(),
+            &CompressorConfig::default(),
+            sample_rate,
+        );
+
+        // A sustained pad with a loud kick-like hit every 500ms.
+        let frames = 48_000 * 4;
+        let mut left: Vec<f32> = (0..frames)
+            .map(|i| {
+                let pad = 0.25 * (i as f32 * 0.031).sin();
+                let t = i % 24_000;
+                let kick = if t < 2_400 {
+                    0.9 * (1.0 - t as f32 / 2_400.0) * (t as f32 * 0.012).sin()
+                } else {
+                    0.0
+                };
+                pad + kick
+            })
+            .collect();
+        let mut right = left.clone();
+
+        // The gain reduction of each 10ms block.
+        let reduction_db: Vec<f32> = left
+            .chunks_mut(480)
+            .zip(right.chunks_mut(480))
+            .map(|(l, r)| processor.compress([l, r]))
+            .collect();
+
+        // Ignore the first second while the detector settles.
+        let settled = &reduction_db[100..];
+        let max = settled.iter().fold(0.0f32, |a, &b| a.max(b));
+        let min = settled.iter().fold(f32::MAX, |a, &b| a.min(b));
+
+        // The preset compresses the mix, but only by a few decibels.
+        assert!(max > 0.5, "max reduction {max}");
+        assert!(max < 6.0, "max reduction {max}");
+
+        // The gain doesn't swing up and down with each hit.
+        assert!(max - min < 3.0, "reduction swings by {}", max - min);
+
+        // Stereo linking keeps both channels identical.
+        assert_eq!(left, right);
+    }
+
+    #[test]
+    fn input_trim_doubles_level_seen_by_compressor() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let params = CompressorStereoNode {
+            detector: EnvelopeFollowerConfig {
+                mode: DetectorMode::Peak,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let trim = Volume::Decibels(6.0206);
+
+        let input: Vec<f32> = (0..48_000).map(|i| 0.2 * (i as f32 * 0.05).sin()).collect();
+
+        // The input trimmed by +6dB, with the trim compensated at the output.
+        let mut trimmed = Processor::new(
+            CompressorStereoNode {
+                input_trim: trim,
+                compensate_input_trim: true,
+                ..params
+            },
+            &CompressorConfig::default(),
+            sample_rate,
+        );
+        let mut trimmed_l = input.clone();
+        let mut trimmed_r = input.clone();
+        let trimmed_reduction = trimmed.compress([&mut trimmed_l, &mut trimmed_r]);
+
+        // The input at twice the level, with no trim.
+        let mut doubled = Processor::new(params, &CompressorConfig::default(), sample_rate);
+        let mut doubled_l: Vec<f32> = input.iter().map(|s| s * 2.0).collect();
+        let mut doubled_r = doubled_l.clone();
+        let doubled_reduction = doubled.compress([&mut doubled_l, &mut doubled_r]);
+
+        // The compressor reacts exactly as if the input was twice as loud.
+        assert!(doubled_reduction > 3.0);
+        assert!((trimmed_reduction - doubled_reduction).abs() < 1e-3);
+
+        // With compensation, the output is back at the original level.
+        for (t, d) in trimmed_l.iter().zip(doubled_l.iter()) {
+            assert!((t * 2.0 - d).abs() < 1e-4);
+        }
+
+        // Without compensation, the trim carries through to the output.
+        let mut uncompensated = Processor::new(
+            CompressorStereoNode {
+                input_trim: trim,
+                ..params
+            },
+            &CompressorConfig::default(),
+            sample_rate,
+        );
+        let mut out_l = input.clone();
+        let mut out_r = input.clone();
+        uncompensated.compress([&mut out_l, &mut out_r]);
+        for (o, d) in out_l.iter().zip(doubled_l.iter()) {
+            assert!((o - d).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn gain_matched_bypass_matches_compressed_loudness() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(
+            CompressorStereoNode {
+                gain_matched_bypass: true,
+                ..Default::default()
+            },
+            &CompressorConfig::default(),
+            sample_rate,
+        );
+
+        // A steady tone well above the threshold.
+        let input: Vec<f32> = (0..480).map(|i| 0.8 * (i as f32 * 0.1309).sin()).collect();
+        let inputs: [&[f32]; 2] = [&input, &input];
+        let mut out_l = vec![0.0; 480];
+        let mut out_r = vec![0.0; 480];
+
+        // Four seconds of the compressed signal.
+        for _ in 0..400 {
+            processor.process_wet(&inputs, &mut [&mut out_l, &mut out_r], 480);
+        }
+        let wet_rms = rms(&out_l);
+
+        processor.dry(&inputs, &mut [&mut out_l, &mut out_r], 480, true);
+        let bypass_rms = rms(&out_l);
+
+        // The compressor turns the tone down, and the bypass follows it.
+        assert!(wet_rms < rms(&input) * 0.5);
+        assert!((amp_to_db(bypass_rms) - amp_to_db(wet_rms)).abs() < 0.1);
+        assert_eq!(out_l, out_r);
+    }
+
+    #[cfg(feature = "musical_transport")]
+    #[test]
+    fn transport_pump_dips_once_per_beat() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let depth_db = 9.0;
+        let mut processor = Processor::new(
+            CompressorStereoNode {
+                transport_pump: TransportPump {
+                    enabled: true,
+                    depth_db,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            &CompressorConfig::default(),
+            sample_rate,
+        );
+
+        // A quiet tone that the detector alone would leave untouched, at
+        // 120 BPM for 4 seconds (8 beats) in blocks of 512 frames.
+        let beats_per_frame = 2.0 / 48_000.0;
+        let input: Vec<f32> = (0..4 * 48_000)
+            .map(|i| 0.05 * (i as f32 * 0.05).sin())
+            .collect();
+        let mut left = input.clone();
+        let mut right = input.clone();
+        for (block_i, (l, r)) in left.chunks_mut(512).zip(right.chunks_mut(512)).enumerate() {
+            processor.pump_beats =
+                Some(((block_i * 512) as f64 * beats_per_frame, beats_per_frame));
+            processor.compress([l, r]);
+        }
+
+        // The gain applied at each frame where the tone is loud enough to
+        // measure it accurately.
+        let gain_db: Vec<(usize, f32)> = input
+            .iter()
+            .zip(left.iter())
+            .enumerate()
+            .filter(|(_, (i, _))| i.abs() > 0.01)
+            .map(|(frame, (i, o))| (frame, amp_to_db(o / i)))
+            .collect();
+
+        // The deepest dip of each beat is at the configured depth, right at
+        // the start of the beat.
+        for beat in gain_db.chunk_by(|a, b| a.0 / 24_000 == b.0 / 24_000) {
+            let (frame, min_db) =
+                beat.iter()
+                    .copied()
+                    .fold((0, f32::MAX), |a, b| if b.1 < a.1 { b } else { a });
+            assert!((min_db + depth_db).abs() < 0.1, "min {min_db}");
+            assert!(frame % 24_000 < 50, "dip at frame {frame}");
+
+            // And the gain is back near unity by the end of the beat.
+            assert!(beat.last().unwrap().1 > -0.1);
+        }
+        assert_eq!(gain_db.last().unwrap().0 / 24_000, 7);
+
+        // Without a playing transport, the gain stays at unity.
+        processor.pump_beats = None;
+        let mut l = input[..512].to_vec();
+        let mut r = l.clone();
+        assert_eq!(processor.compress([&mut l, &mut r]), 0.0);
+        assert_eq!(l, input[..512]);
+    }
+
+    #[test]
+    fn lookahead_reduces_gain_at_onset() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let config = CompressorConfig {
+            lookahead_secs: 0.005,
+        };
+        let lookahead = config.lookahead_frames(sample_rate) as usize;
+        assert_eq!(lookahead, 240);
+
+        // A quiet signal, and then a loud trigger well above the threshold.
+        let onset = 4_800;
+        let input: Vec<f32> = (0..9_600)
+            .map(|i| if i < onset { 0.01 } else { 0.9 })
+            .collect();
+
+        let gain_db_at_onset = |config: &CompressorConfig| {
+            let mut processor =
+                Processor::new(CompressorStereoNode::default(), config, sample_rate);
+            let mut left = input.clone();
+            let mut right = input.clone();
+            for (l, r) in left.chunks_mut(512).zip(right.chunks_mut(512)) {
+                processor.compress([l, r]);
+            }
+
+            // The output is delayed by the lookahead.
+            let latency = config.lookahead_frames(sample_rate) as usize;
+            assert!(left[onset + latency - 1] <= 0.01 + 1e-4);
+            assert_eq!(left, right);
+
+            amp_to_db(left[onset + latency] / 0.9)
+        };
+
+        // Without a lookahead, the first frame of the trigger leaks through
+        // at full level.
+        assert!(gain_db_at_onset(&CompressorConfig::default()) > -0.5);
+
+        // With a lookahead, the gain is already coming down when it arrives.
+        let gain_db = gain_db_at_onset(&config);
+        assert!(gain_db < -3.0, "gain at onset {gain_db}");
+    }
+
+    /// A compressor with a hard knee and a peak detector.
+    fn hard_knee<const CHANNELS: usize>(ratio: f32) -> Processor<CHANNELS> {
+        Processor::new(
+            CompressorNode {
+                threshold_db: -20.0,
+                ratio,
+                knee_db: 0.0,
+                ..Default::default()
+            },
+            &CompressorConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+        )
+    }
+
+    #[test]
+    fn steady_state_reduction_follows_ratio() {
+        // A constant level of -6 dB is 14 dB over the threshold, so a `4:1`
+        // ratio lets `3.5` dB of it through.
+        let level = 0.5;
+        let expected_db = -20.0 + (amp_to_db(level) + 20.0) / 4.0;
+
+        // The quieter right channel gets the same gain as the left channel,
+        // so the image doesn't shift.
+        let mut processor = hard_knee::<2>(4.0);
+        let mut left = vec![level; 48_000];
+        let mut right = vec![level * 0.25; 48_000];
+        processor.compress([&mut left, &mut right]);
+
+        let out_l = *left.last().unwrap();
+        let out_r = *right.last().unwrap();
+        assert!((amp_to_db(out_l) - expected_db).abs() < 0.01);
+        assert!((out_r / out_l - 0.25).abs() < 1e-5);
+
+        // With an infinite ratio, the mono limiter holds the level at the
+        // threshold.
+        let mut limiter = hard_knee::<1>(LIMITER_RATIO);
+        let mut mono = vec![level; 48_000];
+        limiter.compress([&mut mono]);
+        assert!((amp_to_db(*mono.last().unwrap()) + 20.0).abs() < 0.01);
+
+        // Makeup gain is added on top.
+        let mut processor = Processor::<1>::new(
+            CompressorNode {
+                makeup_gain_db: 6.0,
+                ..hard_knee::<1>(4.0).params
+            },
+            &CompressorConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+        );
+        let mut mono = vec![level; 48_000];
+        processor.compress([&mut mono]);
+        assert!((amp_to_db(*mono.last().unwrap()) - expected_db - 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn attack_and_release_are_time_constants() {
+        let mut processor = hard_knee::<1>(2.0);
+        let attack_frames = (processor.params.detector.attack_secs * 48_000.0) as usize;
+        let release_frames = (processor.params.detector.release_secs * 48_000.0) as usize;
+
+        // The envelope the detector must have had to produce the given
+        // output level with a `2:1` ratio.
+        let envelope = |input: f32, output: f32| {
+            let reduction_db = amp_to_db(input) - amp_to_db(output);
+            db_to_amp(-20.0 + reduction_db * 2.0)
+        };
+
+        // After one attack time, the envelope has risen `1 - 1/e` of the
+        // way towards a step up in level.
+        let mut attack = vec![0.5; attack_frames];
+        processor.compress([&mut attack]);
+        let env = envelope(0.5, *attack.last().unwrap());
+        assert!((env / 0.5 - (1.0 - (-1.0f32).exp())).abs() < 0.01, "{env}");
+
+        // Let it settle.
+        processor.compress([&mut vec![0.5; 48_000]]);
+
+        // After one release time, the envelope has fallen `1 - 1/e` of the
+        // way towards a step down in level (which is still over the
+        // threshold).
+        let mut release = vec![0.2; release_frames];
+        processor.compress([&mut release]);
+        let env = envelope(0.2, *release.last().unwrap());
+        let expected = 0.2 + 0.3 * (-1.0f32).exp();
+        assert!((env - expected).abs() < 0.005, "{env} {expected}");
+    }
+
+    fn rms(s: &[f32]) -> f32 {
+        (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt()
+    }
+}
@@ -0,0 +1,205 @@
+//! A node that outputs a constant value.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount, NonZeroChannelCount},
+    diff::{Diff, Patch},
+    dsp::filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+    event::ProcEvents,
+    mask::ConstantMask,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The configuration of a [`ConstantNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantNodeConfig {
+    /// The number of output channels.
+    pub channels: NonZeroChannelCount,
+}
+
+impl Default for ConstantNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::MONO,
+        }
+    }
+}
+
+/// A node that outputs [`ConstantNode::value`] on every channel.
+///
+/// This is useful as a fixed modulation source, or for adding an offset to
+/// a signal by mixing it in. Changes to the value are smoothed so that they
+/// do not click.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConstantNode {
+    /// The value to output.
+    ///
+    /// By default this is set to `0.0`.
+    pub value: f32,
+    /// The time in seconds of the internal smoothing filter for changes to
+    /// the value.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for ConstantNode {
+    fn default() -> Self {
+        Self {
+            value: 0.0,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl ConstantNode {
+    /// Construct a new `ConstantNode` that outputs the given value.
+    pub const fn from_value(value: f32) -> Self {
+        Self {
+            value,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for ConstantNode {
+    type Configuration = ConstantNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("constant")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: config.channels.get(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(self, cx.stream_info.sample_rate);
+        processor
+            .value
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct Processor {
+    value: SmoothedParam,
+}
+
+impl Processor {
+    fn new(params: &ConstantNode, sample_rate: NonZeroU32) -> Self {
+        Self {
+            value: SmoothedParam::new(
+                params.value,
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+        }
+    }
+
+    /// Fill the outputs with the (smoothed) value.
+    ///
+    /// Returns `true` if every output holds the same value in every frame.
+    fn render(&mut self, outputs: &mut [&mut [f32]], frames: usize) -> bool {
+        let (first, rest) = outputs.split_first_mut().unwrap();
+
+        let constant = self.value.has_settled();
+        self.value.process_into_buffer(&mut first[..frames]);
+
+        for out_ch in rest.iter_mut() {
+            out_ch[..frames].copy_from_slice(&first[..frames]);
+        }
+
+        constant
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<ConstantNode>() {
+            match patch {
+                ConstantNodePatch::Value(value) => {
+                    self.value.set_value(value);
+                }
+                ConstantNodePatch::SmoothSeconds(seconds) => {
+                    self.value.set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+        }
+
+        if self.value.has_settled_at(0.0) {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        if self.render(buffers.outputs, info.frames) {
+            ProcessStatus::outputs_modified_with_constant_mask(ConstantMask::new_all_constant(
+                buffers.outputs.len(),
+            ))
+        } else {
+            ProcessStatus::OutputsModified
+        }
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.value.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_constant_and_ramps_on_change() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(&ConstantNode::from_value(0.5), sample_rate);
+
+        let mut out_l = vec![0.0; 256];
+        let mut out_r = vec![0.0; 256];
+
+        // Before any change, the output is exactly the constant.
+        assert!(processor.render(&mut [&mut out_l, &mut out_r], 256));
+        assert!(out_l.iter().chain(out_r.iter()).all(|&s| s == 0.5));
+
+        // A change ramps smoothly up towards the new value.
+        processor.value.set_value(1.0);
+        assert!(!processor.render(&mut [&mut out_l, &mut out_r], 256));
+        assert_eq!(out_l, out_r);
+        assert!(out_l[0] > 0.5 && out_l[255] < 1.0);
+        assert!(out_l.windows(2).all(|w| w[1] > w[0]));
+        assert!(out_l.windows(2).all(|w| w[1] - w[0] < 0.01));
+
+        // Once the smoothing settles, the output is exactly the new value.
+        for _ in 0..100 {
+            processor.render(&mut [&mut out_l, &mut out_r], 256);
+        }
+        assert!(processor.render(&mut [&mut out_l, &mut out_r], 256));
+        assert!(out_l.iter().chain(out_r.iter()).all(|&s| s == 1.0));
+    }
+}
@@ -1,30 +1,41 @@
-use core::f32;
+use bevy_platform::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, Mutex,
+};
+use core::{f32, fmt::Write, num::NonZeroU32};
 
-use fft_convolver::FFTConvolver;
+use fft_convolver::{FFTConvolver, FFTConvolverProcessError};
 use firewheel_core::{
     channel_config::{ChannelConfig, ChannelCount},
-    collector::OwnedGc,
+    collector::{ArcGc, OwnedGc},
     diff::{Diff, Patch},
     dsp::{
-        declick::{DeclickFadeCurve, Declicker},
+        algo::max_peak,
+        declick::{DeclickFadeCurve, DeclickValues, Declicker},
         fade::FadeCurve,
         filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
         mix::{Mix, MixDSP},
-        volume::Volume,
+        tail_gate::{TailGate, TailGateConfig},
+        volume::{Volume, DEFAULT_AMP_EPSILON},
     },
     event::NodeEventType,
     node::{
-        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcessStatus,
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, NodeID,
+        ProcessStatus,
     },
     param::smoother::{SmoothedParam, SmootherConfig},
     sample_resource::SampleResourceF32,
 };
+use ringbuf::traits::{Consumer, Producer, Split};
 
 /// Imparts characteristics of an [`ImpulseResponse`] to the input signal.
 ///
 /// Convolution is often used to achieve reverb effects, but is more
 /// computationally expensive than algorithmic reverb.
-#[derive(Patch, Diff, Debug, Clone, Copy, PartialEq)]
+///
+/// To also route the wet signal on its own (i.e. to a parallel reverb
+/// return), enable [`ConvolutionNodeConfig::wet_output`].
+#[derive(Patch, Diff, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -41,6 +52,10 @@ pub struct ConvolutionNode<const CHANNELS: usize> {
     /// fully the first signal, `1.0` is fully the second signal, and `0.5` is
     /// an equal mix of both.
     ///
+    /// The amount of the second (wet) signal is scaled by the context's
+    /// global wet amount (see
+    /// [`ProcInfo::global_wet`](firewheel_core::node::ProcInfo::global_wet)).
+    ///
     /// By default this is set to [`Mix::CENTER`].
     pub mix: Mix,
 
@@ -61,10 +76,17 @@ pub struct ConvolutionNode<const CHANNELS: usize> {
     ///
     /// Defaults to `0.015` (15ms).
     pub smooth_seconds: f32,
+
+    /// A gate that truncates the wet signal once the input has been quiet
+    /// for a set amount of time, for the classic "gated reverb" drum sound.
+    /// The dry signal is not affected.
+    ///
+    /// By default the gate is disabled.
+    pub gate: TailGateConfig,
 }
 
 /// Node configuration for [`ConvolutionNode`].
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -72,11 +94,150 @@ pub struct ConvolutionNodeConfig<const CHANNELS: usize> {
     /// The maximum number of supported IR channels (must be
     /// `ChannelCount::MONO` or `ChannelCount::STEREO`). This determines the
     /// number of buffers allocated. Loading an impulse response with more
-    /// channels than supported will result in the remaining channels being
-    /// removed.
+    /// channels than supported will result in the extra channels being
+    /// downmixed into the supported ones (see
+    /// [`ConvolutionNodeState::downmixed_from`]).
+    ///
+    /// This is ignored when [`ConvolutionNodeConfig::true_stereo`] is in
+    /// effect.
     pub max_impulse_channel_count: ChannelCount,
 
+    /// If `true` and the node is stereo, then a 4-channel impulse response
+    /// is treated as a true stereo impulse response, with the channels in
+    /// the order `L->L`, `L->R`, `R->L`, `R->R`. Each input is convolved with
+    /// the two paths leading out of it, and the paths leading into each
+    /// output are summed, so sound on one side of the input also bleeds into
+    /// the opposite output like in a recorded space.
+    ///
+    /// Impulse responses with fewer than 4 channels are convolved one
+    /// channel per input as usual. Impulse responses with more than 4
+    /// channels have their extra channels downmixed into the 4 paths.
+    ///
+    /// Files which store the paths in a different order can be loaded with
+    /// [`ConvolutionNodeConfig::true_stereo_layout`].
+    ///
+    /// This has no effect on a mono node.
+    ///
+    /// By default this is set to `false`.
+    pub true_stereo: bool,
+
+    /// The order of the channels in a true stereo impulse response file.
+    ///
+    /// This is applied when the impulse response is created with
+    /// [`ImpulseResponse::new_with_config`], which routes each channel of the
+    /// file to the convolver of its path.
+    ///
+    /// By default this is set to [`TrueStereoLayout::ByInput`].
+    pub true_stereo_layout: TrueStereoLayout,
+
     pub partition_size: usize,
+
+    /// The time in seconds over which the output fades out and back in when
+    /// the impulse response is changed (or when the node is paused).
+    ///
+    /// A shorter fade allows quickly morphing between impulse responses, such
+    /// as when swapping rooms, at the risk of a more audible transition.
+    ///
+    /// If `None`, then the declick time of the audio graph is used.
+    ///
+    /// By default this is set to `None`.
+    pub ir_crossfade_seconds: Option<f32>,
+
+    /// The curve of the fade when the impulse response is changed.
+    ///
+    /// By default this is set to [`DeclickFadeCurve::EqualPower3dB`].
+    pub ir_crossfade_curve: DeclickFadeCurve,
+
+    /// If `true`, then the node has `CHANNELS` extra outputs after the mixed
+    /// outputs which carry only the wet (convolved) signal. This lets the
+    /// node act as a pure reverb processor, i.e. for a parallel reverb
+    /// return.
+    ///
+    /// The wet outputs include the wet gain and the gate, but are not
+    /// affected by `mix` or the global wet amount.
+    ///
+    /// By default this is set to `false`.
+    pub wet_output: bool,
+
+    /// If `true`, then loading an impulse response while the node has none
+    /// keeps the dry signal playing and fades the wet signal in from silence
+    /// (over [`ConvolutionNodeConfig::ir_crossfade_seconds`]). If `false`,
+    /// then the whole output fades out and back in like any other impulse
+    /// response change.
+    ///
+    /// By default this is set to `true`.
+    pub fade_in_first_ir: bool,
+
+    /// The maximum length of the impulse response in milliseconds, trading
+    /// reverb length for lower CPU and memory usage. Longer impulse
+    /// responses are truncated with a short fade-out.
+    ///
+    /// This is applied when the impulse response is created with
+    /// [`ImpulseResponse::new_with_config`].
+    ///
+    /// If `None`, then impulse responses are not truncated.
+    ///
+    /// By default this is set to `None`.
+    pub max_tail_ms: Option<f32>,
+}
+
+/// The number of consecutive blocks the output must stay below
+/// [`DEFAULT_AMP_EPSILON`] (while the input is silent) before the tail is
+/// considered to have decayed.
+const TAIL_QUIET_BLOCKS: u32 = 8;
+
+/// The length of the fade-out applied to the end of a truncated impulse
+/// response in milliseconds (see [`ConvolutionNodeConfig::max_tail_ms`]).
+const TRUNCATION_FADE_MS: f32 = 10.0;
+
+/// The number of channels in a true stereo impulse response (see
+/// [`ConvolutionNodeConfig::true_stereo`]).
+const TRUE_STEREO_IR_CHANNELS: usize = 4;
+
+/// The order of the channels in a true stereo impulse response file (see
+/// [`ConvolutionNodeConfig::true_stereo_layout`]).
+///
+/// A true stereo impulse response has one channel for each path from an
+/// input to an output. The node convolves each input with the two paths
+/// leading out of it, with the convolvers in the order:
+///
+/// | Convolver | Path     |
+/// |-----------|----------|
+/// | 0         | `L->L`   |
+/// | 1         | `L->R`   |
+/// | 2         | `R->L`   |
+/// | 3         | `R->R`   |
+///
+/// Files with more than 4 channels repeat the layout for every group of 4
+/// channels.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TrueStereoLayout {
+    /// The channels are grouped by input: `L->L`, `L->R`, `R->L`, `R->R`.
+    ///
+    /// This matches the order of the convolvers, so the channels are used
+    /// as they are.
+    #[default]
+    ByInput,
+    /// The channels are grouped by output: `L->L`, `R->L`, `L->R`, `R->R`.
+    ByOutput,
+}
+
+impl TrueStereoLayout {
+    /// The index of the convolver which the given channel of a file in this
+    /// layout is routed to.
+    pub const fn convolver_index(&self, file_channel: usize) -> usize {
+        let group = file_channel - file_channel % TRUE_STEREO_IR_CHANNELS;
+        let path = file_channel % TRUE_STEREO_IR_CHANNELS;
+
+        group
+            + match self {
+                Self::ByInput => path,
+                // Swap `R->L` and `L->R`.
+                Self::ByOutput => [0, 2, 1, 3][path],
+            }
+    }
 }
 
 /// The default partition size to use with a [`ConvolutionNode`].
@@ -84,36 +245,204 @@ pub struct ConvolutionNodeConfig<const CHANNELS: usize> {
 /// Smaller blocks may reduce latency at the cost of increased CPU usage.
 pub const DEFAULT_PARTITION_SIZE: usize = 1024;
 
+/// An error that occurred while creating an [`ImpulseResponse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ImpulseResponseError {
+    /// The partition size was zero.
+    #[error("The impulse response partition size cannot be zero")]
+    ZeroPartitionSize,
+    /// The sample contained no frames.
+    #[error("The impulse response sample is empty")]
+    EmptySample,
+    /// A channel of the sample could not be read.
+    #[error("The impulse response sample is missing channel {0}")]
+    MissingChannel(usize),
+    /// The FFT convolver failed to prepare a channel of the sample.
+    #[error("Failed to prepare channel {0} of the impulse response")]
+    Fft(usize),
+}
+
+/// The maximum number of [`ConvolutionErrorEvent`]s that can be queued before
+/// they are read. If the queue is full, then new errors are dropped.
+pub const ERROR_EVENT_CAPACITY: usize = 16;
+
+/// An error that occurred while a [`ConvolutionNode`] was processing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ConvolutionError {
+    /// Convolving with the impulse response failed, so the node now passes
+    /// its input through until a new impulse response is set.
+    #[error("Failed to convolve with the impulse response")]
+    Convolve,
+    /// Convolving with the outgoing impulse response of a crossfade failed,
+    /// so the crossfade was ended early.
+    #[error("Failed to convolve with the outgoing impulse response of a crossfade")]
+    CrossfadeConvolve,
+}
+
+/// An error reported by a [`ConvolutionNode`]. Read these with
+/// [`ConvolutionNodeState::pop_error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConvolutionErrorEvent {
+    /// The ID of the node that reported the error.
+    pub node_id: NodeID,
+    /// The error that occurred.
+    pub error: ConvolutionError,
+}
+
 /// A processed impulse response sample.
 ///
 /// `ImpulseResponse`s are used in [`ConvolutionNode`]s.
-pub struct ImpulseResponse(Vec<FFTConvolver<f32>>);
+pub struct ImpulseResponse {
+    convolvers: Vec<FFTConvolver<f32>>,
+    /// The length of the longest channel in frames.
+    len_frames: usize,
+    /// The number of frames at the start of the impulse response where every
+    /// channel is below [`DEFAULT_AMP_EPSILON`].
+    predelay_frames: usize,
+}
 
 impl ImpulseResponse {
     /// Create a new `ImpulseResponse` with a custom partition size.
     ///
     /// Smaller blocks may reduce latency at the cost of increased CPU usage.
-    pub fn new_with_partition_size(sample: impl SampleResourceF32, partition_size: usize) -> Self {
+    pub fn new_with_partition_size(
+        sample: impl SampleResourceF32,
+        partition_size: usize,
+    ) -> Result<Self, ImpulseResponseError> {
+        Self::new_truncated(sample, partition_size, None)
+    }
+
+    /// Create a new `ImpulseResponse` with the partition size and maximum
+    /// length of the given node configuration.
+    ///
+    /// If the node is in true stereo mode, then the channels of a sample
+    /// with at least 4 channels are routed to the convolvers of their paths
+    /// according to [`ConvolutionNodeConfig::true_stereo_layout`].
+    ///
+    /// * `sample_rate` - The sample rate of `sample`, used to convert
+    /// [`ConvolutionNodeConfig::max_tail_ms`] to frames.
+    pub fn new_with_config<const CHANNELS: usize>(
+        sample: impl SampleResourceF32,
+        config: &ConvolutionNodeConfig<CHANNELS>,
+        sample_rate: NonZeroU32,
+    ) -> Result<Self, ImpulseResponseError> {
+        let truncation = config.max_tail_ms.map(|max_tail_ms| {
+            let frames_per_ms = sample_rate.get() as f32 / 1000.0;
+            let max_frames = ((max_tail_ms.max(0.0) * frames_per_ms).round() as usize).max(1);
+            let fade_frames = (TRUNCATION_FADE_MS * frames_per_ms).round() as usize;
+
+            (max_frames, fade_frames)
+        });
+
+        let mut impulse_response = Self::new_truncated(sample, config.partition_size, truncation)?;
+
+        if is_true_stereo(config) && impulse_response.num_channels() >= TRUE_STEREO_IR_CHANNELS {
+            impulse_response.route_true_stereo_channels(config.true_stereo_layout);
+        }
+
+        Ok(impulse_response)
+    }
+
+    /// Reorder the convolvers (which are in the order of the channels in the
+    /// file) into the order of the true stereo paths.
+    fn route_true_stereo_channels(&mut self, layout: TrueStereoLayout) {
+        if layout == TrueStereoLayout::ByInput {
+            return;
+        }
+
+        let mut convolvers: Vec<_> = self
+            .convolvers
+            .drain(..)
+            .enumerate()
+            .map(|(file_channel, conv)| (layout.convolver_index(file_channel), conv))
+            .collect();
+        convolvers.sort_by_key(|(index, _)| *index);
+
+        self.convolvers
+            .extend(convolvers.into_iter().map(|(_, conv)| conv));
+    }
+
+    /// Create a new `ImpulseResponse`, truncating each channel to
+    /// `truncation.0` frames with a fade-out over the last `truncation.1`
+    /// frames.
+    fn new_truncated(
+        sample: impl SampleResourceF32,
+        partition_size: usize,
+        truncation: Option<(usize, usize)>,
+    ) -> Result<Self, ImpulseResponseError> {
+        if partition_size == 0 {
+            return Err(ImpulseResponseError::ZeroPartitionSize);
+        }
+        if sample.len_frames() == 0 {
+            return Err(ImpulseResponseError::EmptySample);
+        }
+
         let num_channels = sample.num_channels().get();
-        Self(
-            (0..num_channels)
-                .map(|channel_index| {
-                    let mut conv = FFTConvolver::default();
-                    // The sample channel must exist, as our iterator is based
-                    // on its length. The FFT may error, depending on several
-                    // factors. Currently, this will result in a panic.
-                    conv.init(partition_size, sample.channel(channel_index).unwrap())
-                        .unwrap();
-                    conv
-                })
-                .collect(),
-        )
+        let mut len_frames = 0;
+        let mut predelay_frames = usize::MAX;
+        let convolvers = (0..num_channels)
+            .map(|channel_index| {
+                let channel = sample
+                    .channel(channel_index)
+                    .filter(|channel| !channel.is_empty())
+                    .ok_or(ImpulseResponseError::MissingChannel(channel_index))?;
+
+                let truncated;
+                let channel = match truncation {
+                    Some((max_frames, fade_frames)) if channel.len() > max_frames => {
+                        truncated = truncate_with_fade(channel, max_frames, fade_frames);
+                        &truncated[..]
+                    }
+                    _ => channel,
+                };
+
+                len_frames = len_frames.max(channel.len());
+                predelay_frames = predelay_frames.min(
+                    channel
+                        .iter()
+                        .position(|s| s.abs() > DEFAULT_AMP_EPSILON)
+                        .unwrap_or(channel.len()),
+                );
+
+                let mut conv = FFTConvolver::default();
+                conv.init(partition_size, channel)
+                    .map_err(|_| ImpulseResponseError::Fft(channel_index))?;
+                Ok(conv)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            convolvers,
+            len_frames,
+            predelay_frames,
+        })
     }
 
     /// Create a new `ImpulseResponse` with a default partition size of `1024`.
-    pub fn new(sample: impl SampleResourceF32) -> Self {
+    pub fn new(sample: impl SampleResourceF32) -> Result<Self, ImpulseResponseError> {
         Self::new_with_partition_size(sample, DEFAULT_PARTITION_SIZE)
     }
+
+    /// The number of channels in this impulse response.
+    pub fn num_channels(&self) -> usize {
+        self.convolvers.len()
+    }
+}
+
+/// Copy the first `max_frames` frames of `channel`, fading out the last
+/// `fade_frames` of them (at most half of the copied frames).
+fn truncate_with_fade(channel: &[f32], max_frames: usize, fade_frames: usize) -> Vec<f32> {
+    let mut truncated = channel[..max_frames].to_vec();
+
+    let fade_frames = fade_frames.min(max_frames / 2);
+    let fade_start = max_frames - fade_frames;
+    for (i, s) in truncated[fade_start..].iter_mut().enumerate() {
+        // Reach zero just after the last frame.
+        let progress = (i + 1) as f32 / (fade_frames + 1) as f32;
+        *s *= FadeCurve::EqualPower3dB.compute_gains_0_to_1(progress).0;
+    }
+
+    truncated
 }
 
 impl<const CHANNELS: usize> Default for ConvolutionNodeConfig<CHANNELS> {
@@ -121,7 +450,14 @@ impl<const CHANNELS: usize> Default for ConvolutionNodeConfig<CHANNELS> {
         Self {
             // A Convolution node with 0 `CHANNELS` is invalid and will panic.
             max_impulse_channel_count: ChannelCount::new(CHANNELS as u32).unwrap(),
+            true_stereo: false,
+            true_stereo_layout: TrueStereoLayout::ByInput,
             partition_size: DEFAULT_PARTITION_SIZE,
+            ir_crossfade_seconds: None,
+            ir_crossfade_curve: DeclickFadeCurve::EqualPower3dB,
+            wet_output: false,
+            fade_in_first_ir: true,
+            max_tail_ms: None,
         }
     }
 }
@@ -134,6 +470,7 @@ impl<const CHANNELS: usize> Default for ConvolutionNode<CHANNELS> {
             wet_gain: Volume::Decibels(-20.0),
             pause: false,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            gate: TailGateConfig::default(),
         }
     }
 }
@@ -141,7 +478,7 @@ impl<const CHANNELS: usize> Default for ConvolutionNode<CHANNELS> {
 impl<const CHANNELS: usize> AudioNode for ConvolutionNode<CHANNELS> {
     type Configuration = ConvolutionNodeConfig<CHANNELS>;
 
-    fn info(&self, _configuration: &Self::Configuration) -> AudioNodeInfo {
+    fn info(&self, configuration: &Self::Configuration) -> AudioNodeInfo {
         if CHANNELS > 2 {
             panic!(
                 "ConvolutionNode::CHANNELS cannot be greater than 2, got {}",
@@ -150,44 +487,208 @@ impl<const CHANNELS: usize> AudioNode for ConvolutionNode<CHANNELS> {
         }
         AudioNodeInfo::new()
             .debug_name("convolution")
-            .channel_config(ChannelConfig::new(CHANNELS, CHANNELS))
+            .channel_config(ChannelConfig::new(
+                CHANNELS,
+                if configuration.wet_output {
+                    CHANNELS * 2
+                } else {
+                    CHANNELS
+                },
+            ))
+            .custom_state(ConvolutionNodeState::new())
     }
 
     fn construct_processor(
         &self,
-        _configuration: &Self::Configuration,
+        configuration: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
         let sample_rate = cx.stream_info.sample_rate;
-        let smooth_config = SmootherConfig {
+        let smooth_config = cx.smoother_config(SmootherConfig {
             smooth_seconds: self.smooth_seconds,
             ..Default::default()
-        };
+        });
+        let (errors, error_consumer) = ringbuf::HeapRb::new(ERROR_EVENT_CAPACITY).split();
+        let state = cx.custom_state::<ConvolutionNodeState>().unwrap();
+        *state.errors.lock().unwrap() = Some(error_consumer);
+
         ConvolutionProcessor::<CHANNELS> {
+            node_id: cx.node_id,
             params: self.clone(),
-            mix: MixDSP::new(self.mix, self.fade_curve, smooth_config, sample_rate),
+            max_ir_channels: max_ir_channels::<CHANNELS>(configuration),
+            true_stereo: is_true_stereo::<CHANNELS>(configuration),
+            downmixed_from: ArcGc::clone(&state.downmixed_from),
+            errors,
+            mix: MixDSP::new(
+                self.mix,
+                self.fade_curve.clone(),
+                smooth_config,
+                sample_rate,
+            ),
             wet_gain_smoothed: SmoothedParam::new(self.wet_gain.amp(), smooth_config, sample_rate),
             declick: Declicker::default(),
+            ir_crossfade_seconds: configuration.ir_crossfade_seconds,
+            ir_crossfade_values: ir_crossfade_values(
+                configuration.ir_crossfade_seconds,
+                sample_rate,
+            ),
+            ir_crossfade_curve: configuration.ir_crossfade_curve,
+            fade_in_first_ir: configuration.fade_in_first_ir,
+            first_ir_fade: Declicker::SettledAt1,
             impulse_response: OwnedGc::new(None),
+            impulse_response_failed: false,
             next_impulse_response: OwnedGc::new(None),
+            outgoing_impulse_response: OwnedGc::new(None),
+            crossfade_frames: 0,
+            crossfade_frames_left: 0,
+            tail: TailState::default(),
+            gate: TailGate::new(self.gate, sample_rate),
         }
     }
 }
 
+/// Build the fade values used when changing impulse responses, or `None` to
+/// use the declick values of the audio graph.
+fn ir_crossfade_values(seconds: Option<f32>, sample_rate: NonZeroU32) -> Option<DeclickValues> {
+    seconds.map(|seconds| {
+        let frames = (seconds.max(0.0) * sample_rate.get() as f32).round() as u32;
+        DeclickValues::new(NonZeroU32::new(frames).unwrap_or(NonZeroU32::MIN))
+    })
+}
+
 pub enum ConvolutionNodeEvent {
     SetImpulseResponse(Option<ImpulseResponse>),
 }
 
+/// An event that changes the impulse response of a [`ConvolutionNode`] with
+/// an equal-power crossfade.
+///
+/// Sending a new impulse response directly fades the output out and back in.
+/// With this event, the old and new impulse responses are instead both
+/// convolved for the length of the crossfade, so there is no gap in the
+/// output. This is useful for seamless transitions between rooms.
+///
+/// Send this to the node with [`NodeEventType::custom`].
+///
+/// If the node has no impulse response yet, an impulse response change is
+/// already in progress, or the crossfade time is zero, then this behaves
+/// the same as sending the impulse response directly. If a crossfade is
+/// already in progress, then its outgoing impulse response is cut off.
+pub struct ImpulseResponseCrossfade {
+    impulse_response: Option<ImpulseResponse>,
+    crossfade_seconds: f32,
+}
+
+impl ImpulseResponseCrossfade {
+    /// Crossfade to `impulse_response` over `crossfade_seconds` seconds.
+    pub fn new(impulse_response: ImpulseResponse, crossfade_seconds: f32) -> Self {
+        Self {
+            impulse_response: Some(impulse_response),
+            crossfade_seconds,
+        }
+    }
+}
+
+/// The state of a [`ConvolutionNode`].
+#[derive(Clone)]
+pub struct ConvolutionNodeState {
+    downmixed_from: ArcGc<AtomicU32>,
+    errors: Arc<Mutex<Option<ringbuf::HeapCons<ConvolutionErrorEvent>>>>,
+}
+
+impl ConvolutionNodeState {
+    fn new() -> Self {
+        Self {
+            downmixed_from: ArcGc::new(AtomicU32::new(0)),
+            errors: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Pop the oldest error reported by the node, or `None` if there are no
+    /// more.
+    ///
+    /// The node never panics on the audio thread because of a bad impulse
+    /// response. Instead it bypasses the impulse response and reports an
+    /// error here.
+    pub fn pop_error(&self) -> Option<ConvolutionErrorEvent> {
+        self.errors.lock().unwrap().as_mut()?.try_pop()
+    }
+
+    /// If the last impulse response sent to the node had more channels than
+    /// [`ConvolutionNodeConfig::max_impulse_channel_count`], then this returns
+    /// the number of channels it had before it was downmixed.
+    ///
+    /// Returns `None` if the impulse response fit without downmixing.
+    pub fn downmixed_from(&self) -> Option<usize> {
+        match self.downmixed_from.load(Ordering::Relaxed) {
+            0 => None,
+            n => Some(n as usize),
+        }
+    }
+}
+
+/// Whether the node convolves with true stereo impulse responses.
+fn is_true_stereo<const CHANNELS: usize>(config: &ConvolutionNodeConfig<CHANNELS>) -> bool {
+    config.true_stereo && CHANNELS == 2
+}
+
+/// The number of impulse response channels the node will use.
+fn max_ir_channels<const CHANNELS: usize>(config: &ConvolutionNodeConfig<CHANNELS>) -> usize {
+    if is_true_stereo(config) {
+        return TRUE_STEREO_IR_CHANNELS;
+    }
+
+    (config.max_impulse_channel_count.get() as usize).clamp(1, CHANNELS.max(1))
+}
+
 struct ConvolutionProcessor<const CHANNELS: usize> {
+    node_id: NodeID,
     params: ConvolutionNode<CHANNELS>,
+    /// Impulse responses with more channels than this are downmixed.
+    max_ir_channels: usize,
+    /// Whether 4-channel impulse responses are convolved as true stereo.
+    true_stereo: bool,
+    downmixed_from: ArcGc<AtomicU32>,
+    errors: ringbuf::HeapProd<ConvolutionErrorEvent>,
     mix: MixDSP,
     wet_gain_smoothed: SmoothedParam,
     declick: Declicker,
+    ir_crossfade_seconds: Option<f32>,
+    ir_crossfade_values: Option<DeclickValues>,
+    ir_crossfade_curve: DeclickFadeCurve,
+    fade_in_first_ir: bool,
+    // Fades from the dry input to the full output after the first impulse
+    // response is loaded.
+    first_ir_fade: Declicker,
     impulse_response: OwnedGc<Option<ImpulseResponse>>,
+    // Set when convolving with `impulse_response` failed. The impulse response
+    // is bypassed, but it is kept in its slot until it is replaced so that it
+    // is not deallocated on the audio thread.
+    impulse_response_failed: bool,
     // We cannot be certain that the transition to a new impulse response will
     // happen within one block, so we must store the old impulse response until
     // the declicker settles.
     next_impulse_response: OwnedGc<Option<ImpulseResponse>>,
+    // The impulse response being faded out by an `ImpulseResponseCrossfade`.
+    // This is kept after the crossfade finishes so that it is never
+    // deallocated on the audio thread.
+    outgoing_impulse_response: OwnedGc<Option<ImpulseResponse>>,
+    crossfade_frames: usize,
+    crossfade_frames_left: usize,
+    tail: TailState,
+    gate: TailGate,
+}
+
+/// Tracks whether the tail of the convolution has decayed after the input
+/// went silent.
+#[derive(Default)]
+struct TailState {
+    /// The number of frames since the input went silent.
+    silent_input_frames: usize,
+    /// The number of consecutive blocks the output has been below
+    /// [`DEFAULT_AMP_EPSILON`] while the input was silent.
+    quiet_blocks: u32,
+    decayed: bool,
 }
 
 impl<const CHANNELS: usize> AudioNodeProcessor for ConvolutionProcessor<CHANNELS> {
@@ -202,68 +703,77 @@ impl<const CHANNELS: usize> AudioNodeProcessor for ConvolutionProcessor<CHANNELS
             match event {
                 NodeEventType::Param { data, path } => {
                     if let Ok(patch) = ConvolutionNode::<CHANNELS>::patch(&data, &path) {
+                        let mut gate_changed = false;
                         // You can match on the patch directly
                         match patch {
                             ConvolutionNodePatch::Mix(mix) => {
-                                self.mix.set_mix(mix, self.params.fade_curve);
+                                self.mix.set_mix(mix, self.params.fade_curve.clone());
                             }
-                            ConvolutionNodePatch::FadeCurve(curve) => {
-                                self.mix.set_mix(self.params.mix, curve);
+                            ConvolutionNodePatch::FadeCurve(ref curve) => {
+                                self.mix.set_mix(self.params.mix, curve.clone());
                             }
                             ConvolutionNodePatch::WetGain(gain) => {
                                 self.wet_gain_smoothed.set_value(gain.amp());
                             }
                             ConvolutionNodePatch::Pause(pause) => {
-                                self.declick.fade_to_enabled(!pause, &extra.declick_values);
+                                let declick_values = self
+                                    .ir_crossfade_values
+                                    .as_ref()
+                                    .unwrap_or(&extra.declick_values);
+                                self.declick.fade_to_enabled(!pause, declick_values);
                             }
                             ConvolutionNodePatch::SmoothSeconds(smooth_seconds) => {
-                                self.mix = MixDSP::new(
-                                    self.params.mix,
-                                    self.params.fade_curve,
-                                    SmootherConfig {
-                                        smooth_seconds,
-                                        ..Default::default()
-                                    },
-                                    info.sample_rate,
-                                );
+                                self.mix
+                                    .set_smooth_seconds(smooth_seconds, info.sample_rate);
                                 self.wet_gain_smoothed
                                     .set_smooth_seconds(smooth_seconds, info.sample_rate);
                             }
+                            ConvolutionNodePatch::Gate(_) => gate_changed = true,
                         }
                         self.params.apply(patch);
+
+                        if gate_changed {
+                            self.gate.set_config(self.params.gate, info.sample_rate);
+                        }
                     }
                 }
                 NodeEventType::Custom(_) => {
                     if event.downcast_into_owned(&mut self.next_impulse_response) {
-                        // Disable the audio stream while changing IRs
-                        self.declick.fade_to_0(&extra.declick_values);
+                        self.begin_ir_change(&extra.declick_values);
+                    } else if let Some(crossfade) = event.downcast_mut::<ImpulseResponseCrossfade>()
+                    {
+                        self.begin_ir_crossfade(crossfade, info.sample_rate, &extra.declick_values);
                     }
                 }
                 _ => (),
             }
         }
+        self.mix.set_global_wet(info.global_wet);
 
-        // Check to see if there is a new IR waiting. If there is, and the audio
-        // has stopped, swap the IR, and continue
-        if self.next_impulse_response.is_some() && self.declick == Declicker::SettledAt0 {
-            // The next impulse result must exist due to the check in this block
-            let next_impulse_response = self.next_impulse_response.take().unwrap();
-            self.impulse_response.replace(next_impulse_response);
-            // Don't unpause if we're paused manually
-            if !self.params.pause {
-                self.declick.fade_to_1(&extra.declick_values);
-            }
+        if self.swap_pending_ir(&extra.declick_values) {
             // Begin mixing back in with the new impulse response next block
             return ProcessStatus::ClearAllOutputs;
         }
 
+        let inputs_silent = info.in_silence_mask.all_channels_silent(CHANNELS);
+        if inputs_silent && self.tail.decayed {
+            // The tail has fully decayed, so the output stays silent until
+            // new input arrives.
+            self.wet_gain_smoothed.reset_to_target();
+            self.mix.reset_to_target();
+            self.first_ir_fade.reset_to_target();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
         // Only process if an impulse response is supplied
-        if self.impulse_response.is_some() {
-            const WET_GAIN_BUFFER: usize = 0;
-            let wet_gain_buffer = &mut extra.scratch_buffers.channels_mut::<1>()[WET_GAIN_BUFFER];
+        if self.has_impulse_response() {
+            let [wet_gain_buffer, downmix_buffer, outgoing_0, outgoing_1] =
+                extra.scratch_buffers.channels_mut::<4>();
 
             // Amount to scale based on wet signal gain
-            self.wet_gain_smoothed.process_into_buffer(wet_gain_buffer);
+            self.wet_gain_smoothed
+                .process_into_buffer(&mut wet_gain_buffer[..info.frames]);
 
             // If paused, return early after processing wet gain buffers to
             // avoid clicking
@@ -271,73 +781,554 @@ impl<const CHANNELS: usize> AudioNodeProcessor for ConvolutionProcessor<CHANNELS
                 return ProcessStatus::ClearAllOutputs;
             }
 
-            for (input_index, input) in buffers.inputs.iter().enumerate() {
-                // We unfortunately can't add more buffers to the convolution
-                // struct, as we don't own it. This means we can't do stereo
-                // with a mono impulse response. In this case, we'll just pass
-                // the input through if we can't get a channel.
-
-                // We already checked that the impulse response must exist, so
-                // we can safely unwrap.
-                if let Some(conv) = self
-                    .impulse_response
-                    .get_mut()
-                    .as_mut()
-                    .unwrap()
-                    .0
-                    .get_mut(input_index)
-                {
-                    conv.process(input, buffers.outputs[input_index]).unwrap();
-
-                    // Apply wet signal gain
-                    for (output_sample, gain) in buffers.outputs[input_index]
-                        .iter_mut()
-                        .zip(wet_gain_buffer.iter())
-                    {
-                        *output_sample *= gain;
-                    }
-                }
-            }
-        }
-
-        if self.impulse_response.is_some() {
-            match CHANNELS {
-                1 => {
-                    self.mix.mix_dry_into_wet_mono(
-                        buffers.inputs[0],
-                        buffers.outputs[0],
-                        info.frames,
+            if let Err(e) = self.convolve(
+                buffers.inputs,
+                &mut buffers.outputs[..CHANNELS],
+                &wet_gain_buffer[..info.frames],
+                &mut downmix_buffer[..info.frames],
+            ) {
+                // A malformed impulse response must never take down the audio
+                // stream, so bypass it and fall back to passing the input through.
+                let node_id = self.node_id;
+                let _ = extra.logger.try_error_with(|s| {
+                    let _ = write!(
+                        s,
+                        "ConvolutionNode {:?} bypassed after an error: {}",
+                        node_id, e
                     );
-                }
-                2 => {
-                    let (left, right) = buffers.outputs.split_at_mut(1);
-                    self.mix.mix_dry_into_wet_stereo(
-                        buffers.inputs[0],
-                        buffers.inputs[1],
-                        left[0],
-                        right[0],
-                        info.frames,
+                });
+                self.bypass_after_error();
+            } else if let Err(e) = self.crossfade_outgoing(
+                buffers.inputs,
+                &mut buffers.outputs[..CHANNELS],
+                &wet_gain_buffer[..info.frames],
+                &mut downmix_buffer[..info.frames],
+                [
+                    &mut outgoing_0[..info.frames],
+                    &mut outgoing_1[..info.frames],
+                ],
+            ) {
+                let node_id = self.node_id;
+                let _ = extra.logger.try_error_with(|s| {
+                    let _ = write!(
+                        s,
+                        "ConvolutionNode {:?} ended a crossfade early after an error: {}",
+                        node_id, e
                     );
-                }
-                _ => panic!("Only Mono and Stereo are supported"),
+                });
+                self.report_error(ConvolutionError::CrossfadeConvolve);
             }
+        }
+
+        if self.has_impulse_response() {
+            self.mix_dry(buffers.inputs, buffers.outputs, info.frames);
+            self.fade_in_wet(
+                buffers.inputs,
+                buffers.outputs,
+                info.frames,
+                &extra.declick_values,
+            );
         } else {
             // Pass through audio if no impulse provided
-            for (input, output) in buffers.inputs.iter().zip(buffers.outputs.iter_mut()) {
+            let (outputs, wet_outputs) = buffers.outputs.split_at_mut(CHANNELS);
+            for (input, output) in buffers.inputs.iter().zip(outputs.iter_mut()) {
                 output.copy_from_slice(input);
             }
+            for output in wet_outputs.iter_mut() {
+                output.fill(0.0);
+            }
         }
 
         self.declick.process(
             buffers.outputs,
             0..info.frames,
-            &extra.declick_values,
+            self.ir_crossfade_values
+                .as_ref()
+                .unwrap_or(&extra.declick_values),
             1.0,
-            DeclickFadeCurve::EqualPower3dB,
+            self.ir_crossfade_curve,
         );
 
+        self.update_tail(inputs_silent, buffers.outputs, info.frames);
+
         buffers.check_for_silence_on_outputs(f32::EPSILON)
     }
+
+    fn new_stream(
+        &mut self,
+        stream_info: &firewheel_core::StreamInfo,
+        _context: &mut firewheel_core::node::ProcStreamCtx,
+    ) {
+        self.ir_crossfade_values =
+            ir_crossfade_values(self.ir_crossfade_seconds, stream_info.sample_rate);
+        self.gate
+            .set_config(self.params.gate, stream_info.sample_rate);
+    }
+}
+
+impl<const CHANNELS: usize> ConvolutionProcessor<CHANNELS> {
+    /// Whether there is an impulse response to convolve with.
+    fn has_impulse_response(&self) -> bool {
+        self.impulse_response.is_some() && !self.impulse_response_failed
+    }
+
+    /// Bypass the current impulse response after convolving with it failed,
+    /// and report the error to the main thread.
+    fn bypass_after_error(&mut self) {
+        self.impulse_response_failed = true;
+        self.crossfade_frames_left = 0;
+        self.report_error(ConvolutionError::Convolve);
+    }
+
+    /// Send an error event to the [`ConvolutionNodeState`].
+    fn report_error(&mut self, error: ConvolutionError) {
+        // If the queue is full then the error is dropped.
+        let _ = self.errors.try_push(ConvolutionErrorEvent {
+            node_id: self.node_id,
+            error,
+        });
+    }
+
+    /// Start fading out the output to change to the impulse response stored
+    /// in `next_impulse_response`.
+    fn begin_ir_change(&mut self, graph_declick_values: &DeclickValues) {
+        let num_channels = self
+            .next_impulse_response
+            .as_ref()
+            .map(|ir| ir.num_channels())
+            .unwrap_or(0);
+
+        self.report_downmix(num_channels);
+
+        if self.fade_in_first_ir
+            && num_channels > 0
+            && self.impulse_response.is_none()
+            && self.declick == Declicker::SettledAt1
+        {
+            // There is no wet signal to fade out, so use the new IR right
+            // away and only fade in the wet signal.
+            let next_impulse_response = self.next_impulse_response.take().unwrap();
+            self.impulse_response.replace(next_impulse_response);
+
+            self.first_ir_fade = Declicker::SettledAt0;
+            self.first_ir_fade.fade_to_1(
+                self.ir_crossfade_values
+                    .as_ref()
+                    .unwrap_or(graph_declick_values),
+            );
+            return;
+        }
+
+        // Disable the audio stream while changing IRs
+        self.declick.fade_to_0(
+            self.ir_crossfade_values
+                .as_ref()
+                .unwrap_or(graph_declick_values),
+        );
+    }
+
+    /// Start crossfading from the current IR to the IR in `crossfade`.
+    fn begin_ir_crossfade(
+        &mut self,
+        crossfade: &mut ImpulseResponseCrossfade,
+        sample_rate: NonZeroU32,
+        graph_declick_values: &DeclickValues,
+    ) {
+        let Some(num_channels) = crossfade
+            .impulse_response
+            .as_ref()
+            .map(|ir| ir.num_channels())
+        else {
+            return;
+        };
+
+        let frames =
+            (crossfade.crossfade_seconds.max(0.0) * sample_rate.get() as f32).round() as usize;
+
+        if !self.has_impulse_response() || self.next_impulse_response.is_some() || frames == 0 {
+            // There is nothing to crossfade from, so fade out and back in.
+            self.next_impulse_response
+                .swap(&mut crossfade.impulse_response);
+            self.begin_ir_change(graph_declick_values);
+            return;
+        }
+
+        self.report_downmix(num_channels);
+
+        // The current IR becomes the outgoing one, and the previous outgoing
+        // IR is moved into the event so that it is dropped with the event.
+        core::mem::swap(
+            self.impulse_response.get_mut(),
+            self.outgoing_impulse_response.get_mut(),
+        );
+        self.impulse_response.swap(&mut crossfade.impulse_response);
+
+        self.crossfade_frames = frames;
+        self.crossfade_frames_left = frames;
+    }
+
+    /// Store the number of channels of an IR that was downmixed in the shared
+    /// state.
+    fn report_downmix(&self, num_channels: usize) {
+        // Extra channels are folded into the supported ones
+        // when convolving rather than being dropped.
+        self.downmixed_from.store(
+            if num_channels > self.max_ir_channels {
+                num_channels as u32
+            } else {
+                0
+            },
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Check to see if there is a new IR waiting. If there is, and the audio
+    /// has faded out, swap the IR and start fading back in.
+    ///
+    /// Returns `true` if the IR was swapped.
+    fn swap_pending_ir(&mut self, graph_declick_values: &DeclickValues) -> bool {
+        if self.next_impulse_response.is_none() || self.declick != Declicker::SettledAt0 {
+            return false;
+        }
+
+        // The next impulse result must exist due to the check above
+        let next_impulse_response = self.next_impulse_response.take().unwrap();
+        self.impulse_response.replace(next_impulse_response);
+        self.impulse_response_failed = false;
+        // Don't unpause if we're paused manually
+        if !self.params.pause {
+            self.declick.fade_to_1(
+                self.ir_crossfade_values
+                    .as_ref()
+                    .unwrap_or(graph_declick_values),
+            );
+        }
+
+        true
+    }
+
+    /// Update whether the tail has decayed after processing a block.
+    ///
+    /// The tail has decayed once the input has been silent for the length of
+    /// the impulse response, or once the output has stayed below
+    /// [`DEFAULT_AMP_EPSILON`] for [`TAIL_QUIET_BLOCKS`] blocks after the
+    /// pre-delay of the impulse response.
+    fn update_tail(&mut self, inputs_silent: bool, outputs: &[&mut [f32]], frames: usize) {
+        if !inputs_silent {
+            self.tail = TailState::default();
+            return;
+        }
+
+        let peak = outputs
+            .iter()
+            .map(|ch| max_peak(&ch[..frames]))
+            .fold(0.0, f32::max);
+
+        self.tail.silent_input_frames = self.tail.silent_input_frames.saturating_add(frames);
+        self.tail.quiet_blocks = if peak > DEFAULT_AMP_EPSILON {
+            0
+        } else {
+            self.tail.quiet_blocks.saturating_add(1)
+        };
+
+        let (len_frames, predelay_frames) = self
+            .impulse_response
+            .as_ref()
+            .filter(|_| !self.impulse_response_failed)
+            .map(|ir| (ir.len_frames, ir.predelay_frames))
+            .unwrap_or((0, 0));
+
+        self.tail.decayed = self.tail.silent_input_frames >= len_frames
+            || (self.tail.quiet_blocks >= TAIL_QUIET_BLOCKS
+                && self.tail.silent_input_frames > predelay_frames);
+    }
+
+    /// Gate the wet signal in the first `CHANNELS` outputs, copy it to the
+    /// wet-only outputs (if any), and then mix the dry signal into it.
+    fn mix_dry(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        let (outputs, wet_outputs) = outputs.split_at_mut(CHANNELS);
+
+        self.gate.process_block(inputs, outputs, frames);
+
+        for (wet, output) in wet_outputs.iter_mut().zip(outputs.iter()) {
+            wet[..frames].copy_from_slice(&output[..frames]);
+        }
+
+        match CHANNELS {
+            1 => {
+                self.mix
+                    .mix_dry_into_wet_mono(inputs[0], outputs[0], frames);
+            }
+            2 => {
+                let (left, right) = outputs.split_at_mut(1);
+                self.mix
+                    .mix_dry_into_wet_stereo(inputs[0], inputs[1], left[0], right[0], frames);
+            }
+            _ => panic!("Only Mono and Stereo are supported"),
+        }
+    }
+
+    /// If the first impulse response was just loaded, crossfade from the dry
+    /// input to the mixed output, and fade the wet-only outputs in from
+    /// silence.
+    fn fade_in_wet(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        graph_declick_values: &DeclickValues,
+    ) {
+        if self.first_ir_fade.has_settled() {
+            return;
+        }
+
+        let declick_values = self
+            .ir_crossfade_values
+            .as_ref()
+            .unwrap_or(graph_declick_values);
+        let (outputs, wet_outputs) = outputs.split_at_mut(CHANNELS);
+
+        // Both fades must advance by the same amount, so fade the wet
+        // outputs with a copy of the declicker.
+        let mut wet_fade = self.first_ir_fade;
+        wet_fade.process(
+            wet_outputs,
+            0..frames,
+            declick_values,
+            1.0,
+            self.ir_crossfade_curve,
+        );
+
+        self.first_ir_fade.process_crossfade(
+            inputs,
+            outputs,
+            frames,
+            declick_values,
+            self.ir_crossfade_curve,
+        );
+    }
+
+    /// Convolve each input channel with the current impulse response and apply
+    /// the wet gain.
+    ///
+    /// If the node is in true stereo mode, see [`convolve_true_stereo`]
+    /// instead.
+    ///
+    /// Impulse response channel `i` is applied to channel
+    /// `i % max_ir_channels`, and the results are averaged, so extra channels
+    /// are downmixed. Channels without a matching impulse response channel are
+    /// passed through unchanged. If the convolver fails, every output is set
+    /// to the input so the caller can safely bypass.
+    fn convolve(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        wet_gain: &[f32],
+        downmix_buffer: &mut [f32],
+    ) -> Result<(), FFTConvolverProcessError> {
+        let Some(impulse_response) = self.impulse_response.get_mut().as_mut() else {
+            for (input, output) in inputs.iter().zip(outputs.iter_mut()) {
+                output.copy_from_slice(input);
+            }
+            return Ok(());
+        };
+
+        if self.true_stereo {
+            convolve_true_stereo(impulse_response, inputs, outputs, wet_gain, downmix_buffer)
+        } else {
+            convolve_with(
+                impulse_response,
+                self.max_ir_channels,
+                inputs,
+                outputs,
+                wet_gain,
+                downmix_buffer,
+            )
+        }
+    }
+
+    /// If a crossfade is in progress, convolve the inputs with the outgoing
+    /// IR and crossfade it with the response of the current IR in `outputs`.
+    ///
+    /// If the convolver fails, the crossfade ends immediately.
+    fn crossfade_outgoing(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        wet_gain: &[f32],
+        downmix_buffer: &mut [f32],
+        outgoing: [&mut [f32]; 2],
+    ) -> Result<(), FFTConvolverProcessError> {
+        if self.crossfade_frames_left == 0 {
+            return Ok(());
+        }
+        let Some(outgoing_ir) = self.outgoing_impulse_response.get_mut().as_mut() else {
+            self.crossfade_frames_left = 0;
+            return Ok(());
+        };
+
+        let num_channels = outputs.len().min(2);
+        let mut outgoing = outgoing;
+        let result = if self.true_stereo {
+            convolve_true_stereo(
+                outgoing_ir,
+                inputs,
+                &mut outgoing[..num_channels],
+                wet_gain,
+                downmix_buffer,
+            )
+        } else {
+            convolve_with(
+                outgoing_ir,
+                self.max_ir_channels,
+                inputs,
+                &mut outgoing[..num_channels],
+                wet_gain,
+                downmix_buffer,
+            )
+        };
+        if let Err(e) = result {
+            self.crossfade_frames_left = 0;
+            return Err(e);
+        }
+
+        let frames = wet_gain.len();
+        for i in 0..frames.min(self.crossfade_frames_left) {
+            let progress =
+                1.0 - (self.crossfade_frames_left - i) as f32 / self.crossfade_frames as f32;
+            let (gain_out, gain_in) = FadeCurve::EqualPower3dB.compute_gains_0_to_1(progress);
+
+            for (output, outgoing) in outputs.iter_mut().zip(outgoing.iter()) {
+                output[i] = output[i] * gain_in + outgoing[i] * gain_out;
+            }
+        }
+
+        self.crossfade_frames_left = self.crossfade_frames_left.saturating_sub(frames);
+
+        Ok(())
+    }
+}
+
+/// Convolve each input channel with `impulse_response` and apply the wet
+/// gain. See [`ConvolutionProcessor::convolve`].
+fn convolve_with(
+    impulse_response: &mut ImpulseResponse,
+    max_ir_channels: usize,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    wet_gain: &[f32],
+    downmix_buffer: &mut [f32],
+) -> Result<(), FFTConvolverProcessError> {
+    for (input_index, (input, output)) in inputs.iter().zip(outputs.iter_mut()).enumerate() {
+        // We unfortunately can't add more buffers to the convolution
+        // struct, as we don't own it. This means we can't do stereo
+        // with a mono impulse response. In this case, we'll just pass
+        // the input through if we can't get a channel.
+        if input_index >= max_ir_channels || input_index >= impulse_response.convolvers.len() {
+            output.copy_from_slice(input);
+            continue;
+        }
+
+        let mut num_folded = 0;
+        let mut result = Ok(());
+        for conv in impulse_response
+            .convolvers
+            .iter_mut()
+            .skip(input_index)
+            .step_by(max_ir_channels)
+        {
+            if num_folded == 0 {
+                result = conv.process(input, output);
+            } else {
+                result = conv.process(input, downmix_buffer);
+                for (os, &s) in output.iter_mut().zip(downmix_buffer.iter()) {
+                    *os += s;
+                }
+            }
+
+            if result.is_err() {
+                break;
+            }
+            num_folded += 1;
+        }
+
+        if let Err(e) = result {
+            for (input, output) in inputs.iter().zip(outputs.iter_mut()) {
+                output.copy_from_slice(input);
+            }
+            return Err(e);
+        }
+
+        // Apply wet signal gain, averaging any downmixed channels.
+        let norm = (num_folded as f32).recip();
+        for (output_sample, gain) in output.iter_mut().zip(wet_gain.iter()) {
+            *output_sample *= gain * norm;
+        }
+    }
+
+    Ok(())
+}
+
+/// Convolve a stereo input with a true stereo `impulse_response` (see
+/// [`ConvolutionNodeConfig::true_stereo`]) and apply the wet gain.
+///
+/// Convolver `i` is the path from input `(i % 4) / 2` to output `i % 2` (see
+/// [`TrueStereoLayout`]). The paths into each output are summed, and any extra
+/// channels are averaged into their path. Impulse responses with fewer than
+/// 4 channels fall back to [`convolve_with`].
+fn convolve_true_stereo(
+    impulse_response: &mut ImpulseResponse,
+    inputs: &[&[f32]],
+    outputs: &mut [&mut [f32]],
+    wet_gain: &[f32],
+    path_buffer: &mut [f32],
+) -> Result<(), FFTConvolverProcessError> {
+    if impulse_response.convolvers.len() < TRUE_STEREO_IR_CHANNELS
+        || inputs.len() < 2
+        || outputs.len() < 2
+    {
+        return convolve_with(
+            impulse_response,
+            TRUE_STEREO_IR_CHANNELS.min(inputs.len()),
+            inputs,
+            outputs,
+            wet_gain,
+            path_buffer,
+        );
+    }
+
+    for output in outputs.iter_mut() {
+        output.fill(0.0);
+    }
+
+    let mut num_paths = [0; 2];
+    for (ir_channel, conv) in impulse_response.convolvers.iter_mut().enumerate() {
+        let path = ir_channel % TRUE_STEREO_IR_CHANNELS;
+        let (input_index, output_index) = (path / 2, path % 2);
+
+        if let Err(e) = conv.process(inputs[input_index], path_buffer) {
+            for (input, output) in inputs.iter().zip(outputs.iter_mut()) {
+                output.copy_from_slice(input);
+            }
+            return Err(e);
+        }
+
+        for (os, &s) in outputs[output_index].iter_mut().zip(path_buffer.iter()) {
+            *os += s;
+        }
+        num_paths[output_index] += 1;
+    }
+
+    // Apply wet signal gain. Each output sums two paths, and any further
+    // paths are downmixed.
+    for (output, num_paths) in outputs.iter_mut().zip(num_paths) {
+        let norm = 2.0 / num_paths as f32;
+        for (output_sample, gain) in output.iter_mut().zip(wet_gain.iter()) {
+            *output_sample *= gain * norm;
+        }
+    }
+
+    Ok(())
 }
 
 #[cfg(test)]
@@ -357,4 +1348,612 @@ mod tests {
     fn fail_above_stereo() {
         ConvolutionNode::<3>::default().info(&ConvolutionNodeConfig::default());
     }
+
+    fn processor<const CHANNELS: usize>(
+        impulse_response: Option<ImpulseResponse>,
+    ) -> ConvolutionProcessor<CHANNELS> {
+        processor_with_config(impulse_response, &ConvolutionNodeConfig::default())
+    }
+
+    fn processor_with_config<const CHANNELS: usize>(
+        impulse_response: Option<ImpulseResponse>,
+        config: &ConvolutionNodeConfig<CHANNELS>,
+    ) -> ConvolutionProcessor<CHANNELS> {
+        processor_and_state(impulse_response, config).0
+    }
+
+    fn processor_and_state<const CHANNELS: usize>(
+        impulse_response: Option<ImpulseResponse>,
+        config: &ConvolutionNodeConfig<CHANNELS>,
+    ) -> (ConvolutionProcessor<CHANNELS>, ConvolutionNodeState) {
+        let params = ConvolutionNode::<CHANNELS>::default();
+        let sample_rate = NonZeroU32::new(44100).unwrap();
+        let state = ConvolutionNodeState::new();
+        let (errors, error_consumer) = ringbuf::HeapRb::new(ERROR_EVENT_CAPACITY).split();
+        *state.errors.lock().unwrap() = Some(error_consumer);
+
+        let processor = ConvolutionProcessor {
+            node_id: NodeID::DANGLING,
+            params: params.clone(),
+            max_ir_channels: max_ir_channels(config),
+            true_stereo: is_true_stereo(config),
+            downmixed_from: ArcGc::clone(&state.downmixed_from),
+            errors,
+            mix: MixDSP::new(
+                params.mix,
+                params.fade_curve,
+                SmootherConfig::default(),
+                sample_rate,
+            ),
+            wet_gain_smoothed: SmoothedParam::new(1.0, SmootherConfig::default(), sample_rate),
+            declick: Declicker::default(),
+            ir_crossfade_seconds: config.ir_crossfade_seconds,
+            ir_crossfade_values: ir_crossfade_values(config.ir_crossfade_seconds, sample_rate),
+            ir_crossfade_curve: config.ir_crossfade_curve,
+            fade_in_first_ir: config.fade_in_first_ir,
+            first_ir_fade: Declicker::SettledAt1,
+            impulse_response: OwnedGc::new(impulse_response),
+            impulse_response_failed: false,
+            next_impulse_response: OwnedGc::new(None),
+            outgoing_impulse_response: OwnedGc::new(None),
+            crossfade_frames: 0,
+            crossfade_frames_left: 0,
+            tail: TailState::default(),
+            gate: TailGate::new(params.gate, sample_rate),
+        };
+
+        (processor, state)
+    }
+
+    // Malformed impulse responses are rejected instead of panicking
+    #[test]
+    fn invalid_impulse_response() {
+        assert_eq!(
+            ImpulseResponse::new(vec![Vec::<f32>::new()]).err(),
+            Some(ImpulseResponseError::EmptySample)
+        );
+        assert_eq!(
+            ImpulseResponse::new_with_partition_size(vec![vec![1.0]], 0).err(),
+            Some(ImpulseResponseError::ZeroPartitionSize)
+        );
+        assert_eq!(
+            ImpulseResponse::new(vec![vec![1.0, 0.5], vec![]]).err(),
+            Some(ImpulseResponseError::MissingChannel(1))
+        );
+    }
+
+    // A long impulse response is truncated to the configured length, and the
+    // end of the truncated tail is faded out
+    #[test]
+    fn long_impulse_response_is_truncated() {
+        const BLOCK_FRAMES: usize = 256;
+
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let config = ConvolutionNodeConfig::<1> {
+            partition_size: BLOCK_FRAMES,
+            max_tail_ms: Some(100.0),
+            ..Default::default()
+        };
+        let max_frames = 4_800;
+        let fade_frames = 480;
+
+        let mut ir =
+            ImpulseResponse::new_with_config(vec![vec![1.0; 48_000]], &config, sample_rate)
+                .unwrap();
+        assert_eq!(ir.len_frames, max_frames);
+
+        // Convolving a unit impulse reads back the impulse response.
+        let mut response = Vec::new();
+        for block in 0..24 {
+            let mut input = [0.0; BLOCK_FRAMES];
+            if block == 0 {
+                input[0] = 1.0;
+            }
+            let mut output = [0.0; BLOCK_FRAMES];
+            ir.convolvers[0].process(&input, &mut output).unwrap();
+            response.extend_from_slice(&output);
+        }
+
+        let fade_start = max_frames - fade_frames;
+        assert!(response[..fade_start]
+            .iter()
+            .all(|s| (s - 1.0).abs() < 1e-4));
+        assert!(response[fade_start..max_frames]
+            .windows(2)
+            .all(|w| w[1] <= w[0] + 1e-4));
+        assert!(response[max_frames - 1].abs() < 0.01);
+        assert!(response[max_frames..].iter().all(|s| s.abs() < 1e-4));
+
+        // Shorter impulse responses are left as is.
+        let ir =
+            ImpulseResponse::new_with_config(vec![vec![1.0; 1_000]], &config, sample_rate).unwrap();
+        assert_eq!(ir.len_frames, 1_000);
+    }
+
+    // A stereo node with a mono impulse response passes the unmatched channel
+    // through instead of leaving junk in the output
+    #[test]
+    fn mismatched_channels_bypass() {
+        let ir = ImpulseResponse::new_with_partition_size(vec![vec![0.5]], 16).unwrap();
+        let mut processor = processor::<2>(Some(ir));
+
+        let left_in = [1.0, 0.0, 0.0, 0.0];
+        let right_in = [0.25, -0.25, 0.5, -0.5];
+        let mut left_out = [f32::NAN; 4];
+        let mut right_out = [f32::NAN; 4];
+        let wet_gain = [1.0; 4];
+
+        processor
+            .convolve(
+                &[&left_in, &right_in],
+                &mut [&mut left_out, &mut right_out],
+                &wet_gain,
+                &mut [0.0; 4],
+            )
+            .unwrap();
+
+        assert!((left_out[0] - 0.5).abs() < 1e-6);
+        assert_eq!(right_out, right_in);
+    }
+
+    // A failed convolution bypasses the impulse response without dropping it
+    // on the audio thread, and reports an error event for the node
+    #[test]
+    fn convolution_error_bypasses_and_reports_event() {
+        let ir = ImpulseResponse::new_with_partition_size(vec![vec![0.5]], 16).unwrap();
+        let (mut processor, state) =
+            processor_and_state::<1>(Some(ir), &ConvolutionNodeConfig::default());
+
+        assert!(processor.has_impulse_response());
+        assert_eq!(state.pop_error(), None);
+
+        processor.bypass_after_error();
+
+        assert!(!processor.has_impulse_response());
+        // The impulse response stays in its garbage collected slot.
+        assert!(processor.impulse_response.is_some());
+        assert_eq!(
+            state.pop_error(),
+            Some(ConvolutionErrorEvent {
+                node_id: processor.node_id,
+                error: ConvolutionError::Convolve,
+            })
+        );
+        assert_eq!(state.pop_error(), None);
+
+        // The tail no longer waits for the bypassed impulse response.
+        processor.update_tail(true, &[&mut [0.0; 4]], 4);
+        assert!(processor.tail.decayed);
+
+        // A new impulse response clears the bypass.
+        processor
+            .next_impulse_response
+            .replace(ImpulseResponse::new_with_partition_size(vec![vec![0.5]], 16).unwrap());
+        processor.declick = Declicker::SettledAt0;
+        assert!(processor.swap_pending_ir(&DeclickValues::new(NonZeroU32::new(16).unwrap())));
+        assert!(processor.has_impulse_response());
+    }
+
+    // A 4-channel impulse response in a stereo node has its extra channels
+    // downmixed into the stereo pair instead of being ignored
+    #[test]
+    fn extra_ir_channels_are_downmixed() {
+        let ir = ImpulseResponse::new_with_partition_size(
+            vec![vec![1.0], vec![0.25], vec![0.5], vec![0.75]],
+            16,
+        )
+        .unwrap();
+        let mut processor = processor::<2>(Some(ir));
+
+        let input = [1.0, 0.0, 0.0, 0.0];
+        let mut left_out = [f32::NAN; 4];
+        let mut right_out = [f32::NAN; 4];
+        let wet_gain = [1.0; 4];
+
+        processor
+            .convolve(
+                &[&input, &input],
+                &mut [&mut left_out, &mut right_out],
+                &wet_gain,
+                &mut [0.0; 4],
+            )
+            .unwrap();
+
+        // Channels 0 and 2 are averaged into the left output, and channels 1
+        // and 3 into the right.
+        assert!((left_out[0] - 0.75).abs() < 1e-6);
+        assert!((right_out[0] - 0.5).abs() < 1e-6);
+    }
+
+    // Loading the first IR fades the wet signal in instead of it appearing
+    // instantly, without fading out the dry signal
+    #[test]
+    fn first_ir_fades_in_wet() {
+        const BLOCK_FRAMES: usize = 64;
+
+        let graph_declick_values = DeclickValues::new(NonZeroU32::new(256).unwrap());
+        let mut processor = processor_with_config::<1>(
+            None,
+            &ConvolutionNodeConfig {
+                wet_output: true,
+                ..Default::default()
+            },
+        );
+
+        processor
+            .next_impulse_response
+            .replace(ImpulseResponse::new_with_partition_size(vec![vec![0.5]], 16).unwrap());
+        processor.begin_ir_change(&graph_declick_values);
+
+        // The IR is used right away, and the dry signal keeps playing.
+        assert!(processor.impulse_response.is_some());
+        assert_eq!(processor.declick, Declicker::SettledAt1);
+
+        let input = [1.0; BLOCK_FRAMES];
+        let mut output = Vec::new();
+        let mut wet_output = Vec::new();
+        for _ in 0..8 {
+            let mut out = [0.0; BLOCK_FRAMES];
+            let mut wet = [0.0; BLOCK_FRAMES];
+            let mut outputs = [&mut out[..], &mut wet[..]];
+
+            processor
+                .convolve(
+                    &[&input],
+                    &mut outputs[..1],
+                    &[1.0; BLOCK_FRAMES],
+                    &mut [0.0; BLOCK_FRAMES],
+                )
+                .unwrap();
+            processor.mix_dry(&[&input], &mut outputs, BLOCK_FRAMES);
+            processor.fade_in_wet(&[&input], &mut outputs, BLOCK_FRAMES, &graph_declick_values);
+
+            output.extend_from_slice(&out);
+            wet_output.extend_from_slice(&wet);
+        }
+
+        assert!(processor.first_ir_fade.has_settled());
+
+        // The output starts out as the dry input, and the wet signal starts
+        // from silence.
+        assert!((output[0] - 1.0).abs() < 0.01, "{}", output[0]);
+        assert!(wet_output[0].abs() < 0.01, "{}", wet_output[0]);
+
+        // The wet signal rises smoothly to its full level.
+        let max_step = wet_output
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .fold(0.0f32, f32::max);
+        assert!(max_step < 0.02, "{max_step}");
+        assert!(wet_output.windows(2).all(|w| w[1] >= w[0] - 1e-6));
+        assert!((wet_output.last().unwrap() - 0.5).abs() < 1e-5);
+    }
+
+    // A shorter configured crossfade settles faster after an IR change
+    #[test]
+    fn shorter_ir_crossfade_settles_faster() {
+        // The default declick time of the audio graph (10ms at 44.1kHz).
+        let graph_declick_values = DeclickValues::new(NonZeroU32::new(441).unwrap());
+        let ir = || ImpulseResponse::new_with_partition_size(vec![vec![0.5]], 16).unwrap();
+
+        // The number of frames it takes to fade out, swap the IR, and fade
+        // back in.
+        let frames_to_settle = |ir_crossfade_seconds: Option<f32>| {
+            let mut processor = processor_with_config::<1>(
+                Some(ir()),
+                &ConvolutionNodeConfig {
+                    ir_crossfade_seconds,
+                    ..Default::default()
+                },
+            );
+
+            processor.next_impulse_response.replace(ir());
+            processor.begin_ir_change(&graph_declick_values);
+
+            let mut output = [1.0; 64];
+            let mut frames = 0;
+            loop {
+                if !processor.swap_pending_ir(&graph_declick_values) {
+                    if processor.declick == Declicker::SettledAt1 {
+                        break frames;
+                    }
+
+                    processor.declick.process(
+                        &mut [&mut output[..]],
+                        0..64,
+                        processor
+                            .ir_crossfade_values
+                            .as_ref()
+                            .unwrap_or(&graph_declick_values),
+                        1.0,
+                        processor.ir_crossfade_curve,
+                    );
+                }
+
+                frames += 64;
+            }
+        };
+
+        let short = frames_to_settle(Some(0.002));
+        let default = frames_to_settle(None);
+        let long = frames_to_settle(Some(0.05));
+
+        assert!(short < default, "{short} >= {default}");
+        assert!(default < long, "{default} >= {long}");
+    }
+
+    // Once the tail has decayed the node stays silent, without being fooled
+    // by the silence before the tail arrives
+    #[test]
+    fn decayed_tail_reports_silent() {
+        const BLOCK_FRAMES: usize = 256;
+        const PREDELAY_FRAMES: usize = 16 * BLOCK_FRAMES;
+
+        // A silent pre-delay followed by an exponentially decaying tail.
+        let ir: Vec<f32> = (0..PREDELAY_FRAMES + 12_000)
+            .map(|i| {
+                if i < PREDELAY_FRAMES {
+                    0.0
+                } else {
+                    (-((i - PREDELAY_FRAMES) as f32) / 400.0).exp()
+                }
+            })
+            .collect();
+        let len_blocks = ir.len() / BLOCK_FRAMES;
+        let ir = ImpulseResponse::new_with_partition_size(vec![ir], BLOCK_FRAMES).unwrap();
+        let mut processor = processor::<1>(Some(ir));
+
+        let wet_gain = [1.0; BLOCK_FRAMES];
+        let mut tail_peak: f32 = 0.0;
+        let mut skipped_blocks = Vec::new();
+
+        for block in 0..len_blocks + 16 {
+            let mut input = [0.0; BLOCK_FRAMES];
+            if block == 0 {
+                input[0] = 1.0;
+            }
+            let inputs_silent = block != 0;
+
+            // Mirror the early return in `process`.
+            if inputs_silent && processor.tail.decayed {
+                skipped_blocks.push(block);
+                continue;
+            }
+
+            let mut output = [0.0; BLOCK_FRAMES];
+            processor
+                .convolve(
+                    &[&input],
+                    &mut [&mut output],
+                    &wet_gain,
+                    &mut [0.0; BLOCK_FRAMES],
+                )
+                .unwrap();
+            processor.update_tail(inputs_silent, &[&mut output], BLOCK_FRAMES);
+
+            tail_peak = tail_peak.max(max_peak(&output));
+        }
+
+        // The tail was not cut off during the pre-delay.
+        assert!((tail_peak - 1.0).abs() < 1e-4);
+
+        // The tail was detected as decayed before the end of the impulse
+        // response, and every block after that was skipped.
+        let first_skipped = skipped_blocks[0];
+        assert!(
+            first_skipped < len_blocks,
+            "{first_skipped} >= {len_blocks}"
+        );
+        assert_eq!(
+            skipped_blocks,
+            (first_skipped..len_blocks + 16).collect::<Vec<_>>()
+        );
+
+        // New input wakes the node back up.
+        processor.update_tail(false, &[&mut [0.5; BLOCK_FRAMES]], BLOCK_FRAMES);
+        assert!(!processor.tail.decayed);
+    }
+
+    // During a crossfade the output is an equal-power blend of the responses
+    // of both impulse responses
+    #[test]
+    fn crossfade_blends_both_responses() {
+        let graph_declick_values = DeclickValues::new(NonZeroU32::new(441).unwrap());
+        let ir =
+            |gain: f32| ImpulseResponse::new_with_partition_size(vec![vec![gain]], 16).unwrap();
+        let mut processor = processor::<1>(Some(ir(1.0)));
+
+        // 10ms at 44.1kHz.
+        let crossfade_frames = 441;
+        let mut crossfade = ImpulseResponseCrossfade::new(ir(0.25), 0.01);
+        processor.begin_ir_crossfade(
+            &mut crossfade,
+            NonZeroU32::new(44100).unwrap(),
+            &graph_declick_values,
+        );
+        // The outgoing IR is only ever dropped with the event.
+        assert!(crossfade.impulse_response.is_none());
+        assert!(processor.next_impulse_response.is_none());
+
+        let input = [1.0; 64];
+        let wet_gain = [1.0; 64];
+        let mut output = Vec::new();
+        for _ in 0..10 {
+            let mut out = [0.0; 64];
+            let mut outgoing_0 = [0.0; 64];
+            let mut outgoing_1 = [0.0; 64];
+            processor
+                .convolve(&[&input], &mut [&mut out], &wet_gain, &mut [0.0; 64])
+                .unwrap();
+            processor
+                .crossfade_outgoing(
+                    &[&input],
+                    &mut [&mut out],
+                    &wet_gain,
+                    &mut [0.0; 64],
+                    [&mut outgoing_0, &mut outgoing_1],
+                )
+                .unwrap();
+            output.extend_from_slice(&out);
+        }
+
+        for (i, &s) in output.iter().enumerate() {
+            let expected = if i < crossfade_frames {
+                let progress = i as f32 / crossfade_frames as f32;
+                let (gain_out, gain_in) = FadeCurve::EqualPower3dB.compute_gains_0_to_1(progress);
+                gain_out * 1.0 + gain_in * 0.25
+            } else {
+                0.25
+            };
+            assert!((s - expected).abs() < 1e-5, "{i}: {s} != {expected}");
+        }
+
+        // Halfway through, both responses are present at -3dB.
+        let halfway = 0.5f32.sqrt() * 1.25;
+        assert!((output[crossfade_frames / 2] - halfway).abs() < 0.01);
+        assert_eq!(processor.crossfade_frames_left, 0);
+    }
+
+    // The wet-only output carries the convolved, wet-gained signal with no
+    // dry content, while the main output is still mixed.
+    #[test]
+    fn wet_output_has_no_dry_content() {
+        // Delay the input by two frames.
+        let ir = ImpulseResponse::new_with_partition_size(vec![vec![0.0, 0.0, 1.0]], 16).unwrap();
+        let mut processor = processor::<1>(Some(ir));
+
+        let input: Vec<f32> = (0..64).map(|i| 1.0 - i as f32 / 64.0).collect();
+        let wet_gain = [0.5; 64];
+        let mut out = [0.0; 64];
+        let mut wet = [0.0; 64];
+
+        processor
+            .convolve(&[&input], &mut [&mut out], &wet_gain, &mut [0.0; 64])
+            .unwrap();
+        processor.mix_dry(&[&input], &mut [&mut out, &mut wet], 64);
+
+        for i in 0..64 {
+            let expected = if i < 2 { 0.0 } else { input[i - 2] * 0.5 };
+            assert!(
+                (wet[i] - expected).abs() < 1e-6,
+                "{i}: {} != {expected}",
+                wet[i]
+            );
+        }
+
+        // The mixed output still contains the dry signal before the wet
+        // signal arrives.
+        assert!(out[0] > 0.1 && out[1] > 0.1);
+    }
+
+    // A true stereo impulse response bleeds each input into the opposite
+    // output, while a stereo impulse response keeps the sides separate.
+    #[test]
+    fn true_stereo_bleeds_across_channels() {
+        let config = ConvolutionNodeConfig::<2> {
+            true_stereo: true,
+            ..Default::default()
+        };
+        // L->L, L->R, R->L, R->R
+        let ir = ImpulseResponse::new_with_partition_size(
+            vec![vec![1.0], vec![0.5], vec![0.0], vec![0.25]],
+            16,
+        )
+        .unwrap();
+        let mut processor = processor_with_config::<2>(Some(ir), &config);
+
+        let impulse = [1.0, 0.0, 0.0, 0.0];
+        let silence = [0.0; 4];
+        let wet_gain = [1.0; 4];
+
+        let convolve = |processor: &mut ConvolutionProcessor<2>, left_in, right_in| {
+            let mut left_out = [f32::NAN; 4];
+            let mut right_out = [f32::NAN; 4];
+            processor
+                .convolve(
+                    &[left_in, right_in],
+                    &mut [&mut left_out, &mut right_out],
+                    &wet_gain,
+                    &mut [0.0; 4],
+                )
+                .unwrap();
+            (left_out[0], right_out[0])
+        };
+
+        let (left, right) = convolve(&mut processor, &impulse, &silence);
+        assert!((left - 1.0).abs() < 1e-6);
+        assert!((right - 0.5).abs() < 1e-6);
+
+        let (left, right) = convolve(&mut processor, &silence, &impulse);
+        assert!(left.abs() < 1e-6);
+        assert!((right - 0.25).abs() < 1e-6);
+
+        // A stereo impulse response falls back to one channel per input.
+        let ir = ImpulseResponse::new_with_partition_size(vec![vec![1.0], vec![0.5]], 16).unwrap();
+        let mut processor = processor_with_config::<2>(Some(ir), &config);
+
+        let (left, right) = convolve(&mut processor, &impulse, &silence);
+        assert!((left - 1.0).abs() < 1e-6);
+        assert!(right.abs() < 1e-6);
+        processor.report_downmix(2);
+        assert_eq!(processor.downmixed_from.load(Ordering::Relaxed), 0);
+    }
+
+    // The channels of a 4-channel file are routed to the true stereo
+    // convolvers according to the layout of the file.
+    #[test]
+    fn true_stereo_file_channels_map_to_convolvers() {
+        // The gain of each path, so that each convolver can be identified.
+        let (ll, lr, rl, rr) = (1.0, 0.5, 0.25, 0.125);
+        let sample_rate = NonZeroU32::new(44100).unwrap();
+
+        let convolver_gains = |ir: &mut ImpulseResponse| {
+            ir.convolvers
+                .iter_mut()
+                .map(|conv| {
+                    let mut out = [f32::NAN; 4];
+                    conv.process(&[1.0, 0.0, 0.0, 0.0], &mut out).unwrap();
+                    out[0]
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let config = ConvolutionNodeConfig::<2> {
+            true_stereo: true,
+            partition_size: 16,
+            ..Default::default()
+        };
+        let mut ir = ImpulseResponse::new_with_config(
+            vec![vec![ll], vec![lr], vec![rl], vec![rr]],
+            &config,
+            sample_rate,
+        )
+        .unwrap();
+        assert_eq!(convolver_gains(&mut ir), [ll, lr, rl, rr]);
+
+        let config = ConvolutionNodeConfig::<2> {
+            true_stereo_layout: TrueStereoLayout::ByOutput,
+            ..config
+        };
+        let mut ir = ImpulseResponse::new_with_config(
+            vec![vec![ll], vec![rl], vec![lr], vec![rr]],
+            &config,
+            sample_rate,
+        )
+        .unwrap();
+        assert_eq!(convolver_gains(&mut ir), [ll, lr, rl, rr]);
+
+        // Without true stereo, the channels are left in the order of the file.
+        let config = ConvolutionNodeConfig::<2> {
+            true_stereo: false,
+            ..config
+        };
+        let mut ir = ImpulseResponse::new_with_config(
+            vec![vec![ll], vec![rl], vec![lr], vec![rr]],
+            &config,
+            sample_rate,
+        )
+        .unwrap();
+        assert_eq!(convolver_gains(&mut ir), [ll, rl, lr, rr]);
+    }
 }
@@ -0,0 +1,304 @@
+//! A node that blends the two channels of a stereo signal for more natural
+//! headphone listening.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        delay_line::RingDelayLine,
+        filter::{
+            single_pole_iir::{OnePoleIirLPF, OnePoleIirLPFCoeff},
+            smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        },
+        volume::DEFAULT_AMP_EPSILON,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The delay of the crossfed signal in seconds. This is roughly the extra
+/// time it takes sound from a speaker to reach the far ear.
+const CROSSFEED_DELAY_SECONDS: f32 = 0.0003;
+
+/// The lowest and highest allowed cutoff frequencies in hertz.
+const MIN_CUTOFF_HZ: f32 = 20.0;
+const MAX_CUTOFF_HZ: f32 = 20_000.0;
+
+/// A Bauer-style crossfeed node for headphone listening (Stereo input and
+/// output).
+///
+/// On speakers, each ear hears both speakers, with the far speaker arriving
+/// slightly later and with less high-frequency content due to the shadow of
+/// the head. On headphones, hard-panned sounds only reach one ear, which can
+/// sound unnaturally wide and fatiguing. This node mixes a delayed, lowpass
+/// filtered portion of each channel into the other to mimic the speaker
+/// case.
+///
+/// The output is normalized so that sounds panned to the center keep the
+/// same level at low frequencies.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CrossfeedNode {
+    /// Whether or not crossfeed is enabled. When disabled, the signal is
+    /// passed through unchanged.
+    pub enabled: bool,
+    /// The amount of each channel that is fed into the other, in the range
+    /// `[0.0, 1.0]`. At `1.0`, low frequencies are fully mono.
+    ///
+    /// By default this is set to `0.3` (a moderate amount, about `-10` dB
+    /// relative to the direct signal).
+    pub amount: f32,
+    /// The cutoff frequency in hertz of the lowpass filter applied to the
+    /// crossfed signal.
+    ///
+    /// By default this is set to `700.0`.
+    pub cutoff_hz: f32,
+    /// The time in seconds of the internal smoothing filter for `amount`.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for CrossfeedNode {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            amount: 0.3,
+            cutoff_hz: 700.0,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for CrossfeedNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("crossfeed")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::STEREO,
+                num_outputs: ChannelCount::STEREO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(*self, cx.stream_info.sample_rate);
+        processor
+            .amount
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct Processor {
+    params: CrossfeedNode,
+    amount: SmoothedParam,
+    coeff: OnePoleIirLPFCoeff,
+    filters: [OnePoleIirLPF; 2],
+    delays: [RingDelayLine; 2],
+    delay_frames: f32,
+}
+
+impl Processor {
+    fn new(params: CrossfeedNode, sample_rate: NonZeroU32) -> Self {
+        let mut processor = Self {
+            params,
+            amount: SmoothedParam::new(
+                target_amount(&params),
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+            coeff: OnePoleIirLPFCoeff::default(),
+            filters: [OnePoleIirLPF::default(); 2],
+            delays: [RingDelayLine::new(0), RingDelayLine::new(0)],
+            delay_frames: 0.0,
+        };
+        processor.update_sample_rate(sample_rate);
+        processor
+    }
+
+    fn update_sample_rate(&mut self, sample_rate: NonZeroU32) {
+        self.delay_frames = CROSSFEED_DELAY_SECONDS * sample_rate.get() as f32;
+        self.delays = [
+            RingDelayLine::from_seconds(CROSSFEED_DELAY_SECONDS, sample_rate),
+            RingDelayLine::from_seconds(CROSSFEED_DELAY_SECONDS, sample_rate),
+        ];
+        self.filters = [OnePoleIirLPF::default(); 2];
+        self.amount.update_sample_rate(sample_rate);
+        self.update_coeff(sample_rate);
+    }
+
+    fn update_coeff(&mut self, sample_rate: NonZeroU32) {
+        let cutoff_hz = self.params.cutoff_hz.clamp(
+            MIN_CUTOFF_HZ,
+            MAX_CUTOFF_HZ.min(sample_rate.get() as f32 * 0.45),
+        );
+        self.coeff = OnePoleIirLPFCoeff::new(cutoff_hz, (sample_rate.get() as f32).recip());
+    }
+
+    /// Crossfeed a block of stereo frames.
+    fn crossfeed(&mut self, in_l: &[f32], in_r: &[f32], out_l: &mut [f32], out_r: &mut [f32]) {
+        for i in 0..out_l.len() {
+            let amount = self.amount.next_smoothed();
+
+            self.delays[0].write(in_l[i]);
+            self.delays[1].write(in_r[i]);
+
+            let to_l =
+                self.filters[0].process(self.delays[1].read_at(self.delay_frames), self.coeff);
+            let to_r =
+                self.filters[1].process(self.delays[0].read_at(self.delay_frames), self.coeff);
+
+            let norm = (1.0 + amount).recip();
+            out_l[i] = (in_l[i] + to_l * amount) * norm;
+            out_r[i] = (in_r[i] + to_r * amount) * norm;
+        }
+
+        self.amount.settle();
+    }
+
+    fn reset(&mut self) {
+        for delay in self.delays.iter_mut() {
+            delay.reset();
+        }
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+    }
+}
+
+fn target_amount(params: &CrossfeedNode) -> f32 {
+    if params.enabled {
+        params.amount.clamp(0.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<CrossfeedNode>() {
+            if let CrossfeedNodePatch::SmoothSeconds(seconds) = patch {
+                self.amount.set_smooth_seconds(seconds, info.sample_rate);
+            }
+
+            self.params.apply(patch);
+            self.amount.set_value(target_amount(&self.params));
+            self.update_coeff(info.sample_rate);
+
+            if info.prev_output_was_silent {
+                // Previous block was silent, so no need to smooth.
+                self.amount.reset_to_target();
+            }
+        }
+
+        if self.amount.has_settled() && self.amount.target_value() == 0.0 {
+            self.reset();
+            return ProcessStatus::Bypass;
+        }
+
+        if info.in_silence_mask.all_channels_silent(2) && info.prev_output_was_silent {
+            self.amount.reset_to_target();
+            self.reset();
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let in_l = &buffers.inputs[0][..info.frames];
+        let in_r = &buffers.inputs[1][..info.frames];
+        let (out_l, out_r) = buffers.outputs.split_first_mut().unwrap();
+
+        self.crossfeed(
+            in_l,
+            in_r,
+            &mut out_l[..info.frames],
+            &mut out_r[0][..info.frames],
+        );
+
+        // Let the short tail of the delay and filter ring out before
+        // declaring the output silent.
+        buffers.check_for_silence_on_outputs(DEFAULT_AMP_EPSILON)
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The normalized correlation of the two channels at zero lag.
+    fn correlation(l: &[f32], r: &[f32]) -> f32 {
+        let dot: f32 = l.iter().zip(r).map(|(l, r)| l * r).sum();
+        let energy_l: f32 = l.iter().map(|s| s * s).sum();
+        let energy_r: f32 = r.iter().map(|s| s * s).sum();
+
+        if energy_l == 0.0 || energy_r == 0.0 {
+            0.0
+        } else {
+            dot / (energy_l * energy_r).sqrt()
+        }
+    }
+
+    fn render_hard_left(params: CrossfeedNode) -> (Vec<f32>, Vec<f32>) {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(params, sample_rate);
+
+        // A bass-heavy source panned hard left.
+        let in_l: Vec<f32> = (0..9_600)
+            .map(|i| {
+                let t = i as f32 / sample_rate.get() as f32;
+                0.5 * (core::f32::consts::TAU * 110.0 * t).sin()
+                    + 0.25 * (core::f32::consts::TAU * 330.0 * t).sin()
+            })
+            .collect();
+        let in_r = vec![0.0; in_l.len()];
+
+        let mut out_l = vec![0.0; in_l.len()];
+        let mut out_r = vec![0.0; in_l.len()];
+        processor.crossfeed(&in_l, &in_r, &mut out_l, &mut out_r);
+
+        (out_l, out_r)
+    }
+
+    #[test]
+    fn crossfeed_increases_inter_channel_correlation() {
+        let (l, r) = render_hard_left(CrossfeedNode {
+            enabled: false,
+            ..Default::default()
+        });
+        assert!(r.iter().all(|&s| s == 0.0));
+        assert_eq!(correlation(&l, &r), 0.0);
+
+        let (l, r) = render_hard_left(CrossfeedNode::default());
+        assert!(correlation(&l, &r) > 0.7);
+
+        // The crossfed signal is quieter than the direct signal.
+        let peak = |s: &[f32]| s.iter().fold(0.0f32, |m, s| m.max(s.abs()));
+        assert!(peak(&r) < peak(&l) * 0.4);
+    }
+}
@@ -0,0 +1,168 @@
+//! A node that removes DC offset from a signal.
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        dc_blocker::{DcBlocker, DEFAULT_DC_BLOCKER_HZ},
+        declick::{DeclickFadeCurve, Declicker},
+        volume::DEFAULT_AMP_EPSILON,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+/// The minimum cutoff frequency of a [`DcBlockerNode`] in hertz.
+pub const MIN_CUTOFF_HZ: f32 = 1.0;
+/// The maximum cutoff frequency of a [`DcBlockerNode`] in hertz.
+pub const MAX_CUTOFF_HZ: f32 = 40.0;
+
+pub type DcBlockerMonoNode = DcBlockerNode<1>;
+pub type DcBlockerStereoNode = DcBlockerNode<2>;
+
+/// A node that removes DC offset from a signal.
+///
+/// Waveshapers, asymmetric distortion, and some oscillators can introduce a
+/// DC offset, which wastes headroom and can damage speakers. This node uses
+/// a single-pole highpass filter with a very low cutoff to remove it.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DcBlockerNode<const CHANNELS: usize> {
+    /// The cutoff frequency in hertz in the range `[1.0, 40.0]`.
+    ///
+    /// By default this is set to `5.0`.
+    pub cutoff_hz: f32,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl<const CHANNELS: usize> Default for DcBlockerNode<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            cutoff_hz: DEFAULT_DC_BLOCKER_HZ,
+            enabled: true,
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for DcBlockerNode<CHANNELS> {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("dc_blocker")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let blocker = DcBlocker::new(
+            self.cutoff_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ),
+            cx.stream_info.sample_rate_recip as f32,
+        );
+
+        Processor {
+            params: *self,
+            blockers: [blocker; CHANNELS],
+            enable_declicker: Declicker::from_enabled(self.enabled),
+        }
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    params: DcBlockerNode<CHANNELS>,
+    blockers: [DcBlocker; CHANNELS],
+    enable_declicker: Declicker,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn set_cutoff_hz(&mut self, sample_rate_recip: f64) {
+        let cutoff_hz = self.params.cutoff_hz.clamp(MIN_CUTOFF_HZ, MAX_CUTOFF_HZ);
+        for blocker in self.blockers.iter_mut() {
+            blocker.set_cutoff_hz(cutoff_hz, sample_rate_recip as f32);
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<DcBlockerNode<CHANNELS>>() {
+            if let DcBlockerNodePatch::Enabled(enabled) = patch {
+                // Tell the declicker to crossfade.
+                self.enable_declicker
+                    .fade_to_enabled(enabled, &extra.declick_values);
+            }
+
+            self.params.apply(patch);
+            self.set_cutoff_hz(info.sample_rate_recip);
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            for blocker in self.blockers.iter_mut() {
+                blocker.reset();
+            }
+
+            return ProcessStatus::Bypass;
+        }
+
+        // The filter has a long tail after a DC offset is removed, so only
+        // skip processing once that tail has decayed.
+        if info.in_silence_mask.all_channels_silent(CHANNELS)
+            && self.enable_declicker.has_settled()
+            && self
+                .blockers
+                .iter()
+                .all(|b| b.is_silent(DEFAULT_AMP_EPSILON))
+        {
+            for blocker in self.blockers.iter_mut() {
+                blocker.reset();
+            }
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        for ((blocker, in_ch), out_ch) in self
+            .blockers
+            .iter_mut()
+            .zip(buffers.inputs.iter())
+            .zip(buffers.outputs.iter_mut())
+        {
+            out_ch[..info.frames].copy_from_slice(&in_ch[..info.frames]);
+            blocker.process_block(&mut out_ch[..info.frames]);
+        }
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.set_cutoff_hz(stream_info.sample_rate_recip);
+    }
+}
@@ -0,0 +1,296 @@
+//! A split-band de-esser node for taming harsh sibilance.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        declick::{DeclickFadeCurve, Declicker},
+        envelope_follower::{DetectorMode, EnvelopeFollower, EnvelopeFollowerConfig},
+        filter::svf::{SvfCoeff, SvfState},
+        volume::{amp_to_db, db_to_amp},
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+/// The lowest and highest allowed center frequencies in hertz.
+const MIN_FREQUENCY_HZ: f32 = 1_000.0;
+const MAX_FREQUENCY_HZ: f32 = 16_000.0;
+
+/// The lowest allowed Q factor.
+const MIN_Q: f32 = 0.1;
+
+/// The detector is fast so that short "s" and "t" sounds are caught, but
+/// releases slowly enough not to distort the band.
+const DETECTOR: EnvelopeFollowerConfig = EnvelopeFollowerConfig {
+    mode: DetectorMode::Peak,
+    attack_secs: 0.001,
+    release_secs: 0.06,
+    auto_release: false,
+    rms_window_secs: 0.01,
+};
+
+/// A de-esser (Stereo input and output).
+///
+/// The signal is split into a band around [`DeEsserNode::frequency_hz`] and
+/// everything outside of it. Whenever the level of the band rises above the
+/// threshold, only the band is turned down (by at most
+/// [`DeEsserNode::range_db`]), leaving the rest of the signal untouched.
+/// When no gain reduction is applied, the two parts sum back to exactly the
+/// input.
+///
+/// Both channels share a single detector so the stereo image stays stable.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DeEsserNode {
+    /// The center frequency in hertz of the band that is detected and
+    /// attenuated.
+    ///
+    /// By default this is set to `6500.0`.
+    pub frequency_hz: f32,
+    /// The Q factor of the band. Higher values give a narrower band.
+    ///
+    /// By default this is set to `1.0`.
+    pub q_factor: f32,
+    /// The level in decibels above which the band is turned down.
+    ///
+    /// By default this is set to `-30.0`.
+    pub threshold_db: f32,
+    /// The largest amount in decibels the band may be turned down by.
+    ///
+    /// By default this is set to `12.0`.
+    pub range_db: f32,
+    /// If `true`, then only the detection band is output, so it can be
+    /// auditioned while tuning [`DeEsserNode::frequency_hz`].
+    ///
+    /// By default this is set to `false`.
+    pub listen: bool,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for DeEsserNode {
+    fn default() -> Self {
+        Self {
+            frequency_hz: 6_500.0,
+            q_factor: 1.0,
+            threshold_db: -30.0,
+            range_db: 12.0,
+            listen: false,
+            enabled: true,
+        }
+    }
+}
+
+impl AudioNode for DeEsserNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("de_esser")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::STEREO,
+                num_outputs: ChannelCount::STEREO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor {
+    params: DeEsserNode,
+    coeff: SvfCoeff,
+    filters: [SvfState; 2],
+    follower: EnvelopeFollower,
+    sample_rate: NonZeroU32,
+    enable_declicker: Declicker,
+}
+
+impl Processor {
+    fn new(params: DeEsserNode, sample_rate: NonZeroU32) -> Self {
+        let mut processor = Self {
+            params,
+            coeff: SvfCoeff::NO_OP,
+            filters: [SvfState::default(); 2],
+            follower: EnvelopeFollower::new(DETECTOR, sample_rate),
+            sample_rate,
+            enable_declicker: Declicker::from_enabled(params.enabled),
+        };
+        processor.update_coeff();
+        processor
+    }
+
+    fn update_coeff(&mut self) {
+        let sample_rate = self.sample_rate.get() as f32;
+        let frequency_hz = self
+            .params
+            .frequency_hz
+            .clamp(MIN_FREQUENCY_HZ, MAX_FREQUENCY_HZ.min(sample_rate * 0.45));
+
+        self.coeff = SvfCoeff::bandpass(
+            frequency_hz,
+            self.params.q_factor.max(MIN_Q),
+            sample_rate.recip(),
+        );
+    }
+
+    /// De-ess a block of stereo frames.
+    fn de_ess(&mut self, inputs: [&[f32]; 2], outputs: &mut [&mut [f32]], frames: usize) {
+        let range_db = self.params.range_db.max(0.0);
+
+        for i in 0..frames {
+            let in_l = inputs[0][i];
+            let in_r = inputs[1][i];
+
+            let band_l = self.filters[0].process(in_l, &self.coeff);
+            let band_r = self.filters[1].process(in_r, &self.coeff);
+
+            let envelope = self.follower.process(band_l.abs().max(band_r.abs()));
+            let reduction_db =
+                (amp_to_db(envelope) - self.params.threshold_db).clamp(0.0, range_db);
+
+            if self.params.listen {
+                outputs[0][i] = band_l;
+                outputs[1][i] = band_r;
+            } else {
+                // Everything outside the band is left untouched.
+                let gain = db_to_amp(-reduction_db);
+                outputs[0][i] = in_l - band_l + band_l * gain;
+                outputs[1][i] = in_r - band_r + band_r * gain;
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+        self.follower.reset();
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<DeEsserNode>() {
+            if let DeEsserNodePatch::Enabled(enabled) = patch {
+                // Tell the declicker to crossfade.
+                self.enable_declicker
+                    .fade_to_enabled(enabled, &extra.declick_values);
+            }
+
+            self.params.apply(patch);
+            self.update_coeff();
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+            return ProcessStatus::Bypass;
+        }
+
+        if info.in_silence_mask.all_channels_silent(2)
+            && info.prev_output_was_silent
+            && self.enable_declicker.has_settled()
+        {
+            self.reset();
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.de_ess(
+            [buffers.inputs[0], buffers.inputs[1]],
+            buffers.outputs,
+            info.frames,
+        );
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.follower.set_config(DETECTOR, stream_info.sample_rate);
+        self.update_coeff();
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_tone(freq_hz: f32, listen: bool) -> (Vec<f32>, Vec<f32>) {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(
+            DeEsserNode {
+                listen,
+                ..Default::default()
+            },
+            sample_rate,
+        );
+
+        let input: Vec<f32> = (0..24_000)
+            .map(|i| {
+                let t = i as f32 / sample_rate.get() as f32;
+                0.5 * (core::f32::consts::TAU * freq_hz * t).sin()
+            })
+            .collect();
+        let mut out_l = vec![0.0; input.len()];
+        let mut out_r = vec![0.0; input.len()];
+        processor.de_ess([&input, &input], &mut [&mut out_l, &mut out_r], input.len());
+
+        assert_eq!(out_l, out_r);
+        (input, out_l)
+    }
+
+    fn rms(s: &[f32]) -> f32 {
+        (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn only_the_de_ess_band_is_attenuated() {
+        // A loud tone in the middle of the band is turned down by close to
+        // the full range once the detector has settled.
+        let (input, output) = render_tone(6_500.0, false);
+        let reduction_db = amp_to_db(rms(&input[4_800..])) - amp_to_db(rms(&output[4_800..]));
+        assert!(reduction_db > 10.0, "reduction {reduction_db}");
+        assert!(reduction_db < 12.5, "reduction {reduction_db}");
+
+        // A tone well below the band passes through unchanged.
+        let (input, output) = render_tone(200.0, false);
+        for (i, o) in input.iter().zip(output.iter()) {
+            assert!((i - o).abs() < 1e-5);
+        }
+
+        // In listen mode, only the band is heard.
+        let (input, output) = render_tone(200.0, true);
+        assert!(rms(&output) < rms(&input) * 0.05);
+    }
+}
@@ -0,0 +1,41 @@
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+
+/// A single channel of delay with a fractional read position.
+pub(crate) struct DelayBuffer {
+    buffer: Vec<f32>,
+}
+
+impl DelayBuffer {
+    pub fn new(max_delay_frames: usize) -> Self {
+        // Leave room for the interpolated sample past the longest delay.
+        Self {
+            buffer: vec![0.0; max_delay_frames + 2],
+        }
+    }
+
+    /// The length of the buffer in frames. Write positions wrap around at
+    /// this length.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    #[inline]
+    pub fn write(&mut self, write_pos: usize, s: f32) {
+        self.buffer[write_pos] = s;
+    }
+
+    /// Read the sample from `delay_frames` frames before `write_pos`.
+    #[inline]
+    pub fn read(&self, write_pos: usize, delay_frames: f32) -> f32 {
+        let len = self.buffer.len();
+        let delay_int = delay_frames as usize;
+        let frac = delay_frames - delay_int as f32;
+
+        let i0 = (write_pos + len - delay_int) % len;
+        let i1 = (i0 + len - 1) % len;
+
+        self.buffer[i0] + (self.buffer[i1] - self.buffer[i0]) * frac
+    }
+}
@@ -0,0 +1,379 @@
+//! An ADSR envelope generator driven by gate events.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount, NonZeroChannelCount},
+    diff::{Diff, Notify, Patch},
+    dsp::fade::FadeCurve,
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// What happens when the gate of an [`EnvelopeNode`] is opened while it is
+/// already open.
+#[derive(Default, Diff, Patch, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RetriggerMode {
+    /// Restart the attack stage from the current level.
+    #[default]
+    Retrigger,
+    /// Keep going through the current stage as if nothing happened.
+    Legato,
+}
+
+/// The configuration of an [`EnvelopeNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeConfig {
+    /// The number of input and output channels. This is ignored if
+    /// `control_output` is `true`.
+    pub channels: NonZeroChannelCount,
+    /// If `true`, then the node has no inputs and outputs the envelope itself
+    /// as a mono control signal in the range `[0.0, 1.0]`. If `false`, then
+    /// the node multiplies its inputs by the envelope.
+    ///
+    /// By default this is set to `false`.
+    pub control_output: bool,
+}
+
+impl Default for EnvelopeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            control_output: false,
+        }
+    }
+}
+
+/// An ADSR (attack, decay, sustain, release) envelope generator.
+///
+/// Opening the gate ramps the level up to `1.0` over
+/// [`EnvelopeNode::attack_ms`], then down to
+/// [`EnvelopeNode::sustain_level`] over [`EnvelopeNode::decay_ms`], where it
+/// stays until the gate is closed. Closing the gate ramps the level from
+/// wherever it currently is down to `0.0` over [`EnvelopeNode::release_ms`].
+///
+/// Changes to [`EnvelopeNode::gate`] take effect at the start of the
+/// processing block they arrive in. Send them as scheduled events to place
+/// the edges of the gate on an exact frame. The transitions between the
+/// stages after that are sample-accurate.
+#[derive(Diff, Patch, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeNode {
+    /// Open (`true`) or close (`false`) the gate.
+    ///
+    /// Opening the gate again while it is already open retriggers the
+    /// envelope according to [`EnvelopeNode::retrigger`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub gate: Notify<bool>,
+    /// The time in milliseconds it takes to rise from `0.0` to `1.0`.
+    ///
+    /// By default this is set to `5.0`.
+    pub attack_ms: f32,
+    /// The time in milliseconds it takes to fall from `1.0` to
+    /// [`EnvelopeNode::sustain_level`].
+    ///
+    /// By default this is set to `100.0`.
+    pub decay_ms: f32,
+    /// The level (in the range `[0.0, 1.0]`) held while the gate is open.
+    ///
+    /// By default this is set to `0.7`.
+    pub sustain_level: f32,
+    /// The time in milliseconds it takes to fall from the current level to
+    /// `0.0` after the gate is closed.
+    ///
+    /// By default this is set to `200.0`.
+    pub release_ms: f32,
+    /// The shape of each stage. The attack stage follows the rising half of
+    /// the curve, and the decay and release stages follow the falling half.
+    ///
+    /// By default this is set to [`FadeCurve::Linear`].
+    pub curve: FadeCurve,
+    /// What happens when the gate is opened while it is already open.
+    ///
+    /// By default this is set to [`RetriggerMode::Retrigger`].
+    pub retrigger: RetriggerMode,
+}
+
+impl Default for EnvelopeNode {
+    fn default() -> Self {
+        Self {
+            gate: Notify::new(false),
+            attack_ms: 5.0,
+            decay_ms: 100.0,
+            sustain_level: 0.7,
+            release_ms: 200.0,
+            curve: FadeCurve::Linear,
+            retrigger: RetriggerMode::default(),
+        }
+    }
+}
+
+impl EnvelopeNode {
+    /// Open the gate, starting (or retriggering) the envelope.
+    pub fn gate_on(&mut self) {
+        *self.gate = true;
+    }
+
+    /// Close the gate, starting the release stage.
+    pub fn gate_off(&mut self) {
+        *self.gate = false;
+    }
+}
+
+impl AudioNode for EnvelopeNode {
+    type Configuration = EnvelopeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("envelope")
+            .channel_config(if config.control_output {
+                ChannelConfig {
+                    num_inputs: ChannelCount::ZERO,
+                    num_outputs: ChannelCount::MONO,
+                }
+            } else {
+                ChannelConfig {
+                    num_inputs: config.channels.get(),
+                    num_outputs: config.channels.get(),
+                }
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(
+            self.clone(),
+            config.control_output,
+            cx.stream_info.sample_rate,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+struct Processor {
+    params: EnvelopeNode,
+    control_output: bool,
+    gate_open: bool,
+    stage: Stage,
+    level: f32,
+    /// The level at the start of the current stage.
+    stage_start_level: f32,
+    /// The number of frames processed in the current stage.
+    stage_frame: u32,
+    /// The length of the current stage in frames.
+    stage_frames: u32,
+    sample_rate: NonZeroU32,
+}
+
+impl Processor {
+    fn new(params: EnvelopeNode, control_output: bool, sample_rate: NonZeroU32) -> Self {
+        Self {
+            params,
+            control_output,
+            gate_open: false,
+            stage: Stage::Idle,
+            level: 0.0,
+            stage_start_level: 0.0,
+            stage_frame: 0,
+            stage_frames: 0,
+            sample_rate,
+        }
+    }
+
+    fn ms_to_frames(&self, ms: f32) -> u32 {
+        (ms.max(0.0) * self.sample_rate.get() as f32 / 1_000.0).round() as u32
+    }
+
+    fn set_gate(&mut self, open: bool) {
+        if open {
+            if !self.gate_open || self.params.retrigger == RetriggerMode::Retrigger {
+                self.enter_stage(Stage::Attack);
+            }
+        } else if self.gate_open {
+            self.enter_stage(Stage::Release);
+        }
+
+        self.gate_open = open;
+    }
+
+    fn enter_stage(&mut self, stage: Stage) {
+        self.stage = stage;
+        self.stage_start_level = self.level;
+        self.stage_frame = 0;
+        self.stage_frames = match stage {
+            Stage::Attack => self.ms_to_frames(self.params.attack_ms),
+            Stage::Decay => self.ms_to_frames(self.params.decay_ms),
+            Stage::Release => self.ms_to_frames(self.params.release_ms),
+            Stage::Idle | Stage::Sustain => 0,
+        };
+    }
+
+    /// Advance the envelope by one frame and return the new level.
+    fn next_level(&mut self) -> f32 {
+        let sustain_level = self.params.sustain_level.clamp(0.0, 1.0);
+
+        // Stages shorter than a frame are skipped over on the same frame, so
+        // that the following stage starts exactly on time.
+        loop {
+            let (end_level, next_stage) = match self.stage {
+                Stage::Idle => return 0.0,
+                Stage::Sustain => {
+                    self.level = sustain_level;
+                    return self.level;
+                }
+                Stage::Attack => (1.0, Stage::Decay),
+                Stage::Decay => (sustain_level, Stage::Sustain),
+                Stage::Release => (0.0, Stage::Idle),
+            };
+
+            if self.stage_frame >= self.stage_frames {
+                self.level = end_level;
+                self.enter_stage(next_stage);
+                continue;
+            }
+
+            self.stage_frame += 1;
+            let progress = self.stage_frame as f32 / self.stage_frames as f32;
+
+            let (falling, rising) = self.params.curve.compute_gains_0_to_1(progress);
+            self.level = if self.stage == Stage::Attack {
+                self.stage_start_level + (end_level - self.stage_start_level) * rising
+            } else {
+                end_level + (self.stage_start_level - end_level) * falling
+            };
+
+            if self.stage_frame == self.stage_frames {
+                self.level = end_level;
+                self.enter_stage(next_stage);
+            }
+
+            return self.level;
+        }
+    }
+
+    /// Fill `out` with the envelope.
+    fn render(&mut self, out: &mut [f32]) {
+        for s in out.iter_mut() {
+            *s = self.next_level();
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<EnvelopeNode>() {
+            match patch {
+                EnvelopeNodePatch::Gate(gate) => {
+                    self.set_gate(*gate);
+                }
+                patch => self.params.apply(patch),
+            }
+        }
+
+        if self.stage == Stage::Idle {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        if self.control_output {
+            self.render(&mut buffers.outputs[0][..info.frames]);
+        } else {
+            for i in 0..info.frames {
+                let level = self.next_level();
+
+                for (out_ch, in_ch) in buffers.outputs.iter_mut().zip(buffers.inputs.iter()) {
+                    out_ch[i] = in_ch[i] * level;
+                }
+            }
+        }
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reaches_sustain_then_releases_to_zero_on_time() {
+        // 48 frames per millisecond.
+        let mut processor = Processor::new(
+            EnvelopeNode {
+                attack_ms: 10.0,
+                decay_ms: 20.0,
+                sustain_level: 0.5,
+                release_ms: 40.0,
+                ..Default::default()
+            },
+            true,
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        let mut out = vec![0.0; 4800];
+        processor.set_gate(true);
+        processor.render(&mut out[..2400]);
+        processor.set_gate(false);
+        processor.render(&mut out[2400..]);
+
+        // The attack peaks on the last frame of the attack stage, and the
+        // decay reaches the sustain level on the last frame of the decay
+        // stage.
+        assert!(out[..480].windows(2).all(|w| w[1] > w[0]));
+        assert_eq!(out[479], 1.0);
+        assert!(out[480..1440].windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(out[1439], 0.5);
+        assert!(out[1440..2400].iter().all(|&s| s == 0.5));
+
+        // The release ramps down from the sustain level without a jump.
+        assert!((out[2400] - 0.5).abs() < 0.01);
+        assert!(out[2400..2400 + 1920].windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(out[2400 + 1919], 0.0);
+        assert!(out[2400 + 1920..].iter().all(|&s| s == 0.0));
+        assert_eq!(processor.stage, Stage::Idle);
+
+        // Releasing during the attack ramps down from the current level.
+        processor.set_gate(true);
+        processor.render(&mut out[..240]);
+        let level = out[239];
+        assert!((level - 0.5).abs() < 0.01);
+        processor.set_gate(false);
+        processor.render(&mut out[..1920]);
+        assert!(out[0] < level && level - out[0] < 0.01);
+        assert_eq!(out[1919], 0.0);
+    }
+}
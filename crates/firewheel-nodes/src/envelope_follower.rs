@@ -0,0 +1,308 @@
+//! A node that outputs the level envelope of a signal.
+
+use bevy_platform::sync::atomic::Ordering;
+use core::num::NonZeroU32;
+use firewheel_core::{
+    atomic_float::AtomicF32,
+    channel_config::{ChannelConfig, ChannelCount},
+    collector::ArcGc,
+    diff::{Diff, Patch},
+    dsp::{
+        envelope_follower::{EnvelopeFollower, EnvelopeFollowerConfig},
+        volume::amp_to_db,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+/// A node that outputs the detected level envelope of a mono signal as an
+/// audio-rate signal (Mono input and output).
+///
+/// This uses the same detector as the dynamics processors, so it can be
+/// used to draw a sidechain detector in a UI, or routed to modulate other
+/// nodes. The latest value can also be read from [`EnvelopeFollowerState`].
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EnvelopeFollowerNode {
+    /// The configuration of the level detector.
+    pub detector: EnvelopeFollowerConfig,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for EnvelopeFollowerNode {
+    fn default() -> Self {
+        Self {
+            detector: EnvelopeFollowerConfig::default(),
+            enabled: true,
+        }
+    }
+}
+
+/// The state of an [`EnvelopeFollowerNode`]. This contains the latest
+/// envelope value.
+#[derive(Clone)]
+pub struct EnvelopeFollowerState {
+    envelope: ArcGc<AtomicF32>,
+}
+
+impl EnvelopeFollowerState {
+    fn new() -> Self {
+        Self {
+            envelope: ArcGc::new(AtomicF32::new(0.0)),
+        }
+    }
+
+    /// The envelope at the end of the last processed block, in raw amplitude.
+    ///
+    /// If the node is currently disabled, then this will return `0.0`.
+    pub fn envelope(&self) -> f32 {
+        self.envelope.load(Ordering::Relaxed)
+    }
+
+    /// The envelope at the end of the last processed block, in decibels.
+    ///
+    /// * `db_epsilon` - If the value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn envelope_db(&self, db_epsilon: f32) -> f32 {
+        let db = amp_to_db(self.envelope());
+        if db <= db_epsilon {
+            f32::NEG_INFINITY
+        } else {
+            db
+        }
+    }
+}
+
+impl AudioNode for EnvelopeFollowerNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("envelope_follower")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::MONO,
+                num_outputs: ChannelCount::MONO,
+            })
+            .custom_state(EnvelopeFollowerState::new())
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor {
+            params: *self,
+            follower: EnvelopeFollower::new(self.detector, cx.stream_info.sample_rate),
+            sample_rate: cx.stream_info.sample_rate,
+            envelope: ArcGc::clone(&cx.custom_state::<EnvelopeFollowerState>().unwrap().envelope),
+        }
+    }
+}
+
+struct Processor {
+    params: EnvelopeFollowerNode,
+    follower: EnvelopeFollower,
+    sample_rate: NonZeroU32,
+    envelope: ArcGc<AtomicF32>,
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<EnvelopeFollowerNode>() {
+            self.params.apply(patch);
+        }
+        if self.params.detector != *self.follower.config() {
+            self.follower
+                .set_config(self.params.detector, self.sample_rate);
+        }
+
+        if !self.params.enabled {
+            self.follower.reset();
+            self.envelope.store(0.0, Ordering::Relaxed);
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        if info.in_silence_mask.is_channel_silent(0) && self.follower.value() == 0.0 {
+            self.envelope.store(0.0, Ordering::Relaxed);
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.follower.process_block(
+            &buffers.inputs[0][..info.frames],
+            &mut buffers.outputs[0][..info.frames],
+        );
+
+        // Let the envelope settle to exact zero once it is inaudible so that
+        // the node can go back to skipping silent blocks.
+        if self.follower.value() < f32::EPSILON {
+            self.follower.reset();
+        }
+
+        self.envelope
+            .store(self.follower.value(), Ordering::Relaxed);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.follower
+            .set_config(self.params.detector, self.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{any::Any, time::Duration};
+
+    use firewheel_core::{
+        clock::InstantSamples,
+        dsp::{buffer::ChannelBuffer, declick::DeclickValues},
+        log::{realtime_logger, RealtimeLoggerConfig},
+        mask::{ConnectedMask, ConstantMask, SilenceMask},
+        node::{NodeID, ProcStore, StreamStatus},
+    };
+
+    use super::*;
+
+    const BLOCK_FRAMES: usize = 480;
+
+    /// Run a signal through the processor in blocks of [`BLOCK_FRAMES`],
+    /// without any events.
+    fn render(
+        processor: &mut impl AudioNodeProcessor,
+        input: &[f32],
+        stream_info: &StreamInfo,
+    ) -> Vec<f32> {
+        let (logger, _logger_main) = realtime_logger(RealtimeLoggerConfig::default());
+        let mut extra = ProcExtra {
+            scratch_buffers: ChannelBuffer::new(stream_info.max_block_frames.get() as usize),
+            declick_values: DeclickValues::new(stream_info.declick_frames),
+            logger,
+            store: ProcStore::with_capacity(0),
+        };
+
+        let mut output = vec![0.0; input.len()];
+        for (in_block, out_block) in input
+            .chunks(BLOCK_FRAMES)
+            .zip(output.chunks_mut(BLOCK_FRAMES))
+        {
+            let info = ProcInfo {
+                frames: in_block.len(),
+                in_silence_mask: if in_block.iter().all(|&s| s == 0.0) {
+                    SilenceMask::MONO_SILENT
+                } else {
+                    SilenceMask::NONE_SILENT
+                },
+                out_silence_mask: SilenceMask::MONO_SILENT,
+                in_constant_mask: ConstantMask::NONE_CONSTANT,
+                out_constant_mask: ConstantMask::NONE_CONSTANT,
+                in_connected_mask: ConnectedMask::MONO_CONNECTED,
+                out_connected_mask: ConnectedMask::MONO_CONNECTED,
+                prev_output_was_silent: false,
+                sample_rate: stream_info.sample_rate,
+                sample_rate_recip: stream_info.sample_rate_recip,
+                clock_samples: InstantSamples::default(),
+                duration_since_stream_start: Duration::ZERO,
+                stream_status: StreamStatus::empty(),
+                dropped_frames: 0,
+                #[cfg(feature = "musical_transport")]
+                transport_info: None,
+                global_wet: 1.0,
+            };
+
+            let mut indices = Vec::new();
+            let mut events = ProcEvents::new(
+                &mut [],
+                #[cfg(feature = "scheduled_events")]
+                &mut [],
+                &mut indices,
+            );
+
+            let status = processor.process(
+                &info,
+                ProcBuffers {
+                    inputs: &[in_block],
+                    outputs: &mut [out_block],
+                },
+                &mut events,
+                &mut extra,
+            );
+            if let ProcessStatus::ClearAllOutputs = status {
+                out_block.fill(0.0);
+            }
+        }
+        output
+    }
+
+    #[test]
+    fn tracks_step_input() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let stream_info = StreamInfo {
+            sample_rate,
+            sample_rate_recip: (sample_rate.get() as f64).recip(),
+            ..Default::default()
+        };
+        let node = EnvelopeFollowerNode {
+            detector: EnvelopeFollowerConfig {
+                attack_secs: 0.01,
+                release_secs: 0.05,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let mut custom_state: Option<Box<dyn Any + Send>> =
+            Some(Box::new(EnvelopeFollowerState::new()));
+        let state = custom_state
+            .as_ref()
+            .and_then(|s| s.downcast_ref::<EnvelopeFollowerState>())
+            .unwrap()
+            .clone();
+        let mut processor = node.construct_processor(
+            &EmptyConfig,
+            ConstructProcessorContext::new(
+                NodeID::DANGLING,
+                &stream_info,
+                None,
+                false,
+                &mut custom_state,
+            ),
+        );
+
+        // A 100ms step up to full scale.
+        let envelope = render(&mut processor, &[1.0; 4_800], &stream_info);
+
+        // After one attack time constant the envelope is at `1 - 1/e`.
+        let attack_frames = 480;
+        assert!((envelope[attack_frames - 1] - 0.632).abs() < 0.01);
+        assert!(envelope[4_799] > 0.999);
+        assert_eq!(state.envelope(), envelope[4_799]);
+        assert!(state.envelope_db(-100.0).abs() < 0.01);
+
+        // After one release time constant of silence the envelope is at
+        // `1/e`.
+        let release_frames = 2_400;
+        let envelope = render(&mut processor, &[0.0; 4_800], &stream_info);
+        assert!((envelope[release_frames - 1] - 0.368).abs() < 0.01);
+        assert_eq!(state.envelope(), envelope[4_799]);
+        assert!(state.envelope() < 0.368);
+    }
+}
@@ -21,7 +21,7 @@ use firewheel_core::{
     StreamInfo,
 };
 
-use super::{MAX_HZ, MIN_HZ};
+use super::{clamp_cutoff_hz, MAX_HZ, MIN_HZ};
 
 pub type FastBandpassMonoNode = FastBandpassNode<1>;
 pub type FastBandpassStereoNode = FastBandpassNode<2>;
@@ -106,21 +106,15 @@ impl<const CHANNELS: usize> AudioNode for FastBandpassNode<CHANNELS> {
 
         Processor {
             lpf: OnePoleIirLPFSimd::default(),
-            lpf_coeff: OnePoleIirLPFCoeffSimd::<CHANNELS>::splat(OnePoleIirLPFCoeff::new(
-                cutoff_hz,
-                sample_rate_recip,
-            )),
+            lpf_coeff: lpf_coeff::<CHANNELS>(cutoff_hz, sample_rate_recip),
             hpf: OnePoleIirHPFSimd::default(),
-            hpf_coeff: OnePoleIirHPFCoeffSimd::<CHANNELS>::splat(OnePoleIirHPFCoeff::new(
-                cutoff_hz,
-                sample_rate_recip,
-            )),
+            hpf_coeff: hpf_coeff::<CHANNELS>(cutoff_hz, sample_rate_recip),
             cutoff_hz: SmoothedParam::new(
                 cutoff_hz,
-                SmootherConfig {
+                cx.smoother_config(SmootherConfig {
                     smooth_seconds: self.smooth_seconds,
                     ..Default::default()
-                },
+                }),
                 cx.stream_info.sample_rate,
             ),
             enable_declicker: Declicker::from_enabled(self.enabled),
@@ -129,6 +123,30 @@ impl<const CHANNELS: usize> AudioNode for FastBandpassNode<CHANNELS> {
     }
 }
 
+/// The coefficients of the lowpass filter for the given cutoff frequency,
+/// clamped below the Nyquist frequency.
+fn lpf_coeff<const CHANNELS: usize>(
+    cutoff_hz: f32,
+    sample_rate_recip: f32,
+) -> OnePoleIirLPFCoeffSimd<CHANNELS> {
+    OnePoleIirLPFCoeffSimd::splat(OnePoleIirLPFCoeff::new(
+        clamp_cutoff_hz(cutoff_hz, sample_rate_recip),
+        sample_rate_recip,
+    ))
+}
+
+/// The coefficients of the highpass filter for the given cutoff frequency,
+/// clamped below the Nyquist frequency.
+fn hpf_coeff<const CHANNELS: usize>(
+    cutoff_hz: f32,
+    sample_rate_recip: f32,
+) -> OnePoleIirHPFCoeffSimd<CHANNELS> {
+    OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
+        clamp_cutoff_hz(cutoff_hz, sample_rate_recip),
+        sample_rate_recip,
+    ))
+}
+
 struct Processor<const CHANNELS: usize> {
     lpf: OnePoleIirLPFSimd<CHANNELS>,
     hpf: OnePoleIirHPFSimd<CHANNELS>,
@@ -209,14 +227,8 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
                 //
                 // TODO: Alternatively, this could be optimized using a lookup table
                 if self.coeff_update_mask.do_update(i) {
-                    self.lpf_coeff = OnePoleIirLPFCoeffSimd::splat(OnePoleIirLPFCoeff::new(
-                        cutoff_hz,
-                        info.sample_rate_recip as f32,
-                    ));
-                    self.hpf_coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
-                        cutoff_hz,
-                        info.sample_rate_recip as f32,
-                    ));
+                    self.lpf_coeff = lpf_coeff(cutoff_hz, info.sample_rate_recip as f32);
+                    self.hpf_coeff = hpf_coeff(cutoff_hz, info.sample_rate_recip as f32);
                 }
 
                 let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -236,27 +248,19 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
             }
 
             if self.cutoff_hz.settle() {
-                self.lpf_coeff = OnePoleIirLPFCoeffSimd::splat(OnePoleIirLPFCoeff::new(
-                    self.cutoff_hz.target_value(),
-                    info.sample_rate_recip as f32,
-                ));
-                self.hpf_coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
-                    self.cutoff_hz.target_value(),
-                    info.sample_rate_recip as f32,
-                ));
+                self.lpf_coeff =
+                    lpf_coeff(self.cutoff_hz.target_value(), info.sample_rate_recip as f32);
+                self.hpf_coeff =
+                    hpf_coeff(self.cutoff_hz.target_value(), info.sample_rate_recip as f32);
             }
         } else {
             // The cutoff parameter is not currently smoothing, so we can optimize by
             // only updating the filter coefficients once.
             if cutoff_changed {
-                self.lpf_coeff = OnePoleIirLPFCoeffSimd::splat(OnePoleIirLPFCoeff::new(
-                    self.cutoff_hz.target_value(),
-                    info.sample_rate_recip as f32,
-                ));
-                self.hpf_coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
-                    self.cutoff_hz.target_value(),
-                    info.sample_rate_recip as f32,
-                ));
+                self.lpf_coeff =
+                    lpf_coeff(self.cutoff_hz.target_value(), info.sample_rate_recip as f32);
+                self.hpf_coeff =
+                    hpf_coeff(self.cutoff_hz.target_value(), info.sample_rate_recip as f32);
             }
 
             for i in 0..info.frames {
@@ -291,13 +295,13 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
 
     fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
         self.cutoff_hz.update_sample_rate(stream_info.sample_rate);
-        self.lpf_coeff = OnePoleIirLPFCoeffSimd::splat(OnePoleIirLPFCoeff::new(
+        self.lpf_coeff = lpf_coeff(
             self.cutoff_hz.target_value(),
             stream_info.sample_rate_recip as f32,
-        ));
-        self.hpf_coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
+        );
+        self.hpf_coeff = hpf_coeff(
             self.cutoff_hz.target_value(),
             stream_info.sample_rate_recip as f32,
-        ));
+        );
     }
 }
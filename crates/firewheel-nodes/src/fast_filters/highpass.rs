@@ -18,7 +18,7 @@ use firewheel_core::{
     StreamInfo,
 };
 
-use super::{MAX_HZ, MIN_HZ};
+use super::{clamp_cutoff_hz, MAX_HZ, MIN_HZ};
 
 pub type FastHighpassMonoNode = FastHighpassNode<1>;
 pub type FastHighpassStereoNode = FastHighpassNode<2>;
@@ -103,16 +103,13 @@ impl<const CHANNELS: usize> AudioNode for FastHighpassNode<CHANNELS> {
 
         Processor {
             filter: OnePoleIirHPFSimd::default(),
-            coeff: OnePoleIirHPFCoeffSimd::<CHANNELS>::splat(OnePoleIirHPFCoeff::new(
-                cutoff_hz,
-                sample_rate_recip,
-            )),
+            coeff: coeff::<CHANNELS>(cutoff_hz, sample_rate_recip),
             cutoff_hz: SmoothedParam::new(
                 cutoff_hz,
-                SmootherConfig {
+                cx.smoother_config(SmootherConfig {
                     smooth_seconds: self.smooth_seconds,
                     ..Default::default()
-                },
+                }),
                 cx.stream_info.sample_rate,
             ),
             enable_declicker: Declicker::from_enabled(self.enabled),
@@ -121,6 +118,18 @@ impl<const CHANNELS: usize> AudioNode for FastHighpassNode<CHANNELS> {
     }
 }
 
+/// The coefficients of the filter for the given cutoff frequency, clamped
+/// below the Nyquist frequency.
+fn coeff<const CHANNELS: usize>(
+    cutoff_hz: f32,
+    sample_rate_recip: f32,
+) -> OnePoleIirHPFCoeffSimd<CHANNELS> {
+    OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
+        clamp_cutoff_hz(cutoff_hz, sample_rate_recip),
+        sample_rate_recip,
+    ))
+}
+
 struct Processor<const CHANNELS: usize> {
     filter: OnePoleIirHPFSimd<CHANNELS>,
     coeff: OnePoleIirHPFCoeffSimd<CHANNELS>,
@@ -198,10 +207,7 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
                 //
                 // TODO: Alternatively, this could be optimized using a lookup table
                 if self.coeff_update_mask.do_update(i) {
-                    self.coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
-                        cutoff_hz,
-                        info.sample_rate_recip as f32,
-                    ));
+                    self.coeff = coeff(cutoff_hz, info.sample_rate_recip as f32);
                 }
 
                 let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -220,19 +226,13 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
             }
 
             if self.cutoff_hz.settle() {
-                self.coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
-                    self.cutoff_hz.target_value(),
-                    info.sample_rate_recip as f32,
-                ));
+                self.coeff = coeff(self.cutoff_hz.target_value(), info.sample_rate_recip as f32);
             }
         } else {
             // The cutoff parameter is not currently smoothing, so we can optimize by
             // only updating the filter coefficients once.
             if cutoff_changed {
-                self.coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
-                    self.cutoff_hz.target_value(),
-                    info.sample_rate_recip as f32,
-                ));
+                self.coeff = coeff(self.cutoff_hz.target_value(), info.sample_rate_recip as f32);
             }
 
             for i in 0..info.frames {
@@ -266,9 +266,9 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
 
     fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
         self.cutoff_hz.update_sample_rate(stream_info.sample_rate);
-        self.coeff = OnePoleIirHPFCoeffSimd::splat(OnePoleIirHPFCoeff::new(
+        self.coeff = coeff(
             self.cutoff_hz.target_value(),
             stream_info.sample_rate_recip as f32,
-        ));
+        );
     }
 }
@@ -4,3 +4,38 @@ pub mod lowpass;
 
 pub const MIN_HZ: f32 = 20.0;
 pub const MAX_HZ: f32 = 20_480.0;
+
+/// The highest cutoff frequency the filters will use, as a fraction of the
+/// sample rate.
+///
+/// At low sample rates [`MAX_HZ`] can be above the Nyquist frequency, where
+/// the filters become unstable.
+pub const MAX_CUTOFF_RATIO: f32 = 0.49;
+
+/// Clamp a cutoff frequency to the range `[MIN_HZ, MAX_HZ]`, and to below
+/// the Nyquist frequency of the given sample rate.
+pub fn clamp_cutoff_hz(cutoff_hz: f32, sample_rate_recip: f32) -> f32 {
+    cutoff_hz
+        .clamp(MIN_HZ, MAX_HZ)
+        .min(MAX_CUTOFF_RATIO / sample_rate_recip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cutoff_never_exceeds_nyquist() {
+        let sample_rate = 22_050.0;
+        let nyquist = sample_rate * 0.5;
+
+        for cutoff_hz in [MIN_HZ, 1_000.0, 10_000.0, 11_025.0, 15_000.0, MAX_HZ] {
+            let clamped = clamp_cutoff_hz(cutoff_hz, 1.0 / sample_rate);
+            assert!(clamped < nyquist, "{cutoff_hz} was clamped to {clamped}");
+        }
+
+        // Cutoffs below the limit are left alone.
+        assert_eq!(clamp_cutoff_hz(1_000.0, 1.0 / sample_rate), 1_000.0);
+        assert_eq!(clamp_cutoff_hz(1.0, 1.0 / sample_rate), MIN_HZ);
+    }
+}
@@ -98,6 +98,7 @@ impl AudioNode for FastRmsNode {
                 num_outputs: ChannelCount::ZERO,
             })
             .custom_state(FastRmsState::new())
+            .analysis_only(true)
     }
 
     fn construct_processor(
@@ -4,10 +4,15 @@
 #![allow(missing_docs)]
 #![allow(clippy::module_inception)]
 
+use core::num::NonZeroU32;
+
 use firewheel_core::{
     channel_config::{ChannelConfig, ChannelCount},
     diff::{Diff, Notify, Patch},
-    dsp::declick::{DeclickFadeCurve, DeclickValues, Declicker},
+    dsp::{
+        declick::{DeclickFadeCurve, DeclickValues, Declicker},
+        tail_gate::{TailGate, TailGateConfig},
+    },
     event::ProcEvents,
     node::{
         AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
@@ -59,6 +64,12 @@ pub struct FreeverbNode {
     ///
     /// Defaults to `0.015` (15ms).
     pub smooth_seconds: f32,
+
+    /// A gate that cuts the reverb tail once the input has been quiet for
+    /// a set amount of time, for the classic "gated reverb" drum sound.
+    ///
+    /// By default the gate is disabled.
+    pub gate: TailGateConfig,
 }
 
 impl Default for FreeverbNode {
@@ -70,6 +81,7 @@ impl Default for FreeverbNode {
             pause: false,
             reset: Notify::new(()),
             smooth_seconds: 0.015,
+            gate: TailGateConfig::default(),
         }
     }
 }
@@ -91,40 +103,18 @@ impl AudioNode for FreeverbNode {
         _: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        let freeverb = freeverb::Freeverb::new(cx.stream_info.sample_rate.get() as usize);
-        let smoother_config = SmootherConfig {
-            smooth_seconds: self.smooth_seconds,
-            ..Default::default()
-        };
-
-        let mut processor = FreeverbProcessor {
-            freeverb,
-            damping: SmoothedParam::new(
-                self.damping.clamp(0.0, 1.0),
-                smoother_config,
-                cx.stream_info.sample_rate,
-            ),
-            width: SmoothedParam::new(
-                self.width.clamp(0.0, 1.0),
-                smoother_config,
-                cx.stream_info.sample_rate,
-            ),
-            room_size: SmoothedParam::new(
-                self.room_size.clamp(0.0, 1.0),
-                smoother_config,
-                cx.stream_info.sample_rate,
-            ),
-            paused: self.pause,
-            declicker: if self.pause {
-                Declicker::SettledAt0
-            } else {
-                Declicker::SettledAt1
-            },
-            values: DeclickValues::new(cx.stream_info.declick_frames),
-        };
-
-        processor.apply_parameters();
-
+        let mut processor = FreeverbProcessor::new(
+            self,
+            cx.stream_info.sample_rate,
+            cx.stream_info.declick_frames,
+        );
+        for param in [
+            &mut processor.damping,
+            &mut processor.width,
+            &mut processor.room_size,
+        ] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
         processor
     }
 }
@@ -137,6 +127,7 @@ struct FreeverbProcessor {
     paused: bool,
     declicker: Declicker,
     values: DeclickValues,
+    gate: TailGate,
 }
 
 impl AudioNodeProcessor for FreeverbProcessor {
@@ -171,6 +162,11 @@ impl AudioNodeProcessor for FreeverbProcessor {
                         self.declicker.fade_to_1(&self.values);
                     }
                 }
+                FreeverbNodePatch::Gate(patch) => {
+                    let mut gate = *self.gate.config();
+                    gate.apply(patch);
+                    self.gate.set_config(gate, proc_info.sample_rate);
+                }
                 FreeverbNodePatch::SmoothSeconds(value) => {
                     self.room_size
                         .set_smooth_seconds(value, proc_info.sample_rate);
@@ -204,47 +200,11 @@ impl AudioNodeProcessor for FreeverbProcessor {
             self.apply_parameters();
         }
 
-        // just take the slow path if any are smoothing
-        if self.damping.is_smoothing() || self.room_size.is_smoothing() || self.width.is_smoothing()
-        {
-            for frame in 0..proc_info.frames {
-                let damping = self.damping.next_smoothed();
-                let room_size = self.room_size.next_smoothed();
-                let width = self.width.next_smoothed();
-
-                // we assume setting these values is more expensive than
-                // calculating their smoothing
-                if frame.is_multiple_of(4) {
-                    self.freeverb.set_dampening(damping as f64);
-                    self.freeverb.set_room_size(room_size as f64);
-                    self.freeverb.set_width(width as f64);
-
-                    self.freeverb.update_combs();
-                }
-
-                let (left, right) = self.freeverb.tick((
-                    buffers.inputs[0][frame] as f64,
-                    buffers.inputs[1][frame] as f64,
-                ));
-
-                buffers.outputs[0][frame] = left as f32;
-                buffers.outputs[1][frame] = right as f32;
-            }
-
-            self.damping.settle();
-            self.room_size.settle();
-            self.width.settle();
-        } else {
-            for frame in 0..proc_info.frames {
-                let (left, right) = self.freeverb.tick((
-                    buffers.inputs[0][frame] as f64,
-                    buffers.inputs[1][frame] as f64,
-                ));
-
-                buffers.outputs[0][frame] = left as f32;
-                buffers.outputs[1][frame] = right as f32;
-            }
-        }
+        self.render(
+            [buffers.inputs[0], buffers.inputs[1]],
+            buffers.outputs,
+            proc_info.frames,
+        );
 
         // We do this before the declicking just to make sure we
         // finish declicking if we're paused simultaneously with the
@@ -282,10 +242,97 @@ impl AudioNodeProcessor for FreeverbProcessor {
         self.damping.update_sample_rate(stream_info.sample_rate);
         self.width.update_sample_rate(stream_info.sample_rate);
         self.room_size.update_sample_rate(stream_info.sample_rate);
+        self.gate
+            .set_config(*self.gate.config(), stream_info.sample_rate);
+    }
+
+    fn flush_idle_state(&mut self) {
+        // Clear out any residual tail that fell below the silence threshold.
+        self.freeverb.reset();
     }
 }
 
 impl FreeverbProcessor {
+    fn new(params: &FreeverbNode, sample_rate: NonZeroU32, declick_frames: NonZeroU32) -> Self {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+
+        let mut processor = Self {
+            freeverb: freeverb::Freeverb::new(sample_rate.get() as usize),
+            damping: SmoothedParam::new(
+                params.damping.clamp(0.0, 1.0),
+                smoother_config,
+                sample_rate,
+            ),
+            width: SmoothedParam::new(params.width.clamp(0.0, 1.0), smoother_config, sample_rate),
+            room_size: SmoothedParam::new(
+                params.room_size.clamp(0.0, 1.0),
+                smoother_config,
+                sample_rate,
+            ),
+            paused: params.pause,
+            declicker: if params.pause {
+                Declicker::SettledAt0
+            } else {
+                Declicker::SettledAt1
+            },
+            values: DeclickValues::new(declick_frames),
+            gate: TailGate::new(params.gate, sample_rate),
+        };
+
+        processor.apply_parameters();
+
+        processor
+    }
+
+    /// Run the reverb over a block of frames, gating the tail if the gate
+    /// is enabled.
+    fn render(&mut self, inputs: [&[f32]; 2], outputs: &mut [&mut [f32]], frames: usize) {
+        // just take the slow path if any are smoothing
+        if self.damping.is_smoothing() || self.room_size.is_smoothing() || self.width.is_smoothing()
+        {
+            for frame in 0..frames {
+                let damping = self.damping.next_smoothed();
+                let room_size = self.room_size.next_smoothed();
+                let width = self.width.next_smoothed();
+
+                // we assume setting these values is more expensive than
+                // calculating their smoothing
+                if frame.is_multiple_of(4) {
+                    self.freeverb.set_dampening(damping as f64);
+                    self.freeverb.set_room_size(room_size as f64);
+                    self.freeverb.set_width(width as f64);
+
+                    self.freeverb.update_combs();
+                }
+
+                let (left, right) = self
+                    .freeverb
+                    .tick((inputs[0][frame] as f64, inputs[1][frame] as f64));
+
+                outputs[0][frame] = left as f32;
+                outputs[1][frame] = right as f32;
+            }
+
+            self.damping.settle();
+            self.room_size.settle();
+            self.width.settle();
+        } else {
+            for frame in 0..frames {
+                let (left, right) = self
+                    .freeverb
+                    .tick((inputs[0][frame] as f64, inputs[1][frame] as f64));
+
+                outputs[0][frame] = left as f32;
+                outputs[1][frame] = right as f32;
+            }
+        }
+
+        self.gate.process_block(&inputs, &mut outputs[..2], frames);
+    }
+
     fn apply_parameters(&mut self) {
         self.freeverb
             .set_dampening(self.damping.target_value() as f64);
@@ -295,3 +342,70 @@ impl FreeverbProcessor {
         self.freeverb.update_combs();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feed a short burst into a long reverb and return the output.
+    fn render_burst(gate: TailGateConfig, sample_rate: NonZeroU32) -> Vec<f32> {
+        let mut processor = FreeverbProcessor::new(
+            &FreeverbNode {
+                room_size: 0.9,
+                gate,
+                ..Default::default()
+            },
+            sample_rate,
+            NonZeroU32::MIN,
+        );
+
+        let frames = sample_rate.get() as usize;
+        let burst_frames = frames / 100;
+        let input: Vec<f32> = (0..frames)
+            .map(|i| if i < burst_frames { 0.5 } else { 0.0 })
+            .collect();
+
+        let mut left = vec![0.0; frames];
+        let mut right = vec![0.0; frames];
+        for (block, (l, r)) in input
+            .chunks(256)
+            .zip(left.chunks_mut(256).zip(right.chunks_mut(256)))
+        {
+            processor.render([block, block], &mut [l, r], block.len());
+        }
+
+        left
+    }
+
+    #[test]
+    fn gated_tail_is_cut_at_hold_time() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let gate = TailGateConfig {
+            enabled: true,
+            threshold_db: -40.0,
+            hold_secs: 0.2,
+            release_secs: 0.005,
+        };
+
+        let gated = render_burst(gate, sample_rate);
+        let ungated = render_burst(TailGateConfig::default(), sample_rate);
+
+        let burst_end = 480;
+        let hold_end = burst_end + 9_600;
+        let closed = hold_end + 240;
+
+        let peak = |s: &[f32]| s.iter().fold(0.0f32, |m, x| m.max(x.abs()));
+
+        // While the gate is held open, the tail is untouched.
+        assert_eq!(
+            gated[burst_end..hold_end - 1],
+            ungated[burst_end..hold_end - 1]
+        );
+        assert!(peak(&gated[hold_end - 2_400..hold_end]) > 0.001);
+
+        // After the hold and release, the gated tail is silent while the
+        // ungated tail is still ringing.
+        assert!(gated[closed..].iter().all(|&s| s == 0.0));
+        assert!(peak(&ungated[closed..closed + 4_800]) > 0.001);
+    }
+}
@@ -0,0 +1,209 @@
+//! A node that converts trigger/release events into an audio-rate gate signal.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+/// A node that outputs an audio-rate gate signal which is `1.0` while the
+/// gate is open and `0.0` while it is closed (Mono output only).
+///
+/// Route the output to the modulation input of another node to drive a
+/// VCA or an envelope from events.
+///
+/// Changes to [`GateSignalNode::gate`] take effect at the start of the
+/// processing block they arrive in. Send them as scheduled events to place
+/// the edges of the gate on an exact frame.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GateSignalNode {
+    /// Whether the gate is open (`true`) or closed (`false`).
+    ///
+    /// By default this is set to `false`.
+    pub gate: bool,
+    /// The time in seconds it takes the output to rise from `0.0` to `1.0`
+    /// when the gate opens.
+    ///
+    /// By default this is set to `0.001` (1 millisecond).
+    pub rise_seconds: f32,
+    /// The time in seconds it takes the output to fall from `1.0` to `0.0`
+    /// when the gate closes.
+    ///
+    /// By default this is set to `0.001` (1 millisecond).
+    pub fall_seconds: f32,
+}
+
+impl Default for GateSignalNode {
+    fn default() -> Self {
+        Self {
+            gate: false,
+            rise_seconds: 0.001,
+            fall_seconds: 0.001,
+        }
+    }
+}
+
+impl GateSignalNode {
+    /// Open the gate.
+    pub fn trigger(&mut self) {
+        self.gate = true;
+    }
+
+    /// Close the gate.
+    pub fn release(&mut self) {
+        self.gate = false;
+    }
+}
+
+impl AudioNode for GateSignalNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("gate_signal")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: ChannelCount::MONO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor {
+    params: GateSignalNode,
+    value: f32,
+    sample_rate: NonZeroU32,
+}
+
+impl Processor {
+    fn new(params: GateSignalNode, sample_rate: NonZeroU32) -> Self {
+        Self {
+            params,
+            value: if params.gate { 1.0 } else { 0.0 },
+            sample_rate,
+        }
+    }
+
+    /// The amount the output moves each frame over a ramp of the given
+    /// length. Ramps shorter than a frame jump straight to the target.
+    fn step(&self, seconds: f32) -> f32 {
+        let frames = seconds * self.sample_rate.get() as f32;
+        if frames > 1.0 {
+            frames.recip()
+        } else {
+            1.0
+        }
+    }
+
+    /// Fill `out` with the gate signal, ramping towards the current state
+    /// of the gate.
+    fn render(&mut self, out: &mut [f32]) {
+        let target = if self.params.gate { 1.0 } else { 0.0 };
+
+        if self.value == target {
+            out.fill(target);
+            return;
+        }
+
+        if self.params.gate {
+            let step = self.step(self.params.rise_seconds);
+            for s in out.iter_mut() {
+                // Snap to the top to keep rounding errors from adding up.
+                self.value += step;
+                if self.value > 1.0 - step * 0.5 {
+                    self.value = 1.0;
+                }
+                *s = self.value;
+            }
+        } else {
+            let step = self.step(self.params.fall_seconds);
+            for s in out.iter_mut() {
+                self.value -= step;
+                if self.value < step * 0.5 {
+                    self.value = 0.0;
+                }
+                *s = self.value;
+            }
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<GateSignalNode>() {
+            self.params.apply(patch);
+        }
+
+        if !self.params.gate && self.value == 0.0 {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.render(&mut buffers.outputs[0][..info.frames]);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edges_start_at_event_offset() {
+        let mut processor = Processor::new(
+            GateSignalNode {
+                rise_seconds: 0.001,
+                fall_seconds: 0.002,
+                ..Default::default()
+            },
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        // The graph splits a block at each scheduled event, so an event at
+        // frame 100 of a block arrives at the start of a sub-block.
+        let mut out = vec![0.0; 512];
+        processor.render(&mut out[..100]);
+        processor.params.trigger();
+        processor.render(&mut out[100..300]);
+        processor.params.release();
+        processor.render(&mut out[300..]);
+
+        // Rising edge at frame 100, reaching the top after 48 frames.
+        assert!(out[..100].iter().all(|&s| s == 0.0));
+        assert!(out[100] > 0.0);
+        assert!(out[100..148].windows(2).all(|w| w[1] > w[0]));
+        assert!(out[147..300].iter().all(|&s| s == 1.0));
+
+        // Falling edge at frame 300, reaching the bottom after 96 frames.
+        assert!(out[300] < 1.0);
+        assert!(out[300..396].windows(2).all(|w| w[1] < w[0]));
+        assert!(out[395..].iter().all(|&s| s == 0.0));
+    }
+}
@@ -42,10 +42,131 @@ pub mod fast_rms;
 #[cfg(feature = "triple_buffer")]
 pub mod triple_buffer;
 
+#[cfg(feature = "phaser")]
+pub mod phaser;
+
+#[cfg(feature = "phase_rotator")]
+pub mod phase_rotator;
+
+#[cfg(feature = "phasor")]
+pub mod phasor;
+
+#[cfg(feature = "onset_detector")]
+pub mod onset_detector;
+
+#[cfg(feature = "dc_blocker")]
+pub mod dc_blocker;
+
+#[cfg(feature = "envelope_follower")]
+pub mod envelope_follower;
+
+#[cfg(feature = "compressor")]
+pub mod compressor;
+
+#[cfg(feature = "stereo_rotate")]
+pub mod stereo_rotate;
+
+#[cfg(feature = "stereo_delay")]
+pub mod stereo_delay;
+
+#[cfg(feature = "smoother")]
+pub mod smoother;
+
+#[cfg(feature = "beat_repeat")]
+pub mod beat_repeat;
+
+#[cfg(feature = "reverse")]
+pub mod reverse;
+
+#[cfg(feature = "phase_probe")]
+pub mod phase_probe;
+
+#[cfg(feature = "crossfeed")]
+pub mod crossfeed;
+
+#[cfg(feature = "de_esser")]
+pub mod de_esser;
+
+#[cfg(feature = "sample_hold")]
+pub mod sample_hold;
+
+#[cfg(feature = "multiband_splitter")]
+pub mod multiband_splitter;
+
+#[cfg(feature = "wav_recorder")]
+pub mod wav_recorder;
+
+#[cfg(feature = "resample")]
+pub mod resample;
+
+#[cfg(feature = "biquad")]
+pub mod biquad;
+
+#[cfg(feature = "allpass_delay")]
+pub mod allpass_delay;
+
+#[cfg(feature = "gate_signal")]
+pub mod gate_signal;
+
+#[cfg(feature = "spectral_gate")]
+pub mod spectral_gate;
+
+#[cfg(feature = "frequency_shift")]
+pub mod frequency_shift;
+
+#[cfg(feature = "pitch_shift")]
+pub mod pitch_shift;
+
+#[cfg(feature = "auto_wah")]
+pub mod auto_wah;
+
+#[cfg(feature = "constant")]
+pub mod constant;
+
+#[cfg(feature = "smart_sum")]
+pub mod smart_sum;
+
+#[cfg(feature = "oscillator")]
+pub mod oscillator;
+
+#[cfg(feature = "spectral_freeze")]
+pub mod spectral_freeze;
+
+#[cfg(feature = "channel_strip")]
+pub mod channel_strip;
+
+#[cfg(feature = "quantized_trigger")]
+pub mod quantized_trigger;
+
+#[cfg(feature = "tap")]
+pub mod tap;
+
+#[cfg(feature = "envelope")]
+pub mod envelope;
+
+#[cfg(feature = "multiband_compressor")]
+pub mod multiband_compressor;
+
+#[cfg(any(feature = "beat_repeat", feature = "reverse"))]
+mod delay_buffer;
+
+#[cfg(any(
+    feature = "spectral_gate",
+    feature = "pitch_shift",
+    feature = "spectral_freeze"
+))]
+mod stft;
+
+mod splitter;
+mod stereo_split;
 mod stereo_to_mono;
 
-pub use stereo_to_mono::StereoToMonoNode;
+pub use splitter::{SplitterNode, SplitterNodeConfig};
+pub use stereo_split::StereoSplitNode;
+pub use stereo_to_mono::{StereoToMonoMode, StereoToMonoNode};
 
 pub mod volume_pan;
 
+pub mod polarity;
+
 pub mod volume;
@@ -1,3 +1,5 @@
+use core::num::NonZeroU32;
+
 use firewheel_core::{
     channel_config::{ChannelConfig, ChannelCount, NonZeroChannelCount},
     diff::{Diff, Patch},
@@ -43,7 +45,7 @@ impl Default for MixNodeConfig {
 ///
 /// The first half of the inputs are the first signal, and the second half are the
 /// second signal.
-#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[derive(Diff, Patch, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -68,10 +70,19 @@ pub struct MixNode {
     /// By default this is set to [`FadeCurve::EqualPower3dB`].
     pub fade_curve: FadeCurve,
 
-    /// The time in seconds of the internal smoothing filter.
+    /// The time in seconds of the smoothing filter applied to
+    /// [`MixNode::volume`].
     ///
     /// By default this is set to `0.015` (15ms).
     pub smooth_seconds: f32,
+    /// The time in seconds of the smoothing filter applied to
+    /// [`MixNode::mix`] (and to changes of [`MixNode::fade_curve`]).
+    ///
+    /// Increase this to make rapid automation of the mix value crossfade
+    /// more gently between the two signals.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub mix_smooth_seconds: f32,
     /// If the resutling gain (in raw amplitude, not decibels) is less
     /// than or equal to this value, then the gain will be clamped to
     /// `0.0` (silence).
@@ -87,6 +98,7 @@ impl MixNode {
             mix,
             fade_curve: FadeCurve::EqualPower3dB,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            mix_smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
     }
@@ -97,6 +109,7 @@ impl MixNode {
             mix,
             fade_curve: FadeCurve::EqualPower3dB,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            mix_smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
     }
@@ -127,7 +140,7 @@ impl MixNode {
     pub fn compute_gains(&self, amp_epsilon: f32) -> (f32, f32) {
         let global_gain = self.volume.amp_clamped(amp_epsilon);
 
-        let (mut gain_0, mut gain_1) = self.mix.compute_gains(self.fade_curve);
+        let (mut gain_0, mut gain_1) = self.mix.compute_gains(&self.fade_curve);
 
         gain_0 *= global_gain;
         gain_1 *= global_gain;
@@ -150,6 +163,7 @@ impl Default for MixNode {
             mix: Mix::FULLY_FIRST,
             fade_curve: FadeCurve::default(),
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            mix_smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
     }
@@ -179,40 +193,154 @@ impl AudioNode for MixNode {
         _config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        let min_gain = self.min_gain.max(0.0);
+        let mut processor = Processor::new(self.clone(), cx.stream_info.sample_rate);
+        for param in [
+            &mut processor.gain_0,
+            &mut processor.gain_1,
+            &mut processor.volume,
+        ] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+    }
+}
 
-        let (gain_0, gain_1) = self.compute_gains(self.min_gain);
+struct Processor {
+    /// The gains of the two signals from the mix value alone.
+    gain_0: SmoothedParam,
+    gain_1: SmoothedParam,
+    /// The overall volume, smoothed separately from the mix.
+    volume: SmoothedParam,
 
-        Processor {
-            gain_0: SmoothedParam::new(
-                gain_0,
-                SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
-                    ..Default::default()
-                },
-                cx.stream_info.sample_rate,
-            ),
-            gain_1: SmoothedParam::new(
-                gain_1,
+    params: MixNode,
+
+    min_gain: f32,
+}
+
+impl Processor {
+    fn new(params: MixNode, sample_rate: NonZeroU32) -> Self {
+        let (gain_0, gain_1) = mix_gains(&params);
+        let volume = params.volume.amp_clamped(params.min_gain);
+
+        let mix_config = SmootherConfig {
+            smooth_seconds: params.mix_smooth_seconds,
+            ..Default::default()
+        };
+
+        Self {
+            gain_0: SmoothedParam::new(gain_0, mix_config, sample_rate),
+            gain_1: SmoothedParam::new(gain_1, mix_config, sample_rate),
+            volume: SmoothedParam::new(
+                volume,
                 SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
+                    smooth_seconds: params.smooth_seconds,
                     ..Default::default()
                 },
-                cx.stream_info.sample_rate,
+                sample_rate,
             ),
-            params: *self,
-            min_gain,
+            min_gain: params.min_gain.max(0.0),
+            params,
+        }
+    }
+
+    fn apply_patch(&mut self, mut patch: MixNodePatch, sample_rate: NonZeroU32) {
+        match &mut patch {
+            MixNodePatch::Mix(m) => {
+                if m.get() <= 0.00001 {
+                    *m = Mix::new(0.0);
+                } else if m.get() >= 0.99999 {
+                    *m = Mix::new(1.0);
+                }
+            }
+            MixNodePatch::SmoothSeconds(seconds) => {
+                self.volume.set_smooth_seconds(*seconds, sample_rate);
+            }
+            MixNodePatch::MixSmoothSeconds(seconds) => {
+                self.gain_0.set_smooth_seconds(*seconds, sample_rate);
+                self.gain_1.set_smooth_seconds(*seconds, sample_rate);
+            }
+            MixNodePatch::MinGain(min_gain) => {
+                self.min_gain = (*min_gain).max(0.0);
+            }
+            _ => {}
+        }
+
+        self.params.apply(patch);
+
+        let (gain_0, gain_1) = mix_gains(&self.params);
+        self.gain_0.set_value(gain_0);
+        self.gain_1.set_value(gain_1);
+        self.volume
+            .set_value(self.params.volume.amp_clamped(self.min_gain));
+    }
+
+    fn reset_to_target(&mut self) {
+        self.gain_0.reset_to_target();
+        self.gain_1.reset_to_target();
+        self.volume.reset_to_target();
+    }
+
+    fn has_settled(&self) -> bool {
+        self.gain_0.has_settled() && self.gain_1.has_settled() && self.volume.has_settled()
+    }
+
+    fn settle(&mut self) {
+        self.gain_0.settle();
+        self.gain_1.settle();
+        self.volume.settle();
+    }
+
+    /// Get the next pair of gains, including the overall volume.
+    #[inline]
+    fn next_gains(&mut self) -> (f32, f32) {
+        let volume = self.volume.next_smoothed();
+        (
+            self.gain_0.next_smoothed() * volume,
+            self.gain_1.next_smoothed() * volume,
+        )
+    }
+
+    /// The pair of gains (including the overall volume) once smoothing has
+    /// settled.
+    fn target_gains(&self) -> (f32, f32) {
+        let volume = self.volume.target_value();
+        (
+            self.gain_0.target_value() * volume,
+            self.gain_1.target_value() * volume,
+        )
+    }
+
+    fn mix_mono(&mut self, in0: &[f32], in1: &[f32], out: &mut [f32]) {
+        if self.has_settled() {
+            let (gain_0, gain_1) = self.target_gains();
+
+            for ((&in0_s, &in1_s), out_s) in in0.iter().zip(in1.iter()).zip(out.iter_mut()) {
+                *out_s = (in0_s * gain_0) + (in1_s * gain_1);
+            }
+        } else {
+            for ((&in0_s, &in1_s), out_s) in in0.iter().zip(in1.iter()).zip(out.iter_mut()) {
+                let (gain_0, gain_1) = self.next_gains();
+
+                *out_s = (in0_s * gain_0) + (in1_s * gain_1);
+            }
+
+            self.settle();
         }
     }
 }
 
-struct Processor {
-    gain_0: SmoothedParam,
-    gain_1: SmoothedParam,
+/// The gains of the two signals from the mix value alone.
+fn mix_gains(params: &MixNode) -> (f32, f32) {
+    let (mut gain_0, mut gain_1) = params.mix.compute_gains(&params.fade_curve);
 
-    params: MixNode,
+    if gain_0 > 0.99999 && gain_0 < 1.00001 {
+        gain_0 = 1.0;
+    }
+    if gain_1 > 0.99999 && gain_1 < 1.00001 {
+        gain_1 = 1.0;
+    }
 
-    min_gain: f32,
+    (gain_0, gain_1)
 }
 
 impl AudioNodeProcessor for Processor {
@@ -224,62 +352,39 @@ impl AudioNodeProcessor for Processor {
         extra: &mut ProcExtra,
     ) -> ProcessStatus {
         let mut updated = false;
-        for mut patch in events.drain_patches::<MixNode>() {
-            match &mut patch {
-                MixNodePatch::Mix(m) => {
-                    if m.get() <= 0.00001 {
-                        *m = Mix::new(0.0);
-                    } else if m.get() >= 0.99999 {
-                        *m = Mix::new(1.0);
-                    }
-                }
-                MixNodePatch::SmoothSeconds(seconds) => {
-                    self.gain_0.set_smooth_seconds(*seconds, info.sample_rate);
-                    self.gain_1.set_smooth_seconds(*seconds, info.sample_rate);
-                }
-                MixNodePatch::MinGain(min_gain) => {
-                    self.min_gain = (*min_gain).max(0.0);
-                }
-                _ => {}
-            }
-
-            self.params.apply(patch);
+        for patch in events.drain_patches::<MixNode>() {
+            self.apply_patch(patch, info.sample_rate);
             updated = true;
         }
 
-        if updated {
-            let (gain_0, gain_1) = self.params.compute_gains(self.min_gain);
-            self.gain_0.set_value(gain_0);
-            self.gain_1.set_value(gain_1);
-
-            if info.prev_output_was_silent {
-                // Previous block was silent, so no need to smooth.
-                self.gain_0.reset_to_target();
-                self.gain_1.reset_to_target();
-            }
+        if updated && info.prev_output_was_silent {
+            // Previous block was silent, so no need to smooth.
+            self.reset_to_target();
         }
 
         let channels = buffers.outputs.len();
 
-        let gain_0_silent = self.gain_0.has_settled_at_or_below(self.min_gain);
-        let gain_1_silent = self.gain_1.has_settled_at_or_below(self.min_gain);
-        let has_settled = self.gain_0.has_settled() && self.gain_1.has_settled();
+        let volume_silent = self.volume.has_settled_at_or_below(self.min_gain);
+        let gain_0_silent = volume_silent || self.gain_0.has_settled_at_or_below(self.min_gain);
+        let gain_1_silent = volume_silent || self.gain_1.has_settled_at_or_below(self.min_gain);
+        let has_settled = self.has_settled();
 
         if (gain_0_silent && gain_1_silent)
             || info
                 .in_silence_mask
                 .all_channels_silent(buffers.inputs.len())
         {
-            self.gain_0.reset_to_target();
-            self.gain_1.reset_to_target();
+            self.reset_to_target();
 
             return ProcessStatus::ClearAllOutputs;
         }
 
         let mut out_silence_mask = SilenceMask::NONE_SILENT;
 
+        let (target_gain_0, target_gain_1) = self.target_gains();
+
         if has_settled {
-            if self.params.mix.get() == 0.0 && self.gain_0.target_value() == 1.0 {
+            if self.params.mix.get() == 0.0 && target_gain_0 == 1.0 {
                 // Simply copy input 0 to output
                 for (ch_i, (in_ch, out_ch)) in buffers.inputs[..channels]
                     .iter()
@@ -298,7 +403,7 @@ impl AudioNodeProcessor for Processor {
                 }
 
                 return ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(out_silence_mask));
-            } else if self.params.mix.get() == 1.0 && self.gain_1.target_value() == 1.0 {
+            } else if self.params.mix.get() == 1.0 && target_gain_1 == 1.0 {
                 // Simply copy input 1 to output
                 for (ch_i, (in_ch, out_ch)) in buffers.inputs[channels..]
                     .iter()
@@ -323,31 +428,11 @@ impl AudioNodeProcessor for Processor {
         match channels {
             1 => {
                 // Provide an optimized loop for mono
-
-                if has_settled {
-                    for ((&in0_s, &in1_s), out_s) in buffers.inputs[0]
-                        .iter()
-                        .zip(buffers.inputs[1].iter())
-                        .zip(buffers.outputs[0].iter_mut())
-                    {
-                        *out_s = (in0_s * self.gain_0.target_value())
-                            + (in1_s * self.gain_1.target_value());
-                    }
-                } else {
-                    for ((&in0_s, &in1_s), out_s) in buffers.inputs[0]
-                        .iter()
-                        .zip(buffers.inputs[1].iter())
-                        .zip(buffers.outputs[0].iter_mut())
-                    {
-                        let gain_0 = self.gain_0.next_smoothed();
-                        let gain_1 = self.gain_1.next_smoothed();
-
-                        *out_s = (in0_s * gain_0) + (in1_s * gain_1);
-                    }
-
-                    self.gain_0.settle();
-                    self.gain_1.settle();
-                }
+                self.mix_mono(
+                    &buffers.inputs[0][..info.frames],
+                    &buffers.inputs[1][..info.frames],
+                    &mut buffers.outputs[0][..info.frames],
+                );
             }
             2 => {
                 // Provide an optimized loop for stereo
@@ -363,22 +448,18 @@ impl AudioNodeProcessor for Processor {
 
                 if has_settled {
                     for i in 0..info.frames {
-                        out_l[i] = (in0_l[i] * self.gain_0.target_value())
-                            + (in1_l[i] * self.gain_1.target_value());
-                        out_r[i] = (in0_r[i] * self.gain_0.target_value())
-                            + (in1_r[i] * self.gain_1.target_value());
+                        out_l[i] = (in0_l[i] * target_gain_0) + (in1_l[i] * target_gain_1);
+                        out_r[i] = (in0_r[i] * target_gain_0) + (in1_r[i] * target_gain_1);
                     }
                 } else {
                     for i in 0..info.frames {
-                        let gain_0 = self.gain_0.next_smoothed();
-                        let gain_1 = self.gain_1.next_smoothed();
+                        let (gain_0, gain_1) = self.next_gains();
 
                         out_l[i] = (in0_l[i] * gain_0) + (in1_l[i] * gain_1);
                         out_r[i] = (in0_r[i] * gain_0) + (in1_r[i] * gain_1);
                     }
 
-                    self.gain_0.settle();
-                    self.gain_1.settle();
+                    self.settle();
                 }
             }
             _ => {
@@ -406,17 +487,28 @@ impl AudioNodeProcessor for Processor {
                             for ((&in0_s, &in1_s), out_s) in
                                 in0_ch.iter().zip(in1_ch.iter()).zip(out_ch.iter_mut())
                             {
-                                *out_s = (in0_s * self.gain_0.target_value())
-                                    + (in1_s * self.gain_1.target_value());
+                                *out_s = (in0_s * target_gain_0) + (in1_s * target_gain_1);
                             }
                         }
                     }
                 } else {
-                    let [gain_0_buf, gain_1_buf] = extra.scratch_buffers.channels_mut::<2>();
+                    let [gain_0_buf, gain_1_buf, volume_buf] =
+                        extra.scratch_buffers.channels_mut::<3>();
                     self.gain_0
                         .process_into_buffer(&mut gain_0_buf[..info.frames]);
                     self.gain_1
                         .process_into_buffer(&mut gain_1_buf[..info.frames]);
+                    self.volume
+                        .process_into_buffer(&mut volume_buf[..info.frames]);
+
+                    for ((g0, g1), &v) in gain_0_buf[..info.frames]
+                        .iter_mut()
+                        .zip(gain_1_buf[..info.frames].iter_mut())
+                        .zip(volume_buf[..info.frames].iter())
+                    {
+                        *g0 *= v;
+                        *g1 *= v;
+                    }
 
                     for (ch_i, ((in0_ch, in1_ch), out_ch)) in buffers.inputs[0..channels]
                         .iter()
@@ -450,8 +542,7 @@ impl AudioNodeProcessor for Processor {
                         }
                     }
 
-                    self.gain_0.settle();
-                    self.gain_1.settle();
+                    self.settle();
                 }
             }
         }
@@ -466,5 +557,53 @@ impl AudioNodeProcessor for Processor {
     ) {
         self.gain_0.update_sample_rate(stream_info.sample_rate);
         self.gain_1.update_sample_rate(stream_info.sample_rate);
+        self.volume.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mix_step_crossfades_over_mix_smooth_seconds() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(
+            MixNode {
+                fade_curve: FadeCurve::Linear,
+                mix_smooth_seconds: 0.01,
+                ..Default::default()
+            },
+            sample_rate,
+        );
+
+        processor.apply_patch(MixNodePatch::Mix(Mix::FULLY_SECOND), sample_rate);
+
+        // Input 0 is a constant `1.0` and input 1 is silent, so the output is
+        // the gain of the first signal.
+        let in0 = vec![1.0; 9_600];
+        let in1 = vec![0.0; 9_600];
+        let mut out = vec![0.0; 9_600];
+        for ((in0, in1), out) in in0
+            .chunks(256)
+            .zip(in1.chunks(256))
+            .zip(out.chunks_mut(256))
+        {
+            processor.mix_mono(in0, in1, out);
+        }
+
+        // The step does not jump, but fades out smoothly over about the
+        // configured time.
+        assert!(out[0] > 0.9);
+        assert!(out.windows(2).all(|w| w[1] <= w[0]));
+        assert!(out[240] > 0.1 && out[240] < 0.9);
+        assert!(out[4_800] < 0.01);
+        assert_eq!(*out.last().unwrap(), 0.0);
+
+        // The volume is smoothed separately, so a volume change leaves the
+        // mix smoothing time untouched.
+        processor.apply_patch(MixNodePatch::Volume(Volume::Linear(0.5)), sample_rate);
+        assert!(processor.gain_0.has_settled() && processor.gain_1.has_settled());
+        assert!(!processor.volume.has_settled());
     }
 }
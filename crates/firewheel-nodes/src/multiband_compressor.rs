@@ -0,0 +1,427 @@
+//! A compressor that splits a stereo signal into bands and compresses each
+//! band separately.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::ChannelConfig,
+    diff::{Diff, Patch},
+    dsp::{
+        envelope_follower::{EnvelopeFollower, EnvelopeFollowerConfig, Knee, LIMITER_RATIO},
+        filter::{
+            crossover::{max_crossover_hz, Lr4Crossover, Lr4CrossoverCoeffs, MIN_CROSSOVER_HZ},
+            svf::SvfState,
+        },
+        volume::{amp_to_db, db_to_amp, DEFAULT_AMP_EPSILON},
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+/// The largest number of bands a [`MultibandCompressorNode`] can have.
+pub const MAX_BANDS: usize = 6;
+
+/// The settings of a single band of a [`MultibandCompressorNode`].
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CompressorBand {
+    /// The level in decibels at which compression of this band begins.
+    ///
+    /// By default this is set to `-20.0`.
+    pub threshold_db: f32,
+    /// The ratio of compression (i.e. `4.0` means `4:1`). Values less than
+    /// `1.0` are treated as `1.0` (no compression), and values of at least
+    /// [`LIMITER_RATIO`] are treated as infinite.
+    ///
+    /// By default this is set to `1.0`.
+    pub ratio: f32,
+    /// The width in decibels of the soft knee around the threshold. A value
+    /// of `0.0` gives a hard knee.
+    ///
+    /// By default this is set to `6.0`.
+    pub knee_db: f32,
+    /// The time in seconds it takes the detector to rise towards a louder
+    /// level.
+    ///
+    /// By default this is set to `0.005` (5ms).
+    pub attack_secs: f32,
+    /// The time in seconds it takes the detector to fall towards a quieter
+    /// level.
+    ///
+    /// By default this is set to `0.1` (100ms).
+    pub release_secs: f32,
+}
+
+impl Default for CompressorBand {
+    fn default() -> Self {
+        let detector = EnvelopeFollowerConfig::default();
+
+        Self {
+            threshold_db: -20.0,
+            ratio: 1.0,
+            knee_db: 6.0,
+            attack_secs: detector.attack_secs,
+            release_secs: detector.release_secs,
+        }
+    }
+}
+
+impl CompressorBand {
+    fn knee(&self) -> Knee {
+        Knee {
+            threshold_db: self.threshold_db,
+            ratio: if self.ratio >= LIMITER_RATIO {
+                f32::INFINITY
+            } else {
+                self.ratio
+            },
+            knee_db: self.knee_db,
+        }
+    }
+
+    fn detector(&self) -> EnvelopeFollowerConfig {
+        EnvelopeFollowerConfig {
+            attack_secs: self.attack_secs,
+            release_secs: self.release_secs,
+            ..Default::default()
+        }
+    }
+}
+
+/// The configuration of a [`MultibandCompressorNode`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultibandCompressorConfig {
+    /// The number of bands, in the range `[1, MAX_BANDS]`.
+    ///
+    /// By default this is set to `3`.
+    pub num_bands: usize,
+}
+
+impl Default for MultibandCompressorConfig {
+    fn default() -> Self {
+        Self { num_bands: 3 }
+    }
+}
+
+/// A multiband compressor (Stereo input, stereo output).
+///
+/// The input is split into [`MultibandCompressorConfig::num_bands`] bands
+/// with fourth order Linkwitz-Riley crossovers, each band is compressed with
+/// its own settings, and the bands are summed back together. The lower bands
+/// are passed through allpass filters matching the crossovers above them, so
+/// all bands stay phase aligned and the sum has a flat frequency response
+/// while no compression is happening. The crossovers add no latency.
+///
+/// The detector of each band is stereo-linked, so the same gain change is
+/// applied to both channels of a band.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultibandCompressorNode {
+    /// The crossover frequencies in hertz between each pair of neighboring
+    /// bands, from lowest to highest. Only the first `num_bands - 1` are
+    /// used, and each one is kept at or above the one before it.
+    ///
+    /// By default this is set to `[200.0, 2000.0, 6000.0, 10000.0, 15000.0]`.
+    pub crossovers_hz: [f32; MAX_BANDS - 1],
+    /// The settings of each band, from lowest to highest. Only the first
+    /// `num_bands` are used.
+    pub bands: [CompressorBand; MAX_BANDS],
+}
+
+impl Default for MultibandCompressorNode {
+    fn default() -> Self {
+        Self {
+            crossovers_hz: [200.0, 2_000.0, 6_000.0, 10_000.0, 15_000.0],
+            bands: [CompressorBand::default(); MAX_BANDS],
+        }
+    }
+}
+
+impl AudioNode for MultibandCompressorNode {
+    type Configuration = MultibandCompressorConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("multiband_compressor")
+            .channel_config(ChannelConfig::new(2, 2))
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, config.num_bands, cx.stream_info.sample_rate)
+    }
+}
+
+/// The crossover filters of a single channel.
+#[derive(Default, Clone, Copy)]
+struct ChannelFilters {
+    crossovers: [Lr4Crossover; MAX_BANDS - 1],
+    /// The allpass filters each band passes through to match the phase of
+    /// the crossovers above it, indexed by band and then by crossover.
+    allpass: [[SvfState; MAX_BANDS - 1]; MAX_BANDS - 1],
+}
+
+impl ChannelFilters {
+    /// Split a sample into its bands.
+    #[inline]
+    fn split(&mut self, s: f32, coeffs: &Coeffs, num_bands: usize) -> [f32; MAX_BANDS] {
+        let mut bands = [0.0; MAX_BANDS];
+        let num_crossovers = num_bands - 1;
+
+        let mut rest = s;
+        for (band_i, band) in bands[..num_crossovers].iter_mut().enumerate() {
+            let [mut low, high] = self.crossovers[band_i].split(rest, &coeffs.crossovers[band_i]);
+            rest = high;
+
+            for crossover_i in band_i + 1..num_crossovers {
+                low = self.allpass[band_i][crossover_i]
+                    .process(low, &coeffs.crossovers[crossover_i].allpass);
+            }
+
+            *band = low;
+        }
+        bands[num_crossovers] = rest;
+
+        bands
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+struct Coeffs {
+    crossovers: [Lr4CrossoverCoeffs; MAX_BANDS - 1],
+}
+
+impl Coeffs {
+    fn new(params: &MultibandCompressorNode, sample_rate: NonZeroU32) -> Self {
+        let sample_rate_recip = (sample_rate.get() as f32).recip();
+        let max_hz = max_crossover_hz(sample_rate);
+
+        let mut crossovers_hz = [0.0; MAX_BANDS - 1];
+        let mut min_hz = MIN_CROSSOVER_HZ;
+        for (hz, param_hz) in crossovers_hz.iter_mut().zip(params.crossovers_hz) {
+            *hz = param_hz.clamp(min_hz, max_hz);
+            min_hz = *hz;
+        }
+
+        Self {
+            crossovers: crossovers_hz.map(|hz| Lr4CrossoverCoeffs::new(hz, sample_rate_recip)),
+        }
+    }
+}
+
+struct Processor {
+    params: MultibandCompressorNode,
+    num_bands: usize,
+    coeffs: Coeffs,
+    knees: [Knee; MAX_BANDS],
+    filters: [ChannelFilters; 2],
+    followers: [EnvelopeFollower; MAX_BANDS],
+    sample_rate: NonZeroU32,
+}
+
+impl Processor {
+    fn new(params: MultibandCompressorNode, num_bands: usize, sample_rate: NonZeroU32) -> Self {
+        Self {
+            params,
+            num_bands: num_bands.clamp(1, MAX_BANDS),
+            coeffs: Coeffs::new(&params, sample_rate),
+            knees: params.bands.map(|band| band.knee()),
+            filters: [ChannelFilters::default(); 2],
+            followers: params
+                .bands
+                .map(|band| EnvelopeFollower::new(band.detector(), sample_rate)),
+            sample_rate,
+        }
+    }
+
+    fn update_params(&mut self) {
+        self.coeffs = Coeffs::new(&self.params, self.sample_rate);
+
+        for ((knee, follower), band) in self
+            .knees
+            .iter_mut()
+            .zip(self.followers.iter_mut())
+            .zip(self.params.bands.iter())
+        {
+            *knee = band.knee();
+            follower.set_config(band.detector(), self.sample_rate);
+        }
+    }
+
+    /// Split a block of stereo frames into bands, compress each band, and
+    /// sum them back together into `outputs`.
+    fn compress(&mut self, inputs: [&[f32]; 2], outputs: &mut [&mut [f32]], frames: usize) {
+        let num_bands = self.num_bands;
+
+        for i in 0..frames {
+            let left = self.filters[0].split(inputs[0][i], &self.coeffs, num_bands);
+            let right = self.filters[1].split(inputs[1][i], &self.coeffs, num_bands);
+
+            let mut out = [0.0; 2];
+            for band_i in 0..num_bands {
+                // Feed the detector the level of the louder channel.
+                let linked = left[band_i].abs().max(right[band_i].abs());
+                let envelope = self.followers[band_i].process(linked);
+                let gain = db_to_amp(self.knees[band_i].compressor_gain_db(amp_to_db(envelope)));
+
+                out[0] += left[band_i] * gain;
+                out[1] += right[band_i] * gain;
+            }
+
+            outputs[0][i] = out[0];
+            outputs[1][i] = out[1];
+        }
+    }
+
+    fn reset(&mut self) {
+        for filters in self.filters.iter_mut() {
+            filters.reset();
+        }
+        for follower in self.followers.iter_mut() {
+            follower.reset();
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        let mut params_changed = false;
+        for patch in events.drain_patches::<MultibandCompressorNode>() {
+            self.params.apply(patch);
+            params_changed = true;
+        }
+
+        if params_changed {
+            self.update_params();
+        }
+
+        if info.in_silence_mask.all_channels_silent(2) && info.prev_output_was_silent {
+            self.reset();
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.compress(
+            [buffers.inputs[0], buffers.inputs[1]],
+            buffers.outputs,
+            info.frames,
+        );
+
+        // Let the short tail of the filters ring out before declaring the
+        // output silent.
+        buffers.check_for_silence_on_outputs(DEFAULT_AMP_EPSILON)
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.update_params();
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    /// A tone which alternates between a loud and a quiet section every
+    /// quarter of a second.
+    fn pulsing_tone(freq_hz: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize * 2)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                let amp = if (i / (SAMPLE_RATE as usize / 4)).is_multiple_of(2) {
+                    0.5
+                } else {
+                    0.05
+                };
+                amp * (core::f32::consts::TAU * freq_hz * t).sin()
+            })
+            .collect()
+    }
+
+    /// The difference in decibels between the RMS of the last loud and the
+    /// last quiet section, skipping the start of each section while the
+    /// detector settles.
+    fn dynamics_db(s: &[f32]) -> f32 {
+        let section = SAMPLE_RATE as usize / 4;
+        let rms_db = |start: usize| {
+            let s = &s[start + section / 2..start + section];
+            let rms = (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt();
+            20.0 * rms.log10()
+        };
+
+        rms_db(section * 6) - rms_db(section * 7)
+    }
+
+    fn compress(params: MultibandCompressorNode, input: &[f32]) -> Vec<f32> {
+        let mut processor = Processor::new(params, 3, NonZeroU32::new(SAMPLE_RATE).unwrap());
+
+        let mut left = vec![0.0; input.len()];
+        let mut right = vec![0.0; input.len()];
+        processor.compress(
+            [input, input],
+            &mut [left.as_mut_slice(), right.as_mut_slice()],
+            input.len(),
+        );
+        assert_eq!(left, right);
+
+        left
+    }
+
+    #[test]
+    fn compressing_high_band_leaves_low_band_dynamics_intact() {
+        let mut params = MultibandCompressorNode::default();
+        params.bands[2] = CompressorBand {
+            threshold_db: -40.0,
+            ratio: 8.0,
+            knee_db: 0.0,
+            // Release quickly enough to settle within each section.
+            release_secs: 0.02,
+            ..Default::default()
+        };
+
+        // The low band is not compressed, so it keeps its 20dB of dynamics.
+        let low = pulsing_tone(80.0);
+        assert!((dynamics_db(&low) - 20.0).abs() < 0.1);
+        let low_dynamics_db = dynamics_db(&compress(params, &low));
+        assert!(
+            (low_dynamics_db - 20.0).abs() < 0.5,
+            "low band dynamics changed to {low_dynamics_db}dB"
+        );
+
+        // The high band is squashed.
+        let high_dynamics_db = dynamics_db(&compress(params, &pulsing_tone(8_000.0)));
+        assert!(
+            high_dynamics_db < 5.0,
+            "high band dynamics only reduced to {high_dynamics_db}dB"
+        );
+
+        // With no compression at all, the bands sum back to the input.
+        let input = pulsing_tone(2_000.0);
+        let out = compress(MultibandCompressorNode::default(), &input);
+        assert!((dynamics_db(&out) - dynamics_db(&input)).abs() < 0.1);
+    }
+}
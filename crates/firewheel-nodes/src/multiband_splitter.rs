@@ -0,0 +1,369 @@
+//! A node that splits a stereo signal into low, mid, and high bands.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::ChannelConfig,
+    diff::{Diff, Patch},
+    dsp::{
+        filter::{
+            crossover::{max_crossover_hz, Lr4Crossover, Lr4CrossoverCoeffs, MIN_CROSSOVER_HZ},
+            smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+            svf::SvfState,
+        },
+        volume::{Volume, DEFAULT_AMP_EPSILON},
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The number of bands the signal is split into.
+pub const NUM_BANDS: usize = 3;
+
+/// The settings of a single band of a [`MultibandSplitterNode`].
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultibandBand {
+    /// The gain applied to this band.
+    ///
+    /// By default this is set to [`Volume::UNITY_GAIN`].
+    pub gain: Volume,
+    /// If `true`, then this band is soloed. While any band is soloed, the
+    /// bands that are not soloed are silenced.
+    ///
+    /// By default this is set to `false`.
+    pub solo: bool,
+}
+
+impl Default for MultibandBand {
+    fn default() -> Self {
+        Self {
+            gain: Volume::UNITY_GAIN,
+            solo: false,
+        }
+    }
+}
+
+/// A three band crossover (Stereo input, three stereo outputs).
+///
+/// The input is split into low, mid, and high bands with fourth order
+/// Linkwitz-Riley crossovers. The bands are output on ports `0-1` (low),
+/// `2-3` (mid), and `4-5` (high). Connecting all of the bands back into a
+/// single node sums them, and with every band at unity gain and nothing
+/// soloed, the sum has a flat frequency response.
+///
+/// Each band has its own gain and solo, so pairing this node with a summer
+/// gives a simple multiband utility for mastering. Put other effects on
+/// individual bands in between to process them separately.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MultibandSplitterNode {
+    /// The crossover frequency in hertz between the low and mid bands.
+    ///
+    /// By default this is set to `200.0`.
+    pub low_crossover_hz: f32,
+    /// The crossover frequency in hertz between the mid and high bands. This
+    /// is kept at or above [`MultibandSplitterNode::low_crossover_hz`].
+    ///
+    /// By default this is set to `2000.0`.
+    pub high_crossover_hz: f32,
+    /// The settings of the low, mid, and high bands (in that order).
+    pub bands: [MultibandBand; NUM_BANDS],
+    /// The time in seconds of the internal smoothing filter for the band
+    /// gains.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for MultibandSplitterNode {
+    fn default() -> Self {
+        Self {
+            low_crossover_hz: 200.0,
+            high_crossover_hz: 2_000.0,
+            bands: [MultibandBand::default(); NUM_BANDS],
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl MultibandSplitterNode {
+    /// The gain of each band after soloing is taken into account.
+    fn band_gains(&self) -> [f32; NUM_BANDS] {
+        let any_soloed = self.bands.iter().any(|band| band.solo);
+
+        self.bands.map(|band| {
+            if any_soloed && !band.solo {
+                0.0
+            } else {
+                band.gain.amp_clamped(DEFAULT_AMP_EPSILON)
+            }
+        })
+    }
+}
+
+impl AudioNode for MultibandSplitterNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("multiband_splitter")
+            .channel_config(ChannelConfig::new(2, 2 * NUM_BANDS))
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(*self, cx.stream_info.sample_rate);
+        for gain in processor.gains.iter_mut() {
+            gain.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+    }
+}
+
+/// The crossover filters of a single channel.
+#[derive(Default, Clone, Copy)]
+struct ChannelFilters {
+    low: Lr4Crossover,
+    high: Lr4Crossover,
+    /// Matches the phase of the low band to the phase of the mid and high
+    /// bands after the high crossover.
+    low_allpass: SvfState,
+}
+
+impl ChannelFilters {
+    /// Split a sample into its low, mid, and high bands.
+    #[inline]
+    fn split(&mut self, s: f32, coeffs: &Coeffs) -> [f32; NUM_BANDS] {
+        let [low, rest] = self.low.split(s, &coeffs.low);
+        let [mid, high] = self.high.split(rest, &coeffs.high);
+
+        [
+            self.low_allpass.process(low, &coeffs.high.allpass),
+            mid,
+            high,
+        ]
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+struct Coeffs {
+    low: Lr4CrossoverCoeffs,
+    high: Lr4CrossoverCoeffs,
+}
+
+impl Coeffs {
+    fn new(params: &MultibandSplitterNode, sample_rate: NonZeroU32) -> Self {
+        let sample_rate_recip = (sample_rate.get() as f32).recip();
+        let max_hz = max_crossover_hz(sample_rate);
+
+        let low_hz = params.low_crossover_hz.clamp(MIN_CROSSOVER_HZ, max_hz);
+        let high_hz = params.high_crossover_hz.clamp(low_hz, max_hz);
+
+        Self {
+            low: Lr4CrossoverCoeffs::new(low_hz, sample_rate_recip),
+            high: Lr4CrossoverCoeffs::new(high_hz, sample_rate_recip),
+        }
+    }
+}
+
+struct Processor {
+    params: MultibandSplitterNode,
+    coeffs: Coeffs,
+    filters: [ChannelFilters; 2],
+    gains: [SmoothedParam; NUM_BANDS],
+    sample_rate: NonZeroU32,
+}
+
+impl Processor {
+    fn new(params: MultibandSplitterNode, sample_rate: NonZeroU32) -> Self {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+
+        Self {
+            params,
+            coeffs: Coeffs::new(&params, sample_rate),
+            filters: [ChannelFilters::default(); 2],
+            gains: params
+                .band_gains()
+                .map(|gain| SmoothedParam::new(gain, smoother_config, sample_rate)),
+            sample_rate,
+        }
+    }
+
+    /// Split a block of stereo frames into `outputs`, which holds the left
+    /// and right channels of each band in order.
+    fn split(&mut self, inputs: [&[f32]; 2], outputs: &mut [&mut [f32]], frames: usize) {
+        for i in 0..frames {
+            let gains = [
+                self.gains[0].next_smoothed(),
+                self.gains[1].next_smoothed(),
+                self.gains[2].next_smoothed(),
+            ];
+
+            for (ch, filters) in self.filters.iter_mut().enumerate() {
+                let bands = filters.split(inputs[ch][i], &self.coeffs);
+
+                for (band_i, band) in bands.iter().enumerate() {
+                    outputs[band_i * 2 + ch][i] = band * gains[band_i];
+                }
+            }
+        }
+
+        for gain in self.gains.iter_mut() {
+            gain.settle();
+        }
+    }
+
+    fn reset(&mut self) {
+        for filters in self.filters.iter_mut() {
+            filters.reset();
+        }
+        for gain in self.gains.iter_mut() {
+            gain.reset_to_target();
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        let mut params_changed = false;
+        for patch in events.drain_patches::<MultibandSplitterNode>() {
+            if let MultibandSplitterNodePatch::SmoothSeconds(seconds) = patch {
+                for gain in self.gains.iter_mut() {
+                    gain.set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+
+            self.params.apply(patch);
+            params_changed = true;
+        }
+
+        if params_changed {
+            self.coeffs = Coeffs::new(&self.params, self.sample_rate);
+
+            for (gain, target) in self.gains.iter_mut().zip(self.params.band_gains()) {
+                gain.set_value(target);
+            }
+
+            if info.prev_output_was_silent {
+                // Previous block was silent, so no need to smooth.
+                for gain in self.gains.iter_mut() {
+                    gain.reset_to_target();
+                }
+            }
+        }
+
+        if info.in_silence_mask.all_channels_silent(2) && info.prev_output_was_silent {
+            self.reset();
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.split(
+            [buffers.inputs[0], buffers.inputs[1]],
+            buffers.outputs,
+            info.frames,
+        );
+
+        // Let the short tail of the filters ring out before declaring the
+        // output silent.
+        buffers.check_for_silence_on_outputs(DEFAULT_AMP_EPSILON)
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.coeffs = Coeffs::new(&self.params, stream_info.sample_rate);
+        for gain in self.gains.iter_mut() {
+            gain.update_sample_rate(stream_info.sample_rate);
+        }
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn tone(freq_hz: f32) -> Vec<f32> {
+        (0..SAMPLE_RATE as usize / 2)
+            .map(|i| {
+                let t = i as f32 / SAMPLE_RATE as f32;
+                0.5 * (core::f32::consts::TAU * freq_hz * t).sin()
+            })
+            .collect()
+    }
+
+    /// Split the input and sum all of the bands back together.
+    fn split_and_sum(params: MultibandSplitterNode, input: &[f32]) -> Vec<f32> {
+        let mut processor = Processor::new(params, NonZeroU32::new(SAMPLE_RATE).unwrap());
+
+        let mut bands = vec![vec![0.0; input.len()]; 2 * NUM_BANDS];
+        let mut outputs: Vec<&mut [f32]> = bands.iter_mut().map(|b| b.as_mut_slice()).collect();
+        processor.split([input, input], &mut outputs, input.len());
+
+        (0..input.len())
+            .map(|i| bands.iter().step_by(2).map(|band| band[i]).sum())
+            .collect()
+    }
+
+    fn rms_db(s: &[f32]) -> f32 {
+        // Skip the start while the filters settle.
+        let s = &s[4_800..];
+        let rms = (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt();
+        20.0 * rms.log10()
+    }
+
+    #[test]
+    fn soloed_band_silences_others_and_unsoloed_sum_is_flat() {
+        // With nothing soloed, the summed bands keep the level of the input
+        // at every frequency, including right at the crossovers.
+        for freq_hz in [50.0, 200.0, 700.0, 2_000.0, 8_000.0] {
+            let input = tone(freq_hz);
+            let sum = split_and_sum(MultibandSplitterNode::default(), &input);
+            let diff_db = rms_db(&sum) - rms_db(&input);
+            assert!(diff_db.abs() < 0.05, "{freq_hz}Hz is off by {diff_db}dB");
+        }
+
+        let mut solo_mid = MultibandSplitterNode::default();
+        solo_mid.bands[1].solo = true;
+        // The gain of a band that is not soloed doesn't matter.
+        solo_mid.bands[0].gain = Volume::Decibels(6.0);
+
+        // Content in the low and high bands is silenced.
+        for freq_hz in [40.0, 12_000.0] {
+            let input = tone(freq_hz);
+            let sum = split_and_sum(solo_mid, &input);
+            let diff_db = rms_db(&sum) - rms_db(&input);
+            assert!(diff_db < -40.0, "{freq_hz}Hz only dropped by {diff_db}dB");
+        }
+
+        // Content in the soloed band passes.
+        let input = tone(630.0);
+        let sum = split_and_sum(solo_mid, &input);
+        assert!((rms_db(&sum) - rms_db(&input)).abs() < 0.5);
+    }
+}
@@ -6,15 +6,18 @@ use firewheel_core::{
     channel_config::{ChannelConfig, ChannelCount},
     diff::{Diff, Patch},
     dsp::{
+        dc_blocker::{DcBlocker, DEFAULT_DC_BLOCKER_HZ},
         filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        normalize::{Normalize, Normalizer, DEFAULT_NORMALIZE_WINDOW_SECS},
         volume::{Volume, DEFAULT_AMP_EPSILON},
     },
     event::ProcEvents,
     node::{
         AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
-        ProcExtra, ProcInfo, ProcessStatus,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
     },
     param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
 };
 
 const COEFF_A: [i32; 5] = [14055, 12759, 10733, 12273, 15716];
@@ -37,6 +40,15 @@ pub struct PinkNoiseGenNode {
     ///
     /// By default this is set to `0.015` (15ms).
     pub smooth_seconds: f32,
+    /// If `true`, then any DC offset is removed from the output.
+    ///
+    /// By default this is set to `false`.
+    pub dc_block: bool,
+    /// Scale the noise to a target peak or RMS level before
+    /// [`volume`](Self::volume) is applied.
+    ///
+    /// By default this is set to [`Normalize::Off`].
+    pub normalize: Normalize,
 }
 
 impl Default for PinkNoiseGenNode {
@@ -45,6 +57,8 @@ impl Default for PinkNoiseGenNode {
             volume: Volume::Linear(0.4),
             enabled: true,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            dc_block: false,
+            normalize: Normalize::Off,
         }
     }
 }
@@ -56,6 +70,9 @@ impl Default for PinkNoiseGenNode {
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct PinkNoiseGenConfig {
     /// The starting seed. This cannot be zero.
+    ///
+    /// If the context is running in deterministic mode, this is combined
+    /// with the seed the context assigns to this node.
     pub seed: i32,
 }
 
@@ -82,19 +99,28 @@ impl AudioNode for PinkNoiseGenNode {
         config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
+        let seed = match cx.deterministic_seed {
+            Some(node_seed) => config.seed ^ (node_seed ^ (node_seed >> 32)) as i32,
+            None => config.seed,
+        };
         // Seed cannot be zero.
-        let seed = if config.seed == 0 { 17 } else { config.seed };
+        let seed = if seed == 0 { 17 } else { seed };
 
         Processor {
             gain: SmoothedParam::new(
                 self.volume.amp_clamped(DEFAULT_AMP_EPSILON),
-                SmootherConfig {
+                cx.smoother_config(SmootherConfig {
                     smooth_seconds: self.smooth_seconds,
                     ..Default::default()
-                },
+                }),
                 cx.stream_info.sample_rate,
             ),
             params: *self,
+            dc_blocker: DcBlocker::new(
+                DEFAULT_DC_BLOCKER_HZ,
+                cx.stream_info.sample_rate_recip as f32,
+            ),
+            normalizer: Normalizer::new(DEFAULT_NORMALIZE_WINDOW_SECS, cx.stream_info.sample_rate),
             fpd: seed,
             contrib: [0; 5],
             accum: 0,
@@ -106,6 +132,8 @@ impl AudioNode for PinkNoiseGenNode {
 struct Processor {
     params: PinkNoiseGenNode,
     gain: SmoothedParam,
+    dc_blocker: DcBlocker,
+    normalizer: Normalizer,
 
     // white noise generator state
     fpd: i32,
@@ -139,10 +167,14 @@ impl AudioNodeProcessor for Processor {
 
         if !self.params.enabled || self.gain.has_settled_at_or_below(DEFAULT_AMP_EPSILON) {
             self.gain.reset_to_target();
+            self.dc_blocker.reset();
+            self.normalizer.reset();
             return ProcessStatus::ClearAllOutputs;
         }
 
-        for s in buffers.outputs[0].iter_mut() {
+        let out = &mut buffers.outputs[0][..info.frames];
+
+        for s in out.iter_mut() {
             // i16[0,32767]
             let randu: i16 = (rng(&mut self.fpd) & 0x7fff) as i16;
 
@@ -163,13 +195,30 @@ impl AudioNodeProcessor for Processor {
             }
 
             // Get a random normalized value in the range `[-1.0, 1.0]`.
-            let r = self.accum as f32 * (1.0 / 2_147_483_648.0);
+            let mut r = self.accum as f32 * (1.0 / 2_147_483_648.0);
+
+            if self.params.dc_block {
+                r = self.dc_blocker.process(r);
+            }
+
+            *s = r;
+        }
 
-            *s = r * self.gain.next_smoothed();
+        self.normalizer.process(self.params.normalize, out);
+
+        for s in out.iter_mut() {
+            *s *= self.gain.next_smoothed();
         }
 
         ProcessStatus::OutputsModified
     }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.dc_blocker
+            .set_cutoff_hz(DEFAULT_DC_BLOCKER_HZ, stream_info.sample_rate_recip as f32);
+        self.normalizer
+            .set_window(DEFAULT_NORMALIZE_WINDOW_SECS, stream_info.sample_rate);
+    }
 }
 
 #[inline(always)]
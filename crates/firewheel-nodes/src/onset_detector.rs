@@ -0,0 +1,307 @@
+//! A node that detects transients in a signal and reports them as events.
+
+use bevy_platform::sync::{Arc, Mutex};
+use core::num::NonZeroU32;
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    clock::{DurationSamples, InstantSamples},
+    diff::{Diff, Patch},
+    dsp::{
+        envelope_follower::{EnvelopeFollower, EnvelopeFollowerConfig},
+        volume::db_to_amp,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+use ringbuf::traits::{Consumer, Producer, Split};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The configuration of an [`OnsetDetectorNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnsetDetectorConfig {
+    /// The maximum number of onset events that can be queued before they
+    /// are read. If the queue is full, then new onsets are dropped.
+    ///
+    /// By default this is set to `64`.
+    pub event_capacity: usize,
+}
+
+impl Default for OnsetDetectorConfig {
+    fn default() -> Self {
+        Self { event_capacity: 64 }
+    }
+}
+
+/// A node that watches a mono signal for transients and emits an
+/// [`OnsetEvent`] for each one detected (Mono input only).
+///
+/// A sample counts as an onset when it is louder than
+/// [`OnsetDetectorNode::threshold_db`] and rises at least
+/// [`OnsetDetectorNode::sensitivity_db`] above the recent level of the
+/// signal. Use [`OnsetDetectorState::pop_event`] to read the detected
+/// onsets, for example to trigger sampler voices or visuals.
+#[derive(Debug, Diff, Patch, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OnsetDetectorNode {
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+    /// The level in decibels a transient must reach to count as an onset.
+    ///
+    /// By default this is set to `-40.0`.
+    pub threshold_db: f32,
+    /// How far in decibels a transient must rise above the recent level of
+    /// the signal to count as an onset. Lower values are more sensitive.
+    ///
+    /// By default this is set to `6.0`.
+    pub sensitivity_db: f32,
+    /// The minimum time in seconds between two onsets. Any transients
+    /// within this time after an onset are ignored.
+    ///
+    /// By default this is set to `0.05` (50ms).
+    pub min_interval_secs: f32,
+}
+
+impl Default for OnsetDetectorNode {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            threshold_db: -40.0,
+            sensitivity_db: 6.0,
+            min_interval_secs: 50.0 / 1_000.0,
+        }
+    }
+}
+
+/// A transient detected by an [`OnsetDetectorNode`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OnsetEvent {
+    /// The time of the onset on the audio clock.
+    pub time: InstantSamples,
+    /// The peak amplitude (linear) of the sample that triggered the onset.
+    pub intensity: f32,
+}
+
+/// The state of an [`OnsetDetectorNode`]. This contains the detected onsets.
+#[derive(Clone)]
+pub struct OnsetDetectorState {
+    consumer: Arc<Mutex<Option<ringbuf::HeapCons<OnsetEvent>>>>,
+}
+
+impl OnsetDetectorState {
+    fn new() -> Self {
+        Self {
+            consumer: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Pop the oldest detected onset, or `None` if there are no more.
+    pub fn pop_event(&self) -> Option<OnsetEvent> {
+        self.consumer.lock().unwrap().as_mut()?.try_pop()
+    }
+
+    /// Take all of the onsets that have been detected since the last call.
+    pub fn drain_events(&self) -> Vec<OnsetEvent> {
+        self.consumer
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(|c| c.pop_iter().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl AudioNode for OnsetDetectorNode {
+    type Configuration = OnsetDetectorConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("onset_detector")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::MONO,
+                num_outputs: ChannelCount::ZERO,
+            })
+            .custom_state(OnsetDetectorState::new())
+            .analysis_only(true)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        mut cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let (producer, consumer) = ringbuf::HeapRb::new(config.event_capacity.max(1)).split();
+
+        let state = cx.custom_state_mut::<OnsetDetectorState>().unwrap();
+        *state.consumer.lock().unwrap() = Some(consumer);
+
+        Processor::new(*self, producer, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor {
+    params: OnsetDetectorNode,
+    producer: ringbuf::HeapProd<OnsetEvent>,
+    background: EnvelopeFollower,
+    sample_rate: NonZeroU32,
+    min_interval_frames: u64,
+    frames_since_onset: u64,
+}
+
+impl Processor {
+    fn new(
+        params: OnsetDetectorNode,
+        producer: ringbuf::HeapProd<OnsetEvent>,
+        sample_rate: NonZeroU32,
+    ) -> Self {
+        Self {
+            params,
+            producer,
+            background: EnvelopeFollower::new(background_config(), sample_rate),
+            sample_rate,
+            min_interval_frames: interval_frames(params.min_interval_secs, sample_rate),
+            frames_since_onset: u64::MAX,
+        }
+    }
+
+    /// Scan `input` (which starts at `clock_samples`) for onsets.
+    fn detect(&mut self, input: &[f32], clock_samples: InstantSamples) {
+        let threshold = db_to_amp(self.params.threshold_db);
+        let rise = db_to_amp(self.params.sensitivity_db.max(0.0));
+
+        for (i, &s) in input.iter().enumerate() {
+            let level = s.abs();
+
+            if self.frames_since_onset >= self.min_interval_frames
+                && level >= threshold
+                && level > self.background.value() * rise
+            {
+                // If the queue is full then the onset is dropped.
+                let _ = self.producer.try_push(OnsetEvent {
+                    time: clock_samples + DurationSamples(i as i64),
+                    intensity: level,
+                });
+
+                self.frames_since_onset = 0;
+            }
+
+            self.background.process(s);
+            self.frames_since_onset = self.frames_since_onset.saturating_add(1);
+        }
+    }
+
+    fn reset(&mut self) {
+        self.background.reset();
+        self.frames_since_onset = u64::MAX;
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<OnsetDetectorNode>() {
+            if let OnsetDetectorNodePatch::MinIntervalSecs(secs) = patch {
+                self.min_interval_frames = interval_frames(secs, self.sample_rate);
+            }
+
+            self.params.apply(patch);
+        }
+
+        if !self.params.enabled {
+            self.reset();
+
+            return ProcessStatus::Bypass;
+        }
+
+        if info.in_silence_mask.is_channel_silent(0) {
+            // Let the recent level decay as if the silence was processed.
+            for _ in 0..info.frames {
+                self.background.process(0.0);
+            }
+            self.frames_since_onset = self.frames_since_onset.saturating_add(info.frames as u64);
+        } else {
+            self.detect(&buffers.inputs[0][..info.frames], info.clock_samples);
+        }
+
+        // There are no outputs in this node.
+        ProcessStatus::Bypass
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        if self.sample_rate != stream_info.sample_rate {
+            self.sample_rate = stream_info.sample_rate;
+            self.background = EnvelopeFollower::new(background_config(), self.sample_rate);
+            self.min_interval_frames =
+                interval_frames(self.params.min_interval_secs, self.sample_rate);
+        }
+
+        self.reset();
+    }
+}
+
+/// The envelope used to track the recent level of the signal. The attack
+/// is slow enough that the onset itself barely raises it.
+fn background_config() -> EnvelopeFollowerConfig {
+    EnvelopeFollowerConfig {
+        attack_secs: 0.01,
+        release_secs: 0.1,
+        ..Default::default()
+    }
+}
+
+fn interval_frames(secs: f32, sample_rate: NonZeroU32) -> u64 {
+    (secs.max(0.0) * sample_rate.get() as f32).round() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_event_per_impulse() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let (producer, mut consumer) = ringbuf::HeapRb::new(64).split();
+        let mut processor = Processor::new(OnsetDetectorNode::default(), producer, sample_rate);
+
+        // Five short decaying bursts spaced 250ms apart over a quiet noise
+        // floor. Each burst is several samples long, so it must be debounced
+        // into a single onset.
+        let spacing = 12_000;
+        let mut input: Vec<f32> = (0..5 * spacing)
+            .map(|i| if i % 2 == 0 { 0.001 } else { -0.001 })
+            .collect();
+        for n in 0..5 {
+            for (j, s) in input[n * spacing + 100..].iter_mut().take(32).enumerate() {
+                *s = 0.8 * (1.0 - j as f32 / 32.0) * if j % 2 == 0 { 1.0 } else { -1.0 };
+            }
+        }
+
+        for (block_i, block) in input.chunks(512).enumerate() {
+            processor.detect(block, InstantSamples((block_i * 512) as i64));
+        }
+
+        let events: Vec<OnsetEvent> = consumer.pop_iter().collect();
+        assert_eq!(events.len(), 5);
+        for (n, event) in events.iter().enumerate() {
+            assert_eq!(event.time, InstantSamples((n * spacing + 100) as i64));
+            assert!((event.intensity - 0.8).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,573 @@
+//! A band-limited oscillator with pulse-width modulation and hard sync.
+
+use core::num::NonZeroU32;
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        dc_blocker::{DcBlocker, DEFAULT_DC_BLOCKER_HZ},
+        filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        normalize::{Normalize, Normalizer, DEFAULT_NORMALIZE_WINDOW_SECS},
+        phase_accumulator::PhaseAccumulator,
+        volume::{Volume, DEFAULT_AMP_EPSILON},
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The narrowest and widest allowed pulse widths.
+const MIN_PULSE_WIDTH: f32 = 0.01;
+const MAX_PULSE_WIDTH: f32 = 0.99;
+
+/// The highest allowed hard sync ratio.
+const MAX_SYNC_RATIO: f32 = 16.0;
+
+/// The waveform of an [`OscillatorNode`]
+#[derive(Default, Diff, Patch, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Waveform {
+    #[default]
+    Sine,
+    /// A rising sawtooth wave.
+    Saw,
+    /// A pulse wave, which is high for [`OscillatorNode::pulse_width`] of
+    /// each cycle. A pulse width of `0.5` gives a square wave.
+    Pulse,
+}
+
+/// An oscillator node (mono output)
+///
+/// The saw and pulse waveforms are band-limited with PolyBLEP, including the
+/// edges created by pulse-width modulation and hard sync. Because the
+/// correction spreads each edge across two samples, the output is delayed by
+/// one sample.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct OscillatorNode {
+    /// The waveform to output.
+    ///
+    /// By default this is set to [`Waveform::Sine`].
+    pub waveform: Waveform,
+    /// The fundamental frequency in hertz.
+    ///
+    /// By default this is set to `440.0`.
+    pub freq_hz: f32,
+    /// The ratio of the frequency of the synced oscillator to the
+    /// fundamental frequency, in the range `[1.0, 16.0]`.
+    ///
+    /// Above `1.0`, the oscillator runs at `freq_hz * sync_ratio` and has its
+    /// phase reset at the start of every cycle of the fundamental (hard
+    /// sync), which keeps the pitch at `freq_hz` while the ratio sweeps the
+    /// timbre. A ratio of `1.0` disables hard sync.
+    ///
+    /// By default this is set to `1.0`.
+    pub sync_ratio: f32,
+    /// The fraction of each cycle that a [`Waveform::Pulse`] is high, in the
+    /// range `[0.01, 0.99]`.
+    ///
+    /// By default this is set to `0.5` (a square wave).
+    pub pulse_width: f32,
+    /// How far a sine LFO moves the pulse width away from
+    /// [`OscillatorNode::pulse_width`], in the range `[0.0, 0.5]`.
+    ///
+    /// By default this is set to `0.0` (no pulse-width modulation).
+    pub pwm_depth: f32,
+    /// The frequency of the pulse-width modulation LFO in hertz.
+    ///
+    /// By default this is set to `1.0`.
+    pub pwm_rate_hz: f32,
+    /// The overall volume.
+    ///
+    /// By default this is set to `Volume::Linear(0.5)`.
+    pub volume: Volume,
+    /// Whether or not the oscillator is enabled.
+    pub enabled: bool,
+    /// The time in seconds of the internal smoothing filter for `volume`
+    /// and `pulse_width`.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+    /// If `true`, then any DC offset is removed from the output (i.e. the
+    /// offset of a [`Waveform::Pulse`] with a pulse width other than `0.5`).
+    ///
+    /// By default this is set to `false`.
+    pub dc_block: bool,
+    /// Scale the waveform to a target peak or RMS level before
+    /// [`volume`](Self::volume) is applied, so that every waveform plays at
+    /// the same level.
+    ///
+    /// By default this is set to [`Normalize::Off`].
+    pub normalize: Normalize,
+}
+
+impl Default for OscillatorNode {
+    fn default() -> Self {
+        Self {
+            waveform: Waveform::Sine,
+            freq_hz: 440.0,
+            sync_ratio: 1.0,
+            pulse_width: 0.5,
+            pwm_depth: 0.0,
+            pwm_rate_hz: 1.0,
+            volume: Volume::Linear(0.5),
+            enabled: true,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            dc_block: false,
+            normalize: Normalize::Off,
+        }
+    }
+}
+
+impl AudioNode for OscillatorNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("oscillator")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: ChannelCount::MONO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(*self, cx.stream_info.sample_rate);
+        processor
+            .gain
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+/// The state of a band-limited oscillator with hard sync.
+#[derive(Debug, Default, Clone, Copy)]
+struct BlepOscillator {
+    /// The phase of the fundamental, which resets the synced phase.
+    master_phase: f64,
+    /// The phase of the waveform.
+    phase: f64,
+    pulse_width: f32,
+    /// The previous output sample, which still receives the first half of
+    /// the correction for any edge in the current sample.
+    delayed: f32,
+}
+
+/// The corrections for the edges between the previous and current sample.
+#[derive(Default)]
+struct BlepCorrections {
+    prev: f32,
+    cur: f32,
+}
+
+impl BlepCorrections {
+    /// Add the PolyBLEP residual of an edge with a height of `height`, which
+    /// happened `d` samples (in the range `[0.0, 1.0]`) before the current
+    /// sample.
+    fn add(&mut self, d: f64, height: f32) {
+        let d = d as f32;
+        self.prev += 0.5 * height * d * d;
+        self.cur -= 0.5 * height * (1.0 - d) * (1.0 - d);
+    }
+}
+
+fn naive(waveform: Waveform, phase: f64, pulse_width: f32) -> f32 {
+    match waveform {
+        Waveform::Sine => (phase as f32 * core::f32::consts::TAU).sin(),
+        Waveform::Saw => 2.0 * phase as f32 - 1.0,
+        Waveform::Pulse => {
+            if phase < f64::from(pulse_width) {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+    }
+}
+
+impl BlepOscillator {
+    /// Returns the next sample.
+    ///
+    /// `master_inc` is the phase increment of the fundamental, and `inc` is
+    /// the phase increment of the waveform.
+    fn next(&mut self, waveform: Waveform, master_inc: f64, inc: f64, pulse_width: f32) -> f32 {
+        let mut corrections = BlepCorrections::default();
+
+        // Moving the pulse width past the current phase is an edge too.
+        if waveform == Waveform::Pulse {
+            let height = naive(waveform, self.phase, pulse_width)
+                - naive(waveform, self.phase, self.pulse_width);
+            if height != 0.0 {
+                corrections.add(1.0, height);
+            }
+        }
+        self.pulse_width = pulse_width;
+
+        let sync = inc > master_inc;
+        self.master_phase += master_inc;
+
+        if sync && self.master_phase >= 1.0 {
+            self.master_phase -= 1.0;
+            let d = (self.master_phase / master_inc).min(1.0);
+
+            let phase = self.advance(waveform, self.phase, inc, 1.0 - d, d, &mut corrections);
+            corrections.add(
+                d,
+                naive(waveform, 0.0, pulse_width) - naive(waveform, phase, pulse_width),
+            );
+            self.phase = self.advance(waveform, 0.0, inc, d, 0.0, &mut corrections);
+        } else {
+            if self.master_phase >= 1.0 {
+                self.master_phase -= 1.0;
+            }
+            self.phase = self.advance(waveform, self.phase, inc, 1.0, 0.0, &mut corrections);
+        }
+
+        let out = self.delayed + corrections.prev;
+        self.delayed = naive(waveform, self.phase, pulse_width) + corrections.cur;
+        out
+    }
+
+    /// Advance the phase for `span` samples, ending `end_d` samples before
+    /// the current sample, and correct each edge that is crossed.
+    ///
+    /// Returns the new phase.
+    fn advance(
+        &self,
+        waveform: Waveform,
+        mut phase: f64,
+        inc: f64,
+        span: f64,
+        end_d: f64,
+        corrections: &mut BlepCorrections,
+    ) -> f64 {
+        let pulse_width = f64::from(self.pulse_width);
+        let mut t = 0.0;
+
+        loop {
+            let edge = if waveform == Waveform::Pulse && phase < pulse_width {
+                pulse_width
+            } else {
+                1.0
+            };
+
+            let to_edge = (edge - phase) / inc;
+            if inc <= 0.0 || t + to_edge > span {
+                return phase + (span - t) * inc;
+            }
+            t += to_edge;
+
+            let d = end_d + (span - t);
+            if edge == 1.0 {
+                phase = 0.0;
+                match waveform {
+                    Waveform::Sine => {}
+                    Waveform::Saw => corrections.add(d, -2.0),
+                    Waveform::Pulse => corrections.add(d, 2.0),
+                }
+            } else {
+                phase = edge;
+                corrections.add(d, -2.0);
+            }
+        }
+    }
+}
+
+struct Processor {
+    params: OscillatorNode,
+    osc: BlepOscillator,
+    lfo: PhaseAccumulator,
+    gain: SmoothedParam,
+    pulse_width: SmoothedParam,
+    master_inc: f64,
+    inc: f64,
+    dc_blocker: DcBlocker,
+    normalizer: Normalizer,
+}
+
+impl Processor {
+    fn new(params: OscillatorNode, sample_rate: NonZeroU32) -> Self {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+
+        let mut processor = Self {
+            params,
+            osc: BlepOscillator {
+                pulse_width: target_pulse_width(&params),
+                ..Default::default()
+            },
+            lfo: PhaseAccumulator::new(params.pwm_rate_hz, sample_rate),
+            gain: SmoothedParam::new(target_gain(&params), smoother_config, sample_rate),
+            pulse_width: SmoothedParam::new(
+                target_pulse_width(&params),
+                smoother_config,
+                sample_rate,
+            ),
+            master_inc: 0.0,
+            inc: 0.0,
+            dc_blocker: DcBlocker::new(DEFAULT_DC_BLOCKER_HZ, (sample_rate.get() as f32).recip()),
+            normalizer: Normalizer::new(DEFAULT_NORMALIZE_WINDOW_SECS, sample_rate),
+        };
+        processor.update_freq(sample_rate);
+        processor
+    }
+
+    fn update_freq(&mut self, sample_rate: NonZeroU32) {
+        let nyquist = sample_rate.get() as f32 * 0.5;
+        let freq_hz = self.params.freq_hz.clamp(0.0, nyquist);
+        let sync_ratio = self.params.sync_ratio.clamp(1.0, MAX_SYNC_RATIO);
+
+        self.master_inc = f64::from(freq_hz) / f64::from(sample_rate.get());
+        self.inc = f64::from((freq_hz * sync_ratio).min(nyquist)) / f64::from(sample_rate.get());
+        if sync_ratio == 1.0 {
+            self.inc = self.master_inc;
+        }
+
+        self.lfo.set_freq(self.params.pwm_rate_hz, sample_rate);
+    }
+
+    fn render(&mut self, out: &mut [f32]) {
+        let pwm_depth = self.params.pwm_depth.clamp(0.0, 0.5);
+
+        for s in out.iter_mut() {
+            let lfo = (self.lfo.next_phase() * core::f32::consts::TAU).sin();
+            let pulse_width = (self.pulse_width.next_smoothed() + lfo * pwm_depth)
+                .clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH);
+
+            let mut r = self
+                .osc
+                .next(self.params.waveform, self.master_inc, self.inc, pulse_width);
+
+            if self.params.dc_block {
+                r = self.dc_blocker.process(r);
+            }
+
+            *s = r;
+        }
+
+        self.normalizer.process(self.params.normalize, out);
+
+        for s in out.iter_mut() {
+            *s *= self.gain.next_smoothed();
+        }
+
+        self.gain.settle();
+        self.pulse_width.settle();
+    }
+}
+
+fn target_gain(params: &OscillatorNode) -> f32 {
+    if params.enabled {
+        params.volume.amp_clamped(DEFAULT_AMP_EPSILON)
+    } else {
+        0.0
+    }
+}
+
+fn target_pulse_width(params: &OscillatorNode) -> f32 {
+    params.pulse_width.clamp(MIN_PULSE_WIDTH, MAX_PULSE_WIDTH)
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        let Some(out) = buffers.outputs.first_mut() else {
+            return ProcessStatus::ClearAllOutputs;
+        };
+
+        for patch in events.drain_patches::<OscillatorNode>() {
+            if let OscillatorNodePatch::SmoothSeconds(seconds) = patch {
+                self.gain.set_smooth_seconds(seconds, info.sample_rate);
+                self.pulse_width
+                    .set_smooth_seconds(seconds, info.sample_rate);
+            }
+
+            self.params.apply(patch);
+            self.gain.set_value(target_gain(&self.params));
+            self.pulse_width.set_value(target_pulse_width(&self.params));
+            self.update_freq(info.sample_rate);
+
+            if info.prev_output_was_silent {
+                // Previous block was silent, so no need to smooth.
+                self.gain.reset_to_target();
+                self.pulse_width.reset_to_target();
+            }
+        }
+
+        if self.gain.has_settled_at(0.0) {
+            self.osc = BlepOscillator {
+                pulse_width: self.pulse_width.target_value(),
+                ..Default::default()
+            };
+            self.dc_blocker.reset();
+            self.normalizer.reset();
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.render(&mut out[..info.frames]);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.gain.update_sample_rate(stream_info.sample_rate);
+        self.pulse_width.update_sample_rate(stream_info.sample_rate);
+        self.update_freq(stream_info.sample_rate);
+        self.dc_blocker
+            .set_cutoff_hz(DEFAULT_DC_BLOCKER_HZ, stream_info.sample_rate_recip as f32);
+        self.normalizer
+            .set_window(DEFAULT_NORMALIZE_WINDOW_SECS, stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn render(params: OscillatorNode) -> Vec<f32> {
+        let mut processor = Processor::new(params, NonZeroU32::new(SAMPLE_RATE).unwrap());
+        let mut out = vec![0.0; SAMPLE_RATE as usize];
+        processor.render(&mut out);
+        out
+    }
+
+    /// The fraction of samples which are high.
+    fn duty_cycle(samples: &[f32]) -> f32 {
+        samples.iter().filter(|&&s| s > 0.0).count() as f32 / samples.len() as f32
+    }
+
+    /// The number of rising edges.
+    fn rising_edges(samples: &[f32]) -> usize {
+        samples
+            .windows(2)
+            .filter(|w| w[0] <= 0.0 && w[1] > 0.0)
+            .count()
+    }
+
+    #[test]
+    fn pulse_width_changes_duty_cycle_but_not_frequency() {
+        let pulse = |pulse_width: f32, pwm_depth: f32| {
+            render(OscillatorNode {
+                waveform: Waveform::Pulse,
+                freq_hz: 440.0,
+                pulse_width,
+                pwm_depth,
+                volume: Volume::UNITY_GAIN,
+                ..Default::default()
+            })
+        };
+
+        let square = pulse(0.5, 0.0);
+        let narrow = pulse(0.2, 0.0);
+
+        assert!((duty_cycle(&square) - 0.5).abs() < 0.01);
+        assert!((duty_cycle(&narrow) - 0.2).abs() < 0.01);
+
+        // One second of audio has one rising edge per cycle either way.
+        assert!(rising_edges(&square).abs_diff(440) <= 1);
+        assert!(rising_edges(&narrow).abs_diff(440) <= 1);
+
+        // The LFO sweeps the duty cycle over time, while the frequency stays
+        // the same.
+        let modulated = pulse(0.5, 0.3);
+        let quarter = modulated.len() / 4;
+        let rising = duty_cycle(&modulated[..quarter]);
+        let falling = duty_cycle(&modulated[quarter * 2..quarter * 3]);
+        assert!(rising > 0.6);
+        assert!(falling < 0.4);
+        assert!(rising_edges(&modulated).abs_diff(440) <= 1);
+
+        // Hard sync changes the timbre but keeps the pitch of the
+        // fundamental.
+        let synced = render(OscillatorNode {
+            waveform: Waveform::Saw,
+            freq_hz: 440.0,
+            sync_ratio: 2.5,
+            volume: Volume::UNITY_GAIN,
+            ..Default::default()
+        });
+        let cycle = SAMPLE_RATE as f32 / 440.0;
+        let period_error = |lag: usize| {
+            synced[1_000..40_000]
+                .iter()
+                .zip(&synced[1_000 + lag..])
+                .map(|(a, b)| (a - b).abs())
+                .sum::<f32>()
+        };
+        let period = (cycle as usize - 2..cycle as usize + 3)
+            .min_by(|&a, &b| period_error(a).total_cmp(&period_error(b)))
+            .unwrap();
+        assert!((period as f32 - cycle).abs() < 1.0);
+        assert!(synced.iter().all(|s| s.abs() <= 1.1));
+    }
+
+    #[test]
+    fn normalized_waveforms_play_at_target_rms() {
+        let rms = |samples: &[f32]| {
+            (samples.iter().map(|&s| s * s).sum::<f32>() / samples.len() as f32).sqrt()
+        };
+
+        // The raw waveforms have different RMS levels, but all come out at
+        // the target. The DC blocker also removes the offset of the narrow
+        // pulse.
+        for (waveform, pulse_width, dc_block) in [
+            (Waveform::Sine, 0.5, false),
+            (Waveform::Saw, 0.5, false),
+            (Waveform::Pulse, 0.5, false),
+            (Waveform::Pulse, 0.1, true),
+        ] {
+            let params = OscillatorNode {
+                waveform,
+                freq_hz: 220.0,
+                pulse_width,
+                dc_block,
+                normalize: Normalize::Rms(0.25),
+                volume: Volume::UNITY_GAIN,
+                ..Default::default()
+            };
+
+            let mut processor = Processor::new(params, NonZeroU32::new(SAMPLE_RATE).unwrap());
+            // Give the DC blocker and the level measurement time to settle.
+            let mut out = vec![0.0; SAMPLE_RATE as usize * 2];
+            for block in out.chunks_mut(256) {
+                processor.render(block);
+            }
+
+            let tail = &out[SAMPLE_RATE as usize * 3 / 2..];
+            let level = rms(tail);
+            assert!(
+                (level - 0.25).abs() < 0.005,
+                "{waveform:?} ({pulse_width}): {level}"
+            );
+
+            if dc_block {
+                let mean = tail.iter().sum::<f32>() / tail.len() as f32;
+                assert!(mean.abs() < 0.01);
+            }
+        }
+    }
+}
@@ -1,20 +1,32 @@
+use core::num::NonZeroU32;
+
 #[cfg(not(feature = "std"))]
 use num_traits::Float;
 
 use bevy_platform::sync::atomic::Ordering;
 use firewheel_core::{
     atomic_float::AtomicF32,
-    channel_config::{ChannelConfig, ChannelCount},
+    channel_config::{ChannelConfig, NonZeroChannelCount},
     collector::ArcGc,
     diff::{Diff, Patch},
-    dsp::volume::{amp_to_db, DbMeterNormalizer},
+    dsp::{
+        dc_blocker::{DcBlocker, DEFAULT_DC_BLOCKER_HZ},
+        loudness::{mean_square_to_lufs, KWeightingCoeffs, KWeightingFilter, LoudnessWindow},
+        true_peak::{TruePeakCoeffs, TruePeakDetector},
+        volume::{amp_to_db, DbMeterNormalizer},
+    },
     event::ProcEvents,
+    mask::SilenceMask,
     node::{
-        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
-        ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
     },
+    StreamInfo,
 };
 
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Box, Vec};
+
 /// The configuration for a [`PeakMeterSmoother`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
@@ -158,93 +170,640 @@ impl<const NUM_CHANNELS: usize> PeakMeterSmoother<NUM_CHANNELS> {
     }
 }
 
-/// A node that calculates the peak amplitude of a signal, and then sends that value
-/// to [`PeakMeterState`].
+/// The configuration of a [`PeakMeterNode`]
+///
+/// This can be built with [`PeakMeterConfig::new`]:
+///
+/// ```
+/// # use firewheel_core::channel_config::NonZeroChannelCount;
+/// # use firewheel_nodes::peak_meter::PeakMeterConfig;
+/// let config = PeakMeterConfig::new()
+///     .channels(NonZeroChannelCount::new(6).unwrap())
+///     .rms(true)
+///     .true_peak(true)
+///     .loudness(true);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeakMeterConfig {
+    /// The number of channels to measure. One peak value is reported for
+    /// each channel, so this can be used to meter surround layouts.
+    ///
+    /// By default this is set to [`NonZeroChannelCount::STEREO`].
+    pub channels: NonZeroChannelCount,
+    /// If `true`, then the RMS (root-mean-square) amplitude of each channel
+    /// is measured along with the peak amplitude.
+    ///
+    /// By default this is set to `false`.
+    pub rms: bool,
+    /// If `true`, then the true peak amplitude of each channel is measured
+    /// along with the sample peak amplitude.
+    ///
+    /// The true peak is the peak of the reconstructed analog signal, which
+    /// can be higher than the sample peak when the signal peaks in between
+    /// samples. It is measured with `4x` oversampling as described in
+    /// ITU-R BS.1770, and is needed to meet the dBTP limits of broadcast
+    /// loudness standards.
+    ///
+    /// By default this is set to `false`.
+    pub true_peak: bool,
+    /// If `true`, then the signal is AC-coupled before it is measured. That
+    /// is, a DC blocker removes any DC offset first, so that signals which
+    /// intentionally carry DC (i.e. control signals) don't register as level.
+    ///
+    /// If `false`, then the signal is measured as is (DC-coupled).
+    ///
+    /// By default this is set to `false`.
+    pub ac_coupled: bool,
+    /// If `true`, then the momentary (`400ms`) and short-term (`3s`) RMS
+    /// amplitude of each channel is measured, along with the momentary and
+    /// short-term K-weighted loudness of all channels in LUFS as described in
+    /// ITU-R BS.1770.
+    ///
+    /// Unlike [`PeakMeterConfig::rms`], which is measured over each processed
+    /// block, these are measured over fixed windows that slide in steps of
+    /// `100ms`, so they don't depend on the block size.
+    ///
+    /// All channels are weighted equally, so the LFE channel of a surround
+    /// layout should be routed around the meter.
+    ///
+    /// By default this is set to `false`.
+    pub loudness: bool,
+}
+
+impl PeakMeterConfig {
+    /// Construct the default configuration.
+    pub const fn new() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            rms: false,
+            true_peak: false,
+            ac_coupled: false,
+            loudness: false,
+        }
+    }
+
+    /// Set the number of channels to measure.
+    pub const fn channels(mut self, channels: NonZeroChannelCount) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    /// Set whether or not to also measure the RMS amplitude of each channel.
+    pub const fn rms(mut self, rms: bool) -> Self {
+        self.rms = rms;
+        self
+    }
+
+    /// Set whether or not to also measure the true peak amplitude of each
+    /// channel.
+    pub const fn true_peak(mut self, true_peak: bool) -> Self {
+        self.true_peak = true_peak;
+        self
+    }
+
+    /// Set whether or not to remove any DC offset from the signal before
+    /// measuring it.
+    pub const fn ac_coupled(mut self, ac_coupled: bool) -> Self {
+        self.ac_coupled = ac_coupled;
+        self
+    }
+
+    /// Set whether or not to also measure the momentary and short-term
+    /// loudness.
+    pub const fn loudness(mut self, loudness: bool) -> Self {
+        self.loudness = loudness;
+        self
+    }
+}
+
+impl Default for PeakMeterConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A node that calculates the peak (and optionally RMS) amplitude of each
+/// channel of a signal, and then sends those values to [`PeakMeterState`].
 #[derive(Diff, Patch, Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct PeakMeterNode<const NUM_CHANNELS: usize> {
+pub struct PeakMeterNode {
     pub enabled: bool,
 }
 
-/// The state of a [`PeakMeterNode`]. This contains the calculated peak values.
-#[derive(Clone)]
-pub struct PeakMeterState<const NUM_CHANNELS: usize> {
-    shared_state: ArcGc<SharedState<NUM_CHANNELS>>,
+impl Default for PeakMeterNode {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// The raw values measured by a [`PeakMeterNode`], which are written by the
+/// audio thread after every processed block.
+pub struct PeakMeterAtomics {
+    /// The latest peak amplitude (in raw gain) of each channel.
+    pub peak_gains: Box<[AtomicF32]>,
+    /// The latest RMS amplitude (in raw gain) of each channel.
+    ///
+    /// This is empty if [`PeakMeterConfig::rms`] is `false`.
+    pub rms_gains: Box<[AtomicF32]>,
+    /// The latest true peak amplitude (in raw gain) of each channel.
+    ///
+    /// This is empty if [`PeakMeterConfig::true_peak`] is `false`.
+    pub true_peak_gains: Box<[AtomicF32]>,
+    /// The latest loudness measurements.
+    ///
+    /// This is `None` if [`PeakMeterConfig::loudness`] is `false`.
+    pub loudness: Option<LoudnessAtomics>,
+}
+
+/// The loudness values measured by a [`PeakMeterNode`] if
+/// [`PeakMeterConfig::loudness`] is `true`.
+pub struct LoudnessAtomics {
+    /// The RMS amplitude (in raw gain) of each channel over the last `400ms`.
+    pub momentary_rms_gains: Box<[AtomicF32]>,
+    /// The RMS amplitude (in raw gain) of each channel over the last `3s`.
+    pub short_term_rms_gains: Box<[AtomicF32]>,
+    /// The K-weighted loudness of all channels over the last `400ms` in LUFS.
+    pub momentary_lufs: AtomicF32,
+    /// The K-weighted loudness of all channels over the last `3s` in LUFS.
+    pub short_term_lufs: AtomicF32,
 }
 
-impl<const NUM_CHANNELS: usize> PeakMeterState<NUM_CHANNELS> {
-    fn new() -> Self {
-        assert_ne!(NUM_CHANNELS, 0);
-        assert!(NUM_CHANNELS <= 64);
+impl LoudnessAtomics {
+    fn clear(&self) {
+        for gain in self
+            .momentary_rms_gains
+            .iter()
+            .chain(self.short_term_rms_gains.iter())
+        {
+            gain.store(0.0, Ordering::Relaxed);
+        }
+        self.momentary_lufs
+            .store(f32::NEG_INFINITY, Ordering::Relaxed);
+        self.short_term_lufs
+            .store(f32::NEG_INFINITY, Ordering::Relaxed);
+    }
+}
+
+impl PeakMeterAtomics {
+    fn new(config: &PeakMeterConfig) -> Self {
+        let atomics = |len: usize| (0..len).map(|_| AtomicF32::new(0.0)).collect();
+        let num_channels = config.channels.get().get() as usize;
 
         Self {
-            shared_state: ArcGc::new(SharedState {
-                peak_gains: core::array::from_fn(|_| AtomicF32::new(0.0)),
+            peak_gains: atomics(num_channels),
+            rms_gains: atomics(if config.rms { num_channels } else { 0 }),
+            true_peak_gains: atomics(if config.true_peak { num_channels } else { 0 }),
+            loudness: config.loudness.then(|| LoudnessAtomics {
+                momentary_rms_gains: atomics(num_channels),
+                short_term_rms_gains: atomics(num_channels),
+                momentary_lufs: AtomicF32::new(f32::NEG_INFINITY),
+                short_term_lufs: AtomicF32::new(f32::NEG_INFINITY),
             }),
         }
     }
 
-    /// Get the latest peak values for each channel in decibels.
+    fn clear(&self) {
+        for gain in self
+            .peak_gains
+            .iter()
+            .chain(self.rms_gains.iter())
+            .chain(self.true_peak_gains.iter())
+        {
+            gain.store(0.0, Ordering::Relaxed);
+        }
+        if let Some(loudness) = &self.loudness {
+            loudness.clear();
+        }
+    }
+}
+
+/// The state of a [`PeakMeterNode`]. This contains the calculated peak values.
+#[derive(Clone)]
+pub struct PeakMeterState {
+    shared_state: ArcGc<PeakMeterAtomics>,
+}
+
+impl PeakMeterState {
+    fn new(config: &PeakMeterConfig) -> Self {
+        Self {
+            shared_state: ArcGc::new(PeakMeterAtomics::new(config)),
+        }
+    }
+
+    /// The number of channels being measured.
+    pub fn num_channels(&self) -> usize {
+        self.shared_state.peak_gains.len()
+    }
+
+    /// Returns `true` if the RMS amplitude is being measured.
+    pub fn has_rms(&self) -> bool {
+        !self.shared_state.rms_gains.is_empty()
+    }
+
+    /// Returns `true` if the true peak amplitude is being measured.
+    pub fn has_true_peak(&self) -> bool {
+        !self.shared_state.true_peak_gains.is_empty()
+    }
+
+    /// Returns `true` if the momentary and short-term loudness is being
+    /// measured.
+    pub fn has_loudness(&self) -> bool {
+        self.shared_state.loudness.is_some()
+    }
+
+    /// The raw atomic values shared with the audio thread.
+    ///
+    /// These can be read directly from any thread without allocating.
+    pub fn atomics(&self) -> &PeakMeterAtomics {
+        &self.shared_state
+    }
+
+    /// Write the latest peak value of each channel in decibels into `peaks_db`.
+    ///
+    /// If `peaks_db` is longer than [`PeakMeterState::num_channels`], then the
+    /// extra values are set to `f32::NEG_INFINITY`.
     ///
     /// * `db_epsilon` - If a peak value is less than or equal to this value, then it
-    /// will be clamped to `f32::NEG_INFINITY` (silence). (You can use
-    /// [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
     ///
     /// If the node is currently disabled, then this will return a value
     /// of `f32::NEG_INFINITY` (silence) for all channels.
-    pub fn peak_gain_db(&self, db_epsilon: f32) -> [f32; NUM_CHANNELS] {
-        core::array::from_fn(|i| {
-            let db = amp_to_db(self.shared_state.peak_gains[i].load(Ordering::Relaxed));
-            if db <= db_epsilon {
-                f32::NEG_INFINITY
-            } else {
-                db
+    pub fn peak_gains_db(&self, db_epsilon: f32, peaks_db: &mut [f32]) {
+        peaks_db.fill(f32::NEG_INFINITY);
+
+        for (db, gain) in peaks_db.iter_mut().zip(self.shared_state.peak_gains.iter()) {
+            let peak_db = amp_to_db(gain.load(Ordering::Relaxed));
+            if peak_db > db_epsilon {
+                *db = peak_db;
             }
-        })
+        }
+    }
+
+    /// Get the latest peak values for the first `NUM_CHANNELS` channels in
+    /// decibels. This is a convenience wrapper around
+    /// [`PeakMeterState::peak_gains_db`] for use with [`PeakMeterSmoother`].
+    ///
+    /// * `db_epsilon` - If a peak value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn peak_gain_db<const NUM_CHANNELS: usize>(&self, db_epsilon: f32) -> [f32; NUM_CHANNELS] {
+        let mut peaks_db = [f32::NEG_INFINITY; NUM_CHANNELS];
+        self.peak_gains_db(db_epsilon, &mut peaks_db);
+        peaks_db
+    }
+
+    /// Write the latest RMS value of each channel in decibels into `rms_db`.
+    ///
+    /// If [`PeakMeterConfig::rms`] is `false`, or if `rms_db` is longer than
+    /// [`PeakMeterState::num_channels`], then the extra values are set to
+    /// `f32::NEG_INFINITY`.
+    ///
+    /// * `db_epsilon` - If an RMS value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn rms_gains_db(&self, db_epsilon: f32, rms_db: &mut [f32]) {
+        gains_db(&self.shared_state.rms_gains, db_epsilon, rms_db);
+    }
+
+    /// Write the latest true peak value of each channel in decibels (dBTP)
+    /// into `true_peaks_db`.
+    ///
+    /// If [`PeakMeterConfig::true_peak`] is `false`, or if `true_peaks_db` is
+    /// longer than [`PeakMeterState::num_channels`], then the extra values are
+    /// set to `f32::NEG_INFINITY`.
+    ///
+    /// * `db_epsilon` - If a true peak value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn true_peak_gains_db(&self, db_epsilon: f32, true_peaks_db: &mut [f32]) {
+        gains_db(
+            &self.shared_state.true_peak_gains,
+            db_epsilon,
+            true_peaks_db,
+        );
+    }
+
+    /// Write the RMS value of each channel over the last `400ms` in decibels
+    /// into `rms_db`.
+    ///
+    /// If [`PeakMeterConfig::loudness`] is `false`, or if `rms_db` is longer
+    /// than [`PeakMeterState::num_channels`], then the extra values are set to
+    /// `f32::NEG_INFINITY`.
+    ///
+    /// * `db_epsilon` - If an RMS value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn momentary_rms_gains_db(&self, db_epsilon: f32, rms_db: &mut [f32]) {
+        let gains = self
+            .shared_state
+            .loudness
+            .as_ref()
+            .map(|l| &*l.momentary_rms_gains)
+            .unwrap_or_default();
+        gains_db(gains, db_epsilon, rms_db);
+    }
+
+    /// Write the RMS value of each channel over the last `3s` in decibels
+    /// into `rms_db`.
+    ///
+    /// If [`PeakMeterConfig::loudness`] is `false`, or if `rms_db` is longer
+    /// than [`PeakMeterState::num_channels`], then the extra values are set to
+    /// `f32::NEG_INFINITY`.
+    ///
+    /// * `db_epsilon` - If an RMS value is less than or equal to this value, then it
+    ///   will be clamped to `f32::NEG_INFINITY` (silence). (You can use
+    ///   [firewheel_core::dsp::volume::DEFAULT_DB_EPSILON].)
+    pub fn short_term_rms_gains_db(&self, db_epsilon: f32, rms_db: &mut [f32]) {
+        let gains = self
+            .shared_state
+            .loudness
+            .as_ref()
+            .map(|l| &*l.short_term_rms_gains)
+            .unwrap_or_default();
+        gains_db(gains, db_epsilon, rms_db);
+    }
+
+    /// The K-weighted loudness of all channels over the last `400ms` in LUFS.
+    ///
+    /// This returns `f32::NEG_INFINITY` for silence, or if
+    /// [`PeakMeterConfig::loudness`] is `false`.
+    pub fn momentary_lufs(&self) -> f32 {
+        self.shared_state
+            .loudness
+            .as_ref()
+            .map(|l| l.momentary_lufs.load(Ordering::Relaxed))
+            .unwrap_or(f32::NEG_INFINITY)
+    }
+
+    /// The K-weighted loudness of all channels over the last `3s` in LUFS.
+    ///
+    /// This returns `f32::NEG_INFINITY` for silence, or if
+    /// [`PeakMeterConfig::loudness`] is `false`.
+    pub fn short_term_lufs(&self) -> f32 {
+        self.shared_state
+            .loudness
+            .as_ref()
+            .map(|l| l.short_term_lufs.load(Ordering::Relaxed))
+            .unwrap_or(f32::NEG_INFINITY)
     }
 }
 
-impl<const NUM_CHANNELS: usize> AudioNode for PeakMeterNode<NUM_CHANNELS> {
-    type Configuration = EmptyConfig;
+fn gains_db(gains: &[AtomicF32], db_epsilon: f32, out_db: &mut [f32]) {
+    out_db.fill(f32::NEG_INFINITY);
 
-    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+    for (db, gain) in out_db.iter_mut().zip(gains.iter()) {
+        let value_db = amp_to_db(gain.load(Ordering::Relaxed));
+        if value_db > db_epsilon {
+            *db = value_db;
+        }
+    }
+}
+
+impl AudioNode for PeakMeterNode {
+    type Configuration = PeakMeterConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
         AudioNodeInfo::new()
             .debug_name("peak_meter")
             .channel_config(ChannelConfig {
-                num_inputs: ChannelCount::new(NUM_CHANNELS as u32).unwrap(),
-                num_outputs: ChannelCount::new(NUM_CHANNELS as u32).unwrap(),
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
             })
-            .custom_state(PeakMeterState::<NUM_CHANNELS>::new())
+            .custom_state(PeakMeterState::new(config))
+            .analysis_only(true)
     }
 
     fn construct_processor(
         &self,
-        _config: &Self::Configuration,
+        config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        Processor {
-            params: self.clone(),
-            shared_state: ArcGc::clone(
-                &cx.custom_state::<PeakMeterState<NUM_CHANNELS>>()
-                    .unwrap()
-                    .shared_state,
-            ),
+        let processor = Processor::new(
+            *self,
+            ArcGc::clone(&cx.custom_state::<PeakMeterState>().unwrap().shared_state),
+        );
+
+        let processor = if config.ac_coupled {
+            processor.ac_coupled(cx.stream_info)
+        } else {
+            processor
+        };
+
+        if config.loudness {
+            processor.loudness(cx.stream_info)
+        } else {
+            processor
         }
     }
 }
 
-struct SharedState<const NUM_CHANNELS: usize> {
-    peak_gains: [AtomicF32; NUM_CHANNELS],
+struct Processor {
+    params: PeakMeterNode,
+    shared_state: ArcGc<PeakMeterAtomics>,
+    true_peak_detectors: Box<[TruePeakDetector]>,
+    true_peak_coeffs: TruePeakCoeffs,
+    /// One DC blocker per channel if the signal is AC-coupled, otherwise
+    /// empty.
+    dc_blockers: Box<[DcBlocker]>,
+    /// The AC-coupled samples of the channel currently being measured.
+    coupled: Vec<f32>,
+    loudness: Option<LoudnessMeter>,
 }
 
-struct Processor<const NUM_CHANNELS: usize> {
-    params: PeakMeterNode<NUM_CHANNELS>,
-    shared_state: ArcGc<SharedState<NUM_CHANNELS>>,
+/// The state used to measure loudness if [`PeakMeterConfig::loudness`] is
+/// `true`.
+struct LoudnessMeter {
+    coeffs: KWeightingCoeffs,
+    /// The K-weighting filter of each channel.
+    filters: Box<[KWeightingFilter]>,
+    /// The windows of the unweighted signal of each channel.
+    rms_windows: Box<[LoudnessWindow]>,
+    /// The windows of the K-weighted signal of each channel.
+    weighted_windows: Box<[LoudnessWindow]>,
 }
 
-impl<const NUM_CHANNELS: usize> AudioNodeProcessor for Processor<NUM_CHANNELS> {
+impl LoudnessMeter {
+    fn new(num_channels: usize, sample_rate: NonZeroU32) -> Self {
+        Self {
+            coeffs: KWeightingCoeffs::new(sample_rate),
+            filters: vec![KWeightingFilter::default(); num_channels].into_boxed_slice(),
+            rms_windows: vec![LoudnessWindow::new(sample_rate); num_channels].into_boxed_slice(),
+            weighted_windows: vec![LoudnessWindow::new(sample_rate); num_channels]
+                .into_boxed_slice(),
+        }
+    }
+
+    fn reset(&mut self) {
+        for filter in self.filters.iter_mut() {
+            filter.reset();
+        }
+        for window in self
+            .rms_windows
+            .iter_mut()
+            .chain(self.weighted_windows.iter_mut())
+        {
+            window.reset();
+        }
+    }
+
+    fn store(&self, shared: &LoudnessAtomics) {
+        for ((window, momentary), short_term) in self
+            .rms_windows
+            .iter()
+            .zip(shared.momentary_rms_gains.iter())
+            .zip(shared.short_term_rms_gains.iter())
+        {
+            momentary.store(window.momentary_mean_square().sqrt(), Ordering::Relaxed);
+            short_term.store(window.short_term_mean_square().sqrt(), Ordering::Relaxed);
+        }
+
+        let momentary: f32 = self
+            .weighted_windows
+            .iter()
+            .map(|w| w.momentary_mean_square())
+            .sum();
+        let short_term: f32 = self
+            .weighted_windows
+            .iter()
+            .map(|w| w.short_term_mean_square())
+            .sum();
+
+        shared
+            .momentary_lufs
+            .store(mean_square_to_lufs(momentary), Ordering::Relaxed);
+        shared
+            .short_term_lufs
+            .store(mean_square_to_lufs(short_term), Ordering::Relaxed);
+    }
+}
+
+impl Processor {
+    fn new(params: PeakMeterNode, shared_state: ArcGc<PeakMeterAtomics>) -> Self {
+        Self {
+            params,
+            true_peak_detectors: (0..shared_state.true_peak_gains.len())
+                .map(|_| TruePeakDetector::new())
+                .collect(),
+            true_peak_coeffs: TruePeakCoeffs::new(),
+            dc_blockers: Box::new([]),
+            coupled: Vec::new(),
+            loudness: None,
+            shared_state,
+        }
+    }
+
+    /// Remove any DC offset from the signal before measuring it.
+    fn ac_coupled(mut self, stream_info: &StreamInfo) -> Self {
+        self.dc_blockers = (0..self.shared_state.peak_gains.len())
+            .map(|_| DcBlocker::new(DEFAULT_DC_BLOCKER_HZ, stream_info.sample_rate_recip as f32))
+            .collect();
+        self.coupled = vec![0.0; stream_info.max_block_frames.get() as usize];
+        self
+    }
+
+    /// Measure the momentary and short-term loudness.
+    fn loudness(mut self, stream_info: &StreamInfo) -> Self {
+        self.loudness = Some(LoudnessMeter::new(
+            self.shared_state.peak_gains.len(),
+            stream_info.sample_rate,
+        ));
+        self
+    }
+
+    fn measure(&mut self, inputs: &[&[f32]], frames: usize, in_silence_mask: SilenceMask) {
+        for (i, (in_ch, peak_shared)) in inputs
+            .iter()
+            .zip(self.shared_state.peak_gains.iter())
+            .enumerate()
+        {
+            let rms_shared = self.shared_state.rms_gains.get(i);
+            let true_peak = self
+                .shared_state
+                .true_peak_gains
+                .get(i)
+                .zip(self.true_peak_detectors.get_mut(i));
+
+            if in_silence_mask.is_channel_silent(i) {
+                if let Some(blocker) = self.dc_blockers.get_mut(i) {
+                    blocker.reset();
+                }
+
+                peak_shared.store(0.0, Ordering::Relaxed);
+                if let Some(rms_shared) = rms_shared {
+                    rms_shared.store(0.0, Ordering::Relaxed);
+                }
+                if let Some((true_peak_shared, detector)) = true_peak {
+                    detector.reset();
+                    true_peak_shared.store(0.0, Ordering::Relaxed);
+                }
+                if let Some(loudness) = &mut self.loudness {
+                    loudness.filters[i].reset();
+                    loudness.rms_windows[i].push_silence(frames);
+                    loudness.weighted_windows[i].push_silence(frames);
+                }
+
+                continue;
+            }
+
+            let samples = if let Some(blocker) = self.dc_blockers.get_mut(i) {
+                let coupled = &mut self.coupled[..frames];
+                coupled.copy_from_slice(&in_ch[..frames]);
+                blocker.process_block(coupled);
+                coupled
+            } else {
+                &in_ch[..frames]
+            };
+
+            peak_shared.store(
+                firewheel_core::dsp::algo::max_peak(samples),
+                Ordering::Relaxed,
+            );
+
+            if let Some(rms_shared) = rms_shared {
+                if frames == 0 {
+                    rms_shared.store(0.0, Ordering::Relaxed);
+                } else {
+                    let sum_squares: f32 = samples.iter().map(|&s| s * s).sum();
+                    rms_shared.store((sum_squares / frames as f32).sqrt(), Ordering::Relaxed);
+                }
+            }
+
+            if let Some((true_peak_shared, detector)) = true_peak {
+                true_peak_shared.store(
+                    detector.process(samples, &self.true_peak_coeffs),
+                    Ordering::Relaxed,
+                );
+            }
+
+            if let Some(loudness) = &mut self.loudness {
+                let filter = &mut loudness.filters[i];
+                let rms_window = &mut loudness.rms_windows[i];
+                let weighted_window = &mut loudness.weighted_windows[i];
+
+                for &s in samples {
+                    rms_window.push(s);
+                    weighted_window.push(filter.process(s, &loudness.coeffs));
+                }
+            }
+        }
+
+        if let Some((loudness, shared)) = self
+            .loudness
+            .as_ref()
+            .zip(self.shared_state.loudness.as_ref())
+        {
+            loudness.store(shared);
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
     fn process(
         &mut self,
         info: &ProcInfo,
@@ -254,13 +813,20 @@ impl<const NUM_CHANNELS: usize> AudioNodeProcessor for Processor<NUM_CHANNELS> {
     ) -> ProcessStatus {
         let was_enabled = self.params.enabled;
 
-        for patch in events.drain_patches::<PeakMeterNode<NUM_CHANNELS>>() {
+        for patch in events.drain_patches::<PeakMeterNode>() {
             self.params.apply(patch);
         }
 
         if was_enabled && !self.params.enabled {
-            for ch in self.shared_state.peak_gains.iter() {
-                ch.store(0.0, Ordering::Relaxed);
+            self.shared_state.clear();
+            for detector in self.true_peak_detectors.iter_mut() {
+                detector.reset();
+            }
+            for blocker in self.dc_blockers.iter_mut() {
+                blocker.reset();
+            }
+            if let Some(loudness) = &mut self.loudness {
+                loudness.reset();
             }
         }
 
@@ -268,22 +834,221 @@ impl<const NUM_CHANNELS: usize> AudioNodeProcessor for Processor<NUM_CHANNELS> {
             return ProcessStatus::Bypass;
         }
 
-        for (i, (in_ch, peak_shared)) in buffers
-            .inputs
+        self.measure(buffers.inputs, info.frames, info.in_silence_mask);
+
+        ProcessStatus::Bypass
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        if !self.dc_blockers.is_empty() {
+            for blocker in self.dc_blockers.iter_mut() {
+                blocker.set_cutoff_hz(DEFAULT_DC_BLOCKER_HZ, stream_info.sample_rate_recip as f32);
+            }
+            self.coupled
+                .resize(stream_info.max_block_frames.get() as usize, 0.0);
+        }
+
+        if let Some(loudness) = &mut self.loudness {
+            *loudness = LoudnessMeter::new(loudness.filters.len(), stream_info.sample_rate);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use firewheel_core::dsp::volume::db_to_amp;
+
+    #[test]
+    fn surround_channels_are_independent() {
+        let state = PeakMeterState::new(
+            &PeakMeterConfig::new().channels(NonZeroChannelCount::new(6).unwrap()),
+        );
+        let mut processor =
+            Processor::new(PeakMeterNode::default(), ArcGc::clone(&state.shared_state));
+
+        let gains = [1.0, 0.5, 0.25, 0.0, 0.125, 0.0625];
+        let channels: Vec<Vec<f32>> = gains
             .iter()
-            .zip(self.shared_state.peak_gains.iter())
-            .enumerate()
-        {
-            if info.in_silence_mask.is_channel_silent(i) {
-                peak_shared.store(0.0, Ordering::Relaxed);
+            .map(|&g| {
+                (0..256)
+                    .map(|i| if i == 100 { -g } else { g * 0.1 })
+                    .collect()
+            })
+            .collect();
+        let inputs: Vec<&[f32]> = channels.iter().map(|ch| ch.as_slice()).collect();
+
+        // Mark the fourth channel as silent.
+        processor.measure(&inputs, 256, SilenceMask(0b1000));
+
+        let mut peaks_db = [0.0; 7];
+        state.peak_gains_db(-100.0, &mut peaks_db);
+
+        assert_eq!(state.num_channels(), 6);
+        for (&peak_db, &gain) in peaks_db.iter().zip(gains.iter()) {
+            if gain == 0.0 {
+                assert_eq!(peak_db, f32::NEG_INFINITY);
             } else {
-                peak_shared.store(
-                    firewheel_core::dsp::algo::max_peak(in_ch),
-                    Ordering::Relaxed,
-                );
+                assert!((peak_db - amp_to_db(gain)).abs() < 1e-4);
             }
         }
+        assert_eq!(peaks_db[6], f32::NEG_INFINITY);
+        assert!(!state.has_rms());
+    }
 
-        ProcessStatus::Bypass
+    #[test]
+    fn stereo_rms_and_peak_populates_all_atomics() {
+        let state = PeakMeterState::new(
+            &PeakMeterConfig::new()
+                .channels(NonZeroChannelCount::STEREO)
+                .rms(true),
+        );
+        let mut processor =
+            Processor::new(PeakMeterNode::default(), ArcGc::clone(&state.shared_state));
+
+        // A square wave with an amplitude of `0.5`, and an impulse of `0.8`
+        // in silence.
+        let left: Vec<f32> = (0..256)
+            .map(|i| if i % 2 == 0 { 0.5 } else { -0.5 })
+            .collect();
+        let mut right = vec![0.0; 256];
+        right[10] = -0.8;
+
+        processor.measure(&[&left, &right], 256, SilenceMask::NONE_SILENT);
+
+        let atomics = state.atomics();
+        assert!(state.has_rms());
+        assert_eq!(atomics.peak_gains.len(), 2);
+        assert_eq!(atomics.rms_gains.len(), 2);
+
+        let load = |gain: &AtomicF32| gain.load(Ordering::Relaxed);
+        assert_eq!(load(&atomics.peak_gains[0]), 0.5);
+        assert_eq!(load(&atomics.peak_gains[1]), 0.8);
+        assert!((load(&atomics.rms_gains[0]) - 0.5).abs() < 1e-6);
+        assert!((load(&atomics.rms_gains[1]) - 0.8 / 16.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn true_peak_catches_inter_sample_overs() {
+        let state = PeakMeterState::new(&PeakMeterConfig::new().true_peak(true));
+        let mut processor =
+            Processor::new(PeakMeterNode::default(), ArcGc::clone(&state.shared_state));
+
+        // A full-scale sine at a quarter of the sample rate, with its phase
+        // shifted so that every sample lands halfway between the peaks. The
+        // sample peak is -3 dB, while the true peak is 0 dBTP.
+        let signal: Vec<f32> = (0..1024)
+            .map(|i| (core::f32::consts::FRAC_PI_2 * i as f32 + core::f32::consts::FRAC_PI_4).sin())
+            .collect();
+
+        for block in signal.chunks(256) {
+            processor.measure(&[block, block], block.len(), SilenceMask::NONE_SILENT);
+        }
+
+        let mut peaks_db = [0.0; 2];
+        let mut true_peaks_db = [0.0; 2];
+        state.peak_gains_db(-100.0, &mut peaks_db);
+        state.true_peak_gains_db(-100.0, &mut true_peaks_db);
+
+        assert!(state.has_true_peak());
+        for (peak_db, true_peak_db) in peaks_db.into_iter().zip(true_peaks_db) {
+            assert!((peak_db + 3.0103).abs() < 0.01);
+            assert!(true_peak_db > peak_db + 2.5);
+            assert!(true_peak_db.abs() < 0.2, "{true_peak_db}");
+        }
+    }
+
+    #[test]
+    fn ac_coupling_ignores_dc() {
+        let stream_info = StreamInfo::default();
+        let frames = stream_info.max_block_frames.get() as usize;
+        let dc = vec![0.5; frames];
+
+        let measure_dc = |ac_coupled: bool| {
+            let config = PeakMeterConfig::new().rms(true).ac_coupled(ac_coupled);
+            let state = PeakMeterState::new(&config);
+            let processor =
+                Processor::new(PeakMeterNode::default(), ArcGc::clone(&state.shared_state));
+            let mut processor = if ac_coupled {
+                processor.ac_coupled(&stream_info)
+            } else {
+                processor
+            };
+
+            // Let the DC blocker settle for about two seconds.
+            for _ in 0..2 * stream_info.sample_rate.get() as usize / frames {
+                processor.measure(&[&dc, &dc], frames, SilenceMask::NONE_SILENT);
+            }
+
+            let mut peaks_db = [0.0; 2];
+            let mut rms_db = [0.0; 2];
+            state.peak_gains_db(-100.0, &mut peaks_db);
+            state.rms_gains_db(-100.0, &mut rms_db);
+            (peaks_db, rms_db)
+        };
+
+        let (peaks_db, rms_db) = measure_dc(true);
+        assert_eq!(peaks_db, [f32::NEG_INFINITY; 2]);
+        assert_eq!(rms_db, [f32::NEG_INFINITY; 2]);
+
+        let (peaks_db, rms_db) = measure_dc(false);
+        for db in peaks_db.into_iter().chain(rms_db) {
+            assert!((db - amp_to_db(0.5)).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn stereo_sine_at_minus_23_dbfs_reads_minus_23_lufs() {
+        // Test case 1 of EBU Tech 3341: a stereo 1 kHz sine with a peak of
+        // -23 dBFS in both channels reads as -23 LUFS.
+        let stream_info = StreamInfo::default();
+        let sample_rate = stream_info.sample_rate.get() as usize;
+        let amp = db_to_amp(-23.0);
+        let signal: Vec<f32> = (0..4 * sample_rate)
+            .map(|i| amp * (core::f32::consts::TAU * 1_000.0 * i as f32 / sample_rate as f32).sin())
+            .collect();
+
+        let measure = |block_frames: usize| {
+            let state = PeakMeterState::new(&PeakMeterConfig::new().loudness(true));
+            let mut processor =
+                Processor::new(PeakMeterNode::default(), ArcGc::clone(&state.shared_state))
+                    .loudness(&stream_info);
+
+            for block in signal.chunks(block_frames) {
+                processor.measure(&[block, block], block.len(), SilenceMask::NONE_SILENT);
+            }
+
+            let mut momentary_rms_db = [0.0; 2];
+            let mut short_term_rms_db = [0.0; 2];
+            state.momentary_rms_gains_db(-100.0, &mut momentary_rms_db);
+            state.short_term_rms_gains_db(-100.0, &mut short_term_rms_db);
+
+            (
+                state.momentary_lufs(),
+                state.short_term_lufs(),
+                momentary_rms_db,
+                short_term_rms_db,
+            )
+        };
+
+        let (momentary_lufs, short_term_lufs, momentary_rms_db, short_term_rms_db) = measure(64);
+
+        assert!((momentary_lufs + 23.0).abs() < 0.1, "{momentary_lufs}");
+        assert!((short_term_lufs + 23.0).abs() < 0.1, "{short_term_lufs}");
+        for db in momentary_rms_db.into_iter().chain(short_term_rms_db) {
+            // The RMS of a sine is 3 dB below its peak.
+            assert!((db + 26.0103).abs() < 0.01, "{db}");
+        }
+
+        // The windows don't depend on the block size.
+        assert_eq!(
+            measure(1_000),
+            (
+                momentary_lufs,
+                short_term_lufs,
+                momentary_rms_db,
+                short_term_rms_db
+            )
+        );
     }
 }
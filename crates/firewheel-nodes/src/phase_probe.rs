@@ -0,0 +1,356 @@
+//! A node that measures the delay between two signals.
+
+use bevy_platform::sync::atomic::{AtomicI32, Ordering};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::{vec, Vec};
+
+use firewheel_core::{
+    atomic_float::AtomicF32,
+    channel_config::{ChannelConfig, ChannelCount},
+    collector::ArcGc,
+    diff::{Diff, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The value of the shared delay when nothing has been measured yet.
+const NO_MEASUREMENT: i32 = i32::MIN;
+
+/// The configuration of a [`PhaseProbeNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseProbeNodeConfig {
+    /// The largest delay in seconds (in either direction) that can be
+    /// detected.
+    ///
+    /// The cost of a measurement grows linearly with this value, so keep it
+    /// only as large as needed.
+    ///
+    /// By default this is set to `0.01` (10ms).
+    pub max_delay_seconds: f32,
+    /// The length of audio in seconds that each measurement is made over.
+    ///
+    /// By default this is set to `0.1` (100ms).
+    pub window_seconds: f32,
+}
+
+impl Default for PhaseProbeNodeConfig {
+    fn default() -> Self {
+        Self {
+            max_delay_seconds: 0.01,
+            window_seconds: 0.1,
+        }
+    }
+}
+
+/// A debugging node that estimates the delay between its two (mono) inputs
+/// using cross-correlation.
+///
+/// Input `0` is the reference signal and input `1` is the signal being
+/// compared. This is useful for verifying that delay compensation worked in
+/// complex graphs: connect two paths that should be aligned and check that
+/// [`PhaseProbeState::delay_frames`] reports `0`.
+///
+/// This node has no outputs. Measuring is fairly expensive, so it is not
+/// intended to be left running in production.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseProbeNode {
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for PhaseProbeNode {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// The state of a [`PhaseProbeNode`]. This contains the latest measurement.
+#[derive(Clone)]
+pub struct PhaseProbeState {
+    shared_state: ArcGc<SharedState>,
+}
+
+impl PhaseProbeState {
+    fn new() -> Self {
+        Self {
+            shared_state: ArcGc::new(SharedState {
+                delay_frames: AtomicI32::new(NO_MEASUREMENT),
+                correlation: AtomicF32::new(0.0),
+            }),
+        }
+    }
+
+    /// The latest estimate of how many frames input `1` lags behind input
+    /// `0`. A negative value means that input `1` is ahead of input `0`.
+    ///
+    /// Returns `None` if nothing has been measured yet (i.e. if either
+    /// input has been silent the whole time).
+    pub fn delay_frames(&self) -> Option<i32> {
+        let delay_frames = self.shared_state.delay_frames.load(Ordering::Relaxed);
+        (delay_frames != NO_MEASUREMENT).then_some(delay_frames)
+    }
+
+    /// The normalized correlation of the two inputs at the measured delay
+    /// in the range `[-1.0, 1.0]`.
+    ///
+    /// A value close to `1.0` means the inputs are copies of each other, so
+    /// the reported delay can be trusted. A value close to `-1.0` means the
+    /// inputs are copies with opposite polarity.
+    pub fn correlation(&self) -> f32 {
+        self.shared_state.correlation.load(Ordering::Relaxed)
+    }
+}
+
+impl AudioNode for PhaseProbeNode {
+    type Configuration = PhaseProbeNodeConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("phase_probe")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::STEREO,
+                num_outputs: ChannelCount::ZERO,
+            })
+            .custom_state(PhaseProbeState::new())
+            .analysis_only(true)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let custom_state = cx.custom_state::<PhaseProbeState>().unwrap();
+
+        Processor::new(
+            *self,
+            *config,
+            cx.stream_info.sample_rate.get(),
+            ArcGc::clone(&custom_state.shared_state),
+        )
+    }
+}
+
+struct Processor {
+    params: PhaseProbeNode,
+    config: PhaseProbeNodeConfig,
+    shared_state: ArcGc<SharedState>,
+
+    /// The recorded reference and compared signals. Each holds one window
+    /// plus `max_lag` frames of padding on both sides.
+    reference: Vec<f32>,
+    compared: Vec<f32>,
+    filled: usize,
+    max_lag: usize,
+}
+
+impl Processor {
+    fn new(
+        params: PhaseProbeNode,
+        config: PhaseProbeNodeConfig,
+        sample_rate: u32,
+        shared_state: ArcGc<SharedState>,
+    ) -> Self {
+        let mut processor = Self {
+            params,
+            config,
+            shared_state,
+            reference: Vec::new(),
+            compared: Vec::new(),
+            filled: 0,
+            max_lag: 0,
+        };
+        processor.allocate(sample_rate);
+        processor
+    }
+
+    fn allocate(&mut self, sample_rate: u32) {
+        let sample_rate = sample_rate as f32;
+        self.max_lag = (self.config.max_delay_seconds.max(0.0) * sample_rate).round() as usize;
+        let window = ((self.config.window_seconds * sample_rate).round() as usize).max(1);
+
+        let len = window + 2 * self.max_lag;
+        self.reference = vec![0.0; len];
+        self.compared = vec![0.0; len];
+        self.filled = 0;
+    }
+
+    /// Record the two inputs, making a measurement every time the buffers
+    /// are full.
+    fn push(&mut self, reference: &[f32], compared: &[f32]) {
+        let mut offset = 0;
+        while offset < reference.len() {
+            let frames = (reference.len() - offset).min(self.reference.len() - self.filled);
+
+            self.reference[self.filled..self.filled + frames]
+                .copy_from_slice(&reference[offset..offset + frames]);
+            self.compared[self.filled..self.filled + frames]
+                .copy_from_slice(&compared[offset..offset + frames]);
+
+            self.filled += frames;
+            offset += frames;
+
+            if self.filled == self.reference.len() {
+                self.measure();
+                self.filled = 0;
+            }
+        }
+    }
+
+    fn measure(&mut self) {
+        let max_lag = self.max_lag;
+        let window = self.reference.len() - 2 * max_lag;
+        let reference = &self.reference[max_lag..max_lag + window];
+
+        let reference_energy: f32 = reference.iter().map(|s| s * s).sum();
+        if reference_energy == 0.0 {
+            return;
+        }
+
+        let mut best_lag = 0;
+        let mut best_corr = 0.0;
+        let mut best_score = f32::NEG_INFINITY;
+        for lag in 0..=2 * max_lag {
+            let compared = &self.compared[lag..lag + window];
+
+            let mut dot = 0.0;
+            let mut compared_energy = 0.0;
+            for (&r, &c) in reference.iter().zip(compared.iter()) {
+                dot += r * c;
+                compared_energy += c * c;
+            }
+
+            if compared_energy == 0.0 {
+                continue;
+            }
+
+            let corr = dot / (reference_energy * compared_energy).sqrt();
+            if corr.abs() > best_score {
+                best_score = corr.abs();
+                best_corr = corr;
+                best_lag = lag;
+            }
+        }
+
+        if best_score == f32::NEG_INFINITY {
+            return;
+        }
+
+        self.shared_state
+            .delay_frames
+            .store(best_lag as i32 - max_lag as i32, Ordering::Relaxed);
+        self.shared_state
+            .correlation
+            .store(best_corr, Ordering::Relaxed);
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<PhaseProbeNode>() {
+            self.params.apply(patch);
+        }
+
+        if !self.params.enabled {
+            self.filled = 0;
+            return ProcessStatus::Bypass;
+        }
+
+        self.push(
+            &buffers.inputs[0][..info.frames],
+            &buffers.inputs[1][..info.frames],
+        );
+
+        // There are no outputs in this node.
+        ProcessStatus::Bypass
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        if stream_info.sample_rate != stream_info.prev_sample_rate {
+            self.allocate(stream_info.sample_rate.get());
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SharedState {
+    delay_frames: AtomicI32,
+    correlation: AtomicF32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noise(frames: usize) -> Vec<f32> {
+        let mut seed = 0x1234_5678u32;
+        (0..frames)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1u32 << 24) as f32 * 2.0 - 1.0
+            })
+            .collect()
+    }
+
+    fn measure_delay(delay: i32) -> PhaseProbeState {
+        let state = PhaseProbeState::new();
+        let mut processor = Processor::new(
+            PhaseProbeNode::default(),
+            PhaseProbeNodeConfig::default(),
+            48_000,
+            ArcGc::clone(&state.shared_state),
+        );
+
+        let signal = noise(48_000);
+        let shifted: Vec<f32> = (0..signal.len() as i32)
+            .map(|i| {
+                signal
+                    .get((i - delay) as usize)
+                    .copied()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        assert_eq!(state.delay_frames(), None);
+
+        for (reference, compared) in signal.chunks(512).zip(shifted.chunks(512)) {
+            processor.push(reference, compared);
+        }
+
+        state
+    }
+
+    #[test]
+    fn reports_delay_of_delayed_copy() {
+        let state = measure_delay(37);
+        assert_eq!(state.delay_frames(), Some(37));
+        assert!(state.correlation() > 0.95);
+
+        let state = measure_delay(-120);
+        assert_eq!(state.delay_frames(), Some(-120));
+
+        let state = measure_delay(0);
+        assert_eq!(state.delay_frames(), Some(0));
+        assert!(state.correlation() > 0.99);
+    }
+}
@@ -0,0 +1,396 @@
+use bevy_platform::prelude::Vec;
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        coeff_update::{CoeffUpdateFactor, CoeffUpdateMask},
+        declick::{DeclickFadeCurve, Declicker},
+        filter::{
+            butterworth::Q_BUTTERWORTH_ORD2,
+            smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+            svf::{SvfCoeff, SvfCoeffSimd, SvfStateSimd},
+        },
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The default number of allpass stages in a [`PhaseRotatorNode`].
+pub const DEFAULT_STAGES: usize = 2;
+
+pub const MIN_HZ: f32 = 20.0;
+pub const MIN_Q: f32 = 0.1;
+pub const MAX_Q: f32 = 10.0;
+
+pub type PhaseRotatorMonoNode = PhaseRotatorNode<1>;
+pub type PhaseRotatorStereoNode = PhaseRotatorNode<2>;
+
+/// The configuration for a [`PhaseRotatorNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseRotatorNodeConfig {
+    /// The number of allpass stages in the chain, which sets the amount of
+    /// rotation. Each stage rotates the phase by `180` degrees at
+    /// [`PhaseRotatorNode::center_hz`], and by up to `360` degrees at the
+    /// top of the spectrum.
+    ///
+    /// By default this is set to `2`.
+    pub stages: usize,
+}
+
+impl Default for PhaseRotatorNodeConfig {
+    fn default() -> Self {
+        Self {
+            stages: DEFAULT_STAGES,
+        }
+    }
+}
+
+/// A node that rotates the phase of a signal across frequency without
+/// changing its magnitude, using a chain of allpass filters.
+///
+/// This is useful for aligning the phase of two similar signals that are
+/// mixed together (i.e. two microphones on the same source), or for making
+/// an asymmetric waveform more symmetric.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaseRotatorNode<const CHANNELS: usize> {
+    /// The frequency in hertz around which the phase is rotated.
+    ///
+    /// By default this is set to `200.0`.
+    pub center_hz: f32,
+    /// The quality factor of each allpass stage. Higher values make the
+    /// rotation happen over a narrower range of frequencies.
+    ///
+    /// By default this is set to `0.7071` (a Butterworth response).
+    pub q_factor: f32,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+
+    /// The time in seconds of the internal smoothing filter.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+    /// An exponent representing the rate at which DSP coefficients are
+    /// updated when parameters are being smoothed.
+    ///
+    /// The resulting number of frames (samples in a single channel of audio)
+    /// that will elapse between each update is calculated as
+    /// `2^coeff_update_factor`.
+    ///
+    /// By default this is set to `5`.
+    pub coeff_update_factor: CoeffUpdateFactor,
+}
+
+impl<const CHANNELS: usize> Default for PhaseRotatorNode<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            center_hz: 200.0,
+            q_factor: Q_BUTTERWORTH_ORD2,
+            enabled: true,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            coeff_update_factor: CoeffUpdateFactor(5),
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for PhaseRotatorNode<CHANNELS> {
+    type Configuration = PhaseRotatorNodeConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("phase_rotator")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(self, config, cx.stream_info);
+        for param in [&mut processor.center_hz, &mut processor.q_factor] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    stages: Vec<SvfStateSimd<CHANNELS>>,
+    coeff: SvfCoeffSimd<CHANNELS>,
+
+    center_hz: SmoothedParam,
+    q_factor: SmoothedParam,
+
+    enable_declicker: Declicker,
+    coeff_update_mask: CoeffUpdateMask,
+    max_hz: f32,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(
+        params: &PhaseRotatorNode<CHANNELS>,
+        config: &PhaseRotatorNodeConfig,
+        info: &StreamInfo,
+    ) -> Self {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+        let max_hz = max_hz(info.sample_rate.get());
+        let center_hz = params.center_hz.clamp(MIN_HZ, max_hz);
+        let q_factor = params.q_factor.clamp(MIN_Q, MAX_Q);
+
+        Self {
+            stages: (0..config.stages.max(1))
+                .map(|_| SvfStateSimd::default())
+                .collect(),
+            coeff: SvfCoeffSimd::splat(SvfCoeff::allpass(
+                center_hz,
+                q_factor,
+                info.sample_rate_recip as f32,
+            )),
+            center_hz: SmoothedParam::new(center_hz, smoother_config, info.sample_rate),
+            q_factor: SmoothedParam::new(q_factor, smoother_config, info.sample_rate),
+            enable_declicker: Declicker::from_enabled(params.enabled),
+            coeff_update_mask: params.coeff_update_factor.mask(),
+            max_hz,
+        }
+    }
+
+    fn update_coeff(&mut self, center_hz: f32, q_factor: f32, sample_rate_recip: f32) {
+        self.coeff = SvfCoeffSimd::splat(SvfCoeff::allpass(center_hz, q_factor, sample_rate_recip));
+    }
+
+    /// Render the phase-rotated signal into `outputs`.
+    fn process_block(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        sample_rate_recip: f32,
+    ) {
+        assert!(inputs.len() == CHANNELS);
+        assert!(outputs.len() == CHANNELS);
+        for ch in inputs.iter() {
+            assert!(ch.len() >= frames);
+        }
+        for ch in outputs.iter() {
+            assert!(ch.len() >= frames);
+        }
+
+        let smoothing = self.center_hz.is_smoothing() || self.q_factor.is_smoothing();
+
+        for i in 0..frames {
+            if smoothing {
+                let center_hz = self.center_hz.next_smoothed();
+                let q_factor = self.q_factor.next_smoothed();
+
+                if self.coeff_update_mask.do_update(i) {
+                    self.update_coeff(center_hz, q_factor, sample_rate_recip);
+                }
+            }
+
+            let mut s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
+                // Safety: These bounds have been checked above.
+                unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
+            });
+
+            for stage in self.stages.iter_mut() {
+                s = stage.process(s, &self.coeff);
+            }
+
+            for (ch_i, &s) in s.iter().enumerate() {
+                // Safety: These bounds have been checked above.
+                unsafe {
+                    *outputs.get_unchecked_mut(ch_i).get_unchecked_mut(i) = s;
+                }
+            }
+        }
+
+        if smoothing && (self.center_hz.settle() & self.q_factor.settle()) {
+            self.update_coeff(
+                self.center_hz.target_value(),
+                self.q_factor.target_value(),
+                sample_rate_recip,
+            );
+        }
+    }
+
+    fn reset_state(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<PhaseRotatorNode<CHANNELS>>() {
+            match patch {
+                PhaseRotatorNodePatch::CenterHz(center_hz) => {
+                    self.center_hz
+                        .set_value(center_hz.clamp(MIN_HZ, self.max_hz));
+                }
+                PhaseRotatorNodePatch::QFactor(q_factor) => {
+                    self.q_factor.set_value(q_factor.clamp(MIN_Q, MAX_Q));
+                }
+                PhaseRotatorNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                }
+                PhaseRotatorNodePatch::SmoothSeconds(seconds) => {
+                    self.center_hz.set_smooth_seconds(seconds, info.sample_rate);
+                    self.q_factor.set_smooth_seconds(seconds, info.sample_rate);
+                }
+                PhaseRotatorNodePatch::CoeffUpdateFactor(f) => {
+                    self.coeff_update_mask = f.mask();
+                }
+            }
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            return ProcessStatus::Bypass;
+        }
+
+        let inputs_silent = info.in_silence_mask.all_channels_silent(CHANNELS);
+
+        if inputs_silent && info.prev_output_was_silent && self.enable_declicker.has_settled() {
+            // Outputs will be silent, so no need to process.
+            if self.center_hz.is_smoothing() || self.q_factor.is_smoothing() {
+                self.center_hz.reset_to_target();
+                self.q_factor.reset_to_target();
+                self.update_coeff(
+                    self.center_hz.target_value(),
+                    self.q_factor.target_value(),
+                    info.sample_rate_recip as f32,
+                );
+            }
+            self.reset_state();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.process_block(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            info.sample_rate_recip as f32,
+        );
+
+        // Crossfade between the rotated and dry signals to declick
+        // enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        if inputs_silent {
+            // Let the filter tails ring out, then stop processing.
+            return buffers.check_for_silence_on_outputs(f32::EPSILON);
+        }
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.center_hz.update_sample_rate(stream_info.sample_rate);
+        self.q_factor.update_sample_rate(stream_info.sample_rate);
+        self.max_hz = max_hz(stream_info.sample_rate.get());
+
+        let center_hz = self.center_hz.target_value().min(self.max_hz);
+        self.center_hz.set_value(center_hz);
+        self.center_hz.reset_to_target();
+        self.q_factor.reset_to_target();
+        self.update_coeff(
+            center_hz,
+            self.q_factor.target_value(),
+            stream_info.sample_rate_recip as f32,
+        );
+    }
+
+    fn flush_idle_state(&mut self) {
+        self.reset_state();
+    }
+}
+
+fn max_hz(sample_rate: u32) -> f32 {
+    sample_rate as f32 * 0.45
+}
+
+#[cfg(test)]
+mod tests {
+    use core::f32::consts::TAU;
+
+    use super::*;
+
+    const FRAMES: usize = 8192;
+
+    #[test]
+    fn magnitude_is_flat_and_phase_is_rotated() {
+        let stream_info = StreamInfo::default();
+        let sample_rate = stream_info.sample_rate.get() as f32;
+        let node = PhaseRotatorMonoNode {
+            center_hz: 1_000.0,
+            ..Default::default()
+        };
+        let mut processor =
+            Processor::new(&node, &PhaseRotatorNodeConfig { stages: 3 }, &stream_info);
+
+        let mut impulse = vec![0.0; FRAMES];
+        impulse[0] = 1.0;
+        let mut response = vec![0.0; FRAMES];
+        processor.process_block(
+            &[&impulse],
+            &mut [&mut response],
+            FRAMES,
+            sample_rate.recip(),
+        );
+
+        let mut max_phase_diff: f32 = 0.0;
+
+        // Evaluate the DFT of the impulse response at log-spaced frequencies.
+        for i in 0..100 {
+            let hz = 50.0 * (300.0f32).powf(i as f32 / 100.0);
+            let w = TAU * hz / sample_rate;
+            let (re, im) = response
+                .iter()
+                .enumerate()
+                .fold((0.0f32, 0.0f32), |(re, im), (n, &s)| {
+                    (re + s * (w * n as f32).cos(), im - s * (w * n as f32).sin())
+                });
+
+            let magnitude = (re * re + im * im).sqrt();
+            assert!((magnitude - 1.0).abs() < 0.01, "{magnitude} at {hz}Hz");
+
+            max_phase_diff = max_phase_diff.max(im.atan2(re).abs());
+        }
+
+        // The phase of the input is `0` at every frequency.
+        assert!(max_phase_diff > 1.0);
+    }
+}
@@ -0,0 +1,481 @@
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use core::f32::consts::TAU;
+
+use bevy_platform::prelude::Vec;
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        coeff_update::{CoeffUpdateFactor, CoeffUpdateMask},
+        declick::{DeclickFadeCurve, Declicker},
+        fade::FadeCurve,
+        filter::{
+            butterworth::Q_BUTTERWORTH_ORD2,
+            smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+            svf::{SvfCoeff, SvfCoeffSimd, SvfStateSimd},
+        },
+        mix::{Mix, MixDSP},
+        phase_accumulator::PhaseAccumulator,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The default number of allpass stages in a [`PhaserNode`].
+pub const DEFAULT_STAGES: usize = 4;
+
+/// The number of octaves the LFO sweeps above and below `center_hz` when
+/// `depth` is `1.0`.
+pub const DEPTH_OCTAVES: f32 = 2.0;
+
+/// The maximum absolute value of the feedback parameter.
+pub const MAX_FEEDBACK: f32 = 0.95;
+
+const MIN_HZ: f32 = 20.0;
+
+pub type PhaserMonoNode = PhaserNode<1>;
+pub type PhaserStereoNode = PhaserNode<2>;
+
+/// The configuration for a [`PhaserNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaserNodeConfig {
+    /// The number of allpass stages in the chain. Each stage adds one notch
+    /// to the frequency response.
+    ///
+    /// By default this is set to `4`.
+    pub stages: usize,
+}
+
+impl Default for PhaserNodeConfig {
+    fn default() -> Self {
+        Self {
+            stages: DEFAULT_STAGES,
+        }
+    }
+}
+
+/// A phaser node, which sweeps a series of notches across the spectrum
+/// using a chain of allpass filters modulated by an LFO.
+#[derive(Diff, Patch, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhaserNode<const CHANNELS: usize> {
+    /// The rate of the LFO in hertz.
+    ///
+    /// By default this is set to `0.5`.
+    pub rate_hz: f32,
+    /// The center frequency of the sweep in hertz.
+    ///
+    /// By default this is set to `800.0`.
+    pub center_hz: f32,
+    /// The depth of the sweep in the range `[0.0, 1.0]`, where `1.0` sweeps
+    /// [`DEPTH_OCTAVES`] octaves above and below `center_hz`.
+    ///
+    /// By default this is set to `0.5`.
+    pub depth: f32,
+    /// The amount of the allpass chain's output that is fed back into its
+    /// input, in the range `[-0.95, 0.95]`.
+    ///
+    /// Positive values deepen the notches (most noticeably when `mix` is
+    /// below [`Mix::CENTER`]), while negative values emphasize resonant peaks
+    /// between them.
+    ///
+    /// By default this is set to `0.0`.
+    pub feedback: f32,
+    /// The mix between the dry and the phased signal. With no feedback, the
+    /// notches cancel out completely at [`Mix::CENTER`].
+    ///
+    /// The wet amount is scaled by the context's global wet amount (see
+    /// [`ProcInfo::global_wet`](firewheel_core::node::ProcInfo::global_wet)).
+    ///
+    /// By default this is set to [`Mix::CENTER`].
+    pub mix: Mix,
+    /// The algorithm used to map the normalized mix value in the range `[0.0,
+    /// 1.0]` to the corresponding gain values for the two signals.
+    ///
+    /// By default this is set to [`FadeCurve::Linear`].
+    pub fade_curve: FadeCurve,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+
+    /// The time in seconds of the internal smoothing filter.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+    /// An exponent representing the rate at which DSP coefficients are
+    /// updated as the LFO sweeps.
+    ///
+    /// The resulting number of frames (samples in a single channel of audio)
+    /// that will elapse between each update is calculated as
+    /// `2^coeff_update_factor`.
+    ///
+    /// By default this is set to `5`.
+    pub coeff_update_factor: CoeffUpdateFactor,
+}
+
+impl<const CHANNELS: usize> Default for PhaserNode<CHANNELS> {
+    fn default() -> Self {
+        Self {
+            rate_hz: 0.5,
+            center_hz: 800.0,
+            depth: 0.5,
+            feedback: 0.0,
+            mix: Mix::CENTER,
+            fade_curve: FadeCurve::Linear,
+            enabled: true,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            coeff_update_factor: CoeffUpdateFactor(5),
+        }
+    }
+}
+
+impl<const CHANNELS: usize> AudioNode for PhaserNode<CHANNELS> {
+    type Configuration = PhaserNodeConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("phaser")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+                num_outputs: ChannelCount::new(CHANNELS as u32).unwrap(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(self, config, cx.stream_info);
+        for param in [
+            &mut processor.center_hz,
+            &mut processor.depth,
+            &mut processor.feedback,
+        ] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+            .mix
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    stages: Vec<SvfStateSimd<CHANNELS>>,
+    coeff: SvfCoeffSimd<CHANNELS>,
+    feedback_state: [f32; CHANNELS],
+
+    lfo: PhaseAccumulator,
+    rate_hz: f32,
+
+    center_hz: SmoothedParam,
+    depth: SmoothedParam,
+    feedback: SmoothedParam,
+    mix: MixDSP,
+    mix_value: Mix,
+    fade_curve: FadeCurve,
+
+    enable_declicker: Declicker,
+    coeff_update_mask: CoeffUpdateMask,
+    max_hz: f32,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(params: &PhaserNode<CHANNELS>, config: &PhaserNodeConfig, info: &StreamInfo) -> Self {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+
+        Self {
+            stages: (0..config.stages.max(1))
+                .map(|_| SvfStateSimd::default())
+                .collect(),
+            coeff: SvfCoeffSimd::default(),
+            feedback_state: [0.0; CHANNELS],
+            lfo: PhaseAccumulator::new(params.rate_hz, info.sample_rate),
+            rate_hz: params.rate_hz.max(0.0),
+            center_hz: SmoothedParam::new(params.center_hz, smoother_config, info.sample_rate),
+            depth: SmoothedParam::new(
+                params.depth.clamp(0.0, 1.0),
+                smoother_config,
+                info.sample_rate,
+            ),
+            feedback: SmoothedParam::new(
+                params.feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK),
+                smoother_config,
+                info.sample_rate,
+            ),
+            mix: MixDSP::new(
+                params.mix,
+                params.fade_curve.clone(),
+                smoother_config,
+                info.sample_rate,
+            ),
+            mix_value: params.mix,
+            fade_curve: params.fade_curve.clone(),
+            enable_declicker: Declicker::from_enabled(params.enabled),
+            coeff_update_mask: params.coeff_update_factor.mask(),
+            max_hz: max_hz(info.sample_rate.get()),
+        }
+    }
+
+    /// Render the phased (wet) signal into `outputs`.
+    fn process_wet(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        sample_rate_recip: f32,
+    ) {
+        assert!(inputs.len() == CHANNELS);
+        assert!(outputs.len() == CHANNELS);
+        for ch in inputs.iter() {
+            assert!(ch.len() >= frames);
+        }
+        for ch in outputs.iter() {
+            assert!(ch.len() >= frames);
+        }
+
+        for i in 0..frames {
+            let center_hz = self.center_hz.next_smoothed();
+            let depth = self.depth.next_smoothed();
+            let feedback = self.feedback.next_smoothed();
+
+            // Keep the LFO phase continuous across blocks.
+            let lfo_phase = self.lfo.next_phase();
+
+            if self.coeff_update_mask.do_update(i) {
+                let lfo = (lfo_phase * TAU).sin();
+                let cutoff_hz =
+                    (center_hz * (depth * DEPTH_OCTAVES * lfo).exp2()).clamp(MIN_HZ, self.max_hz);
+
+                self.coeff = SvfCoeffSimd::splat(SvfCoeff::allpass(
+                    cutoff_hz,
+                    Q_BUTTERWORTH_ORD2,
+                    sample_rate_recip,
+                ));
+            }
+
+            let mut s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
+                // Safety: These bounds have been checked above.
+                let x = unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) };
+                x - feedback * self.feedback_state[ch_i]
+            });
+
+            for stage in self.stages.iter_mut() {
+                s = stage.process(s, &self.coeff);
+            }
+
+            self.feedback_state = s;
+
+            for (ch_i, &s) in s.iter().enumerate() {
+                // Safety: These bounds have been checked above.
+                unsafe {
+                    *outputs.get_unchecked_mut(ch_i).get_unchecked_mut(i) = s;
+                }
+            }
+        }
+
+        self.center_hz.settle();
+        self.depth.settle();
+        self.feedback.settle();
+    }
+
+    fn reset_state(&mut self) {
+        for stage in self.stages.iter_mut() {
+            stage.reset();
+        }
+        self.feedback_state = [0.0; CHANNELS];
+    }
+}
+
+impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<PhaserNode<CHANNELS>>() {
+            match patch {
+                PhaserNodePatch::RateHz(rate_hz) => {
+                    self.rate_hz = rate_hz.max(0.0);
+                    self.lfo.set_freq(self.rate_hz, info.sample_rate);
+                }
+                PhaserNodePatch::CenterHz(center_hz) => {
+                    self.center_hz.set_value(center_hz);
+                }
+                PhaserNodePatch::Depth(depth) => {
+                    self.depth.set_value(depth.clamp(0.0, 1.0));
+                }
+                PhaserNodePatch::Feedback(feedback) => {
+                    self.feedback
+                        .set_value(feedback.clamp(-MAX_FEEDBACK, MAX_FEEDBACK));
+                }
+                PhaserNodePatch::Mix(mix) => {
+                    self.mix_value = mix;
+                    self.mix.set_mix(mix, self.fade_curve.clone());
+                }
+                PhaserNodePatch::FadeCurve(fade_curve) => {
+                    self.mix.set_mix(self.mix_value, fade_curve.clone());
+                    self.fade_curve = fade_curve;
+                }
+                PhaserNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                }
+                PhaserNodePatch::SmoothSeconds(seconds) => {
+                    self.center_hz.set_smooth_seconds(seconds, info.sample_rate);
+                    self.depth.set_smooth_seconds(seconds, info.sample_rate);
+                    self.feedback.set_smooth_seconds(seconds, info.sample_rate);
+                    self.mix.set_smooth_seconds(seconds, info.sample_rate);
+                }
+                PhaserNodePatch::CoeffUpdateFactor(f) => {
+                    self.coeff_update_mask = f.mask();
+                }
+            }
+        }
+        self.mix.set_global_wet(info.global_wet);
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            return ProcessStatus::Bypass;
+        }
+
+        let inputs_silent = info.in_silence_mask.all_channels_silent(CHANNELS);
+
+        if inputs_silent && info.prev_output_was_silent && self.enable_declicker.has_settled() {
+            // Outputs will be silent, so no need to process.
+            self.center_hz.reset_to_target();
+            self.depth.reset_to_target();
+            self.feedback.reset_to_target();
+            self.mix.reset_to_target();
+            self.reset_state();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.process_wet(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            info.sample_rate_recip as f32,
+        );
+
+        let [scratch_0, scratch_1] = extra.scratch_buffers.channels_mut::<2>();
+        self.mix.mix_dry_into_wet(
+            info.frames,
+            buffers.inputs,
+            buffers.outputs,
+            scratch_0,
+            scratch_1,
+        );
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        if inputs_silent {
+            // Let the feedback tail ring out, then stop processing.
+            return buffers.check_for_silence_on_outputs(f32::EPSILON);
+        }
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.center_hz.update_sample_rate(stream_info.sample_rate);
+        self.depth.update_sample_rate(stream_info.sample_rate);
+        self.feedback.update_sample_rate(stream_info.sample_rate);
+        self.mix.update_sample_rate(stream_info.sample_rate);
+        self.max_hz = max_hz(stream_info.sample_rate.get());
+        self.lfo.set_freq(self.rate_hz, stream_info.sample_rate);
+    }
+
+    fn flush_idle_state(&mut self) {
+        self.reset_state();
+    }
+}
+
+fn max_hz(sample_rate: u32) -> f32 {
+    sample_rate as f32 * 0.45
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FRAMES: usize = 8192;
+
+    /// Find the lowest point in the magnitude response of a static (non-
+    /// sweeping) phaser.
+    fn min_magnitude(feedback: f32) -> f32 {
+        let node = PhaserMonoNode {
+            rate_hz: 0.0,
+            depth: 0.0,
+            feedback,
+            mix: Mix::new(0.3),
+            ..Default::default()
+        };
+        let stream_info = StreamInfo::default();
+        let sample_rate = stream_info.sample_rate.get() as f32;
+        let mut processor = Processor::new(&node, &PhaserNodeConfig::default(), &stream_info);
+
+        let mut impulse = vec![0.0; FRAMES];
+        impulse[0] = 1.0;
+        let mut wet = vec![0.0; FRAMES];
+        processor.process_wet(&[&impulse], &mut [&mut wet], FRAMES, sample_rate.recip());
+
+        let (dry_gain, wet_gain) = node.mix.compute_gains(&node.fade_curve);
+        let response: Vec<f32> = impulse
+            .iter()
+            .zip(wet.iter())
+            .map(|(&d, &w)| d * dry_gain + w * wet_gain)
+            .collect();
+
+        // Evaluate the DFT of the impulse response at log-spaced frequencies.
+        (0..400)
+            .map(|i| {
+                let hz = 50.0 * (300.0f32).powf(i as f32 / 400.0);
+                let w = TAU * hz / sample_rate;
+                let (re, im) = response
+                    .iter()
+                    .enumerate()
+                    .fold((0.0, 0.0), |(re, im), (n, &s)| {
+                        (re + s * (w * n as f32).cos(), im - s * (w * n as f32).sin())
+                    });
+                (re * re + im * im).sqrt()
+            })
+            .fold(f32::MAX, f32::min)
+    }
+
+    #[test]
+    fn feedback_deepens_notches() {
+        let no_feedback = min_magnitude(0.0);
+        let some_feedback = min_magnitude(0.3);
+        let more_feedback = min_magnitude(0.5);
+
+        assert!(some_feedback < no_feedback);
+        assert!(more_feedback < some_feedback);
+    }
+}
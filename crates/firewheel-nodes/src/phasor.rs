@@ -0,0 +1,175 @@
+//! A node that outputs the position of the musical transport as an
+//! audio-rate ramp.
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The smallest allowed value for [`PhasorNode::division_beats`].
+pub const MIN_DIVISION_BEATS: f64 = 1.0 / 64.0;
+
+/// A node that outputs a `0.0 -> 1.0` ramp locked to the musical transport
+/// (Mono output only).
+///
+/// The ramp completes one cycle every [`PhasorNode::division_beats`] beats and
+/// is computed directly from the transport position on every frame, so it
+/// never drifts out of sync. This can be used to drive the modulation inputs
+/// of other nodes for tempo-locked effects.
+///
+/// The output jumps from just below `1.0` back to `0.0` at the end of each
+/// cycle. Avoid smoothing this signal directly, as a smoother would turn
+/// that jump into a glitch partway through the next cycle.
+///
+/// If the transport is not playing, then the last phase is held.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PhasorNode {
+    /// The length of one cycle in beats. For example, `1.0` is one cycle per
+    /// beat, `4.0` is one cycle per bar of `4/4`, and `0.25` is one cycle per
+    /// sixteenth note.
+    ///
+    /// By default this is set to `1.0`.
+    pub division_beats: f64,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for PhasorNode {
+    fn default() -> Self {
+        Self {
+            division_beats: 1.0,
+            enabled: true,
+        }
+    }
+}
+
+impl AudioNode for PhasorNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("phasor")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: ChannelCount::MONO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        _cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor {
+            params: *self,
+            last_phase: 0.0,
+        }
+    }
+}
+
+struct Processor {
+    params: PhasorNode,
+    last_phase: f32,
+}
+
+impl Processor {
+    /// Fill `out` with the phase starting at `start_beats`, advancing by
+    /// `beats_per_frame` each frame.
+    fn render(&mut self, start_beats: f64, beats_per_frame: f64, out: &mut [f32]) {
+        let cycles_per_beat = self.params.division_beats.max(MIN_DIVISION_BEATS).recip();
+
+        for (i, s) in out.iter_mut().enumerate() {
+            let cycles = (start_beats + beats_per_frame * i as f64) * cycles_per_beat;
+            let phase = (cycles - cycles.floor()) as f32;
+
+            // Rounding to `f32` can land exactly on `1.0` right before the
+            // wrap, so fold that back to the start of the next cycle.
+            *s = if phase >= 1.0 { 0.0 } else { phase };
+        }
+
+        if let Some(&last) = out.last() {
+            self.last_phase = last;
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<PhasorNode>() {
+            self.params.apply(patch);
+        }
+
+        if !self.params.enabled {
+            self.last_phase = 0.0;
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let Some(playhead) = info.playhead_range() else {
+            if self.last_phase == 0.0 {
+                return ProcessStatus::ClearAllOutputs;
+            }
+
+            buffers.outputs[0].fill(self.last_phase);
+            return ProcessStatus::OutputsModified;
+        };
+
+        let beats_per_frame = (playhead.end.0 - playhead.start.0) / info.frames as f64;
+        self.render(playhead.start.0, beats_per_frame, buffers.outputs[0]);
+
+        ProcessStatus::OutputsModified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count_cycles(division_beats: f64) -> usize {
+        let mut processor = Processor {
+            params: PhasorNode {
+                division_beats,
+                ..Default::default()
+            },
+            last_phase: 0.0,
+        };
+
+        // 120 BPM at 48kHz for 4 seconds (8 beats) in blocks of 512 frames.
+        let beats_per_frame = 2.0 / 48_000.0;
+        let mut out = vec![0.0; 4 * 48_000];
+        for (block_i, block) in out.chunks_mut(512).enumerate() {
+            let start_beats = (block_i * 512) as f64 * beats_per_frame;
+            processor.render(start_beats, beats_per_frame, block);
+        }
+
+        assert!(out.iter().all(|&s| (0.0..1.0).contains(&s)));
+
+        // Count the number of times the ramp wraps back around.
+        out.windows(2).filter(|w| w[1] < w[0]).count()
+    }
+
+    #[test]
+    fn one_cycle_per_division() {
+        // The first cycle starts at frame 0, so the final wrap at beat 8 lies
+        // just past the end of the rendered range.
+        assert_eq!(count_cycles(1.0), 7);
+        assert_eq!(count_cycles(4.0), 1);
+        assert_eq!(count_cycles(0.25), 31);
+    }
+}
@@ -0,0 +1,384 @@
+//! A real-time pitch shifter which keeps the duration of its input.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Patch},
+    dsp::filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+use realfft::num_complex::Complex;
+
+use crate::stft::Stft;
+
+/// The smallest allowed value for [`PitchShiftNodeConfig::fft_size`].
+pub const MIN_FFT_SIZE: usize = 256;
+
+/// The lowest allowed value for [`PitchShiftNode::semitones`].
+pub const MIN_SEMITONES: f32 = -24.0;
+/// The highest allowed value for [`PitchShiftNode::semitones`].
+pub const MAX_SEMITONES: f32 = 24.0;
+
+/// The configuration of a [`PitchShiftNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PitchShiftNodeConfig {
+    /// The number of input and output channels.
+    pub channels: NonZeroChannelCount,
+    /// The size of the FFT in frames. This is rounded up to a power of two of
+    /// at least [`MIN_FFT_SIZE`].
+    ///
+    /// Larger sizes track low and steady tones more accurately, at the cost
+    /// of smearing transients. The node adds this many frames of latency.
+    ///
+    /// By default this is set to `2048`.
+    pub fft_size: usize,
+}
+
+impl Default for PitchShiftNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            fft_size: 2048,
+        }
+    }
+}
+
+impl PitchShiftNodeConfig {
+    fn rounded_fft_size(&self) -> usize {
+        self.fft_size.max(MIN_FFT_SIZE).next_power_of_two()
+    }
+}
+
+/// A node that shifts the pitch of its input without changing its duration.
+///
+/// Unlike changing the playback speed of a sample, this works on any live
+/// signal. It uses a phase vocoder: the input is split into overlapping
+/// frames, the exact frequency of each bin is estimated from how far its
+/// phase advanced since the last frame, and each bin is moved to its
+/// shifted frequency before the frames are resynthesized with overlap-add.
+///
+/// This node adds latency of [`PitchShiftNodeConfig::fft_size`] frames.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PitchShiftNode {
+    /// The amount to shift the pitch by in semitones, in the range
+    /// `[-24.0, 24.0]`. For example, `12.0` shifts up by an octave.
+    ///
+    /// By default this is set to `0.0`.
+    pub semitones: f32,
+    /// The time in seconds of the internal smoothing filter for the pitch.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for PitchShiftNode {
+    fn default() -> Self {
+        Self {
+            semitones: 0.0,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl PitchShiftNode {
+    /// Construct a new `PitchShiftNode` from the given shift in semitones.
+    pub const fn from_semitones(semitones: f32) -> Self {
+        Self {
+            semitones,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for PitchShiftNode {
+    type Configuration = PitchShiftNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("pitch_shift")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+            .latency_frames(config.rounded_fft_size() as u32)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(*self, config, cx.stream_info.sample_rate);
+        processor
+            .semitones
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+/// The state of a single channel.
+struct Channel {
+    /// The phase of each analysis bin on the last frame.
+    last_phase: Vec<f32>,
+    /// The accumulated phase of each synthesis bin.
+    sum_phase: Vec<f32>,
+}
+
+struct Processor {
+    semitones: SmoothedParam,
+    stft: Stft,
+    channels: Vec<Channel>,
+
+    /// The magnitude and the estimated frequency (in bins) of each analysis
+    /// bin.
+    analysis: Vec<(f32, f32)>,
+    /// The magnitude and frequency (in bins) of each synthesis bin.
+    synthesis: Vec<(f32, f32)>,
+
+    /// The number of consecutive frames of silent input, saturating once
+    /// every buffer is guaranteed to have been flushed.
+    silent_frames: usize,
+}
+
+impl Processor {
+    fn new(params: PitchShiftNode, config: &PitchShiftNodeConfig, sample_rate: NonZeroU32) -> Self {
+        let num_channels = config.channels.get().get() as usize;
+        let stft = Stft::new(config.rounded_fft_size(), num_channels);
+        let num_bins = stft.num_bins();
+
+        Self {
+            semitones: SmoothedParam::new(
+                params.semitones.clamp(MIN_SEMITONES, MAX_SEMITONES),
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+            stft,
+            channels: (0..num_channels)
+                .map(|_| Channel {
+                    last_phase: vec![0.0; num_bins],
+                    sum_phase: vec![0.0; num_bins],
+                })
+                .collect(),
+            analysis: vec![(0.0, 0.0); num_bins],
+            synthesis: vec![(0.0, 0.0); num_bins],
+            silent_frames: 0,
+        }
+    }
+
+    /// Pitch shift a block of frames.
+    fn render(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        let hop_size = self.stft.hop_size();
+        let num_bins = self.stft.num_bins();
+
+        // The amount the phase of a bin advances between two analysis frames
+        // for every bin of frequency.
+        let phase_per_bin = core::f32::consts::TAU * hop_size as f32 / self.stft.fft_size() as f32;
+
+        self.stft.process(inputs, outputs, frames, |spectra| {
+            // The pitch only changes once per analysis frame, but the
+            // smoother still runs at the audio rate.
+            let mut semitones = self.semitones.target_value();
+            if self.semitones.is_smoothing() {
+                for _ in 0..hop_size {
+                    semitones = self.semitones.next_smoothed();
+                }
+                self.semitones.settle();
+            }
+            let ratio = 2.0f32.powf(semitones / 12.0);
+
+            for (spectrum, ch) in spectra.iter_mut().zip(self.channels.iter_mut()) {
+                // Estimate the exact frequency of each bin from the deviation
+                // of its phase advance from that of the bin's center
+                // frequency.
+                for (k, ((bin, last_phase), analysis)) in spectrum
+                    .iter()
+                    .zip(ch.last_phase.iter_mut())
+                    .zip(self.analysis.iter_mut())
+                    .enumerate()
+                {
+                    let (magnitude, phase) = bin.to_polar();
+                    let deviation = wrap_phase(phase - *last_phase - k as f32 * phase_per_bin);
+                    *last_phase = phase;
+
+                    *analysis = (magnitude, k as f32 + deviation / phase_per_bin);
+                }
+
+                // Move each bin to its shifted frequency.
+                self.synthesis.fill((0.0, 0.0));
+                for (k, &(magnitude, freq)) in self.analysis.iter().enumerate() {
+                    let shifted = (k as f32 * ratio).round() as usize;
+                    if shifted < num_bins {
+                        let synthesis = &mut self.synthesis[shifted];
+                        synthesis.0 += magnitude;
+                        synthesis.1 = freq * ratio;
+                    }
+                }
+
+                for ((bin, sum_phase), &(magnitude, freq)) in spectrum
+                    .iter_mut()
+                    .zip(ch.sum_phase.iter_mut())
+                    .zip(self.synthesis.iter())
+                {
+                    *sum_phase = wrap_phase(*sum_phase + freq * phase_per_bin);
+                    *bin = Complex::from_polar(magnitude, *sum_phase);
+                }
+            }
+        });
+    }
+}
+
+/// Wrap a phase in radians into the range `[-PI, PI]`.
+fn wrap_phase(phase: f32) -> f32 {
+    phase - core::f32::consts::TAU * (phase / core::f32::consts::TAU).round()
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<PitchShiftNode>() {
+            match patch {
+                PitchShiftNodePatch::Semitones(semitones) => {
+                    self.semitones
+                        .set_value(semitones.clamp(MIN_SEMITONES, MAX_SEMITONES));
+                }
+                PitchShiftNodePatch::SmoothSeconds(seconds) => {
+                    self.semitones.set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+
+            if info.prev_output_was_silent {
+                // Previous block was silent, so no need to smooth.
+                self.semitones.reset_to_target();
+            }
+        }
+
+        // Once the input has been silent for long enough, every buffer has
+        // been flushed and the output stays silent.
+        let flush_frames = self.stft.fft_size() * 2;
+        if info
+            .in_silence_mask
+            .all_channels_silent(buffers.inputs.len())
+        {
+            self.silent_frames = (self.silent_frames + info.frames).min(flush_frames);
+        } else {
+            self.silent_frames = 0;
+        }
+        if self.silent_frames == flush_frames && info.prev_output_was_silent {
+            self.semitones.reset_to_target();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.render(buffers.inputs, buffers.outputs, info.frames);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.semitones.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use realfft::RealFftPlanner;
+
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 48_000;
+
+    fn rms(s: &[f32]) -> f32 {
+        (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt()
+    }
+
+    /// Run a mono signal through the processor in blocks of 256 frames.
+    fn render(processor: &mut Processor, input: &[f32]) -> Vec<f32> {
+        let mut output = vec![0.0; input.len()];
+        for (in_block, out_block) in input.chunks(256).zip(output.chunks_mut(256)) {
+            processor.render(&[in_block], &mut [out_block], in_block.len());
+        }
+        output
+    }
+
+    /// The index of the loudest bin of the Hann-windowed signal.
+    fn peak_bin(signal: &[f32]) -> usize {
+        let n = signal.len();
+        let fft = RealFftPlanner::<f32>::new().plan_fft_forward(n);
+
+        let mut windowed: Vec<f32> = signal
+            .iter()
+            .enumerate()
+            .map(|(i, s)| s * (0.5 - 0.5 * (core::f32::consts::TAU * i as f32 / n as f32).cos()))
+            .collect();
+        let mut spectrum = fft.make_output_vec();
+        fft.process(&mut windowed, &mut spectrum).unwrap();
+
+        spectrum
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn octave_up_doubles_frequency_and_keeps_duration() {
+        let config = PitchShiftNodeConfig {
+            channels: NonZeroChannelCount::MONO,
+            fft_size: 2048,
+        };
+        let sample_rate = NonZeroU32::new(SAMPLE_RATE).unwrap();
+        let mut processor =
+            Processor::new(PitchShiftNode::from_semitones(12.0), &config, sample_rate);
+        let latency = config.rounded_fft_size();
+
+        // Half a second of a tone which lands exactly on bin 64 of a 4096
+        // point FFT, followed by half a second of silence.
+        let tone_frames = SAMPLE_RATE as usize / 2;
+        let freq_hz = 750.0;
+        let input: Vec<f32> = (0..SAMPLE_RATE as usize)
+            .map(|i| {
+                if i < tone_frames {
+                    0.5 * (core::f32::consts::TAU * freq_hz * i as f32 / SAMPLE_RATE as f32).sin()
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let output = render(&mut processor, &input);
+
+        // The steady part of the output is an octave higher, at roughly the
+        // same level.
+        let steady = &output[latency * 2..latency * 2 + 4096];
+        assert_eq!(peak_bin(&input[..4096]), 64);
+        assert_eq!(peak_bin(steady), 128);
+        let level_db = 20.0 * (rms(steady) / rms(&input[..4096])).log10();
+        assert!(level_db.abs() < 3.0, "level {level_db}");
+
+        // The tone still lasts for as long as the input, lined up for latency.
+        let tone_end = tone_frames + latency;
+        assert!(rms(&output[tone_end - latency..tone_end - latency / 2]) > 0.25);
+        assert!(rms(&output[tone_end + latency..]) < 1e-4);
+    }
+}
@@ -0,0 +1,182 @@
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Patch},
+    dsp::filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+    event::ProcEvents,
+    mask::MaskType,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+};
+
+use crate::volume::polarity_sign;
+
+/// The configuration of a [`PolarityNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolarityNodeConfig {
+    /// The number of input and output channels.
+    pub channels: NonZeroChannelCount,
+}
+
+impl Default for PolarityNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+        }
+    }
+}
+
+/// A node that inverts the polarity of a signal
+///
+/// This is useful for lining up the phase of signals that are mixed
+/// together, such as two microphones on the same source.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PolarityNode {
+    /// If `true`, then every channel is multiplied by `-1.0`.
+    ///
+    /// By default this is set to `true`.
+    pub invert: bool,
+    /// The time in seconds of the internal smoothing filter, which avoids a
+    /// click when toggling [`PolarityNode::invert`].
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for PolarityNode {
+    fn default() -> Self {
+        Self {
+            invert: true,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for PolarityNode {
+    type Configuration = PolarityNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("polarity")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = PolarityProcessor::new(self, cx.stream_info.sample_rate);
+        processor
+            .polarity
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct PolarityProcessor {
+    polarity: SmoothedParam,
+}
+
+impl PolarityProcessor {
+    fn new(params: &PolarityNode, sample_rate: NonZeroU32) -> Self {
+        Self {
+            polarity: SmoothedParam::new(
+                polarity_sign(params.invert),
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+        }
+    }
+}
+
+impl AudioNodeProcessor for PolarityProcessor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<PolarityNode>() {
+            match patch {
+                PolarityNodePatch::Invert(invert) => {
+                    self.polarity.set_value(polarity_sign(invert));
+
+                    if info.prev_output_was_silent {
+                        // Previous block was silent, so no need to smooth.
+                        self.polarity.reset_to_target();
+                    }
+                }
+                PolarityNodePatch::SmoothSeconds(seconds) => {
+                    self.polarity.set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+        }
+
+        if info
+            .in_silence_mask
+            .all_channels_silent(buffers.inputs.len())
+        {
+            self.polarity.reset_to_target();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        if self.polarity.has_settled() && self.polarity.target_value() == 1.0 {
+            return ProcessStatus::Bypass;
+        }
+
+        let scratch_buffer = extra.scratch_buffers.first_mut();
+        self.polarity
+            .process_into_buffer(&mut scratch_buffer[..info.frames]);
+
+        for (ch_i, (out_ch, in_ch)) in buffers
+            .outputs
+            .iter_mut()
+            .zip(buffers.inputs.iter())
+            .enumerate()
+        {
+            if info.in_silence_mask.is_channel_silent(ch_i) {
+                if !info.out_silence_mask.is_channel_silent(ch_i) {
+                    out_ch.fill(0.0);
+                }
+                continue;
+            }
+
+            for ((os, &is), &g) in out_ch[..info.frames]
+                .iter_mut()
+                .zip(in_ch.iter())
+                .zip(scratch_buffer[..info.frames].iter())
+            {
+                *os = is * g;
+            }
+        }
+
+        ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(info.in_silence_mask))
+    }
+
+    fn new_stream(
+        &mut self,
+        stream_info: &firewheel_core::StreamInfo,
+        _context: &mut ProcStreamCtx,
+    ) {
+        self.polarity.update_sample_rate(stream_info.sample_rate);
+    }
+}
@@ -0,0 +1,267 @@
+//! A node that delays triggers to the next point on the musical transport
+//! grid.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Notify, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The smallest allowed value for [`QuantizedTriggerNode::division_beats`].
+pub const MIN_DIVISION_BEATS: f64 = 1.0 / 64.0;
+
+/// A node that outputs a trigger pulse on the next division of the musical
+/// transport after [`QuantizedTriggerNode::trigger`] is notified (Mono output
+/// only).
+///
+/// The pulse is `1.0` for [`QuantizedTriggerNode::pulse_seconds`] starting on
+/// the first frame at or after the grid point, and `0.0` otherwise. Route the
+/// output to the trigger input of another node for beat-locked sample
+/// launching.
+///
+/// Triggers take effect at the start of the processing block they arrive in.
+/// If the transport is not playing, then a trigger waits until the transport
+/// starts and reaches a grid point.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QuantizedTriggerNode {
+    /// The spacing of the grid in beats. For example, `1.0` fires on the next
+    /// beat, `0.25` on the next sixteenth note, and `4.0` on the next bar of
+    /// `4/4`.
+    ///
+    /// By default this is set to `1.0`.
+    pub division_beats: f64,
+    /// The length of the output pulse in seconds. The pulse is always at
+    /// least one frame long.
+    ///
+    /// By default this is set to `0.001` (1 millisecond).
+    pub pulse_seconds: f32,
+    /// Fire a pulse at the next grid point.
+    ///
+    /// Notifying this again before the grid point is reached still fires
+    /// only a single pulse.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub trigger: Notify<()>,
+    /// Whether or not this node is enabled.
+    ///
+    /// Disabling the node cancels a trigger which is waiting for the next
+    /// grid point.
+    pub enabled: bool,
+}
+
+impl Default for QuantizedTriggerNode {
+    fn default() -> Self {
+        Self {
+            division_beats: 1.0,
+            pulse_seconds: 0.001,
+            trigger: Notify::default(),
+            enabled: true,
+        }
+    }
+}
+
+impl AudioNode for QuantizedTriggerNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("quantized_trigger")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: ChannelCount::MONO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor {
+    params: QuantizedTriggerNode,
+    /// Set by [`QuantizedTriggerNode::trigger`] until the next grid point.
+    armed: bool,
+    /// The index of the grid division of the last processed frame, or `None`
+    /// if the transport was not playing.
+    prev_division: Option<i64>,
+    pulse_frames: usize,
+    pulse_frames_left: usize,
+    sample_rate: NonZeroU32,
+}
+
+impl Processor {
+    fn new(params: QuantizedTriggerNode, sample_rate: NonZeroU32) -> Self {
+        let mut processor = Self {
+            params,
+            armed: false,
+            prev_division: None,
+            pulse_frames: 1,
+            pulse_frames_left: 0,
+            sample_rate,
+        };
+        processor.update_pulse_frames();
+        processor
+    }
+
+    fn update_pulse_frames(&mut self) {
+        self.pulse_frames =
+            ((self.params.pulse_seconds * self.sample_rate.get() as f32).round() as usize).max(1);
+    }
+
+    /// Fill `out` with the trigger signal. The transport starts at
+    /// `start_beats` and advances by `beats_per_frame` each frame, or is not
+    /// playing if `start_beats` is `None`.
+    fn render(&mut self, start_beats: Option<f64>, beats_per_frame: f64, out: &mut [f32]) {
+        let divisions_per_beat = self.params.division_beats.max(MIN_DIVISION_BEATS).recip();
+
+        for (i, s) in out.iter_mut().enumerate() {
+            if let Some(start_beats) = start_beats {
+                let position = (start_beats + beats_per_frame * i as f64) * divisions_per_beat;
+                let division = position.floor() as i64;
+
+                let on_grid = match self.prev_division {
+                    // This also fires if the transport jumped to another
+                    // division (i.e. when looping).
+                    Some(prev_division) => division != prev_division,
+                    // The transport just started.
+                    None => position.fract() == 0.0,
+                };
+
+                if self.armed && on_grid {
+                    self.armed = false;
+                    self.pulse_frames_left = self.pulse_frames;
+                }
+
+                self.prev_division = Some(division);
+            } else {
+                self.prev_division = None;
+            }
+
+            *s = if self.pulse_frames_left > 0 {
+                self.pulse_frames_left -= 1;
+                1.0
+            } else {
+                0.0
+            };
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<QuantizedTriggerNode>() {
+            match patch {
+                QuantizedTriggerNodePatch::Trigger(_) => {
+                    self.armed = true;
+                }
+                QuantizedTriggerNodePatch::PulseSeconds(pulse_seconds) => {
+                    self.params.pulse_seconds = pulse_seconds;
+                    self.update_pulse_frames();
+                }
+                patch => self.params.apply(patch),
+            }
+        }
+
+        if !self.params.enabled {
+            self.armed = false;
+            self.prev_division = None;
+            self.pulse_frames_left = 0;
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let playhead = info.playhead_range();
+        if playhead.is_none() && self.pulse_frames_left == 0 {
+            self.prev_division = None;
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let (start_beats, beats_per_frame) = match playhead {
+            Some(playhead) => (
+                Some(playhead.start.0),
+                (playhead.end.0 - playhead.start.0) / info.frames as f64,
+            ),
+            None => (None, 0.0),
+        };
+
+        self.render(
+            start_beats,
+            beats_per_frame,
+            &mut buffers.outputs[0][..info.frames],
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.update_pulse_frames();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn off_grid_trigger_fires_at_next_grid_frame() {
+        let mut processor = Processor::new(
+            QuantizedTriggerNode {
+                division_beats: 1.0,
+                pulse_seconds: 0.001,
+                ..Default::default()
+            },
+            NonZeroU32::new(48_000).unwrap(),
+        );
+        assert_eq!(processor.pulse_frames, 48);
+
+        // 1024 frames per beat, in blocks that don't line up with the grid.
+        let beats_per_frame = 1.0 / 1024.0;
+        let mut out = vec![0.0; 4096];
+        let mut frame = 0;
+        for block in out.chunks_mut(100) {
+            // The trigger arrives in the block starting at frame 300.
+            if frame == 300 {
+                processor.armed = true;
+            }
+            processor.render(Some(frame as f64 * beats_per_frame), beats_per_frame, block);
+            frame += block.len();
+        }
+
+        // The pulse starts on the first frame of the next beat, and only
+        // fires once.
+        assert!(out[..1024].iter().all(|&s| s == 0.0));
+        assert!(out[1024..1024 + 48].iter().all(|&s| s == 1.0));
+        assert!(out[1024 + 48..].iter().all(|&s| s == 0.0));
+
+        // A trigger while the transport is stopped waits for it to start on
+        // a grid point.
+        let mut out = vec![0.0; 64];
+        processor.armed = true;
+        processor.render(None, 0.0, &mut out);
+        assert!(out.iter().all(|&s| s == 0.0));
+        processor.render(Some(2.0), beats_per_frame, &mut out);
+        assert_eq!(out[0], 1.0);
+    }
+}
@@ -0,0 +1,353 @@
+//! A node that converts its input from one sample rate to another.
+
+use core::num::{NonZeroU32, NonZeroUsize};
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+use fixed_resample::FixedResampler;
+
+pub use fixed_resample::ResampleQuality;
+
+/// The maximum number of channels supported by a [`ResampleNode`].
+pub const MAX_CHANNELS: usize = 8;
+
+/// The configuration of a [`ResampleNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResampleNodeConfig {
+    /// The number of channels, up to [`MAX_CHANNELS`].
+    pub channels: NonZeroChannelCount,
+    /// The sample rate of the input signal.
+    ///
+    /// By default this is set to `48000`.
+    pub input_sample_rate: NonZeroU32,
+    /// The sample rate of the output signal.
+    ///
+    /// By default this is set to `44100`.
+    pub output_sample_rate: NonZeroU32,
+    /// The quality of the resampling algorithm.
+    ///
+    /// By default this is set to [`ResampleQuality::Low`].
+    #[cfg_attr(feature = "bevy_reflect", reflect(ignore))]
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub quality: ResampleQuality,
+}
+
+impl Default for ResampleNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            input_sample_rate: NonZeroU32::new(48_000).unwrap(),
+            output_sample_rate: NonZeroU32::new(44_100).unwrap(),
+            quality: ResampleQuality::Low,
+        }
+    }
+}
+
+/// A node that converts its input from one sample rate to another, i.e. to
+/// embed a section of the graph that must run at a fixed rate.
+///
+/// The node reports the latency of the conversion in frames of the output
+/// sample rate.
+///
+/// Like every node, this node consumes and produces one block of frames per
+/// process cycle. When the conversion changes the amount of audio (i.e.
+/// `48000` to `44100`), the resampled frames are buffered internally, excess
+/// frames are dropped once that buffer is full, and missing frames are
+/// filled with silence. For offline conversion, use [`Resampler`] directly.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ResampleNode;
+
+impl AudioNode for ResampleNode {
+    type Configuration = ResampleNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        // The latency depends on the internal resampler, so construct one
+        // to query it. This only happens on the main thread.
+        let latency_frames = Resampler::new(
+            num_channels(config),
+            config.input_sample_rate,
+            config.output_sample_rate,
+            config.quality,
+            0,
+        )
+        .latency_frames();
+
+        AudioNodeInfo::new()
+            .debug_name("resample")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+            .latency_frames(latency_frames as u32)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor {
+            resampler: Resampler::new(
+                num_channels(config),
+                config.input_sample_rate,
+                config.output_sample_rate,
+                config.quality,
+                cx.stream_info.max_block_frames.get() as usize,
+            ),
+        }
+    }
+}
+
+fn num_channels(config: &ResampleNodeConfig) -> NonZeroUsize {
+    NonZeroUsize::new((config.channels.get().get() as usize).min(MAX_CHANNELS)).unwrap()
+}
+
+/// A realtime-safe streaming sample rate converter.
+///
+/// Unlike the resampler it wraps, this accepts and produces any number of
+/// frames per call, buffering the resampled output in between. This is what
+/// [`ResampleNode`] uses internally.
+pub struct Resampler {
+    resampler: FixedResampler<f32, MAX_CHANNELS>,
+    buffer: [Vec<f32>; MAX_CHANNELS],
+    buffer_len: usize,
+    latency_frames: usize,
+    num_dropped_frames: u64,
+}
+
+impl Resampler {
+    /// Construct a new resampler.
+    ///
+    /// * `num_channels` - The number of channels, up to [`MAX_CHANNELS`].
+    /// * `in_sample_rate` - The sample rate of the input data.
+    /// * `out_sample_rate` - The sample rate of the output data.
+    /// * `quality` - The quality of the resampling algorithm.
+    /// * `max_input_frames` - The maximum number of input frames that will
+    /// be passed to a single call to [`Resampler::process`].
+    ///
+    /// # Panics
+    /// Panics if `num_channels > MAX_CHANNELS`.
+    pub fn new(
+        num_channels: NonZeroUsize,
+        in_sample_rate: NonZeroU32,
+        out_sample_rate: NonZeroU32,
+        quality: ResampleQuality,
+        max_input_frames: usize,
+    ) -> Self {
+        let resampler = FixedResampler::new(
+            num_channels,
+            in_sample_rate.get(),
+            out_sample_rate.get(),
+            quality,
+            false,
+        );
+
+        // The internal resampler only outputs whole packets, so keep one
+        // packet of silence buffered ahead of the output to never run dry.
+        let prefill_frames = resampler.max_output_block_frames();
+        let latency_frames = prefill_frames + resampler.output_delay();
+
+        let capacity = prefill_frames
+            + resampler.out_alloc_frames(max_input_frames as u64) as usize
+            + resampler.max_output_block_frames();
+
+        let buffer = core::array::from_fn(|ch| {
+            if ch < num_channels.get() {
+                let mut buffer = Vec::with_capacity(capacity);
+                buffer.resize(prefill_frames, 0.0);
+                buffer
+            } else {
+                Vec::new()
+            }
+        });
+
+        Self {
+            resampler,
+            buffer,
+            buffer_len: prefill_frames,
+            latency_frames,
+            num_dropped_frames: 0,
+        }
+    }
+
+    /// The latency of the conversion in frames of the output sample rate.
+    pub fn latency_frames(&self) -> usize {
+        self.latency_frames
+    }
+
+    /// The number of output frames that were dropped because the internal
+    /// buffer was full.
+    pub fn num_dropped_frames(&self) -> u64 {
+        self.num_dropped_frames
+    }
+
+    /// Resample `input_frames` frames from `inputs`, and fill the first
+    /// `output_frames` frames of `outputs` with resampled audio. If not enough
+    /// resampled audio is available, then the rest is filled with silence.
+    ///
+    /// This method is realtime-safe.
+    pub fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        input_frames: usize,
+        outputs: &mut [&mut [f32]],
+        output_frames: usize,
+    ) {
+        let num_channels = self.resampler.num_channels().get();
+
+        let Self {
+            resampler,
+            buffer,
+            buffer_len,
+            num_dropped_frames,
+            ..
+        } = self;
+
+        resampler.process(
+            &inputs[..num_channels],
+            0..input_frames,
+            |packet| {
+                let frames = packet[0].len();
+                let copy_frames = frames.min(buffer[0].capacity() - *buffer_len);
+
+                for (buf, packet_ch) in buffer.iter_mut().zip(packet.iter()) {
+                    buf.extend_from_slice(&packet_ch[..copy_frames]);
+                }
+
+                *buffer_len += copy_frames;
+                *num_dropped_frames += (frames - copy_frames) as u64;
+            },
+            None,
+            false,
+        );
+
+        let read_frames = output_frames.min(self.buffer_len);
+
+        for (out_ch, buf) in outputs.iter_mut().zip(self.buffer.iter_mut()) {
+            out_ch[..read_frames].copy_from_slice(&buf[..read_frames]);
+            out_ch[read_frames..output_frames].fill(0.0);
+
+            buf.copy_within(read_frames.., 0);
+            buf.truncate(self.buffer_len - read_frames);
+        }
+
+        self.buffer_len -= read_frames;
+    }
+}
+
+struct Processor {
+    resampler: Resampler,
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        _events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        self.resampler
+            .process(buffers.inputs, info.frames, buffers.outputs, info.frames);
+
+        for out_ch in buffers.outputs.iter_mut().skip(MAX_CHANNELS) {
+            out_ch[..info.frames].fill(0.0);
+        }
+
+        ProcessStatus::OutputsModified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f64::consts::TAU;
+    use firewheel_core::node::AudioNodeInfoInner;
+
+    const TONE_HZ: f64 = 440.0;
+
+    fn resample(
+        resampler: &mut Resampler,
+        input: &[f32],
+        in_block: usize,
+        out_block: usize,
+    ) -> Vec<f32> {
+        let mut output = vec![0.0; input.len() / in_block * out_block];
+
+        for (in_chunk, out_chunk) in input
+            .chunks_exact(in_block)
+            .zip(output.chunks_exact_mut(out_block))
+        {
+            resampler.process(&[in_chunk], in_block, &mut [out_chunk], out_block);
+        }
+
+        output
+    }
+
+    #[test]
+    fn round_trip_reproduces_tone_after_reported_latency() {
+        let low_rate = NonZeroU32::new(44_100).unwrap();
+        let high_rate = NonZeroU32::new(88_200).unwrap();
+        let config = ResampleNodeConfig {
+            channels: NonZeroChannelCount::MONO,
+            input_sample_rate: low_rate,
+            output_sample_rate: high_rate,
+            quality: ResampleQuality::High,
+        };
+
+        let mut up = Resampler::new(
+            NonZeroUsize::MIN,
+            low_rate,
+            high_rate,
+            ResampleQuality::High,
+            256,
+        );
+        let mut down = Resampler::new(
+            NonZeroUsize::MIN,
+            high_rate,
+            low_rate,
+            ResampleQuality::High,
+            512,
+        );
+
+        let info: AudioNodeInfoInner = ResampleNode.info(&config).into();
+        assert_eq!(info.latency_frames as usize, up.latency_frames());
+
+        let input: Vec<f32> = (0..low_rate.get() as usize)
+            .map(|i| (TAU * TONE_HZ * i as f64 / low_rate.get() as f64).sin() as f32)
+            .collect();
+
+        let upsampled = resample(&mut up, &input, 256, 512);
+        let output = resample(&mut down, &upsampled, 512, 256);
+        assert_eq!(up.num_dropped_frames(), 0);
+        assert_eq!(down.num_dropped_frames(), 0);
+
+        // The latency of the first stage is in frames of the higher rate.
+        let latency = up.latency_frames() as f64 / 2.0 + down.latency_frames() as f64;
+        assert!(output[..latency as usize - 1]
+            .iter()
+            .all(|s| s.abs() < 0.001));
+
+        // Skip the filters' settling time after the tone starts.
+        let start = latency as usize + 512;
+        for (i, &s) in output.iter().enumerate().skip(start) {
+            let expected = (TAU * TONE_HZ * (i as f64 - latency) / low_rate.get() as f64).sin();
+            assert!(
+                (s as f64 - expected).abs() < 0.01,
+                "frame {i}: {s} != {expected}"
+            );
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! A reverse effect node synced to the musical transport.
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Notify, Patch},
+    dsp::declick::{DeclickFadeCurve, Declicker},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+use crate::delay_buffer::DelayBuffer;
+
+/// The smallest allowed value for [`ReverseNode::window_beats`].
+pub const MIN_WINDOW_BEATS: f64 = 1.0 / 64.0;
+
+/// The length of the fade at the edges of the reversed window, and of the
+/// crossfade between the live input and the reversed audio.
+const FADE_SECONDS: f32 = 0.002;
+
+/// The configuration of a [`ReverseNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReverseNodeConfig {
+    /// The number of input and output channels.
+    pub channels: NonZeroChannelCount,
+    /// The longest window that can be reversed in seconds. This determines
+    /// how much memory is allocated. If a window is longer than this, then
+    /// the rest of it is played as silence.
+    ///
+    /// By default this is set to `4.0`.
+    pub max_window_seconds: f32,
+}
+
+impl Default for ReverseNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            max_window_seconds: 4.0,
+        }
+    }
+}
+
+/// A node that plays the last window of its input backwards, synced to the
+/// musical transport.
+///
+/// The input is recorded continuously. When [`ReverseNode::trigger`] is
+/// notified, then at the next boundary of [`ReverseNode::window_beats`] the
+/// window of input leading up to that boundary is played backwards in place
+/// of the input for the length of one window. This is useful for risers
+/// and transitions, such as reverse cymbals and reverse reverb swells.
+///
+/// If the transport is not playing, then the input is passed through.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReverseNode {
+    /// The length of the reversed window in beats. For example, `1.0` is a
+    /// quarter note and `4.0` is a bar of `4/4`.
+    ///
+    /// By default this is set to `1.0`.
+    pub window_beats: f64,
+    /// Play the last window backwards, starting at the next boundary of
+    /// [`ReverseNode::window_beats`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub trigger: Notify<()>,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for ReverseNode {
+    fn default() -> Self {
+        Self {
+            window_beats: 1.0,
+            trigger: Notify::default(),
+            enabled: true,
+        }
+    }
+}
+
+impl AudioNode for ReverseNode {
+    type Configuration = ReverseNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("reverse")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, config, cx.stream_info)
+    }
+}
+
+/// A window being played backwards.
+struct Reverse {
+    /// The position of the transport in beats where playback started.
+    start_beats: f64,
+    /// The length of the window in beats.
+    window_beats: f64,
+    /// The write position of the buffers when playback started.
+    start_write_pos: usize,
+}
+
+struct Processor {
+    params: ReverseNode,
+    buffers: Vec<DelayBuffer>,
+    write_pos: usize,
+    max_window_seconds: f32,
+
+    reverse: Option<Reverse>,
+    /// Set by [`ReverseNode::trigger`] until the next window boundary.
+    armed: bool,
+    /// The position of the transport on the last processed frame, or `None`
+    /// if the transport was not playing.
+    prev_beats: Option<f64>,
+
+    /// The amount of the reversed signal in the output, where `0.0` is only
+    /// the live input.
+    wet: f32,
+    fade_frames: f32,
+
+    enable_declicker: Declicker,
+}
+
+impl Processor {
+    fn new(params: ReverseNode, config: &ReverseNodeConfig, info: &StreamInfo) -> Self {
+        let max_window_seconds = config.max_window_seconds.max(0.0);
+
+        Self {
+            params,
+            buffers: (0..config.channels.get().get())
+                .map(|_| DelayBuffer::new(buffer_frames(max_window_seconds, info)))
+                .collect(),
+            write_pos: 0,
+            max_window_seconds,
+            reverse: None,
+            armed: false,
+            prev_beats: None,
+            wet: 0.0,
+            fade_frames: fade_frames(info),
+            enable_declicker: Declicker::from_enabled(params.enabled),
+        }
+    }
+
+    /// The longest window in frames that can be read back before it is
+    /// overwritten by the input recorded during playback.
+    fn max_window_frames(&self) -> f32 {
+        ((self.buffers[0].len() - 2) / 2) as f32
+    }
+
+    /// End a finished reverse and start a new one when the transport moves
+    /// from `prev_beats` to `beats`.
+    fn update_reverse(&mut self, prev_beats: f64, beats: f64) {
+        if let Some(reverse) = &self.reverse {
+            let elapsed = beats - reverse.start_beats;
+
+            // Also end the reverse if the transport jumped backwards past it.
+            if elapsed >= 0.0 && elapsed < reverse.window_beats {
+                return;
+            }
+
+            self.reverse = None;
+        }
+
+        if !self.armed {
+            return;
+        }
+
+        let window_beats = self.params.window_beats.max(MIN_WINDOW_BEATS);
+        if (beats / window_beats).floor() == (prev_beats / window_beats).floor() {
+            return;
+        }
+
+        self.armed = false;
+        self.reverse = Some(Reverse {
+            start_beats: (beats / window_beats).floor() * window_beats,
+            window_beats,
+            start_write_pos: self.write_pos,
+        });
+    }
+
+    /// Render a block starting at `start_beats` on the transport, advancing by
+    /// `beats_per_frame` each frame.
+    ///
+    /// Returns `false` if the output is identical to the input.
+    fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        start_beats: f64,
+        beats_per_frame: f64,
+    ) -> bool {
+        let fade_step = self.fade_frames.recip();
+        let max_window_frames = self.max_window_frames();
+        let buffer_len = self.buffers[0].len();
+        let mut modified = false;
+
+        for i in 0..frames {
+            let beats = start_beats + beats_per_frame * i as f64;
+            if let Some(prev_beats) = self.prev_beats {
+                self.update_reverse(prev_beats, beats);
+            }
+            self.prev_beats = Some(beats);
+
+            let mut wet_target = 0.0;
+            let mut window = 0.0;
+            let mut read_frames = 0.0;
+            let mut start_write_pos = 0;
+
+            if let Some(reverse) = &self.reverse {
+                let window_frames = (reverse.window_beats / beats_per_frame) as f32;
+                read_frames = ((beats - reverse.start_beats) / beats_per_frame) as f32;
+                window = ((read_frames * fade_step).min((window_frames - read_frames) * fade_step))
+                    .clamp(0.0, 1.0);
+                start_write_pos = reverse.start_write_pos;
+                wet_target = 1.0;
+            }
+
+            if self.wet < wet_target {
+                self.wet = (self.wet + fade_step).min(wet_target);
+            } else if self.wet > wet_target {
+                self.wet = (self.wet - fade_step).max(wet_target);
+            }
+
+            if self.wet > 0.0 {
+                modified = true;
+            }
+
+            for ((buffer, in_ch), out_ch) in self
+                .buffers
+                .iter_mut()
+                .zip(inputs.iter())
+                .zip(outputs.iter_mut())
+            {
+                let live = in_ch[i];
+                let reversed = if window > 0.0 && read_frames < max_window_frames {
+                    // Read backwards from the frame just before playback
+                    // started.
+                    buffer.read(start_write_pos, read_frames + 1.0) * window
+                } else {
+                    0.0
+                };
+
+                buffer.write(self.write_pos, live);
+
+                out_ch[i] = live + (reversed - live) * self.wet;
+            }
+
+            self.write_pos = (self.write_pos + 1) % buffer_len;
+        }
+
+        modified
+    }
+
+    fn reset(&mut self) {
+        self.reverse = None;
+        self.prev_beats = None;
+        self.wet = 0.0;
+    }
+}
+
+fn buffer_frames(max_window_seconds: f32, info: &StreamInfo) -> usize {
+    // The window being played back must not be overwritten by the input
+    // recorded at the same time, so keep room for two windows.
+    (max_window_seconds * info.sample_rate.get() as f32).ceil() as usize * 2
+}
+
+fn fade_frames(info: &StreamInfo) -> f32 {
+    (FADE_SECONDS * info.sample_rate.get() as f32).max(1.0)
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<ReverseNode>() {
+            match patch {
+                ReverseNodePatch::Trigger(_) => {
+                    self.armed = true;
+                }
+                ReverseNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                    self.params.enabled = enabled;
+                }
+                patch => self.params.apply(patch),
+            }
+        }
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+            self.armed = false;
+
+            return ProcessStatus::Bypass;
+        }
+
+        let Some(playhead) = info.playhead_range() else {
+            self.reset();
+
+            return ProcessStatus::Bypass;
+        };
+
+        let beats_per_frame = (playhead.end.0 - playhead.start.0) / info.frames as f64;
+        let modified = self.render(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            playhead.start.0,
+            beats_per_frame,
+        );
+
+        if !modified && self.enable_declicker.has_settled() {
+            return ProcessStatus::Bypass;
+        }
+
+        // Crossfade between the processed and input signals to declick
+        // enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        let buffer_frames = buffer_frames(self.max_window_seconds, stream_info);
+        for buffer in self.buffers.iter_mut() {
+            *buffer = DelayBuffer::new(buffer_frames);
+        }
+        self.write_pos = 0;
+        self.fade_frames = fade_frames(stream_info);
+        self.reset();
+    }
+
+    fn flush_idle_state(&mut self) {
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use super::*;
+
+    const WINDOW_FRAMES: usize = 8_192;
+
+    #[test]
+    fn triggered_reverse_of_ramp_is_decreasing_ramp() {
+        let info = StreamInfo {
+            sample_rate: NonZeroU32::new(48_000).unwrap(),
+            ..Default::default()
+        };
+        let mut processor = Processor::new(
+            ReverseNode {
+                window_beats: 0.25,
+                ..Default::default()
+            },
+            &ReverseNodeConfig {
+                channels: NonZeroChannelCount::MONO,
+                ..Default::default()
+            },
+            &info,
+        );
+        processor.armed = true;
+
+        // A power of two beats per frame keeps the beat positions exact, so
+        // the first window boundary is `WINDOW_FRAMES` in.
+        let beats_per_frame = 1.0 / 32_768.0;
+
+        let frames = WINDOW_FRAMES * 3;
+        let input: Vec<f32> = (0..frames).map(|i| i as f32 / frames as f32).collect();
+        let mut output = vec![0.0; frames];
+
+        for (block_i, (in_block, out_block)) in
+            input.chunks(256).zip(output.chunks_mut(256)).enumerate()
+        {
+            processor.render(
+                &[in_block],
+                &mut [out_block],
+                in_block.len(),
+                (block_i * 256) as f64 * beats_per_frame,
+                beats_per_frame,
+            );
+        }
+
+        let fade = processor.fade_frames as usize + 1;
+
+        // The input passes through until the window boundary.
+        assert_eq!(&output[..WINDOW_FRAMES], &input[..WINDOW_FRAMES]);
+
+        // The window leading up to the boundary is then played backwards.
+        let reversed = &output[WINDOW_FRAMES..WINDOW_FRAMES * 2];
+        for i in fade..WINDOW_FRAMES - fade {
+            assert!((reversed[i] - input[WINDOW_FRAMES - 1 - i]).abs() < 1e-6);
+        }
+        for w in reversed[fade..WINDOW_FRAMES - fade].windows(2) {
+            assert!(w[1] < w[0]);
+        }
+
+        // After that the input passes through again.
+        let end = WINDOW_FRAMES * 2;
+        assert_eq!(&output[end + fade..], &input[end + fade..]);
+        assert!(processor.reverse.is_none());
+    }
+}
@@ -0,0 +1,307 @@
+//! A node that outputs a stepped random signal for modulation.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The lowest and highest allowed values for [`SampleHoldNode::rate_hz`].
+pub const MIN_RATE_HZ: f32 = 0.01;
+pub const MAX_RATE_HZ: f32 = 1_000.0;
+
+/// The smallest allowed value for [`SampleHoldNode::sync_division_beats`].
+#[cfg(feature = "musical_transport")]
+pub const MIN_DIVISION_BEATS: f64 = 1.0 / 64.0;
+
+/// A sample and hold node that outputs a stepped random signal in the range
+/// `[-1.0, 1.0]` (Mono output only).
+///
+/// A new random value is picked at a steady rate and held until the next
+/// step, giving the classic "random" LFO shape. Route the output to the
+/// modulation input of another node for glitchy sound design.
+///
+/// The sequence of values only depends on the seed in
+/// [`SampleHoldConfig`], so the same seed always produces the same pattern.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleHoldNode {
+    /// The number of new values picked per second.
+    ///
+    /// This is ignored while the output is synced to the musical transport.
+    ///
+    /// By default this is set to `8.0`.
+    pub rate_hz: f32,
+    /// If set, then a new value is picked at the start of every division of
+    /// this many beats of the musical transport instead of at
+    /// [`SampleHoldNode::rate_hz`]. For example, `0.25` picks a new value
+    /// every sixteenth note.
+    ///
+    /// While synced, the last value is held when the transport is not
+    /// playing.
+    ///
+    /// By default this is set to `None`.
+    #[cfg(feature = "musical_transport")]
+    pub sync_division_beats: Option<f64>,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+}
+
+impl Default for SampleHoldNode {
+    fn default() -> Self {
+        Self {
+            rate_hz: 8.0,
+            #[cfg(feature = "musical_transport")]
+            sync_division_beats: None,
+            enabled: true,
+        }
+    }
+}
+
+/// The configuration for a [`SampleHoldNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SampleHoldConfig {
+    /// The starting seed. This cannot be zero.
+    ///
+    /// If the context is running in deterministic mode, this is combined
+    /// with the seed the context assigns to this node.
+    pub seed: i32,
+}
+
+impl Default for SampleHoldConfig {
+    fn default() -> Self {
+        Self { seed: 17 }
+    }
+}
+
+impl AudioNode for SampleHoldNode {
+    type Configuration = SampleHoldConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("sample_hold")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: ChannelCount::MONO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let seed = match cx.deterministic_seed {
+            Some(node_seed) => config.seed ^ (node_seed ^ (node_seed >> 32)) as i32,
+            None => config.seed,
+        };
+
+        Processor::new(*self, seed, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor {
+    params: SampleHoldNode,
+    fpd: i32,
+    value: f32,
+    frames_per_step: f64,
+    /// The number of frames left until the next free-running step.
+    frames_left: f64,
+    /// The index of the last transport division a value was picked in.
+    #[cfg(feature = "musical_transport")]
+    last_division: Option<i64>,
+    sample_rate: NonZeroU32,
+}
+
+impl Processor {
+    fn new(params: SampleHoldNode, seed: i32, sample_rate: NonZeroU32) -> Self {
+        let mut processor = Self {
+            params,
+            // Seed cannot be zero.
+            fpd: if seed == 0 { 17 } else { seed },
+            value: 0.0,
+            frames_per_step: 1.0,
+            frames_left: 0.0,
+            #[cfg(feature = "musical_transport")]
+            last_division: None,
+            sample_rate,
+        };
+        processor.update_rate();
+        processor
+    }
+
+    fn update_rate(&mut self) {
+        self.frames_per_step = self.sample_rate.get() as f64
+            / self.params.rate_hz.clamp(MIN_RATE_HZ, MAX_RATE_HZ) as f64;
+
+        // Don't wait out a long step after switching to a faster rate.
+        self.frames_left = self.frames_left.min(self.frames_per_step);
+    }
+
+    /// Pick a new random value in the range `[-1.0, 1.0]`.
+    fn next_value(&mut self) -> f32 {
+        self.fpd ^= self.fpd << 13;
+        self.fpd ^= self.fpd >> 17;
+        self.fpd ^= self.fpd << 5;
+
+        self.fpd as f32 * (1.0 / 2_147_483_648.0)
+    }
+
+    /// Fill `out` with the held value, picking a new one every
+    /// `frames_per_step` frames.
+    fn render_free(&mut self, out: &mut [f32]) {
+        for s in out.iter_mut() {
+            if self.frames_left <= 0.0 {
+                self.value = self.next_value();
+                self.frames_left += self.frames_per_step;
+            }
+            self.frames_left -= 1.0;
+
+            *s = self.value;
+        }
+    }
+
+    /// Fill `out` with the held value, picking a new one at the start of each
+    /// transport division. The transport starts at `start_beats` and advances
+    /// by `beats_per_frame` each frame.
+    #[cfg(feature = "musical_transport")]
+    fn render_synced(
+        &mut self,
+        division_beats: f64,
+        start_beats: f64,
+        beats_per_frame: f64,
+        out: &mut [f32],
+    ) {
+        let divisions_per_beat = division_beats.max(MIN_DIVISION_BEATS).recip();
+
+        for (i, s) in out.iter_mut().enumerate() {
+            let division =
+                ((start_beats + beats_per_frame * i as f64) * divisions_per_beat).floor() as i64;
+
+            if self.last_division != Some(division) {
+                self.last_division = Some(division);
+                self.value = self.next_value();
+            }
+
+            *s = self.value;
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<SampleHoldNode>() {
+            self.params.apply(patch);
+            self.update_rate();
+        }
+
+        if !self.params.enabled {
+            self.value = 0.0;
+            self.frames_left = 0.0;
+            #[cfg(feature = "musical_transport")]
+            {
+                self.last_division = None;
+            }
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let out = &mut buffers.outputs[0][..info.frames];
+
+        #[cfg(feature = "musical_transport")]
+        if let Some(division_beats) = self.params.sync_division_beats {
+            self.frames_left = 0.0;
+
+            let Some(playhead) = info.playhead_range() else {
+                if self.value == 0.0 {
+                    return ProcessStatus::ClearAllOutputs;
+                }
+
+                out.fill(self.value);
+                return ProcessStatus::OutputsModified;
+            };
+
+            let beats_per_frame = (playhead.end.0 - playhead.start.0) / info.frames as f64;
+            self.render_synced(division_beats, playhead.start.0, beats_per_frame, out);
+
+            return ProcessStatus::OutputsModified;
+        }
+
+        #[cfg(feature = "musical_transport")]
+        {
+            self.last_division = None;
+        }
+        self.render_free(out);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.update_rate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(seed: i32) -> Vec<f32> {
+        let mut processor = Processor::new(
+            SampleHoldNode {
+                rate_hz: 100.0,
+                ..Default::default()
+            },
+            seed,
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        // One second in blocks that don't line up with the step length.
+        let mut out = vec![0.0; 48_000];
+        for block in out.chunks_mut(333) {
+            processor.render_free(block);
+        }
+        out
+    }
+
+    #[test]
+    fn steps_at_rate_boundaries_and_is_deterministic() {
+        let out = render(1234);
+
+        // The value only ever changes on a step boundary (every 480 frames at
+        // 100Hz), and every step picks a new value.
+        for i in 1..out.len() {
+            if i % 480 == 0 {
+                assert_ne!(out[i], out[i - 1], "no step at frame {i}");
+            } else {
+                assert_eq!(out[i], out[i - 1], "unexpected step at frame {i}");
+            }
+        }
+        assert!(out.iter().all(|s| (-1.0..=1.0).contains(s)));
+
+        // The same seed always gives the same pattern, and a different seed
+        // gives a different one.
+        assert_eq!(out, render(1234));
+        assert_ne!(out, render(4321));
+    }
+}
@@ -6,12 +6,15 @@ use firewheel_core::node::{ProcBuffers, ProcExtra, ProcStreamCtx};
 #[cfg(not(feature = "std"))]
 use num_traits::Float;
 
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
+
 use bevy_platform::sync::atomic::{AtomicU64, Ordering};
 use bevy_platform::time::Instant;
 use core::sync::atomic::AtomicU32;
 use core::{
     num::{NonZeroU32, NonZeroUsize},
-    ops::Range,
+    ops::{Range, RangeInclusive},
 };
 use firewheel_core::diff::{EventQueue, PatchError, PathBuilder, RealtimeClone};
 use smallvec::SmallVec;
@@ -22,9 +25,14 @@ use firewheel_core::{
     collector::ArcGc,
     diff::{Diff, Notify, ParamPath, Patch},
     dsp::{
-        buffer::InstanceBuffer,
+        buffer::{InstanceBuffer, VarChannelBuffer},
         declick::{DeclickFadeCurve, Declicker},
-        volume::{Volume, DEFAULT_AMP_EPSILON},
+        fade::FadeCurve,
+        filter::{
+            butterworth::Q_BUTTERWORTH_ORD2,
+            svf::{SvfCoeff, SvfState},
+        },
+        volume::{db_to_amp, Volume, DEFAULT_AMP_EPSILON},
     },
     event::{NodeEventType, ParamData, ProcEvents},
     mask::{MaskType, SilenceMask},
@@ -44,7 +52,7 @@ pub const DEFAULT_NUM_DECLICKERS: usize = 2;
 pub const MIN_PLAYBACK_SPEED: f64 = 0.0000001;
 
 /// The configuration of a [`SamplerNode`]
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -61,6 +69,56 @@ pub struct SamplerConfig {
     /// The quality of the resampling algorithm used when changing the playback
     /// speed.
     pub speed_quality: PlaybackSpeedQuality,
+    /// If `true`, then a lowpass filter that tracks the playback speed is
+    /// applied to the sample before it is resampled when playing faster than
+    /// the original speed, so that the pitched-up sound doesn't alias.
+    ///
+    /// Set this to `false` to save CPU when the sampler is rarely pitched
+    /// up, or when the samples have little high frequency content.
+    ///
+    /// By default this is set to `true`.
+    pub anti_alias: bool,
+    /// An optional filter that each triggered voice is run through.
+    ///
+    /// By default this is set to `None`.
+    pub voice_filter: Option<SamplerVoiceFilter>,
+    /// An optional amplitude envelope that is applied to each triggered
+    /// voice.
+    ///
+    /// By default this is set to `None`.
+    pub amp_envelope: Option<SamplerEnvelope>,
+    /// Optional random variation of the start offset, pitch, and gain of
+    /// each triggered voice, to avoid the "machine gun" effect when the same
+    /// sample is played repeatedly.
+    ///
+    /// By default this is set to `None`.
+    pub humanize: Option<SamplerHumanize>,
+    /// An optional short fade applied when each voice starts and stops, to
+    /// avoid a click when the sample doesn't start or stop at a
+    /// zero-crossing.
+    ///
+    /// By default this is set to `None`.
+    pub voice_fade: Option<SamplerVoiceFade>,
+    /// An optional length in seconds of the fade applied to the old sound of
+    /// a voice that is restarted or given a new sample while it is still
+    /// playing (i.e. when the voice is stolen by an `AudioNodePool`). The old
+    /// sound fades out over this time while the new sound starts, so the
+    /// steal doesn't click.
+    ///
+    /// If this is `None`, then the old sound is faded out like any other
+    /// stopped voice.
+    ///
+    /// By default this is set to `None`.
+    pub steal_fade_secs: Option<f32>,
+    /// The number of processed blocks between each update of the playhead
+    /// reported in [`SamplerState`]. Higher values reduce the amount of
+    /// traffic to the main thread at the cost of a less precise playhead.
+    ///
+    /// Seeking and reaching the end of the sample are always reported
+    /// right away. A value of `0` is treated as `1`.
+    ///
+    /// By default this is set to `1` (every block).
+    pub playhead_report_interval_blocks: u32,
 }
 
 impl Default for SamplerConfig {
@@ -69,6 +127,163 @@ impl Default for SamplerConfig {
             channels: NonZeroChannelCount::STEREO,
             num_declickers: DEFAULT_NUM_DECLICKERS as u32,
             speed_quality: PlaybackSpeedQuality::default(),
+            anti_alias: true,
+            voice_filter: None,
+            amp_envelope: None,
+            humanize: None,
+            voice_fade: None,
+            steal_fade_secs: None,
+            playhead_report_interval_blocks: 1,
+        }
+    }
+}
+
+/// The type of a [`SamplerVoiceFilter`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SamplerFilterType {
+    /// Lowpass (-12 dB per octave)
+    #[default]
+    Lowpass,
+    /// Highpass (-12 dB per octave)
+    Highpass,
+}
+
+/// A filter applied to each triggered voice of a [`SamplerNode`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplerVoiceFilter {
+    /// The type of filter.
+    pub filter_type: SamplerFilterType,
+    /// The cutoff frequency in hertz.
+    pub cutoff_hz: f32,
+    /// The Q factor of the filter.
+    ///
+    /// By default this is set to `0.7071` (a Butterworth response).
+    pub q: f32,
+}
+
+impl Default for SamplerVoiceFilter {
+    fn default() -> Self {
+        Self {
+            filter_type: SamplerFilterType::Lowpass,
+            cutoff_hz: 20_000.0,
+            q: Q_BUTTERWORTH_ORD2,
+        }
+    }
+}
+
+/// An amplitude envelope applied to each triggered voice of a
+/// [`SamplerNode`].
+///
+/// The gain rises linearly from `0.0` to `1.0` over the attack time, then
+/// falls linearly to the sustain level over the decay time, where it stays
+/// until the voice is stopped. A sustain level of `0.0` creates a one-shot
+/// envelope that fades the voice out after the decay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplerEnvelope {
+    /// The attack time in seconds.
+    ///
+    /// By default this is set to `0.0`.
+    pub attack_secs: f32,
+    /// The decay time in seconds.
+    ///
+    /// By default this is set to `0.0`.
+    pub decay_secs: f32,
+    /// The sustain level in raw amplitude, in the range `[0.0, 1.0]`.
+    ///
+    /// By default this is set to `1.0`.
+    pub sustain_level: f32,
+}
+
+impl Default for SamplerEnvelope {
+    fn default() -> Self {
+        Self {
+            attack_secs: 0.0,
+            decay_secs: 0.0,
+            sustain_level: 1.0,
+        }
+    }
+}
+
+/// A short fade applied when each voice of a [`SamplerNode`] starts and
+/// stops.
+///
+/// Unlike [`SamplerEnvelope`], this is meant to be short enough (i.e. 1-5ms)
+/// to be inaudible as a fade.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplerVoiceFade {
+    /// The time in seconds it takes a voice to fade in when it starts.
+    ///
+    /// By default this is set to `0.002` (2ms).
+    pub attack_secs: f32,
+    /// The time in seconds it takes a voice to fade out when it is stopped.
+    ///
+    /// By default this is set to `0.002` (2ms).
+    pub release_secs: f32,
+    /// The shape of the fade.
+    ///
+    /// By default this is set to [`FadeCurve::EqualPower3dB`].
+    pub curve: FadeCurve,
+}
+
+impl Default for SamplerVoiceFade {
+    fn default() -> Self {
+        Self {
+            attack_secs: 0.002,
+            release_secs: 0.002,
+            curve: FadeCurve::EqualPower3dB,
+        }
+    }
+}
+
+/// Random variation applied to each triggered voice of a [`SamplerNode`].
+///
+/// A new random start offset, pitch, and gain is chosen each time the sample
+/// is started from the beginning. Voices that start after the beginning of
+/// the sample are faded in to avoid clicks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SamplerHumanize {
+    /// The maximum random offset in seconds added to the start of the
+    /// sample.
+    ///
+    /// By default this is set to `0.0`.
+    pub max_start_offset_secs: f64,
+    /// The range of the random pitch change in semitones. The pitch of each
+    /// voice is shifted by a random amount in `[-pitch_range_semitones,
+    /// pitch_range_semitones]` by changing its playback speed.
+    ///
+    /// By default this is set to `0.0`.
+    pub pitch_range_semitones: f32,
+    /// The range of the random gain change in decibels. The gain of each
+    /// voice is changed by a random amount in `[-gain_range_db, gain_range_db]`.
+    ///
+    /// By default this is set to `0.0`.
+    pub gain_range_db: f32,
+    /// The starting seed of the random number generator. This cannot be zero.
+    ///
+    /// If the context is running in deterministic mode, this is combined
+    /// with the seed the context assigns to this node.
+    ///
+    /// By default this is set to `17`.
+    pub seed: u32,
+}
+
+impl Default for SamplerHumanize {
+    fn default() -> Self {
+        Self {
+            max_start_offset_secs: 0.0,
+            pitch_range_semitones: 0.0,
+            gain_range_db: 0.0,
+            seed: 17,
         }
     }
 }
@@ -83,8 +298,9 @@ pub enum PlaybackSpeedQuality {
     #[default]
     /// Low quality, fast performance. Recommended for most use cases.
     ///
-    /// More specifically, this uses a linear resampling algorithm with no
-    /// antialiasing filter.
+    /// More specifically, this uses a linear resampling algorithm, with an
+    /// antialiasing filter when pitching up if [`SamplerConfig::anti_alias`]
+    /// is enabled.
     LinearFast,
     // TODO: more quality options
 }
@@ -144,6 +360,43 @@ pub struct SamplerNode {
     ///
     /// By default this is set to `0.00001` (-100 decibels).
     pub min_gain: f32,
+
+    /// If `true`, then playback wraps from [`SamplerNode::loop_end_frame`]
+    /// back to [`SamplerNode::loop_start_frame`] for as long as this is set,
+    /// regardless of the [`SamplerNode::repeat_mode`].
+    ///
+    /// If the playhead is past the end of the loop region (i.e. because the
+    /// loop points were moved during playback), then it jumps to the start of
+    /// the loop region.
+    ///
+    /// By default this is set to `false`.
+    pub loop_enabled: bool,
+    /// The frame in the sample where the loop region starts.
+    ///
+    /// By default this is set to `0`.
+    pub loop_start_frame: u64,
+    /// The frame in the sample where the loop region ends (exclusive). This
+    /// is clamped to the length of the sample.
+    ///
+    /// If this is less than or equal to [`SamplerNode::loop_start_frame`],
+    /// then the loop region is ignored.
+    ///
+    /// By default this is set to `u64::MAX` (the end of the sample).
+    pub loop_end_frame: u64,
+    /// The number of frames at the end of the loop region that are crossfaded
+    /// with the frames leading up to the start of the loop region, so that
+    /// there is no click at the loop seam.
+    ///
+    /// This is clamped to the length of the loop region and to
+    /// [`SamplerNode::loop_start_frame`] (there must be audio before the start
+    /// of the loop region to fade into).
+    ///
+    /// By default this is set to `0` (no crossfade).
+    pub loop_crossfade_frames: u32,
+    /// The curve used to crossfade the loop seam.
+    ///
+    /// By default this is set to [`FadeCurve::EqualPower3dB`].
+    pub loop_crossfade_curve: FadeCurve,
 }
 
 impl Default for SamplerNode {
@@ -158,6 +411,11 @@ impl Default for SamplerNode {
             mono_to_stereo: true,
             crossfade_on_seek: true,
             min_gain: DEFAULT_AMP_EPSILON,
+            loop_enabled: false,
+            loop_start_frame: 0,
+            loop_end_frame: u64::MAX,
+            loop_crossfade_frames: 0,
+            loop_crossfade_curve: FadeCurve::EqualPower3dB,
         }
     }
 }
@@ -174,6 +432,11 @@ impl core::fmt::Debug for SamplerNode {
         f.field("mono_to_stereo", &self.mono_to_stereo);
         f.field("crossfade_on_seek", &self.crossfade_on_seek);
         f.field("min_gain", &self.min_gain);
+        f.field("loop_enabled", &self.loop_enabled);
+        f.field("loop_start_frame", &self.loop_start_frame);
+        f.field("loop_end_frame", &self.loop_end_frame);
+        f.field("loop_crossfade_frames", &self.loop_crossfade_frames);
+        f.field("loop_crossfade_curve", &self.loop_crossfade_curve);
         f.finish()
     }
 }
@@ -184,6 +447,17 @@ impl SamplerNode {
         self.sample = Some(sample);
     }
 
+    /// The loop region clamped to a sample with the given length, or `None`
+    /// if looping is disabled or the loop region is empty.
+    fn loop_region(&self, sample_len_frames: u64) -> Option<Range<u64>> {
+        if !self.loop_enabled {
+            return None;
+        }
+
+        let end = self.loop_end_frame.min(sample_len_frames);
+        (self.loop_start_frame < end).then_some(self.loop_start_frame..end)
+    }
+
     /// Returns an event type to sync the `sample` parameter.
     pub fn sync_sample_event(&self) -> NodeEventType {
         NodeEventType::Param {
@@ -261,6 +535,10 @@ impl SamplerNode {
     ///
     /// Calling [`SamplerNode::resume`] after this will restart the sample from
     /// the beginning.
+    ///
+    /// When sent as a scheduled event, the voice begins its release (see
+    /// [`SamplerConfig::voice_fade`]) on the exact frame it was scheduled
+    /// for, even in the middle of a processing block.
     pub fn stop(&mut self) {
         self.play_from = PlayFrom::BEGINNING;
         *self.play = false;
@@ -285,8 +563,110 @@ impl SamplerNode {
     pub fn stop_requested(&self) -> bool {
         !*self.play && self.play_from != PlayFrom::Resume
     }
+
+    /// Start a note with the given velocity (in the range `[0, 127]`),
+    /// playing the sample that `layers` selects for it from the beginning.
+    ///
+    /// Returns `false` (and leaves this node untouched) if no velocity zone
+    /// of `layers` covers the velocity.
+    pub fn note_on(&mut self, layers: &mut SampleLayers, velocity: u8) -> bool {
+        let Some(sample) = layers.select(velocity) else {
+            return false;
+        };
+
+        self.set_sample(sample);
+        self.start_or_restart();
+        true
+    }
+}
+
+/// A range of velocities mapped to one or more round-robin variations of a
+/// sample. Used in [`SampleLayers`].
+#[derive(Clone)]
+pub struct VelocityZone {
+    /// The velocities (in the range `[0, 127]`) that select this zone.
+    pub velocities: RangeInclusive<u8>,
+    /// The variations of the sample, which are played in turn each time this
+    /// zone is selected.
+    pub samples: Vec<ArcGc<dyn SampleResource>>,
+    next_sample: usize,
+}
+
+impl VelocityZone {
+    /// Construct a new velocity zone.
+    pub fn new(
+        velocities: RangeInclusive<u8>,
+        samples: impl IntoIterator<Item = ArcGc<dyn SampleResource>>,
+    ) -> Self {
+        Self {
+            velocities,
+            samples: samples.into_iter().collect(),
+            next_sample: 0,
+        }
+    }
+}
+
+/// A set of samples for a sampled instrument, layered by velocity with
+/// round-robin variations within each layer.
+///
+/// Pass this to [`SamplerNode::note_on`] to pick the sample for a note.
+#[derive(Clone, Default)]
+pub struct SampleLayers {
+    /// The velocity zones. If zones overlap, then the first zone that covers
+    /// a velocity is used.
+    pub zones: Vec<VelocityZone>,
+}
+
+impl SampleLayers {
+    /// Construct an empty set of layers.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a velocity zone with the given round-robin variations.
+    pub fn with_zone(
+        mut self,
+        velocities: RangeInclusive<u8>,
+        samples: impl IntoIterator<Item = ArcGc<dyn SampleResource>>,
+    ) -> Self {
+        self.zones.push(VelocityZone::new(velocities, samples));
+        self
+    }
+
+    /// Select the sample for a note with the given velocity, advancing to the
+    /// next round-robin variation of its zone.
+    ///
+    /// Returns `None` if no zone covers the velocity, or if that zone has no
+    /// samples.
+    pub fn select(&mut self, velocity: u8) -> Option<ArcGc<dyn SampleResource>> {
+        let zone = self
+            .zones
+            .iter_mut()
+            .find(|zone| zone.velocities.contains(&velocity))?;
+
+        if zone.samples.is_empty() {
+            return None;
+        }
+
+        // The samples may have been changed since the last selection.
+        let sample = ArcGc::clone(&zone.samples[zone.next_sample % zone.samples.len()]);
+        zone.next_sample = (zone.next_sample + 1) % zone.samples.len();
+
+        Some(sample)
+    }
+
+    /// Restart the round-robin cycle of every zone at its first variation.
+    pub fn reset_round_robin(&mut self) {
+        for zone in self.zones.iter_mut() {
+            zone.next_sample = 0;
+        }
+    }
 }
 
+/// The playback state of a [`SamplerNode`], shared with its processor.
+///
+/// Get it with `FirewheelCtx::node_state::<SamplerState>(node_id)`. Reading
+/// it never blocks.
 #[derive(Clone)]
 pub struct SamplerState {
     shared_state: ArcGc<SharedState>,
@@ -301,6 +681,11 @@ impl SamplerState {
 
     /// Get the current position of the playhead in units of frames (samples of
     /// a single channel of audio).
+    ///
+    /// This is updated every [`SamplerConfig::playhead_report_interval_blocks`]
+    /// processed blocks, and right away when seeking or when the end of the
+    /// sample is reached. While looping, this is the position within the
+    /// sample (it wraps back around at the loop point).
     pub fn playhead_frames(&self) -> DurationSamples {
         DurationSamples(
             self.shared_state
@@ -509,6 +894,10 @@ impl Diff for PlayFrom {
             event_queue.push_param(self.as_param_data(), path);
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        path.is_empty().then(|| self.as_param_data())
+    }
 }
 
 impl Patch for PlayFrom {
@@ -572,15 +961,7 @@ impl AudioNode for SamplerNode {
         config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        let stop_declicker_buffers = if config.num_declickers == 0 {
-            None
-        } else {
-            Some(InstanceBuffer::<f32, MAX_OUT_CHANNELS>::new(
-                config.num_declickers as usize,
-                NonZeroUsize::new(config.channels.get().get() as usize).unwrap(),
-                cx.stream_info.declick_frames.get() as usize,
-            ))
-        };
+        let stop_declicker_buffers = stop_declicker_buffers(config, cx.stream_info);
 
         SamplerProcessor {
             config: config.clone(),
@@ -591,19 +972,124 @@ impl AudioNode for SamplerNode {
             stop_declicker_buffers,
             stop_declickers: smallvec::smallvec![StopDeclickerState::default(); config.num_declickers as usize],
             num_active_stop_declickers: 0,
-            resampler: Some(Resampler::new(config.speed_quality)),
+            stop_fade_frames: stop_fade_frames(config, cx.stream_info),
+            resampler: Some(Resampler::new(config.speed_quality, config.anti_alias)),
             speed: self.speed.max(MIN_PLAYBACK_SPEED),
+            loop_crossfade_buffer: VarChannelBuffer::new(
+                NonZeroUsize::new(config.channels.get().get() as usize).unwrap(),
+                cx.stream_info.max_block_frames.get() as usize,
+            ),
             playing: *self.play,
             paused: !*self.play && self.play_from == PlayFrom::Resume,
             #[cfg(feature = "scheduled_events")]
             queued_playback_instant: None,
             min_gain: self.min_gain.max(0.0),
+            voice: VoiceFx::new(config, cx.stream_info.sample_rate),
+            humanizer: config
+                .humanize
+                .map(|humanize| Humanizer::new(humanize, cx.deterministic_seed)),
             is_first_process: true,
             max_block_frames: cx.stream_info.max_block_frames.get() as usize,
+            blocks_until_playhead_report: 0,
+        }
+    }
+}
+
+/// Mix the frames leading up to the start of the loop region into the frames
+/// at the end of the loop region, so that the audio is continuous when the
+/// playhead wraps back to the start.
+///
+/// * `buffers` - The buffers that were filled with the sample starting at
+///   `playhead_frames`.
+/// * `range_in_buffer` - The range in `buffers` that was filled.
+fn crossfade_loop_seam(
+    sample: &dyn SampleResource,
+    buffers: &mut [&mut [f32]],
+    range_in_buffer: Range<usize>,
+    playhead_frames: u64,
+    loop_region: &Range<u64>,
+    params: &SamplerNode,
+    scratch_buffer: &mut VarChannelBuffer<f32, MAX_OUT_CHANNELS>,
+) {
+    let loop_len = loop_region.end - loop_region.start;
+    let crossfade_frames = (params.loop_crossfade_frames as u64)
+        .min(loop_region.start)
+        .min(loop_len);
+    if crossfade_frames == 0 {
+        return;
+    }
+
+    let fade_start = loop_region.end - crossfade_frames;
+    let block_end = playhead_frames + (range_in_buffer.end - range_in_buffer.start) as u64;
+    if block_end <= fade_start {
+        return;
+    }
+
+    let mut frame = playhead_frames.max(fade_start);
+    let num_channels = buffers.len().min(scratch_buffer.num_channels().get());
+
+    while frame < block_end {
+        let chunk_frames = ((block_end - frame) as usize).min(scratch_buffer.frames());
+        let mut scratch = scratch_buffer.channels_mut(num_channels, chunk_frames);
+
+        sample.fill_buffers(&mut scratch, 0..chunk_frames, frame - loop_len);
+
+        let buf_start = range_in_buffer.start + (frame - playhead_frames) as usize;
+
+        for i in 0..chunk_frames {
+            let fade = (frame + i as u64 - fade_start + 1) as f32 / crossfade_frames as f32;
+            let (gain_end, gain_start) = params.loop_crossfade_curve.compute_gains_0_to_1(fade);
+
+            for (b, s) in buffers.iter_mut().zip(scratch.iter()) {
+                b[buf_start + i] = b[buf_start + i] * gain_end + s[i] * gain_start;
+            }
         }
+
+        frame += chunk_frames as u64;
     }
 }
 
+/// Allocate the buffers that voices are faded out into when they are stopped.
+fn stop_declicker_buffers(
+    config: &SamplerConfig,
+    stream_info: &StreamInfo,
+) -> Option<InstanceBuffer<f32, MAX_OUT_CHANNELS>> {
+    if config.num_declickers == 0 {
+        return None;
+    }
+
+    let fade_out_frames = stop_fade_frames(config, stream_info)
+        .max(steal_fade_frames(config, stream_info.sample_rate).unwrap_or(0));
+
+    Some(InstanceBuffer::<f32, MAX_OUT_CHANNELS>::new(
+        config.num_declickers as usize,
+        NonZeroUsize::new(config.channels.get().get() as usize).unwrap(),
+        fade_out_frames,
+    ))
+}
+
+/// The number of frames a stopped voice is faded out over.
+fn stop_fade_frames(config: &SamplerConfig, stream_info: &StreamInfo) -> usize {
+    match &config.voice_fade {
+        Some(voice_fade) => {
+            secs_to_frames(voice_fade.release_secs, stream_info.sample_rate).max(1) as usize
+        }
+        None => stream_info.declick_frames.get() as usize,
+    }
+}
+
+/// The number of frames a stolen voice is faded out over, if a steal fade
+/// is set.
+fn steal_fade_frames(config: &SamplerConfig, sample_rate: NonZeroU32) -> Option<usize> {
+    config
+        .steal_fade_secs
+        .map(|secs| secs_to_frames(secs, sample_rate).max(1) as usize)
+}
+
+fn secs_to_frames(secs: f32, sample_rate: NonZeroU32) -> u64 {
+    (secs.max(0.0) * sample_rate.get() as f32).round() as u64
+}
+
 struct SamplerProcessor {
     config: SamplerConfig,
     params: SamplerNode,
@@ -619,20 +1105,65 @@ struct SamplerProcessor {
     stop_declicker_buffers: Option<InstanceBuffer<f32, MAX_OUT_CHANNELS>>,
     stop_declickers: SmallVec<[StopDeclickerState; DEFAULT_NUM_DECLICKERS]>,
     num_active_stop_declickers: usize,
+    /// The number of frames a stopped voice is faded out over.
+    stop_fade_frames: usize,
 
     resampler: Option<Resampler>,
     speed: f64,
 
+    /// The frames leading up to the start of the loop region are read into
+    /// this buffer when crossfading the loop seam.
+    loop_crossfade_buffer: VarChannelBuffer<f32, MAX_OUT_CHANNELS>,
+
     #[cfg(feature = "scheduled_events")]
     queued_playback_instant: Option<EventInstant>,
 
     min_gain: f32,
 
+    voice: VoiceFx,
+    humanizer: Option<Humanizer>,
+
     is_first_process: bool,
     max_block_frames: usize,
+
+    /// The number of blocks left until the playhead is reported to the
+    /// [`SamplerState`].
+    blocks_until_playhead_report: u32,
 }
 
 impl SamplerProcessor {
+    fn update_speed(&mut self) {
+        let humanize_speed = self.humanizer.as_ref().map(|h| h.speed).unwrap_or(1.0);
+        self.speed = (self.params.speed * humanize_speed).max(MIN_PLAYBACK_SPEED);
+
+        if self.speed > 0.99999 && self.speed < 1.00001 {
+            self.speed = 1.0;
+        }
+    }
+
+    /// Report the current playhead to the [`SamplerState`], and restart the
+    /// reporting interval.
+    fn report_playhead(&mut self) {
+        if let Some(state) = &self.loaded_sample_state {
+            self.shared_state
+                .sample_playhead_frames
+                .store(state.playhead_frames, Ordering::Relaxed);
+        }
+
+        self.blocks_until_playhead_report = self.config.playhead_report_interval_blocks.max(1);
+    }
+
+    fn gain(&self) -> f32 {
+        let humanize_gain = self.humanizer.as_ref().map(|h| h.gain).unwrap_or(1.0);
+        let gain = self.params.volume.amp_clamped(self.min_gain) * humanize_gain;
+
+        if gain > 0.99999 && gain < 1.00001 {
+            1.0
+        } else {
+            gain
+        }
+    }
+
     /// Returns `true` if the sample has finished playing, and also
     /// returns the number of channels that were filled.
     fn process_internal(
@@ -678,6 +1209,8 @@ impl SamplerProcessor {
             }
         }
 
+        self.voice.process(&mut buffers[..channels_filled], frames);
+
         if state.sample_mono_to_stereo {
             let (b0, b1) = buffers.split_first_mut().unwrap();
             b1[0][..frames].copy_from_slice(&b0[..frames]);
@@ -703,58 +1236,13 @@ impl SamplerProcessor {
             return (true, 0);
         };
 
-        assert!(state.playhead_frames <= state.sample_len_frames);
-
-        let block_frames = range_in_buffer.end - range_in_buffer.start;
-        let first_copy_frames =
-            if state.playhead_frames + block_frames as u64 > state.sample_len_frames {
-                (state.sample_len_frames - state.playhead_frames) as usize
-            } else {
-                block_frames
-            };
-
-        if first_copy_frames > 0 {
-            state.sample.fill_buffers(
-                buffers,
-                range_in_buffer.start..range_in_buffer.start + first_copy_frames,
-                state.playhead_frames,
-            );
-
-            state.playhead_frames += first_copy_frames as u64;
-        }
-
-        if first_copy_frames < block_frames {
-            if looping {
-                let mut frames_copied = first_copy_frames;
-
-                while frames_copied < block_frames {
-                    let copy_frames = ((block_frames - frames_copied) as u64)
-                        .min(state.sample_len_frames)
-                        as usize;
-
-                    state.sample.fill_buffers(
-                        buffers,
-                        range_in_buffer.start + frames_copied
-                            ..range_in_buffer.start + frames_copied + copy_frames,
-                        0,
-                    );
-
-                    state.playhead_frames = copy_frames as u64;
-                    state.num_times_looped_back += 1;
-
-                    frames_copied += copy_frames;
-                }
-            } else {
-                let n_channels = buffers.len().min(state.sample_num_channels.get());
-                for b in buffers[..n_channels].iter_mut() {
-                    b[range_in_buffer.start + first_copy_frames..range_in_buffer.end].fill(0.0);
-                }
-
-                return (true, n_channels);
-            }
-        }
-
-        (false, buffers.len().min(state.sample_num_channels.get()))
+        state.copy_into(
+            buffers,
+            range_in_buffer,
+            looping,
+            &self.params,
+            &mut self.loop_crossfade_buffer,
+        )
     }
 
     fn currently_processing_sample(&self) -> bool {
@@ -777,12 +1265,31 @@ impl SamplerProcessor {
         }
     }
 
-    fn stop(&mut self, num_out_channels: usize, extra: &mut ProcExtra) {
-        if self.currently_processing_sample() {
+    /// Stop the current voice, fading out its sound.
+    ///
+    /// If `restart` is `true`, then a new sound is about to start in its
+    /// place, and the steal fade is used if one is set.
+    fn stop(
+        &mut self,
+        num_out_channels: usize,
+        restart: bool,
+        sample_rate: NonZeroU32,
+        extra: &mut ProcExtra,
+    ) {
+        // There is nothing to fade out if the sample hasn't been loaded yet.
+        if self.currently_processing_sample() && self.loaded_sample_state.is_some() {
             // Fade out the sample into a temporary look-ahead
             // buffer to declick.
 
-            self.declicker.fade_to_0(&extra.declick_values);
+            let steal_fade_frames = if restart {
+                steal_fade_frames(&self.config, sample_rate)
+            } else {
+                None
+            };
+
+            if self.config.voice_fade.is_none() && steal_fade_frames.is_none() {
+                self.declicker.fade_to_0(&extra.declick_values);
+            }
 
             // Work around the borrow checker.
             if let Some(mut stop_declicker_buffers) = self.stop_declicker_buffers.take() {
@@ -796,8 +1303,9 @@ impl SamplerProcessor {
 
                     let n_channels = self.num_channels_filled(num_out_channels);
 
-                    let fade_out_frames = stop_declicker_buffers.frames();
+                    let fade_out_frames = steal_fade_frames.unwrap_or(self.stop_fade_frames);
 
+                    self.stop_declickers[declicker_i].frames = fade_out_frames;
                     self.stop_declickers[declicker_i].frames_left = fade_out_frames;
                     self.stop_declickers[declicker_i].channels = n_channels;
 
@@ -807,6 +1315,10 @@ impl SamplerProcessor {
 
                     self.process_internal(&mut tmp_buffers, fade_out_frames, false, extra);
 
+                    if steal_fade_frames.is_some() || self.config.voice_fade.is_some() {
+                        self.voice.fade_out(&mut tmp_buffers, fade_out_frames);
+                    }
+
                     self.num_active_stop_declickers += 1;
                 }
 
@@ -827,10 +1339,7 @@ impl SamplerProcessor {
     }
 
     fn load_sample(&mut self, sample: ArcGc<dyn SampleResource>, num_out_channels: usize) {
-        let mut gain = self.params.volume.amp_clamped(self.min_gain);
-        if gain > 0.99999 && gain < 1.00001 {
-            gain = 1.0;
-        }
+        let gain = self.gain();
 
         let sample_len_frames = sample.len_frames();
         let sample_num_channels = sample.num_channels();
@@ -911,19 +1420,13 @@ impl AudioNodeProcessor for SamplerProcessor {
         }
 
         if speed_changed {
-            self.speed = self.params.speed.max(MIN_PLAYBACK_SPEED);
-
-            if self.speed > 0.99999 && self.speed < 1.00001 {
-                self.speed = 1.0;
-            }
+            self.update_speed();
         }
 
         if volume_changed {
+            let gain = self.gain();
             if let Some(loaded_sample) = &mut self.loaded_sample_state {
-                loaded_sample.gain = self.params.volume.amp_clamped(self.min_gain);
-                if loaded_sample.gain > 0.99999 && loaded_sample.gain < 1.00001 {
-                    loaded_sample.gain = 1.0;
-                }
+                loaded_sample.gain = gain;
             }
         }
 
@@ -934,7 +1437,12 @@ impl AudioNodeProcessor for SamplerProcessor {
         }
 
         if sample_changed {
-            self.stop(buffers.outputs.len(), extra);
+            self.stop(
+                buffers.outputs.len(),
+                new_playing == Some(true),
+                info.sample_rate,
+                extra,
+            );
 
             #[cfg(feature = "scheduled_events")]
             if new_playing == Some(true) && playback_instant.is_none() {
@@ -953,6 +1461,7 @@ impl AudioNodeProcessor for SamplerProcessor {
         }
 
         if let Some(mut new_playing) = new_playing {
+            let was_paused = self.paused;
             self.paused = false;
 
             if new_playing {
@@ -983,7 +1492,24 @@ impl AudioNodeProcessor for SamplerProcessor {
                     }
                 }
 
+                // Resuming a paused voice continues its envelope, anything
+                // else triggers a new voice.
+                let new_voice = !(was_paused && self.params.play_from == PlayFrom::Resume);
+
+                let mut humanize_offset_frames = 0;
+                if playhead_frames_at_play_instant.is_some() && new_voice {
+                    if let Some(humanizer) = &mut self.humanizer {
+                        humanize_offset_frames = humanizer.trigger(info.sample_rate);
+
+                        self.update_speed();
+                        let gain = self.gain();
+                        self.loaded_sample_state.as_mut().unwrap().gain = gain;
+                    }
+                }
+
                 if let Some(playhead_frames_at_play_instant) = playhead_frames_at_play_instant {
+                    let playhead_frames_at_play_instant =
+                        playhead_frames_at_play_instant + humanize_offset_frames;
                     let loaded_sample_state = self.loaded_sample_state.as_mut().unwrap();
                     let prev_playhead_frames = loaded_sample_state.playhead_frames;
 
@@ -1036,14 +1562,16 @@ impl AudioNodeProcessor for SamplerProcessor {
                     }
 
                     if prev_playhead_frames != new_playhead_frames {
-                        self.stop(buffers.outputs.len(), extra);
+                        self.stop(buffers.outputs.len(), true, info.sample_rate, extra);
 
                         self.loaded_sample_state.as_mut().unwrap().playhead_frames =
                             new_playhead_frames;
 
-                        self.shared_state
-                            .sample_playhead_frames
-                            .store(new_playhead_frames, Ordering::Relaxed);
+                        self.report_playhead();
+                    }
+
+                    if new_voice {
+                        self.voice.reset();
                     }
 
                     if new_playhead_frames
@@ -1075,7 +1603,7 @@ impl AudioNodeProcessor for SamplerProcessor {
                     self.paused = true;
                 } else {
                     // Stop
-                    self.stop(buffers.outputs.len(), extra);
+                    self.stop(buffers.outputs.len(), false, info.sample_rate, extra);
                     self.shared_state
                         .finished
                         .store(self.params.play.id(), Ordering::Relaxed);
@@ -1119,10 +1647,10 @@ impl AudioNodeProcessor for SamplerProcessor {
 
             num_filled_channels = n_channels;
 
-            self.shared_state.sample_playhead_frames.store(
-                self.loaded_sample_state.as_ref().unwrap().playhead_frames,
-                Ordering::Relaxed,
-            );
+            self.blocks_until_playhead_report = self.blocks_until_playhead_report.saturating_sub(1);
+            if self.blocks_until_playhead_report == 0 || finished {
+                self.report_playhead();
+            }
 
             if finished {
                 self.playing = false;
@@ -1149,13 +1677,14 @@ impl AudioNodeProcessor for SamplerProcessor {
 
         if self.num_active_stop_declickers > 0 {
             let tmp_buffers = self.stop_declicker_buffers.as_ref().unwrap();
-            let fade_out_frames = tmp_buffers.frames();
 
             for (declicker_i, declicker) in self.stop_declickers.iter_mut().enumerate() {
                 if declicker.frames_left == 0 {
                     continue;
                 }
 
+                let fade_out_frames = declicker.frames;
+
                 let tmp_buffers = tmp_buffers
                     .instance(declicker_i, declicker.channels, fade_out_frames)
                     .unwrap();
@@ -1196,15 +1725,10 @@ impl AudioNodeProcessor for SamplerProcessor {
 
     fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
         if stream_info.sample_rate != stream_info.prev_sample_rate {
-            self.stop_declicker_buffers = if self.config.num_declickers == 0 {
-                None
-            } else {
-                Some(InstanceBuffer::<f32, MAX_OUT_CHANNELS>::new(
-                    self.config.num_declickers as usize,
-                    NonZeroUsize::new(self.config.channels.get().get() as usize).unwrap(),
-                    stream_info.declick_frames.get() as usize,
-                ))
-            };
+            self.voice = VoiceFx::new(&self.config, stream_info.sample_rate);
+
+            self.stop_declicker_buffers = stop_declicker_buffers(&self.config, stream_info);
+            self.stop_fade_frames = stop_fade_frames(&self.config, stream_info);
 
             // The sample rate has changed, meaning that the sample resources now have
             // the incorrect sample rate and the user must reload them.
@@ -1264,28 +1788,354 @@ struct LoadedSampleState {
     num_times_looped_back: u64,
 }
 
+impl LoadedSampleState {
+    /// Copy the sample into `buffers` starting from the playhead, advancing
+    /// the playhead and wrapping it around the loop region (or the whole
+    /// sample if `looping` is `true`).
+    ///
+    /// Returns whether the sample finished playing, and the number of
+    /// channels that were filled.
+    fn copy_into(
+        &mut self,
+        buffers: &mut [&mut [f32]],
+        range_in_buffer: Range<usize>,
+        looping: bool,
+        params: &SamplerNode,
+        loop_crossfade_buffer: &mut VarChannelBuffer<f32, MAX_OUT_CHANNELS>,
+    ) -> (bool, usize) {
+        assert!(self.playhead_frames <= self.sample_len_frames);
+
+        let n_channels = buffers.len().min(self.sample_num_channels.get());
+        let loop_region = params.loop_region(self.sample_len_frames);
+
+        let block_frames = range_in_buffer.end - range_in_buffer.start;
+        let mut frames_copied = 0;
+
+        while frames_copied < block_frames {
+            let end_frame = if let Some(loop_region) = &loop_region {
+                // This also handles the case where the loop points were moved
+                // behind the playhead during playback.
+                if self.playhead_frames >= loop_region.end {
+                    self.playhead_frames = loop_region.start;
+                }
+
+                loop_region.end
+            } else {
+                self.sample_len_frames
+            };
+
+            if self.playhead_frames == end_frame {
+                if looping && self.sample_len_frames > 0 {
+                    self.playhead_frames = 0;
+                    self.num_times_looped_back += 1;
+                    continue;
+                }
+
+                for b in buffers[..n_channels].iter_mut() {
+                    b[range_in_buffer.start + frames_copied..range_in_buffer.end].fill(0.0);
+                }
+
+                return (true, n_channels);
+            }
+
+            let copy_frames = (end_frame - self.playhead_frames)
+                .min((block_frames - frames_copied) as u64) as usize;
+            let copy_range = range_in_buffer.start + frames_copied
+                ..range_in_buffer.start + frames_copied + copy_frames;
+
+            self.sample
+                .fill_buffers(buffers, copy_range.clone(), self.playhead_frames);
+
+            if let Some(loop_region) = &loop_region {
+                crossfade_loop_seam(
+                    &*self.sample,
+                    &mut buffers[..n_channels],
+                    copy_range,
+                    self.playhead_frames,
+                    loop_region,
+                    params,
+                    loop_crossfade_buffer,
+                );
+            }
+
+            self.playhead_frames += copy_frames as u64;
+            frames_copied += copy_frames;
+        }
+
+        (false, n_channels)
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 struct StopDeclickerState {
+    /// The length of the fade in frames.
+    frames: usize,
     frames_left: usize,
     channels: usize,
 }
 
+/// The per-voice filter and amplitude envelope of a sampler.
+struct VoiceFx {
+    filter_coeff: Option<SvfCoeff>,
+    filter_states: [SvfState; MAX_OUT_CHANNELS],
+    attack_frames: u64,
+    decay_frames: u64,
+    sustain_level: f32,
+    envelope: bool,
+    envelope_frame: u64,
+    fade_in_frames: u64,
+    fade_curve: FadeCurve,
+}
+
+impl VoiceFx {
+    fn new(config: &SamplerConfig, sample_rate: NonZeroU32) -> Self {
+        let sample_rate_recip = (sample_rate.get() as f64).recip() as f32;
+        let secs_to_frames = |secs: f32| secs_to_frames(secs, sample_rate);
+
+        let filter_coeff = config.voice_filter.map(|filter| {
+            let cutoff_hz = filter
+                .cutoff_hz
+                .clamp(1.0, sample_rate.get() as f32 * 0.5 - 1.0);
+            let q = filter.q.max(0.01);
+
+            match filter.filter_type {
+                SamplerFilterType::Lowpass => {
+                    SvfCoeff::lowpass_ord2(cutoff_hz, q, sample_rate_recip)
+                }
+                SamplerFilterType::Highpass => {
+                    SvfCoeff::highpass_ord2(cutoff_hz, q, sample_rate_recip)
+                }
+            }
+        });
+
+        let envelope = config.amp_envelope.unwrap_or_default();
+
+        Self {
+            filter_coeff,
+            filter_states: [SvfState::default(); MAX_OUT_CHANNELS],
+            attack_frames: secs_to_frames(envelope.attack_secs),
+            decay_frames: secs_to_frames(envelope.decay_secs),
+            sustain_level: envelope.sustain_level.clamp(0.0, 1.0),
+            envelope: config.amp_envelope.is_some(),
+            envelope_frame: 0,
+            fade_in_frames: config
+                .voice_fade
+                .as_ref()
+                .map(|voice_fade| secs_to_frames(voice_fade.attack_secs))
+                .unwrap_or(0),
+            fade_curve: config
+                .voice_fade
+                .as_ref()
+                .map(|voice_fade| voice_fade.curve.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Restart the envelope and clear the filter state for a newly
+    /// triggered voice.
+    fn reset(&mut self) {
+        self.envelope_frame = 0;
+        for state in self.filter_states.iter_mut() {
+            state.reset();
+        }
+    }
+
+    fn envelope_gain(&self) -> f32 {
+        let frame = self.envelope_frame;
+
+        if frame < self.attack_frames {
+            (frame + 1) as f32 / self.attack_frames as f32
+        } else if frame < self.attack_frames + self.decay_frames {
+            let t = (frame - self.attack_frames + 1) as f32 / self.decay_frames as f32;
+            1.0 - ((1.0 - self.sustain_level) * t)
+        } else {
+            self.sustain_level
+        }
+    }
+
+    fn process(&mut self, buffers: &mut [&mut [f32]], frames: usize) {
+        if let Some(coeff) = &self.filter_coeff {
+            for (b, state) in buffers.iter_mut().zip(self.filter_states.iter_mut()) {
+                for s in b[..frames].iter_mut() {
+                    *s = state.process(*s, coeff);
+                }
+            }
+        }
+
+        if !self.envelope && self.envelope_frame >= self.fade_in_frames {
+            return;
+        }
+
+        for i in 0..frames {
+            let mut gain = if self.envelope {
+                self.envelope_gain()
+            } else {
+                1.0
+            };
+
+            if self.envelope_frame < self.fade_in_frames {
+                let fade = (self.envelope_frame + 1) as f32 / self.fade_in_frames as f32;
+                gain *= self.fade_curve.compute_gains_0_to_1(fade).1;
+            }
+
+            for b in buffers.iter_mut() {
+                b[i] *= gain;
+            }
+
+            self.envelope_frame = self.envelope_frame.saturating_add(1);
+        }
+    }
+
+    /// Fade out a stopped voice over the given number of frames.
+    fn fade_out(&self, buffers: &mut [&mut [f32]], frames: usize) {
+        for i in 0..frames {
+            let fade = (i + 1) as f32 / frames as f32;
+            let gain = self.fade_curve.compute_gains_0_to_1(fade).0;
+
+            for b in buffers.iter_mut() {
+                b[i] *= gain;
+            }
+        }
+    }
+}
+
+/// Chooses the random start offset, pitch, and gain of each triggered voice.
+struct Humanizer {
+    config: SamplerHumanize,
+    fpd: u32,
+    /// The playback speed multiplier of the current voice.
+    speed: f64,
+    /// The gain multiplier (in raw amplitude) of the current voice.
+    gain: f32,
+}
+
+impl Humanizer {
+    fn new(config: SamplerHumanize, deterministic_seed: Option<u64>) -> Self {
+        let seed = match deterministic_seed {
+            Some(node_seed) => config.seed ^ (node_seed ^ (node_seed >> 32)) as u32,
+            None => config.seed,
+        };
+        // Seed cannot be zero.
+        let seed = if seed == 0 { 17 } else { seed };
+
+        Self {
+            config,
+            fpd: seed,
+            speed: 1.0,
+            gain: 1.0,
+        }
+    }
+
+    /// Returns a random value in the range `[0.0, 1.0]`.
+    fn next_unipolar(&mut self) -> f32 {
+        self.fpd ^= self.fpd << 13;
+        self.fpd ^= self.fpd >> 17;
+        self.fpd ^= self.fpd << 5;
+
+        (self.fpd >> 8) as f32 / (u32::MAX >> 8) as f32
+    }
+
+    /// Returns a random value in the range `[-1.0, 1.0]`.
+    fn next_bipolar(&mut self) -> f32 {
+        self.next_unipolar() * 2.0 - 1.0
+    }
+
+    /// Choose the variation of a newly triggered voice.
+    ///
+    /// Returns the random start offset in frames.
+    fn trigger(&mut self, sample_rate: NonZeroU32) -> u64 {
+        let offset = self.next_unipolar() as f64 * self.config.max_start_offset_secs.max(0.0);
+        let semitones = self.next_bipolar() * self.config.pitch_range_semitones.abs();
+        let gain_db = self.next_bipolar() * self.config.gain_range_db.abs();
+
+        self.speed = 2.0f64.powf(semitones as f64 / 12.0);
+        self.gain = db_to_amp(gain_db);
+
+        (offset * sample_rate.get() as f64).round() as u64
+    }
+}
+
+/// The cutoff of the antialiasing filter relative to the Nyquist frequency
+/// of the resampled output.
+const ANTI_ALIAS_CUTOFF: f32 = 0.9;
+
+/// A lowpass filter applied to the sample before it is resampled, with a
+/// cutoff that tracks the playback speed.
+struct AntiAliasFilter {
+    states: [[SvfState; 2]; MAX_OUT_CHANNELS],
+    coeffs: [SvfCoeff; 2],
+    /// The speed the coefficients were computed for, or `None` if the
+    /// filter is inactive (the speed is not greater than `1.0`).
+    speed: Option<f64>,
+}
+
+impl AntiAliasFilter {
+    fn new() -> Self {
+        Self {
+            states: [[SvfState::default(); 2]; MAX_OUT_CHANNELS],
+            coeffs: [SvfCoeff::NO_OP; 2],
+            speed: None,
+        }
+    }
+
+    /// Update the cutoff for the given playback speed.
+    fn set_speed(&mut self, speed: f64) {
+        if speed <= 1.0 {
+            self.speed = None;
+            return;
+        }
+        if self.speed == Some(speed) {
+            return;
+        }
+        if self.speed.is_none() {
+            self.reset();
+        }
+        self.speed = Some(speed);
+
+        // The cutoff is in cycles per input frame, where the Nyquist
+        // frequency of the output is at `0.5 / speed`.
+        let cutoff = 0.5 * ANTI_ALIAS_CUTOFF / speed as f32;
+        self.coeffs = SvfCoeff::lowpass_ord4(cutoff, Q_BUTTERWORTH_ORD2, 1.0);
+    }
+
+    fn is_active(&self) -> bool {
+        self.speed.is_some()
+    }
+
+    fn process(&mut self, channel: usize, data: &mut [f32]) {
+        let [state_0, state_1] = &mut self.states[channel];
+        for s in data.iter_mut() {
+            let y = state_0.process(*s, &self.coeffs[0]);
+            *s = state_1.process(y, &self.coeffs[1]);
+        }
+    }
+
+    fn reset(&mut self) {
+        for [state_0, state_1] in self.states.iter_mut() {
+            state_0.reset();
+            state_1.reset();
+        }
+    }
+}
+
 struct Resampler {
     fract_in_frame: f64,
     is_first_process: bool,
     prev_speed: f64,
     _quality: PlaybackSpeedQuality,
     wraparound_buffer: [[f32; 2]; MAX_OUT_CHANNELS],
+    anti_alias: Option<AntiAliasFilter>,
 }
 
 impl Resampler {
-    pub fn new(quality: PlaybackSpeedQuality) -> Self {
+    pub fn new(quality: PlaybackSpeedQuality, anti_alias: bool) -> Self {
         Self {
             fract_in_frame: 0.0,
             is_first_process: true,
             prev_speed: 1.0,
             _quality: quality,
             wraparound_buffer: [[0.0; 2]; MAX_OUT_CHANNELS],
+            anti_alias: anti_alias.then(AntiAliasFilter::new),
         }
     }
 
@@ -1324,6 +2174,13 @@ impl Resampler {
                 in_frame_start + (out_frame * start_speed) + (out_frame * out_frame * half_accel)
             };
 
+        if let Some(anti_alias) = &mut self.anti_alias {
+            if self.is_first_process {
+                anti_alias.reset();
+            }
+            anti_alias.set_speed(self.prev_speed.max(processor.speed));
+        }
+
         let num_channels = processor.num_channels_filled(out_buffers.len());
         let copy_start = if self.is_first_process { 0 } else { 2 };
         let mut finished_playing = false;
@@ -1410,6 +2267,12 @@ impl Resampler {
                 if finished {
                     *finished_playing = true;
                 }
+
+                if let Some(anti_alias) = self.anti_alias.as_mut().filter(|f| f.is_active()) {
+                    for (ch_i, r_ch) in scratch_buffers[..num_channels].iter_mut().enumerate() {
+                        anti_alias.process(ch_i, &mut r_ch[copy_start..input_frames]);
+                    }
+                }
             }
 
             let max_block_frames_minus_1 = processor.max_block_frames - 1;
@@ -1419,7 +2282,6 @@ impl Resampler {
 
             // Have an optimized loop for stereo audio.
             if num_channels == 2 {
-                let mut last_in_frame = 0;
                 let mut last_fract_frame = 0.0;
 
                 let (out_ch_0, out_ch_1) = out_buffers.split_first_mut().unwrap();
@@ -1462,17 +2324,19 @@ impl Resampler {
                     *out_s_0 = s0_0 + ((s1_0 - s0_0) * fract_frame as f32);
                     *out_s_1 = s0_1 + ((s1_1 - s0_1) * fract_frame as f32);
 
-                    last_in_frame = in_frame_usize;
                     last_fract_frame = fract_frame;
 
                     out_frames_count += 1;
                 }
 
-                self.wraparound_buffer[0][0] = r_ch_0[last_in_frame];
-                self.wraparound_buffer[1][0] = r_ch_1[last_in_frame];
+                // Carry over the last two frames that were copied (not the
+                // last two frames that were read), so the next chunk lines up
+                // with the playhead even if this chunk ended early.
+                self.wraparound_buffer[0][0] = r_ch_0[input_frames - 2];
+                self.wraparound_buffer[1][0] = r_ch_1[input_frames - 2];
 
-                self.wraparound_buffer[0][1] = r_ch_0[last_in_frame + 1];
-                self.wraparound_buffer[1][1] = r_ch_1[last_in_frame + 1];
+                self.wraparound_buffer[0][1] = r_ch_0[input_frames - 1];
+                self.wraparound_buffer[1][1] = r_ch_1[input_frames - 1];
 
                 self.fract_in_frame = last_fract_frame;
             } else {
@@ -1489,7 +2353,6 @@ impl Resampler {
                         r_ch[1] = w_ch[1];
                     }
 
-                    let mut last_in_frame = 0;
                     let mut last_fract_frame = 0.0;
                     let mut out_frames_ch_count = 0;
                     for (i, out_s) in out_ch[out_ch_start..out_buffer_range.end]
@@ -1512,12 +2375,11 @@ impl Resampler {
 
                         *out_s = s0 + ((s1 - s0) * last_fract_frame as f32);
 
-                        last_in_frame = in_frame_usize;
                         out_frames_ch_count += 1;
                     }
 
-                    w_ch[0] = r_ch[last_in_frame];
-                    w_ch[1] = r_ch[last_in_frame + 1];
+                    w_ch[0] = r_ch[input_frames - 2];
+                    w_ch[1] = r_ch[input_frames - 1];
 
                     self.fract_in_frame = last_fract_frame;
                     out_frames_count = out_frames_ch_count;
@@ -1534,5 +2396,294 @@ impl Resampler {
     pub fn reset(&mut self) {
         self.fract_in_frame = 0.0;
         self.is_first_process = true;
+
+        if let Some(anti_alias) = &mut self.anti_alias {
+            anti_alias.reset();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_envelope_fades_out_voice() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let config = SamplerConfig {
+            amp_envelope: Some(SamplerEnvelope {
+                attack_secs: 0.001,
+                decay_secs: 0.01,
+                sustain_level: 0.0,
+            }),
+            ..Default::default()
+        };
+        let mut voice = VoiceFx::new(&config, sample_rate);
+
+        // 11ms of envelope, then 9ms of silence.
+        let envelope_frames = 528;
+        let mut left = vec![1.0; 960];
+        let mut right = vec![1.0; 960];
+
+        voice.reset();
+        voice.process(&mut [&mut left[..], &mut right[..]], 960);
+
+        assert!(left[..envelope_frames - 1].iter().any(|&s| s > 0.9));
+        assert!(left[envelope_frames..].iter().all(|&s| s == 0.0));
+        assert_eq!(left, right);
+
+        // Retriggering the voice restarts the envelope.
+        let mut data = [1.0; 48];
+        voice.reset();
+        voice.process(&mut [&mut data[..]], 48);
+        assert!((data[47] - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn voice_fade_ramps_dc_sample() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let config = SamplerConfig {
+            voice_fade: Some(SamplerVoiceFade {
+                attack_secs: 0.002,
+                release_secs: 0.001,
+                curve: FadeCurve::Linear,
+            }),
+            ..Default::default()
+        };
+        let mut voice = VoiceFx::new(&config, sample_rate);
+
+        // A DC sample ramps up over 2ms instead of jumping to full level.
+        let attack_frames = 96;
+        let mut data = vec![1.0; 256];
+        voice.reset();
+        voice.process(&mut [&mut data[..]], 256);
+
+        assert!(data[0] < 0.02);
+        assert!(data[..attack_frames].windows(2).all(|w| w[1] > w[0]));
+        assert!((data[attack_frames / 2 - 1] - 0.5).abs() < 1e-6);
+        assert!(data[attack_frames - 1..].iter().all(|&s| s == 1.0));
+
+        // Stopping the voice ramps it back down to silence.
+        let release_frames = 48;
+        let mut data = vec![1.0; release_frames];
+        voice.fade_out(&mut [&mut data[..]], release_frames);
+
+        assert!(data[0] > 0.97);
+        assert!(data.windows(2).all(|w| w[1] < w[0]));
+        assert_eq!(data[release_frames - 1], 0.0);
+    }
+
+    /// Pitch up `input` by `speed` the way the resampler does (optionally
+    /// filtering it first), returning the energy of the output.
+    fn pitched_up_energy(input: &[f32], speed: f64, anti_alias: bool) -> f32 {
+        let mut data = input.to_vec();
+        if anti_alias {
+            let mut filter = AntiAliasFilter::new();
+            filter.set_speed(speed);
+            assert!(filter.is_active());
+            filter.process(0, &mut data);
+        }
+
+        let out_frames = ((data.len() - 1) as f64 / speed) as usize;
+        (0..out_frames)
+            .map(|i| {
+                let in_frame = i as f64 * speed;
+                let i0 = in_frame.trunc() as usize;
+                let fract = in_frame.fract() as f32;
+                let s = data[i0] + (data[i0 + 1] - data[i0]) * fract;
+                s * s
+            })
+            .sum()
+    }
+
+    #[test]
+    fn anti_alias_filter_reduces_aliasing_when_pitching_up() {
+        let speed = 2.0;
+
+        // Content spread across the whole band above the Nyquist frequency
+        // of the pitched-up output (0.25 cycles per frame), which all mirrors
+        // back down when pitched up an octave.
+        let high: Vec<f32> = (0..16_384)
+            .map(|i| {
+                [0.3f32, 0.35, 0.4, 0.45]
+                    .iter()
+                    .map(|f| 0.25 * (core::f32::consts::TAU * f * i as f32).sin())
+                    .sum()
+            })
+            .collect();
+
+        let aliased = pitched_up_energy(&high, speed, false);
+        let filtered = pitched_up_energy(&high, speed, true);
+        assert!(filtered < aliased * 0.1);
+
+        // Content below the new Nyquist frequency passes through mostly
+        // untouched.
+        let low: Vec<f32> = (0..16_384)
+            .map(|i| 0.5 * (core::f32::consts::TAU * 0.05 * i as f32).sin())
+            .collect();
+
+        let unfiltered = pitched_up_energy(&low, speed, false);
+        let filtered = pitched_up_energy(&low, speed, true);
+        assert!(filtered > unfiltered * 0.9);
+
+        // The filter is inactive when not pitching up.
+        let mut filter = AntiAliasFilter::new();
+        filter.set_speed(1.0);
+        assert!(!filter.is_active());
+    }
+
+    #[test]
+    fn humanized_pitch_varies_within_range() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut humanizer = Humanizer::new(
+            SamplerHumanize {
+                max_start_offset_secs: 0.01,
+                pitch_range_semitones: 2.0,
+                gain_range_db: 3.0,
+                ..Default::default()
+            },
+            None,
+        );
+
+        let min_speed = 2.0f64.powf(-2.0 / 12.0);
+        let max_speed = 2.0f64.powf(2.0 / 12.0);
+
+        let mut speeds = Vec::new();
+        for _ in 0..64 {
+            let offset = humanizer.trigger(sample_rate);
+
+            assert!(offset <= 480);
+            assert!(humanizer.speed >= min_speed && humanizer.speed <= max_speed);
+            assert!(humanizer.gain >= db_to_amp(-3.0) && humanizer.gain <= db_to_amp(3.0));
+
+            speeds.push(humanizer.speed);
+        }
+
+        // The playback rate changes from trigger to trigger, and covers both
+        // sides of the original pitch.
+        assert!(speeds.windows(2).all(|w| w[0] != w[1]));
+        assert!(speeds.iter().any(|&s| s < 0.97));
+        assert!(speeds.iter().any(|&s| s > 1.03));
+
+        // The same seed produces the same sequence.
+        let mut other = Humanizer::new(humanizer.config, None);
+        other.trigger(sample_rate);
+        assert_eq!(other.speed, speeds[0]);
+    }
+
+    #[test]
+    fn loop_crossfade_keeps_ramp_continuous_across_seam() {
+        let sample: ArcGc<dyn SampleResource> = ArcGc::new_unsized(|| {
+            std::sync::Arc::new(vec![(0..200).map(|i| i as f32).collect::<Vec<f32>>()]) as _
+        });
+        let mut scratch = VarChannelBuffer::new(NonZeroUsize::new(1).unwrap(), 64);
+
+        let mut play = |state: &mut LoadedSampleState, params: &SamplerNode, frames: usize| {
+            let mut out = vec![0.0; frames];
+            for block in out.chunks_mut(64) {
+                let block_frames = block.len();
+                let (finished, _) =
+                    state.copy_into(&mut [block], 0..block_frames, false, params, &mut scratch);
+                assert!(!finished);
+            }
+            out
+        };
+        let new_state = || LoadedSampleState {
+            sample: ArcGc::clone(&sample),
+            sample_len_frames: 200,
+            sample_num_channels: NonZeroUsize::new(1).unwrap(),
+            sample_mono_to_stereo: false,
+            gain: 1.0,
+            playhead_frames: 0,
+            num_times_looped_back: 0,
+        };
+        let max_step = |out: &[f32]| {
+            out.windows(2)
+                .map(|w| (w[1] - w[0]).abs())
+                .fold(0.0, f32::max)
+        };
+
+        let mut params = SamplerNode {
+            loop_enabled: true,
+            loop_start_frame: 100,
+            loop_end_frame: 180,
+            ..Default::default()
+        };
+
+        // Without a crossfade the ramp jumps back down at the seam.
+        let out = play(&mut new_state(), &params, 400);
+        assert_eq!((out[179], out[180]), (179.0, 100.0));
+        assert!(max_step(&out) > 50.0);
+
+        // With a crossfade the end of the loop fades into the frames leading
+        // up to the start of the loop, so the seam is continuous.
+        params.loop_crossfade_frames = 20;
+        params.loop_crossfade_curve = FadeCurve::Linear;
+        let out = play(&mut new_state(), &params, 400);
+        assert!((0..160).all(|i| out[i] == i as f32));
+        assert_eq!((out[179], out[180]), (99.0, 100.0));
+        assert_eq!(out[260], 100.0);
+        assert!(max_step(&out) <= 3.0 + 1e-4);
+
+        // A loop region shorter than a block wraps several times per block,
+        // and a loop end past the end of the sample is clamped.
+        params.loop_start_frame = 190;
+        params.loop_end_frame = u64::MAX;
+        params.loop_crossfade_frames = 4;
+        let out = play(&mut new_state(), &params, 256);
+        assert_eq!((out[199], out[200], out[210]), (189.0, 190.0, 190.0));
+        assert!(max_step(&out) <= 1.5 + 1e-4);
+
+        // Moving the loop end behind the playhead during playback jumps back
+        // to the start of the loop.
+        params.loop_start_frame = 100;
+        params.loop_end_frame = 180;
+        params.loop_crossfade_frames = 0;
+        let mut state = new_state();
+        play(&mut state, &params, 150);
+        params.loop_end_frame = 120;
+        let out = play(&mut state, &params, 30);
+        assert_eq!((out[0], out[19], out[20]), (100.0, 119.0, 100.0));
+    }
+
+    #[test]
+    fn note_on_selects_layer_by_velocity_and_round_robin() {
+        fn sample(value: f32) -> ArcGc<dyn SampleResource> {
+            ArcGc::new_unsized(|| std::sync::Arc::new(vec![vec![value]]) as _)
+        }
+
+        let quiet = [sample(0.1), sample(0.2)];
+        let loud = [sample(0.8), sample(0.9), sample(1.0)];
+        let mut layers = SampleLayers::new()
+            .with_zone(0..=63, quiet.iter().cloned())
+            .with_zone(64..=127, loud.iter().cloned());
+
+        let mut node = SamplerNode::default();
+        let mut played = |velocity| {
+            assert!(node.note_on(&mut layers, velocity));
+            assert!(node.start_or_restart_requested());
+            node.sample.clone().unwrap()
+        };
+
+        // Low velocities cycle through the quiet layer, and high velocities
+        // through the loud layer, each keeping its own place in the cycle.
+        for expected in [&quiet[0], &quiet[1], &quiet[0]] {
+            assert!(ArcGc::ptr_eq(&played(20), expected));
+        }
+        for expected in [&loud[0], &loud[1], &loud[2], &loud[0]] {
+            assert!(ArcGc::ptr_eq(&played(120), expected));
+        }
+        assert!(ArcGc::ptr_eq(&played(63), &quiet[1]));
+        assert!(ArcGc::ptr_eq(&played(64), &loud[1]));
+
+        layers.reset_round_robin();
+        assert!(node.note_on(&mut layers, 100));
+        assert!(ArcGc::ptr_eq(node.sample.as_ref().unwrap(), &loud[0]));
+
+        // A velocity outside every zone leaves the node untouched.
+        let mut layers = SampleLayers::new().with_zone(10..=127, quiet.iter().cloned());
+        let mut node = SamplerNode::default();
+        assert!(!node.note_on(&mut layers, 5));
+        assert!(node.sample.is_none());
     }
 }
@@ -0,0 +1,311 @@
+//! A node that sums several inputs, compensating the gain for how correlated
+//! the inputs are.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount, NonZeroChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        filter::smoothing_filter::{SmoothingFilter, SmoothingFilterCoeff, DEFAULT_SMOOTH_SECONDS},
+        volume::{Volume, DEFAULT_AMP_EPSILON},
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// The configuration for a [`SmartSumNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmartSumNodeConfig {
+    /// The number of channels in each input. This is also the number of
+    /// output channels.
+    ///
+    /// By default this is set to [`NonZeroChannelCount::STEREO`].
+    pub channels: NonZeroChannelCount,
+
+    /// The number of inputs that are summed together.
+    ///
+    /// By default this is set to `2`.
+    ///
+    /// ## Panics
+    ///
+    /// This will cause a panic if `channels * num_inputs` is `0` or greater
+    /// than `64`.
+    pub num_inputs: u32,
+}
+
+impl Default for SmartSumNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            num_inputs: 2,
+        }
+    }
+}
+
+/// A node that sums several inputs, compensating the gain for how correlated
+/// the inputs are
+///
+/// With `N` channels per input, inputs `0..N` are the first input, inputs
+/// `N..2N` are the second input, and so on.
+///
+/// Summing uncorrelated signals adds their power, while summing correlated
+/// signals adds their amplitudes, which is up to `3` dB louder per doubling
+/// and can clip. This node measures the power of the sum against the summed
+/// power of the inputs, and attenuates the output by the difference, so the
+/// sum stays at the level of an uncorrelated sum. The output is never
+/// boosted, so partially cancelling (anti-correlated) inputs are left as
+/// they are.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmartSumNode {
+    /// The volume applied to the sum after gain compensation.
+    ///
+    /// By default this is set to [`Volume::UNITY_GAIN`].
+    pub volume: Volume,
+    /// The time in seconds over which the correlation of the inputs is
+    /// measured. Shorter times follow changes faster, but let the gain
+    /// fluctuate more.
+    ///
+    /// By default this is set to `0.05` (50ms).
+    pub response_seconds: f32,
+    /// The time in seconds of the internal smoothing filter for `volume`.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for SmartSumNode {
+    fn default() -> Self {
+        Self {
+            volume: Volume::UNITY_GAIN,
+            response_seconds: 0.05,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for SmartSumNode {
+    type Configuration = SmartSumNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        let num_channels = config.channels.get().get();
+        let num_inputs = num_channels
+            .checked_mul(config.num_inputs)
+            .filter(|&n| n > 0)
+            .and_then(ChannelCount::new)
+            .unwrap_or_else(|| {
+                panic!(
+                    "SmartSumNodeConfig must have between 1 and 64 input channels in total, got {} channels * {} inputs",
+                    num_channels, config.num_inputs
+                )
+            });
+
+        AudioNodeInfo::new()
+            .debug_name("smart_sum")
+            .channel_config(ChannelConfig {
+                num_inputs,
+                num_outputs: config.channels.get(),
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(
+            *self,
+            config.channels.get().get() as usize,
+            cx.stream_info.sample_rate,
+        );
+        processor
+            .gain
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct Processor {
+    params: SmartSumNode,
+    channels: usize,
+    gain: SmoothedParam,
+    power_coeff: SmoothingFilterCoeff,
+    /// The average power of the sum.
+    sum_power: SmoothingFilter,
+    /// The average of the summed power of the inputs.
+    input_power: SmoothingFilter,
+}
+
+impl Processor {
+    fn new(params: SmartSumNode, channels: usize, sample_rate: NonZeroU32) -> Self {
+        Self {
+            params,
+            channels,
+            gain: SmoothedParam::new(
+                params.volume.amp_clamped(DEFAULT_AMP_EPSILON),
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+            power_coeff: SmoothingFilterCoeff::new(sample_rate, params.response_seconds),
+            sum_power: SmoothingFilter::new(0.0),
+            input_power: SmoothingFilter::new(0.0),
+        }
+    }
+
+    /// Sum a block of frames into the outputs.
+    fn sum(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        for i in 0..frames {
+            let mut sum_power = 0.0;
+            let mut input_power = 0.0;
+
+            for (ch, out_ch) in outputs.iter_mut().enumerate() {
+                let mut sum = 0.0;
+                for input in inputs.iter().skip(ch).step_by(self.channels) {
+                    let s = input[i];
+                    sum += s;
+                    input_power += s * s;
+                }
+
+                out_ch[i] = sum;
+                sum_power += sum * sum;
+            }
+
+            let sum_power = self.sum_power.process(sum_power, self.power_coeff);
+            let input_power = self.input_power.process(input_power, self.power_coeff);
+
+            // The power of the sum is equal to the summed power of the inputs
+            // when they are uncorrelated, and up to `num_inputs` times higher
+            // when they are identical.
+            let compensation = if sum_power > input_power {
+                (input_power / sum_power).sqrt()
+            } else {
+                1.0
+            };
+
+            let gain = self.gain.next_smoothed() * compensation;
+            for out_ch in outputs.iter_mut() {
+                out_ch[i] *= gain;
+            }
+        }
+
+        self.gain.settle();
+    }
+
+    fn reset(&mut self) {
+        self.sum_power = SmoothingFilter::new(0.0);
+        self.input_power = SmoothingFilter::new(0.0);
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<SmartSumNode>() {
+            match patch {
+                SmartSumNodePatch::Volume(volume) => {
+                    self.gain.set_value(volume.amp_clamped(DEFAULT_AMP_EPSILON));
+
+                    if info.prev_output_was_silent {
+                        // Previous block was silent, so no need to smooth.
+                        self.gain.reset_to_target();
+                    }
+                }
+                SmartSumNodePatch::ResponseSeconds(seconds) => {
+                    self.power_coeff = SmoothingFilterCoeff::new(info.sample_rate, seconds);
+                }
+                SmartSumNodePatch::SmoothSeconds(seconds) => {
+                    self.gain.set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+
+            self.params.apply(patch);
+        }
+
+        if info
+            .in_silence_mask
+            .all_channels_silent(buffers.inputs.len())
+        {
+            self.gain.reset_to_target();
+            self.reset();
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.sum(buffers.inputs, buffers.outputs, info.frames);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.gain.update_sample_rate(stream_info.sample_rate);
+        self.power_coeff =
+            SmoothingFilterCoeff::new(stream_info.sample_rate, self.params.response_seconds);
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The ratio of the output level to the level of the plain sum, measured
+    /// after the correlation measurement has settled.
+    fn compensation_gain(a: &[f32], b: &[f32]) -> f32 {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(SmartSumNode::default(), 1, sample_rate);
+
+        let mut out = vec![0.0; a.len()];
+        processor.sum(&[a, b], &mut [&mut out], a.len());
+
+        let tail = a.len() / 2..a.len();
+        let out_power: f32 = out[tail.clone()].iter().map(|s| s * s).sum();
+        let sum_power: f32 = tail.map(|i| (a[i] + b[i]).powi(2)).sum();
+        (out_power / sum_power).sqrt()
+    }
+
+    fn noise(seed: u32, frames: usize) -> Vec<f32> {
+        let mut state = seed;
+        (0..frames)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    #[test]
+    fn identical_inputs_are_attenuated_more_than_uncorrelated_inputs() {
+        let a = noise(1, 48_000);
+        let b = noise(2, 48_000);
+
+        let identical = compensation_gain(&a, &a);
+        let uncorrelated = compensation_gain(&a, &b);
+
+        // Identical inputs sum to twice the amplitude, so they are brought
+        // down by `3` dB to the level of an uncorrelated sum.
+        assert!((identical - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!(uncorrelated > 0.95);
+        assert!(identical < uncorrelated * 0.75);
+    }
+}
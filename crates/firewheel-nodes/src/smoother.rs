@@ -0,0 +1,262 @@
+//! A node that turns parameter events into a smoothed audio-rate signal.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::filter::smoothing_filter::{
+        SmoothingFilter, SmoothingFilterCoeff, DEFAULT_SMOOTH_SECONDS,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    StreamInfo,
+};
+
+/// The distance to the target value at which an exponential transition
+/// snaps to the target.
+const SETTLE_EPSILON: f32 = 0.00001;
+
+/// The shape of the transition of a [`SmootherNode`] to a new value.
+#[derive(Default, Diff, Patch, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SmootherCurve {
+    /// An exponential approach (a one-pole lowpass filter), where
+    /// [`SmootherNode::smooth_seconds`] is the time constant. After one time
+    /// constant the output has covered about 63% of the distance to the new
+    /// value.
+    #[default]
+    Exponential,
+    /// A straight line that reaches the new value after exactly
+    /// [`SmootherNode::smooth_seconds`].
+    Linear,
+}
+
+/// A node that outputs its [`SmootherNode::value`] parameter as a smoothed
+/// audio-rate signal (Mono output only).
+///
+/// This bridges discrete parameter changes to audio-rate modulation. The
+/// output can be routed into the modulation inputs of other nodes.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SmootherNode {
+    /// The value the output moves towards.
+    ///
+    /// By default this is set to `0.0`.
+    pub value: f32,
+    /// The smoothing time in seconds. See [`SmootherCurve`] for how this is
+    /// interpreted.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+    /// The shape of the transition to a new value.
+    ///
+    /// By default this is set to [`SmootherCurve::Exponential`].
+    pub curve: SmootherCurve,
+}
+
+impl Default for SmootherNode {
+    fn default() -> Self {
+        Self {
+            value: 0.0,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+            curve: SmootherCurve::Exponential,
+        }
+    }
+}
+
+impl AudioNode for SmootherNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("smoother")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::ZERO,
+                num_outputs: ChannelCount::MONO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, cx.stream_info.sample_rate)
+    }
+}
+
+struct Processor {
+    params: SmootherNode,
+    sample_rate: NonZeroU32,
+
+    filter: SmoothingFilter,
+    coeff: SmoothingFilterCoeff,
+
+    linear_step: f32,
+    linear_frames_left: u32,
+}
+
+impl Processor {
+    fn new(params: SmootherNode, sample_rate: NonZeroU32) -> Self {
+        Self {
+            params,
+            sample_rate,
+            filter: SmoothingFilter::new(params.value),
+            coeff: SmoothingFilterCoeff::new(sample_rate, params.smooth_seconds),
+            linear_step: 0.0,
+            linear_frames_left: 0,
+        }
+    }
+
+    fn has_settled(&self) -> bool {
+        self.filter.z1 == self.params.value
+    }
+
+    /// Start moving from the current output towards the target value.
+    fn begin_transition(&mut self) {
+        self.coeff = SmoothingFilterCoeff::new(self.sample_rate, self.params.smooth_seconds);
+
+        let frames = (self.params.smooth_seconds.max(0.0) * self.sample_rate.get() as f32) as u32;
+        if frames == 0 {
+            self.filter.z1 = self.params.value;
+            self.linear_frames_left = 0;
+        } else {
+            self.linear_step = (self.params.value - self.filter.z1) / frames as f32;
+            self.linear_frames_left = frames;
+        }
+    }
+
+    fn render(&mut self, out: &mut [f32]) {
+        if self.has_settled() {
+            out.fill(self.params.value);
+            return;
+        }
+
+        let target = self.params.value;
+
+        match self.params.curve {
+            SmootherCurve::Exponential => {
+                let prev = self.filter.z1;
+
+                for s in out.iter_mut() {
+                    *s = self.filter.process(target, self.coeff);
+                }
+
+                // With long smoothing times, rounding errors can stop the
+                // filter just short of the target.
+                if (self.filter.z1 - target).abs() < SETTLE_EPSILON || self.filter.z1 == prev {
+                    self.filter.z1 = target;
+                }
+            }
+            SmootherCurve::Linear => {
+                for s in out.iter_mut() {
+                    if self.linear_frames_left > 0 {
+                        self.linear_frames_left -= 1;
+                        self.filter.z1 = if self.linear_frames_left == 0 {
+                            target
+                        } else {
+                            self.filter.z1 + self.linear_step
+                        };
+                    }
+
+                    *s = self.filter.z1;
+                }
+            }
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        let mut value_changed = false;
+        for patch in events.drain_patches::<SmootherNode>() {
+            value_changed |= !matches!(patch, SmootherNodePatch::Curve(_));
+            self.params.apply(patch);
+        }
+
+        if value_changed {
+            self.begin_transition();
+        }
+
+        if self.has_settled() && self.params.value == 0.0 {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.render(&mut buffers.outputs[0][..info.frames]);
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.coeff = SmoothingFilterCoeff::new(self.sample_rate, self.params.smooth_seconds);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_step(curve: SmootherCurve) -> Vec<f32> {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(
+            SmootherNode {
+                smooth_seconds: 0.01,
+                curve,
+                ..Default::default()
+            },
+            sample_rate,
+        );
+
+        processor.params.value = 1.0;
+        processor.begin_transition();
+
+        let mut out = vec![0.0; 9_600];
+        for block in out.chunks_mut(256) {
+            processor.render(block);
+        }
+
+        out
+    }
+
+    #[test]
+    fn step_approaches_exponentially() {
+        let out = render_step(SmootherCurve::Exponential);
+
+        // The output covers about 63% of the distance after one time constant
+        // (10ms), and about 86% after two.
+        let one_tau = 1.0 - (-1.0f32).exp();
+        let two_tau = 1.0 - (-2.0f32).exp();
+        assert!((out[479] - one_tau).abs() < 1e-3);
+        assert!((out[959] - two_tau).abs() < 1e-3);
+
+        // The remaining distance shrinks by the same ratio every frame.
+        let ratio = (1.0 - out[101]) / (1.0 - out[100]);
+        assert!(((1.0 - out[1001]) / (1.0 - out[1000]) - ratio).abs() < 1e-4);
+
+        assert!(out.windows(2).all(|w| w[1] >= w[0]));
+        assert_eq!(*out.last().unwrap(), 1.0);
+    }
+
+    #[test]
+    fn linear_step_reaches_target_on_time() {
+        let out = render_step(SmootherCurve::Linear);
+
+        assert!((out[239] - 0.5).abs() < 1e-4);
+        assert!(out[478] < 1.0);
+        assert!(out[479..].iter().all(|&s| s == 1.0));
+    }
+}
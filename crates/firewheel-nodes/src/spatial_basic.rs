@@ -2,6 +2,8 @@
 //! be used for 2D audio.) It does not make use of any fancy binaural algorithms,
 //! rather it just applies basic panning and filtering.
 
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Vec;
 #[cfg(not(feature = "std"))]
 use num_traits::Float;
 
@@ -30,7 +32,7 @@ use firewheel_core::{
 /// A 3D spatial positioning node using a basic but fast algorithm. (It can also be used
 /// for 2D audio). It does not make use of any fancy binaural algorithms, rather it just
 /// applies basic panning and filtering.
-#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[derive(Diff, Patch, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -62,6 +64,23 @@ pub struct SpatialBasicNode {
     /// By default this is set to `0.6`.
     pub panning_threshold: f32,
 
+    /// The algorithm used to map the pan position of the sound source in the
+    /// range `[-1.0, 1.0]` to the corresponding gain values for the left and
+    /// right channels.
+    ///
+    /// By default this is set to [`FadeCurve::EqualPower3dB`] (constant power).
+    pub pan_law: FadeCurve,
+
+    /// The stereo width of the spatialized output in the range `[0.0, 1.0]`,
+    /// where `0.0` is mono and `1.0` leaves the left and right channels
+    /// unchanged.
+    ///
+    /// Lower values narrow the panning, as well as the stereo image of the
+    /// input if [`SpatialBasicNode::downmix`] is `false`.
+    ///
+    /// By default this is set to `1.0`.
+    pub width: f32,
+
     /// If `true`, then any stereo input signals will be downmixed to mono before
     /// going throught the spatialization algorithm. If `false` then the left and
     /// right channels will be processed independently.
@@ -108,6 +127,14 @@ pub struct SpatialBasicNode {
     ///
     /// By default this is set to `5`.
     pub coeff_update_factor: CoeffUpdateFactor,
+
+    /// If `true`, then this node fades out to silence and then stops
+    /// processing to save CPU.
+    ///
+    /// This is usually managed by a [`SpatialVoiceLimit`].
+    ///
+    /// By default this is set to `false`.
+    pub muted: bool,
 }
 
 impl Default for SpatialBasicNode {
@@ -116,12 +143,15 @@ impl Default for SpatialBasicNode {
             volume: Volume::default(),
             offset: Vec3::new(0.0, 0.0, 0.0),
             panning_threshold: 0.6,
+            pan_law: FadeCurve::EqualPower3dB,
+            width: 1.0,
             downmix: true,
             distance_attenuation: DistanceAttenuation::default(),
             muffle_cutoff_hz: MUFFLE_CUTOFF_HZ_MAX,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: 0.0001,
             coeff_update_factor: CoeffUpdateFactor::default(),
+            muted: false,
         }
     }
 }
@@ -158,6 +188,21 @@ impl SpatialBasicNode {
         self.volume = Volume::Decibels(decibels);
     }
 
+    /// The distance between the listener and the sound source.
+    pub fn distance(&self) -> f32 {
+        ((self.offset.x * self.offset.x)
+            + (self.offset.y * self.offset.y)
+            + (self.offset.z * self.offset.z))
+            .sqrt()
+    }
+
+    /// The gain (in raw amplitude) of the sound source as heard by the
+    /// listener, taking both the volume and the distance attenuation into
+    /// account (but not panning or muffling).
+    pub fn audible_gain(&self) -> f32 {
+        self.volume.amp() * self.distance_attenuation.gain_at_distance(self.distance())
+    }
+
     fn compute_values(&self) -> ComputedValues {
         let x2_z2 = (self.offset.x * self.offset.x) + (self.offset.z * self.offset.z);
         let xz_distance = x2_z2.sqrt();
@@ -168,9 +213,9 @@ impl SpatialBasicNode {
         } else {
             0.0
         };
-        let (pan_gain_l, pan_gain_r) = FadeCurve::EqualPower3dB.compute_gains_neg1_to_1(pan);
+        let (pan_gain_l, pan_gain_r) = self.pan_law.compute_gains_neg1_to_1(pan);
 
-        let mut volume_gain = self.volume.amp();
+        let mut volume_gain = if self.muted { 0.0 } else { self.volume.amp() };
         if volume_gain > 0.99999 && volume_gain < 1.00001 {
             volume_gain = 1.0;
         }
@@ -221,29 +266,37 @@ impl AudioNode for SpatialBasicNode {
         Processor {
             gain_l: SmoothedParam::new(
                 computed_values.gain_l,
-                SmootherConfig {
+                cx.smoother_config(SmootherConfig {
                     smooth_seconds: self.smooth_seconds,
                     ..Default::default()
-                },
+                }),
                 cx.stream_info.sample_rate,
             ),
             gain_r: SmoothedParam::new(
                 computed_values.gain_r,
-                SmootherConfig {
+                cx.smoother_config(SmootherConfig {
                     smooth_seconds: self.smooth_seconds,
                     ..Default::default()
-                },
+                }),
+                cx.stream_info.sample_rate,
+            ),
+            width: SmoothedParam::new(
+                self.width.clamp(0.0, 1.0),
+                cx.smoother_config(SmootherConfig {
+                    smooth_seconds: self.smooth_seconds,
+                    ..Default::default()
+                }),
                 cx.stream_info.sample_rate,
             ),
             distance_attenuator: DistanceAttenuatorStereoDsp::new(
-                SmootherConfig {
+                cx.smoother_config(SmootherConfig {
                     smooth_seconds: self.smooth_seconds,
                     ..Default::default()
-                },
+                }),
                 cx.stream_info.sample_rate,
                 self.coeff_update_factor,
             ),
-            params: *self,
+            params: self.clone(),
         }
     }
 }
@@ -251,6 +304,7 @@ impl AudioNode for SpatialBasicNode {
 struct Processor {
     gain_l: SmoothedParam,
     gain_r: SmoothedParam,
+    width: SmoothedParam,
 
     distance_attenuator: DistanceAttenuatorStereoDsp,
 
@@ -276,9 +330,14 @@ impl AudioNodeProcessor for Processor {
                 SpatialBasicNodePatch::PanningThreshold(threshold) => {
                     *threshold = threshold.clamp(0.0, 1.0);
                 }
+                SpatialBasicNodePatch::Width(width) => {
+                    *width = width.clamp(0.0, 1.0);
+                    self.width.set_value(*width);
+                }
                 SpatialBasicNodePatch::SmoothSeconds(seconds) => {
                     self.gain_l.set_smooth_seconds(*seconds, info.sample_rate);
                     self.gain_r.set_smooth_seconds(*seconds, info.sample_rate);
+                    self.width.set_smooth_seconds(*seconds, info.sample_rate);
                     self.distance_attenuator
                         .set_smooth_seconds(*seconds, info.sample_rate);
                 }
@@ -312,6 +371,7 @@ impl AudioNodeProcessor for Processor {
                 // Previous block was silent, so no need to smooth.
                 self.gain_l.reset_to_target();
                 self.gain_r.reset_to_target();
+                self.width.reset_to_target();
                 self.distance_attenuator.reset();
             }
         }
@@ -319,6 +379,7 @@ impl AudioNodeProcessor for Processor {
         if info.in_silence_mask.all_channels_silent(2) {
             self.gain_l.reset_to_target();
             self.gain_r.reset_to_target();
+            self.width.reset_to_target();
             self.distance_attenuator.reset();
 
             return ProcessStatus::ClearAllOutputs;
@@ -368,7 +429,7 @@ impl AudioNodeProcessor for Processor {
         if self.gain_l.has_settled() && self.gain_r.has_settled() {
             if self.gain_l.target_value() <= self.params.min_gain
                 && self.gain_r.target_value() <= self.params.min_gain
-                && self.distance_attenuator.is_silent()
+                && (self.params.muted || self.distance_attenuator.is_silent())
             {
                 self.gain_l.reset_to_target();
                 self.gain_r.reset_to_target();
@@ -394,6 +455,16 @@ impl AudioNodeProcessor for Processor {
             self.gain_r.settle();
         }
 
+        if !self.width.has_settled() || self.width.target_value() < 1.0 {
+            for (s1, s2) in out1.iter_mut().zip(out2.iter_mut()) {
+                let (l, r) = apply_width(*s1, *s2, self.width.next_smoothed());
+                *s1 = l;
+                *s2 = r;
+            }
+
+            self.width.settle();
+        }
+
         let clear_outputs =
             self.distance_attenuator
                 .process(info.frames, out1, out2, info.sample_rate_recip);
@@ -416,7 +487,146 @@ impl AudioNodeProcessor for Processor {
     ) {
         self.gain_l.update_sample_rate(stream_info.sample_rate);
         self.gain_r.update_sample_rate(stream_info.sample_rate);
+        self.width.update_sample_rate(stream_info.sample_rate);
         self.distance_attenuator
             .update_sample_rate(stream_info.sample_rate);
     }
 }
+
+/// How a [`SpatialVoiceLimit`] decides which voices stay audible.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpatialVoicePriority {
+    /// Keep the voices closest to the listener.
+    #[default]
+    Nearest,
+    /// Keep the voices that are the loudest to the listener (see
+    /// [`SpatialBasicNode::audible_gain`]).
+    Loudest,
+}
+
+/// Limits how many voices in a group of [`SpatialBasicNode`]s are audible at
+/// once, to save CPU in large scenes with many sound sources.
+///
+/// Every voice beyond the limit is [`muted`](SpatialBasicNode::muted), which
+/// fades it out and then stops it from processing. Call
+/// [`SpatialVoiceLimit::apply`] whenever the sources or the listener move,
+/// and then send the changes to the nodes as usual (i.e. with a `Memo`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpatialVoiceLimit {
+    /// The maximum number of voices in the group that are audible at once.
+    pub max_audible: usize,
+    /// How to decide which voices stay audible.
+    ///
+    /// By default this is set to [`SpatialVoicePriority::Nearest`].
+    pub priority: SpatialVoicePriority,
+}
+
+impl SpatialVoiceLimit {
+    pub const fn new(max_audible: usize) -> Self {
+        Self {
+            max_audible,
+            priority: SpatialVoicePriority::Nearest,
+        }
+    }
+
+    /// Unmute the highest priority voices in the group up to
+    /// [`SpatialVoiceLimit::max_audible`], and mute the rest.
+    pub fn apply<'a>(&self, voices: impl IntoIterator<Item = &'a mut SpatialBasicNode>) {
+        let mut voices: Vec<(f32, &'a mut SpatialBasicNode)> = voices
+            .into_iter()
+            .map(|voice| {
+                let score = match self.priority {
+                    SpatialVoicePriority::Nearest => voice.distance(),
+                    // Sort the loudest voices first.
+                    SpatialVoicePriority::Loudest => -voice.audible_gain(),
+                };
+
+                (score, voice)
+            })
+            .collect();
+
+        voices.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        for (i, (_, voice)) in voices.into_iter().enumerate() {
+            voice.muted = i >= self.max_audible;
+        }
+    }
+}
+
+/// Narrow a stereo signal, where a `width` of `0.0` is mono and `1.0` leaves
+/// the signal unchanged.
+#[inline]
+fn apply_width(l: f32, r: f32, width: f32) -> (f32, f32) {
+    let direct = (1.0 + width) * 0.5;
+    let cross = (1.0 - width) * 0.5;
+
+    (l * direct + r * cross, r * direct + l * cross)
+}
+
+#[cfg(test)]
+mod tests {
+    use firewheel_core::dsp::volume::amp_to_db;
+
+    use super::*;
+
+    #[test]
+    fn constant_power_center_is_minus_3_db() {
+        for offset in [Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 5.0)] {
+            let values = SpatialBasicNode {
+                offset,
+                pan_law: FadeCurve::EqualPower3dB,
+                ..Default::default()
+            }
+            .compute_values();
+
+            assert_eq!(values.gain_l, values.gain_r);
+            assert!((amp_to_db(values.gain_l) + 3.0103).abs() < 1e-3);
+        }
+
+        // The linear law drops the center by 6 dB instead.
+        let values = SpatialBasicNode {
+            pan_law: FadeCurve::Linear,
+            ..Default::default()
+        }
+        .compute_values();
+        assert!((amp_to_db(values.gain_l) + 6.0206).abs() < 1e-3);
+    }
+
+    #[test]
+    fn voice_limit_mutes_farthest_sources() {
+        let mut voices: Vec<SpatialBasicNode> = [30.0, 2.0, 50.0, 10.0, 1.0]
+            .into_iter()
+            .map(|z| SpatialBasicNode::from_volume_offset(Volume::UNITY_GAIN, [0.0, 0.0, z]))
+            .collect();
+
+        SpatialVoiceLimit::new(3).apply(voices.iter_mut());
+        let muted: Vec<bool> = voices.iter().map(|v| v.muted).collect();
+        assert_eq!(muted, [true, false, true, false, false]);
+
+        // Muted voices are silent.
+        let values = voices[0].compute_values();
+        assert_eq!((values.gain_l, values.gain_r), (0.0, 0.0));
+        assert!(voices[1].compute_values().gain_l > 0.0);
+
+        // A quiet nearby source yields to a loud farther one.
+        voices[4].volume = Volume::Decibels(-60.0);
+        SpatialVoiceLimit {
+            max_audible: 3,
+            priority: SpatialVoicePriority::Loudest,
+        }
+        .apply(voices.iter_mut());
+        let muted: Vec<bool> = voices.iter().map(|v| v.muted).collect();
+        assert_eq!(muted, [false, false, true, false, true]);
+    }
+
+    #[test]
+    fn zero_width_is_mono() {
+        assert_eq!(apply_width(1.0, 0.0, 1.0), (1.0, 0.0));
+        assert_eq!(apply_width(1.0, 0.0, 0.0), (0.5, 0.5));
+        assert_eq!(apply_width(1.0, 0.0, 0.5), (0.75, 0.25));
+    }
+}
@@ -0,0 +1,375 @@
+//! A node that captures the spectrum of its input and sustains it as a drone.
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+use realfft::num_complex::Complex;
+
+use crate::stft::{Stft, OVERLAP};
+
+/// The smallest allowed value for [`SpectralFreezeNodeConfig::fft_size`].
+pub const MIN_FFT_SIZE: usize = 64;
+
+/// The configuration of a [`SpectralFreezeNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralFreezeNodeConfig {
+    /// The number of input and output channels.
+    pub channels: NonZeroChannelCount,
+    /// The size of the FFT in frames. This is rounded up to a power of two of
+    /// at least [`MIN_FFT_SIZE`].
+    ///
+    /// Larger sizes capture the spectrum more finely in frequency, which
+    /// gives smoother drones. The node adds this many frames of latency.
+    ///
+    /// By default this is set to `4096`.
+    pub fft_size: usize,
+}
+
+impl Default for SpectralFreezeNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            fft_size: 4096,
+        }
+    }
+}
+
+impl SpectralFreezeNodeConfig {
+    fn rounded_fft_size(&self) -> usize {
+        self.fft_size.max(MIN_FFT_SIZE).next_power_of_two()
+    }
+}
+
+/// A node that captures the spectrum of its input and sustains it
+/// indefinitely, for turning any sound into an ambient pad.
+///
+/// When [`SpectralFreezeNode::frozen`] is set to `true`, the magnitude of
+/// each frequency bin of the input is captured and resynthesized with new
+/// random phases on every analysis frame, so the drone keeps the timbre of
+/// the captured moment without sounding like a static looped tone. Setting it
+/// back to `false` releases the drone and returns to the live input.
+///
+/// The input is resynthesized with overlap-add, so this node adds latency of
+/// [`SpectralFreezeNodeConfig::fft_size`] frames.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralFreezeNode {
+    /// Whether the output is the captured spectrum (`true`) or the live input
+    /// (`false`).
+    ///
+    /// The spectrum is captured on the first analysis frame after this
+    /// changes to `true`, which is within `fft_size / 4` frames.
+    ///
+    /// By default this is set to `false`.
+    pub frozen: bool,
+    /// The time in seconds to crossfade between the live input and the
+    /// frozen spectrum.
+    ///
+    /// By default this is set to `0.1` (100ms).
+    pub fade_seconds: f32,
+}
+
+impl Default for SpectralFreezeNode {
+    fn default() -> Self {
+        Self {
+            frozen: false,
+            fade_seconds: 0.1,
+        }
+    }
+}
+
+impl AudioNode for SpectralFreezeNode {
+    type Configuration = SpectralFreezeNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("spectral_freeze")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+            .latency_frames(config.rounded_fft_size() as u32)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        _cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, config)
+    }
+}
+
+struct Processor {
+    params: SpectralFreezeNode,
+    stft: Stft,
+    /// The captured magnitude of each bin of each channel.
+    frozen: Vec<Vec<f32>>,
+
+    /// Whether the spectrum should be captured on the next analysis frame.
+    capture: bool,
+    /// Whether a spectrum has been captured yet.
+    has_frozen: bool,
+    /// The amount of the frozen spectrum in the output, in the range
+    /// `[0.0, 1.0]`.
+    mix: f32,
+    /// The state of the random number generator for the phases.
+    rng: u32,
+
+    /// The number of consecutive frames of silent input, saturating once
+    /// every buffer is guaranteed to have been flushed.
+    silent_frames: usize,
+}
+
+impl Processor {
+    fn new(params: SpectralFreezeNode, config: &SpectralFreezeNodeConfig) -> Self {
+        let num_channels = config.channels.get().get() as usize;
+        let stft = Stft::new(config.rounded_fft_size(), num_channels);
+        let num_bins = stft.num_bins();
+
+        Self {
+            params,
+            stft,
+            frozen: vec![vec![0.0; num_bins]; num_channels],
+            // Capture as soon as processing starts if the node is created
+            // frozen.
+            capture: params.frozen,
+            has_frozen: false,
+            mix: 0.0,
+            rng: 0x9E37_79B9,
+            silent_frames: 0,
+        }
+    }
+
+    #[cfg(test)]
+    fn hop_size(&self) -> usize {
+        self.stft.hop_size()
+    }
+
+    fn set_frozen(&mut self, frozen: bool) {
+        if frozen && !self.params.frozen {
+            self.capture = true;
+        }
+        self.params.frozen = frozen;
+    }
+
+    /// Returns `true` if the output only depends on the live input.
+    fn is_live(&self) -> bool {
+        !self.params.frozen && self.mix == 0.0
+    }
+
+    /// Run the freeze over a block of frames.
+    fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        sample_rate: f32,
+    ) {
+        let hop_size = self.stft.hop_size();
+        let fade_frames = self.params.fade_seconds.max(0.0) * sample_rate;
+
+        self.stft.process(inputs, outputs, frames, |spectra| {
+            let capture = core::mem::take(&mut self.capture);
+            if capture {
+                self.has_frozen = true;
+            }
+
+            let target = if self.params.frozen && self.has_frozen {
+                1.0
+            } else {
+                0.0
+            };
+            self.mix = if fade_frames <= hop_size as f32 {
+                target
+            } else {
+                let step = hop_size as f32 / fade_frames;
+                if target > self.mix {
+                    (self.mix + step).min(target)
+                } else {
+                    (self.mix - step).max(target)
+                }
+            };
+            let live_gain = 1.0 - self.mix;
+            // Frames with random phases don't add up coherently like the
+            // overlapping frames of the live input do, so only their power
+            // adds.
+            let frozen_gain = self.mix * (OVERLAP as f32).sqrt();
+
+            for (spectrum, frozen) in spectra.iter_mut().zip(self.frozen.iter_mut()) {
+                if capture {
+                    for (frozen, bin) in frozen.iter_mut().zip(spectrum.iter()) {
+                        *frozen = bin.norm();
+                    }
+                }
+
+                if self.mix > 0.0 {
+                    for (bin, &frozen) in spectrum.iter_mut().zip(frozen.iter()) {
+                        let frozen = Complex::from_polar(frozen, next_phase(&mut self.rng));
+                        *bin = *bin * live_gain + frozen * frozen_gain;
+                    }
+                } else {
+                    for bin in spectrum.iter_mut() {
+                        *bin *= live_gain;
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Generate a random phase in the range `[0.0, TAU]`.
+fn next_phase(rng: &mut u32) -> f32 {
+    *rng ^= *rng << 13;
+    *rng ^= *rng >> 17;
+    *rng ^= *rng << 5;
+    *rng as f32 * (core::f32::consts::TAU / u32::MAX as f32)
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<SpectralFreezeNode>() {
+            match patch {
+                SpectralFreezeNodePatch::Frozen(frozen) => self.set_frozen(frozen),
+                _ => self.params.apply(patch),
+            }
+        }
+
+        // Once the input has been silent for long enough, every buffer has
+        // been flushed and the output stays silent. A frozen spectrum keeps
+        // sounding no matter the input.
+        let flush_frames = self.stft.fft_size() * 2;
+        if info
+            .in_silence_mask
+            .all_channels_silent(buffers.inputs.len())
+        {
+            self.silent_frames = (self.silent_frames + info.frames).min(flush_frames);
+        } else {
+            self.silent_frames = 0;
+        }
+        if self.silent_frames == flush_frames && self.is_live() && info.prev_output_was_silent {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.render(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            info.sample_rate.get() as f32,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use realfft::RealFftPlanner;
+
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    fn sine(freq_hz: f32, frames: usize) -> Vec<f32> {
+        (0..frames)
+            .map(|i| 0.5 * (core::f32::consts::TAU * freq_hz * i as f32 / SAMPLE_RATE).sin())
+            .collect()
+    }
+
+    /// Run a mono signal through the processor in blocks of 256 frames.
+    fn render(processor: &mut Processor, input: &[f32]) -> Vec<f32> {
+        let mut output = vec![0.0; input.len()];
+        for (in_block, out_block) in input.chunks(256).zip(output.chunks_mut(256)) {
+            processor.render(&[in_block], &mut [out_block], in_block.len(), SAMPLE_RATE);
+        }
+        output
+    }
+
+    /// The power spectrum of a signal, summed into bands of `100` Hz.
+    fn band_powers(signal: &[f32]) -> Vec<f32> {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let fft = planner.plan_fft_forward(signal.len());
+        let mut input: Vec<f32> = signal
+            .iter()
+            .enumerate()
+            .map(|(i, s)| {
+                let phase = i as f32 / signal.len() as f32;
+                s * (0.5 - 0.5 * (core::f32::consts::TAU * phase).cos())
+            })
+            .collect();
+        let mut spectrum = fft.make_output_vec();
+        fft.process(&mut input, &mut spectrum).unwrap();
+
+        let bin_hz = SAMPLE_RATE / signal.len() as f32;
+        let mut bands = vec![0.0; (SAMPLE_RATE / 2.0 / 100.0) as usize + 1];
+        for (i, bin) in spectrum.iter().enumerate() {
+            bands[(i as f32 * bin_hz / 100.0) as usize] += bin.norm_sqr();
+        }
+        bands
+    }
+
+    #[test]
+    fn frozen_spectrum_stays_constant_while_input_changes() {
+        let config = SpectralFreezeNodeConfig {
+            channels: NonZeroChannelCount::MONO,
+            fft_size: 2048,
+        };
+        let mut processor = Processor::new(
+            SpectralFreezeNode {
+                fade_seconds: 0.0,
+                ..Default::default()
+            },
+            &config,
+        );
+        let segment = 16_384;
+
+        // Freeze on a 1 kHz tone. The spectrum is captured on the next
+        // analysis frame, so keep playing the tone for one more hop.
+        let tone = sine(1_000.0, 8_192 + processor.hop_size());
+        render(&mut processor, &tone[..8_192]);
+        processor.set_frozen(true);
+        render(&mut processor, &tone[8_192..]);
+
+        // Then play a 3 kHz tone, and then a 5 kHz tone, into the frozen node.
+        render(&mut processor, &sine(3_000.0, 8_192));
+        let first = render(&mut processor, &sine(3_000.0, segment));
+        let second = render(&mut processor, &sine(5_000.0, segment));
+
+        let first = band_powers(&first);
+        let second = band_powers(&second);
+        let total = |bands: &[f32]| bands.iter().sum::<f32>();
+
+        // Both segments contain the frozen 1 kHz tone at the same level, and
+        // none of the tones that were played after freezing. (The random
+        // phases spread some of the tone out into the neighbouring bands.)
+        for bands in [&first, &second] {
+            assert!(bands[9..=10].iter().sum::<f32>() > total(bands) * 0.8);
+            assert!(bands[29..=30].iter().sum::<f32>() < total(bands) * 0.01);
+            assert!(bands[49..=50].iter().sum::<f32>() < total(bands) * 0.01);
+        }
+        assert!((total(&first) / total(&second) - 1.0).abs() < 0.1);
+
+        // Unfreezing returns to the live input.
+        processor.set_frozen(false);
+        render(&mut processor, &sine(5_000.0, 8_192));
+        let live = band_powers(&render(&mut processor, &sine(5_000.0, segment)));
+        assert!(live[49..=50].iter().sum::<f32>() > total(&live) * 0.95);
+    }
+}
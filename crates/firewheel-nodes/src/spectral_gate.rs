@@ -0,0 +1,347 @@
+//! A spectral noise gate for cleaning up recordings.
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Notify, Patch},
+    dsp::volume::db_to_amp,
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+
+use crate::stft::Stft;
+
+/// The smallest allowed value for [`SpectralGateNodeConfig::fft_size`].
+pub const MIN_FFT_SIZE: usize = 64;
+
+/// The time in seconds the running average spectrum (which is captured by
+/// [`SpectralGateNode::learn`]) is averaged over.
+const AVERAGE_SECONDS: f32 = 0.5;
+
+/// The amount the gain of a gated bin moves back down towards its target on
+/// each analysis frame. Closing gradually keeps isolated bins from flickering
+/// in and out ("musical noise").
+const RELEASE: f32 = 0.5;
+
+/// The configuration of a [`SpectralGateNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralGateNodeConfig {
+    /// The number of input and output channels.
+    pub channels: NonZeroChannelCount,
+    /// The size of the FFT in frames. This is rounded up to a power of two of
+    /// at least [`MIN_FFT_SIZE`].
+    ///
+    /// Larger sizes resolve the noise more finely in frequency, at the cost
+    /// of smearing transients. The node adds this many frames of latency.
+    ///
+    /// By default this is set to `2048`.
+    pub fft_size: usize,
+}
+
+impl Default for SpectralGateNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            fft_size: 2048,
+        }
+    }
+}
+
+impl SpectralGateNodeConfig {
+    fn rounded_fft_size(&self) -> usize {
+        self.fft_size.max(MIN_FFT_SIZE).next_power_of_two()
+    }
+}
+
+/// A noise reduction node that attenuates each frequency bin of its input
+/// which falls below a learned noise profile.
+///
+/// To use it, play a section of the recording that contains only the noise
+/// (i.e. room tone or tape hiss) and notify [`SpectralGateNode::learn`]. The
+/// spectrum of the input averaged over the last half second becomes the
+/// noise profile, and from then on any bin which is not at least
+/// [`SpectralGateNode::threshold_db`] above the profile is turned down by
+/// [`SpectralGateNode::reduction_db`]. Until a profile is learned, the input
+/// passes through unchanged.
+///
+/// The input is resynthesized with overlap-add, so this node adds latency of
+/// [`SpectralGateNodeConfig::fft_size`] frames.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralGateNode {
+    /// How far in decibels a bin must rise above the noise profile to pass
+    /// through untouched.
+    ///
+    /// The magnitude of noise in each bin fluctuates a lot around its
+    /// average, so this should be kept well above `0.0`.
+    ///
+    /// By default this is set to `12.0`.
+    pub threshold_db: f32,
+    /// How far in decibels a bin below the threshold is turned down.
+    ///
+    /// By default this is set to `24.0`.
+    pub reduction_db: f32,
+    /// Capture the current spectrum of the input as the noise profile.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub learn: Notify<()>,
+}
+
+impl Default for SpectralGateNode {
+    fn default() -> Self {
+        Self {
+            threshold_db: 12.0,
+            reduction_db: 24.0,
+            learn: Notify::default(),
+        }
+    }
+}
+
+impl AudioNode for SpectralGateNode {
+    type Configuration = SpectralGateNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("spectral_gate")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+            .latency_frames(config.rounded_fft_size() as u32)
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        _cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor::new(*self, config)
+    }
+}
+
+struct Processor {
+    params: SpectralGateNode,
+    stft: Stft,
+    /// The gain applied to each bin of each channel on the last analysis
+    /// frame.
+    gains: Vec<Vec<f32>>,
+
+    /// The magnitude of each bin averaged over all channels and over the
+    /// last [`AVERAGE_SECONDS`].
+    average: Vec<f32>,
+    /// The learned noise profile.
+    profile: Vec<f32>,
+    /// Whether or not a noise profile has been learned yet.
+    has_profile: bool,
+
+    /// The number of consecutive frames of silent input, saturating once
+    /// every buffer is guaranteed to have been flushed.
+    silent_frames: usize,
+}
+
+impl Processor {
+    fn new(params: SpectralGateNode, config: &SpectralGateNodeConfig) -> Self {
+        let num_channels = config.channels.get().get() as usize;
+        let stft = Stft::new(config.rounded_fft_size(), num_channels);
+        let num_bins = stft.num_bins();
+
+        Self {
+            params,
+            stft,
+            gains: vec![vec![1.0; num_bins]; num_channels],
+            average: vec![0.0; num_bins],
+            profile: vec![0.0; num_bins],
+            has_profile: false,
+            silent_frames: 0,
+        }
+    }
+
+    /// Run the gate over a block of frames.
+    fn render(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        sample_rate: f32,
+    ) {
+        let num_channels = self.gains.len() as f32;
+
+        let threshold = db_to_amp(self.params.threshold_db);
+        let reduction = db_to_amp(-self.params.reduction_db.max(0.0));
+
+        let average_frames = AVERAGE_SECONDS * sample_rate / self.stft.hop_size() as f32;
+        let average_coeff = 1.0 - (-average_frames.recip()).exp();
+
+        self.stft.process(inputs, outputs, frames, |spectra| {
+            self.average
+                .iter_mut()
+                .for_each(|a| *a *= 1.0 - average_coeff);
+
+            for (spectrum, gains) in spectra.iter_mut().zip(self.gains.iter_mut()) {
+                for (i, (bin, gain)) in spectrum.iter_mut().zip(gains.iter_mut()).enumerate() {
+                    let magnitude = bin.norm();
+                    self.average[i] += magnitude * average_coeff / num_channels;
+
+                    let target = if self.has_profile && magnitude < self.profile[i] * threshold {
+                        reduction
+                    } else {
+                        1.0
+                    };
+                    *gain = if target >= *gain {
+                        target
+                    } else {
+                        target + (*gain - target) * RELEASE
+                    };
+
+                    *bin *= *gain;
+                }
+            }
+        });
+    }
+
+    fn learn(&mut self) {
+        self.profile.copy_from_slice(&self.average);
+        self.has_profile = true;
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<SpectralGateNode>() {
+            if let SpectralGateNodePatch::Learn(_) = patch {
+                self.learn();
+            }
+            self.params.apply(patch);
+        }
+
+        // Once the input has been silent for long enough, every buffer has
+        // been flushed and the output stays silent.
+        let flush_frames = self.stft.fft_size() * 2;
+        if info
+            .in_silence_mask
+            .all_channels_silent(buffers.inputs.len())
+        {
+            self.silent_frames = (self.silent_frames + info.frames).min(flush_frames);
+        } else {
+            self.silent_frames = 0;
+        }
+        if self.silent_frames == flush_frames && info.prev_output_was_silent {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        self.render(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            info.sample_rate.get() as f32,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: f32 = 48_000.0;
+
+    /// Deterministic white noise in the range `[-amplitude, amplitude]`.
+    fn noise(frames: usize, amplitude: f32, seed: &mut u32) -> Vec<f32> {
+        (0..frames)
+            .map(|_| {
+                *seed ^= *seed << 13;
+                *seed ^= *seed >> 17;
+                *seed ^= *seed << 5;
+                (*seed as f32 / u32::MAX as f32 * 2.0 - 1.0) * amplitude
+            })
+            .collect()
+    }
+
+    fn rms(s: &[f32]) -> f32 {
+        (s.iter().map(|s| s * s).sum::<f32>() / s.len() as f32).sqrt()
+    }
+
+    /// Run a mono signal through the processor in blocks of 256 frames.
+    fn render(processor: &mut Processor, input: &[f32]) -> Vec<f32> {
+        let mut output = vec![0.0; input.len()];
+        for (in_block, out_block) in input.chunks(256).zip(output.chunks_mut(256)) {
+            processor.render(&[in_block], &mut [out_block], in_block.len(), SAMPLE_RATE);
+        }
+        output
+    }
+
+    #[test]
+    fn noise_is_reduced_while_tone_passes() {
+        let config = SpectralGateNodeConfig {
+            channels: NonZeroChannelCount::MONO,
+            fft_size: 1024,
+        };
+        let mut processor = Processor::new(SpectralGateNode::default(), &config);
+        let latency = config.rounded_fft_size();
+        let mut seed = 17;
+
+        // Learn the profile of the noise on its own.
+        let frames = SAMPLE_RATE as usize;
+        render(&mut processor, &noise(frames, 0.05, &mut seed));
+        processor.learn();
+
+        // Fresh noise of the same kind is turned down by close to the full
+        // reduction amount.
+        let noise_only = noise(frames, 0.05, &mut seed);
+        let output = render(&mut processor, &noise_only);
+        assert!(rms(&output[latency..]) < rms(&noise_only) * db_to_amp(-18.0));
+
+        // A strong tone on top of the noise passes at its full level, while
+        // most of the noise around it is still removed.
+        let tone: Vec<f32> = (0..frames)
+            .map(|i| 0.5 * (core::f32::consts::TAU * 1_000.0 * i as f32 / SAMPLE_RATE).sin())
+            .collect();
+        let noise_part = noise(frames, 0.05, &mut seed);
+        let noisy_tone: Vec<f32> = noise_part
+            .iter()
+            .zip(tone.iter())
+            .map(|(n, t)| n + t)
+            .collect();
+        let output = render(&mut processor, &noisy_tone);
+
+        // Compare the output against the clean tone, lined up for latency.
+        let settled = latency * 2;
+        let output = &output[settled..];
+        let tone = &tone[settled - latency..frames - latency];
+        let residual: Vec<f32> = output.iter().zip(tone).map(|(o, t)| o - t).collect();
+
+        assert!((rms(output) / rms(tone) - 1.0).abs() < 0.05);
+        assert!(rms(&residual) < rms(&noise_part) * 0.25);
+    }
+
+    #[test]
+    fn passes_through_before_learning() {
+        let config = SpectralGateNodeConfig {
+            channels: NonZeroChannelCount::MONO,
+            fft_size: 256,
+        };
+        let mut processor = Processor::new(SpectralGateNode::default(), &config);
+        let latency = config.rounded_fft_size();
+
+        let input = noise(4096, 0.5, &mut 17);
+        let output = render(&mut processor, &input);
+
+        assert!(output[latency..]
+            .iter()
+            .zip(input.iter())
+            .all(|(o, i)| (o - i).abs() < 1e-4));
+    }
+}
@@ -0,0 +1,189 @@
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount, NonZeroChannelCount},
+    event::ProcEvents,
+    mask::{MaskType, SilenceMask},
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+
+/// The configuration for a [`SplitterNode`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplitterNodeConfig {
+    /// The number of input channels. This is also the number of channels
+    /// in each copy.
+    ///
+    /// By default this is set to [`NonZeroChannelCount::STEREO`].
+    pub channels: NonZeroChannelCount,
+
+    /// The number of identical copies of the input.
+    ///
+    /// By default this is set to `2`.
+    ///
+    /// ## Panics
+    ///
+    /// This will cause a panic if `channels * copies` is `0` or greater
+    /// than `64`.
+    pub copies: u32,
+}
+
+impl Default for SplitterNodeConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            copies: 2,
+        }
+    }
+}
+
+/// A node that duplicates its input into several identical outputs
+///
+/// With `N` input channels, outputs `0..N` are the first copy, outputs
+/// `N..2N` are the second copy, and so on. Each copy can then be routed to a
+/// different chain of nodes to process the same source in parallel.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SplitterNode;
+
+impl AudioNode for SplitterNode {
+    type Configuration = SplitterNodeConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        let num_channels = config.channels.get().get();
+        let num_outputs = num_channels
+            .checked_mul(config.copies)
+            .filter(|&n| n > 0)
+            .and_then(ChannelCount::new)
+            .unwrap_or_else(|| {
+                panic!(
+                    "SplitterNodeConfig must have between 1 and 64 output channels in total, got {} channels * {} copies",
+                    num_channels, config.copies
+                )
+            });
+
+        AudioNodeInfo::new()
+            .debug_name("splitter")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        _cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        SplitterProcessor
+    }
+}
+
+struct SplitterProcessor;
+
+impl SplitterProcessor {
+    /// Copy the inputs into every copy of the outputs, returning the silence
+    /// mask of the outputs.
+    fn split(
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        in_silence_mask: SilenceMask,
+    ) -> SilenceMask {
+        let mut out_silence_mask = SilenceMask::NONE_SILENT;
+
+        for (out_ch_i, out_ch) in outputs.iter_mut().enumerate() {
+            let in_ch_i = out_ch_i % inputs.len();
+
+            if in_silence_mask.is_channel_silent(in_ch_i) {
+                out_silence_mask.set_channel(out_ch_i, true);
+                out_ch[..frames].fill(0.0);
+            } else {
+                out_ch[..frames].copy_from_slice(&inputs[in_ch_i][..frames]);
+            }
+        }
+
+        out_silence_mask
+    }
+}
+
+impl AudioNodeProcessor for SplitterProcessor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        _events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        if buffers.inputs.is_empty()
+            || info
+                .in_silence_mask
+                .all_channels_silent(buffers.inputs.len())
+        {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let out_silence_mask = Self::split(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            info.in_silence_mask,
+        );
+
+        ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(out_silence_mask))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copies_are_bit_identical_to_input() {
+        let left: Vec<f32> = (0..256).map(|i| (i as f32 * 0.05).sin()).collect();
+        let right: Vec<f32> = (0..256).map(|i| -(i as f32 * 0.013).cos()).collect();
+
+        let mut outputs = vec![vec![1.0; 256]; 6];
+        let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|o| &mut o[..]).collect();
+
+        let mask = SplitterProcessor::split(
+            &[&left, &right],
+            &mut output_refs,
+            256,
+            SilenceMask::NONE_SILENT,
+        );
+
+        assert_eq!(mask, SilenceMask::NONE_SILENT);
+        for copy in outputs.chunks(2) {
+            assert!(copy[0]
+                .iter()
+                .zip(&left)
+                .all(|(a, b)| a.to_bits() == b.to_bits()));
+            assert!(copy[1]
+                .iter()
+                .zip(&right)
+                .all(|(a, b)| a.to_bits() == b.to_bits()));
+        }
+
+        // A silent input channel is silent in every copy.
+        let mut output_refs: Vec<&mut [f32]> = outputs.iter_mut().map(|o| &mut o[..]).collect();
+        let mask = SplitterProcessor::split(
+            &[&left, &right],
+            &mut output_refs,
+            256,
+            SilenceMask::MONO_SILENT,
+        );
+
+        for (i, copy) in outputs.chunks(2).enumerate() {
+            assert!(mask.is_channel_silent(i * 2));
+            assert!(!mask.is_channel_silent(i * 2 + 1));
+            assert!(copy[0].iter().all(|&s| s == 0.0));
+            assert_eq!(copy[1], right);
+        }
+    }
+}
@@ -0,0 +1,662 @@
+//! A stereo echo node with independent left and right delay times.
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::{
+        declick::{DeclickFadeCurve, Declicker},
+        delay_line::{delay_frames_for_seconds, RingDelayLine},
+        envelope_follower::{EnvelopeFollower, EnvelopeFollowerConfig},
+        fade::FadeCurve,
+        filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+        mix::{Mix, MixDSP},
+        volume::Volume,
+    },
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+/// The maximum value of the feedback parameters.
+pub const MAX_FEEDBACK: f32 = 0.95;
+
+/// The shortest possible delay time in milliseconds.
+const MIN_DELAY_MS: f32 = 0.1;
+
+/// The configuration for a [`StereoDelayNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StereoDelayNodeConfig {
+    /// The maximum delay time in seconds. This determines the size of the
+    /// allocated delay buffers.
+    ///
+    /// By default this is set to `2.0`.
+    pub max_delay_seconds: f32,
+}
+
+impl Default for StereoDelayNodeConfig {
+    fn default() -> Self {
+        Self {
+            max_delay_seconds: 2.0,
+        }
+    }
+}
+
+/// A stereo echo with independent delay times and feedback for the left and
+/// right channels (Stereo input and output).
+///
+/// Different delay times for each channel give rhythmic stereo effects,
+/// and the cross-feedback sends the echoes of each channel into the other
+/// (at `1.0`, the echoes bounce back and forth in a "ping-pong" pattern).
+///
+/// The echoes can also be ducked while the dry input is present with
+/// [`StereoDelayNode::ducking`], so that they only bloom in the gaps and stay
+/// out of the way of the main signal.
+#[derive(Diff, Patch, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StereoDelayNode {
+    /// The delay time of the left channel in milliseconds.
+    ///
+    /// By default this is set to `250.0`.
+    pub delay_ms_l: f32,
+    /// The delay time of the right channel in milliseconds.
+    ///
+    /// By default this is set to `375.0`.
+    pub delay_ms_r: f32,
+    /// The amount of the left channel's echoes that is fed back into the
+    /// delay, in the range `[0.0, 0.95]`.
+    ///
+    /// By default this is set to `0.4`.
+    pub feedback_l: f32,
+    /// The amount of the right channel's echoes that is fed back into the
+    /// delay, in the range `[0.0, 0.95]`.
+    ///
+    /// By default this is set to `0.4`.
+    pub feedback_r: f32,
+    /// The portion of each channel's feedback that is taken from the echoes
+    /// of the other channel, in the range `[0.0, 1.0]`.
+    ///
+    /// By default this is set to `0.0`.
+    pub cross_feedback: f32,
+    /// The mix between the dry and the delayed signal.
+    ///
+    /// The wet amount is scaled by the context's global wet amount (see
+    /// [`ProcInfo::global_wet`](firewheel_core::node::ProcInfo::global_wet)).
+    ///
+    /// By default this is set to [`Mix::CENTER`].
+    pub mix: Mix,
+    /// The algorithm used to map the normalized mix value in the range `[0.0,
+    /// 1.0]` to the corresponding gain values for the two signals.
+    ///
+    /// By default this is set to [`FadeCurve::EqualPower3dB`].
+    pub fade_curve: FadeCurve,
+    /// How much the echoes are ducked while the dry input is present, in the
+    /// range `[0.0, 1.0]`. At `1.0` the echoes are silenced while the input
+    /// is at or above [`StereoDelayNode::duck_threshold`]. Quieter input
+    /// ducks the echoes proportionally less.
+    ///
+    /// Only the output is ducked, so the echoes still build up in the delay
+    /// and rise once the input falls away.
+    ///
+    /// By default this is set to `0.0` (no ducking).
+    pub ducking: f32,
+    /// The level of the dry input at which the echoes are fully ducked.
+    ///
+    /// By default this is set to `-30` dB.
+    pub duck_threshold: Volume,
+    /// The level detector that follows the dry input (the louder of the two
+    /// channels) for ducking.
+    ///
+    /// By default this uses a 10ms attack and a 250ms release.
+    pub duck_detector: EnvelopeFollowerConfig,
+    /// Whether or not this node is enabled.
+    pub enabled: bool,
+
+    /// The time in seconds of the internal smoothing filter.
+    ///
+    /// Changing a delay time glides smoothly to the new time, which briefly
+    /// bends the pitch of the echoes like a tape delay.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for StereoDelayNode {
+    fn default() -> Self {
+        Self {
+            delay_ms_l: 250.0,
+            delay_ms_r: 375.0,
+            feedback_l: 0.4,
+            feedback_r: 0.4,
+            cross_feedback: 0.0,
+            mix: Mix::CENTER,
+            fade_curve: FadeCurve::EqualPower3dB,
+            ducking: 0.0,
+            duck_threshold: Volume::Decibels(-30.0),
+            duck_detector: EnvelopeFollowerConfig {
+                attack_secs: 0.01,
+                release_secs: 0.25,
+                ..Default::default()
+            },
+            enabled: true,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for StereoDelayNode {
+    type Configuration = StereoDelayNodeConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("stereo_delay")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::STEREO,
+                num_outputs: ChannelCount::STEREO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(self, config, cx.stream_info);
+        for param in [
+            &mut processor.delay_ms_l,
+            &mut processor.delay_ms_r,
+            &mut processor.feedback_l,
+            &mut processor.feedback_r,
+            &mut processor.cross_feedback,
+            &mut processor.ducking,
+        ] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+            .mix
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct Processor {
+    lines: [RingDelayLine; 2],
+    max_delay_seconds: f32,
+    /// The number of frames the delay has been fed only silence. Once this
+    /// covers the whole buffer, the echoes have died out.
+    silent_frames: usize,
+
+    delay_ms_l: SmoothedParam,
+    delay_ms_r: SmoothedParam,
+    feedback_l: SmoothedParam,
+    feedback_r: SmoothedParam,
+    cross_feedback: SmoothedParam,
+    mix: MixDSP,
+    mix_value: Mix,
+    fade_curve: FadeCurve,
+
+    ducking: SmoothedParam,
+    duck_threshold_amp: f32,
+    duck_follower: EnvelopeFollower,
+
+    enable_declicker: Declicker,
+    frames_per_ms: f32,
+    max_delay_ms: f32,
+}
+
+impl Processor {
+    fn new(params: &StereoDelayNode, config: &StereoDelayNodeConfig, info: &StreamInfo) -> Self {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+        let max_delay_seconds = config.max_delay_seconds.max(MIN_DELAY_MS / 1_000.0);
+        let max_delay_ms = max_delay_seconds * 1_000.0;
+        let max_delay_frames = delay_frames_for_seconds(max_delay_seconds, info.sample_rate);
+
+        let delay_param = |delay_ms: f32| {
+            SmoothedParam::new(
+                delay_ms.clamp(MIN_DELAY_MS, max_delay_ms),
+                smoother_config,
+                info.sample_rate,
+            )
+        };
+        let feedback_param = |feedback: f32| {
+            SmoothedParam::new(
+                feedback.clamp(0.0, MAX_FEEDBACK),
+                smoother_config,
+                info.sample_rate,
+            )
+        };
+
+        Self {
+            lines: [
+                RingDelayLine::new(max_delay_frames),
+                RingDelayLine::new(max_delay_frames),
+            ],
+            max_delay_seconds,
+            silent_frames: usize::MAX,
+            delay_ms_l: delay_param(params.delay_ms_l),
+            delay_ms_r: delay_param(params.delay_ms_r),
+            feedback_l: feedback_param(params.feedback_l),
+            feedback_r: feedback_param(params.feedback_r),
+            cross_feedback: SmoothedParam::new(
+                params.cross_feedback.clamp(0.0, 1.0),
+                smoother_config,
+                info.sample_rate,
+            ),
+            mix: MixDSP::new(
+                params.mix,
+                params.fade_curve.clone(),
+                smoother_config,
+                info.sample_rate,
+            ),
+            mix_value: params.mix,
+            fade_curve: params.fade_curve.clone(),
+            ducking: SmoothedParam::new(
+                params.ducking.clamp(0.0, 1.0),
+                smoother_config,
+                info.sample_rate,
+            ),
+            duck_threshold_amp: duck_threshold_amp(params.duck_threshold),
+            duck_follower: EnvelopeFollower::new(params.duck_detector, info.sample_rate),
+            enable_declicker: Declicker::from_enabled(params.enabled),
+            frames_per_ms: info.sample_rate.get() as f32 / 1_000.0,
+            max_delay_ms,
+        }
+    }
+
+    /// Render the delayed (wet) signal into the output buffers.
+    fn process_wet(
+        &mut self,
+        in_l: &[f32],
+        in_r: &[f32],
+        out_l: &mut [f32],
+        out_r: &mut [f32],
+        amp_epsilon: f32,
+    ) {
+        let mut peak: f32 = 0.0;
+
+        for i in 0..out_l.len() {
+            let delay_l = self.delay_ms_l.next_smoothed() * self.frames_per_ms;
+            let delay_r = self.delay_ms_r.next_smoothed() * self.frames_per_ms;
+            let feedback_l = self.feedback_l.next_smoothed();
+            let feedback_r = self.feedback_r.next_smoothed();
+            let cross = self.cross_feedback.next_smoothed();
+            let ducking = self.ducking.next_smoothed();
+
+            // The echo is read before this frame is written, so a delay of
+            // one frame is the most recently written sample.
+            let echo_l = self.lines[0].read_at(delay_l.max(1.0) - 1.0);
+            let echo_r = self.lines[1].read_at(delay_r.max(1.0) - 1.0);
+
+            let write_l = in_l[i] + feedback_l * (echo_l + (echo_r - echo_l) * cross);
+            let write_r = in_r[i] + feedback_r * (echo_r + (echo_l - echo_r) * cross);
+
+            self.lines[0].write(write_l);
+            self.lines[1].write(write_r);
+
+            peak = peak.max(write_l.abs()).max(write_r.abs());
+
+            let duck_gain = if ducking > 0.0 {
+                let level = self.duck_follower.process(in_l[i].abs().max(in_r[i].abs()));
+                1.0 - ducking * (level / self.duck_threshold_amp).min(1.0)
+            } else {
+                1.0
+            };
+
+            out_l[i] = echo_l * duck_gain;
+            out_r[i] = echo_r * duck_gain;
+        }
+
+        if peak > amp_epsilon {
+            self.silent_frames = 0;
+        } else {
+            self.silent_frames = self.silent_frames.saturating_add(out_l.len());
+        }
+
+        self.delay_ms_l.settle();
+        self.delay_ms_r.settle();
+        self.feedback_l.settle();
+        self.feedback_r.settle();
+        self.cross_feedback.settle();
+        self.ducking.settle();
+    }
+
+    /// Returns `true` if everything in the delay buffers has died out.
+    fn is_idle(&self) -> bool {
+        self.silent_frames >= self.lines[0].capacity()
+    }
+
+    fn reset(&mut self) {
+        for line in self.lines.iter_mut() {
+            line.reset();
+        }
+        self.duck_follower.reset();
+        self.silent_frames = usize::MAX;
+    }
+}
+
+fn duck_threshold_amp(duck_threshold: Volume) -> f32 {
+    duck_threshold.amp().max(f32::EPSILON)
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<StereoDelayNode>() {
+            match patch {
+                StereoDelayNodePatch::DelayMsL(delay_ms) => {
+                    self.delay_ms_l
+                        .set_value(delay_ms.clamp(MIN_DELAY_MS, self.max_delay_ms));
+                }
+                StereoDelayNodePatch::DelayMsR(delay_ms) => {
+                    self.delay_ms_r
+                        .set_value(delay_ms.clamp(MIN_DELAY_MS, self.max_delay_ms));
+                }
+                StereoDelayNodePatch::FeedbackL(feedback) => {
+                    self.feedback_l.set_value(feedback.clamp(0.0, MAX_FEEDBACK));
+                }
+                StereoDelayNodePatch::FeedbackR(feedback) => {
+                    self.feedback_r.set_value(feedback.clamp(0.0, MAX_FEEDBACK));
+                }
+                StereoDelayNodePatch::CrossFeedback(cross) => {
+                    self.cross_feedback.set_value(cross.clamp(0.0, 1.0));
+                }
+                StereoDelayNodePatch::Mix(mix) => {
+                    self.mix_value = mix;
+                    self.mix.set_mix(mix, self.fade_curve.clone());
+                }
+                StereoDelayNodePatch::FadeCurve(fade_curve) => {
+                    self.mix.set_mix(self.mix_value, fade_curve.clone());
+                    self.fade_curve = fade_curve;
+                }
+                StereoDelayNodePatch::Ducking(ducking) => {
+                    self.ducking.set_value(ducking.clamp(0.0, 1.0));
+                }
+                StereoDelayNodePatch::DuckThreshold(duck_threshold) => {
+                    self.duck_threshold_amp = duck_threshold_amp(duck_threshold);
+                }
+                StereoDelayNodePatch::DuckDetector(patch) => {
+                    let mut detector = *self.duck_follower.config();
+                    detector.apply(patch);
+                    self.duck_follower.set_config(detector, info.sample_rate);
+                }
+                StereoDelayNodePatch::Enabled(enabled) => {
+                    // Tell the declicker to crossfade.
+                    self.enable_declicker
+                        .fade_to_enabled(enabled, &extra.declick_values);
+                }
+                StereoDelayNodePatch::SmoothSeconds(seconds) => {
+                    for param in [
+                        &mut self.delay_ms_l,
+                        &mut self.delay_ms_r,
+                        &mut self.feedback_l,
+                        &mut self.feedback_r,
+                        &mut self.cross_feedback,
+                        &mut self.ducking,
+                    ] {
+                        param.set_smooth_seconds(seconds, info.sample_rate);
+                    }
+                }
+            }
+        }
+        self.mix.set_global_wet(info.global_wet);
+
+        if self.enable_declicker.disabled() {
+            // Disabled. Bypass this node.
+            self.reset();
+
+            return ProcessStatus::Bypass;
+        }
+
+        let inputs_silent = info.in_silence_mask.all_channels_silent(2);
+
+        if inputs_silent && self.is_idle() && self.enable_declicker.has_settled() {
+            // Outputs will be silent, so no need to process.
+            self.delay_ms_l.reset_to_target();
+            self.delay_ms_r.reset_to_target();
+            self.feedback_l.reset_to_target();
+            self.feedback_r.reset_to_target();
+            self.cross_feedback.reset_to_target();
+            self.ducking.reset_to_target();
+            self.mix.reset_to_target();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        let (out_l, out_r) = buffers.outputs.split_first_mut().unwrap();
+        self.process_wet(
+            &buffers.inputs[0][..info.frames],
+            &buffers.inputs[1][..info.frames],
+            &mut out_l[..info.frames],
+            &mut out_r[0][..info.frames],
+            f32::EPSILON,
+        );
+
+        let [scratch_0, scratch_1] = extra.scratch_buffers.channels_mut::<2>();
+        self.mix.mix_dry_into_wet(
+            info.frames,
+            buffers.inputs,
+            buffers.outputs,
+            scratch_0,
+            scratch_1,
+        );
+
+        // Crossfade between the wet and dry signals to declick enabling/disabling.
+        self.enable_declicker.process_crossfade(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            &extra.declick_values,
+            DeclickFadeCurve::Linear,
+        );
+
+        ProcessStatus::OutputsModified
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        for param in [
+            &mut self.delay_ms_l,
+            &mut self.delay_ms_r,
+            &mut self.feedback_l,
+            &mut self.feedback_r,
+            &mut self.cross_feedback,
+            &mut self.ducking,
+        ] {
+            param.update_sample_rate(stream_info.sample_rate);
+        }
+        self.duck_follower
+            .set_config(*self.duck_follower.config(), stream_info.sample_rate);
+        self.mix.update_sample_rate(stream_info.sample_rate);
+        self.frames_per_ms = stream_info.sample_rate.get() as f32 / 1_000.0;
+
+        self.lines = [
+            RingDelayLine::from_seconds(self.max_delay_seconds, stream_info.sample_rate),
+            RingDelayLine::from_seconds(self.max_delay_seconds, stream_info.sample_rate),
+        ];
+        self.silent_frames = usize::MAX;
+    }
+
+    fn flush_idle_state(&mut self) {
+        self.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use super::*;
+
+    fn stream_info() -> StreamInfo {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+
+        StreamInfo {
+            sample_rate,
+            sample_rate_recip: 48_000.0f64.recip(),
+            prev_sample_rate: sample_rate,
+            ..Default::default()
+        }
+    }
+
+    /// The indices of the samples with a magnitude above `threshold`.
+    fn peaks(signal: &[f32], threshold: f32) -> Vec<usize> {
+        signal
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.abs() > threshold)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    #[test]
+    fn channels_echo_at_their_own_intervals() {
+        let stream_info = stream_info();
+        let frames_per_ms = 48;
+
+        let node = StereoDelayNode {
+            delay_ms_l: 10.0,
+            delay_ms_r: 15.0,
+            feedback_l: 0.5,
+            feedback_r: 0.5,
+            ..Default::default()
+        };
+        let mut processor = Processor::new(&node, &StereoDelayNodeConfig::default(), &stream_info);
+
+        // An impulse on both channels, processed in blocks.
+        let frames = 50 * frames_per_ms;
+        let mut input = vec![0.0; frames];
+        input[0] = 1.0;
+        let mut out_l = vec![0.0; frames];
+        let mut out_r = vec![0.0; frames];
+        for start in (0..frames).step_by(256) {
+            let end = (start + 256).min(frames);
+            processor.process_wet(
+                &input[start..end],
+                &input[start..end],
+                &mut out_l[start..end],
+                &mut out_r[start..end],
+                f32::EPSILON,
+            );
+        }
+
+        // Each channel repeats at its own interval, decaying by the feedback.
+        let delay_l = 10 * frames_per_ms;
+        let delay_r = 15 * frames_per_ms;
+        assert_eq!(
+            peaks(&out_l, 1e-6),
+            vec![delay_l, 2 * delay_l, 3 * delay_l, 4 * delay_l]
+        );
+        assert_eq!(peaks(&out_r, 1e-6), vec![delay_r, 2 * delay_r, 3 * delay_r]);
+        assert!((out_l[delay_l] - 1.0).abs() < 1e-6);
+        assert!((out_l[2 * delay_l] - 0.5).abs() < 1e-6);
+        assert!((out_r[2 * delay_r] - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cross_feedback_bounces_between_channels() {
+        let stream_info = stream_info();
+        let frames_per_ms = 48;
+
+        let node = StereoDelayNode {
+            delay_ms_l: 10.0,
+            delay_ms_r: 10.0,
+            feedback_l: 0.5,
+            feedback_r: 0.5,
+            cross_feedback: 1.0,
+            ..Default::default()
+        };
+        let mut processor = Processor::new(&node, &StereoDelayNodeConfig::default(), &stream_info);
+
+        // An impulse on the left channel only.
+        let frames = 35 * frames_per_ms;
+        let mut in_l = vec![0.0; frames];
+        in_l[0] = 1.0;
+        let in_r = vec![0.0; frames];
+        let mut out_l = vec![0.0; frames];
+        let mut out_r = vec![0.0; frames];
+        processor.process_wet(&in_l, &in_r, &mut out_l, &mut out_r, f32::EPSILON);
+
+        // The echoes alternate between the left and right channels.
+        let delay = 10 * frames_per_ms;
+        assert_eq!(peaks(&out_l, 1e-6), vec![delay, 3 * delay]);
+        assert_eq!(peaks(&out_r, 1e-6), vec![2 * delay]);
+    }
+
+    #[test]
+    fn ducking_holds_echoes_back_while_input_is_present() {
+        let stream_info = stream_info();
+        let frames_per_ms = 48;
+
+        let render = |ducking: f32| {
+            let node = StereoDelayNode {
+                delay_ms_l: 20.0,
+                delay_ms_r: 20.0,
+                feedback_l: 0.9,
+                feedback_r: 0.9,
+                ducking,
+                duck_detector: EnvelopeFollowerConfig {
+                    attack_secs: 0.005,
+                    release_secs: 0.02,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            let mut processor =
+                Processor::new(&node, &StereoDelayNodeConfig::default(), &stream_info);
+
+            // A sustained input for 100ms, followed by silence.
+            let frames = 300 * frames_per_ms;
+            let input: Vec<f32> = (0..frames)
+                .map(|i| if i < 100 * frames_per_ms { 0.5 } else { 0.0 })
+                .collect();
+            let mut out_l = vec![0.0; frames];
+            let mut out_r = vec![0.0; frames];
+            for start in (0..frames).step_by(256) {
+                let end = (start + 256).min(frames);
+                processor.process_wet(
+                    &input[start..end],
+                    &input[start..end],
+                    &mut out_l[start..end],
+                    &mut out_r[start..end],
+                    f32::EPSILON,
+                );
+            }
+
+            out_l
+        };
+
+        let dry = render(0.0);
+        let ducked = render(1.0);
+
+        let peak = |signal: &[f32], range_ms: core::ops::Range<usize>| {
+            signal[range_ms.start * frames_per_ms..range_ms.end * frames_per_ms]
+                .iter()
+                .fold(0.0f32, |peak, s| peak.max(s.abs()))
+        };
+
+        // The echoes are held back while the input is present.
+        assert!(peak(&dry, 50..100) > 0.5);
+        assert!(peak(&ducked, 50..100) < 1e-3);
+
+        // Once the input stops, the echoes rise to their normal level.
+        for i in 250 * frames_per_ms..300 * frames_per_ms {
+            assert!((ducked[i] - dry[i]).abs() <= dry[i].abs() * 0.01);
+        }
+        assert!(peak(&ducked, 250..300) > 0.1);
+    }
+}
@@ -0,0 +1,261 @@
+//! A node that rotates the stereo field.
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::filter::smoothing_filter::DEFAULT_SMOOTH_SECONDS,
+    event::ProcEvents,
+    mask::MaskType,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+    param::smoother::{SmoothedParam, SmootherConfig},
+    StreamInfo,
+};
+
+#[cfg(not(feature = "std"))]
+use num_traits::Float;
+
+/// A node that rotates the stereo field by an angle (Stereo input and
+/// output).
+///
+/// This is the classic Blumlein rotation matrix. Rotating the left and right
+/// channels by an angle is equivalent to rotating the mid and side channels
+/// by the same angle, so small angles shift the stereo image to one side
+/// while keeping its width, and a rotation of `90.0` degrees turns the mid
+/// content into side content and vice versa. This is useful for subtle
+/// stereo placement and for correcting a lopsided recording.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StereoRotateNode {
+    /// The angle of rotation in degrees. Positive values rotate the stereo
+    /// image towards the right, and negative values towards the left.
+    ///
+    /// By default this is set to `0.0` (no rotation).
+    pub angle_degrees: f32,
+    /// The time in seconds of the internal smoothing filter for the angle.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub smooth_seconds: f32,
+}
+
+impl Default for StereoRotateNode {
+    fn default() -> Self {
+        Self {
+            angle_degrees: 0.0,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl StereoRotateNode {
+    /// Construct a new `StereoRotateNode` from the given angle in degrees.
+    pub const fn from_angle_degrees(angle_degrees: f32) -> Self {
+        Self {
+            angle_degrees,
+            smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
+
+impl AudioNode for StereoRotateNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("stereo_rotate")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::STEREO,
+                num_outputs: ChannelCount::STEREO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        let mut processor = Processor::new(*self, cx.stream_info.sample_rate);
+        processor
+            .angle
+            .set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct Processor {
+    angle: SmoothedParam,
+}
+
+impl Processor {
+    fn new(params: StereoRotateNode, sample_rate: NonZeroU32) -> Self {
+        Self {
+            angle: SmoothedParam::new(
+                params.angle_degrees.to_radians(),
+                SmootherConfig {
+                    smooth_seconds: params.smooth_seconds,
+                    ..Default::default()
+                },
+                sample_rate,
+            ),
+        }
+    }
+
+    /// Rotate a block of stereo frames.
+    ///
+    /// Returns `true` if the angle was still being smoothed.
+    fn rotate(&mut self, in_l: &[f32], in_r: &[f32], out_l: &mut [f32], out_r: &mut [f32]) -> bool {
+        if self.angle.has_settled() {
+            let (sin, cos) = self.angle.target_value().sin_cos();
+
+            for i in 0..out_l.len() {
+                out_l[i] = in_l[i] * cos - in_r[i] * sin;
+                out_r[i] = in_l[i] * sin + in_r[i] * cos;
+            }
+
+            false
+        } else {
+            for i in 0..out_l.len() {
+                let (sin, cos) = self.angle.next_smoothed().sin_cos();
+
+                out_l[i] = in_l[i] * cos - in_r[i] * sin;
+                out_r[i] = in_l[i] * sin + in_r[i] * cos;
+            }
+
+            self.angle.settle();
+
+            true
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<StereoRotateNode>() {
+            match patch {
+                StereoRotateNodePatch::AngleDegrees(angle_degrees) => {
+                    self.angle.set_value(angle_degrees.to_radians());
+                }
+                StereoRotateNodePatch::SmoothSeconds(seconds) => {
+                    self.angle.set_smooth_seconds(seconds, info.sample_rate);
+                }
+            }
+
+            if info.prev_output_was_silent {
+                // Previous block was silent, so no need to smooth.
+                self.angle.reset_to_target();
+            }
+        }
+
+        if info.in_silence_mask.all_channels_silent(2) {
+            self.angle.reset_to_target();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        if self.angle.has_settled() && self.angle.target_value() == 0.0 {
+            return ProcessStatus::Bypass;
+        }
+
+        let in_l = &buffers.inputs[0][..info.frames];
+        let in_r = &buffers.inputs[1][..info.frames];
+        let (out_l, out_r) = buffers.outputs.split_first_mut().unwrap();
+        let out_l = &mut out_l[..info.frames];
+        let out_r = &mut out_r[0][..info.frames];
+
+        self.rotate(in_l, in_r, out_l, out_r);
+
+        if info.in_silence_mask.any_channel_silent(2) {
+            // One silent input channel still leaks into both outputs.
+            ProcessStatus::OutputsModified
+        } else {
+            ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(info.in_silence_mask))
+        }
+    }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.angle.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQRT_2: f32 = core::f32::consts::SQRT_2;
+
+    /// Rotate a single stereo frame and return the output as mid/side.
+    fn rotate_mid_side(processor: &mut Processor, l: f32, r: f32) -> (f32, f32) {
+        let mut out_l = [0.0];
+        let mut out_r = [0.0];
+        processor.rotate(&[l], &[r], &mut out_l, &mut out_r);
+
+        (
+            (out_l[0] + out_r[0]) / SQRT_2,
+            (out_l[0] - out_r[0]) / SQRT_2,
+        )
+    }
+
+    #[test]
+    fn quarter_turn_swaps_mid_and_side() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+
+        // Zero degrees is an identity.
+        let mut processor = Processor::new(StereoRotateNode::default(), sample_rate);
+        let in_l = [0.3, -0.5, 0.9, 0.0];
+        let in_r = [0.1, 0.7, -0.2, 1.0];
+        let mut out_l = [0.0; 4];
+        let mut out_r = [0.0; 4];
+        processor.rotate(&in_l, &in_r, &mut out_l, &mut out_r);
+        assert_eq!(out_l, in_l);
+        assert_eq!(out_r, in_r);
+
+        let mut processor = Processor::new(StereoRotateNode::from_angle_degrees(90.0), sample_rate);
+
+        // Pure mid content becomes pure side content.
+        let (mid, side) = rotate_mid_side(&mut processor, 1.0, 1.0);
+        assert!(mid.abs() < 1e-6);
+        assert!((side.abs() - SQRT_2).abs() < 1e-6);
+
+        // Pure side content becomes pure mid content.
+        let (mid, side) = rotate_mid_side(&mut processor, 1.0, -1.0);
+        assert!((mid.abs() - SQRT_2).abs() < 1e-6);
+        assert!(side.abs() < 1e-6);
+    }
+
+    #[test]
+    fn angle_change_is_smoothed() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = Processor::new(StereoRotateNode::default(), sample_rate);
+
+        processor.angle.set_value(90.0f32.to_radians());
+
+        let input = [1.0; 64];
+        let mut out_l = Vec::new();
+        for _ in 0..100 {
+            let mut block_l = [0.0; 64];
+            let mut block_r = [0.0; 64];
+            processor.rotate(&input, &input, &mut block_l, &mut block_r);
+            out_l.extend_from_slice(&block_l);
+        }
+
+        // The left channel of a centered signal moves gradually to the
+        // inverted right channel.
+        assert!((out_l[0] - 1.0).abs() < 0.01);
+        for w in out_l.windows(2) {
+            assert!(w[1] <= w[0] && w[0] - w[1] < 0.01);
+        }
+        assert!((out_l.last().unwrap() + 1.0).abs() < 0.001);
+    }
+}
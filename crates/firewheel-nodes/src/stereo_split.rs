@@ -0,0 +1,117 @@
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount},
+    event::ProcEvents,
+    mask::{MaskType, SilenceMask},
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
+        ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+
+/// A node that splits a stereo signal into two separate mono signals
+///
+/// Output `0` is the left channel and output `1` is the right channel. Each
+/// output can then be routed to a different mono node to process the
+/// channels independently, and then routed back into the two inputs of
+/// another node to recombine them.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StereoSplitNode;
+
+impl AudioNode for StereoSplitNode {
+    type Configuration = EmptyConfig;
+
+    fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("stereo_split")
+            .channel_config(ChannelConfig {
+                num_inputs: ChannelCount::STEREO,
+                num_outputs: ChannelCount::STEREO,
+            })
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        _cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        StereoSplitProcessor
+    }
+}
+
+struct StereoSplitProcessor;
+
+impl StereoSplitProcessor {
+    fn split(inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize, mask: SilenceMask) {
+        for (ch_i, (out_ch, in_ch)) in outputs.iter_mut().zip(inputs.iter()).enumerate() {
+            if mask.is_channel_silent(ch_i) {
+                out_ch[..frames].fill(0.0);
+            } else {
+                out_ch[..frames].copy_from_slice(&in_ch[..frames]);
+            }
+        }
+    }
+}
+
+impl AudioNodeProcessor for StereoSplitProcessor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        _events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        if info.in_silence_mask.all_channels_silent(2)
+            || buffers.inputs.len() < 2
+            || buffers.outputs.len() < 2
+        {
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        Self::split(
+            buffers.inputs,
+            buffers.outputs,
+            info.frames,
+            info.in_silence_mask,
+        );
+
+        ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(info.in_silence_mask))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outputs_match_input_channels() {
+        let left: Vec<f32> = (0..256).map(|i| (i as f32 * 0.05).sin()).collect();
+        let right: Vec<f32> = (0..256).map(|i| -(i as f32 * 0.013).cos()).collect();
+
+        let mut out_left = vec![1.0; 256];
+        let mut out_right = vec![1.0; 256];
+
+        StereoSplitProcessor::split(
+            &[&left, &right],
+            &mut [&mut out_left, &mut out_right],
+            256,
+            SilenceMask::NONE_SILENT,
+        );
+
+        assert_eq!(out_left, left);
+        assert_eq!(out_right, right);
+
+        // A silent input channel produces silence on its output only.
+        StereoSplitProcessor::split(
+            &[&left, &right],
+            &mut [&mut out_left, &mut out_right],
+            256,
+            SilenceMask::MONO_SILENT,
+        );
+
+        assert!(out_left.iter().all(|&s| s == 0.0));
+        assert_eq!(out_right, right);
+    }
+}
@@ -1,18 +1,66 @@
+use core::num::NonZeroU32;
+
 use firewheel_core::{
     channel_config::{ChannelConfig, ChannelCount},
+    diff::{Diff, Patch},
+    dsp::declick::DeclickValues,
     event::ProcEvents,
     node::{
         AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, EmptyConfig,
-        ProcBuffers, ProcExtra, ProcInfo, ProcessStatus,
+        ProcBuffers, ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
     },
+    StreamInfo,
 };
 
+/// How a [`StereoToMonoNode`] combines the two channels.
+#[derive(Default, Diff, Patch, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StereoToMonoMode {
+    /// The average of the two channels (their sum at -6 dB). This keeps a
+    /// signal that is the same in both channels at the same level.
+    #[default]
+    Average,
+    /// The sum of the two channels. This keeps a signal that is only in
+    /// one channel at the same level.
+    Sum,
+}
+
+impl StereoToMonoMode {
+    #[inline]
+    fn mix(self, left: f32, right: f32) -> f32 {
+        match self {
+            Self::Average => (left + right) * 0.5,
+            Self::Sum => left + right,
+        }
+    }
+}
+
 /// A node that converts a stereo signal into a mono signal
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct StereoToMonoNode;
+pub struct StereoToMonoNode {
+    /// How the two channels are combined.
+    ///
+    /// By default this is set to [`StereoToMonoMode::Average`].
+    pub mode: StereoToMonoMode,
+    /// The time in seconds to crossfade from the old mode to the new one
+    /// when [`StereoToMonoNode::mode`] changes, which avoids a click.
+    ///
+    /// By default this is set to `0.01` (10ms).
+    pub crossfade_seconds: f32,
+}
+
+impl Default for StereoToMonoNode {
+    fn default() -> Self {
+        Self {
+            mode: StereoToMonoMode::Average,
+            crossfade_seconds: DeclickValues::DEFAULT_FADE_SECONDS,
+        }
+    }
+}
 
 impl AudioNode for StereoToMonoNode {
     type Configuration = EmptyConfig;
@@ -29,36 +77,146 @@ impl AudioNode for StereoToMonoNode {
     fn construct_processor(
         &self,
         _config: &Self::Configuration,
-        _cx: ConstructProcessorContext,
+        cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        StereoToMonoProcessor
+        StereoToMonoProcessor::new(*self, cx.stream_info.sample_rate)
     }
 }
 
-struct StereoToMonoProcessor;
+struct StereoToMonoProcessor {
+    params: StereoToMonoNode,
+    sample_rate: NonZeroU32,
+
+    /// The mode being crossfaded away from.
+    prev_mode: StereoToMonoMode,
+    /// The progress of the crossfade from `prev_mode` to the current mode,
+    /// in the range `[0.0, 1.0]`.
+    fade: f32,
+    fade_step: f32,
+}
+
+impl StereoToMonoProcessor {
+    fn new(params: StereoToMonoNode, sample_rate: NonZeroU32) -> Self {
+        Self {
+            params,
+            sample_rate,
+            prev_mode: params.mode,
+            fade: 1.0,
+            fade_step: 0.0,
+        }
+    }
+
+    fn set_mode(&mut self, mode: StereoToMonoMode) {
+        if mode == self.params.mode {
+            return;
+        }
+
+        let frames = self.params.crossfade_seconds.max(0.0) * self.sample_rate.get() as f32;
+
+        // If switching back to the mode that was fading out, then reverse the
+        // crossfade from where it is instead of jumping.
+        self.fade = if self.fade < 1.0 && mode == self.prev_mode {
+            1.0 - self.fade
+        } else {
+            0.0
+        };
+        self.prev_mode = self.params.mode;
+        self.params.mode = mode;
+
+        if frames < 1.0 {
+            self.fade = 1.0;
+        } else {
+            self.fade_step = frames.recip();
+        }
+    }
+
+    fn mix(&mut self, left: &[f32], right: &[f32], out: &mut [f32]) {
+        let mode = self.params.mode;
+
+        if self.fade >= 1.0 {
+            for (out_s, (&l, &r)) in out.iter_mut().zip(left.iter().zip(right.iter())) {
+                *out_s = mode.mix(l, r);
+            }
+            return;
+        }
+
+        for (out_s, (&l, &r)) in out.iter_mut().zip(left.iter().zip(right.iter())) {
+            self.fade = (self.fade + self.fade_step).min(1.0);
+
+            let old = self.prev_mode.mix(l, r);
+            *out_s = old + (mode.mix(l, r) - old) * self.fade;
+        }
+    }
+}
 
 impl AudioNodeProcessor for StereoToMonoProcessor {
     fn process(
         &mut self,
         info: &ProcInfo,
         buffers: ProcBuffers,
-        _events: &mut ProcEvents,
+        events: &mut ProcEvents,
         _extra: &mut ProcExtra,
     ) -> ProcessStatus {
+        for patch in events.drain_patches::<StereoToMonoNode>() {
+            match patch {
+                StereoToMonoNodePatch::Mode(mode) => self.set_mode(mode),
+                StereoToMonoNodePatch::CrossfadeSeconds(seconds) => {
+                    self.params.crossfade_seconds = seconds;
+                }
+            }
+        }
+
         if info.in_silence_mask.all_channels_silent(2)
             || buffers.inputs.len() < 2
             || buffers.outputs.is_empty()
         {
+            // There is nothing to crossfade.
+            self.fade = 1.0;
+
             return ProcessStatus::ClearAllOutputs;
         }
 
-        for (out_s, (&in1, &in2)) in buffers.outputs[0]
-            .iter_mut()
-            .zip(buffers.inputs[0].iter().zip(buffers.inputs[1].iter()))
-        {
-            *out_s = (in1 + in2) * 0.5;
-        }
+        self.mix(
+            &buffers.inputs[0][..info.frames],
+            &buffers.inputs[1][..info.frames],
+            &mut buffers.outputs[0][..info.frames],
+        );
 
         ProcessStatus::OutputsModified
     }
+
+    fn new_stream(&mut self, stream_info: &StreamInfo, _context: &mut ProcStreamCtx) {
+        self.sample_rate = stream_info.sample_rate;
+        self.fade = 1.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mode_switch_crossfades() {
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+        let mut processor = StereoToMonoProcessor::new(StereoToMonoNode::default(), sample_rate);
+
+        let left = vec![0.5; 1_024];
+        let right = vec![0.5; 1_024];
+        let mut out = vec![0.0; 1_024];
+
+        processor.mix(&left, &right, &mut out);
+        assert!(out.iter().all(|&s| s == 0.5));
+
+        // A 10ms crossfade at 48kHz.
+        processor.set_mode(StereoToMonoMode::Sum);
+        processor.mix(&left, &right, &mut out);
+        let fade_frames = 480;
+
+        // The level rises smoothly instead of jumping, and then holds the
+        // sum.
+        assert!(out[0] < 0.51);
+        assert!(out.windows(2).all(|w| w[1] >= w[0] && w[1] - w[0] < 0.01));
+        assert!(out[fade_frames - 2] < 1.0);
+        assert!(out[fade_frames..].iter().all(|&s| s == 1.0));
+    }
 }
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use realfft::{num_complex::Complex, ComplexToReal, RealFftPlanner, RealToComplex};
+
+/// The number of analysis frames that overlap each frame of audio.
+pub(crate) const OVERLAP: usize = 4;
+
+/// The state of a single channel.
+struct Channel {
+    /// The last `fft_size` frames of input.
+    input: Vec<f32>,
+    /// The resynthesized output waiting to be played, which the following
+    /// analysis frames are added on to.
+    output: Vec<f32>,
+}
+
+/// A short-time Fourier transform which analyzes overlapping frames of its
+/// input and resynthesizes them with overlap-add.
+///
+/// The output lags `fft_size` frames behind the input.
+pub(crate) struct Stft {
+    channels: Vec<Channel>,
+    /// The spectrum of each channel on the current analysis frame.
+    spectra: Vec<Vec<Complex<f32>>>,
+
+    forward: Arc<dyn RealToComplex<f32>>,
+    inverse: Arc<dyn ComplexToReal<f32>>,
+    /// A square root Hann window, applied both before analysis and after
+    /// resynthesis.
+    window: Vec<f32>,
+    frame: Vec<f32>,
+    scratch: Vec<Complex<f32>>,
+
+    /// The number of frames that have been written since the last analysis
+    /// frame.
+    hop_pos: usize,
+}
+
+impl Stft {
+    pub fn new(fft_size: usize, num_channels: usize) -> Self {
+        let mut planner = RealFftPlanner::<f32>::new();
+        let forward = planner.plan_fft_forward(fft_size);
+        let inverse = planner.plan_fft_inverse(fft_size);
+        let num_bins = forward.complex_len();
+        let scratch_len = forward.get_scratch_len().max(inverse.get_scratch_len());
+
+        let window = (0..fft_size)
+            .map(|i| {
+                let phase = i as f32 / fft_size as f32;
+                (0.5 - 0.5 * (core::f32::consts::TAU * phase).cos()).sqrt()
+            })
+            .collect();
+
+        Self {
+            channels: (0..num_channels)
+                .map(|_| Channel {
+                    input: vec![0.0; fft_size],
+                    output: vec![0.0; fft_size],
+                })
+                .collect(),
+            spectra: vec![vec![Complex::default(); num_bins]; num_channels],
+            forward,
+            inverse,
+            window,
+            frame: vec![0.0; fft_size],
+            scratch: vec![Complex::default(); scratch_len],
+            hop_pos: 0,
+        }
+    }
+
+    pub fn fft_size(&self) -> usize {
+        self.window.len()
+    }
+
+    pub fn hop_size(&self) -> usize {
+        self.fft_size() / OVERLAP
+    }
+
+    pub fn num_bins(&self) -> usize {
+        self.forward.complex_len()
+    }
+
+    /// Run a block of frames through the transform.
+    ///
+    /// `process_spectra` is called once per analysis frame with the spectrum
+    /// of each channel, which it modifies in place before they are
+    /// resynthesized.
+    pub fn process(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+        mut process_spectra: impl FnMut(&mut [Vec<Complex<f32>>]),
+    ) {
+        let hop_size = self.hop_size();
+        let fft_size = self.fft_size();
+
+        let mut frame = 0;
+        while frame < frames {
+            let block_frames = (hop_size - self.hop_pos).min(frames - frame);
+            let range = frame..frame + block_frames;
+            let hop_range = self.hop_pos..self.hop_pos + block_frames;
+
+            for ((ch, in_ch), out_ch) in self
+                .channels
+                .iter_mut()
+                .zip(inputs.iter())
+                .zip(outputs.iter_mut())
+            {
+                let write_start = fft_size - hop_size;
+                ch.input[write_start + hop_range.start..write_start + hop_range.end]
+                    .copy_from_slice(&in_ch[range.clone()]);
+                out_ch[range.clone()].copy_from_slice(&ch.output[hop_range.clone()]);
+            }
+
+            self.hop_pos += block_frames;
+            frame += block_frames;
+
+            if self.hop_pos == hop_size {
+                self.hop_pos = 0;
+                self.process_frame(&mut process_spectra);
+            }
+        }
+    }
+
+    /// Analyze the last `fft_size` frames of input, let `process_spectra`
+    /// modify them, and add the result on to the output.
+    fn process_frame(&mut self, process_spectra: &mut impl FnMut(&mut [Vec<Complex<f32>>])) {
+        let fft_size = self.fft_size();
+        let hop_size = self.hop_size();
+
+        // The overlapping windows sum to `OVERLAP / 2`, and the inverse FFT
+        // is unnormalized.
+        let scale = 2.0 / (OVERLAP as f32 * fft_size as f32);
+
+        for (ch, spectrum) in self.channels.iter().zip(self.spectra.iter_mut()) {
+            for ((f, s), w) in self
+                .frame
+                .iter_mut()
+                .zip(ch.input.iter())
+                .zip(self.window.iter())
+            {
+                *f = s * w;
+            }
+
+            // The buffers always have the correct length, so this cannot fail.
+            let _ = self
+                .forward
+                .process_with_scratch(&mut self.frame, spectrum, &mut self.scratch);
+        }
+
+        (process_spectra)(&mut self.spectra);
+
+        for (ch, spectrum) in self.channels.iter_mut().zip(self.spectra.iter_mut()) {
+            // The imaginary parts of the DC and Nyquist bins are always zero
+            // for a real signal, and must be for the inverse FFT.
+            spectrum[0].im = 0.0;
+            spectrum.last_mut().unwrap().im = 0.0;
+
+            let _ = self
+                .inverse
+                .process_with_scratch(spectrum, &mut self.frame, &mut self.scratch);
+
+            ch.output.copy_within(hop_size.., 0);
+            ch.output[fft_size - hop_size..].fill(0.0);
+            for ((out, f), w) in ch
+                .output
+                .iter_mut()
+                .zip(self.frame.iter())
+                .zip(self.window.iter())
+            {
+                *out += f * w * scale;
+            }
+
+            ch.input.copy_within(hop_size.., 0);
+        }
+    }
+}
@@ -8,6 +8,7 @@ use firewheel_core::{
         declick::{DeclickFadeCurve, Declicker},
         filter::{
             butterworth::Q_BUTTERWORTH_ORD2,
+            half_band::{Downsampler2x, HalfBandCoeffs, Upsampler2x},
             smoothing_filter::DEFAULT_SMOOTH_SECONDS,
             svf::{SvfCoeff, SvfCoeffSimd, SvfStateSimd},
         },
@@ -63,6 +64,16 @@ pub struct SvfNodeConfig {
     /// It is generally not recommended to increase this range
     /// unless you know what you are doing.
     pub gain_db_range: Range<f32>,
+
+    /// If `true`, then the filter runs at twice the sample rate.
+    ///
+    /// Filters near the Nyquist frequency are squashed by the bilinear
+    /// transform, which is most audible with high-gain bell and shelf
+    /// filters. Oversampling keeps their shape closer to the analog filter
+    /// at the cost of roughly twice the CPU usage.
+    ///
+    /// By default this is set to `false`.
+    pub oversample: bool,
 }
 
 impl Default for SvfNodeConfig {
@@ -71,6 +82,7 @@ impl Default for SvfNodeConfig {
             freq_range: DEFAULT_MIN_HZ..DEFAULT_MAX_HZ,
             q_range: DEFAULT_MIN_Q..DEFAULT_MAX_Q,
             gain_db_range: DEFAULT_MIN_GAIN_DB..DEFAULT_MAX_GAIN_DB,
+            oversample: false,
         }
     }
 }
@@ -494,87 +506,270 @@ impl<const CHANNELS: usize> AudioNode for SvfNode<CHANNELS> {
         config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        let cutoff_hz = self
+        let mut processor = Processor::new(self, config, cx.stream_info);
+        for param in [
+            &mut processor.cutoff_hz,
+            &mut processor.q_factor,
+            &mut processor.gain,
+        ] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+    }
+}
+
+struct Processor<const CHANNELS: usize> {
+    filter_0: SvfStateSimd<CHANNELS>,
+    filter_1: SvfStateSimd<CHANNELS>,
+    num_filters: usize,
+
+    filter_0_coeff: SvfCoeffSimd<CHANNELS>,
+    filter_1_coeff: SvfCoeffSimd<CHANNELS>,
+
+    filter_type: SvfType,
+    cutoff_hz: SmoothedParam,
+    q_factor: SmoothedParam,
+    gain: SmoothedParam,
+
+    enable_declicker: Declicker,
+
+    freq_range: Range<f32>,
+    q_range: Range<f32>,
+    gain_range: Range<f32>,
+    coeff_update_mask: CoeffUpdateMask,
+
+    oversample: bool,
+    half_band_coeffs: HalfBandCoeffs,
+    upsampler: Upsampler2x<CHANNELS>,
+    downsampler: Downsampler2x<CHANNELS>,
+
+    /// The filter of the previous filter type, which is crossfaded out
+    /// after the filter type changes.
+    prev_type_filter_0: SvfStateSimd<CHANNELS>,
+    prev_type_filter_1: SvfStateSimd<CHANNELS>,
+    prev_type_num_filters: usize,
+    prev_type_filter_0_coeff: SvfCoeffSimd<CHANNELS>,
+    prev_type_filter_1_coeff: SvfCoeffSimd<CHANNELS>,
+    prev_type_upsampler: Upsampler2x<CHANNELS>,
+    prev_type_downsampler: Downsampler2x<CHANNELS>,
+    type_fade_frames_left: usize,
+    declick_frames: usize,
+}
+
+impl<const CHANNELS: usize> Processor<CHANNELS> {
+    fn new(node: &SvfNode<CHANNELS>, config: &SvfNodeConfig, stream_info: &StreamInfo) -> Self {
+        let cutoff_hz = node
             .cutoff_hz
             .clamp(config.freq_range.start, config.freq_range.end);
-        let q_factor = self
+        let q_factor = node
             .q_factor
             .clamp(config.q_range.start, config.q_range.end);
 
         let min_gain = db_to_amp(config.gain_db_range.start);
         let max_gain = db_to_amp(config.gain_db_range.end);
-        let mut gain = self.gain.amp().clamp(min_gain, max_gain);
+        let mut gain = node.gain.amp().clamp(min_gain, max_gain);
         if gain > 0.99999 && gain < 1.00001 {
             gain = 1.0;
         }
 
-        let mut new_self = Processor {
+        let smoother_config = SmootherConfig {
+            smooth_seconds: node.smooth_seconds,
+            ..Default::default()
+        };
+
+        let mut new_self = Self {
             filter_0: SvfStateSimd::<CHANNELS>::default(),
             filter_1: SvfStateSimd::<CHANNELS>::default(),
             num_filters: 0,
             filter_0_coeff: SvfCoeffSimd::<CHANNELS>::default(),
             filter_1_coeff: SvfCoeffSimd::<CHANNELS>::default(),
-            filter_type: self.filter_type,
-            cutoff_hz: SmoothedParam::new(
-                cutoff_hz,
-                SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
-                    ..Default::default()
-                },
-                cx.stream_info.sample_rate,
-            ),
-            q_factor: SmoothedParam::new(
-                q_factor,
-                SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
-                    ..Default::default()
-                },
-                cx.stream_info.sample_rate,
-            ),
-            gain: SmoothedParam::new(
-                gain,
-                SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
-                    ..Default::default()
-                },
-                cx.stream_info.sample_rate,
-            ),
-            enable_declicker: Declicker::from_enabled(self.enabled),
+            filter_type: node.filter_type,
+            cutoff_hz: SmoothedParam::new(cutoff_hz, smoother_config, stream_info.sample_rate),
+            q_factor: SmoothedParam::new(q_factor, smoother_config, stream_info.sample_rate),
+            gain: SmoothedParam::new(gain, smoother_config, stream_info.sample_rate),
+            enable_declicker: Declicker::from_enabled(node.enabled),
             freq_range: config.freq_range.clone(),
             q_range: config.q_range.clone(),
             gain_range: min_gain..max_gain,
-            coeff_update_mask: self.coeff_update_factor.mask(),
+            coeff_update_mask: node.coeff_update_factor.mask(),
+            oversample: config.oversample,
+            half_band_coeffs: HalfBandCoeffs::default(),
+            upsampler: Upsampler2x::default(),
+            downsampler: Downsampler2x::default(),
+            prev_type_filter_0: SvfStateSimd::<CHANNELS>::default(),
+            prev_type_filter_1: SvfStateSimd::<CHANNELS>::default(),
+            prev_type_num_filters: 0,
+            prev_type_filter_0_coeff: SvfCoeffSimd::<CHANNELS>::default(),
+            prev_type_filter_1_coeff: SvfCoeffSimd::<CHANNELS>::default(),
+            prev_type_upsampler: Upsampler2x::default(),
+            prev_type_downsampler: Downsampler2x::default(),
+            type_fade_frames_left: 0,
+            declick_frames: stream_info.declick_frames.get() as usize,
         };
 
-        new_self.calc_coefficients(cx.stream_info.sample_rate_recip as f32);
+        new_self.calc_coefficients(stream_info.sample_rate_recip as f32);
 
         new_self
     }
-}
 
-struct Processor<const CHANNELS: usize> {
-    filter_0: SvfStateSimd<CHANNELS>,
-    filter_1: SvfStateSimd<CHANNELS>,
-    num_filters: usize,
+    /// Switch to a new filter type, keeping the old filter running so it
+    /// can be crossfaded out to avoid a click.
+    fn set_filter_type(&mut self, filter_type: SvfType) {
+        if filter_type == self.filter_type {
+            return;
+        }
 
-    filter_0_coeff: SvfCoeffSimd<CHANNELS>,
-    filter_1_coeff: SvfCoeffSimd<CHANNELS>,
+        self.prev_type_filter_0 = self.filter_0;
+        self.prev_type_filter_1 = self.filter_1;
+        self.prev_type_num_filters = self.num_filters;
+        self.prev_type_filter_0_coeff = self.filter_0_coeff;
+        self.prev_type_filter_1_coeff = self.filter_1_coeff;
+        self.prev_type_upsampler = self.upsampler;
+        self.prev_type_downsampler = self.downsampler;
+        self.type_fade_frames_left = self.declick_frames;
 
-    filter_type: SvfType,
-    cutoff_hz: SmoothedParam,
-    q_factor: SmoothedParam,
-    gain: SmoothedParam,
+        self.filter_type = filter_type;
+    }
 
-    enable_declicker: Declicker,
+    /// Crossfade the output of the previous filter type into the output of
+    /// the current filter type.
+    fn process_type_crossfade(
+        &mut self,
+        inputs: &[&[f32]],
+        outputs: &mut [&mut [f32]],
+        frames: usize,
+    ) {
+        let fade_frames = frames.min(self.type_fade_frames_left);
+        let step = (self.declick_frames as f32).recip();
 
-    freq_range: Range<f32>,
-    q_range: Range<f32>,
-    gain_range: Range<f32>,
-    coeff_update_mask: CoeffUpdateMask,
-}
+        for i in 0..fade_frames {
+            let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| inputs[ch_i][i]);
+
+            let prev = self.tick_prev_type(s);
+
+            let new_gain = 1.0 - ((self.type_fade_frames_left - i) as f32 * step);
+
+            for (out_ch, &prev_s) in outputs.iter_mut().zip(prev.iter()) {
+                out_ch[i] = prev_s + ((out_ch[i] - prev_s) * new_gain);
+            }
+        }
+
+        self.type_fade_frames_left -= fade_frames;
+    }
+
+    /// Process with constant filter coefficients.
+    fn process_static(&mut self, inputs: &[&[f32]], outputs: &mut [&mut [f32]], frames: usize) {
+        assert!(inputs.len() == CHANNELS);
+        assert!(outputs.len() == CHANNELS);
+        for ch in inputs.iter() {
+            assert!(ch.len() >= frames);
+        }
+        for ch in outputs.iter() {
+            assert!(ch.len() >= frames);
+        }
+
+        if self.num_filters == 1 {
+            for i in 0..frames {
+                let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
+                    // Safety: These bounds have been checked above.
+                    unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
+                });
+
+                let out = self.tick::<1>(s);
+
+                for ch_i in 0..CHANNELS {
+                    // Safety: These bounds have been checked above.
+                    unsafe {
+                        *outputs.get_unchecked_mut(ch_i).get_unchecked_mut(i) = out[ch_i];
+                    }
+                }
+            }
+        } else {
+            for i in 0..frames {
+                let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
+                    // Safety: These bounds have been checked above.
+                    unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
+                });
+
+                let out = self.tick::<2>(s);
+
+                for ch_i in 0..CHANNELS {
+                    // Safety: These bounds have been checked above.
+                    unsafe {
+                        *outputs.get_unchecked_mut(ch_i).get_unchecked_mut(i) = out[ch_i];
+                    }
+                }
+            }
+        }
+    }
+
+    /// The reciprocal of the sample rate the filters run at.
+    fn filter_sample_rate_recip(&self, sample_rate_recip: f32) -> f32 {
+        if self.oversample {
+            sample_rate_recip * 0.5
+        } else {
+            sample_rate_recip
+        }
+    }
+
+    /// Run one frame through the filters, oversampling if enabled.
+    #[inline(always)]
+    fn tick<const NUM_FILTERS: usize>(&mut self, s: [f32; CHANNELS]) -> [f32; CHANNELS] {
+        if !self.oversample {
+            return run_filters::<CHANNELS, NUM_FILTERS>(
+                s,
+                [&mut self.filter_0, &mut self.filter_1],
+                [&self.filter_0_coeff, &self.filter_1_coeff],
+            );
+        }
+
+        let up = self.upsampler.process(s, &self.half_band_coeffs);
+        let filtered = up.map(|s| {
+            run_filters::<CHANNELS, NUM_FILTERS>(
+                s,
+                [&mut self.filter_0, &mut self.filter_1],
+                [&self.filter_0_coeff, &self.filter_1_coeff],
+            )
+        });
+        self.downsampler.process(filtered, &self.half_band_coeffs)
+    }
+
+    /// Run one frame through the filters of the previous filter type.
+    fn tick_prev_type(&mut self, s: [f32; CHANNELS]) -> [f32; CHANNELS] {
+        let mut run = |s| {
+            if self.prev_type_num_filters == 2 {
+                run_filters::<CHANNELS, 2>(
+                    s,
+                    [&mut self.prev_type_filter_0, &mut self.prev_type_filter_1],
+                    [
+                        &self.prev_type_filter_0_coeff,
+                        &self.prev_type_filter_1_coeff,
+                    ],
+                )
+            } else {
+                run_filters::<CHANNELS, 1>(
+                    s,
+                    [&mut self.prev_type_filter_0, &mut self.prev_type_filter_1],
+                    [
+                        &self.prev_type_filter_0_coeff,
+                        &self.prev_type_filter_1_coeff,
+                    ],
+                )
+            }
+        };
+
+        if !self.oversample {
+            return run(s);
+        }
+
+        let up = self.prev_type_upsampler.process(s, &self.half_band_coeffs);
+        let filtered = up.map(&mut run);
+        self.prev_type_downsampler
+            .process(filtered, &self.half_band_coeffs)
+    }
 
-impl<const CHANNELS: usize> Processor<CHANNELS> {
     pub fn calc_coefficients(&mut self, sample_rate_recip: f32) {
+        let sample_rate_recip = self.filter_sample_rate_recip(sample_rate_recip);
         let cutoff_hz = self.cutoff_hz.target_value();
         let q = self.q_factor.target_value();
         let gain = self.gain.target_value();
@@ -670,6 +865,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -681,11 +878,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::lowpass_ord2(
-                    cutoff_hz,
-                    q,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::lowpass_ord2(cutoff_hz, q, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -693,7 +887,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -719,6 +913,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -730,8 +926,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                let [coeff_0, coeff_1] =
-                    SvfCoeff::lowpass_ord4(cutoff_hz, q, info.sample_rate_recip as f32);
+                let [coeff_0, coeff_1] = SvfCoeff::lowpass_ord4(cutoff_hz, q, sample_rate_recip);
                 self.filter_0_coeff = SvfCoeffSimd::splat(coeff_0);
                 self.filter_1_coeff = SvfCoeffSimd::splat(coeff_1);
             }
@@ -741,8 +936,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let s = self.filter_0.process(s, &self.filter_0_coeff);
-            let out = self.filter_1.process(s, &self.filter_1_coeff);
+            let out = self.tick::<2>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -768,6 +962,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -779,11 +975,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::highpass_ord2(
-                    cutoff_hz,
-                    q,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::highpass_ord2(cutoff_hz, q, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -791,7 +984,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -817,6 +1010,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -828,8 +1023,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                let [coeff_0, coeff_1] =
-                    SvfCoeff::highpass_ord4(cutoff_hz, q, info.sample_rate_recip as f32);
+                let [coeff_0, coeff_1] = SvfCoeff::highpass_ord4(cutoff_hz, q, sample_rate_recip);
                 self.filter_0_coeff = SvfCoeffSimd::splat(coeff_0);
                 self.filter_1_coeff = SvfCoeffSimd::splat(coeff_1);
             }
@@ -839,8 +1033,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let s = self.filter_0.process(s, &self.filter_0_coeff);
-            let out = self.filter_1.process(s, &self.filter_1_coeff);
+            let out = self.tick::<2>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -861,6 +1054,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -872,16 +1067,10 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::lowpass_ord2(
-                    cutoff_hz,
-                    q,
-                    info.sample_rate_recip as f32,
-                ));
-                self.filter_1_coeff = SvfCoeffSimd::splat(SvfCoeff::highpass_ord2(
-                    cutoff_hz,
-                    q,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::lowpass_ord2(cutoff_hz, q, sample_rate_recip));
+                self.filter_1_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::highpass_ord2(cutoff_hz, q, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -889,8 +1078,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let s = self.filter_0.process(s, &self.filter_0_coeff);
-            let out = self.filter_1.process(s, &self.filter_1_coeff);
+            let out = self.tick::<2>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -911,6 +1099,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -923,12 +1113,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::low_shelf(
-                    cutoff_hz,
-                    q,
-                    gain,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::low_shelf(cutoff_hz, q, gain, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -936,7 +1122,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -957,6 +1143,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -973,7 +1161,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                     cutoff_hz,
                     q,
                     gain,
-                    info.sample_rate_recip as f32,
+                    sample_rate_recip,
                 ));
             }
 
@@ -982,7 +1170,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -1003,6 +1191,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -1015,12 +1205,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::bell(
-                    cutoff_hz,
-                    q,
-                    gain,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::bell(cutoff_hz, q, gain, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -1028,7 +1214,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -1049,6 +1235,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -1060,11 +1248,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::notch(
-                    cutoff_hz,
-                    q,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::notch(cutoff_hz, q, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -1072,7 +1257,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -1093,6 +1278,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             assert!(ch.len() >= info.frames);
         }
 
+        let sample_rate_recip = self.filter_sample_rate_recip(info.sample_rate_recip as f32);
+
         for i in 0..info.frames {
             let cutoff_hz = self.cutoff_hz.next_smoothed();
             let q = self.q_factor.next_smoothed();
@@ -1104,11 +1291,8 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
             //
             // TODO: Alternatively, this could be optimized using a lookup table
             if self.coeff_update_mask.do_update(i) {
-                self.filter_0_coeff = SvfCoeffSimd::splat(SvfCoeff::allpass(
-                    cutoff_hz,
-                    q,
-                    info.sample_rate_recip as f32,
-                ));
+                self.filter_0_coeff =
+                    SvfCoeffSimd::splat(SvfCoeff::allpass(cutoff_hz, q, sample_rate_recip));
             }
 
             let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
@@ -1116,7 +1300,7 @@ impl<const CHANNELS: usize> Processor<CHANNELS> {
                 unsafe { *inputs.get_unchecked(ch_i).get_unchecked(i) }
             });
 
-            let out = self.filter_0.process(s, &self.filter_0_coeff);
+            let out = self.tick::<1>(s);
 
             for ch_i in 0..CHANNELS {
                 // Safety: These bounds have been checked above.
@@ -1142,7 +1326,7 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
             match patch {
                 SvfNodePatch::FilterType(filter_type) => {
                     params_changed = true;
-                    self.filter_type = filter_type;
+                    self.set_filter_type(filter_type);
                 }
                 SvfNodePatch::CutoffHz(cutoff) => {
                     params_changed = true;
@@ -1190,6 +1374,9 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
             self.cutoff_hz.reset_to_target();
             self.filter_0.reset();
             self.filter_1.reset();
+            self.upsampler.reset();
+            self.downsampler.reset();
+            self.type_fade_frames_left = 0;
             self.enable_declicker.reset_to_target();
 
             return ProcessStatus::ClearAllOutputs;
@@ -1222,51 +1409,11 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
                 self.calc_coefficients(info.sample_rate_recip as f32);
             }
 
-            assert!(buffers.inputs.len() == CHANNELS);
-            assert!(buffers.outputs.len() == CHANNELS);
-            for ch in buffers.inputs.iter() {
-                assert!(ch.len() >= info.frames);
-            }
-            for ch in buffers.outputs.iter() {
-                assert!(ch.len() >= info.frames);
-            }
+            self.process_static(buffers.inputs, buffers.outputs, info.frames);
+        }
 
-            if self.num_filters == 1 {
-                for i in 0..info.frames {
-                    let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
-                        // Safety: These bounds have been checked above.
-                        unsafe { *buffers.inputs.get_unchecked(ch_i).get_unchecked(i) }
-                    });
-
-                    let out = self.filter_0.process(s, &self.filter_0_coeff);
-
-                    for ch_i in 0..CHANNELS {
-                        // Safety: These bounds have been checked above.
-                        unsafe {
-                            *buffers.outputs.get_unchecked_mut(ch_i).get_unchecked_mut(i) =
-                                out[ch_i];
-                        }
-                    }
-                }
-            } else {
-                for i in 0..info.frames {
-                    let s: [f32; CHANNELS] = core::array::from_fn(|ch_i| {
-                        // Safety: These bounds have been checked above.
-                        unsafe { *buffers.inputs.get_unchecked(ch_i).get_unchecked(i) }
-                    });
-
-                    let s = self.filter_0.process(s, &self.filter_0_coeff);
-                    let out = self.filter_1.process(s, &self.filter_1_coeff);
-
-                    for ch_i in 0..CHANNELS {
-                        // Safety: These bounds have been checked above.
-                        unsafe {
-                            *buffers.outputs.get_unchecked_mut(ch_i).get_unchecked_mut(i) =
-                                out[ch_i];
-                        }
-                    }
-                }
-            }
+        if self.type_fade_frames_left > 0 {
+            self.process_type_crossfade(buffers.inputs, buffers.outputs, info.frames);
         }
 
         // Crossfade between the wet and dry signals to declick enabling/disabling.
@@ -1285,7 +1432,144 @@ impl<const CHANNELS: usize> AudioNodeProcessor for Processor<CHANNELS> {
         self.cutoff_hz.update_sample_rate(stream_info.sample_rate);
         self.q_factor.update_sample_rate(stream_info.sample_rate);
         self.gain.update_sample_rate(stream_info.sample_rate);
+        self.declick_frames = stream_info.declick_frames.get() as usize;
+        self.type_fade_frames_left = 0;
 
         self.calc_coefficients(stream_info.sample_rate_recip as f32);
     }
 }
+
+/// Run a frame through `NUM_FILTERS` filters in series.
+#[inline(always)]
+fn run_filters<const CHANNELS: usize, const NUM_FILTERS: usize>(
+    mut s: [f32; CHANNELS],
+    filters: [&mut SvfStateSimd<CHANNELS>; 2],
+    coeffs: [&SvfCoeffSimd<CHANNELS>; 2],
+) -> [f32; CHANNELS] {
+    for (filter, coeff) in filters.into_iter().zip(coeffs).take(NUM_FILTERS) {
+        s = filter.process(s, coeff);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use core::num::NonZeroU32;
+
+    use firewheel_core::dsp::volume::amp_to_db;
+
+    use super::*;
+
+    /// The largest jump between two consecutive output samples when the
+    /// filter type is switched from lowpass to highpass halfway through.
+    fn max_jump_on_type_switch(declick_frames: u32) -> f32 {
+        let stream_info = StreamInfo {
+            declick_frames: NonZeroU32::new(declick_frames).unwrap(),
+            ..Default::default()
+        };
+        let node = SvfMonoNode {
+            filter_type: SvfType::Lowpass,
+            cutoff_hz: 1_000.0,
+            ..Default::default()
+        };
+        let mut processor = Processor::new(&node, &SvfNodeConfig::default(), &stream_info);
+
+        // A 200Hz sine wave, which the lowpass passes and the highpass removes.
+        let input: Vec<f32> = (0..8_820)
+            .map(|i| (i as f32 * core::f32::consts::TAU * 200.0 / 44_100.0).sin())
+            .collect();
+        let mut output = vec![0.0; input.len()];
+
+        for (block_i, (in_block, out_block)) in
+            input.chunks(441).zip(output.chunks_mut(441)).enumerate()
+        {
+            if block_i == 10 {
+                processor.set_filter_type(SvfType::Highpass);
+                processor.calc_coefficients(stream_info.sample_rate_recip as f32);
+            }
+
+            let frames = in_block.len();
+            processor.process_static(&[in_block], &mut [&mut *out_block], frames);
+            if processor.type_fade_frames_left > 0 {
+                processor.process_type_crossfade(&[in_block], &mut [out_block], frames);
+            }
+        }
+
+        output
+            .windows(2)
+            .fold(0.0f32, |max, w| max.max((w[1] - w[0]).abs()))
+    }
+
+    /// The largest error in decibels between the magnitude response of a
+    /// +24 dB bell at 15kHz and the analog filter it models, up to 19kHz.
+    fn bell_warp_error_db(oversample: bool) -> f32 {
+        const FRAMES: usize = 8192;
+        const CUTOFF_HZ: f32 = 15_000.0;
+
+        let stream_info = StreamInfo::default();
+        let sample_rate = stream_info.sample_rate.get() as f32;
+        let node = SvfMonoNode::from_bell(CUTOFF_HZ, Volume::Decibels(24.0), 1.0, true);
+        let config = SvfNodeConfig {
+            oversample,
+            ..Default::default()
+        };
+        let mut processor = Processor::new(&node, &config, &stream_info);
+
+        let mut impulse = vec![0.0; FRAMES];
+        impulse[0] = 1.0;
+        let mut response = vec![0.0; FRAMES];
+        processor.process_static(&[&impulse], &mut [&mut response], FRAMES);
+
+        let raw_gain = db_to_amp(24.0);
+        let k = 1.0 / raw_gain.sqrt();
+
+        (0..200)
+            .map(|i| {
+                let hz = 1_000.0 + 18_000.0 * i as f32 / 200.0;
+
+                let w = core::f32::consts::TAU * hz / sample_rate;
+                let (re, im) =
+                    response
+                        .iter()
+                        .enumerate()
+                        .fold((0.0f32, 0.0f32), |(re, im), (n, &s)| {
+                            (re + s * (w * n as f32).cos(), im - s * (w * n as f32).sin())
+                        });
+                let digital_db = amp_to_db((re * re + im * im).sqrt());
+
+                // H(s) = 1 + k(G - 1)s / (s^2 + ks + 1), with s = jf/fc
+                let x = hz / CUTOFF_HZ;
+                let (den_re, den_im) = (1.0 - x * x, k * x);
+                let m1 = k * (raw_gain - 1.0);
+                let (num_re, num_im) = (den_re, den_im + m1 * x);
+                let analog_db = amp_to_db(
+                    ((num_re * num_re + num_im * num_im) / (den_re * den_re + den_im * den_im))
+                        .sqrt(),
+                );
+
+                (digital_db - analog_db).abs()
+            })
+            .fold(0.0, f32::max)
+    }
+
+    #[test]
+    fn oversampling_reduces_warp_error() {
+        let error = bell_warp_error_db(false);
+        let oversampled_error = bell_warp_error_db(true);
+
+        assert!(
+            oversampled_error < error * 0.5,
+            "{oversampled_error} dB vs {error} dB"
+        );
+    }
+
+    #[test]
+    fn filter_type_switch_is_declicked() {
+        // Without a crossfade the output jumps when the transfer function
+        // is swapped.
+        assert!(max_jump_on_type_switch(1) > 0.2);
+
+        // The normal slope of the sine wave is about `0.03` per sample.
+        assert!(max_jump_on_type_switch(441) < 0.05);
+    }
+}
@@ -0,0 +1,318 @@
+//! A pass-through node that sends a copy of its input to another thread.
+
+use bevy_platform::sync::{
+    atomic::{AtomicU32, AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use core::{ops::Range, sync::atomic::fence};
+use firewheel_core::{
+    channel_config::{ChannelConfig, NonZeroChannelCount},
+    diff::{Diff, Patch},
+    event::ProcEvents,
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcessStatus,
+    },
+};
+
+#[cfg(not(feature = "std"))]
+use bevy_platform::prelude::Box;
+
+/// The configuration of a [`TapNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TapConfig {
+    /// The number of channels
+    pub channels: NonZeroChannelCount,
+    /// The number of frames (samples in a single channel of audio) the ring
+    /// buffer can hold before the oldest frames are dropped.
+    ///
+    /// By default this is set to `8192`.
+    pub capacity_frames: usize,
+}
+
+impl Default for TapConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            capacity_frames: 8192,
+        }
+    }
+}
+
+/// A node that passes its input through unchanged while copying it into a
+/// ring buffer that can be read from another thread. Useful for building
+/// meters, oscilloscopes, and other waveform displays.
+///
+/// Use [`TapState::read`] to drain the samples. If the reader falls behind
+/// by more than [`TapConfig::capacity_frames`], then the oldest frames are
+/// dropped so that the reader always gets the most recent audio.
+#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TapNode {
+    /// Whether or not the node is enabled. While disabled, the signal is
+    /// still passed through, but nothing is written to the ring buffer.
+    pub enabled: bool,
+}
+
+impl Default for TapNode {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// The state of a [`TapNode`]. This is the reading end of the ring buffer.
+#[derive(Clone)]
+pub struct TapState {
+    num_channels: NonZeroChannelCount,
+    ring: Arc<TapRing>,
+    reader: Arc<Mutex<Reader>>,
+}
+
+impl TapState {
+    fn new(config: &TapConfig) -> Self {
+        Self {
+            num_channels: config.channels,
+            ring: Arc::new(TapRing::new(
+                config.capacity_frames.max(1) * config.channels.get().get() as usize,
+            )),
+            reader: Arc::new(Mutex::new(Reader::default())),
+        }
+    }
+
+    /// The number of channels in this buffer.
+    pub fn num_channels(&self) -> NonZeroChannelCount {
+        self.num_channels
+    }
+
+    /// Drain as many samples as are available into `out`, without blocking
+    /// the audio thread.
+    ///
+    /// The samples are in interleaved format, and only whole frames are
+    /// read. Returns the number of samples (not frames) written to `out`.
+    ///
+    /// If the reader fell behind by more than [`TapConfig::capacity_frames`],
+    /// then the oldest frames were dropped and reading continues from the
+    /// oldest frame still in the buffer.
+    pub fn read(&self, out: &mut [f32]) -> usize {
+        let num_channels = self.num_channels.get().get() as usize;
+        let mut reader = self.reader.lock().unwrap();
+
+        let len = out.len() - (out.len() % num_channels);
+        let (read, dropped) = self.ring.read(reader.pos, &mut out[..len]);
+
+        reader.pos = read.end;
+        reader.dropped_frames += dropped / num_channels as u64;
+
+        (read.end - read.start) as usize
+    }
+
+    /// The total number of frames that were dropped because the reader fell
+    /// behind.
+    pub fn dropped_frames(&self) -> u64 {
+        self.reader.lock().unwrap().dropped_frames
+    }
+}
+
+#[derive(Default)]
+struct Reader {
+    /// The position of the next sample to read.
+    pos: u64,
+    dropped_frames: u64,
+}
+
+/// A single-producer ring buffer of interleaved samples which overwrites the
+/// oldest samples when it is full.
+///
+/// The writer never waits on the reader. The reader detects samples that
+/// were overwritten while it was copying them (in the style of a seqlock)
+/// and discards them.
+struct TapRing {
+    /// The bits of each `f32` sample.
+    data: Box<[AtomicU32]>,
+    /// The end position of the samples the writer is about to write.
+    claimed: AtomicU64,
+    /// The end position of the samples which have been fully written.
+    committed: AtomicU64,
+}
+
+impl TapRing {
+    fn new(capacity: usize) -> Self {
+        Self {
+            data: (0..capacity).map(|_| AtomicU32::new(0)).collect(),
+            claimed: AtomicU64::new(0),
+            committed: AtomicU64::new(0),
+        }
+    }
+
+    fn capacity(&self) -> u64 {
+        self.data.len() as u64
+    }
+
+    fn index(&self, pos: u64) -> usize {
+        (pos % self.capacity()) as usize
+    }
+
+    /// Write the given samples. This must only be called from a single
+    /// thread.
+    fn write(&self, samples: impl ExactSizeIterator<Item = f32>) {
+        let start = self.committed.load(Ordering::Relaxed);
+        let end = start + samples.len() as u64;
+
+        self.claimed.store(end, Ordering::Relaxed);
+        fence(Ordering::Release);
+
+        for (i, s) in samples.enumerate() {
+            self.data[self.index(start + i as u64)].store(s.to_bits(), Ordering::Relaxed);
+        }
+
+        self.committed.store(end, Ordering::Release);
+    }
+
+    /// Read the samples starting at the position `from` into `out`.
+    ///
+    /// Positions count the samples written since the buffer was created,
+    /// so they never wrap around in practice.
+    ///
+    /// Returns the range of positions that were read into the start of
+    /// `out`, along with the number of samples that were dropped because
+    /// they were overwritten before they could be read.
+    fn read(&self, from: u64, out: &mut [f32]) -> (Range<u64>, u64) {
+        let capacity = self.capacity();
+        let committed = self.committed.load(Ordering::Acquire);
+
+        // Skip ahead to the oldest sample still in the buffer if the reader
+        // fell behind.
+        let mut start = from.max(committed.saturating_sub(capacity));
+        let mut len = (committed - start).min(out.len() as u64) as usize;
+
+        for (i, s) in out[..len].iter_mut().enumerate() {
+            *s = f32::from_bits(self.data[self.index(start + i as u64)].load(Ordering::Relaxed));
+        }
+
+        // Discard any samples the writer started overwriting while they
+        // were being copied.
+        fence(Ordering::Acquire);
+        let claimed = self.claimed.load(Ordering::Relaxed);
+        let overwritten = ((claimed - start).saturating_sub(capacity) as usize).min(len);
+        if overwritten > 0 {
+            out.copy_within(overwritten..len, 0);
+            start += overwritten as u64;
+            len -= overwritten;
+        }
+
+        (start..start + len as u64, start - from)
+    }
+}
+
+impl AudioNode for TapNode {
+    type Configuration = TapConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("tap")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: config.channels.get(),
+            })
+            .custom_state(TapState::new(config))
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor {
+            params: *self,
+            ring: Arc::clone(&cx.custom_state::<TapState>().unwrap().ring),
+        }
+    }
+}
+
+struct Processor {
+    params: TapNode,
+    ring: Arc<TapRing>,
+}
+
+impl Processor {
+    /// Copy `frames` frames of the given de-interleaved channels into the
+    /// ring buffer.
+    fn write(&self, inputs: &[&[f32]], frames: usize) {
+        let num_channels = inputs.len();
+
+        self.ring
+            .write((0..frames * num_channels).map(|i| inputs[i % num_channels][i / num_channels]));
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for patch in events.drain_patches::<TapNode>() {
+            self.params.apply(patch);
+        }
+
+        if self.params.enabled {
+            self.write(buffers.inputs, info.frames);
+        }
+
+        ProcessStatus::Bypass
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(capacity_frames: usize) -> TapState {
+        TapState::new(&TapConfig {
+            channels: NonZeroChannelCount::STEREO,
+            capacity_frames,
+        })
+    }
+
+    #[test]
+    fn reads_back_written_blocks_and_drops_oldest_on_overrun() {
+        let state = state(8);
+        let processor = Processor {
+            params: TapNode::default(),
+            ring: Arc::clone(&state.ring),
+        };
+
+        let left: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let right: Vec<f32> = (0..20).map(|i| -(i as f32)).collect();
+
+        // A block that fits is read back interleaved, in whole frames.
+        processor.write(&[&left[..5], &right[..5]], 5);
+        let mut out = [0.0; 7];
+        assert_eq!(state.read(&mut out), 6);
+        assert_eq!(out[..6], [0.0, -0.0, 1.0, -1.0, 2.0, -2.0]);
+        assert_eq!(state.read(&mut out), 4);
+        assert_eq!(out[..4], [3.0, -3.0, 4.0, -4.0]);
+        assert_eq!(state.read(&mut out), 0);
+
+        // Writing 12 frames into a buffer of 8 drops the oldest 4.
+        processor.write(&[&left[5..17], &right[5..17]], 12);
+        let mut out = [0.0; 32];
+        assert_eq!(state.read(&mut out), 16);
+        let expected: Vec<f32> = (9..17).flat_map(|i| [i as f32, -(i as f32)]).collect();
+        assert_eq!(out[..16], expected[..]);
+        assert_eq!(state.dropped_frames(), 4);
+
+        // Reading continues normally after an overrun.
+        processor.write(&[&left[17..], &right[17..]], 3);
+        assert_eq!(state.read(&mut out), 6);
+        assert_eq!(out[..6], [17.0, -17.0, 18.0, -18.0, 19.0, -19.0]);
+        assert_eq!(state.dropped_frames(), 4);
+    }
+}
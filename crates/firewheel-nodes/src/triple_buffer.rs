@@ -74,6 +74,17 @@ impl Diff for WindowSize {
             }
         }
     }
+
+    fn get_param(&self, path: &[u32]) -> Option<ParamData> {
+        if !path.is_empty() {
+            return None;
+        }
+
+        Some(match self {
+            WindowSize::Samples(samples) => (*samples).into(),
+            WindowSize::Seconds(seconds) => (*seconds).into(),
+        })
+    }
 }
 
 impl Patch for WindowSize {
@@ -219,6 +230,7 @@ impl AudioNode for TripleBufferNode {
                 num_channels: config.channels,
                 active_state: Arc::new(Mutex::new(None)),
             })
+            .analysis_only(true)
     }
 
     fn construct_processor(
@@ -1,3 +1,5 @@
+use core::num::NonZeroU32;
+
 use firewheel_core::{
     channel_config::{ChannelConfig, NonZeroChannelCount},
     diff::{Diff, Patch},
@@ -40,6 +42,36 @@ impl Default for VolumeNodeConfig {
 pub struct VolumeNode {
     /// The volume to apply to the signal
     pub volume: Volume,
+    /// The stereo balance in the range `[-1.0, 1.0]`, where `-1.0` is full
+    /// left, `0.0` is center, and `1.0` is full right.
+    ///
+    /// Unlike panning, this only turns down the opposite channel, so each
+    /// channel's content stays on its own side. This only has an effect when
+    /// the node has exactly two channels.
+    ///
+    /// By default this is set to `0.0`.
+    pub balance: f32,
+    /// If `true`, then the polarity of every channel is inverted (the signal
+    /// is multiplied by `-1.0`).
+    ///
+    /// Toggling this is smoothed to avoid a click from the sudden sign flip.
+    ///
+    /// By default this is set to `false`.
+    pub polarity_invert: bool,
+    /// If `true`, then [`VolumeNode::volume`] is smoothed as a single gain
+    /// that is applied to both channels, so a volume change never shifts the
+    /// stereo image. If `false`, then `volume` only applies to the left
+    /// channel and [`VolumeNode::right_volume`] applies to the right channel.
+    ///
+    /// This only has an effect when the node has exactly two channels.
+    ///
+    /// By default this is set to `true`.
+    pub stereo_link: bool,
+    /// The volume of the right channel when [`VolumeNode::stereo_link`] is
+    /// `false`.
+    ///
+    /// By default this is set to unity gain.
+    pub right_volume: Volume,
 
     /// The time in seconds of the internal smoothing filter.
     ///
@@ -57,6 +89,10 @@ impl Default for VolumeNode {
     fn default() -> Self {
         Self {
             volume: Volume::default(),
+            balance: 0.0,
+            polarity_invert: false,
+            stereo_link: true,
+            right_volume: Volume::UNITY_GAIN,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
@@ -72,6 +108,10 @@ impl VolumeNode {
     pub const fn from_linear(linear: f32) -> Self {
         Self {
             volume: Volume::Linear(linear),
+            balance: 0.0,
+            polarity_invert: false,
+            stereo_link: true,
+            right_volume: Volume::UNITY_GAIN,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
@@ -84,6 +124,10 @@ impl VolumeNode {
     pub const fn from_percent(percent: f32) -> Self {
         Self {
             volume: Volume::from_percent(percent),
+            balance: 0.0,
+            polarity_invert: false,
+            stereo_link: true,
+            right_volume: Volume::UNITY_GAIN,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
@@ -94,6 +138,10 @@ impl VolumeNode {
     pub const fn from_decibels(decibels: f32) -> Self {
         Self {
             volume: Volume::Decibels(decibels),
+            balance: 0.0,
+            polarity_invert: false,
+            stereo_link: true,
+            right_volume: Volume::UNITY_GAIN,
             smooth_seconds: DEFAULT_SMOOTH_SECONDS,
             min_gain: DEFAULT_AMP_EPSILON,
         }
@@ -137,30 +185,170 @@ impl AudioNode for VolumeNode {
 
     fn construct_processor(
         &self,
-        _config: &Self::Configuration,
+        config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        let min_gain = self.min_gain.max(0.0);
-        let gain = self.volume.amp_clamped(min_gain);
-
-        VolumeProcessor {
-            gain: SmoothedParam::new(
-                gain,
-                SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
-                    ..Default::default()
+        let mut processor = VolumeProcessor::new(self, config, cx.stream_info.sample_rate);
+        processor.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        processor
+    }
+}
+
+struct VolumeProcessor {
+    gain: SmoothedParam,
+    /// The gain of the right channel while it is not linked to `gain`.
+    gain_right: SmoothedParam,
+    balance: SmoothedParam,
+    polarity: SmoothedParam,
+
+    min_gain: f32,
+    is_stereo: bool,
+    stereo_link: bool,
+    right_volume: Volume,
+    /// Whether the right channel uses `gain` directly. After relinking, this
+    /// stays `false` until `gain_right` has caught up with `gain`.
+    right_follows_left: bool,
+}
+
+impl VolumeProcessor {
+    fn new(params: &VolumeNode, config: &VolumeNodeConfig, sample_rate: NonZeroU32) -> Self {
+        let min_gain = params.min_gain.max(0.0);
+        let gain = params.volume.amp_clamped(min_gain);
+        let is_stereo = config.channels.get().get() == 2;
+        let right_gain = if params.stereo_link {
+            gain
+        } else {
+            params.right_volume.amp_clamped(min_gain)
+        };
+
+        let smoother_config = SmootherConfig {
+            smooth_seconds: params.smooth_seconds,
+            ..Default::default()
+        };
+
+        Self {
+            gain: SmoothedParam::new(gain, smoother_config, sample_rate),
+            gain_right: SmoothedParam::new(right_gain, smoother_config, sample_rate),
+            balance: SmoothedParam::new(
+                if is_stereo {
+                    params.balance.clamp(-1.0, 1.0)
+                } else {
+                    0.0
                 },
-                cx.stream_info.sample_rate,
+                smoother_config,
+                sample_rate,
+            ),
+            polarity: SmoothedParam::new(
+                polarity_sign(params.polarity_invert),
+                smoother_config,
+                sample_rate,
             ),
             min_gain,
+            is_stereo,
+            stereo_link: params.stereo_link,
+            right_volume: params.right_volume,
+            right_follows_left: params.stereo_link,
+        }
+    }
+
+    fn set_min_smooth_seconds(&mut self, seconds: f32, sample_rate: NonZeroU32) {
+        for param in [
+            &mut self.gain,
+            &mut self.gain_right,
+            &mut self.balance,
+            &mut self.polarity,
+        ] {
+            param.set_min_smooth_seconds(seconds, sample_rate);
+        }
+    }
+
+    fn set_stereo_link(&mut self, stereo_link: bool) {
+        self.stereo_link = stereo_link;
+
+        if stereo_link {
+            // Keep using the separate smoother until it has caught up, so
+            // that the right channel doesn't jump.
+            self.gain_right.set_value(self.gain.target_value());
+        } else {
+            if self.right_follows_left {
+                // Continue from wherever the linked gain currently is.
+                self.gain_right = self.gain;
+                self.right_follows_left = false;
+            }
+            self.gain_right
+                .set_value(self.right_volume.amp_clamped(self.min_gain));
+        }
+    }
+
+    fn right_gain_target(&self) -> f32 {
+        if self.right_follows_left {
+            self.gain.target_value()
+        } else {
+            self.gain_right.target_value()
+        }
+    }
+
+    fn settle(&mut self) {
+        self.gain.settle();
+        self.gain_right.settle();
+        self.balance.settle();
+        self.polarity.settle();
+
+        if self.stereo_link
+            && !self.right_follows_left
+            && self.gain.has_settled()
+            && self.gain_right.has_settled()
+            && self.gain_right.target_value() == self.gain.target_value()
+        {
+            self.right_follows_left = true;
+        }
+    }
+
+    fn reset_to_target(&mut self) {
+        self.gain.reset_to_target();
+        self.gain_right.reset_to_target();
+        self.balance.reset_to_target();
+        self.polarity.reset_to_target();
+
+        if self.stereo_link {
+            self.right_follows_left = true;
+        }
+    }
+
+    fn process_stereo(&mut self, in0: &[f32], in1: &[f32], out0: &mut [f32], out1: &mut [f32]) {
+        for (((&in0, &in1), out0), out1) in in0
+            .iter()
+            .zip(in1.iter())
+            .zip(out0.iter_mut())
+            .zip(out1.iter_mut())
+        {
+            let polarity = self.polarity.next_smoothed();
+            let gain_left = self.gain.next_smoothed() * polarity;
+            let gain_right = if self.right_follows_left {
+                gain_left
+            } else {
+                self.gain_right.next_smoothed() * polarity
+            };
+            let (left, right) = balance_gains(self.balance.next_smoothed());
+
+            *out0 = in0 * gain_left * left;
+            *out1 = in1 * gain_right * right;
         }
     }
 }
 
-struct VolumeProcessor {
-    gain: SmoothedParam,
+/// Returns the gain that sets the polarity of a signal.
+pub(crate) fn polarity_sign(invert: bool) -> f32 {
+    if invert {
+        -1.0
+    } else {
+        1.0
+    }
+}
 
-    min_gain: f32,
+/// Returns the gains of the left and right channels for the given balance.
+fn balance_gains(balance: f32) -> (f32, f32) {
+    ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0))
 }
 
 impl AudioNodeProcessor for VolumeProcessor {
@@ -179,14 +367,54 @@ impl AudioNodeProcessor for VolumeProcessor {
                         gain = 1.0;
                     }
                     self.gain.set_value(gain);
+                    if self.stereo_link {
+                        self.gain_right.set_value(gain);
+                    }
 
                     if info.prev_output_was_silent {
                         // Previous block was silent, so no need to smooth.
                         self.gain.reset_to_target();
+                        if self.stereo_link {
+                            self.gain_right.reset_to_target();
+                        }
+                    }
+                }
+                VolumeNodePatch::StereoLink(stereo_link) => {
+                    self.set_stereo_link(stereo_link);
+                }
+                VolumeNodePatch::RightVolume(v) => {
+                    self.right_volume = v;
+
+                    if !self.stereo_link {
+                        self.gain_right.set_value(v.amp_clamped(self.min_gain));
+
+                        if info.prev_output_was_silent {
+                            self.gain_right.reset_to_target();
+                        }
+                    }
+                }
+                VolumeNodePatch::Balance(balance) => {
+                    if self.is_stereo {
+                        self.balance.set_value(balance.clamp(-1.0, 1.0));
+
+                        if info.prev_output_was_silent {
+                            self.balance.reset_to_target();
+                        }
+                    }
+                }
+                VolumeNodePatch::PolarityInvert(invert) => {
+                    self.polarity.set_value(polarity_sign(invert));
+
+                    if info.prev_output_was_silent {
+                        self.polarity.reset_to_target();
                     }
                 }
                 VolumeNodePatch::SmoothSeconds(seconds) => {
                     self.gain.set_smooth_seconds(seconds, info.sample_rate);
+                    self.gain_right
+                        .set_smooth_seconds(seconds, info.sample_rate);
+                    self.balance.set_smooth_seconds(seconds, info.sample_rate);
+                    self.polarity.set_smooth_seconds(seconds, info.sample_rate);
                 }
                 VolumeNodePatch::MinGain(min_gain) => {
                     self.min_gain = min_gain.max(0.0);
@@ -200,18 +428,33 @@ impl AudioNodeProcessor for VolumeProcessor {
         {
             // All channels are silent, so there is no need to process. Also reset
             // the filter since it doesn't need to smooth anything.
-            self.gain.reset_to_target();
+            self.reset_to_target();
 
             return ProcessStatus::ClearAllOutputs;
         }
 
-        if self.gain.has_settled() {
-            if self.gain.target_value() <= self.min_gain {
+        let right_settled =
+            !self.is_stereo || self.right_follows_left || self.gain_right.has_settled();
+        if self.gain.has_settled()
+            && right_settled
+            && self.balance.has_settled()
+            && self.polarity.has_settled()
+        {
+            let balance = self.balance.target_value();
+            let polarity = self.polarity.target_value();
+            let gain = self.gain.target_value();
+            let gain_right = if self.is_stereo {
+                self.right_gain_target()
+            } else {
+                gain
+            };
+
+            if gain <= self.min_gain && gain_right <= self.min_gain {
                 // Muted, so there is no need to process.
                 return ProcessStatus::ClearAllOutputs;
-            } else if self.gain.target_value() == 1.0 {
+            } else if gain == 1.0 && gain_right == 1.0 && balance == 0.0 && polarity == 1.0 {
                 // Unity gain, there is no need to process.
-                return ProcessStatus::Bypass;
+                return ProcessStatus::PassThrough;
             } else {
                 for (ch_i, (out_ch, in_ch)) in buffers
                     .outputs
@@ -224,8 +467,15 @@ impl AudioNodeProcessor for VolumeProcessor {
                             out_ch.fill(0.0);
                         }
                     } else {
+                        let gain = polarity
+                            * match ch_i {
+                                0 => gain * balance_gains(balance).0,
+                                1 if self.is_stereo => gain_right * balance_gains(balance).1,
+                                _ => gain,
+                            };
+
                         for (os, &is) in out_ch.iter_mut().zip(in_ch.iter()) {
-                            *os = is * self.gain.target_value();
+                            *os = is * gain;
                         }
                     }
                 }
@@ -239,28 +489,27 @@ impl AudioNodeProcessor for VolumeProcessor {
         if buffers.inputs.len() == 1 {
             // Provide an optimized loop for mono.
             for (os, &is) in buffers.outputs[0].iter_mut().zip(buffers.inputs[0].iter()) {
-                *os = is * self.gain.next_smoothed();
+                *os = is * self.gain.next_smoothed() * self.polarity.next_smoothed();
             }
         } else if buffers.inputs.len() == 2 {
             // Provide an optimized loop for stereo.
 
-            let in0 = &buffers.inputs[0][..info.frames];
-            let in1 = &buffers.inputs[1][..info.frames];
             let (out0, out1) = buffers.outputs.split_first_mut().unwrap();
-            let out0 = &mut out0[..info.frames];
-            let out1 = &mut out1[0][..info.frames];
-
-            for i in 0..info.frames {
-                let gain = self.gain.next_smoothed();
 
-                out0[i] = in0[i] * gain;
-                out1[i] = in1[i] * gain;
-            }
+            self.process_stereo(
+                &buffers.inputs[0][..info.frames],
+                &buffers.inputs[1][..info.frames],
+                &mut out0[..info.frames],
+                &mut out1[0][..info.frames],
+            );
         } else {
             let scratch_buffer = extra.scratch_buffers.first_mut();
 
             self.gain
                 .process_into_buffer(&mut scratch_buffer[..info.frames]);
+            for g in scratch_buffer[..info.frames].iter_mut() {
+                *g *= self.polarity.next_smoothed();
+            }
 
             for (ch_i, (out_ch, in_ch)) in buffers
                 .outputs
@@ -285,7 +534,7 @@ impl AudioNodeProcessor for VolumeProcessor {
             }
         }
 
-        self.gain.settle();
+        self.settle();
 
         ProcessStatus::OutputsModified
     }
@@ -296,5 +545,120 @@ impl AudioNodeProcessor for VolumeProcessor {
         _context: &mut ProcStreamCtx,
     ) {
         self.gain.update_sample_rate(stream_info.sample_rate);
+        self.gain_right.update_sample_rate(stream_info.sample_rate);
+        self.balance.update_sample_rate(stream_info.sample_rate);
+        self.polarity.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_right_balance_silences_left() {
+        let mut processor = VolumeProcessor::new(
+            &VolumeNode::default(),
+            &VolumeNodeConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        processor.balance.set_value(1.0);
+
+        let input: Vec<f32> = (0..64).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut out_l = [0.0; 64];
+        let mut out_r = [0.0; 64];
+        for _ in 0..100 {
+            processor.process_stereo(&input, &input, &mut out_l, &mut out_r);
+            processor.balance.settle();
+        }
+
+        // The left channel is silenced while the right is left unchanged.
+        assert!(out_l.iter().all(|&s| s == 0.0));
+        assert_eq!(&out_r[..], &input[..]);
+    }
+
+    #[test]
+    fn linked_stereo_volume_ramps_both_channels_equally() {
+        let mut processor = VolumeProcessor::new(
+            &VolumeNode::default(),
+            &VolumeNodeConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        let input = [1.0; 64];
+        let mut out_l = [0.0; 64];
+        let mut out_r = [0.0; 64];
+
+        fn ramp(processor: &mut VolumeProcessor, gain: f32) {
+            processor.gain.set_value(gain);
+            if processor.stereo_link {
+                processor.gain_right.set_value(gain);
+            }
+
+            let input = [1.0; 64];
+            let mut out_l = [0.0; 64];
+            let mut out_r = [0.0; 64];
+            for _ in 0..100 {
+                processor.process_stereo(&input, &input, &mut out_l, &mut out_r);
+                assert_eq!(out_l, out_r);
+                processor.settle();
+            }
+            assert_eq!(out_l, [gain; 64]);
+        }
+
+        ramp(&mut processor, 0.25);
+
+        // Unlinked channels can have different gains.
+        processor.right_volume = Volume::Decibels(-6.0);
+        processor.set_stereo_link(false);
+        for _ in 0..100 {
+            processor.process_stereo(&input, &input, &mut out_l, &mut out_r);
+            processor.settle();
+        }
+        assert_eq!(out_l, [0.25; 64]);
+        assert_eq!(out_r, [Volume::Decibels(-6.0).amp(); 64]);
+
+        // Once relinked, the right channel catches up with the left, and
+        // both channels ramp together again.
+        processor.set_stereo_link(true);
+        for _ in 0..100 {
+            processor.process_stereo(&input, &input, &mut out_l, &mut out_r);
+            processor.settle();
+        }
+        assert!(processor.right_follows_left);
+        ramp(&mut processor, 0.75);
+    }
+
+    #[test]
+    fn polarity_invert_negates_input_after_declick() {
+        let mut processor = VolumeProcessor::new(
+            &VolumeNode::default(),
+            &VolumeNodeConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        processor.polarity.set_value(polarity_sign(true));
+
+        let input: Vec<f32> = (0..64).map(|i| (i as f32 * 0.1).sin()).collect();
+        let mut out_l = [0.0; 64];
+        let mut out_r = [0.0; 64];
+
+        // The sign flip is smoothed instead of happening all at once.
+        processor.process_stereo(&input, &input, &mut out_l, &mut out_r);
+        processor.polarity.settle();
+        assert!(out_l[1..]
+            .iter()
+            .zip(input[1..].iter())
+            .all(|(&o, &i)| o.abs() < i.abs()));
+
+        for _ in 0..100 {
+            processor.process_stereo(&input, &input, &mut out_l, &mut out_r);
+            processor.polarity.settle();
+        }
+
+        let negated: Vec<f32> = input.iter().map(|&s| -s).collect();
+        assert_eq!(&out_l[..], &negated[..]);
+        assert_eq!(&out_r[..], &negated[..]);
     }
 }
@@ -1,3 +1,4 @@
+use core::num::NonZeroU32;
 use firewheel_core::{
     channel_config::{ChannelConfig, ChannelCount},
     diff::{Diff, Patch},
@@ -15,10 +16,31 @@ use firewheel_core::{
     param::smoother::{SmoothedParam, SmootherConfig},
 };
 
-pub use super::volume::VolumeNodeConfig;
+/// The configuration of a [`VolumePanNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VolumePanNodeConfig {
+    /// The time in seconds of the smoothing filter applied to
+    /// [`VolumePanNode::pan`]. The channel gains are recomputed from the
+    /// smoothed pan value on every frame, so fast pan automation doesn't
+    /// cause zipper noise.
+    ///
+    /// By default this is set to `0.015` (15ms).
+    pub pan_smooth_seconds: f32,
+}
+
+impl Default for VolumePanNodeConfig {
+    fn default() -> Self {
+        Self {
+            pan_smooth_seconds: DEFAULT_SMOOTH_SECONDS,
+        }
+    }
+}
 
 /// A node that applies volume and panning to a stereo signal
-#[derive(Diff, Patch, Debug, Clone, Copy, PartialEq)]
+#[derive(Diff, Patch, Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
 #[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -33,7 +55,10 @@ pub struct VolumePanNode {
     /// channels.
     pub pan_law: FadeCurve,
 
-    /// The time in seconds of the internal smoothing filter.
+    /// The time in seconds of the internal smoothing filter for the volume.
+    ///
+    /// The smoothing time of the pan is set with
+    /// [`VolumePanNodeConfig::pan_smooth_seconds`].
     ///
     /// By default this is set to `0.015` (15ms).
     pub smooth_seconds: f32,
@@ -114,22 +139,28 @@ impl VolumePanNode {
     }
 
     pub fn compute_gains(&self, amp_epsilon: f32) -> (f32, f32) {
-        let global_gain = self.volume.amp_clamped(amp_epsilon);
-
-        let (mut gain_l, mut gain_r) = self.pan_law.compute_gains_neg1_to_1(self.pan);
+        pan_gains(
+            &self.pan_law,
+            self.pan,
+            self.volume.amp_clamped(amp_epsilon),
+        )
+    }
+}
 
-        gain_l *= global_gain;
-        gain_r *= global_gain;
+fn pan_gains(pan_law: &FadeCurve, pan: f32, global_gain: f32) -> (f32, f32) {
+    let (mut gain_l, mut gain_r) = pan_law.compute_gains_neg1_to_1(pan);
 
-        if gain_l > 0.99999 && gain_l < 1.00001 {
-            gain_l = 1.0;
-        }
-        if gain_r > 0.99999 && gain_r < 1.00001 {
-            gain_r = 1.0;
-        }
+    gain_l *= global_gain;
+    gain_r *= global_gain;
 
-        (gain_l, gain_r)
+    if gain_l > 0.99999 && gain_l < 1.00001 {
+        gain_l = 1.0;
     }
+    if gain_r > 0.99999 && gain_r < 1.00001 {
+        gain_r = 1.0;
+    }
+
+    (gain_l, gain_r)
 }
 
 impl Default for VolumePanNode {
@@ -145,7 +176,7 @@ impl Default for VolumePanNode {
 }
 
 impl AudioNode for VolumePanNode {
-    type Configuration = VolumeNodeConfig;
+    type Configuration = VolumePanNodeConfig;
 
     fn info(&self, _config: &Self::Configuration) -> AudioNodeInfo {
         AudioNodeInfo::new()
@@ -158,43 +189,93 @@ impl AudioNode for VolumePanNode {
 
     fn construct_processor(
         &self,
-        _config: &Self::Configuration,
+        config: &Self::Configuration,
         cx: ConstructProcessorContext,
     ) -> impl AudioNodeProcessor {
-        let min_gain = self.min_gain.max(0.0);
+        let mut processor = Processor::new(self.clone(), config, cx.stream_info.sample_rate);
+        for param in [&mut processor.gain, &mut processor.pan] {
+            param.set_min_smooth_seconds(cx.min_param_ramp_seconds, cx.stream_info.sample_rate);
+        }
+        processor
+    }
+}
 
-        let (gain_l, gain_r) = self.compute_gains(self.min_gain);
+struct Processor {
+    gain: SmoothedParam,
+    pan: SmoothedParam,
+
+    params: VolumePanNode,
 
-        Processor {
-            gain_l: SmoothedParam::new(
-                gain_l,
+    min_gain: f32,
+}
+
+impl Processor {
+    fn new(params: VolumePanNode, config: &VolumePanNodeConfig, sample_rate: NonZeroU32) -> Self {
+        let min_gain = params.min_gain.max(0.0);
+
+        Self {
+            gain: SmoothedParam::new(
+                params.volume.amp_clamped(min_gain),
                 SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
+                    smooth_seconds: params.smooth_seconds,
                     ..Default::default()
                 },
-                cx.stream_info.sample_rate,
+                sample_rate,
             ),
-            gain_r: SmoothedParam::new(
-                gain_r,
+            pan: SmoothedParam::new(
+                params.pan.clamp(-1.0, 1.0),
                 SmootherConfig {
-                    smooth_seconds: self.smooth_seconds,
+                    smooth_seconds: config.pan_smooth_seconds,
                     ..Default::default()
                 },
-                cx.stream_info.sample_rate,
+                sample_rate,
             ),
-            params: *self,
+            params,
             min_gain,
         }
     }
-}
 
-struct Processor {
-    gain_l: SmoothedParam,
-    gain_r: SmoothedParam,
+    /// Apply the (possibly smoothed) gains to a block of stereo frames.
+    ///
+    /// Returns `true` if the gains were still being smoothed.
+    fn apply_gains(
+        &mut self,
+        in1: &[f32],
+        in2: &[f32],
+        out1: &mut [f32],
+        out2: &mut [f32],
+    ) -> bool {
+        if self.gain.has_settled() && self.pan.has_settled() {
+            let (gain_l, gain_r) = pan_gains(
+                &self.params.pan_law,
+                self.pan.target_value(),
+                self.gain.target_value(),
+            );
+
+            for i in 0..out1.len() {
+                out1[i] = in1[i] * gain_l;
+                out2[i] = in2[i] * gain_r;
+            }
 
-    params: VolumePanNode,
+            false
+        } else {
+            for i in 0..out1.len() {
+                let (gain_l, gain_r) = pan_gains(
+                    &self.params.pan_law,
+                    self.pan.next_smoothed(),
+                    self.gain.next_smoothed(),
+                );
 
-    min_gain: f32,
+                out1[i] = in1[i] * gain_l;
+                out2[i] = in2[i] * gain_r;
+            }
+
+            self.gain.settle();
+            self.pan.settle();
+
+            true
+        }
+    }
 }
 
 impl AudioNodeProcessor for Processor {
@@ -210,10 +291,10 @@ impl AudioNodeProcessor for Processor {
             match &mut patch {
                 VolumePanNodePatch::Pan(p) => {
                     *p = p.clamp(-1.0, 1.0);
+                    self.pan.set_value(*p);
                 }
                 VolumePanNodePatch::SmoothSeconds(seconds) => {
-                    self.gain_l.set_smooth_seconds(*seconds, info.sample_rate);
-                    self.gain_r.set_smooth_seconds(*seconds, info.sample_rate);
+                    self.gain.set_smooth_seconds(*seconds, info.sample_rate);
                 }
                 VolumePanNodePatch::MinGain(min_gain) => {
                     self.min_gain = (*min_gain).max(0.0);
@@ -226,20 +307,25 @@ impl AudioNodeProcessor for Processor {
         }
 
         if updated {
-            let (gain_l, gain_r) = self.params.compute_gains(self.min_gain);
-            self.gain_l.set_value(gain_l);
-            self.gain_r.set_value(gain_r);
+            self.gain
+                .set_value(self.params.volume.amp_clamped(self.min_gain));
 
             if info.prev_output_was_silent {
                 // Previous block was silent, so no need to smooth.
-                self.gain_l.reset_to_target();
-                self.gain_r.reset_to_target();
+                self.gain.reset_to_target();
+                self.pan.reset_to_target();
             }
         }
 
         if info.in_silence_mask.all_channels_silent(2) {
-            self.gain_l.reset_to_target();
-            self.gain_r.reset_to_target();
+            self.gain.reset_to_target();
+            self.pan.reset_to_target();
+
+            return ProcessStatus::ClearAllOutputs;
+        }
+
+        if self.gain.has_settled() && self.gain.target_value() <= self.min_gain {
+            self.pan.reset_to_target();
 
             return ProcessStatus::ClearAllOutputs;
         }
@@ -250,35 +336,10 @@ impl AudioNodeProcessor for Processor {
         let out1 = &mut out1[..info.frames];
         let out2 = &mut out2[0][..info.frames];
 
-        if self.gain_l.has_settled() && self.gain_r.has_settled() {
-            if self.gain_l.target_value() <= self.min_gain
-                && self.gain_r.target_value() <= self.min_gain
-            {
-                self.gain_l.reset_to_target();
-                self.gain_r.reset_to_target();
-
-                ProcessStatus::ClearAllOutputs
-            } else {
-                for i in 0..info.frames {
-                    out1[i] = in1[i] * self.gain_l.target_value();
-                    out2[i] = in2[i] * self.gain_r.target_value();
-                }
-
-                ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(info.in_silence_mask))
-            }
-        } else {
-            for i in 0..info.frames {
-                let gain_l = self.gain_l.next_smoothed();
-                let gain_r = self.gain_r.next_smoothed();
-
-                out1[i] = in1[i] * gain_l;
-                out2[i] = in2[i] * gain_r;
-            }
-
-            self.gain_l.settle();
-            self.gain_r.settle();
-
+        if self.apply_gains(in1, in2, out1, out2) {
             ProcessStatus::OutputsModified
+        } else {
+            ProcessStatus::OutputsModifiedWithMask(MaskType::Silence(info.in_silence_mask))
         }
     }
 
@@ -287,7 +348,46 @@ impl AudioNodeProcessor for Processor {
         stream_info: &firewheel_core::StreamInfo,
         _context: &mut ProcStreamCtx,
     ) {
-        self.gain_l.update_sample_rate(stream_info.sample_rate);
-        self.gain_r.update_sample_rate(stream_info.sample_rate);
+        self.gain.update_sample_rate(stream_info.sample_rate);
+        self.pan.update_sample_rate(stream_info.sample_rate);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pan_step_is_smoothed() {
+        let mut processor = Processor::new(
+            VolumePanNode::default(),
+            &VolumePanNodeConfig::default(),
+            NonZeroU32::new(48_000).unwrap(),
+        );
+
+        processor.pan.set_value(1.0);
+
+        let input = [1.0; 64];
+        let mut out_l = Vec::new();
+        let mut out_r = Vec::new();
+        for _ in 0..100 {
+            let mut block_l = [0.0; 64];
+            let mut block_r = [0.0; 64];
+            processor.apply_gains(&input, &input, &mut block_l, &mut block_r);
+            out_l.extend_from_slice(&block_l);
+            out_r.extend_from_slice(&block_r);
+        }
+
+        // Both channels start from the centered equal-power gain.
+        assert!((out_l[0] - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+        assert!((out_r[0] - core::f32::consts::FRAC_1_SQRT_2).abs() < 0.01);
+
+        // The gains move gradually and monotonically to hard right.
+        for (l, r) in out_l.windows(2).zip(out_r.windows(2)) {
+            assert!(l[1] <= l[0] && (l[0] - l[1]) < 0.01);
+            assert!(r[1] >= r[0] && (r[1] - r[0]) < 0.01);
+        }
+        assert!(*out_l.last().unwrap() < 0.001);
+        assert!(*out_r.last().unwrap() > 0.999);
     }
 }
@@ -0,0 +1,478 @@
+//! A node that records its input to a WAV file on a background thread.
+
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use core::num::NonZeroU32;
+
+use firewheel_core::{
+    channel_config::{ChannelConfig, ChannelCount, NonZeroChannelCount},
+    event::{NodeEventType, ProcEvents},
+    node::{
+        AudioNode, AudioNodeInfo, AudioNodeProcessor, ConstructProcessorContext, ProcBuffers,
+        ProcExtra, ProcInfo, ProcStreamCtx, ProcessStatus,
+    },
+};
+use ringbuf::traits::{Consumer, Observer, Producer, Split};
+
+/// How long the writer thread sleeps when there is no audio waiting to be
+/// written.
+const WRITER_POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+/// The configuration of a [`WavRecorderNode`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WavRecorderConfig {
+    /// The number of channels.
+    pub channels: NonZeroChannelCount,
+    /// The amount of audio in seconds that can be waiting to be written to
+    /// the file at once. If the writer thread falls further behind than
+    /// this, then blocks of audio are dropped (see
+    /// [`WavRecorderState::overflow_occurred`]).
+    ///
+    /// By default this is set to `1.0`.
+    pub capacity_seconds: f32,
+}
+
+impl Default for WavRecorderConfig {
+    fn default() -> Self {
+        Self {
+            channels: NonZeroChannelCount::STEREO,
+            capacity_seconds: 1.0,
+        }
+    }
+}
+
+/// A node that records its input to a WAV file, encoding and writing the
+/// file incrementally on a background thread. This allows exporting the
+/// output of the graph while it plays.
+///
+/// Recordings are started and stopped with [`WavRecorderState`], which can
+/// be accessed with `FirewheelCtx::node_state_mut`. Samples are written as
+/// 32 bit floats.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "bevy", derive(bevy_ecs::prelude::Component))]
+#[cfg_attr(feature = "bevy_reflect", derive(bevy_reflect::Reflect))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WavRecorderNode;
+
+/// An error that occurred while recording with a [`WavRecorderNode`].
+#[derive(Debug, thiserror::Error)]
+pub enum WavRecorderError {
+    /// A recording is already in progress.
+    #[error("A recording is already in progress")]
+    AlreadyRecording,
+    /// There is no recording in progress.
+    #[error("There is no recording in progress")]
+    NotRecording,
+    /// The WAV file could not be created or written.
+    #[error("Failed to write the WAV file: {0}")]
+    Wav(#[from] hound::Error),
+    /// The writer thread panicked.
+    #[error("The WAV writer thread panicked")]
+    WriterPanicked,
+}
+
+/// The state of a [`WavRecorderNode`].
+pub struct WavRecorderState {
+    channels: NonZeroChannelCount,
+    capacity_seconds: f32,
+    recording: Option<Recording>,
+}
+
+struct Recording {
+    shared: Arc<SharedState>,
+    writer: JoinHandle<Result<u64, hound::Error>>,
+    stopping: bool,
+}
+
+impl WavRecorderState {
+    fn new(config: &WavRecorderConfig) -> Self {
+        Self {
+            channels: config.channels,
+            capacity_seconds: config.capacity_seconds,
+            recording: None,
+        }
+    }
+
+    /// Begin recording to a new WAV file at the given path.
+    ///
+    /// The returned event must be sent to the node's processor for this to
+    /// take effect.
+    ///
+    /// * `path` - The path of the file. If the file exists, then it is
+    /// overwritten.
+    /// * `sample_rate` - The sample rate of the active audio stream.
+    ///
+    /// If there is already a recording in progress (including one that has
+    /// been stopped but not finished), then this will return an error.
+    pub fn start_recording(
+        &mut self,
+        path: impl AsRef<Path>,
+        sample_rate: NonZeroU32,
+    ) -> Result<StartRecordingEvent, WavRecorderError> {
+        if self.recording.is_some() {
+            return Err(WavRecorderError::AlreadyRecording);
+        }
+
+        let num_channels = self.channels.get().get() as usize;
+        let writer = hound::WavWriter::create(
+            path,
+            hound::WavSpec {
+                channels: num_channels as u16,
+                sample_rate: sample_rate.get(),
+                bits_per_sample: 32,
+                sample_format: hound::SampleFormat::Float,
+            },
+        )?;
+
+        let capacity_frames =
+            (self.capacity_seconds.max(0.0) * sample_rate.get() as f32).ceil() as usize;
+        let (prod, cons) = ringbuf::HeapRb::new(capacity_frames.max(1) * num_channels).split();
+
+        let shared = Arc::new(SharedState::new());
+        let writer_shared = Arc::clone(&shared);
+        let writer =
+            std::thread::spawn(move || write_samples(writer, cons, num_channels, &writer_shared));
+
+        self.recording = Some(Recording {
+            shared: Arc::clone(&shared),
+            writer,
+            stopping: false,
+        });
+
+        Ok(StartRecordingEvent {
+            recording: Some(ActiveRecording { prod, shared }),
+        })
+    }
+
+    /// Stop the recording in progress.
+    ///
+    /// The returned event must be sent to the node's processor for this to
+    /// take effect. All audio the processor received before the event is
+    /// written to the file. Afterwards, call
+    /// [`WavRecorderState::finish_recording`] to wait for the file to be
+    /// finalized.
+    ///
+    /// If there is no recording in progress, then this will return an error.
+    pub fn stop_recording(&mut self) -> Result<StopRecordingEvent, WavRecorderError> {
+        match &mut self.recording {
+            Some(recording) if !recording.stopping => {
+                recording.stopping = true;
+                Ok(StopRecordingEvent { recording: None })
+            }
+            _ => Err(WavRecorderError::NotRecording),
+        }
+    }
+
+    /// Wait for the writer thread to write the rest of the recording and
+    /// finalize the WAV header.
+    ///
+    /// This blocks until the processor has received the event returned by
+    /// [`WavRecorderState::stop_recording`] (or until the audio stream has
+    /// stopped). Use [`WavRecorderState::is_finished`] to check whether this
+    /// would block.
+    ///
+    /// Returns the number of frames in the file.
+    pub fn finish_recording(&mut self) -> Result<u64, WavRecorderError> {
+        match self.recording.take() {
+            Some(recording) if recording.stopping => recording
+                .writer
+                .join()
+                .map_err(|_| WavRecorderError::WriterPanicked)?
+                .map_err(WavRecorderError::from),
+            recording => {
+                self.recording = recording;
+                Err(WavRecorderError::NotRecording)
+            }
+        }
+    }
+
+    /// Returns `true` if a recording is in progress and has not been
+    /// stopped.
+    pub fn is_recording(&self) -> bool {
+        self.recording.as_ref().is_some_and(|r| !r.stopping)
+    }
+
+    /// Returns `true` if the recording has been stopped and the file has
+    /// been finalized, so [`WavRecorderState::finish_recording`] will not
+    /// block.
+    pub fn is_finished(&self) -> bool {
+        self.recording
+            .as_ref()
+            .is_some_and(|r| r.stopping && r.writer.is_finished())
+    }
+
+    /// Returns `true` if audio was dropped because the writer thread could
+    /// not keep up.
+    ///
+    /// If this happens, you may want to consider increasing
+    /// [`WavRecorderConfig::capacity_seconds`].
+    ///
+    /// (Calling this will also reset the flag indicating whether an
+    /// overflow occurred.)
+    pub fn overflow_occurred(&self) -> bool {
+        self.recording
+            .as_ref()
+            .is_some_and(|r| r.shared.overflow_occurred.swap(false, Ordering::Relaxed))
+    }
+}
+
+impl Drop for WavRecorderState {
+    fn drop(&mut self) {
+        // Let the writer thread finalize the file in the background.
+        if let Some(recording) = &self.recording {
+            recording.shared.finished.store(true, Ordering::Release);
+        }
+    }
+}
+
+/// Write samples to the file until the processor has finished recording
+/// and all of the samples it pushed have been written.
+fn write_samples(
+    mut writer: hound::WavWriter<BufWriter<File>>,
+    mut cons: ringbuf::HeapCons<f32>,
+    num_channels: usize,
+    shared: &SharedState,
+) -> Result<u64, hound::Error> {
+    let mut scratch = vec![0.0; 4096];
+
+    loop {
+        // Check this before draining so that every sample pushed before the
+        // processor finished is written.
+        let finished = shared.finished.load(Ordering::Acquire);
+
+        while !cons.is_empty() {
+            let n = cons.pop_slice(&mut scratch);
+            for &s in &scratch[..n] {
+                writer.write_sample(s)?;
+            }
+        }
+
+        if finished {
+            break;
+        }
+
+        std::thread::sleep(WRITER_POLL_INTERVAL);
+    }
+
+    let frames = writer.len() as u64 / num_channels as u64;
+    writer.finalize()?;
+
+    Ok(frames)
+}
+
+struct SharedState {
+    /// Set once the processor will push no more samples.
+    finished: AtomicBool,
+    overflow_occurred: AtomicBool,
+}
+
+impl SharedState {
+    fn new() -> Self {
+        Self {
+            finished: AtomicBool::new(false),
+            overflow_occurred: AtomicBool::new(false),
+        }
+    }
+}
+
+struct ActiveRecording {
+    prod: ringbuf::HeapProd<f32>,
+    shared: Arc<SharedState>,
+}
+
+impl ActiveRecording {
+    fn finish(&self) {
+        self.shared.finished.store(true, Ordering::Release);
+    }
+}
+
+impl AudioNode for WavRecorderNode {
+    type Configuration = WavRecorderConfig;
+
+    fn info(&self, config: &Self::Configuration) -> AudioNodeInfo {
+        AudioNodeInfo::new()
+            .debug_name("wav_recorder")
+            .channel_config(ChannelConfig {
+                num_inputs: config.channels.get(),
+                num_outputs: ChannelCount::ZERO,
+            })
+            .custom_state(WavRecorderState::new(config))
+    }
+
+    fn construct_processor(
+        &self,
+        _config: &Self::Configuration,
+        _cx: ConstructProcessorContext,
+    ) -> impl AudioNodeProcessor {
+        Processor { recording: None }
+    }
+}
+
+struct Processor {
+    recording: Option<ActiveRecording>,
+}
+
+impl Processor {
+    fn start(&mut self, event: &mut StartRecordingEvent) {
+        // Swap the values so that the old recording gets dropped on the
+        // main thread.
+        core::mem::swap(&mut self.recording, &mut event.recording);
+
+        if let Some(old) = &event.recording {
+            old.finish();
+        }
+    }
+
+    fn stop(&mut self, event: &mut StopRecordingEvent) {
+        if let Some(recording) = &self.recording {
+            recording.finish();
+        }
+
+        core::mem::swap(&mut self.recording, &mut event.recording);
+    }
+
+    /// Push a block of frames to the writer thread.
+    fn record(&mut self, inputs: &[&[f32]], frames: usize) {
+        let Some(recording) = &mut self.recording else {
+            return;
+        };
+
+        // Drop the whole block if it does not fit so that the channels stay
+        // aligned.
+        if recording.prod.vacant_len() < frames * inputs.len() {
+            recording
+                .shared
+                .overflow_occurred
+                .store(true, Ordering::Relaxed);
+            return;
+        }
+
+        for i in 0..frames {
+            for ch in inputs.iter() {
+                let _ = recording.prod.try_push(ch[i]);
+            }
+        }
+    }
+}
+
+impl AudioNodeProcessor for Processor {
+    fn process(
+        &mut self,
+        info: &ProcInfo,
+        buffers: ProcBuffers,
+        events: &mut ProcEvents,
+        _extra: &mut ProcExtra,
+    ) -> ProcessStatus {
+        for mut event in events.drain() {
+            if let Some(start_event) = event.downcast_mut::<StartRecordingEvent>() {
+                self.start(start_event);
+            } else if let Some(stop_event) = event.downcast_mut::<StopRecordingEvent>() {
+                self.stop(stop_event);
+            }
+        }
+
+        self.record(buffers.inputs, info.frames);
+
+        ProcessStatus::Bypass
+    }
+
+    fn stream_stopped(&mut self, _context: &mut ProcStreamCtx) {
+        // Finalize the file since no more audio will arrive.
+        if let Some(recording) = self.recording.take() {
+            recording.finish();
+        }
+    }
+}
+
+/// An event that starts a recording on a [`WavRecorderNode`], created with
+/// [`WavRecorderState::start_recording`].
+pub struct StartRecordingEvent {
+    recording: Option<ActiveRecording>,
+}
+
+impl From<StartRecordingEvent> for NodeEventType {
+    fn from(value: StartRecordingEvent) -> Self {
+        NodeEventType::custom(value)
+    }
+}
+
+/// An event that stops the recording on a [`WavRecorderNode`], created with
+/// [`WavRecorderState::stop_recording`].
+pub struct StopRecordingEvent {
+    recording: Option<ActiveRecording>,
+}
+
+impl From<StopRecordingEvent> for NodeEventType {
+    fn from(value: StopRecordingEvent) -> Self {
+        NodeEventType::custom(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recording_produces_matching_wav() {
+        let path = std::env::temp_dir().join(format!(
+            "firewheel_wav_recorder_test_{}.wav",
+            std::process::id()
+        ));
+        let sample_rate = NonZeroU32::new(48_000).unwrap();
+
+        let mut state = WavRecorderState::new(&WavRecorderConfig::default());
+        let mut processor = Processor { recording: None };
+
+        let mut start = state.start_recording(&path, sample_rate).unwrap();
+        processor.start(&mut start);
+        assert!(state.is_recording());
+
+        // A tenth of a second of a different tone in each channel, in
+        // blocks of 256 frames.
+        let frames = 4_800;
+        let left: Vec<f32> = (0..frames).map(|i| (i as f32 * 0.01).sin()).collect();
+        let right: Vec<f32> = (0..frames).map(|i| 0.5 * (i as f32 * 0.03).cos()).collect();
+        for (l, r) in left.chunks(256).zip(right.chunks(256)) {
+            processor.record(&[l, r], l.len());
+        }
+
+        let mut stop = state.stop_recording().unwrap();
+        processor.stop(&mut stop);
+
+        // Audio after the stop is not recorded.
+        processor.record(&[&left[..256], &right[..256]], 256);
+
+        assert_eq!(state.finish_recording().unwrap(), frames as u64);
+        assert!(!state.overflow_occurred());
+
+        let mut reader = hound::WavReader::open(&path).unwrap();
+        let spec = reader.spec();
+        assert_eq!(spec.channels, 2);
+        assert_eq!(spec.sample_rate, 48_000);
+        assert_eq!(spec.sample_format, hound::SampleFormat::Float);
+        assert_eq!(reader.duration(), frames as u32);
+
+        let samples: Vec<f32> = reader.samples::<f32>().map(|s| s.unwrap()).collect();
+        let expected: Vec<f32> = left
+            .iter()
+            .zip(right.iter())
+            .flat_map(|(&l, &r)| [l, r])
+            .collect();
+        assert_eq!(samples, expected);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -531,6 +531,51 @@ where
         self.num_active_workers = 0;
     }
 
+    /// Apply a parameter change to every active worker at once.
+    ///
+    /// This is useful for parameters that apply to all voices, such as a pitch
+    /// bend or a mod wheel. The closure is given the current parameters of each
+    /// active worker, and only the fields it changes are sent to that worker.
+    ///
+    /// * `time` - The instant that the change should take effect. If this is
+    /// `None`, then the parameters will take effect as soon as the node receives
+    /// the event.
+    /// * `cx` - The Firewheel context
+    /// * `f` - A closure which modifies the parameters of a single worker.
+    ///
+    /// Workers that are stopped by the new parameters are removed from the pool,
+    /// in the same way as in [`AudioNodePool::sync_worker_params`].
+    pub fn broadcast_params<B: AudioBackend>(
+        &mut self,
+        #[cfg(feature = "scheduled_events")] time: Option<EventInstant>,
+        cx: &mut FirewheelCtx<B>,
+        mut f: impl FnMut(&mut N::AudioNode),
+    ) {
+        for worker in self.workers.iter_mut() {
+            let Some(worker_id) = worker.assigned_worker_id else {
+                continue;
+            };
+
+            let mut new_params = worker.first_node_params.clone();
+            (f)(&mut new_params);
+
+            #[cfg(not(feature = "scheduled_events"))]
+            let mut event_queue = cx.event_queue(worker.first_node_id);
+            #[cfg(feature = "scheduled_events")]
+            let mut event_queue = cx.event_queue_scheduled(worker.first_node_id, time);
+
+            N::diff(&worker.first_node_params, &new_params, &mut event_queue);
+
+            if N::params_stopped(&new_params) {
+                self.worker_ids.remove(worker_id.0);
+                worker.assigned_worker_id = None;
+                self.num_active_workers -= 1;
+            }
+
+            worker.first_node_params = new_params;
+        }
+    }
+
     /// Get the first node parameters of the given worker.
     pub fn first_node(&self, worker_id: WorkerID) -> Option<&N::AudioNode> {
         self.worker_ids
@@ -644,3 +689,190 @@ pub enum PoolError {
     #[error("A node with ID {0:?} does not exist in this pool")]
     InvalidNodeID(NodeID),
 }
+
+#[cfg(all(test, feature = "sampler"))]
+mod tests {
+    use super::*;
+    use firewheel_core::collector::ArcGc;
+    use firewheel_graph::{
+        backend::dummy_backend::{DummyBackend, DummyStream},
+        FirewheelConfig,
+    };
+    use firewheel_nodes::{
+        sampler::{SamplerConfig, SamplerNode},
+        volume_pan::VolumePanNode,
+    };
+
+    #[test]
+    fn broadcast_pitch_bend_shifts_all_active_voices() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let graph_out = cx.graph_out_node_id();
+
+        let mut pool = SamplerPoolVolumePan::new(
+            4,
+            SamplerNode::default(),
+            None,
+            graph_out,
+            NonZeroChannelCount::STEREO,
+            &mut cx,
+        );
+
+        let mut stream = DummyStream::default();
+        stream.stream_info.sample_rate = core::num::NonZeroU32::new(44_100).unwrap();
+        stream.stream_info.num_stream_out_channels = 2;
+        cx.start_stream(stream.clone()).unwrap();
+
+        // Two voices playing a slow ramp at different speeds, one panned hard
+        // left and one hard right so that each can be heard on its own.
+        const RAMP_FRAMES: usize = 44_100 * 4;
+        let mut voices = Vec::new();
+        for (speed, pan) in [(1.0, -1.0), (1.5, 1.0)] {
+            let mut params = SamplerNode::default();
+            params.set_sample(ArcGc::new_unsized(|| {
+                std::sync::Arc::new(vec![(0..RAMP_FRAMES)
+                    .map(|i| i as f32 / RAMP_FRAMES as f32)
+                    .collect::<Vec<f32>>()]) as _
+            }));
+            params.speed = speed;
+            params.start_or_restart();
+
+            let result = pool
+                .new_worker(
+                    &params,
+                    #[cfg(feature = "scheduled_events")]
+                    None,
+                    false,
+                    &mut cx,
+                    |fx_state, cx| {
+                        fx_state.fx_chain.set_params(
+                            VolumePanNode {
+                                pan,
+                                ..Default::default()
+                            },
+                            #[cfg(feature = "scheduled_events")]
+                            None,
+                            &fx_state.node_ids,
+                            cx,
+                        );
+                    },
+                )
+                .unwrap();
+            voices.push((result.worker_id, speed));
+        }
+        assert_eq!(pool.num_active_workers(), 2);
+
+        // The per-frame rise of the ramp on each channel is proportional to
+        // the speed of the voice panned to it.
+        let slopes = |stream: &DummyStream| {
+            let out = stream.process_block(1024);
+            let frames = out.len() / 2;
+            [0, 1].map(|ch| (out[(frames - 1) * 2 + ch] - out[ch]) / (frames - 1) as f32)
+        };
+
+        // Let the pan and speed settle.
+        cx.update().unwrap();
+        stream.process_block(4096);
+        let before = slopes(&stream);
+
+        // Bend everything up by two semitones.
+        let bend = 2.0f64.powf(2.0 / 12.0);
+        pool.broadcast_params(
+            #[cfg(feature = "scheduled_events")]
+            None,
+            &mut cx,
+            |params| params.speed *= bend,
+        );
+        cx.update().unwrap();
+        stream.process_block(4096);
+        let after = slopes(&stream);
+
+        for ch in 0..2 {
+            let ratio = after[ch] / before[ch];
+            assert!(
+                (ratio - bend as f32).abs() < 1e-2,
+                "channel {ch}: slope ratio {ratio}"
+            );
+        }
+
+        for (worker_id, speed) in voices {
+            let params = pool.first_node(worker_id).unwrap();
+            assert!((params.speed - speed * bend).abs() < 1e-12);
+        }
+
+        // Idle workers are left untouched.
+        for worker in pool.workers.iter() {
+            if worker.assigned_worker_id.is_none() {
+                assert_eq!(worker.first_node_params.speed, 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn stealing_a_voice_fades_out_the_old_content() {
+        let mut cx = FirewheelCtx::<DummyBackend>::new(FirewheelConfig::default());
+        let graph_out = cx.graph_out_node_id();
+
+        // A single worker, so the second voice has to steal the first.
+        let mut pool = SamplerPoolVolumePan::new(
+            1,
+            SamplerNode::default(),
+            Some(SamplerConfig {
+                steal_fade_secs: Some(0.05),
+                ..Default::default()
+            }),
+            graph_out,
+            NonZeroChannelCount::STEREO,
+            &mut cx,
+        );
+
+        let mut stream = DummyStream::default();
+        stream.stream_info.sample_rate = core::num::NonZeroU32::new(44_100).unwrap();
+        stream.stream_info.num_stream_out_channels = 2;
+        cx.start_stream(stream.clone()).unwrap();
+
+        let voice = |value: f32| {
+            let mut params = SamplerNode::default();
+            params.set_sample(ArcGc::new_unsized(|| {
+                std::sync::Arc::new(vec![vec![value; 44_100]; 2]) as _
+            }));
+            params.start_or_restart();
+            params
+        };
+
+        // A sustaining voice settles at a constant level.
+        pool.new_worker(
+            &voice(1.0),
+            #[cfg(feature = "scheduled_events")]
+            None,
+            false,
+            &mut cx,
+            |_, _| {},
+        )
+        .unwrap();
+        cx.update().unwrap();
+        let out = stream.process_block(2048);
+        let level = out[out.len() - 2];
+        assert!(level > 0.5);
+
+        // Steal it with a silent voice.
+        pool.new_worker(
+            &voice(0.0),
+            #[cfg(feature = "scheduled_events")]
+            None,
+            true,
+            &mut cx,
+            |_, _| {},
+        )
+        .unwrap();
+        cx.update().unwrap();
+        let out = stream.process_block(4096);
+        let left: Vec<f32> = out.iter().step_by(2).copied().collect();
+
+        // The old content ramps down over the 50ms (2205 frame) steal fade,
+        // well past the default 10ms declick, instead of being cut off.
+        assert!(left[0] > level * 0.9);
+        assert!(left[1102] > level * 0.2 && left[1102] < level * 0.8);
+        assert!(left[..2205].windows(2).all(|w| w[1] <= w[0] + 1e-6));
+        assert!(left[2205..].iter().all(|&s| s.abs() < 1e-6));
+    }
+}
@@ -12,7 +12,7 @@ use crate::FxChain;
 /// A default [`FxChain`] for 3D game audio.
 ///
 /// This chain contains a single `SpatialBasic` node.
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct SpatialBasicChain {
     pub spatial_basic: firewheel_nodes::spatial_basic::SpatialBasicNode,
 }
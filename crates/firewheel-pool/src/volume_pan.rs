@@ -16,10 +16,10 @@ use crate::FxChain;
 /// A default [`FxChain`] for 2D game audio.
 ///
 /// This chain contains a single `VolumePan` node.
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq)]
 pub struct VolumePanChain {
     pub volume_pan: firewheel_nodes::volume_pan::VolumePanNode,
-    pub config: firewheel_nodes::volume_pan::VolumeNodeConfig,
+    pub config: firewheel_nodes::volume_pan::VolumePanNodeConfig,
 }
 
 impl VolumePanChain {
@@ -1,20 +1,86 @@
-use std::{num::NonZeroUsize, ops::Range};
+use std::{
+    io::Read,
+    num::{NonZeroU32, NonZeroUsize},
+    ops::Range,
+    path::Path,
+    sync::Arc,
+};
 
 use firewheel_core::{
     collector::ArcGc,
-    sample_resource::{SampleResource, SampleResourceInfo},
+    sample_resource::{
+        bake_loop_crossfade, SampleResource, SampleResourceInfo, StreamDecoder,
+        StreamingSampleResource,
+    },
+};
+use symphonium::{
+    error::LoadError,
+    symphonia::core::{
+        audio::{AudioBuffer, Signal},
+        codecs::Decoder,
+        formats::{FormatReader, SeekMode, SeekTo},
+        io::{MediaSource, MediaSourceStream},
+        probe::Hint,
+    },
 };
 
+/// An error while loading an audio file with one of the helper methods in
+/// this crate.
+#[derive(Debug, thiserror::Error)]
+pub enum LoadAudioFileError {
+    /// Symphonium failed to load the file.
+    #[error("{0}")]
+    Load(#[from] LoadError),
+    /// The file does not say what sample rate it was recorded at.
+    ///
+    /// Symphonium would otherwise assume a sample rate of `44100`, which can
+    /// silently play the file back at the wrong speed.
+    #[error("Could not load audio file: its sample rate is unknown")]
+    UnknownSampleRate,
+    /// The file does not say how many frames it contains, which is needed to
+    /// stream it.
+    #[error("Could not stream audio file: its length is unknown")]
+    UnknownLength,
+    /// Failed to read the audio source into memory.
+    #[error("Could not read audio source: {0}")]
+    Io(#[from] std::io::Error),
+}
+
 /// A wrapper around [`symphonium::DecodedAudio`] which implements the
 /// [`SampleResource`] trait.
 #[derive(Debug, Clone)]
 pub struct DecodedAudio(pub symphonium::DecodedAudio);
 
 impl DecodedAudio {
+    /// The sample rate of the decoded audio data.
+    ///
+    /// If the file was resampled while loading, then this is the sample rate
+    /// it was resampled to. Use [`probe_sample_rate`] to get the sample rate
+    /// of the file itself.
+    pub fn sample_rate(&self) -> u32 {
+        self.0.sample_rate()
+    }
+
     pub fn duration_seconds(&self) -> f64 {
         self.0.frames() as f64 / self.0.sample_rate() as f64
     }
 
+    /// Create a copy of this resource with an equal-power crossfade baked
+    /// into the end of the loop region, so a sampler can loop it with a plain
+    /// linear read.
+    ///
+    /// See [`bake_loop_crossfade`] for details.
+    pub fn bake_loop_crossfade(
+        &self,
+        loop_range: Range<u64>,
+        crossfade_frames: usize,
+    ) -> DecodedAudioF32 {
+        DecodedAudioF32(symphonium::DecodedAudioF32::new(
+            bake_loop_crossfade(self, loop_range, crossfade_frames),
+            self.0.sample_rate(),
+        ))
+    }
+
     pub fn into_dyn_resource(self) -> ArcGc<dyn SampleResource> {
         ArcGc::new_unsized(|| {
             bevy_platform::sync::Arc::new(self) as bevy_platform::sync::Arc<dyn SampleResource>
@@ -77,9 +143,30 @@ impl From<symphonium::DecodedAudio> for DecodedAudio {
 pub struct DecodedAudioF32(pub symphonium::DecodedAudioF32);
 
 impl DecodedAudioF32 {
+    /// The sample rate of the decoded audio data.
+    ///
+    /// If the file was resampled while loading, then this is the sample rate
+    /// it was resampled to. Use [`probe_sample_rate`] to get the sample rate
+    /// of the file itself.
+    pub fn sample_rate(&self) -> u32 {
+        self.0.sample_rate
+    }
+
     pub fn duration_seconds(&self, sample_rate: u32) -> f64 {
         self.0.frames() as f64 / sample_rate as f64
     }
+
+    /// Create a copy of this resource with an equal-power crossfade baked
+    /// into the end of the loop region, so a sampler can loop it with a plain
+    /// linear read.
+    ///
+    /// See [`bake_loop_crossfade`] for details.
+    pub fn bake_loop_crossfade(&self, loop_range: Range<u64>, crossfade_frames: usize) -> Self {
+        Self(symphonium::DecodedAudioF32::new(
+            bake_loop_crossfade(self, loop_range, crossfade_frames),
+            self.0.sample_rate,
+        ))
+    }
 }
 
 impl SampleResourceInfo for DecodedAudioF32 {
@@ -114,19 +201,92 @@ impl From<symphonium::DecodedAudioF32> for DecodedAudioF32 {
     }
 }
 
+/// Read the sample rate of an audio file from its metadata without decoding
+/// it.
+///
+/// This uses Symphonium's default format probe, so it recognizes the same
+/// formats as a loader created with [`symphonium::SymphoniumLoader::new`].
+///
+/// Returns [`LoadAudioFileError::UnknownSampleRate`] if the file does not
+/// specify a sample rate.
+pub fn probe_sample_rate<P: AsRef<Path>>(path: P) -> Result<NonZeroU32, LoadAudioFileError> {
+    let path = path.as_ref();
+    let file = std::fs::File::open(path).map_err(LoadError::FileNotFound)?;
+
+    probe_source_sample_rate(Box::new(file), path_hint(path))
+}
+
+fn probe_source_sample_rate(
+    source: Box<dyn MediaSource>,
+    hint: Option<Hint>,
+) -> Result<NonZeroU32, LoadAudioFileError> {
+    let probed = symphonium::symphonia::default::get_probe()
+        .format(
+            &hint.unwrap_or_default(),
+            MediaSourceStream::new(source, Default::default()),
+            &Default::default(),
+            &Default::default(),
+        )
+        .map_err(LoadError::UnkownFormat)?;
+
+    let track = probed
+        .format
+        .default_track()
+        .ok_or(LoadError::NoTrackFound)?;
+
+    track
+        .codec_params
+        .sample_rate
+        .and_then(NonZeroU32::new)
+        .ok_or(LoadAudioFileError::UnknownSampleRate)
+}
+
+fn path_hint(path: &Path) -> Option<Hint> {
+    let extension = path.extension()?.to_str()?;
+
+    let mut hint = Hint::new();
+    hint.with_extension(extension);
+    Some(hint)
+}
+
+/// Read a custom source into memory so it can be probed for its sample rate
+/// before it is loaded.
+///
+/// Returns the sample rate along with a copy of the source to load from.
+fn buffer_and_probe_source(
+    mut source: Box<dyn MediaSource>,
+    hint: &Option<Hint>,
+) -> Result<(NonZeroU32, Box<dyn MediaSource>), LoadAudioFileError> {
+    let mut bytes = Vec::new();
+    source.read_to_end(&mut bytes)?;
+    let bytes: Arc<[u8]> = bytes.into();
+
+    let sample_rate = probe_source_sample_rate(
+        Box::new(std::io::Cursor::new(Arc::clone(&bytes))),
+        hint.clone(),
+    )?;
+
+    Ok((sample_rate, Box::new(std::io::Cursor::new(bytes))))
+}
+
 /// A helper method to load an audio file from a path using Symphonium.
 ///
 /// * `loader` - The symphonium loader.
 /// * `path`` - The path to the audio file stored on disk.
 /// * `sample_rate` - The sample rate of the audio stream.
 /// * `resample_quality` - The quality of the resampler to use.
-pub fn load_audio_file<P: AsRef<std::path::Path>>(
+///
+/// Returns [`LoadAudioFileError::UnknownSampleRate`] if the file does not
+/// specify a sample rate.
+pub fn load_audio_file<P: AsRef<Path>>(
     loader: &mut symphonium::SymphoniumLoader,
     path: P,
     #[cfg(feature = "resample")] sample_rate: core::num::NonZeroU32,
     #[cfg(feature = "resample")] resample_quality: symphonium::ResampleQuality,
-) -> Result<DecodedAudio, symphonium::error::LoadError> {
-    loader
+) -> Result<DecodedAudio, LoadAudioFileError> {
+    probe_sample_rate(&path)?;
+
+    let decoded = loader
         .load(
             path,
             #[cfg(feature = "resample")]
@@ -135,7 +295,9 @@ pub fn load_audio_file<P: AsRef<std::path::Path>>(
             resample_quality,
             None,
         )
-        .map(|d| DecodedAudio(d))
+        .map(|d| DecodedAudio(d))?;
+
+    Ok(decoded)
 }
 
 /// A helper method to load an audio file from a custom source using Symphonium.
@@ -146,15 +308,19 @@ pub fn load_audio_file<P: AsRef<std::path::Path>>(
 /// * `sample_rate` - The sample rate of the audio stream.
 /// * `resample_quality` - The quality of the resampler to use.
 ///
-/// [`MediaSource`]: symphonium::symphonia::core::io::MediaSource
+/// The source is read into memory first so its sample rate can be checked.
+/// Returns [`LoadAudioFileError::UnknownSampleRate`] if the source does not
+/// specify a sample rate.
 pub fn load_audio_file_from_source(
     loader: &mut symphonium::SymphoniumLoader,
-    source: Box<dyn symphonium::symphonia::core::io::MediaSource>,
-    hint: Option<symphonium::symphonia::core::probe::Hint>,
+    source: Box<dyn MediaSource>,
+    hint: Option<Hint>,
     #[cfg(feature = "resample")] sample_rate: core::num::NonZeroU32,
     #[cfg(feature = "resample")] resample_quality: symphonium::ResampleQuality,
-) -> Result<DecodedAudio, symphonium::error::LoadError> {
-    loader
+) -> Result<DecodedAudio, LoadAudioFileError> {
+    let (_, source) = buffer_and_probe_source(source, &hint)?;
+
+    let decoded = loader
         .load_from_source(
             source,
             hint,
@@ -164,7 +330,9 @@ pub fn load_audio_file_from_source(
             resample_quality,
             None,
         )
-        .map(|d| DecodedAudio(d))
+        .map(|d| DecodedAudio(d))?;
+
+    Ok(decoded)
 }
 
 /// A helper method to load an audio file from a path using Symphonium. This
@@ -178,15 +346,19 @@ pub fn load_audio_file_from_source(
 /// greater than `1.0` will decrease the pitch & increase the length. If a `target_sample_rate`
 /// is given, then the final amount will automatically be adjusted to account for that.
 #[cfg(feature = "stretch")]
-pub fn load_audio_file_stretched<P: AsRef<std::path::Path>>(
+pub fn load_audio_file_stretched<P: AsRef<Path>>(
     loader: &mut symphonium::SymphoniumLoader,
     path: P,
     sample_rate: core::num::NonZeroU32,
     stretch: f64,
-) -> Result<DecodedAudio, symphonium::error::LoadError> {
-    loader
+) -> Result<DecodedAudio, LoadAudioFileError> {
+    probe_sample_rate(&path)?;
+
+    let decoded = loader
         .load_f32_stretched(path, stretch, Some(sample_rate.get()), None)
-        .map(|d| DecodedAudio(d.into()))
+        .map(|d| DecodedAudio(d.into()))?;
+
+    Ok(decoded)
 }
 
 /// A helper method to load an audio file from a custom source using Symphonium. This
@@ -204,14 +376,18 @@ pub fn load_audio_file_stretched<P: AsRef<std::path::Path>>(
 #[cfg(feature = "stretch")]
 pub fn load_audio_file_from_source_stretched(
     loader: &mut symphonium::SymphoniumLoader,
-    source: Box<dyn symphonium::symphonia::core::io::MediaSource>,
-    hint: Option<symphonium::symphonia::core::probe::Hint>,
+    source: Box<dyn MediaSource>,
+    hint: Option<Hint>,
     sample_rate: core::num::NonZeroU32,
     stretch: f64,
-) -> Result<DecodedAudio, symphonium::error::LoadError> {
-    loader
+) -> Result<DecodedAudio, LoadAudioFileError> {
+    let (_, source) = buffer_and_probe_source(source, &hint)?;
+
+    let decoded = loader
         .load_f32_from_source_stretched(source, hint, stretch, Some(sample_rate.get()), None)
-        .map(|d| DecodedAudio(d.into()))
+        .map(|d| DecodedAudio(d.into()))?;
+
+    Ok(decoded)
 }
 
 /// A helper method to convert a [`symphonium::DecodedAudio`] resource into
@@ -229,3 +405,290 @@ pub fn decoded_f32_to_resource(
 ) -> bevy_platform::sync::Arc<dyn SampleResource> {
     bevy_platform::sync::Arc::new(DecodedAudioF32(data))
 }
+
+/// A [`StreamDecoder`] which decodes the default track of an audio file with
+/// Symphonia, for use in a [`StreamingSampleResource`].
+///
+/// The audio is not resampled, so it plays at the sample rate of the file
+/// (see [`SymphoniumStreamDecoder::sample_rate`]).
+pub struct SymphoniumStreamDecoder {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    num_channels: NonZeroUsize,
+    len_frames: u64,
+    sample_rate: NonZeroU32,
+    /// The frame the next decoded sample should be at. Frames before this
+    /// in a packet are skipped after a seek.
+    next_frame: u64,
+    conversion_buf: Option<AudioBuffer<f32>>,
+}
+
+impl SymphoniumStreamDecoder {
+    /// Open the audio file at the given path for streaming.
+    ///
+    /// Returns [`LoadAudioFileError::UnknownSampleRate`] or
+    /// [`LoadAudioFileError::UnknownLength`] if the file does not specify its
+    /// sample rate or length.
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, LoadAudioFileError> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(LoadError::FileNotFound)?;
+
+        Self::from_source(Box::new(file), path_hint(path))
+    }
+
+    /// Open the audio in the given source for streaming.
+    ///
+    /// * `source` - The audio source which implements the [`MediaSource`] trait.
+    /// * `hint` -  An optional hint to help the format registry guess what format reader is appropriate.
+    pub fn from_source(
+        source: Box<dyn MediaSource>,
+        hint: Option<Hint>,
+    ) -> Result<Self, LoadAudioFileError> {
+        let probed = symphonium::symphonia::default::get_probe()
+            .format(
+                &hint.unwrap_or_default(),
+                MediaSourceStream::new(source, Default::default()),
+                &Default::default(),
+                &Default::default(),
+            )
+            .map_err(LoadError::UnkownFormat)?;
+
+        let track = probed
+            .format
+            .default_track()
+            .ok_or(LoadError::NoTrackFound)?;
+
+        let sample_rate = track
+            .codec_params
+            .sample_rate
+            .and_then(NonZeroU32::new)
+            .ok_or(LoadAudioFileError::UnknownSampleRate)?;
+        let num_channels = track
+            .codec_params
+            .channels
+            .and_then(|channels| NonZeroUsize::new(channels.count()))
+            .ok_or(LoadError::NoChannelsFound)?;
+        let len_frames = track
+            .codec_params
+            .n_frames
+            .ok_or(LoadAudioFileError::UnknownLength)?;
+
+        let decoder = symphonium::symphonia::default::get_codecs()
+            .make(&track.codec_params, &Default::default())
+            .map_err(LoadError::CouldNotCreateDecoder)?;
+
+        Ok(Self {
+            track_id: track.id,
+            format: probed.format,
+            decoder,
+            num_channels,
+            len_frames,
+            sample_rate,
+            next_frame: 0,
+            conversion_buf: None,
+        })
+    }
+
+    /// The sample rate of the audio file.
+    pub fn sample_rate(&self) -> NonZeroU32 {
+        self.sample_rate
+    }
+}
+
+impl StreamDecoder for SymphoniumStreamDecoder {
+    fn num_channels(&self) -> NonZeroUsize {
+        self.num_channels
+    }
+
+    fn len_frames(&self) -> u64 {
+        self.len_frames
+    }
+
+    fn seek(&mut self, frame: u64) -> bool {
+        let seeked = self.format.seek(
+            SeekMode::Accurate,
+            SeekTo::TimeStamp {
+                ts: frame,
+                track_id: self.track_id,
+            },
+        );
+        self.decoder.reset();
+
+        match seeked {
+            Ok(seeked) => {
+                self.next_frame = seeked.required_ts;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn decode(&mut self, out: &mut Vec<f32>) -> usize {
+        loop {
+            let packet = match self.format.next_packet() {
+                Ok(packet) => packet,
+                Err(_) => return 0,
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+
+            let decoded = match self.decoder.decode(&packet) {
+                Ok(decoded) => decoded,
+                Err(symphonium::symphonia::core::errors::Error::DecodeError(_)) => continue,
+                Err(_) => return 0,
+            };
+
+            let buf = match &mut self.conversion_buf {
+                Some(buf) if buf.capacity() >= decoded.capacity() => buf,
+                buf => buf.insert(AudioBuffer::new(decoded.capacity() as u64, *decoded.spec())),
+            };
+            decoded.convert(buf);
+
+            // Skip the part of the packet before the frame that was seeked to.
+            let frames = buf.frames();
+            let skip = (self.next_frame.saturating_sub(packet.ts()) as usize).min(frames);
+            if skip == frames {
+                continue;
+            }
+
+            let planes = buf.planes();
+            let planes = planes.planes();
+            for i in skip..frames {
+                out.extend(planes.iter().map(|plane| plane[i]));
+            }
+
+            self.next_frame = packet.ts() + frames as u64;
+
+            return frames - skip;
+        }
+    }
+}
+
+/// A helper method to stream an audio file from disk with Symphonia, so that
+/// only a small part of it is decoded into memory at a time.
+///
+/// See [`StreamingSampleResource`] for details. Use
+/// [`SymphoniumStreamDecoder::new`] and [`StreamingSampleResource::new`]
+/// directly to set the size of the decode-ahead buffer or to get the sample
+/// rate of the file.
+pub fn stream_audio_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<StreamingSampleResource, LoadAudioFileError> {
+    Ok(StreamingSampleResource::new(
+        SymphoniumStreamDecoder::new(path)?,
+        StreamingSampleResource::DEFAULT_BUFFER_FRAMES,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BEEP_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../assets/test_files/beep_up.wav"
+    );
+
+    /// A 2.45 second stereo file.
+    const BIRD_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../assets/test_files/bird-sound.wav"
+    );
+
+    #[test]
+    fn loading_exposes_true_sample_rate() {
+        assert_eq!(probe_sample_rate(BEEP_PATH).unwrap().get(), 44_100);
+
+        let mut loader = symphonium::SymphoniumLoader::new();
+
+        // Without resampling, the decoded data keeps the rate of the file.
+        let decoded = load_audio_file(
+            &mut loader,
+            BEEP_PATH,
+            #[cfg(feature = "resample")]
+            NonZeroU32::new(44_100).unwrap(),
+            #[cfg(feature = "resample")]
+            Default::default(),
+        )
+        .unwrap();
+        assert_eq!(decoded.sample_rate(), 44_100);
+        assert!((decoded.duration_seconds() - 11_116.0 / 44_100.0).abs() < 1e-9);
+
+        // The same goes for a file loaded from a custom source.
+        let source = std::fs::File::open(BEEP_PATH).unwrap();
+        let decoded = load_audio_file_from_source(
+            &mut loader,
+            Box::new(source),
+            path_hint(Path::new(BEEP_PATH)),
+            #[cfg(feature = "resample")]
+            NonZeroU32::new(44_100).unwrap(),
+            #[cfg(feature = "resample")]
+            Default::default(),
+        )
+        .unwrap();
+        assert_eq!(decoded.sample_rate(), 44_100);
+
+        assert!(matches!(
+            probe_sample_rate("does_not_exist.wav"),
+            Err(LoadAudioFileError::Load(LoadError::FileNotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn streaming_matches_decoded_at_seek_points() {
+        let mut loader = symphonium::SymphoniumLoader::new();
+        let decoded = load_audio_file(
+            &mut loader,
+            BIRD_PATH,
+            #[cfg(feature = "resample")]
+            NonZeroU32::new(44_100).unwrap(),
+            #[cfg(feature = "resample")]
+            Default::default(),
+        )
+        .unwrap();
+
+        let decoder = SymphoniumStreamDecoder::new(BIRD_PATH).unwrap();
+        assert_eq!(decoder.sample_rate().get(), 44_100);
+        // Use a buffer much shorter than the file.
+        let streaming = StreamingSampleResource::new(decoder, 8_192);
+
+        assert_eq!(streaming.len_frames(), decoded.len_frames());
+        assert_eq!(streaming.num_channels(), decoded.num_channels());
+
+        const FRAMES: usize = 1_000;
+        let len_frames = streaming.len_frames();
+
+        // Read forward, jump ahead, then jump back.
+        for start_frame in [0, 1_000, 50_000, 107_500, 20_000, 19_500, 80_123] {
+            let end_frame = (start_frame + FRAMES as u64).min(len_frames);
+
+            streaming.prefetch(start_frame);
+            let mut buffered = false;
+            for _ in 0..5_000 {
+                let range = streaming.buffered_frames();
+                if range.start <= start_frame && range.end >= end_frame {
+                    buffered = true;
+                    break;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            assert!(buffered, "frame {start_frame} was never buffered");
+
+            let mut expected = [[0.0; FRAMES]; 2];
+            let mut actual = [[f32::NAN; FRAMES]; 2];
+            let n = (end_frame - start_frame) as usize;
+            {
+                let [l, r] = &mut expected;
+                decoded.fill_buffers(&mut [&mut l[..], &mut r[..]], 0..n, start_frame);
+            }
+            {
+                let [l, r] = &mut actual;
+                streaming.fill_buffers(&mut [&mut l[..], &mut r[..]], 0..FRAMES, start_frame);
+            }
+
+            assert_eq!(expected, actual, "frames at {start_frame}");
+        }
+    }
+}
@@ -130,7 +130,7 @@ impl FxChain for MyCustomChain {
         assert_eq!(sampler_num_channels, NonZeroChannelCount::STEREO);
         assert_eq!(dst_num_channels, NonZeroChannelCount::STEREO);
 
-        let stereo_to_mono_node_id = cx.add_node(StereoToMonoNode, None);
+        let stereo_to_mono_node_id = cx.add_node(StereoToMonoNode::default(), None);
 
         let volume_params = VolumeNode::default();
         let volume_node_id = cx.add_node(
@@ -4,7 +4,7 @@ use firewheel::{
     error::UpdateError,
     node::NodeID,
     nodes::{
-        peak_meter::{PeakMeterNode, PeakMeterSmoother, PeakMeterState},
+        peak_meter::{PeakMeterConfig, PeakMeterNode, PeakMeterSmoother, PeakMeterState},
         sampler::{RepeatMode, SamplerNode, SamplerState},
     },
     FirewheelContext,
@@ -44,10 +44,10 @@ impl AudioSystem {
 
         let graph_out = cx.graph_out_node_id();
 
-        let peak_meter_node = PeakMeterNode::<2> { enabled: true };
+        let peak_meter_node = PeakMeterNode { enabled: true };
         let peak_meter_smoother = PeakMeterSmoother::<2>::new(Default::default());
 
-        let peak_meter_id = cx.add_node(peak_meter_node.clone(), None);
+        let peak_meter_id = cx.add_node(peak_meter_node.clone(), Some(PeakMeterConfig::default()));
         cx.connect(peak_meter_id, graph_out, &[(0, 0), (1, 1)], false)
             .unwrap();
 
@@ -189,7 +189,7 @@ impl AudioSystem {
     pub fn update_meters(&mut self, delta_seconds: f32) {
         self.peak_meter_smoother.update(
             self.cx
-                .node_state::<PeakMeterState<2>>(self.peak_meter_id)
+                .node_state::<PeakMeterState>(self.peak_meter_id)
                 .unwrap()
                 .peak_gain_db(DEFAULT_DB_EPSILON),
             delta_seconds,
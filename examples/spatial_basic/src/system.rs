@@ -47,7 +47,7 @@ impl AudioSystem {
         let sampler_node_id = cx.add_node(sampler_node.clone(), None);
 
         let spatial_basic_node = SpatialBasicNode::default();
-        let spatial_basic_node_id = cx.add_node(spatial_basic_node, None);
+        let spatial_basic_node_id = cx.add_node(spatial_basic_node.clone(), None);
 
         cx.connect(
             sampler_node_id,
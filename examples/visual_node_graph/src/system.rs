@@ -17,7 +17,7 @@ use firewheel::{
         svf::SvfNode,
         volume::{VolumeNode, VolumeNodeConfig},
         volume_pan::VolumePanNode,
-        StereoToMonoNode,
+        StereoSplitNode, StereoToMonoNode,
     },
     sample_resource::SampleResource,
     ContextQueue, CpalBackend, FirewheelContext,
@@ -39,6 +39,7 @@ pub enum NodeType {
     WhiteNoiseGen,
     PinkNoiseGen,
     StereoToMono,
+    StereoSplit,
     VolumeMono,
     VolumeStereo,
     VolumePan,
@@ -126,7 +127,8 @@ impl AudioSystem {
     }
 
     pub fn remove_node(&mut self, node_id: NodeID) {
-        if let Err(_) = self.cx.remove_node(node_id) {
+        // Fade the node out first so removing it doesn't click.
+        if let Err(_) = self.cx.remove_node_faded(node_id, 10.0) {
             tracing::error!("Node already removed!");
         }
     }
@@ -136,7 +138,8 @@ impl AudioSystem {
             NodeType::BeepTest => self.cx.add_node(BeepTestNode::default(), None),
             NodeType::WhiteNoiseGen => self.cx.add_node(WhiteNoiseGenNode::default(), None),
             NodeType::PinkNoiseGen => self.cx.add_node(PinkNoiseGenNode::default(), None),
-            NodeType::StereoToMono => self.cx.add_node(StereoToMonoNode, None),
+            NodeType::StereoToMono => self.cx.add_node(StereoToMonoNode::default(), None),
+            NodeType::StereoSplit => self.cx.add_node(StereoSplitNode, None),
             NodeType::VolumeMono => self.cx.add_node(
                 VolumeNode::default(),
                 Some(VolumeNodeConfig {
@@ -194,6 +197,7 @@ impl AudioSystem {
                 params: Default::default(),
             },
             NodeType::StereoToMono => GuiAudioNode::StereoToMono { id },
+            NodeType::StereoSplit => GuiAudioNode::StereoSplit { id },
             NodeType::VolumeMono => GuiAudioNode::VolumeMono {
                 id,
                 params: Default::default(),
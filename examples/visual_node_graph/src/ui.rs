@@ -50,6 +50,9 @@ pub enum GuiAudioNode {
     StereoToMono {
         id: firewheel::node::NodeID,
     },
+    StereoSplit {
+        id: firewheel::node::NodeID,
+    },
     VolumeMono {
         id: firewheel::node::NodeID,
         params: Memo<VolumeNode>,
@@ -113,6 +116,7 @@ impl GuiAudioNode {
             &Self::WhiteNoiseGen { id, .. } => id,
             &Self::PinkNoiseGen { id, .. } => id,
             &Self::StereoToMono { id } => id,
+            &Self::StereoSplit { id } => id,
             &Self::VolumeMono { id, .. } => id,
             &Self::VolumeStereo { id, .. } => id,
             &Self::VolumePan { id, .. } => id,
@@ -137,6 +141,7 @@ impl GuiAudioNode {
             &Self::WhiteNoiseGen { .. } => "White Noise Generator",
             &Self::PinkNoiseGen { .. } => "Pink Noise Generator",
             &Self::StereoToMono { .. } => "Stereo To Mono",
+            &Self::StereoSplit { .. } => "Stereo Split",
             &Self::VolumeMono { .. } => "Volume (Mono)",
             &Self::VolumeStereo { .. } => "Volume (Stereo)",
             &Self::VolumePan { .. } => "Volume & Pan",
@@ -162,6 +167,7 @@ impl GuiAudioNode {
             &Self::WhiteNoiseGen { .. } => 0,
             &Self::PinkNoiseGen { .. } => 0,
             &Self::StereoToMono { .. } => 2,
+            &Self::StereoSplit { .. } => 2,
             &Self::VolumeMono { .. } => 1,
             &Self::VolumeStereo { .. } => 2,
             &Self::VolumePan { .. } => 2,
@@ -186,6 +192,7 @@ impl GuiAudioNode {
             &Self::WhiteNoiseGen { .. } => 1,
             &Self::PinkNoiseGen { .. } => 1,
             &Self::StereoToMono { .. } => 1,
+            &Self::StereoSplit { .. } => 2,
             &Self::VolumeMono { .. } => 1,
             &Self::VolumeStereo { .. } => 2,
             &Self::VolumePan { .. } => 2,
@@ -337,6 +344,11 @@ impl<'a> SnarlViewer<GuiAudioNode> for DemoViewer<'a> {
             snarl.insert_node(pos, node);
             ui.close_kind(UiKind::Menu);
         }
+        if ui.button("Stereo Split").clicked() {
+            let node = self.audio_system.add_node(NodeType::StereoSplit);
+            snarl.insert_node(pos, node);
+            ui.close_kind(UiKind::Menu);
+        }
         ui.menu_button("Volume", |ui| {
             if ui.button("Volume (mono)").clicked() {
                 let node = self.audio_system.add_node(NodeType::VolumeMono);
@@ -464,7 +476,8 @@ impl<'a> SnarlViewer<GuiAudioNode> for DemoViewer<'a> {
         match node {
             GuiAudioNode::SystemIn { .. }
             | GuiAudioNode::SystemOut { .. }
-            | GuiAudioNode::StereoToMono { .. } => false,
+            | GuiAudioNode::StereoToMono { .. }
+            | GuiAudioNode::StereoSplit { .. } => false,
             _ => true,
         }
     }
@@ -686,12 +699,26 @@ impl<'a> SnarlViewer<GuiAudioNode> for DemoViewer<'a> {
                         .changed()
                     {
                         params.volume = Volume::Linear(linear_volume);
-                        params.update_memo(&mut self.audio_system.event_queue(*id));
                     }
 
                     let mut mix = params.mix.get();
-                    ui.add(egui::Slider::new(&mut mix, 0.0..=1.0).text("mix"));
-                    params.mix = Mix::new(mix);
+                    if ui
+                        .add(egui::Slider::new(&mut mix, 0.0..=1.0).text("mix"))
+                        .changed()
+                    {
+                        params.mix = Mix::new(mix);
+                    }
+
+                    let mut mix_smooth_ms = params.mix_smooth_seconds * 1000.0;
+                    if ui
+                        .add(
+                            egui::Slider::new(&mut mix_smooth_ms, 1.0..=500.0)
+                                .text("mix smoothing (ms)"),
+                        )
+                        .changed()
+                    {
+                        params.mix_smooth_seconds = mix_smooth_ms / 1000.0;
+                    }
 
                     fade_curve_ui(ui, &mut params.fade_curve);
 
@@ -809,6 +836,7 @@ fn fade_curve_ui(ui: &mut Ui, curve: &mut FadeCurve) {
             FadeCurve::EqualPower6dB => "Equal Power 6dB",
             FadeCurve::SquareRoot => "Square Root",
             FadeCurve::Linear => "Linear",
+            FadeCurve::Custom(_) => "Custom",
         })
         .show_ui(ui, |ui| {
             ui.selectable_value(curve, FadeCurve::EqualPower3dB, "Equal Power 3dB");
@@ -860,8 +888,8 @@ fn convolution_ui<const CHANNELS: usize>(
                         ui.selectable_value(&mut temp_current_ir, Some(sample_index), *name)
                             .clicked()
                             .then_some(|| {
-                                let ir = ImpulseResponse::new(sample.clone());
-                                NodeEventType::custom(Some(ir))
+                                let ir = ImpulseResponse::new(sample.clone()).ok();
+                                NodeEventType::custom(ir)
                             })
                     })
                     .next();